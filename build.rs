@@ -30,6 +30,13 @@ fn get_git_sha() -> Option<String> {
 fn main() {
     let sha = format!("{:?}", get_git_sha());
 
+    // Expose the target triple crater is compiled for, used to check per-crate platform
+    // constraints
+    println!(
+        "cargo:rustc-env=CRATER_HOST_TARGET={}",
+        std::env::var("TARGET").unwrap()
+    );
+
     let output = std::env::var("OUT_DIR").unwrap();
     ::std::fs::write(format!("{}/sha", output), sha.as_bytes()).unwrap();
 }
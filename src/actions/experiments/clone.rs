@@ -0,0 +1,345 @@
+use crate::actions::experiments::{
+    parse_failure_threshold, parse_sandbox_image, validate_deny_lints, validate_namespace,
+    validate_toolchains, ExperimentError,
+};
+use crate::actions::{Action, ActionsCtx};
+use crate::db::QueryUtils;
+use crate::experiments::{
+    canary, Assignee, CapLints, CrateQueue, CrateSelect, Experiment, FailureThreshold, GitHubIssue,
+    Mode, Status, TestRunner,
+};
+use crate::prelude::*;
+use crate::toolchain::Toolchain;
+use chrono::Utc;
+
+/// Create a new experiment based on the definition of an existing one. Every field left as `None`
+/// is copied from the original experiment, including the exact list of crates.
+pub struct CloneExperiment {
+    pub name: String,
+    pub new_name: String,
+    pub toolchains: [Option<Toolchain>; 2],
+    pub crates: Option<CrateSelect>,
+    pub mode: Option<Mode>,
+    pub cap_lints: Option<CapLints>,
+    pub priority: Option<i32>,
+    pub github_issue: Option<GitHubIssue>,
+    pub ignore_blacklist: Option<bool>,
+    pub assign: Option<Assignee>,
+    pub requirement: Option<String>,
+    pub cargo_update: Option<bool>,
+    pub failure_threshold: Option<Option<FailureThreshold>>,
+    pub reuse_baseline: Option<bool>,
+    pub canary: Option<bool>,
+    pub test_runner: Option<TestRunner>,
+    /// `Some(None)` builds the crates in the sandbox image of the workspace.
+    pub sandbox_image: Option<Option<String>>,
+}
+
+impl CloneExperiment {
+    #[cfg(test)]
+    pub fn dummy(name: &str, new_name: &str) -> Self {
+        CloneExperiment {
+            name: name.to_string(),
+            new_name: new_name.to_string(),
+            toolchains: [None, None],
+            crates: None,
+            mode: None,
+            cap_lints: None,
+            priority: None,
+            github_issue: None,
+            ignore_blacklist: None,
+            assign: None,
+            requirement: None,
+            cargo_update: None,
+            failure_threshold: None,
+            reuse_baseline: None,
+            canary: None,
+            test_runner: None,
+            sandbox_image: None,
+        }
+    }
+
+    /// Apply an override in the `key=value` format, using the same keys as the bot.
+    pub fn set_override(&mut self, input: &str) -> Fallible<()> {
+        let mut segments = input.splitn(2, '=');
+        let key = segments.next().unwrap_or("");
+        let value = match segments.next() {
+            Some(value) => value,
+            None => bail!("invalid override: {}", input),
+        };
+
+        match key {
+            "start" => self.toolchains[0] = Some(value.parse()?),
+            "end" => self.toolchains[1] = Some(value.parse()?),
+            "crates" => self.crates = Some(value.parse()?),
+            "mode" => self.mode = Some(value.parse()?),
+            "cap-lints" => self.cap_lints = Some(value.parse()?),
+            "p" | "priority" => self.priority = Some(value.parse()?),
+            "ignore-blacklist" => self.ignore_blacklist = Some(value.parse()?),
+            "assign" => self.assign = Some(value.parse()?),
+            "requirement" => self.requirement = Some(value.to_string()),
+            "cargo-update" => self.cargo_update = Some(value.parse()?),
+            "failure-threshold" => self.failure_threshold = Some(parse_failure_threshold(value)?),
+            "reuse-baseline" => self.reuse_baseline = Some(value.parse()?),
+            "canary" => self.canary = Some(value.parse()?),
+            "test-runner" => self.test_runner = Some(value.parse()?),
+            "sandbox-image" => self.sandbox_image = Some(parse_sandbox_image(value)),
+            _ => bail!("unknown override key: {}", key),
+        }
+
+        Ok(())
+    }
+}
+
+impl Action for CloneExperiment {
+    fn apply(self, ctx: &ActionsCtx) -> Fallible<()> {
+        let ex = match Experiment::get(&ctx.db, &self.name)? {
+            Some(ex) => ex,
+            None => return Err(ExperimentError::NotFound(self.name).into()),
+        };
+
+        // Ensure no duplicate experiments are created
+        if Experiment::exists(&ctx.db, &self.new_name)? {
+            return Err(ExperimentError::AlreadyExists(self.new_name).into());
+        }
+
+        let [start, end] = self.toolchains;
+        let start = start.unwrap_or_else(|| ex.toolchains[0].clone());
+        let toolchains = if ex.survey {
+            // The only toolchain of surveys is stored in both slots
+            if end.is_some() {
+                return Err(ExperimentError::SurveyToolchains.into());
+            }
+            [start.clone(), start]
+        } else {
+            [start, end.unwrap_or_else(|| ex.toolchains[1].clone())]
+        };
+        let mode = self.mode.unwrap_or(ex.mode);
+        validate_toolchains(&toolchains, ex.survey, mode)?;
+
+        let crates = if let Some(crates) = &self.crates {
+            crate::crates::lists::get_crates(crates, &ctx.db, &ctx.config)?
+        } else {
+            ex.get_crates(&ctx.db)?
+        };
+
+        let new_name = self.new_name;
+        let cap_lints = self.cap_lints.unwrap_or(ex.cap_lints);
+        let priority = self.priority.unwrap_or(ex.priority);
+        let github_issue = self.github_issue;
+        let ignore_blacklist = self.ignore_blacklist.unwrap_or(ex.ignore_blacklist);
+        // The assignee of the original experiment is not copied, as it's replaced with the agent
+        // running it once the experiment starts.
+        let assign = self.assign;
+        let requirement = self.requirement.or(ex.requirement);
+        let cargo_config = ex.cargo_config;
+        let expected_regressions = serde_json::to_string(&ex.expected_regressions)?;
+        let env = serde_json::to_string(&ex.env)?;
+        validate_deny_lints(&ex.deny_lints, cap_lints)?;
+        let deny_lints = serde_json::to_string(&ex.deny_lints)?;
+        let cargo_update = self.cargo_update.unwrap_or(ex.cargo_update);
+        let failure_threshold = self
+            .failure_threshold
+            .unwrap_or(ex.failure_threshold)
+            .map(|t| t.to_string());
+        let reuse_baseline = self.reuse_baseline.unwrap_or(ex.reuse_baseline);
+        let canary = self.canary.unwrap_or(ex.canary);
+        let test_runner = self.test_runner.unwrap_or(ex.test_runner);
+        let sandbox_image = self.sandbox_image.unwrap_or(ex.sandbox_image);
+        // Clones stay in the namespace of the original experiment, counting against its quota
+        validate_namespace(ctx, ex.namespace.as_deref())?;
+        let namespace = ex.namespace;
+        let survey = ex.survey;
+
+        ctx.db.transaction(|transaction| {
+            transaction.execute(
+                "INSERT INTO experiments \
+                 (name, mode, cap_lints, toolchain_start, toolchain_end, priority, created_at, \
+                 status, github_issue, github_issue_url, github_issue_number, ignore_blacklist, \
+                 assigned_to, requirement, cargo_config, expected_regressions, env, deny_lints, \
+                 cargo_update, failure_threshold, reuse_baseline, canary, test_runner, \
+                 namespace, survey, sandbox_image) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, \
+                 ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26);",
+                &[
+                    &new_name,
+                    &mode.to_str(),
+                    &cap_lints.to_str(),
+                    &toolchains[0].to_string(),
+                    &toolchains[1].to_string(),
+                    &priority,
+                    &Utc::now(),
+                    &Status::Queued.to_str(),
+                    &github_issue.as_ref().map(|i| i.api_url.as_str()),
+                    &github_issue.as_ref().map(|i| i.html_url.as_str()),
+                    &github_issue.as_ref().map(|i| i.number),
+                    &ignore_blacklist,
+                    &assign.as_ref().map(|a| a.to_string()),
+                    &requirement,
+                    &cargo_config,
+                    &expected_regressions,
+                    &env,
+                    &deny_lints,
+                    &cargo_update,
+                    &failure_threshold,
+                    &reuse_baseline,
+                    &canary,
+                    &test_runner.to_str(),
+                    &namespace,
+                    &survey,
+                    &sandbox_image,
+                ],
+            )?;
+
+            let queue = CrateQueue::new(&new_name);
+            for krate in &crates {
+                let skipped = !ignore_blacklist && ctx.config.should_skip(krate);
+                queue.enqueue(transaction, krate, skipped)?;
+            }
+            if canary {
+                queue.hold_except(transaction, &canary::select(&crates))?;
+            }
+
+            Ok(())
+        })?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CloneExperiment;
+    use crate::actions::{Action, ActionsCtx, CreateExperiment, ExperimentError};
+    use crate::config::Config;
+    use crate::db::Database;
+    use crate::experiments::{
+        CapLints, CrateSelect, Experiment, FailureThreshold, Mode, Status, TestRunner,
+    };
+    use crate::toolchain::{MAIN_TOOLCHAIN, TEST_TOOLCHAIN};
+
+    #[test]
+    fn test_clone() {
+        let db = Database::temp().unwrap();
+        let config = Config::default();
+        let ctx = ActionsCtx::new(&db, &config);
+
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+
+        CreateExperiment {
+            cap_lints: CapLints::Warn,
+            priority: 5,
+            requirement: Some("linux".to_string()),
+            cargo_config: Some("[net]\nretry = 5\n".to_string()),
+            expected_regressions: vec!["lint `foo`".to_string()],
+            env: vec![("RUSTC_BOOTSTRAP".to_string(), "1".to_string())]
+                .into_iter()
+                .collect(),
+            deny_lints: vec!["clippy::needless_return".to_string()],
+            cargo_update: true,
+            failure_threshold: Some(FailureThreshold::Percent(10)),
+            reuse_baseline: true,
+            canary: true,
+            test_runner: TestRunner::Nextest,
+            sandbox_image: Some("crates-build-env-foo".to_string()),
+            ..CreateExperiment::dummy("foo")
+        }
+        .apply(&ctx)
+        .unwrap();
+
+        let mut clone = CloneExperiment::dummy("foo", "bar");
+        clone.set_override("mode=check-only").unwrap();
+        clone.set_override("p=10").unwrap();
+        clone.apply(&ctx).unwrap();
+
+        let original = Experiment::get(&db, "foo").unwrap().unwrap();
+        let ex = Experiment::get(&db, "bar").unwrap().unwrap();
+        assert_eq!(
+            ex.toolchains,
+            [MAIN_TOOLCHAIN.clone(), TEST_TOOLCHAIN.clone()]
+        );
+        assert_eq!(ex.mode, Mode::CheckOnly);
+        assert_eq!(ex.cap_lints, CapLints::Warn);
+        assert_eq!(ex.priority, 10);
+        assert_eq!(ex.status, Status::Queued);
+        assert_eq!(ex.requirement, Some("linux".to_string()));
+        assert_eq!(ex.cargo_config, original.cargo_config);
+        assert_eq!(ex.expected_regressions, original.expected_regressions);
+        assert_eq!(ex.env, original.env);
+        assert_eq!(ex.deny_lints, original.deny_lints);
+        assert_eq!(ex.cargo_update, original.cargo_update);
+        assert_eq!(ex.failure_threshold, original.failure_threshold);
+        assert_eq!(ex.reuse_baseline, original.reuse_baseline);
+        assert_eq!(ex.canary, original.canary);
+        assert_eq!(ex.test_runner, original.test_runner);
+        assert_eq!(ex.sandbox_image, original.sandbox_image);
+        assert_eq!(ex.namespace, original.namespace);
+        assert!(ex.github_issue.is_none());
+        assert_eq!(
+            ex.get_crates(&db).unwrap(),
+            original.get_crates(&db).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_clone_with_crates() {
+        let db = Database::temp().unwrap();
+        let config = Config::default();
+        let ctx = ActionsCtx::new(&db, &config);
+
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+
+        CreateExperiment::dummy("foo").apply(&ctx).unwrap();
+
+        let mut clone = CloneExperiment::dummy("foo", "bar");
+        clone.set_override("crates=dummy").unwrap();
+        clone.apply(&ctx).unwrap();
+
+        let ex = Experiment::get(&db, "bar").unwrap().unwrap();
+        assert_eq!(
+            ex.get_crates(&db).unwrap(),
+            crate::crates::lists::get_crates(&CrateSelect::Dummy, &db, &config).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_clone_errors() {
+        let db = Database::temp().unwrap();
+        let config = Config::default();
+        let ctx = ActionsCtx::new(&db, &config);
+
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+
+        let err = CloneExperiment::dummy("foo", "bar")
+            .apply(&ctx)
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref(),
+            Some(&ExperimentError::NotFound("foo".into()))
+        );
+
+        CreateExperiment::dummy("foo").apply(&ctx).unwrap();
+        CreateExperiment::dummy("bar").apply(&ctx).unwrap();
+        let err = CloneExperiment::dummy("foo", "bar")
+            .apply(&ctx)
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref(),
+            Some(&ExperimentError::AlreadyExists("bar".into()))
+        );
+
+        let mut clone = CloneExperiment::dummy("foo", "baz");
+        clone
+            .set_override(&format!("start={}", TEST_TOOLCHAIN.to_string()))
+            .unwrap();
+        let err = clone.apply(&ctx).unwrap_err();
+        assert_eq!(
+            err.downcast_ref(),
+            Some(&ExperimentError::DuplicateToolchains)
+        );
+
+        let mut clone = CloneExperiment::dummy("foo", "baz");
+        assert!(clone.set_override("color=red").is_err());
+        assert!(clone.set_override("mode").is_err());
+    }
+}
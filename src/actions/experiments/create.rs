@@ -1,9 +1,17 @@
-use crate::actions::{experiments::ExperimentError, Action, ActionsCtx};
+use crate::actions::experiments::{
+    validate_cargo_config, validate_deny_lints, validate_env, validate_expected_regressions,
+    validate_namespace, validate_parent, validate_toolchains, ExperimentError,
+};
+use crate::actions::{Action, ActionsCtx};
 use crate::db::QueryUtils;
-use crate::experiments::{Assignee, CapLints, CrateSelect, Experiment, GitHubIssue, Mode, Status};
+use crate::experiments::{
+    canary, incremental, Assignee, CapLints, CrateQueue, CrateSelect, Experiment, FailureThreshold,
+    GitHubIssue, Mode, Status, TestRunner,
+};
 use crate::prelude::*;
 use crate::toolchain::Toolchain;
 use chrono::Utc;
+use std::collections::{BTreeMap, HashMap};
 
 pub struct CreateExperiment {
     pub name: String,
@@ -16,6 +24,20 @@ pub struct CreateExperiment {
     pub ignore_blacklist: bool,
     pub assign: Option<Assignee>,
     pub requirement: Option<String>,
+    pub cargo_config: Option<String>,
+    pub expected_regressions: Vec<String>,
+    pub env: BTreeMap<String, String>,
+    pub deny_lints: Vec<String>,
+    pub cargo_update: bool,
+    pub failure_threshold: Option<FailureThreshold>,
+    pub reuse_baseline: bool,
+    pub canary: bool,
+    pub test_runner: TestRunner,
+    pub namespace: Option<String>,
+    pub survey: bool,
+    /// Experiment whose results are inherited by the crates that didn't change since it ran.
+    pub parent: Option<String>,
+    pub sandbox_image: Option<String>,
 }
 
 impl CreateExperiment {
@@ -34,6 +56,19 @@ impl CreateExperiment {
             ignore_blacklist: false,
             assign: None,
             requirement: None,
+            cargo_config: None,
+            expected_regressions: Vec::new(),
+            env: BTreeMap::new(),
+            deny_lints: Vec::new(),
+            cargo_update: false,
+            failure_threshold: None,
+            reuse_baseline: false,
+            canary: false,
+            test_runner: TestRunner::Cargo,
+            namespace: None,
+            survey: false,
+            parent: None,
+            sandbox_image: None,
         }
     }
 }
@@ -46,19 +81,43 @@ impl Action for CreateExperiment {
         }
 
         // Ensure no experiment with duplicate toolchains is created
-        if self.toolchains[0] == self.toolchains[1] {
-            return Err(ExperimentError::DuplicateToolchains.into());
+        validate_toolchains(&self.toolchains, self.survey, self.mode)?;
+
+        if let Some(cargo_config) = &self.cargo_config {
+            validate_cargo_config(cargo_config)?;
         }
+        validate_expected_regressions(&self.expected_regressions)?;
+        validate_env(&self.env)?;
+        validate_deny_lints(&self.deny_lints, self.cap_lints)?;
+        validate_namespace(ctx, self.namespace.as_deref())?;
+        let parent = match &self.parent {
+            Some(parent) => Some(validate_parent(
+                ctx,
+                parent,
+                self.mode,
+                self.cap_lints,
+                self.survey,
+                self.sandbox_image.as_deref(),
+            )?),
+            None => None,
+        };
 
         let crates = crate::crates::lists::get_crates(&self.crates, &ctx.db, &ctx.config)?;
+        let unchanged = match &parent {
+            Some(parent) => incremental::unchanged(&ctx.db, parent, &crates)?,
+            None => HashMap::new(),
+        };
 
         ctx.db.transaction(|transaction| {
             transaction.execute(
                 "INSERT INTO experiments \
                  (name, mode, cap_lints, toolchain_start, toolchain_end, priority, created_at, \
                  status, github_issue, github_issue_url, github_issue_number, ignore_blacklist, \
-                 assigned_to, requirement) \
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14);",
+                 assigned_to, requirement, cargo_config, expected_regressions, env, deny_lints, \
+                 cargo_update, failure_threshold, reuse_baseline, canary, test_runner, \
+                 namespace, survey, parent, sandbox_image) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, \
+                 ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27);",
                 &[
                     &self.name,
                     &self.mode.to_str(),
@@ -74,15 +133,45 @@ impl Action for CreateExperiment {
                     &self.ignore_blacklist,
                     &self.assign.map(|a| a.to_string()),
                     &self.requirement,
+                    &self.cargo_config,
+                    &serde_json::to_string(&self.expected_regressions)?,
+                    &serde_json::to_string(&self.env)?,
+                    &serde_json::to_string(&self.deny_lints)?,
+                    &self.cargo_update,
+                    &self.failure_threshold.map(|t| t.to_string()),
+                    &self.reuse_baseline,
+                    &self.canary,
+                    &self.test_runner.to_str(),
+                    &self.namespace,
+                    &self.survey,
+                    &self.parent,
+                    &self.sandbox_image,
                 ],
             )?;
 
+            let queue = CrateQueue::new(&self.name);
+            let mut changed = Vec::new();
             for krate in &crates {
                 let skipped = !self.ignore_blacklist && ctx.config.should_skip(krate);
-                transaction.execute(
-                    "INSERT INTO experiment_crates (experiment, crate, skipped, status) VALUES (?1, ?2, ?3, ?4);",
-                    &[&self.name, &krate.id(), &skipped, &Status::Queued.to_string()],
-                )?;
+                match (unchanged.get(krate), &parent) {
+                    (Some(parent_crate), Some(parent)) if !skipped => {
+                        queue.enqueue_completed(transaction, parent_crate)?;
+                        incremental::inherit(
+                            transaction,
+                            &self.name,
+                            &self.toolchains,
+                            parent,
+                            parent_crate,
+                        )?;
+                    }
+                    _ => {
+                        queue.enqueue(transaction, krate, skipped)?;
+                        changed.push(krate.clone());
+                    }
+                }
+            }
+            if self.canary {
+                queue.hold_except(transaction, &canary::select(&changed))?;
             }
 
             Ok(())
@@ -96,13 +185,15 @@ impl Action for CreateExperiment {
 mod tests {
     use super::CreateExperiment;
     use crate::actions::{Action, ActionsCtx, ExperimentError};
-    use crate::config::{Config, CrateConfig};
+    use crate::config::{Config, CrateConfig, NamespaceConfig, NetworkMode};
     use crate::crates::Crate;
     use crate::db::{Database, QueryUtils};
     use crate::experiments::{
-        Assignee, CapLints, CrateSelect, Experiment, GitHubIssue, Mode, Status,
+        Assignee, CapLints, CrateSelect, Experiment, FailureThreshold, GitHubIssue, Mode, Status,
+        TestRunner,
     };
-    use crate::toolchain::{MAIN_TOOLCHAIN, TEST_TOOLCHAIN};
+    use crate::toolchain::{Toolchain, MAIN_TOOLCHAIN, TEST_TOOLCHAIN};
+    use std::collections::BTreeMap;
 
     #[test]
     fn test_creation() {
@@ -130,6 +221,21 @@ mod tests {
             ignore_blacklist: true,
             assign: None,
             requirement: Some("linux".to_string()),
+            cargo_config: Some("[net]\nretry = 5\n".to_string()),
+            expected_regressions: vec!["error: the `foo` lint".to_string()],
+            env: vec![("RUST_BACKTRACE".to_string(), "0".to_string())]
+                .into_iter()
+                .collect(),
+            deny_lints: vec!["unused_imports".to_string()],
+            cargo_update: true,
+            failure_threshold: Some(FailureThreshold::Percent(5)),
+            reuse_baseline: true,
+            canary: true,
+            test_runner: TestRunner::Nextest,
+            namespace: None,
+            survey: false,
+            parent: None,
+            sandbox_image: Some("crates-build-env-foo".to_string()),
         }
         .apply(&ctx)
         .unwrap();
@@ -157,6 +263,61 @@ mod tests {
         assert!(ex.assigned_to.is_none());
         assert!(ex.ignore_blacklist);
         assert_eq!(ex.requirement, Some("linux".to_string()));
+        assert_eq!(ex.cargo_config, Some("[net]\nretry = 5\n".to_string()));
+        assert_eq!(
+            ex.expected_regressions,
+            vec!["error: the `foo` lint".to_string()]
+        );
+        assert_eq!(ex.env.get("RUST_BACKTRACE").map(|v| v.as_str()), Some("0"));
+        assert_eq!(ex.deny_lints, vec!["unused_imports".to_string()]);
+        assert!(ex.cargo_update);
+        assert_eq!(ex.failure_threshold, Some(FailureThreshold::Percent(5)));
+        assert!(ex.reuse_baseline);
+        assert!(ex.canary);
+        assert_eq!(ex.test_runner, TestRunner::Nextest);
+        assert_eq!(ex.sandbox_image.as_deref(), Some("crates-build-env-foo"));
+    }
+
+    #[test]
+    fn test_invalid_cargo_config() {
+        let db = Database::temp().unwrap();
+        let config = Config::default();
+        let ctx = ActionsCtx::new(&db, &config);
+
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+
+        let err = CreateExperiment {
+            cargo_config: Some("[net".to_string()),
+            ..CreateExperiment::dummy("foo")
+        }
+        .apply(&ctx)
+        .unwrap_err();
+        match err.downcast_ref() {
+            Some(ExperimentError::InvalidCargoConfig(_)) => {}
+            _ => panic!("unexpected error: {}", err),
+        }
+        assert!(!Experiment::exists(&db, "foo").unwrap());
+    }
+
+    #[test]
+    fn test_invalid_expected_regression() {
+        let db = Database::temp().unwrap();
+        let config = Config::default();
+        let ctx = ActionsCtx::new(&db, &config);
+
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+
+        let err = CreateExperiment {
+            expected_regressions: vec!["error: (unclosed".to_string()],
+            ..CreateExperiment::dummy("foo")
+        }
+        .apply(&ctx)
+        .unwrap_err();
+        match err.downcast_ref() {
+            Some(ExperimentError::InvalidExpectedRegression(_)) => {}
+            _ => panic!("unexpected error: {}", err),
+        }
+        assert!(!Experiment::exists(&db, "foo").unwrap());
     }
 
     #[test]
@@ -213,6 +374,13 @@ mod tests {
                 skip_tests: false,
                 quiet: false,
                 broken: false,
+                targets: Vec::new(),
+                skip_targets: Vec::new(),
+                network: NetworkMode::Disabled,
+                memory_limit: None,
+                timeout: None,
+                disk_limit: None,
+                sandbox_image: None,
             },
         );
         let ctx = ActionsCtx::new(&db, &config);
@@ -256,6 +424,19 @@ mod tests {
             ignore_blacklist: false,
             assign: None,
             requirement: None,
+            cargo_config: None,
+            expected_regressions: Vec::new(),
+            env: BTreeMap::new(),
+            deny_lints: Vec::new(),
+            cargo_update: false,
+            failure_threshold: None,
+            reuse_baseline: false,
+            canary: false,
+            test_runner: TestRunner::Cargo,
+            namespace: None,
+            survey: false,
+            parent: None,
+            sandbox_image: None,
         }
         .apply(&ctx)
         .unwrap_err();
@@ -266,6 +447,43 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_survey() {
+        let db = Database::temp().unwrap();
+        let config = Config::default();
+        let ctx = ActionsCtx::new(&db, &config);
+
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+
+        let survey = |name: &str, end: &Toolchain, mode: Mode| CreateExperiment {
+            toolchains: [MAIN_TOOLCHAIN.clone(), end.clone()],
+            mode,
+            survey: true,
+            ..CreateExperiment::dummy(name)
+        };
+
+        // Surveys have a single toolchain, stored in both slots
+        let err = survey("foo", &TEST_TOOLCHAIN, Mode::BuildAndTest)
+            .apply(&ctx)
+            .unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&ExperimentError::SurveyToolchains));
+
+        let err = survey("foo", &MAIN_TOOLCHAIN, Mode::Msrv)
+            .apply(&ctx)
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref(),
+            Some(&ExperimentError::SurveyMode(Mode::Msrv))
+        );
+
+        survey("foo", &MAIN_TOOLCHAIN, Mode::BuildAndTest)
+            .apply(&ctx)
+            .unwrap();
+        let ex = Experiment::get(&db, "foo").unwrap().unwrap();
+        assert!(ex.survey);
+        assert_eq!(ex.tested_toolchains(), &[MAIN_TOOLCHAIN.clone()]);
+    }
+
     #[test]
     fn test_duplicate_name() {
         let db = Database::temp().unwrap();
@@ -286,6 +504,19 @@ mod tests {
             ignore_blacklist: false,
             assign: None,
             requirement: None,
+            cargo_config: None,
+            expected_regressions: Vec::new(),
+            env: BTreeMap::new(),
+            deny_lints: Vec::new(),
+            cargo_update: false,
+            failure_threshold: None,
+            reuse_baseline: false,
+            canary: false,
+            test_runner: TestRunner::Cargo,
+            namespace: None,
+            survey: false,
+            parent: None,
+            sandbox_image: None,
         }
         .apply(&ctx)
         .unwrap();
@@ -302,6 +533,19 @@ mod tests {
             ignore_blacklist: false,
             assign: None,
             requirement: None,
+            cargo_config: None,
+            expected_regressions: Vec::new(),
+            env: BTreeMap::new(),
+            deny_lints: Vec::new(),
+            cargo_update: false,
+            failure_threshold: None,
+            reuse_baseline: false,
+            canary: false,
+            test_runner: TestRunner::Cargo,
+            namespace: None,
+            survey: false,
+            parent: None,
+            sandbox_image: None,
         }
         .apply(&ctx)
         .unwrap_err();
@@ -311,4 +555,94 @@ mod tests {
             Some(&ExperimentError::AlreadyExists("foo".into()))
         );
     }
+
+    #[test]
+    fn test_namespace_quota() {
+        let db = Database::temp().unwrap();
+        let mut config = Config::default();
+        config.namespaces.insert(
+            "clippy".into(),
+            NamespaceConfig {
+                max_experiments: Some(1),
+            },
+        );
+        let ctx = ActionsCtx::new(&db, &config);
+
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+
+        let in_namespace = |name: &str, namespace: &str| CreateExperiment {
+            namespace: Some(namespace.into()),
+            ..CreateExperiment::dummy(name)
+        };
+
+        let err = in_namespace("foo", "cargo").apply(&ctx).unwrap_err();
+        assert_eq!(
+            err.downcast_ref(),
+            Some(&ExperimentError::UnknownNamespace("cargo".into()))
+        );
+
+        in_namespace("foo", "clippy").apply(&ctx).unwrap();
+        let mut ex = Experiment::get(&db, "foo").unwrap().unwrap();
+        assert_eq!(ex.namespace.as_deref(), Some("clippy"));
+
+        // Experiments of the default namespace don't count against the quota
+        CreateExperiment::dummy("bar").apply(&ctx).unwrap();
+
+        let err = in_namespace("baz", "clippy").apply(&ctx).unwrap_err();
+        assert_eq!(
+            err.downcast_ref(),
+            Some(&ExperimentError::NamespaceQuotaExceeded("clippy".into(), 1))
+        );
+
+        // Completed experiments don't count against the quota either
+        ex.set_status(&db, Status::Completed).unwrap();
+        in_namespace("baz", "clippy").apply(&ctx).unwrap();
+    }
+
+    #[test]
+    fn test_parent() {
+        let db = Database::temp().unwrap();
+        let config = Config::default();
+        let ctx = ActionsCtx::new(&db, &config);
+
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+
+        let with_parent = |name: &str, parent: &str| CreateExperiment {
+            parent: Some(parent.into()),
+            ..CreateExperiment::dummy(name)
+        };
+
+        let err = with_parent("child", "missing").apply(&ctx).unwrap_err();
+        assert_eq!(
+            err.downcast_ref(),
+            Some(&ExperimentError::NotFound("missing".into()))
+        );
+
+        // The results of the parent must be final
+        CreateExperiment::dummy("parent").apply(&ctx).unwrap();
+        let err = with_parent("child", "parent").apply(&ctx).unwrap_err();
+        assert_eq!(
+            err.downcast_ref(),
+            Some(&ExperimentError::ParentNotCompleted("parent".into()))
+        );
+
+        let mut parent = Experiment::get(&db, "parent").unwrap().unwrap();
+        parent.set_status(&db, Status::Completed).unwrap();
+        let err = CreateExperiment {
+            mode: Mode::CheckOnly,
+            ..with_parent("child", "parent")
+        }
+        .apply(&ctx)
+        .unwrap_err();
+        assert_eq!(
+            err.downcast_ref(),
+            Some(&ExperimentError::IncompatibleParent("parent".into()))
+        );
+
+        with_parent("child", "parent").apply(&ctx).unwrap();
+        let child = Experiment::get(&db, "child").unwrap().unwrap();
+        assert_eq!(child.parent.as_deref(), Some("parent"));
+        // Local crates can't be compared with the ones built by the parent, so none is inherited
+        assert_eq!(child.raw_progress(&db).unwrap().0, 0);
+    }
 }
@@ -1,8 +1,15 @@
-use crate::actions::{experiments::ExperimentError, Action, ActionsCtx};
+use crate::actions::experiments::{
+    validate_cargo_config, validate_deny_lints, validate_env, validate_toolchains, ExperimentError,
+};
+use crate::actions::{Action, ActionsCtx};
 use crate::db::QueryUtils;
-use crate::experiments::{Assignee, CapLints, CrateSelect, Experiment, Mode, Status};
+use crate::experiments::{
+    canary, Assignee, CapLints, CrateQueue, CrateSelect, Experiment, FailureThreshold, Mode,
+    Status, TestRunner,
+};
 use crate::prelude::*;
 use crate::toolchain::Toolchain;
+use std::collections::BTreeMap;
 
 pub struct EditExperiment {
     pub name: String,
@@ -14,6 +21,17 @@ pub struct EditExperiment {
     pub ignore_blacklist: Option<bool>,
     pub assign: Option<Assignee>,
     pub requirement: Option<String>,
+    pub cargo_config: Option<String>,
+    pub env: Option<BTreeMap<String, String>>,
+    pub deny_lints: Option<Vec<String>>,
+    pub cargo_update: Option<bool>,
+    /// `Some(None)` removes the failure threshold of the experiment.
+    pub failure_threshold: Option<Option<FailureThreshold>>,
+    pub reuse_baseline: Option<bool>,
+    pub canary: Option<bool>,
+    pub test_runner: Option<TestRunner>,
+    /// `Some(None)` builds the crates in the sandbox image of the workspace.
+    pub sandbox_image: Option<Option<String>>,
 }
 
 impl EditExperiment {
@@ -29,6 +47,15 @@ impl EditExperiment {
             ignore_blacklist: None,
             assign: None,
             requirement: None,
+            cargo_config: None,
+            env: None,
+            deny_lints: None,
+            cargo_update: None,
+            failure_threshold: None,
+            reuse_baseline: None,
+            canary: None,
+            test_runner: None,
+            sandbox_image: None,
         }
     }
 }
@@ -45,6 +72,25 @@ impl Action for EditExperiment {
             return Err(ExperimentError::CanOnlyEditQueuedExperiments.into());
         }
 
+        // The inherited results are only valid for the crates and settings they were built with
+        let rebuilds = self.toolchains.iter().any(Option::is_some)
+            || self.crates.is_some()
+            || self.ignore_blacklist.is_some()
+            || self.mode.is_some()
+            || self.cap_lints.is_some()
+            || self.sandbox_image.is_some();
+        if ex.parent.is_some() && rebuilds {
+            return Err(ExperimentError::CantEditIncremental.into());
+        }
+
+        // The only toolchain of surveys is stored in both slots
+        if ex.survey {
+            if self.toolchains[1].is_some() {
+                return Err(ExperimentError::SurveyToolchains.into());
+            }
+            self.toolchains[1] = self.toolchains[0].clone();
+        }
+
         ctx.db.transaction(|t| {
             // Try to update both toolchains
             for (i, col) in ["toolchain_start", "toolchain_end"].iter().enumerate() {
@@ -52,7 +98,7 @@ impl Action for EditExperiment {
                     ex.toolchains[i] = tc;
 
                     // Ensure no duplicate toolchain is inserted
-                    if ex.toolchains[0] == ex.toolchains[1] {
+                    if !ex.survey && ex.toolchains[0] == ex.toolchains[1] {
                         return Err(ExperimentError::DuplicateToolchains.into());
                     }
 
@@ -88,29 +134,23 @@ impl Action for EditExperiment {
             } else {
                 None
             };
-            if let Some(crates_vec) = new_crates {
+            if let Some(crates_vec) = &new_crates {
                 // Recreate the list of crates without checking if it was the same
                 // This is done to allow reloading the list of crates in an existing experiment
                 t.execute(
                     "DELETE FROM experiment_crates WHERE experiment = ?1;",
                     &[&self.name],
                 )?;
-                for krate in &crates_vec {
-                    t.execute(
-                        "INSERT INTO experiment_crates (experiment, crate, skipped, status) \
-                         VALUES (?1, ?2, ?3, ?4);",
-                        &[
-                            &self.name,
-                            &krate.id(),
-                            &(!ex.ignore_blacklist && ctx.config.should_skip(krate)),
-                            &Status::Queued.to_string(),
-                        ],
-                    )?;
+                let queue = CrateQueue::new(&self.name);
+                for krate in crates_vec {
+                    let skipped = !ex.ignore_blacklist && ctx.config.should_skip(krate);
+                    queue.enqueue(t, krate, skipped)?;
                 }
             }
 
             // Try to update the mode
             if let Some(mode) = self.mode {
+                validate_toolchains(&ex.toolchains, ex.survey, mode)?;
                 let changes = t.execute(
                     "UPDATE experiments SET mode = ?1 WHERE name = ?2;",
                     &[&mode.to_str(), &self.name],
@@ -159,6 +199,119 @@ impl Action for EditExperiment {
                 ex.requirement = Some(requirement);
             }
 
+            // Try to update the cargo config, removing it if it's empty
+            if let Some(cargo_config) = self.cargo_config {
+                let cargo_config = if cargo_config.trim().is_empty() {
+                    None
+                } else {
+                    validate_cargo_config(&cargo_config)?;
+                    Some(cargo_config)
+                };
+                let changes = t.execute(
+                    "UPDATE experiments SET cargo_config = ?1 WHERE name = ?2;",
+                    &[&cargo_config, &self.name],
+                )?;
+                assert_eq!(changes, 1);
+                ex.cargo_config = cargo_config;
+            }
+
+            // Try to update the environment, replacing the previous one
+            if let Some(env) = self.env {
+                validate_env(&env)?;
+                let changes = t.execute(
+                    "UPDATE experiments SET env = ?1 WHERE name = ?2;",
+                    &[&serde_json::to_string(&env)?, &self.name],
+                )?;
+                assert_eq!(changes, 1);
+                ex.env = env;
+            }
+
+            // Try to update the denied lints, replacing the previous ones
+            if let Some(deny_lints) = self.deny_lints {
+                let changes = t.execute(
+                    "UPDATE experiments SET deny_lints = ?1 WHERE name = ?2;",
+                    &[&serde_json::to_string(&deny_lints)?, &self.name],
+                )?;
+                assert_eq!(changes, 1);
+                ex.deny_lints = deny_lints;
+            }
+
+            // Try to update whether the dependencies are updated before building
+            if let Some(cargo_update) = self.cargo_update {
+                let changes = t.execute(
+                    "UPDATE experiments SET cargo_update = ?1 WHERE name = ?2;",
+                    &[&cargo_update, &self.name],
+                )?;
+                assert_eq!(changes, 1);
+                ex.cargo_update = cargo_update;
+            }
+
+            // Try to update the regressions after which the experiment is paused
+            if let Some(failure_threshold) = self.failure_threshold {
+                let changes = t.execute(
+                    "UPDATE experiments SET failure_threshold = ?1 WHERE name = ?2;",
+                    &[&failure_threshold.map(|t| t.to_string()), &self.name],
+                )?;
+                assert_eq!(changes, 1);
+                ex.failure_threshold = failure_threshold;
+            }
+
+            // Try to update whether the banked baseline results are reused
+            if let Some(reuse_baseline) = self.reuse_baseline {
+                let changes = t.execute(
+                    "UPDATE experiments SET reuse_baseline = ?1 WHERE name = ?2;",
+                    &[&reuse_baseline, &self.name],
+                )?;
+                assert_eq!(changes, 1);
+                ex.reuse_baseline = reuse_baseline;
+            }
+
+            // Try to update whether the experiment starts with a canary
+            if let Some(canary) = self.canary {
+                let changes = t.execute(
+                    "UPDATE experiments SET canary = ?1 WHERE name = ?2;",
+                    &[&canary, &self.name],
+                )?;
+                assert_eq!(changes, 1);
+                ex.canary = canary;
+            }
+
+            // Try to update the tool running the tests
+            if let Some(test_runner) = self.test_runner {
+                let changes = t.execute(
+                    "UPDATE experiments SET test_runner = ?1 WHERE name = ?2;",
+                    &[&test_runner.to_str(), &self.name],
+                )?;
+                assert_eq!(changes, 1);
+                ex.test_runner = test_runner;
+            }
+
+            // Try to update the sandbox image the crates are built in
+            if let Some(sandbox_image) = self.sandbox_image.take() {
+                let changes = t.execute(
+                    "UPDATE experiments SET sandbox_image = ?1 WHERE name = ?2;",
+                    &[&sandbox_image, &self.name],
+                )?;
+                assert_eq!(changes, 1);
+                ex.sandbox_image = sandbox_image;
+            }
+
+            // The canaries depend on the list of crates, so they're selected again if it changed
+            if new_crates.is_some() || self.canary.is_some() {
+                let queue = CrateQueue::new(&self.name);
+                queue.release(t)?;
+                if ex.canary {
+                    let crates = match new_crates {
+                        Some(crates) => crates,
+                        None => ex.get_crates(&ctx.db)?,
+                    };
+                    queue.hold_except(t, &canary::select(&crates))?;
+                }
+            }
+
+            // The cap on the lints might have changed as well
+            validate_deny_lints(&ex.deny_lints, ex.cap_lints)?;
+
             Ok(())
         })?;
         Ok(())
@@ -169,11 +322,14 @@ impl Action for EditExperiment {
 mod tests {
     use super::EditExperiment;
     use crate::actions::{Action, ActionsCtx, CreateExperiment, ExperimentError};
-    use crate::config::{Config, CrateConfig};
+    use crate::config::{Config, CrateConfig, NetworkMode};
     use crate::crates::Crate;
     use crate::db::{Database, QueryUtils};
-    use crate::experiments::{Assignee, CapLints, CrateSelect, Experiment, Mode, Status};
+    use crate::experiments::{
+        Assignee, CapLints, CrateSelect, Experiment, FailureThreshold, Mode, Status, TestRunner,
+    };
     use crate::toolchain::{MAIN_TOOLCHAIN, TEST_TOOLCHAIN};
+    use std::collections::BTreeMap;
 
     #[test]
     fn test_edit_with_no_changes() {
@@ -207,6 +363,19 @@ mod tests {
             ignore_blacklist: false,
             assign: None,
             requirement: None,
+            cargo_config: None,
+            expected_regressions: Vec::new(),
+            env: BTreeMap::new(),
+            deny_lints: Vec::new(),
+            cargo_update: false,
+            failure_threshold: None,
+            reuse_baseline: false,
+            canary: false,
+            test_runner: TestRunner::Cargo,
+            namespace: None,
+            survey: false,
+            parent: None,
+            sandbox_image: None,
         }
         .apply(&ctx)
         .unwrap();
@@ -225,6 +394,19 @@ mod tests {
             ignore_blacklist: Some(true),
             assign: Some(Assignee::CLI),
             requirement: Some("windows".to_string()),
+            cargo_config: Some("[profile.dev]\ndebug = 0\n".to_string()),
+            env: Some(
+                vec![("RUST_MIN_STACK".to_string(), "16777216".to_string())]
+                    .into_iter()
+                    .collect(),
+            ),
+            deny_lints: Some(vec!["unused_imports".to_string()]),
+            cargo_update: Some(true),
+            failure_threshold: Some(Some(FailureThreshold::Regressions(50))),
+            reuse_baseline: Some(true),
+            canary: Some(true),
+            test_runner: Some(TestRunner::Nextest),
+            sandbox_image: Some(Some("crates-build-env-foo".to_string())),
         }
         .apply(&ctx)
         .unwrap();
@@ -240,6 +422,24 @@ mod tests {
         assert_eq!(ex.ignore_blacklist, true);
         assert_eq!(ex.assigned_to, Some(Assignee::CLI));
         assert_eq!(ex.requirement, Some("windows".to_string()));
+        assert_eq!(
+            ex.cargo_config,
+            Some("[profile.dev]\ndebug = 0\n".to_string())
+        );
+        assert_eq!(
+            ex.env.get("RUST_MIN_STACK").map(|v| v.as_str()),
+            Some("16777216")
+        );
+        assert_eq!(ex.deny_lints, vec!["unused_imports".to_string()]);
+        assert!(ex.cargo_update);
+        assert_eq!(
+            ex.failure_threshold,
+            Some(FailureThreshold::Regressions(50))
+        );
+        assert!(ex.reuse_baseline);
+        assert!(ex.canary);
+        assert_eq!(ex.test_runner, TestRunner::Nextest);
+        assert_eq!(ex.sandbox_image.as_deref(), Some("crates-build-env-foo"));
 
         assert_eq!(
             ex.get_crates(&ctx.db).unwrap(),
@@ -281,6 +481,13 @@ mod tests {
                 skip_tests: false,
                 quiet: false,
                 broken: false,
+                targets: Vec::new(),
+                skip_targets: Vec::new(),
+                network: NetworkMode::Disabled,
+                memory_limit: None,
+                timeout: None,
+                disk_limit: None,
+                sandbox_image: None,
             },
         );
         let ctx = ActionsCtx::new(&db, &config);
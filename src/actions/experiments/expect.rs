@@ -0,0 +1,96 @@
+use crate::actions::experiments::{validate_expected_regressions, ExperimentError};
+use crate::actions::{Action, ActionsCtx};
+use crate::db::QueryUtils;
+use crate::experiments::Experiment;
+use crate::prelude::*;
+
+/// Register new patterns of expected regressions: regressed crates whose log matches one of them
+/// are marked as expected in the report. Unlike the rest of the configuration, this can be changed
+/// while the experiment is running.
+pub struct ExpectRegressions {
+    pub name: String,
+    pub patterns: Vec<String>,
+}
+
+impl Action for ExpectRegressions {
+    fn apply(self, ctx: &ActionsCtx) -> Fallible<()> {
+        let mut ex = match Experiment::get(&ctx.db, &self.name)? {
+            Some(ex) => ex,
+            None => return Err(ExperimentError::NotFound(self.name).into()),
+        };
+
+        validate_expected_regressions(&self.patterns)?;
+        for pattern in self.patterns {
+            if !ex.expected_regressions.contains(&pattern) {
+                ex.expected_regressions.push(pattern);
+            }
+        }
+
+        let changes = ctx.db.execute(
+            "UPDATE experiments SET expected_regressions = ?1 WHERE name = ?2;",
+            &[&serde_json::to_string(&ex.expected_regressions)?, &ex.name],
+        )?;
+        assert_eq!(changes, 1);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ExpectRegressions;
+    use crate::actions::{Action, ActionsCtx, CreateExperiment, ExperimentError};
+    use crate::config::Config;
+    use crate::db::Database;
+    use crate::experiments::{Experiment, Status};
+
+    #[test]
+    fn test_expect_regressions() {
+        let db = Database::temp().unwrap();
+        let config = Config::default();
+        let ctx = ActionsCtx::new(&db, &config);
+
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+
+        CreateExperiment::dummy("foo").apply(&ctx).unwrap();
+        let mut ex = Experiment::get(&db, "foo").unwrap().unwrap();
+        ex.set_status(&db, Status::Running).unwrap();
+
+        // Patterns can be added to running experiments, and duplicates are ignored
+        for patterns in &[vec!["lint `foo`"], vec!["lint `foo`", "E0[0-9]+"]] {
+            ExpectRegressions {
+                name: "foo".to_string(),
+                patterns: patterns.iter().map(|p| p.to_string()).collect(),
+            }
+            .apply(&ctx)
+            .unwrap();
+        }
+        let ex = Experiment::get(&db, "foo").unwrap().unwrap();
+        assert_eq!(
+            ex.expected_regressions,
+            vec!["lint `foo`".to_string(), "E0[0-9]+".to_string()]
+        );
+
+        let err = ExpectRegressions {
+            name: "foo".to_string(),
+            patterns: vec!["(".to_string()],
+        }
+        .apply(&ctx)
+        .unwrap_err();
+        match err.downcast_ref() {
+            Some(ExperimentError::InvalidExpectedRegression(_)) => {}
+            _ => panic!("unexpected error: {}", err),
+        }
+
+        let err = ExpectRegressions {
+            name: "missing".to_string(),
+            patterns: Vec::new(),
+        }
+        .apply(&ctx)
+        .unwrap_err();
+        assert_eq!(
+            err.downcast_ref(),
+            Some(&ExperimentError::NotFound("missing".into()))
+        );
+    }
+}
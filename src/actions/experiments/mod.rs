@@ -1,10 +1,20 @@
+mod clone;
 mod create;
 mod delete;
 mod edit;
+mod expect;
 
+pub use self::clone::CloneExperiment;
 pub use self::create::CreateExperiment;
 pub use self::delete::DeleteExperiment;
 pub use self::edit::EditExperiment;
+pub use self::expect::ExpectRegressions;
+
+use crate::actions::ActionsCtx;
+use crate::experiments::{CapLints, Experiment, FailureThreshold, Mode, Status};
+use crate::prelude::*;
+use crate::toolchain::Toolchain;
+use std::collections::BTreeMap;
 
 #[derive(Debug, failure::Fail)]
 #[cfg_attr(test, derive(PartialEq, Eq))]
@@ -15,6 +25,297 @@ pub enum ExperimentError {
     AlreadyExists(String),
     #[fail(display = "duplicate toolchains provided")]
     DuplicateToolchains,
+    #[fail(display = "survey experiments only have one toolchain")]
+    SurveyToolchains,
+    #[fail(
+        display = "the {} mode compares two toolchains, it can't be used by surveys",
+        _0
+    )]
+    SurveyMode(Mode),
     #[fail(display = "it's only possible to edit queued experiments")]
     CanOnlyEditQueuedExperiments,
+    #[fail(display = "invalid cargo config: {}", _0)]
+    InvalidCargoConfig(String),
+    #[fail(display = "invalid expected regression pattern: {}", _0)]
+    InvalidExpectedRegression(String),
+    #[fail(display = "invalid environment variable: {}", _0)]
+    InvalidEnv(String),
+    #[fail(display = "invalid denied lint: {}", _0)]
+    InvalidDenyLint(String),
+    #[fail(display = "namespace '{}' doesn't exist", _0)]
+    UnknownNamespace(String),
+    #[fail(display = "namespace '{}' already has {} active experiments", _0, _1)]
+    NamespaceQuotaExceeded(String, u32),
+    #[fail(display = "parent experiment '{}' didn't complete yet", _0)]
+    ParentNotCompleted(String),
+    #[fail(
+        display = "parent experiment '{}' doesn't use the same mode, lints cap and sandbox image",
+        _0
+    )]
+    IncompatibleParent(String),
+    #[fail(
+        display = "the crates, toolchains, mode and sandbox image of incremental experiments \
+                   can't be edited"
+    )]
+    CantEditIncremental,
+}
+
+/// Ensure the toolchains of an experiment are different, except for surveys which have a single
+/// toolchain (stored in both slots) and can't use the modes comparing two toolchains.
+fn validate_toolchains(toolchains: &[Toolchain; 2], survey: bool, mode: Mode) -> Fallible<()> {
+    if !survey {
+        if toolchains[0] == toolchains[1] {
+            return Err(ExperimentError::DuplicateToolchains.into());
+        }
+    } else if toolchains[0] != toolchains[1] {
+        return Err(ExperimentError::SurveyToolchains.into());
+    } else if mode == Mode::CheckThenTest || mode == Mode::Msrv {
+        return Err(ExperimentError::SurveyMode(mode).into());
+    }
+    Ok(())
+}
+
+/// Ensure the cargo config fragment of an experiment is valid TOML before storing it.
+fn validate_cargo_config(config: &str) -> Fallible<()> {
+    match config.parse::<toml::Value>() {
+        Ok(toml::Value::Table(_)) => Ok(()),
+        Ok(_) => Err(ExperimentError::InvalidCargoConfig("not a table".into()).into()),
+        Err(err) => Err(ExperimentError::InvalidCargoConfig(err.to_string()).into()),
+    }
+}
+
+/// Ensure the patterns of the expected regressions are valid regexes before storing them.
+fn validate_expected_regressions(patterns: &[String]) -> Fallible<()> {
+    for pattern in patterns {
+        if let Err(err) = regex::Regex::new(pattern) {
+            return Err(ExperimentError::InvalidExpectedRegression(err.to_string()).into());
+        }
+    }
+    Ok(())
+}
+
+/// Check the value of an environment variable changing the behavior of the toolchain, returning
+/// `None` if experiments are not allowed to set it.
+fn is_valid_env(name: &str, value: &str) -> Option<bool> {
+    let positive = |value: &str| value.parse::<u64>().map(|n| n > 0).unwrap_or(false);
+    Some(match name {
+        "RUST_BACKTRACE" => value == "0" || value == "1" || value == "full",
+        "RUSTC_BOOTSTRAP" => {
+            value == "1"
+                || value.split(',').all(|krate| {
+                    !krate.is_empty() && krate.chars().all(|c| c.is_alphanumeric() || c == '_')
+                })
+        }
+        "RUST_MIN_STACK" | "RUST_TEST_THREADS" => positive(value),
+        "CARGO_INCREMENTAL" => value == "0" || value == "1",
+        _ => return None,
+    })
+}
+
+/// Parse a list of `NAME=value` environment variables, as provided by the users.
+pub fn parse_env(vars: &[String]) -> Fallible<BTreeMap<String, String>> {
+    let mut env = BTreeMap::new();
+    for var in vars {
+        let mut parts = var.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some(name), Some(value)) if !name.is_empty() => {
+                env.insert(name.to_string(), value.to_string());
+            }
+            _ => {
+                let message = format!("{} (expected NAME=value)", var);
+                return Err(ExperimentError::InvalidEnv(message).into());
+            }
+        }
+    }
+    Ok(env)
+}
+
+/// Ensure only known environment variables with sensible values are set by an experiment.
+fn validate_env(env: &BTreeMap<String, String>) -> Fallible<()> {
+    for (name, value) in env {
+        match is_valid_env(name, value) {
+            Some(true) => {}
+            Some(false) => {
+                return Err(ExperimentError::InvalidEnv(format!("{}={}", name, value)).into());
+            }
+            None => {
+                return Err(ExperimentError::InvalidEnv(format!("{} can't be set", name)).into());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Ensure the namespace of a new experiment is configured, and that the experiment fits in the
+/// quota of the namespace.
+fn validate_namespace(ctx: &ActionsCtx, namespace: Option<&str>) -> Fallible<()> {
+    let namespace = match namespace {
+        Some(namespace) => namespace,
+        None => return Ok(()),
+    };
+    let config = ctx
+        .config
+        .namespaces
+        .get(namespace)
+        .ok_or_else(|| ExperimentError::UnknownNamespace(namespace.into()))?;
+    if let Some(max) = config.max_experiments {
+        if Experiment::active_in_namespace(&ctx.db, namespace)? >= max {
+            return Err(ExperimentError::NamespaceQuotaExceeded(namespace.into(), max).into());
+        }
+    }
+    Ok(())
+}
+
+/// Ensure the parent of an incremental experiment completed, and that its results are comparable
+/// with the ones of the new experiment, returning it.
+fn validate_parent(
+    ctx: &ActionsCtx,
+    parent: &str,
+    mode: Mode,
+    cap_lints: CapLints,
+    survey: bool,
+    sandbox_image: Option<&str>,
+) -> Fallible<Experiment> {
+    let parent = Experiment::get(&ctx.db, parent)?
+        .ok_or_else(|| ExperimentError::NotFound(parent.into()))?;
+    match parent.status {
+        Status::Queued | Status::Running | Status::Paused | Status::Failed => {
+            return Err(ExperimentError::ParentNotCompleted(parent.name).into());
+        }
+        Status::NeedsReport
+        | Status::GeneratingReport
+        | Status::ReportFailed
+        | Status::AwaitingApproval
+        | Status::Completed => {}
+    }
+    if parent.mode != mode
+        || parent.cap_lints != cap_lints
+        || parent.survey != survey
+        || parent.sandbox_image.as_deref() != sandbox_image
+    {
+        return Err(ExperimentError::IncompatibleParent(parent.name).into());
+    }
+    Ok(parent)
+}
+
+/// Parse a comma-separated list of lints, as provided by the users.
+pub fn parse_lints(lints: &str) -> Vec<String> {
+    lints
+        .split(',')
+        .map(|lint| lint.trim())
+        .filter(|lint| !lint.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Parse the failure threshold of an experiment as provided by the users, where `none` means the
+/// experiment is never paused.
+pub fn parse_failure_threshold(value: &str) -> Fallible<Option<FailureThreshold>> {
+    if value == "none" {
+        Ok(None)
+    } else {
+        Ok(Some(value.parse()?))
+    }
+}
+
+/// Parse the sandbox image of an experiment as provided by the users, where `default` means the
+/// crates are built in the sandbox image of the workspace.
+pub fn parse_sandbox_image(value: &str) -> Option<String> {
+    if value == "default" {
+        None
+    } else {
+        Some(value.to_string())
+    }
+}
+
+/// Ensure the denied lints are valid lint names (optionally prefixed by a tool name, like
+/// `clippy::`), and that the lints will actually be emitted.
+fn validate_deny_lints(lints: &[String], cap_lints: CapLints) -> Fallible<()> {
+    let is_ident = |s: &str| {
+        !s.is_empty()
+            && s.chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    };
+    for lint in lints {
+        let mut parts = lint.splitn(2, "::");
+        let valid = match (parts.next(), parts.next()) {
+            (Some(name), None) => is_ident(name),
+            (Some(tool), Some(name)) => is_ident(tool) && is_ident(name),
+            _ => false,
+        };
+        if !valid {
+            return Err(ExperimentError::InvalidDenyLint(lint.clone()).into());
+        }
+    }
+
+    if !lints.is_empty() && cap_lints == CapLints::Allow {
+        let message = "lints can't be denied with cap-lints=allow".to_string();
+        return Err(ExperimentError::InvalidDenyLint(message).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_env, parse_lints, validate_deny_lints, validate_env, ExperimentError};
+    use crate::experiments::CapLints;
+
+    #[test]
+    fn test_env() {
+        let env = parse_env(&[
+            "RUST_BACKTRACE=0".to_string(),
+            "RUSTC_BOOTSTRAP=foo,bar_baz".to_string(),
+        ])
+        .unwrap();
+        assert_eq!(env.get("RUST_BACKTRACE").map(|v| v.as_str()), Some("0"));
+        assert!(validate_env(&env).is_ok());
+
+        for invalid in &["RUST_BACKTRACE", "=1"] {
+            let err = parse_env(&[invalid.to_string()]).unwrap_err();
+            match err.downcast_ref() {
+                Some(ExperimentError::InvalidEnv(_)) => {}
+                _ => panic!("unexpected error: {}", err),
+            }
+        }
+
+        for invalid in &[
+            "RUST_BACKTRACE=yes",
+            "RUST_MIN_STACK=0",
+            "LD_PRELOAD=/tmp/foo.so",
+        ] {
+            let env = parse_env(&[invalid.to_string()]).unwrap();
+            let err = validate_env(&env).unwrap_err();
+            match err.downcast_ref() {
+                Some(ExperimentError::InvalidEnv(_)) => {}
+                _ => panic!("unexpected error: {}", err),
+            }
+        }
+    }
+
+    #[test]
+    fn test_deny_lints() {
+        let lints = parse_lints("unused_imports, clippy::needless_return,");
+        assert_eq!(
+            lints,
+            vec![
+                "unused_imports".to_string(),
+                "clippy::needless_return".to_string()
+            ]
+        );
+        assert!(validate_deny_lints(&lints, CapLints::Warn).is_ok());
+        assert!(validate_deny_lints(&[], CapLints::Allow).is_ok());
+
+        for (lints, cap_lints) in &[
+            ("unused_imports", CapLints::Allow),
+            ("Unused-Imports", CapLints::Warn),
+            ("clippy::", CapLints::Warn),
+            ("a::b::c", CapLints::Warn),
+        ] {
+            let err = validate_deny_lints(&parse_lints(lints), *cap_lints).unwrap_err();
+            match err.downcast_ref() {
+                Some(ExperimentError::InvalidDenyLint(_)) => {}
+                _ => panic!("unexpected error: {}", err),
+            }
+        }
+    }
 }
@@ -22,12 +22,12 @@ impl Action for UpdateLists {
     fn apply(self, ctx: &ActionsCtx) -> Fallible<()> {
         if self.github {
             info!("updating GitHub repositories list");
-            GitHubList::default().update(&ctx.db)?;
+            GitHubList::new(&ctx.config.github).update(&ctx.db)?;
         }
 
         if self.registry {
             info!("updating crates.io crates list");
-            RegistryList.update(&ctx.db)?;
+            RegistryList::new(&ctx.config.registry).update(&ctx.db)?;
         }
 
         if self.local {
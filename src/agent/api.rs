@@ -2,11 +2,13 @@ use crate::agent::Capabilities;
 use crate::crates::Crate;
 use crate::experiments::Experiment;
 use crate::prelude::*;
-use crate::results::TestResult;
+use crate::results::{DependencyGraph, EncodedLog, EncodingType, SandboxCounters, TestResult};
 use crate::server::api_types::{AgentConfig, ApiResponse, CraterToken};
 use crate::toolchain::Toolchain;
 use crate::utils;
+use crate::utils::hex::sha1_hex;
 use http::{header::AUTHORIZATION, Method, StatusCode};
+use rand::{thread_rng, Rng};
 use reqwest::RequestBuilder;
 use serde::de::DeserializeOwned;
 use serde_json::json;
@@ -25,7 +27,7 @@ pub enum AgentApiError {
     InternalServerError(String),
 }
 
-trait ResponseExt {
+pub(crate) trait ResponseExt {
     fn to_api_response<T: DeserializeOwned>(self) -> Fallible<T>;
 }
 
@@ -61,6 +63,15 @@ impl ResponseExt for ::reqwest::Response {
 }
 
 const RETRY_AFTER: u64 = 5;
+/// Logs bigger than this (after being compressed) are uploaded in chunks of this size.
+const UPLOAD_CHUNK_SIZE: usize = 1024 * 1024;
+/// How many times in a row a chunk can be rejected by the server before the upload fails.
+const UPLOAD_CHUNK_ATTEMPTS: usize = 5;
+
+fn random_key() -> String {
+    let mut rng = thread_rng();
+    format!("{:016x}{:016x}", rng.gen::<u64>(), rng.gen::<u64>())
+}
 
 pub struct AgentApi {
     url: String,
@@ -151,8 +162,32 @@ impl AgentApi {
         toolchain: &Toolchain,
         log: &[u8],
         result: &TestResult,
+        memory_limit: Option<u64>,
+        sandbox_counters: Option<&SandboxCounters>,
+        sandbox_image: Option<&str>,
+        dependency_graph: Option<&DependencyGraph>,
+        duration: Option<u64>,
         version: Option<(&Crate, &Crate)>,
     ) -> Fallible<()> {
+        // The key is the same for all the retries, so the server can ignore the results it
+        // already received when only the response was lost
+        let idempotency_key = random_key();
+
+        // Big logs over slow connections made the submissions time out, so logs are compressed,
+        // and the biggest ones are uploaded in chunks before the result
+        let log = EncodedLog::from_plain_slice(log, EncodingType::Gzip)?;
+        let checksum = sha1_hex(log.as_slice());
+        let (upload, encoded_log) = if log.as_slice().len() > UPLOAD_CHUNK_SIZE {
+            // Renamed crates are still leased under their old name
+            let leased = version.map(|(old, _)| old).unwrap_or(krate);
+            (
+                Some(self.upload_log(ex, leased, log.as_slice())?),
+                String::new(),
+            )
+        } else {
+            (None, base64::encode(log.as_slice()))
+        };
+
         self.retry(|this| {
             let _: bool = this
                 .build_request(Method::POST, "record-progress")
@@ -163,10 +198,19 @@ impl AgentApi {
                             "crate": krate,
                             "toolchain": toolchain,
                             "result": result,
-                            "log": base64::encode(log),
+                            "log": encoded_log,
+                            "encoding": log.get_encoding_type(),
+                            "upload": upload,
+                            "checksum": checksum,
+                            "memory_limit": memory_limit,
+                            "sandbox_counters": sandbox_counters,
+                            "sandbox_image": sandbox_image,
+                            "dependency_graph": dependency_graph,
+                            "duration": duration,
                         },
                     ],
-                    "version": version
+                    "version": version,
+                    "idempotency-key": idempotency_key,
                 }))
                 .send()?
                 .to_api_response()?;
@@ -174,6 +218,104 @@ impl AgentApi {
         })
     }
 
+    /// Upload a log in chunks, returning the ID of the upload. An interrupted upload is resumed
+    /// from the last chunk the server received.
+    fn upload_log(&self, ex: &Experiment, krate: &Crate, log: &[u8]) -> Fallible<String> {
+        let upload = random_key();
+        let mut offset = 0;
+        let mut rejected = 0;
+        while offset < log.len() {
+            let chunk = &log[offset..log.len().min(offset + UPLOAD_CHUNK_SIZE)];
+            let received: usize = self.retry(|this| {
+                this.build_request(Method::POST, "upload-log-chunk")
+                    .json(&json!({
+                        "experiment-name": ex.name,
+                        "crate": krate,
+                        "upload": upload,
+                        "offset": offset,
+                        "chunk": base64::encode(chunk),
+                        "checksum": sha1_hex(chunk),
+                    }))
+                    .send()?
+                    .to_api_response()
+            })?;
+
+            if received > log.len() {
+                bail!(
+                    "the server received more data than the log upload {} has",
+                    upload
+                );
+            } else if received <= offset {
+                rejected += 1;
+                if rejected >= UPLOAD_CHUNK_ATTEMPTS {
+                    bail!(
+                        "the server rejected the chunk at offset {} of the log",
+                        offset
+                    );
+                }
+                warn!("the server rejected a chunk of the log, uploading it again");
+            } else {
+                rejected = 0;
+            }
+            offset = received;
+        }
+        Ok(upload)
+    }
+
+    pub fn banked_result(
+        &self,
+        ex: &Experiment,
+        krate: &Crate,
+        sandbox_image: Option<&str>,
+    ) -> Fallible<Option<TestResult>> {
+        self.retry(|this| {
+            this.build_request(Method::POST, "banked-result")
+                .json(&json!({
+                    "experiment-name": ex.name,
+                    "crate": krate,
+                    "sandbox_image": sandbox_image,
+                }))
+                .send()?
+                .to_api_response()
+        })
+    }
+
+    /// Seconds the builds of the crate are allowed to take according to the duration of its
+    /// previous builds, if the server learned it.
+    pub fn learned_timeout(&self, ex: &Experiment, krate: &Crate) -> Fallible<Option<u64>> {
+        self.retry(|this| {
+            this.build_request(Method::POST, "learned-timeout")
+                .json(&json!({
+                    "experiment-name": ex.name,
+                    "crate": krate,
+                }))
+                .send()?
+                .to_api_response()
+        })
+    }
+
+    /// Upload the part of the log of a running build starting at `offset`. Failed uploads are
+    /// not retried, as the chunk is sent again along with the next one anyway.
+    pub fn stream_log(
+        &self,
+        ex: &Experiment,
+        krate: &Crate,
+        toolchain: &Toolchain,
+        offset: usize,
+        chunk: &str,
+    ) -> Fallible<bool> {
+        self.build_request(Method::POST, "stream-log")
+            .json(&json!({
+                "experiment-name": ex.name,
+                "crate": krate,
+                "toolchain": toolchain,
+                "offset": offset,
+                "chunk": chunk,
+            }))
+            .send()?
+            .to_api_response()
+    }
+
     pub fn heartbeat(&self) -> Fallible<()> {
         self.retry(|this| {
             let _: bool = this
@@ -1,5 +1,8 @@
-mod api;
+pub(crate) mod api;
 mod results;
+mod simulate;
+
+pub use crate::agent::simulate::{run as simulate, Simulation};
 
 use crate::agent::api::AgentApi;
 use crate::agent::results::ResultsUploader;
@@ -106,6 +109,17 @@ fn run_heartbeat(url: &str, token: &str) {
     });
 }
 
+fn log_workspace_usage() {
+    match crate::utils::workspace::disk_usage(&crate::dirs::WORK_DIR) {
+        Ok(usage) => {
+            for (name, category) in usage.categories().iter() {
+                info!("workspace {} use {} bytes", name, category.total);
+            }
+        }
+        Err(err) => warn!("failed to calculate the workspace disk usage: {}", err),
+    }
+}
+
 fn run_experiment(
     agent: &Agent,
     workspace: &Workspace,
@@ -124,6 +138,7 @@ fn run_experiment(
     match DiskUsage::fetch() {
         Ok(usage) => {
             if usage.is_threshold_reached(PURGE_CACHES_THRESHOLD) {
+                log_workspace_usage();
                 warn!("purging all caches");
                 workspace.purge_all_caches().map_err(|err| (None, err))?;
             }
@@ -144,9 +159,10 @@ pub fn run(
     threads_count: usize,
     caps: &Capabilities,
     workspace: &Workspace,
+    sandbox_image: Option<String>,
 ) -> Fallible<()> {
     let agent = Agent::new(url, token, caps)?;
-    let db = results::ResultsUploader::new(&agent.api);
+    let db = results::ResultsUploader::new(&agent.api, sandbox_image);
 
     run_heartbeat(url, token);
 
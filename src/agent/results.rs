@@ -3,36 +3,97 @@ use crate::config::Config;
 use crate::crates::Crate;
 use crate::experiments::Experiment;
 use crate::prelude::*;
-use crate::results::{EncodingType, TestResult, WriteResults};
+use crate::results::{
+    log_output, DependencyGraph, EncodingType, RecordSandboxImage, Redactor, SandboxCounters,
+    TestResult, WriteResults,
+};
 use crate::toolchain::Toolchain;
+use crossbeam_utils::thread::scope;
 use rustwide::logging::{self, LogStorage};
 use std::collections::{hash_map::Entry::Occupied, HashMap};
+use std::sync::mpsc::{self, RecvTimeoutError};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 #[derive(Clone)]
 pub struct ResultsUploader<'a> {
     api: &'a AgentApi,
     versions: Arc<Mutex<HashMap<Crate, (Crate, bool)>>>,
+    /// Dependency graphs resolved while building, sent along with the result of the build.
+    dependency_graphs: Arc<Mutex<HashMap<(Crate, Toolchain), DependencyGraph>>>,
+    sandbox_image: Option<String>,
 }
 
 impl<'a> ResultsUploader<'a> {
-    pub fn new(api: &'a AgentApi) -> Self {
+    pub fn new(api: &'a AgentApi, sandbox_image: Option<String>) -> Self {
         ResultsUploader {
             api,
             versions: Arc::new(Mutex::new(HashMap::new())),
+            dependency_graphs: Arc::new(Mutex::new(HashMap::new())),
+            sandbox_image,
         }
     }
+
+    /// Run the build, uploading its log every `server.live-logs.every-secs` seconds while it
+    /// runs so that the operators can see what a build stuck for a long time is doing.
+    fn stream_log<T>(
+        &self,
+        ex: &Experiment,
+        toolchain: &Toolchain,
+        krate: &Crate,
+        storage: &LogStorage,
+        redactor: &Redactor,
+        interval: u64,
+        f: impl FnOnce() -> T,
+    ) -> T {
+        if interval == 0 {
+            return f();
+        }
+
+        let (stop, stopped) = mpsc::channel::<()>();
+        scope(|scope| {
+            scope.spawn(move || {
+                let mut sent = 0;
+                while let Err(RecvTimeoutError::Timeout) =
+                    stopped.recv_timeout(Duration::from_secs(interval))
+                {
+                    let output = storage.to_string();
+                    let output = redactor.redact(&output);
+                    if output.len() <= sent || !output.is_char_boundary(sent) {
+                        continue;
+                    }
+                    match self
+                        .api
+                        .stream_log(ex, krate, toolchain, sent, &output[sent..])
+                    {
+                        Ok(true) => sent = output.len(),
+                        // The server lost the previous chunks, send the whole log again
+                        Ok(false) => sent = 0,
+                        Err(err) => warn!("failed to upload the live log: {}", err),
+                    }
+                }
+            });
+            let result = f();
+            drop(stop);
+            result
+        })
+    }
 }
 
 impl<'a> WriteResults for ResultsUploader<'a> {
     fn get_result(
         &self,
-        _ex: &Experiment,
-        _toolchain: &Toolchain,
-        _krate: &Crate,
+        ex: &Experiment,
+        toolchain: &Toolchain,
+        krate: &Crate,
     ) -> Fallible<Option<TestResult>> {
-        // TODO: not yet implemented
-        Ok(None)
+        // Only the banked baseline results are known by the server before the crate is built
+        if ex.reuse_baseline && *toolchain == ex.toolchains[0] {
+            let sandbox_image = self.sandbox_image.as_ref().map(|image| image.as_str());
+            self.api.banked_result(ex, krate, sandbox_image)
+        } else {
+            Ok(None)
+        }
     }
 
     fn update_crate_version(&self, _ex: &Experiment, old: &Crate, new: &Crate) -> Fallible<()> {
@@ -43,6 +104,32 @@ impl<'a> WriteResults for ResultsUploader<'a> {
         Ok(())
     }
 
+    fn record_dependency_graph(
+        &self,
+        _ex: &Experiment,
+        toolchain: &Toolchain,
+        krate: &Crate,
+        graph: &DependencyGraph,
+    ) -> Fallible<()> {
+        self.dependency_graphs
+            .lock()
+            .unwrap()
+            .insert((krate.clone(), toolchain.clone()), graph.clone());
+        Ok(())
+    }
+
+    fn learned_timeout(
+        &self,
+        ex: &Experiment,
+        krate: &Crate,
+        _config: &Config,
+    ) -> Fallible<Option<Duration>> {
+        Ok(self
+            .api
+            .learned_timeout(ex, krate)?
+            .map(Duration::from_secs))
+    }
+
     fn record_result<F>(
         &self,
         ex: &Experiment,
@@ -57,8 +144,21 @@ impl<'a> WriteResults for ResultsUploader<'a> {
         F: FnOnce() -> Fallible<TestResult>,
     {
         let storage = existing_logs.unwrap_or_else(|| LogStorage::from(config));
-        let result = logging::capture(&storage, f)?;
-        let output = storage.to_string();
+        let redactor = Redactor::for_config(config)?;
+        let interval = config.server.live_logs.every_secs;
+        let start = Instant::now();
+        let result =
+            self.stream_log(ex, toolchain, krate, &storage, &redactor, interval, || {
+                logging::capture(&storage, f)
+            })?;
+        let duration = start.elapsed();
+        let output = log_output(&storage, config);
+        let output = redactor.redact(&output).into_owned();
+        let dependency_graph = self
+            .dependency_graphs
+            .lock()
+            .unwrap()
+            .remove(&(krate.clone(), toolchain.clone()));
 
         let mut updated = None;
         let mut new_version = None;
@@ -87,9 +187,25 @@ impl<'a> WriteResults for ResultsUploader<'a> {
             toolchain,
             output.as_bytes(),
             &result,
+            result.memory_limit(config, krate),
+            SandboxCounters::from_log(&output).as_ref(),
+            self.sandbox_image.as_ref().map(|image| image.as_str()),
+            dependency_graph.as_ref(),
+            Some(duration.as_secs()),
             new_version.map(|new| (krate, new)),
         )?;
 
         Ok(result)
     }
 }
+
+impl<'a> RecordSandboxImage for ResultsUploader<'a> {
+    fn for_sandbox_image(&self, sandbox_image: Option<String>) -> Self {
+        ResultsUploader {
+            api: self.api,
+            versions: self.versions.clone(),
+            dependency_graphs: self.dependency_graphs.clone(),
+            sandbox_image,
+        }
+    }
+}
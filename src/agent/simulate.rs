@@ -0,0 +1,177 @@
+use crate::agent::api::AgentApi;
+use crate::agent::Capabilities;
+use crate::prelude::*;
+use crate::results::{FailureReason, TestResult};
+use crate::utils;
+use crossbeam_utils::thread::scope;
+use rand::{thread_rng, Rng};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Behavior of the fake agents started by `crater agent --simulate`.
+pub struct Simulation {
+    /// Number of fake agents to register.
+    pub agents: usize,
+    /// Range of the time spent "building" each crate on each toolchain, in milliseconds.
+    pub latency: (u64, u64),
+    /// Probability of a result being a build failure.
+    pub failure_rate: f64,
+    /// Probability of a result being an error.
+    pub error_rate: f64,
+    /// Probability of an agent crashing after processing a crate: a crashed agent stops sending
+    /// heartbeats long enough for its leases to expire, and then starts again.
+    pub crash_rate: f64,
+}
+
+impl Simulation {
+    fn latency(&self) -> Duration {
+        let (min, max) = self.latency;
+        Duration::from_millis(thread_rng().gen_range(min, max.max(min) + 1))
+    }
+
+    fn result(&self) -> TestResult {
+        let roll: f64 = thread_rng().gen();
+        if roll < self.error_rate {
+            TestResult::Error
+        } else if roll < self.error_rate + self.failure_rate {
+            TestResult::BuildFail(FailureReason::Unknown)
+        } else {
+            TestResult::TestPass
+        }
+    }
+
+    fn crashes(&self) -> bool {
+        thread_rng().gen::<f64>() < self.crash_rate
+    }
+}
+
+/// Register the fake agents and start producing synthetic results, to load-test the server
+/// without a real fleet. The n-th agent authenticates with `<token>-<n>` (starting from 1), so
+/// those tokens need to be present in the server's `tokens.toml`.
+pub fn run(url: &str, token: &str, caps: &Capabilities, simulation: &Simulation) -> Fallible<()> {
+    scope(|scope| -> Fallible<()> {
+        for id in 1..=simulation.agents {
+            let token = format!("{}-{}", token, id);
+            scope
+                .builder()
+                .name(format!("simulated-agent-{}", id))
+                .spawn(move || {
+                    if let Err(err) = run_agent(url, &token, caps, simulation) {
+                        utils::report_failure(&err);
+                    }
+                })?;
+        }
+        Ok(())
+    })
+}
+
+fn run_agent(url: &str, token: &str, caps: &Capabilities, simulation: &Simulation) -> Fallible<()> {
+    let api = AgentApi::new(url, token);
+    let config = api.config(caps)?;
+    let name = config.agent_name;
+    let lease_timeout = config.crater_config.server.distributed.lease_timeout;
+    info!("registered simulated agent {}", name);
+
+    let crashed = AtomicBool::new(false);
+    let stopped = AtomicBool::new(false);
+    scope(|scope| {
+        scope.spawn(|| {
+            while !stopped.load(Ordering::SeqCst) {
+                if !crashed.load(Ordering::SeqCst) {
+                    if let Err(err) = api.heartbeat() {
+                        utils::report_failure(&err);
+                    }
+                }
+                thread::sleep(HEARTBEAT_INTERVAL);
+            }
+        });
+
+        let res = simulate_experiments(&api, &name, simulation, lease_timeout, &crashed);
+        stopped.store(true, Ordering::SeqCst);
+        res
+    })
+}
+
+fn simulate_experiments(
+    api: &AgentApi,
+    name: &str,
+    simulation: &Simulation,
+    lease_timeout: u64,
+    crashed: &AtomicBool,
+) -> Fallible<()> {
+    loop {
+        let (ex, crates) = api.next_experiment()?;
+        info!(
+            "{}: simulating {} crates of {}",
+            name,
+            crates.len(),
+            ex.name
+        );
+
+        for krate in &crates {
+            for tc in ex.tested_toolchains() {
+                thread::sleep(simulation.latency());
+                let result = simulation.result();
+                let log = format!("simulated result by {}: {}", name, result);
+                api.record_progress(
+                    &ex,
+                    krate,
+                    tc,
+                    log.as_bytes(),
+                    &result,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                )?;
+            }
+
+            if simulation.crashes() {
+                warn!("{}: simulating a crash", name);
+                crashed.store(true, Ordering::SeqCst);
+                thread::sleep(Duration::from_secs(lease_timeout) + HEARTBEAT_INTERVAL);
+                crashed.store(false, Ordering::SeqCst);
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Simulation;
+    use crate::results::{FailureReason, TestResult};
+    use std::time::Duration;
+
+    fn simulation(failure_rate: f64, error_rate: f64) -> Simulation {
+        Simulation {
+            agents: 1,
+            latency: (10, 20),
+            failure_rate,
+            error_rate,
+            crash_rate: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_simulated_results() {
+        assert_eq!(simulation(0.0, 0.0).result(), TestResult::TestPass);
+        assert_eq!(
+            simulation(1.0, 0.0).result(),
+            TestResult::BuildFail(FailureReason::Unknown)
+        );
+        assert_eq!(simulation(0.0, 1.0).result(), TestResult::Error);
+        assert!(!simulation(0.0, 0.0).crashes());
+
+        for _ in 0..100 {
+            let latency = simulation(0.0, 0.0).latency();
+            assert!(latency >= Duration::from_millis(10));
+            assert!(latency <= Duration::from_millis(20));
+        }
+    }
+}
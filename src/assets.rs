@@ -61,6 +61,7 @@ load_files! {
         "ui/layout.html",
 
         "ui/agents.html",
+        "ui/heatmap.html",
 
         "ui/queue.html",
         "ui/experiment.html",
@@ -71,6 +72,7 @@ load_files! {
         "report/layout.html",
         "report/downloads.html",
         "report/results.html",
+        "report/survey.html",
     ],
     assets: [
         "ui.css" => mime::TEXT_CSS,
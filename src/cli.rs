@@ -13,19 +13,24 @@ use crater::actions::{self, Action, ActionsCtx};
 use crater::agent::{self, Capabilities};
 use crater::config::Config;
 use crater::crates::Crate;
-use crater::db::Database;
-use crater::experiments::{Assignee, CapLints, DeferredCrateSelect, Experiment, Mode, Status};
+use crater::db::{Database, QueryUtils};
+use crater::experiments::{
+    Assignee, CapLints, DeferredCrateSelect, Experiment, FailureThreshold, Mode, Status, TestRunner,
+};
 use crater::report;
 use crater::results::{DatabaseDB, DeleteResults};
 use crater::runner;
 use crater::server;
 use crater::toolchain::Toolchain;
+use crater::utils::workspace::ContainerRuntime;
 use failure::{bail, Error, Fallible};
-use rustwide::{cmd::SandboxImage, Workspace, WorkspaceBuilder};
+use log::info;
+use rustwide::Workspace;
 use std::collections::HashSet;
+use std::fs::{self, read_to_string};
 use std::path::PathBuf;
 use std::str::FromStr;
-use std::time::Duration;
+use std::time::Instant;
 use structopt::clap::AppSettings;
 
 // An experiment name
@@ -104,8 +109,8 @@ pub enum Crater {
         ex: Ex,
         #[structopt(name = "tc-1")]
         tc1: Toolchain,
-        #[structopt(name = "tc-2")]
-        tc2: Toolchain,
+        #[structopt(name = "tc-2", required_unless = "survey")]
+        tc2: Option<Toolchain>,
         #[structopt(
             name = "mode",
             long = "mode",
@@ -121,8 +126,13 @@ pub enum Crater {
             help = "The set of crates on which the experiment will run.",
             long_help = "The set of crates on which the experiment will run.\n\n\
                          This can be one of (full, demo, random-{d}, top-{d}, local) \
-                         where {d} is a positive integer, or \"list:\" followed \
-                         by a comma-separated list of crates.",
+                         where {d} is a positive integer, \"list:\" followed \
+                         by a comma-separated list of crates, \"rdeps:\" followed by \
+                         the name of a crate (and optionally by \":{d}\") to select \
+                         the most popular crates depending on it, \"expr:\" followed \
+                         by an expression on the crates.io metadata of the crates (like \
+                         \"expr:downloads > 10000 && depends_on('tokio')\"), or \"file:\" \
+                         followed by the path of a TOML or JSON file listing the crates.",
             raw(default_value = "\"demo\"",)
         )]
         crates: DeferredCrateSelect,
@@ -143,6 +153,128 @@ pub enum Crater {
         assign: Option<Assignee>,
         #[structopt(name = "requirement", long = "requirement")]
         requirement: Option<String>,
+        #[structopt(
+            name = "cargo-config",
+            long = "cargo-config",
+            parse(from_os_str),
+            help = "File containing the cargo configuration used to build every crate."
+        )]
+        cargo_config: Option<PathBuf>,
+        #[structopt(
+            name = "expect-regression",
+            long = "expect-regression",
+            help = "Regex matching the logs of regressions caused on purpose by the experiment.",
+            raw(number_of_values = "1")
+        )]
+        expected_regressions: Vec<String>,
+        #[structopt(
+            name = "env",
+            long = "env",
+            help = "Environment variable (NAME=value) changing the behavior of the toolchains.",
+            long_help = "Environment variable (NAME=value) changing the behavior of the \
+                         toolchains, set in every build.\n\n\
+                         The allowed variables are RUST_BACKTRACE, RUSTC_BOOTSTRAP, \
+                         RUST_MIN_STACK, RUST_TEST_THREADS and CARGO_INCREMENTAL.",
+            raw(number_of_values = "1")
+        )]
+        env: Vec<String>,
+        #[structopt(
+            name = "deny-lint",
+            long = "deny-lint",
+            help = "Lint failing the build when emitted by the crate, even if lints are capped.",
+            long_help = "Lint failing the build when emitted by the crate, even if lints are \
+                         capped.\n\n\
+                         Combined with `--cap-lints=warn` this measures the impact of a single \
+                         lint, without the noise of the lints denied by the crates themselves.",
+            raw(number_of_values = "1")
+        )]
+        deny_lints: Vec<String>,
+        #[structopt(
+            name = "cargo-update",
+            long = "cargo-update",
+            help = "Update the dependencies of every crate before building it.",
+            long_help = "Update the dependencies of every crate before building it.\n\n\
+                         The lockfiles of the crates are ignored, and every dependency is \
+                         updated to its latest compatible version with `cargo update`."
+        )]
+        cargo_update: bool,
+        #[structopt(
+            name = "failure-threshold",
+            long = "failure-threshold",
+            help = "Regressions after which the experiment is paused (like `50` or `5%`).",
+            long_help = "Regressions after which the experiment is paused (like `50` or `5%`).\n\n\
+                         Percentages are relative to the crates compared so far, and are only \
+                         checked after the first 100 crates. The threshold is only checked by \
+                         the server, as results are received from the agents."
+        )]
+        failure_threshold: Option<FailureThreshold>,
+        #[structopt(
+            name = "reuse-baseline",
+            long = "reuse-baseline",
+            help = "Reuse the banked results of the start toolchain instead of rebuilding them.",
+            long_help = "Reuse the banked results of the start toolchain instead of rebuilding \
+                         them.\n\n\
+                         Only toolchains always referring to the same compiler are banked: stable \
+                         releases (like `1.70.0`), dated nightlies and betas (like \
+                         `nightly-2023-06-01`) and CI builds of a commit (like `master#<sha>` or \
+                         `try#<sha>`). Their results are banked when an experiment without custom \
+                         environment variables, cargo configuration, denied lints or dependency \
+                         updates is completed. Crates without a banked result for the same mode \
+                         and lints cap are built as usual."
+        )]
+        reuse_baseline: bool,
+        #[structopt(
+            name = "canary",
+            long = "canary",
+            help = "Build a small set of well-known crates before queuing the other ones.",
+            long_help = "Build a small set of well-known crates before queuing the other ones.\n\n\
+                         The rest of the crates is queued only if the toolchains work on the \
+                         canary crates. Otherwise the experiment is paused, waiting for a human \
+                         to resume it. The canary is only checked by the server, as results are \
+                         received from the agents."
+        )]
+        canary: bool,
+        #[structopt(
+            name = "test-runner",
+            long = "test-runner",
+            help = "Tool running the tests of the crates.",
+            raw(
+                default_value = "TestRunner::Cargo.to_str()",
+                possible_values = "TestRunner::possible_values()"
+            )
+        )]
+        test_runner: TestRunner,
+        #[structopt(
+            name = "namespace",
+            long = "namespace",
+            help = "Namespace of the team owning the experiment, counted against its quota."
+        )]
+        namespace: Option<String>,
+        #[structopt(
+            name = "survey",
+            long = "survey",
+            help = "Measure the ecosystem with a single toolchain, without a baseline.",
+            long_help = "Measure the ecosystem with a single toolchain, without a baseline.\n\n\
+                         Only the first toolchain is passed, and the report shows statistics \
+                         about the results of the crates instead of regressions."
+        )]
+        survey: bool,
+        #[structopt(
+            name = "parent",
+            long = "parent",
+            help = "Only build the crates that changed since this experiment ran.",
+            long_help = "Only build the crates that changed since this experiment ran.\n\n\
+                         Registry crates are built again if their version changed, and git \
+                         repositories if their HEAD moved: the results of the other crates are \
+                         copied from the parent experiment, which must be completed."
+        )]
+        parent: Option<String>,
+        #[structopt(
+            name = "sandbox-image",
+            long = "sandbox-image",
+            help = "Sandbox image the crates are built in, instead of the one of the agents."
+        )]
+        sandbox_image: Option<String>,
     },
 
     #[structopt(name = "edit", about = "edit an experiment configuration")]
@@ -165,8 +297,13 @@ pub enum Crater {
             help = "The set of crates on which the experiment will run.",
             long_help = "The set of crates on which the experiment will run.\n\n\
                          This can be one of (full, demo, random-{d}, top-{d}, local) \
-                         where {d} is a positive integer, or \"list:\" followed \
-                         by a comma-separated list of crates."
+                         where {d} is a positive integer, \"list:\" followed \
+                         by a comma-separated list of crates, \"rdeps:\" followed by \
+                         the name of a crate (and optionally by \":{d}\") to select \
+                         the most popular crates depending on it, \"expr:\" followed \
+                         by an expression on the crates.io metadata of the crates (like \
+                         \"expr:downloads > 10000 && depends_on('tokio')\"), or \"file:\" \
+                         followed by the path of a TOML or JSON file listing the crates."
         )]
         crates: Option<DeferredCrateSelect>,
         #[structopt(
@@ -193,6 +330,158 @@ pub enum Crater {
         assign: Option<Assignee>,
         #[structopt(name = "requirement", long = "requirement")]
         requirement: Option<String>,
+        #[structopt(
+            name = "cargo-config",
+            long = "cargo-config",
+            parse(from_os_str),
+            help = "File containing the cargo configuration used to build every crate.",
+            long_help = "File containing the cargo configuration used to build every crate.\n\n\
+                         An empty file removes the cargo configuration of the experiment."
+        )]
+        cargo_config: Option<PathBuf>,
+        #[structopt(
+            name = "env",
+            long = "env",
+            help = "Environment variable (NAME=value) changing the behavior of the toolchains.",
+            long_help = "Environment variable (NAME=value) changing the behavior of the \
+                         toolchains, set in every build.\n\n\
+                         The variables provided replace the whole environment of the experiment.",
+            raw(number_of_values = "1"),
+            conflicts_with = "clear-env"
+        )]
+        env: Vec<String>,
+        #[structopt(
+            name = "clear-env",
+            long = "clear-env",
+            help = "Remove all the environment variables of the experiment."
+        )]
+        clear_env: bool,
+        #[structopt(
+            name = "deny-lint",
+            long = "deny-lint",
+            help = "Lint failing the build when emitted by the crate, even if lints are capped.",
+            long_help = "Lint failing the build when emitted by the crate, even if lints are \
+                         capped.\n\n\
+                         The lints provided replace all the denied lints of the experiment.",
+            raw(number_of_values = "1"),
+            conflicts_with = "clear-deny-lints"
+        )]
+        deny_lints: Vec<String>,
+        #[structopt(
+            name = "clear-deny-lints",
+            long = "clear-deny-lints",
+            help = "Remove all the denied lints of the experiment."
+        )]
+        clear_deny_lints: bool,
+        #[structopt(
+            name = "cargo-update",
+            long = "cargo-update",
+            help = "Update the dependencies of every crate before building it.",
+            conflicts_with = "no-cargo-update"
+        )]
+        cargo_update: bool,
+        #[structopt(
+            name = "no-cargo-update",
+            long = "no-cargo-update",
+            help = "Build the crates with the dependencies in their lockfiles.",
+            conflicts_with = "cargo-update"
+        )]
+        no_cargo_update: bool,
+        #[structopt(
+            name = "failure-threshold",
+            long = "failure-threshold",
+            help = "Regressions after which the experiment is paused (like `50` or `5%`).",
+            conflicts_with = "clear-failure-threshold"
+        )]
+        failure_threshold: Option<FailureThreshold>,
+        #[structopt(
+            name = "clear-failure-threshold",
+            long = "clear-failure-threshold",
+            help = "Never pause the experiment, regardless of the regressions."
+        )]
+        clear_failure_threshold: bool,
+        #[structopt(
+            name = "reuse-baseline",
+            long = "reuse-baseline",
+            help = "Reuse the banked results of the start toolchain instead of rebuilding them.",
+            conflicts_with = "no-reuse-baseline"
+        )]
+        reuse_baseline: bool,
+        #[structopt(
+            name = "no-reuse-baseline",
+            long = "no-reuse-baseline",
+            help = "Build the crates with the start toolchain even if results are banked.",
+            conflicts_with = "reuse-baseline"
+        )]
+        no_reuse_baseline: bool,
+        #[structopt(
+            name = "canary",
+            long = "canary",
+            help = "Build a small set of well-known crates before queuing the other ones.",
+            conflicts_with = "no-canary"
+        )]
+        canary: bool,
+        #[structopt(
+            name = "no-canary",
+            long = "no-canary",
+            help = "Queue all the crates at once.",
+            conflicts_with = "canary"
+        )]
+        no_canary: bool,
+        #[structopt(
+            name = "test-runner",
+            long = "test-runner",
+            help = "Tool running the tests of the crates.",
+            raw(possible_values = "TestRunner::possible_values()")
+        )]
+        test_runner: Option<TestRunner>,
+        #[structopt(
+            name = "sandbox-image",
+            long = "sandbox-image",
+            help = "Sandbox image the crates are built in, instead of the one of the agents.",
+            conflicts_with = "clear-sandbox-image"
+        )]
+        sandbox_image: Option<String>,
+        #[structopt(
+            name = "clear-sandbox-image",
+            long = "clear-sandbox-image",
+            help = "Build the crates in the sandbox image of the agents."
+        )]
+        clear_sandbox_image: bool,
+    },
+
+    #[structopt(
+        name = "expect-regression",
+        about = "mark regressions matching a pattern as expected, even while running"
+    )]
+    ExpectRegression {
+        #[structopt(name = "name")]
+        name: String,
+        #[structopt(name = "pattern", raw(required = "true"))]
+        patterns: Vec<String>,
+    },
+
+    #[structopt(
+        name = "clone-experiment",
+        about = "define an experiment based on an existing one"
+    )]
+    CloneExperiment {
+        #[structopt(name = "name")]
+        name: String,
+        #[structopt(name = "new-name", long = "new-name")]
+        new_name: String,
+        #[structopt(
+            name = "override",
+            long = "override",
+            help = "Change a field of the cloned experiment, in the key=value format.",
+            long_help = "Change a field of the cloned experiment, in the key=value format.\n\n\
+                         The supported keys are the same ones accepted by the bot: start, end, \
+                         mode, crates, cap-lints, p, ignore-blacklist, assign, requirement, \
+                         cargo-update, failure-threshold, reuse-baseline, canary, test-runner and \
+                         sandbox-image.",
+            raw(number_of_values = "1")
+        )]
+        overrides: Vec<String>,
     },
 
     #[structopt(name = "delete-ex", about = "delete shared data for experiment")]
@@ -223,6 +512,17 @@ pub enum Crater {
         krate: Crate,
     },
 
+    #[structopt(
+        name = "invalidate-agent-results",
+        about = "remove the results of an agent from an experiment and queue its crates again"
+    )]
+    InvalidateAgentResults {
+        #[structopt(name = "experiment")]
+        ex: Ex,
+        #[structopt(name = "agent")]
+        agent: String,
+    },
+
     #[structopt(name = "run-graph", about = "run a parallelized experiment")]
     RunGraph {
         #[structopt(name = "experiment", long = "ex", default_value = "default")]
@@ -233,6 +533,12 @@ pub enum Crater {
         docker_env: Option<String>,
         #[structopt(name = "fast-workspace-init", long = "fast-workspace-init")]
         fast_workspace_init: bool,
+        #[structopt(
+            name = "offline-workspace-init",
+            long = "offline-workspace-init",
+            help = "only validate the existing workspace instead of downloading missing tools"
+        )]
+        offline_workspace_init: bool,
     },
 
     #[structopt(name = "gen-report", about = "generate the experiment report")]
@@ -247,6 +553,63 @@ pub enum Crater {
         output_templates: bool,
     },
 
+    #[structopt(
+        name = "local",
+        about = "define, run and report a small experiment in a single process"
+    )]
+    Local {
+        #[structopt(name = "experiment", long = "ex", default_value = "local")]
+        ex: Ex,
+        #[structopt(name = "tc-1")]
+        tc1: Toolchain,
+        #[structopt(name = "tc-2")]
+        tc2: Toolchain,
+        #[structopt(
+            name = "mode",
+            long = "mode",
+            raw(
+                default_value = "Mode::BuildAndTest.to_str()",
+                possible_values = "Mode::possible_values()"
+            )
+        )]
+        mode: Mode,
+        #[structopt(
+            name = "crate-select",
+            long = "crate-select",
+            help = "The set of crates on which the experiment will run.",
+            raw(default_value = "\"top-50\"",)
+        )]
+        crates: DeferredCrateSelect,
+        #[structopt(
+            name = "level",
+            long = "cap-lints",
+            raw(
+                default_value = "CapLints::Forbid.to_str()",
+                possible_values = "CapLints::possible_values()"
+            )
+        )]
+        cap_lints: CapLints,
+        #[structopt(name = "threads", short = "t", long = "threads", default_value = "1")]
+        threads: usize,
+        #[structopt(name = "docker-env", long = "docker-env")]
+        docker_env: Option<String>,
+        #[structopt(name = "fast-workspace-init", long = "fast-workspace-init")]
+        fast_workspace_init: bool,
+        #[structopt(
+            name = "destination",
+            long = "dest",
+            default_value = "work/local-report",
+            help = "Directory the report is written to."
+        )]
+        dest: Dest,
+        #[structopt(
+            name = "replace",
+            long = "replace",
+            help = "Delete an existing experiment with the same name before starting."
+        )]
+        replace: bool,
+    },
+
     #[structopt(name = "publish-report", about = "publish the experiment report to S3")]
     PublishReport {
         #[structopt(
@@ -279,6 +642,12 @@ pub enum Crater {
         docker_env: Option<String>,
         #[structopt(name = "fast-workspace-init", long = "fast-workspace-init")]
         fast_workspace_init: bool,
+        #[structopt(
+            name = "offline-workspace-init",
+            long = "offline-workspace-init",
+            help = "only validate the existing workspace instead of downloading missing tools"
+        )]
+        offline_workspace_init: bool,
         #[structopt(
             name = "capabilities",
             help = "Registers additional capabilities for this agent.",
@@ -295,6 +664,52 @@ pub enum Crater {
             help = "Disables the default capabilities for this platform."
         )]
         no_default_capabilities: bool,
+        #[structopt(
+            name = "simulate",
+            long = "simulate",
+            help = "Register N fake agents producing synthetic results instead of building crates.",
+            long_help = "Register N fake agents producing synthetic results instead of building \
+                         crates, to load-test the server.\n\n \
+                         The n-th agent authenticates with the `<token>-<n>` token (starting \
+                         from 1), which must be configured in the server's tokens.toml."
+        )]
+        simulate: Option<usize>,
+        #[structopt(
+            name = "simulate-min-latency",
+            long = "simulate-min-latency",
+            default_value = "100",
+            help = "Minimum time spent on each simulated build, in milliseconds."
+        )]
+        simulate_min_latency: u64,
+        #[structopt(
+            name = "simulate-max-latency",
+            long = "simulate-max-latency",
+            default_value = "1000",
+            help = "Maximum time spent on each simulated build, in milliseconds."
+        )]
+        simulate_max_latency: u64,
+        #[structopt(
+            name = "simulate-failure-rate",
+            long = "simulate-failure-rate",
+            default_value = "0.1",
+            help = "Probability of a simulated build failing."
+        )]
+        simulate_failure_rate: f64,
+        #[structopt(
+            name = "simulate-error-rate",
+            long = "simulate-error-rate",
+            default_value = "0.01",
+            help = "Probability of a simulated build returning an error."
+        )]
+        simulate_error_rate: f64,
+        #[structopt(
+            name = "simulate-crash-rate",
+            long = "simulate-crash-rate",
+            default_value = "0",
+            help = "Probability of a simulated agent crashing after each crate, letting its \
+                    leases expire."
+        )]
+        simulate_crash_rate: f64,
     },
 
     #[structopt(
@@ -308,6 +723,163 @@ pub enum Crater {
         ex: Ex,
     },
 
+    #[structopt(
+        name = "logs",
+        about = "save the logs of a crate on both toolchains of an experiment"
+    )]
+    Logs {
+        #[structopt(name = "experiment")]
+        ex: Ex,
+        #[structopt(name = "crate")]
+        krate: Crate,
+        #[structopt(
+            name = "dest",
+            parse(from_os_str),
+            help = "Directory the logs are saved into, or file path with --tarball.",
+            default_value = "."
+        )]
+        dest: PathBuf,
+        #[structopt(
+            name = "tarball",
+            long = "tarball",
+            help = "Save the logs as a .tar.gz"
+        )]
+        tarball: bool,
+    },
+
+    #[structopt(
+        name = "diff-experiments",
+        about = "list the crates whose classification changed between two experiments"
+    )]
+    DiffExperiments {
+        #[structopt(name = "before")]
+        before: Ex,
+        #[structopt(name = "after")]
+        after: Ex,
+        #[structopt(name = "json", long = "json", help = "Print the diff as JSON.")]
+        json: bool,
+    },
+
+    #[structopt(
+        name = "regressed-dependents",
+        about = "list the regressed crates depending, even transitively, on a package"
+    )]
+    RegressedDependents {
+        #[structopt(name = "experiment")]
+        ex: Ex,
+        #[structopt(name = "package")]
+        package: String,
+        #[structopt(
+            name = "version",
+            long = "version",
+            help = "only count the crates depending on this exact version of the package"
+        )]
+        version: Option<String>,
+    },
+
+    #[structopt(
+        name = "prepare-crate",
+        about = "fetch and patch a crate into a directory without building it"
+    )]
+    PrepareCrate {
+        #[structopt(
+            name = "toolchain",
+            long = "toolchain",
+            short = "t",
+            default_value = "stable"
+        )]
+        tc: Toolchain,
+        #[structopt(name = "crate")]
+        krate: Crate,
+        #[structopt(name = "dest", parse(from_os_str))]
+        dest: PathBuf,
+        #[structopt(name = "fast-workspace-init", long = "fast-workspace-init")]
+        fast_workspace_init: bool,
+        #[structopt(
+            name = "offline-workspace-init",
+            long = "offline-workspace-init",
+            help = "only validate the existing workspace instead of downloading missing tools"
+        )]
+        offline_workspace_init: bool,
+    },
+
+    #[structopt(
+        name = "triage",
+        about = "classify the regressions of an experiment from the terminal"
+    )]
+    Triage {
+        #[structopt(name = "experiment")]
+        ex: Ex,
+        #[structopt(name = "url", long = "url", help = "URL of the Crater server")]
+        url: String,
+        #[structopt(name = "token", long = "token")]
+        token: String,
+        #[structopt(
+            name = "all",
+            long = "all",
+            help = "Also show the regressions already classified."
+        )]
+        all: bool,
+    },
+
+    #[structopt(
+        name = "disk-usage",
+        about = "show the disk space used by the toolchains, caches and build directories"
+    )]
+    DiskUsage {
+        #[structopt(
+            name = "all",
+            long = "all",
+            help = "Show the size of every item instead of only the biggest ones."
+        )]
+        all: bool,
+    },
+
+    #[structopt(
+        name = "build-sandbox-image",
+        about = "build a sandbox image deriving from the default one with extra system packages"
+    )]
+    BuildSandboxImage {
+        #[structopt(name = "tag")]
+        tag: String,
+        #[structopt(
+            name = "package",
+            long = "package",
+            help = "System package to install with apt, can be passed multiple times."
+        )]
+        packages: Vec<String>,
+        #[structopt(
+            name = "dockerfile",
+            long = "dockerfile",
+            parse(from_os_str),
+            help = "File with more Dockerfile instructions to run after installing the packages."
+        )]
+        dockerfile: Option<PathBuf>,
+        #[structopt(
+            name = "base",
+            long = "base",
+            help = "Image to derive from instead of the default sandbox image."
+        )]
+        base: Option<String>,
+    },
+
+    #[structopt(
+        name = "sandbox-run-as-root",
+        about = "run a setup command as root in the sandbox image and tag the result"
+    )]
+    SandboxRunAsRoot {
+        #[structopt(name = "tag")]
+        tag: String,
+        #[structopt(
+            name = "base",
+            long = "base",
+            help = "Image to run the command in instead of the default sandbox image."
+        )]
+        base: Option<String>,
+        #[structopt(name = "command", raw(required = "true", last = "true"))]
+        command: Vec<String>,
+    },
+
     #[structopt(
         name = "check-config",
         about = "check if the config.toml file is valid"
@@ -325,7 +897,7 @@ impl Crater {
                 let mut lists: HashSet<_> = lists.iter().map(|s| s.as_str()).collect();
 
                 let config = Config::load()?;
-                let db = Database::open()?;
+                let db = Database::open(&config)?;
                 let ctx = ActionsCtx::new(&db, &config);
 
                 let action = if lists.is_empty() {
@@ -346,7 +918,7 @@ impl Crater {
             }
             Crater::PrepareLocal => {
                 let config = Config::load()?;
-                let db = Database::open()?;
+                let db = Database::open(&config)?;
                 let ctx = ActionsCtx::new(&db, &config);
                 actions::UpdateLists::default().apply(&ctx)?;
             }
@@ -361,14 +933,35 @@ impl Crater {
                 ref ignore_blacklist,
                 ref assign,
                 ref requirement,
+                ref cargo_config,
+                ref expected_regressions,
+                ref env,
+                ref deny_lints,
+                cargo_update,
+                failure_threshold,
+                reuse_baseline,
+                canary,
+                test_runner,
+                ref namespace,
+                survey,
+                ref parent,
+                ref sandbox_image,
             } => {
                 let config = Config::load()?;
-                let db = Database::open()?;
+                let db = Database::open(&config)?;
                 let ctx = ActionsCtx::new(&db, &config);
 
+                // Survey experiments store their only toolchain in both slots
+                let tc2 = match (tc2, survey) {
+                    (Some(_), true) => bail!("survey experiments only have one toolchain"),
+                    (Some(tc2), false) => tc2.clone(),
+                    (None, true) => tc1.clone(),
+                    (None, false) => bail!("missing the second toolchain"),
+                };
+
                 actions::CreateExperiment {
                     name: ex.0.clone(),
-                    toolchains: [tc1.clone(), tc2.clone()],
+                    toolchains: [tc1.clone(), tc2],
                     mode: *mode,
                     crates: crates.clone().resolve()?,
                     cap_lints: *cap_lints,
@@ -377,6 +970,19 @@ impl Crater {
                     ignore_blacklist: *ignore_blacklist,
                     assign: assign.clone(),
                     requirement: requirement.clone(),
+                    cargo_config: cargo_config.as_ref().map(read_to_string).transpose()?,
+                    expected_regressions: expected_regressions.clone(),
+                    env: actions::parse_env(env)?,
+                    deny_lints: deny_lints.clone(),
+                    cargo_update,
+                    failure_threshold,
+                    reuse_baseline,
+                    canary,
+                    test_runner,
+                    namespace: namespace.clone(),
+                    survey,
+                    parent: parent.clone(),
+                    sandbox_image: sandbox_image.clone(),
                 }
                 .apply(&ctx)?;
             }
@@ -392,9 +998,25 @@ impl Crater {
                 ref no_ignore_blacklist,
                 ref assign,
                 ref requirement,
+                ref cargo_config,
+                ref env,
+                clear_env,
+                ref deny_lints,
+                clear_deny_lints,
+                cargo_update,
+                no_cargo_update,
+                failure_threshold,
+                clear_failure_threshold,
+                reuse_baseline,
+                no_reuse_baseline,
+                canary,
+                no_canary,
+                test_runner,
+                ref sandbox_image,
+                clear_sandbox_image,
             } => {
                 let config = Config::load()?;
-                let db = Database::open()?;
+                let db = Database::open(&config)?;
                 let ctx = ActionsCtx::new(&db, &config);
 
                 let ignore_blacklist = if *ignore_blacklist {
@@ -404,6 +1026,27 @@ impl Crater {
                 } else {
                     None
                 };
+                let cargo_update = if cargo_update {
+                    Some(true)
+                } else if no_cargo_update {
+                    Some(false)
+                } else {
+                    None
+                };
+                let reuse_baseline = if reuse_baseline {
+                    Some(true)
+                } else if no_reuse_baseline {
+                    Some(false)
+                } else {
+                    None
+                };
+                let canary = if canary {
+                    Some(true)
+                } else if no_canary {
+                    Some(false)
+                } else {
+                    None
+                };
 
                 actions::EditExperiment {
                     name: name.clone(),
@@ -415,18 +1058,91 @@ impl Crater {
                     ignore_blacklist,
                     assign: assign.clone(),
                     requirement: requirement.clone(),
+                    cargo_config: cargo_config.as_ref().map(read_to_string).transpose()?,
+                    env: if clear_env || !env.is_empty() {
+                        Some(actions::parse_env(env)?)
+                    } else {
+                        None
+                    },
+                    deny_lints: if clear_deny_lints || !deny_lints.is_empty() {
+                        Some(deny_lints.clone())
+                    } else {
+                        None
+                    },
+                    cargo_update,
+                    failure_threshold: if clear_failure_threshold {
+                        Some(None)
+                    } else {
+                        failure_threshold.map(Some)
+                    },
+                    reuse_baseline,
+                    canary,
+                    test_runner,
+                    sandbox_image: if clear_sandbox_image {
+                        Some(None)
+                    } else {
+                        sandbox_image.clone().map(Some)
+                    },
                 }
                 .apply(&ctx)?;
             }
+            Crater::ExpectRegression {
+                ref name,
+                ref patterns,
+            } => {
+                let config = Config::load()?;
+                let db = Database::open(&config)?;
+                let ctx = ActionsCtx::new(&db, &config);
+
+                actions::ExpectRegressions {
+                    name: name.clone(),
+                    patterns: patterns.clone(),
+                }
+                .apply(&ctx)?;
+            }
+            Crater::CloneExperiment {
+                ref name,
+                ref new_name,
+                ref overrides,
+            } => {
+                let config = Config::load()?;
+                let db = Database::open(&config)?;
+                let ctx = ActionsCtx::new(&db, &config);
+
+                let mut action = actions::CloneExperiment {
+                    name: name.clone(),
+                    new_name: new_name.clone(),
+                    toolchains: [None, None],
+                    crates: None,
+                    mode: None,
+                    cap_lints: None,
+                    priority: None,
+                    github_issue: None,
+                    ignore_blacklist: None,
+                    assign: None,
+                    requirement: None,
+                    cargo_update: None,
+                    failure_threshold: None,
+                    reuse_baseline: None,
+                    canary: None,
+                    test_runner: None,
+                    sandbox_image: None,
+                };
+                for item in overrides {
+                    action.set_override(item)?;
+                }
+                action.apply(&ctx)?;
+            }
             Crater::DeleteEx { ref ex } => {
                 let config = Config::load()?;
-                let db = Database::open()?;
+                let db = Database::open(&config)?;
                 let ctx = ActionsCtx::new(&db, &config);
 
                 actions::DeleteExperiment { name: ex.0.clone() }.apply(&ctx)?;
             }
             Crater::DeleteAllResults { ref ex } => {
-                let db = Database::open()?;
+                let config = Config::load()?;
+                let db = Database::open(&config)?;
                 let result_db = DatabaseDB::new(&db);
 
                 if let Some(mut experiment) = Experiment::get(&db, &ex.0)? {
@@ -441,14 +1157,15 @@ impl Crater {
                 ref tc,
                 ref krate,
             } => {
-                let db = Database::open()?;
+                let config = Config::load()?;
+                let db = Database::open(&config)?;
                 let result_db = DatabaseDB::new(&db);
 
                 if let Some(mut experiment) = Experiment::get(&db, &ex.0)? {
                     if let Some(tc) = tc {
                         result_db.delete_result(&experiment, tc, krate)?;
                     } else {
-                        for tc in &experiment.toolchains {
+                        for tc in experiment.tested_toolchains() {
                             result_db.delete_result(&experiment, tc, krate)?;
                         }
                     }
@@ -458,14 +1175,38 @@ impl Crater {
                     bail!("missing experiment {}", ex.0);
                 }
             }
+            Crater::InvalidateAgentResults { ref ex, ref agent } => {
+                let config = Config::load()?;
+                let db = Database::open(&config)?;
+
+                if let Some(mut experiment) = Experiment::get(&db, &ex.0)? {
+                    let assignee = Assignee::Agent(agent.clone());
+                    let requeued = experiment.queue().requeue_processed_by(&db, &assignee)?;
+                    info!(
+                        "queued again {} crates processed by agent {}",
+                        requeued.len(),
+                        agent
+                    );
+
+                    // Experiments that already finished need to run again
+                    match experiment.status {
+                        Status::Queued | Status::Running | Status::Paused => {}
+                        _ if requeued.is_empty() => {}
+                        _ => experiment.set_status(&db, Status::Queued)?,
+                    }
+                } else {
+                    bail!("missing experiment {}", ex.0);
+                }
+            }
             Crater::RunGraph {
                 ref ex,
                 threads,
                 ref docker_env,
                 fast_workspace_init,
+                offline_workspace_init,
             } => {
                 let config = Config::load()?;
-                let db = Database::open()?;
+                let db = Database::open(&config)?;
 
                 if let Some(mut experiment) = Experiment::get(&db, &ex.0)? {
                     // Ensure the experiment is properly assigned
@@ -482,15 +1223,17 @@ impl Crater {
                         other => bail!("can't run an experiment with status {}", other.to_str()),
                     }
 
-                    let result_db = DatabaseDB::new(&db);
-
-                    let workspace = self
-                        .workspace(docker_env.as_ref().map(|s| s.as_str()), fast_workspace_init)?;
+                    let docker_env = docker_env.as_ref().map(|s| s.as_str());
+                    let workspace =
+                        self.workspace(docker_env, fast_workspace_init, offline_workspace_init)?;
                     workspace.purge_all_build_dirs()?;
+                    let sandbox_image = crater::utils::workspace::sandbox_image_digest(docker_env);
+                    let result_db = DatabaseDB::new(&db).with_sandbox_image(sandbox_image);
+                    let crates = experiment.get_uncompleted_crates(&db, &config, &Assignee::CLI)?;
                     let res = runner::run_ex(
                         &experiment,
                         &workspace,
-                        &experiment.get_uncompleted_crates(&db, &config, &Assignee::CLI)?,
+                        &crates,
                         &result_db,
                         threads,
                         &config,
@@ -510,7 +1253,7 @@ impl Crater {
                 output_templates,
             } => {
                 let config = Config::load()?;
-                let db = Database::open()?;
+                let db = Database::open(&config)?;
 
                 if let Some(mut experiment) = Experiment::get(&db, &ex.0)? {
                     // Update the status
@@ -545,6 +1288,85 @@ impl Crater {
                     bail!("missing experiment: {}", ex.0);
                 }
             }
+            Crater::Local {
+                ref ex,
+                ref tc1,
+                ref tc2,
+                mode,
+                ref crates,
+                cap_lints,
+                threads,
+                ref docker_env,
+                fast_workspace_init,
+                ref dest,
+                replace,
+            } => {
+                {
+                    let config = Config::load()?;
+                    let db = Database::open(&config)?;
+                    let ctx = ActionsCtx::new(&db, &config);
+
+                    if Experiment::exists(&db, &ex.0)? {
+                        if !replace {
+                            bail!(
+                                "experiment {} already exists (use --replace to run it again)",
+                                ex.0
+                            );
+                        }
+                        actions::DeleteExperiment { name: ex.0.clone() }.apply(&ctx)?;
+                    }
+
+                    // The lists are only fetched the first time, `create-lists` refreshes them
+                    if !db.exists("SELECT rowid FROM crates LIMIT 1;", &[])? {
+                        info!("fetching the lists of crates");
+                        actions::UpdateLists::default().apply(&ctx)?;
+                    }
+                }
+
+                Crater::DefineEx {
+                    ex: ex.clone(),
+                    tc1: tc1.clone(),
+                    tc2: Some(tc2.clone()),
+                    mode,
+                    crates: crates.clone(),
+                    cap_lints,
+                    priority: 0,
+                    ignore_blacklist: false,
+                    assign: Some(Assignee::CLI),
+                    requirement: None,
+                    cargo_config: None,
+                    expected_regressions: Vec::new(),
+                    env: Vec::new(),
+                    deny_lints: Vec::new(),
+                    cargo_update: false,
+                    failure_threshold: None,
+                    reuse_baseline: false,
+                    canary: false,
+                    test_runner: TestRunner::Cargo,
+                    namespace: None,
+                    survey: false,
+                    parent: None,
+                    sandbox_image: None,
+                }
+                .run()?;
+                Crater::RunGraph {
+                    ex: ex.clone(),
+                    threads,
+                    docker_env: docker_env.clone(),
+                    fast_workspace_init,
+                    offline_workspace_init: false,
+                }
+                .run()?;
+                Crater::GenReport {
+                    ex: ex.clone(),
+                    dest: dest.clone(),
+                    force: false,
+                    output_templates: false,
+                }
+                .run()?;
+
+                info!("report written to {}", dest.0.join("index.html").display());
+            }
             Crater::PublishReport {
                 ref ex,
                 ref s3_prefix,
@@ -552,7 +1374,7 @@ impl Crater {
                 output_templates,
             } => {
                 let config = Config::load()?;
-                let db = Database::open()?;
+                let db = Database::open(&config)?;
 
                 if let Some(mut experiment) = Experiment::get(&db, &ex.0)? {
                     // Update the status
@@ -599,8 +1421,15 @@ impl Crater {
                 threads,
                 ref docker_env,
                 fast_workspace_init,
+                offline_workspace_init,
                 ref capabilities,
                 no_default_capabilities,
+                simulate,
+                simulate_min_latency,
+                simulate_max_latency,
+                simulate_failure_rate,
+                simulate_error_rate,
+                simulate_crash_rate,
             } => {
                 let mut caps = if no_default_capabilities {
                     Capabilities::default()
@@ -609,18 +1438,26 @@ impl Crater {
                 };
                 caps.extend(capabilities.clone().into_iter());
 
-                agent::run(
-                    url,
-                    token,
-                    threads,
-                    &caps,
-                    &self
-                        .workspace(docker_env.as_ref().map(|s| s.as_str()), fast_workspace_init)?,
-                )?;
+                if let Some(agents) = simulate {
+                    let simulation = agent::Simulation {
+                        agents,
+                        latency: (simulate_min_latency, simulate_max_latency),
+                        failure_rate: simulate_failure_rate,
+                        error_rate: simulate_error_rate,
+                        crash_rate: simulate_crash_rate,
+                    };
+                    return agent::simulate(url, token, &caps, &simulation);
+                }
+
+                let docker_env = docker_env.as_ref().map(|s| s.as_str());
+                let workspace =
+                    self.workspace(docker_env, fast_workspace_init, offline_workspace_init)?;
+                let sandbox_image = crater::utils::workspace::sandbox_image_digest(docker_env);
+                agent::run(url, token, threads, &caps, &workspace, sandbox_image)?;
             }
             Crater::DumpTasksGraph { ref dest, ref ex } => {
                 let config = Config::load()?;
-                let db = Database::open()?;
+                let db = Database::open(&config)?;
 
                 if let Some(experiment) = Experiment::get(&db, &ex.0)? {
                     runner::dump_dot(&experiment, &experiment.get_crates(&db)?, &config, dest)?;
@@ -628,6 +1465,170 @@ impl Crater {
                     bail!("missing experiment: {}", ex.0);
                 }
             }
+            Crater::Logs {
+                ref ex,
+                ref krate,
+                ref dest,
+                tarball,
+            } => {
+                let config = Config::load()?;
+                let db = Database::open(&config)?;
+                let experiment = match Experiment::get(&db, &ex.0)? {
+                    Some(experiment) => experiment,
+                    None => bail!("missing experiment: {}", ex.0),
+                };
+
+                let logs = report::load_crate_logs(&DatabaseDB::new(&db), &experiment, krate)?;
+                if logs.is_empty() {
+                    bail!("no logs of {} in experiment {}", krate, ex.0);
+                }
+
+                if tarball {
+                    fs::write(dest, report::crate_logs_archive(krate, &logs)?)?;
+                    info!("saved the logs of {} into {}", krate, dest.display());
+                } else {
+                    fs::create_dir_all(dest)?;
+                    for (tc, log) in &logs {
+                        let path = dest.join(format!("{}.txt", tc.to_path_component()));
+                        fs::write(&path, log)?;
+                        info!(
+                            "saved the log of {} on {} into {}",
+                            krate,
+                            tc,
+                            path.display()
+                        );
+                    }
+                }
+            }
+            Crater::DiffExperiments {
+                ref before,
+                ref after,
+                json,
+            } => {
+                let config = Config::load()?;
+                let db = Database::open(&config)?;
+                let get = |ex: &Ex| -> Fallible<Experiment> {
+                    match Experiment::get(&db, &ex.0)? {
+                        Some(experiment) => Ok(experiment),
+                        None => bail!("missing experiment: {}", ex.0),
+                    }
+                };
+
+                let diff = report::diff_experiments(
+                    &DatabaseDB::new(&db),
+                    &config,
+                    &get(before)?,
+                    &get(after)?,
+                )?;
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&diff)?);
+                } else {
+                    print!("{}", diff.render_markdown()?);
+                }
+            }
+            Crater::RegressedDependents {
+                ref ex,
+                ref package,
+                ref version,
+            } => {
+                let config = Config::load()?;
+                let db = Database::open(&config)?;
+                let experiment = match Experiment::get(&db, &ex.0)? {
+                    Some(experiment) => experiment,
+                    None => bail!("missing experiment: {}", ex.0),
+                };
+
+                let dependents = report::regressed_dependents(
+                    &DatabaseDB::new(&db),
+                    &config,
+                    &experiment,
+                    package,
+                    version.as_ref().map(|version| version.as_str()),
+                )?;
+                for krate in dependents {
+                    println!("{}", krate);
+                }
+            }
+            Crater::PrepareCrate {
+                ref tc,
+                ref krate,
+                ref dest,
+                fast_workspace_init,
+                offline_workspace_init,
+            } => {
+                let config = Config::load()?;
+                let workspace =
+                    self.workspace(None, fast_workspace_init, offline_workspace_init)?;
+                let metadata = runner::prepare_into(&workspace, &config, krate, tc, dest)?;
+                info!(
+                    "prepared {} into {} (lockfile {})",
+                    metadata.krate,
+                    dest.display(),
+                    if metadata.lockfile_generated {
+                        "generated"
+                    } else {
+                        "provided by the crate"
+                    }
+                );
+            }
+            Crater::Triage {
+                ref ex,
+                ref url,
+                ref token,
+                all,
+            } => {
+                crater::triage::run(url, token, &ex.0, all)?;
+            }
+            Crater::DiskUsage { all } => {
+                let usage = crater::utils::workspace::disk_usage(&crater::dirs::WORK_DIR)?;
+                for (name, category) in usage.categories().iter() {
+                    println!("{}: {}", name, format_bytes(category.total));
+                    let shown = if all { category.items.len() } else { 5 };
+                    for item in category.items.iter().take(shown) {
+                        println!("    {}: {}", item.name, format_bytes(item.size));
+                    }
+                    if category.items.len() > shown {
+                        println!("    ... and {} more", category.items.len() - shown);
+                    }
+                }
+                println!("total: {}", format_bytes(usage.total()));
+            }
+            Crater::BuildSandboxImage {
+                ref tag,
+                ref packages,
+                ref dockerfile,
+                ref base,
+            } => {
+                let fragment = match dockerfile {
+                    Some(path) => read_to_string(path)?,
+                    None => String::new(),
+                };
+                crater::utils::workspace::build_sandbox_image(
+                    tag,
+                    base.as_ref().map(|s| s.as_str()),
+                    packages,
+                    &fragment,
+                )?;
+                info!(
+                    "built the sandbox image {}, use it with `--docker-env {}`",
+                    tag, tag
+                );
+            }
+            Crater::SandboxRunAsRoot {
+                ref tag,
+                ref base,
+                ref command,
+            } => {
+                crater::utils::workspace::run_as_root(
+                    tag,
+                    base.as_ref().map(|s| s.as_str()),
+                    command,
+                )?;
+                info!(
+                    "created the sandbox image {}, use it with `--docker-env {}`",
+                    tag, tag
+                );
+            }
             Crater::CheckConfig { ref filename } => {
                 if let Err(ref e) = Config::check(filename) {
                     bail!("check-config failed: {}", e);
@@ -638,20 +1639,59 @@ impl Crater {
         Ok(())
     }
 
-    fn workspace(&self, docker_env: Option<&str>, fast_init: bool) -> Result<Workspace, Error> {
-        let mut builder = WorkspaceBuilder::new(&crater::dirs::WORK_DIR, &crater::USER_AGENT)
-            .fast_init(fast_init)
-            .fetch_registry_index_during_builds(false)
-            .command_timeout(Some(Duration::from_secs(15 * 60)))
-            .command_no_output_timeout(Some(Duration::from_secs(5 * 60)))
-            .running_inside_docker(std::env::var("CRATER_INSIDE_DOCKER").is_ok());
+    fn workspace(
+        &self,
+        docker_env: Option<&str>,
+        fast_init: bool,
+        offline: bool,
+    ) -> Result<Workspace, Error> {
+        // Offline initialization doesn't update anything, so the workspace must already be
+        // complete and the sandbox image must be available locally
+        if offline {
+            crater::utils::workspace::check_offline(&crater::dirs::WORK_DIR)?;
+        }
+
+        let mut builder = crater::utils::workspace::builder().fast_init(fast_init || offline);
+        let docker_env = match docker_env {
+            Some(env) => Some(env.to_string()),
+            // The default image of rustwide can't be pulled with an unqualified name by Podman
+            None if ContainerRuntime::current() == ContainerRuntime::Podman => {
+                Some(crater::utils::workspace::default_sandbox_image())
+            }
+            None => None,
+        };
         if let Some(env) = docker_env {
-            builder = builder.sandbox_image(if env.contains('/') {
-                SandboxImage::remote(env)?
-            } else {
-                SandboxImage::local(env)?
-            });
+            let image = crater::utils::workspace::sandbox_image(&env, offline)?;
+            builder = builder.sandbox_image(image);
         }
-        Ok(builder.init()?)
+
+        info!(
+            "initializing the workspace in {}{}",
+            crater::dirs::WORK_DIR.display(),
+            if fast_init || offline {
+                ""
+            } else {
+                " (installing or updating rustup and the toolchains, this can take a while)"
+            }
+        );
+        let start = Instant::now();
+        let workspace = builder.init()?;
+        info!("workspace initialized in {:.1?}", start.elapsed());
+        Ok(workspace)
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
     }
 }
@@ -1,6 +1,8 @@
 use crate::crates::Crate;
 use crate::prelude::*;
+use crate::results::{EncodingType, FailureReason};
 use crate::utils::size::Size;
+use crate::utils::timeout::Timeout;
 use log::LevelFilter;
 use regex::Regex;
 use rustwide::logging::LogStorage;
@@ -10,6 +12,10 @@ use std::ffi::OsStr;
 use std::fs::File;
 use std::io::Read;
 use std::path::PathBuf;
+use std::time::Duration;
+
+/// Factor applied to the memory limit and the timeouts of the heavyweight retries.
+pub(crate) const HEAVYWEIGHT_SCALE: usize = 2;
 
 fn default_config_file() -> PathBuf {
     env::var_os("CRATER_CONFIG")
@@ -32,18 +38,88 @@ pub struct CrateConfig {
     pub quiet: bool,
     #[serde(default = "default_false")]
     pub broken: bool,
+    #[serde(default)]
+    pub targets: Vec<String>,
+    #[serde(default)]
+    pub skip_targets: Vec<String>,
+    #[serde(default)]
+    pub network: NetworkMode,
+    /// Memory limit of the sandbox, replacing the one of the `[sandbox]` section.
+    #[serde(default)]
+    pub memory_limit: Option<Size>,
+    /// Timeout of each command run in the sandbox, replacing the default one.
+    #[serde(default)]
+    pub timeout: Option<Timeout>,
+    /// Disk limit of the builds, replacing the one of the `[sandbox]` section.
+    #[serde(default)]
+    pub disk_limit: Option<Size>,
+    /// Sandbox image the crate is built in, replacing the one of the workspace and of the
+    /// experiment.
+    #[serde(default)]
+    pub sandbox_image: Option<String>,
+}
+
+string_enum!(pub enum NetworkMode {
+    Disabled => "disabled",
+    Localhost => "localhost",
+    Enabled => "enabled",
+});
+
+impl Default for NetworkMode {
+    fn default() -> Self {
+        NetworkMode::Disabled
+    }
+}
+
+string_enum!(pub enum Artifact {
+    Binaries => "binaries",
+    Rlibs => "rlibs",
+    Docs => "docs",
+    JsonMessages => "json-messages",
+});
+
+fn default_lease_timeout() -> u64 {
+    60 * 60
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_dead_agent_timeout() -> u64 {
+    10 * 60
 }
 
 fn default_false() -> bool {
     false
 }
 
+fn default_true() -> bool {
+    true
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct ServerConfig {
     pub bot_acl: BotACL,
     pub labels: ServerLabels,
     pub distributed: ChunkConfig,
+    #[serde(default)]
+    pub partial_reports: PartialReportsConfig,
+    #[serde(default)]
+    pub stream_logs: StreamLogsConfig,
+    #[serde(default)]
+    pub live_logs: LiveLogsConfig,
+    #[serde(default)]
+    pub progress_comments: ProgressCommentsConfig,
+    #[serde(default)]
+    pub early_abort: EarlyAbortConfig,
+    #[serde(default)]
+    pub email_digest: EmailDigestConfig,
+    #[serde(default)]
+    pub sign_off: SignOffConfig,
+    #[serde(default)]
+    pub ecosystem_canary: EcosystemCanaryConfig,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -51,6 +127,8 @@ pub struct ServerConfig {
 pub struct BotACL {
     pub rust_teams: bool,
     pub github: Vec<String>,
+    #[serde(default)]
+    pub admins: Vec<String>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -62,6 +140,113 @@ pub struct ServerLabels {
     pub experiment_completed: String,
 }
 
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PartialReportsConfig {
+    #[serde(default)]
+    pub every: u32,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct StreamLogsConfig {
+    #[serde(default)]
+    pub enabled: bool,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct LiveLogsConfig {
+    #[serde(default)]
+    pub every_secs: u64,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ProgressCommentsConfig {
+    #[serde(default)]
+    pub every_hours: u32,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct EarlyAbortConfig {
+    #[serde(default)]
+    pub crates: u32,
+}
+
+/// Continuous testing of a rotating sample of crates on every new nightly, disabled when `crates`
+/// is zero.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct EcosystemCanaryConfig {
+    #[serde(default)]
+    pub crates: usize,
+    /// Number of most downloaded crates the samples rotate through.
+    #[serde(default = "default_ecosystem_canary_pool")]
+    pub pool: usize,
+    /// Increase of the failure rate, in percentage points, which triggers an alert.
+    #[serde(default = "default_ecosystem_canary_threshold")]
+    pub threshold: u32,
+    /// Issue the alerts are posted on, like `rust-lang/crater#1`.
+    #[serde(default)]
+    pub issue: Option<String>,
+}
+
+impl Default for EcosystemCanaryConfig {
+    fn default() -> Self {
+        EcosystemCanaryConfig {
+            crates: 0,
+            pool: default_ecosystem_canary_pool(),
+            threshold: default_ecosystem_canary_threshold(),
+            issue: None,
+        }
+    }
+}
+
+fn default_ecosystem_canary_pool() -> usize {
+    10_000
+}
+
+fn default_ecosystem_canary_threshold() -> u32 {
+    5
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct EmailDigestConfig {
+    #[serde(default)]
+    pub smtp_server: Option<String>,
+    #[serde(default)]
+    pub from: String,
+    #[serde(default)]
+    pub to: Vec<String>,
+    #[serde(default = "default_digest_error_groups")]
+    pub error_groups: usize,
+}
+
+impl Default for EmailDigestConfig {
+    fn default() -> Self {
+        EmailDigestConfig {
+            smtp_server: None,
+            from: String::new(),
+            to: Vec::new(),
+            error_groups: default_digest_error_groups(),
+        }
+    }
+}
+
+fn default_digest_error_groups() -> usize {
+    5
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SignOffConfig {
+    #[serde(default)]
+    pub enabled: bool,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct DemoCrates {
@@ -76,12 +261,364 @@ pub struct SandboxConfig {
     pub memory_limit: Size,
     pub build_log_max_size: Size,
     pub build_log_max_lines: usize,
+    /// Maximum size of the target directory of each build.
+    #[serde(default)]
+    pub disk_limit: Option<Size>,
+    /// Allow running builds on macOS hosts, in the Linux virtual machine of Docker.
+    #[serde(default)]
+    pub allow_macos_linux_vm: bool,
+    /// DNS servers of the sandbox of the builds with network access.
+    #[serde(default)]
+    pub dns: Vec<String>,
+    /// Extra `/etc/hosts` entries of the sandbox of the builds with network access, as `name:ip`.
+    #[serde(default)]
+    pub extra_hosts: Vec<String>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct LogsConfig {
+    #[serde(default)]
+    pub redact_patterns: Vec<String>,
+    #[serde(default)]
+    pub detect_anomalies: bool,
+    #[serde(default)]
+    pub success_max_size: Option<Size>,
+    #[serde(default)]
+    pub success_sample_rate: f64,
+    /// Compression of the logs stored in the database. By default the logs received from the
+    /// agents are gzipped, while the ones of local runs are stored as they are.
+    #[serde(default)]
+    pub compression: Option<EncodingType>,
+    /// Compression of the logs uploaded to the reports, which is the one they're stored with
+    /// by default.
+    #[serde(default)]
+    pub report_compression: Option<EncodingType>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ReportsConfig {
+    #[serde(default)]
+    pub exclude_nightly_only: bool,
+    #[serde(default = "default_reports_threads")]
+    pub threads: usize,
+    #[serde(default)]
+    pub storage: StorageBackend,
+}
+
+impl Default for ReportsConfig {
+    fn default() -> Self {
+        ReportsConfig {
+            exclude_nightly_only: false,
+            threads: default_reports_threads(),
+            storage: StorageBackend::default(),
+        }
+    }
+}
+
+string_enum!(pub enum StorageBackend {
+    S3 => "s3",
+    Gcs => "gcs",
+    Azure => "azure",
+});
+
+impl Default for StorageBackend {
+    fn default() -> Self {
+        StorageBackend::S3
+    }
+}
+
+fn default_reports_threads() -> usize {
+    4
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DatabaseConfig {
+    #[serde(default)]
+    pub backend: DatabaseBackend,
+    #[serde(default = "default_database_pool_size")]
+    pub pool_size: u32,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            backend: DatabaseBackend::default(),
+            pool_size: default_database_pool_size(),
+        }
+    }
+}
+
+string_enum!(pub enum DatabaseBackend {
+    Sqlite => "sqlite",
+    Postgres => "postgres",
+});
+
+impl Default for DatabaseBackend {
+    fn default() -> Self {
+        DatabaseBackend::Sqlite
+    }
+}
+
+fn default_database_pool_size() -> u32 {
+    16
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct OwnersConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_owners_request_interval")]
+    pub request_interval: u64,
+}
+
+impl Default for OwnersConfig {
+    fn default() -> Self {
+        OwnersConfig {
+            enabled: false,
+            request_interval: default_owners_request_interval(),
+        }
+    }
+}
+
+fn default_owners_request_interval() -> u64 {
+    1000
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct GitHubConfig {
+    #[serde(default = "default_github_api_url")]
+    pub api_url: String,
+    #[serde(default = "default_github_url")]
+    pub url: String,
+    #[serde(default = "default_github_repos_list")]
+    pub repos_list: String,
+}
+
+impl Default for GitHubConfig {
+    fn default() -> Self {
+        GitHubConfig {
+            api_url: default_github_api_url(),
+            url: default_github_url(),
+            repos_list: default_github_repos_list(),
+        }
+    }
+}
+
+fn default_github_api_url() -> String {
+    "https://api.github.com".into()
+}
+
+pub(crate) fn default_github_url() -> String {
+    "https://github.com".into()
+}
+
+fn default_github_repos_list() -> String {
+    "https://raw.githubusercontent.com/rust-lang/rust-repos/master/data/github.csv".into()
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RegistryConfig {
+    #[serde(default)]
+    pub sparse_index: bool,
+    #[serde(default = "default_registry_downloads_max_age")]
+    pub downloads_max_age: u64,
+}
+
+impl Default for RegistryConfig {
+    fn default() -> Self {
+        RegistryConfig {
+            sparse_index: false,
+            downloads_max_age: default_registry_downloads_max_age(),
+        }
+    }
+}
+
+fn default_registry_downloads_max_age() -> u64 {
+    24
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct FetchConfig {
+    #[serde(default = "default_fetch_retries")]
+    pub retries: u32,
+    #[serde(default = "default_fetch_backoff")]
+    pub backoff: u64,
+    #[serde(default)]
+    pub mirrors: Vec<String>,
+    #[serde(default)]
+    pub shared_cache: Option<String>,
+    #[serde(default)]
+    pub shared_cache_upload: bool,
+    #[serde(default)]
+    pub git_depth: Option<u32>,
+}
+
+impl Default for FetchConfig {
+    fn default() -> Self {
+        FetchConfig {
+            retries: default_fetch_retries(),
+            backoff: default_fetch_backoff(),
+            mirrors: Vec::new(),
+            shared_cache: None,
+            shared_cache_upload: false,
+            git_depth: None,
+        }
+    }
+}
+
+fn default_fetch_retries() -> u32 {
+    3
+}
+
+fn default_fetch_backoff() -> u64 {
+    2
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RetryConfig {
+    #[serde(default = "default_retry_max_retries")]
+    pub max_retries: u32,
+    #[serde(default = "default_retry_backoff")]
+    pub backoff: u64,
+    #[serde(default = "default_retry_reasons")]
+    pub reasons: Vec<String>,
+    #[serde(default = "default_true")]
+    pub heavyweight: bool,
+}
+
+impl RetryConfig {
+    /// Whether a build failing for this reason should be run again before recording its result.
+    pub(crate) fn is_retryable(&self, reason: &FailureReason) -> bool {
+        let reason = reason.to_string();
+        self.reasons.iter().any(|r| *r == reason)
+    }
+
+    /// Whether a build failing for this reason should be run again with increased resources, as
+    /// it was probably starved by the other builds running on the agent.
+    pub(crate) fn needs_heavyweight(&self, reason: &FailureReason) -> bool {
+        self.heavyweight && matches!(reason, FailureReason::OOM | FailureReason::Timeout)
+    }
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            max_retries: default_retry_max_retries(),
+            backoff: default_retry_backoff(),
+            reasons: default_retry_reasons(),
+            heavyweight: true,
+        }
+    }
+}
+
+fn default_retry_max_retries() -> u32 {
+    2
+}
+
+fn default_retry_backoff() -> u64 {
+    10
+}
+
+fn default_retry_reasons() -> Vec<String> {
+    vec!["network".into(), "docker".into()]
+}
+
+/// Artifacts of the builds copied out of their target directory before it's cleaned up.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ArtifactsConfig {
+    /// Directory the artifacts are copied to, which disables copying them if missing.
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+    #[serde(default)]
+    pub kinds: Vec<Artifact>,
+}
+
+/// Timeouts of each crate learned from the duration of its recent successful builds, replacing
+/// the default timeout for the crates without a `timeout` in their configuration.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct AdaptiveTimeoutsConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Factor applied to the median duration of the recent builds.
+    #[serde(default = "default_adaptive_timeouts_multiplier")]
+    pub multiplier: f64,
+    /// Number of recent successful builds of each crate whose duration is kept.
+    #[serde(default = "default_adaptive_timeouts_samples")]
+    pub samples: u32,
+    /// Builds needed before a timeout is learned for a crate.
+    #[serde(default = "default_adaptive_timeouts_min_samples")]
+    pub min_samples: u32,
+    #[serde(default = "default_adaptive_timeouts_min")]
+    pub min: Timeout,
+    #[serde(default = "default_adaptive_timeouts_max")]
+    pub max: Timeout,
+}
+
+impl Default for AdaptiveTimeoutsConfig {
+    fn default() -> Self {
+        AdaptiveTimeoutsConfig {
+            enabled: false,
+            multiplier: default_adaptive_timeouts_multiplier(),
+            samples: default_adaptive_timeouts_samples(),
+            min_samples: default_adaptive_timeouts_min_samples(),
+            min: default_adaptive_timeouts_min(),
+            max: default_adaptive_timeouts_max(),
+        }
+    }
+}
+
+fn default_adaptive_timeouts_multiplier() -> f64 {
+    3.0
+}
+
+fn default_adaptive_timeouts_samples() -> u32 {
+    10
+}
+
+fn default_adaptive_timeouts_min_samples() -> u32 {
+    3
+}
+
+fn default_adaptive_timeouts_min() -> Timeout {
+    Timeout::Minutes(15)
+}
+
+fn default_adaptive_timeouts_max() -> Timeout {
+    Timeout::Hours(2)
+}
+
+/// Namespace sharing the deployment with the other teams, whose experiments are created by
+/// prefixing the bot commands with the name of the namespace.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct NamespaceConfig {
+    /// Maximum number of queued, running or paused experiments of the namespace.
+    #[serde(default)]
+    pub max_experiments: Option<u32>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct ChunkConfig {
     pub chunk_size: i32,
+    #[serde(default = "default_lease_timeout")]
+    pub lease_timeout: u64,
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    /// Number of seconds without an heartbeat after which the crates leased to an agent are
+    /// given to the other agents, even if the lease didn't expire yet.
+    #[serde(default = "default_dead_agent_timeout")]
+    pub dead_agent_timeout: u64,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -93,6 +630,28 @@ pub struct Config {
     pub local_crates: HashMap<String, CrateConfig>,
     pub server: ServerConfig,
     pub sandbox: SandboxConfig,
+    #[serde(default)]
+    pub logs: LogsConfig,
+    #[serde(default)]
+    pub fetch: FetchConfig,
+    #[serde(default)]
+    pub retry: RetryConfig,
+    #[serde(default)]
+    pub adaptive_timeouts: AdaptiveTimeoutsConfig,
+    #[serde(default)]
+    pub artifacts: ArtifactsConfig,
+    #[serde(default)]
+    pub owners: OwnersConfig,
+    #[serde(default)]
+    pub github: GitHubConfig,
+    #[serde(default)]
+    pub registry: RegistryConfig,
+    #[serde(default)]
+    pub reports: ReportsConfig,
+    #[serde(default)]
+    pub database: DatabaseConfig,
+    #[serde(default)]
+    pub namespaces: HashMap<String, NamespaceConfig>,
 }
 
 impl Config {
@@ -134,6 +693,24 @@ impl Config {
         self.crate_config(c).map(|c| c.broken).unwrap_or(false)
     }
 
+    pub fn network_mode(&self, c: &Crate) -> NetworkMode {
+        self.crate_config(c).map(|c| c.network).unwrap_or_default()
+    }
+
+    /// Check whether the crate can be built on the provided target triple, according to the
+    /// `targets` and `skip-targets` lists of the crate. Each item of the lists can be either a
+    /// full target triple or one of its components (for example `windows` or `x86_64`).
+    pub fn is_applicable(&self, c: &Crate, target: &str) -> bool {
+        let matches = |item: &String| item == target || target.split('-').any(|part| part == item);
+
+        self.crate_config(c)
+            .map(|c| {
+                (c.targets.is_empty() || c.targets.iter().any(matches))
+                    && !c.skip_targets.iter().any(matches)
+            })
+            .unwrap_or(true)
+    }
+
     pub fn demo_crates(&self) -> &DemoCrates {
         &self.demo_crates
     }
@@ -142,6 +719,44 @@ impl Config {
         self.server.distributed.chunk_size
     }
 
+    /// Memory limit of the builds of the crate, before it's scaled for the heavyweight retries.
+    pub(crate) fn memory_limit(&self, c: &Crate) -> Size {
+        self.crate_config(c)
+            .and_then(|c| c.memory_limit)
+            .unwrap_or(self.sandbox.memory_limit)
+    }
+
+    /// Memory limit the builds of the crate ran with when they were finally killed for exceeding
+    /// it, which is the one of the heavyweight retry if it's enabled.
+    pub(crate) fn oom_memory_limit(&self, c: &Crate) -> Size {
+        if self.retry.needs_heavyweight(&FailureReason::OOM) {
+            self.memory_limit(c).scaled(HEAVYWEIGHT_SCALE)
+        } else {
+            self.memory_limit(c)
+        }
+    }
+
+    /// Maximum size of the target directory while building the crate, if there's one.
+    pub(crate) fn disk_limit(&self, c: &Crate) -> Option<Size> {
+        self.crate_config(c)
+            .and_then(|c| c.disk_limit)
+            .or(self.sandbox.disk_limit)
+    }
+
+    /// Sandbox image the crate must be built in, if it overrides the one of the experiment.
+    pub(crate) fn sandbox_image(&self, c: &Crate) -> Option<&str> {
+        self.crate_config(c)
+            .and_then(|c| c.sandbox_image.as_ref())
+            .map(|image| image.as_str())
+    }
+
+    /// Timeout of the commands building the crate, if it overrides the default one.
+    pub(crate) fn build_timeout(&self, c: &Crate) -> Option<Duration> {
+        self.crate_config(c)
+            .and_then(|c| c.timeout)
+            .map(|timeout| timeout.to_duration())
+    }
+
     pub fn check(file: &Option<String>) -> Fallible<()> {
         if let Some(file) = file {
             Self::check_all(file.into())
@@ -156,10 +771,13 @@ impl Config {
         let buffer = Self::load_as_string(filename)?;
         let mut has_errors = Self::check_for_dup_keys(&buffer).is_err();
         let cfg: Self = ::toml::from_str(&buffer)?;
-        let db = crate::db::Database::open()?;
+        let db = crate::db::Database::open(&cfg)?;
         let crates = crate::crates::lists::get_crates(&CrateSelect::Full, &db, &cfg)?;
         has_errors |= cfg.check_for_missing_crates(&crates).is_err();
         has_errors |= cfg.check_for_missing_repos(&crates).is_err();
+        has_errors |= cfg.check_redact_patterns().is_err();
+        has_errors |= cfg.check_retry_reasons().is_err();
+        has_errors |= cfg.check_namespaces().is_err();
         if has_errors {
             Err(BadConfig.into())
         } else {
@@ -176,6 +794,58 @@ impl Config {
         }
     }
 
+    fn check_redact_patterns(&self) -> Fallible<()> {
+        let mut any_invalid = false;
+        for pattern in &self.logs.redact_patterns {
+            if let Err(e) = Regex::new(pattern) {
+                error!(
+                    "check-config failed: invalid redact pattern `{}`: {}",
+                    pattern, e
+                );
+                any_invalid = true;
+            }
+        }
+        if any_invalid {
+            Err(BadConfig.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check_retry_reasons(&self) -> Fallible<()> {
+        let mut any_invalid = false;
+        for reason in &self.retry.reasons {
+            if reason.parse::<FailureReason>().is_err() {
+                error!("check-config failed: unknown retry reason `{}`", reason);
+                any_invalid = true;
+            }
+        }
+        if any_invalid {
+            Err(BadConfig.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check_namespaces(&self) -> Fallible<()> {
+        let mut any_invalid = false;
+        for name in self.namespaces.keys() {
+            let valid = !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+            if !valid {
+                error!("check-config failed: invalid namespace name `{}`", name);
+                any_invalid = true;
+            }
+        }
+        if any_invalid {
+            Err(BadConfig.into())
+        } else {
+            Ok(())
+        }
+    }
+
     fn check_for_missing_crates(&self, crates: &[Crate]) -> Fallible<()> {
         if self.crates.is_empty() {
             return Ok(());
@@ -257,19 +927,48 @@ impl Default for Config {
                 memory_limit: Size::Gigabytes(2),
                 build_log_max_size: Size::Megabytes(1),
                 build_log_max_lines: 1000,
+                disk_limit: None,
+                allow_macos_linux_vm: false,
+                dns: Vec::new(),
+                extra_hosts: Vec::new(),
             },
             server: ServerConfig {
                 bot_acl: BotACL {
                     rust_teams: false,
                     github: vec![],
+                    admins: vec![],
                 },
                 labels: ServerLabels {
                     remove: Regex::new("^$").unwrap(),
                     experiment_queued: "".into(),
                     experiment_completed: "".into(),
                 },
-                distributed: ChunkConfig { chunk_size: 1 },
+                distributed: ChunkConfig {
+                    chunk_size: 1,
+                    lease_timeout: default_lease_timeout(),
+                    max_attempts: default_max_attempts(),
+                    dead_agent_timeout: default_dead_agent_timeout(),
+                },
+                partial_reports: PartialReportsConfig::default(),
+                stream_logs: StreamLogsConfig::default(),
+                live_logs: LiveLogsConfig::default(),
+                progress_comments: ProgressCommentsConfig::default(),
+                early_abort: EarlyAbortConfig::default(),
+                email_digest: EmailDigestConfig::default(),
+                sign_off: SignOffConfig::default(),
+                ecosystem_canary: EcosystemCanaryConfig::default(),
             },
+            logs: LogsConfig::default(),
+            fetch: FetchConfig::default(),
+            retry: RetryConfig::default(),
+            adaptive_timeouts: AdaptiveTimeoutsConfig::default(),
+            artifacts: ArtifactsConfig::default(),
+            owners: OwnersConfig::default(),
+            github: GitHubConfig::default(),
+            registry: RegistryConfig::default(),
+            reports: ReportsConfig::default(),
+            database: DatabaseConfig::default(),
+            namespaces: HashMap::new(),
         }
     }
 }
@@ -285,8 +984,11 @@ impl From<&'_ Config> for LogStorage {
 
 #[cfg(test)]
 mod tests {
-    use super::Config;
+    use super::{Config, NetworkMode};
     use crate::crates::{Crate, GitHubRepo, RegistryCrate};
+    use crate::results::FailureReason;
+    use crate::utils::size::Size;
+    use std::time::Duration;
 
     #[test]
     fn test_config() {
@@ -311,9 +1013,14 @@ mod tests {
             "build-log-max-lines = 1000\n",
             "[crates]\n",
             "lazy_static = { skip = true }\n",
+            "hyper = { network = \"localhost\" }\n",
+            "openssl-src = { network = \"enabled\" }\n",
+            "servo = { memory-limit = \"8G\", timeout = \"40m\", disk-limit = \"50G\" }\n",
             "[github-repos]\n",
             "\"rust-lang/rust\" = { quiet = true }\n", // :(
-            "[local-crates]\n"
+            "[local-crates]\n",
+            "[namespaces.clippy]\n",
+            "max-experiments = 2\n"
         );
 
         let list: Config = ::toml::from_str(&config).unwrap();
@@ -338,6 +1045,80 @@ mod tests {
             sha: None,
         })));
 
+        let krate = |name: &str| {
+            Crate::Registry(RegistryCrate {
+                name: name.into(),
+                version: "42".into(),
+            })
+        };
+        assert_eq!(list.network_mode(&krate("hyper")), NetworkMode::Localhost);
+        assert_eq!(
+            list.network_mode(&krate("openssl-src")),
+            NetworkMode::Enabled
+        );
+        assert_eq!(list.network_mode(&krate("rand")), NetworkMode::Disabled);
+        assert_eq!(list.memory_limit(&krate("servo")), Size::Gigabytes(8));
+        assert_eq!(list.memory_limit(&krate("rand")), Size::Gigabytes(2));
+        assert_eq!(list.oom_memory_limit(&krate("servo")), Size::Gigabytes(16));
+        assert_eq!(
+            list.build_timeout(&krate("servo")),
+            Some(Duration::from_secs(40 * 60))
+        );
+        assert_eq!(list.build_timeout(&krate("rand")), None);
+        assert_eq!(list.disk_limit(&krate("servo")), Some(Size::Gigabytes(50)));
+        assert_eq!(list.disk_limit(&krate("rand")), None);
+
         assert_eq!(list.chunk_size(), 32);
+        assert_eq!(list.github.api_url, "https://api.github.com");
+        assert!(list.retry.is_retryable(&FailureReason::Network));
+        assert!(!list.retry.is_retryable(&FailureReason::OOM));
+        assert!(list.retry.needs_heavyweight(&FailureReason::OOM));
+        assert!(!list.retry.needs_heavyweight(&FailureReason::Network));
+        assert_eq!(list.namespaces["clippy"].max_experiments, Some(2));
+        assert!(list.check_namespaces().is_ok());
+    }
+
+    #[test]
+    fn test_is_applicable() {
+        let config = concat!(
+            "[server.bot-acl]\n",
+            "rust-teams = false\n",
+            "github = []\n",
+            "[server.labels]\n",
+            "remove = \"\"\n",
+            "experiment-queued = \"\"\n",
+            "experiment-completed = \"\"\n",
+            "[server.distributed]\n",
+            "chunk-size = 32\n",
+            "[demo-crates]\n",
+            "crates = []\n",
+            "github-repos = []\n",
+            "local-crates = []\n",
+            "[sandbox]\n",
+            "memory-limit = \"2G\"\n",
+            "build-log-max-size = \"2M\"\n",
+            "build-log-max-lines = 1000\n",
+            "[crates]\n",
+            "winapi = { targets = [\"windows\"] }\n",
+            "nix = { skip-targets = [\"x86_64-pc-windows-msvc\"] }\n",
+            "[github-repos]\n",
+            "[local-crates]\n"
+        );
+        let list: Config = ::toml::from_str(&config).unwrap();
+
+        let krate = |name: &str| {
+            Crate::Registry(RegistryCrate {
+                name: name.into(),
+                version: "1.0".into(),
+            })
+        };
+        let linux = "x86_64-unknown-linux-gnu";
+        let windows = "x86_64-pc-windows-msvc";
+
+        assert!(!list.is_applicable(&krate("winapi"), linux));
+        assert!(list.is_applicable(&krate("winapi"), windows));
+        assert!(list.is_applicable(&krate("nix"), linux));
+        assert!(!list.is_applicable(&krate("nix"), windows));
+        assert!(list.is_applicable(&krate("rand"), linux));
     }
 }
@@ -0,0 +1,404 @@
+//! Expressions selecting the registry crates to test from their metadata, like
+//! `downloads > 10000 && depends_on("tokio") && !category("embedded")`.
+//!
+//! Fields (`downloads`, `recent_downloads` and `dependents`) are compared with numbers, while
+//! functions (`depends_on`, `category` and `keyword`) take a string between single or double
+//! quotes. Conditions are combined with `&&`, `||`, `!` and parentheses.
+
+use crate::crates::sources::dump::CrateMetadata;
+use crate::prelude::*;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::{Chars, FromStr};
+
+/// Maximum nesting of parentheses and `!` in an expression, to avoid overflowing the stack while
+/// parsing and evaluating expressions received from the outside.
+const MAX_DEPTH: usize = 64;
+
+string_enum!(enum Field {
+    Downloads => "downloads",
+    RecentDownloads => "recent_downloads",
+    Dependents => "dependents",
+});
+
+string_enum!(enum Function {
+    DependsOn => "depends_on",
+    Category => "category",
+    Keyword => "keyword",
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    fn compare(self, left: u64, right: u64) -> bool {
+        match self {
+            Comparison::Eq => left == right,
+            Comparison::Ne => left != right,
+            Comparison::Lt => left < right,
+            Comparison::Le => left <= right,
+            Comparison::Gt => left > right,
+            Comparison::Ge => left >= right,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Number(u64),
+    Str(String),
+    Compare(Comparison),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Ident(ident) => write!(f, "`{}`", ident),
+            Token::Number(n) => write!(f, "`{}`", n),
+            Token::Str(s) => write!(f, "string {:?}", s),
+            Token::Compare(_) => write!(f, "comparison"),
+            Token::And => write!(f, "`&&`"),
+            Token::Or => write!(f, "`||`"),
+            Token::Not => write!(f, "`!`"),
+            Token::Open => write!(f, "`(`"),
+            Token::Close => write!(f, "`)`"),
+        }
+    }
+}
+
+fn expect_char(chars: &mut Peekable<Chars>, expected: char, token: &str) -> Fallible<()> {
+    if chars.next() != Some(expected) {
+        bail!("expected `{}` in the crates expression", token);
+    }
+    Ok(())
+}
+
+fn tokenize(input: &str) -> Fallible<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(chr) = chars.next() {
+        let token = match chr {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '&' => {
+                expect_char(&mut chars, '&', "&&")?;
+                Token::And
+            }
+            '|' => {
+                expect_char(&mut chars, '|', "||")?;
+                Token::Or
+            }
+            '=' => {
+                expect_char(&mut chars, '=', "==")?;
+                Token::Compare(Comparison::Eq)
+            }
+            '!' | '<' | '>' => {
+                let with_eq = chars.peek() == Some(&'=');
+                if with_eq {
+                    chars.next();
+                }
+                match (chr, with_eq) {
+                    ('!', false) => Token::Not,
+                    ('!', true) => Token::Compare(Comparison::Ne),
+                    ('<', false) => Token::Compare(Comparison::Lt),
+                    ('<', true) => Token::Compare(Comparison::Le),
+                    ('>', false) => Token::Compare(Comparison::Gt),
+                    _ => Token::Compare(Comparison::Ge),
+                }
+            }
+            quote @ '"' | quote @ '\'' => {
+                let mut content = String::new();
+                loop {
+                    match chars.next() {
+                        Some(c) if c == quote => break,
+                        Some(c) => content.push(c),
+                        None => bail!("unterminated string in the crates expression"),
+                    }
+                }
+                Token::Str(content)
+            }
+            c if c.is_ascii_digit() => {
+                let mut number = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if !c.is_ascii_digit() && c != '_' {
+                        break;
+                    }
+                    number.push(c);
+                    chars.next();
+                }
+                Token::Number(number.replace('_', "").parse()?)
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut ident = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if !c.is_ascii_alphanumeric() && c != '_' {
+                        break;
+                    }
+                    ident.push(c);
+                    chars.next();
+                }
+                Token::Ident(ident)
+            }
+            c => bail!("unexpected character `{}` in the crates expression", c),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Not(Box<Node>),
+    Compare(Field, Comparison, u64),
+    Call(Function, String),
+}
+
+/// Recursive descent parser of the expressions. `||` has the lowest precedence, followed by `&&`
+/// and then by `!`.
+struct Parser {
+    tokens: std::vec::IntoIter<Token>,
+    peeked: Option<Token>,
+    depth: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        self.peeked.take().or_else(|| self.tokens.next())
+    }
+
+    fn eat(&mut self, expected: &Token) -> bool {
+        match self.next() {
+            Some(ref token) if token == expected => true,
+            other => {
+                self.peeked = other;
+                false
+            }
+        }
+    }
+
+    fn nested(&mut self, f: impl FnOnce(&mut Self) -> Fallible<Node>) -> Fallible<Node> {
+        if self.depth >= MAX_DEPTH {
+            bail!(
+                "the crates expression is nested more than {} levels deep",
+                MAX_DEPTH
+            );
+        }
+        self.depth += 1;
+        let node = f(self);
+        self.depth -= 1;
+        node
+    }
+
+    fn or(&mut self) -> Fallible<Node> {
+        let mut node = self.and()?;
+        while self.eat(&Token::Or) {
+            node = Node::Or(Box::new(node), Box::new(self.and()?));
+        }
+        Ok(node)
+    }
+
+    fn and(&mut self) -> Fallible<Node> {
+        let mut node = self.not()?;
+        while self.eat(&Token::And) {
+            node = Node::And(Box::new(node), Box::new(self.not()?));
+        }
+        Ok(node)
+    }
+
+    fn not(&mut self) -> Fallible<Node> {
+        if self.eat(&Token::Not) {
+            Ok(Node::Not(Box::new(self.nested(Self::not)?)))
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Fallible<Node> {
+        match self.next() {
+            Some(Token::Open) => {
+                let node = self.nested(Self::or)?;
+                if !self.eat(&Token::Close) {
+                    bail!("missing `)` in the crates expression");
+                }
+                Ok(node)
+            }
+            Some(Token::Ident(ref name)) if self.eat(&Token::Open) => {
+                let function: Function = name
+                    .parse()
+                    .map_err(|_| err_msg(format!("unknown function `{}`", name)))?;
+                let arg = match self.next() {
+                    Some(Token::Str(arg)) => arg,
+                    _ => bail!("`{}` requires a string as its argument", name),
+                };
+                if !self.eat(&Token::Close) {
+                    bail!("`{}` requires a single argument", name);
+                }
+                Ok(Node::Call(function, arg))
+            }
+            Some(Token::Ident(name)) => {
+                let field: Field = name
+                    .parse()
+                    .map_err(|_| err_msg(format!("unknown field `{}`", name)))?;
+                match (self.next(), self.next()) {
+                    (Some(Token::Compare(cmp)), Some(Token::Number(n))) => {
+                        Ok(Node::Compare(field, cmp, n))
+                    }
+                    _ => bail!("`{}` must be compared with a number", name),
+                }
+            }
+            Some(token) => bail!("unexpected {} in the crates expression", token),
+            None => bail!("the crates expression ended unexpectedly"),
+        }
+    }
+}
+
+/// What's known about a registry crate when an expression is evaluated.
+pub(crate) struct CrateFacts<'a> {
+    pub(crate) metadata: &'a CrateMetadata,
+    /// Names of the crates the latest version depends on.
+    pub(crate) dependencies: &'a [String],
+    /// Number of crates whose latest version depends on this one.
+    pub(crate) dependents: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CrateExpr(Node);
+
+impl CrateExpr {
+    pub(crate) fn matches(&self, krate: &CrateFacts) -> bool {
+        Self::eval(&self.0, krate)
+    }
+
+    fn eval(node: &Node, krate: &CrateFacts) -> bool {
+        match node {
+            Node::And(left, right) => Self::eval(left, krate) && Self::eval(right, krate),
+            Node::Or(left, right) => Self::eval(left, krate) || Self::eval(right, krate),
+            Node::Not(inner) => !Self::eval(inner, krate),
+            Node::Compare(field, cmp, value) => {
+                let actual = match field {
+                    Field::Downloads => krate.metadata.downloads,
+                    Field::RecentDownloads => krate.metadata.recent_downloads,
+                    Field::Dependents => krate.dependents,
+                };
+                cmp.compare(actual, *value)
+            }
+            Node::Call(Function::DependsOn, name) => krate.dependencies.iter().any(|d| d == name),
+            // Subcategories like `network-programming::http-client` belong to their parent too
+            Node::Call(Function::Category, slug) => krate.metadata.categories.iter().any(|c| {
+                c == slug || (c.starts_with(slug.as_str()) && c[slug.len()..].starts_with("::"))
+            }),
+            Node::Call(Function::Keyword, keyword) => krate
+                .metadata
+                .keywords
+                .iter()
+                .any(|k| k.eq_ignore_ascii_case(keyword)),
+        }
+    }
+}
+
+impl FromStr for CrateExpr {
+    type Err = failure::Error;
+
+    fn from_str(input: &str) -> Fallible<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(input)?.into_iter(),
+            peeked: None,
+            depth: 0,
+        };
+        let node = parser.or()?;
+        if let Some(token) = parser.next() {
+            bail!("unexpected {} in the crates expression", token);
+        }
+        Ok(CrateExpr(node))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CrateExpr, CrateFacts};
+    use crate::crates::sources::dump::CrateMetadata;
+
+    #[test]
+    fn test_parse_errors() {
+        for expr in &[
+            "",
+            "downloads",
+            "downloads > many",
+            "downloads = 10",
+            "stars > 10",
+            "depends_on(tokio)",
+            "depends_on('tokio'",
+            "depends_on(\"tokio)",
+            "depends_on('tokio', 'serde')",
+            "owner('alexcrichton')",
+            "(downloads > 10",
+            "downloads > 10 downloads < 20",
+            "downloads > 10 &",
+            "!",
+        ] {
+            assert!(expr.parse::<CrateExpr>().is_err(), "{} parsed", expr);
+        }
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        let nested = |depth| format!("{}downloads > 10{}", "!(".repeat(depth), ")".repeat(depth));
+        assert!(nested(super::MAX_DEPTH / 2).parse::<CrateExpr>().is_ok());
+        assert!(nested(100_000).parse::<CrateExpr>().is_err());
+    }
+
+    #[test]
+    fn test_matches() {
+        let metadata = CrateMetadata {
+            downloads: 50_000,
+            recent_downloads: 2_000,
+            categories: vec!["network-programming::http-client".into()],
+            keywords: vec!["HTTP".into()],
+        };
+        let dependencies = vec!["tokio".to_string(), "serde".to_string()];
+        let krate = CrateFacts {
+            metadata: &metadata,
+            dependencies: &dependencies,
+            dependents: 3,
+        };
+        let matches = |expr: &str| expr.parse::<CrateExpr>().unwrap().matches(&krate);
+
+        assert!(matches(
+            "downloads>10000 && depends_on(\"tokio\") && !category(\"embedded\")"
+        ));
+        assert!(matches(
+            "downloads >= 50_000 && downloads <= 50000 && downloads == 50000"
+        ));
+        assert!(!matches("downloads != 50000 || recent_downloads < 1000"));
+        assert!(matches("dependents > 2 && keyword('http')"));
+        assert!(matches("category('network-programming')"));
+        assert!(!matches("category('network')"));
+        assert!(!matches("depends_on('async-std')"));
+
+        // `&&` binds tighter than `||`, and `!` tighter than both
+        assert!(matches(
+            "depends_on('rand') && depends_on('libc') || depends_on('serde')"
+        ));
+        assert!(!matches(
+            "depends_on('rand') && (depends_on('libc') || depends_on('serde'))"
+        ));
+        assert!(matches("!depends_on('rand') && depends_on('serde')"));
+        assert!(!matches("!(depends_on('rand') || depends_on('serde'))"));
+    }
+}
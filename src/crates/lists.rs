@@ -1,15 +1,18 @@
 use crate::config::Config;
+use crate::crates::expr::{CrateExpr, CrateFacts};
+use crate::crates::sources::dump::{self, CrateMetadata};
 use crate::crates::sources::github::GitHubRepo;
+use crate::crates::sources::registry;
 use crate::crates::{Crate, RegistryCrate};
 use crate::db::{Database, QueryUtils};
 use crate::experiments::CrateSelect;
 use crate::prelude::*;
 use chrono::Utc;
 use rand::{thread_rng, Rng};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 pub(crate) use crate::crates::sources::{
-    github::GitHubList, local::LocalList, registry::RegistryList,
+    file::FileList, github::GitHubList, local::LocalList, registry::RegistryList,
 };
 
 pub(crate) trait List {
@@ -118,6 +121,13 @@ pub(crate) fn get_crates(
         CrateSelect::List(list) => {
             let mut desired = list.clone();
 
+            // Packages in a subdirectory of a repository aren't part of any list, so they're
+            // selected with their full ID instead of their name
+            for id in desired.iter().filter(|id| id.starts_with("gitsub/")) {
+                crates.push(id.parse()?);
+            }
+            desired.retain(|id| !id.starts_with("gitsub/"));
+
             let mut all_crates = Vec::new();
             all_crates.append(&mut RegistryList::get(db)?);
             all_crates.append(&mut GitHubList::get(db)?);
@@ -151,9 +161,58 @@ pub(crate) fn get_crates(
             crates.append(&mut RegistryList::get(db)?);
             crates.truncate(*n as usize);
         }
+        CrateSelect::ReverseDeps(name, limit) => {
+            let rdeps = registry::reverse_dependencies(name)?;
+            // The registry list is sorted by popularity, so the most used dependents come first
+            crates.extend(
+                RegistryList::get(db)?
+                    .into_iter()
+                    .filter(|krate| match krate {
+                        Crate::Registry(RegistryCrate { name, .. }) => rdeps.contains(name),
+                        _ => false,
+                    }),
+            );
+            if let Some(n) = limit {
+                crates.truncate(*n as usize);
+            }
+        }
+        CrateSelect::Expr(expr) => {
+            let expr: CrateExpr = expr.parse()?;
+            let metadata = dump::crates_metadata(&config.registry)?;
+            let dependencies = registry::dependencies()?;
+            let mut dependents = HashMap::new();
+            for deps in dependencies.values() {
+                for dep in deps.iter().collect::<HashSet<_>>() {
+                    *dependents.entry(dep.as_str()).or_insert(0) += 1;
+                }
+            }
+
+            // Crates missing from the dump were published after it was generated
+            let unknown = CrateMetadata::default();
+            crates.extend(
+                RegistryList::get(db)?
+                    .into_iter()
+                    .filter(|krate| match krate {
+                        Crate::Registry(RegistryCrate { name, .. }) => expr.matches(&CrateFacts {
+                            metadata: metadata.get(name).unwrap_or(&unknown),
+                            dependencies: dependencies
+                                .get(name)
+                                .map(|d| d.as_slice())
+                                .unwrap_or(&[]),
+                            dependents: dependents.get(name.as_str()).cloned().unwrap_or(0),
+                        }),
+                        _ => false,
+                    }),
+            );
+        }
         CrateSelect::Local => {
             crates.append(&mut LocalList::get(db)?);
         }
+        CrateSelect::File(path) => {
+            // The file is loaded again every time, as it's usually edited between experiments
+            FileList::new(path).update(db)?;
+            crates.append(&mut FileList::get(db)?);
+        }
         CrateSelect::Dummy => crates.push(Crate::GitHub(GitHubRepo::dummy())),
     }
 
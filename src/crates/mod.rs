@@ -1,3 +1,4 @@
+pub(crate) mod expr;
 pub(crate) mod lists;
 mod sources;
 
@@ -18,6 +19,19 @@ pub(crate) use crate::crates::sources::registry::RegistryCrate;
 pub struct GitRepo {
     pub url: String,
     pub sha: Option<String>,
+    /// Directory of the repository containing the package to build, for packages living in a
+    /// subdirectory of a monorepo.
+    #[serde(default)]
+    pub subpath: Option<String>,
+}
+
+/// Whether a path points inside the repository it's relative to.
+fn is_valid_subpath(path: &str) -> bool {
+    !path.is_empty()
+        && !path.starts_with('/')
+        && path
+            .split('/')
+            .all(|part| !part.is_empty() && part != "." && part != "..")
 }
 
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize, Clone)]
@@ -30,6 +44,28 @@ pub enum Crate {
 }
 
 impl Crate {
+    /// Package living in the `path` subdirectory of the git repository at `url`.
+    pub(crate) fn git_subpath(url: &str, path: &str) -> Fallible<Crate> {
+        let path = path.trim_end_matches('/');
+        if !is_valid_subpath(path) {
+            bail!("invalid path inside the repository: {}", path);
+        }
+        Ok(Crate::Git(GitRepo {
+            url: url.to_string(),
+            sha: None,
+            subpath: Some(path.to_string()),
+        }))
+    }
+
+    /// Directory of the source containing the package to build, relative to the root of the
+    /// source, if the package isn't at the root.
+    pub(crate) fn subpath(&self) -> Option<&str> {
+        match self {
+            Crate::Git(repo) => repo.subpath.as_ref().map(|path| path.as_str()),
+            _ => None,
+        }
+    }
+
     pub(crate) fn id(&self) -> String {
         match *self {
             Crate::Registry(ref details) => format!("reg/{}/{}", details.name, details.version),
@@ -45,25 +81,46 @@ impl Crate {
                 format!("path/{}", utf8_percent_encode(&path, &NON_ALPHANUMERIC))
             }
             Crate::Git(ref repo) => {
-                if let Some(ref sha) = repo.sha {
+                let mut id = if let Some(ref subpath) = repo.subpath {
                     format!(
-                        "git/{}/{}",
+                        "gitsub/{}/{}",
                         utf8_percent_encode(&repo.url, &NON_ALPHANUMERIC),
-                        sha
+                        utf8_percent_encode(subpath, &NON_ALPHANUMERIC)
                     )
                 } else {
-                    format!("git/{}", utf8_percent_encode(&repo.url, &NON_ALPHANUMERIC),)
+                    format!("git/{}", utf8_percent_encode(&repo.url, &NON_ALPHANUMERIC))
+                };
+                if let Some(ref sha) = repo.sha {
+                    id.push('/');
+                    id.push_str(sha);
                 }
+                id
             }
         }
     }
 
+    /// Identifier of the crate that doesn't change between releases or commits, used to attach
+    /// information to the crate itself rather than to one of its versions.
+    pub(crate) fn unversioned_id(&self) -> String {
+        match *self {
+            Crate::Registry(ref details) => format!("reg/{}", details.name),
+            Crate::GitHub(ref repo) => format!("gh/{}/{}", repo.org, repo.name),
+            Crate::Git(ref repo) if repo.subpath.is_some() => Crate::Git(GitRepo {
+                sha: None,
+                ..repo.clone()
+            })
+            .id(),
+            Crate::Git(ref repo) => {
+                format!("git/{}", utf8_percent_encode(&repo.url, &NON_ALPHANUMERIC))
+            }
+            Crate::Local(_) | Crate::Path(_) => self.id(),
+        }
+    }
+
     pub(crate) fn to_rustwide(&self) -> RustwideCrate {
         match self {
             Self::Registry(krate) => RustwideCrate::crates_io(&krate.name, &krate.version),
-            Self::GitHub(repo) => {
-                RustwideCrate::git(&format!("https://github.com/{}/{}", repo.org, repo.name))
-            }
+            Self::GitHub(repo) => RustwideCrate::git(&repo.clone_url()),
             Self::Local(name) => RustwideCrate::local(&LOCAL_CRATES_DIR.join(name)),
             Self::Path(path) => RustwideCrate::local(Path::new(&path)),
             Self::Git(repo) => RustwideCrate::git(&repo.url),
@@ -104,12 +161,14 @@ impl TryFrom<&'_ PackageId> for Crate {
                         (Some(url), None) => Ok(Crate::Git(GitRepo {
                             url: url.to_string(),
                             sha: None,
+                            subpath: None,
                         })),
                         (Some(url), Some(sha)) => Ok(Crate::Git(GitRepo {
                             // remove additional queries if the sha is present
                             // as the crate version is already uniquely determined
                             url: url.split('?').next().unwrap().to_string(),
                             sha: Some(sha.to_string()),
+                            subpath: None,
                         })),
                         _ => bail!("malformed git repo: {}", repo),
                     }
@@ -139,16 +198,17 @@ impl fmt::Display for Crate {
                 Crate::Local(ref name) => format!("{} (local)", name),
                 Crate::Path(ref path) =>
                     format!("{}", utf8_percent_encode(path, &NON_ALPHANUMERIC)),
-                Crate::Git(ref repo) =>
+                Crate::Git(ref repo) => {
+                    let mut name = utf8_percent_encode(&repo.url, &NON_ALPHANUMERIC).to_string();
                     if let Some(ref sha) = repo.sha {
-                        format!(
-                            "{}/{}",
-                            utf8_percent_encode(&repo.url, &NON_ALPHANUMERIC),
-                            sha
-                        )
-                    } else {
-                        utf8_percent_encode(&repo.url, &NON_ALPHANUMERIC).to_string()
-                    },
+                        name.push('/');
+                        name.push_str(sha);
+                    }
+                    if let Some(ref subpath) = repo.subpath {
+                        name.push_str(&format!(" ({})", subpath));
+                    }
+                    name
+                }
             }
         )
     }
@@ -177,11 +237,24 @@ impl FromStr for Crate {
             ["git", repo, sha] => Ok(Crate::Git(GitRepo {
                 url: percent_decode_str(repo).decode_utf8()?.to_string(),
                 sha: Some(sha.to_string()),
+                subpath: None,
             })),
             ["git", repo] => Ok(Crate::Git(GitRepo {
                 url: percent_decode_str(repo).decode_utf8()?.to_string(),
                 sha: None,
+                subpath: None,
             })),
+            ["gitsub", repo, subpath] => Crate::git_subpath(
+                &percent_decode_str(repo).decode_utf8()?,
+                &percent_decode_str(subpath).decode_utf8()?,
+            ),
+            ["gitsub", repo, subpath, sha] => {
+                let mut krate: Crate = format!("gitsub/{}/{}", repo, subpath).parse()?;
+                if let Crate::Git(ref mut repo) = krate {
+                    repo.sha = Some(sha.to_string());
+                }
+                Ok(krate)
+            }
             ["local", name] => Ok(Crate::Local(name.to_string())),
             ["path", path] => Ok(Crate::Path(
                 percent_decode_str(path).decode_utf8()?.to_string(),
@@ -237,22 +310,26 @@ mod tests {
             "dummy 0.1.0 (git+https://gitlab.com/dummy_org/dummy#9823f01cf4948a41279f6a3febcf793130cab4f6)" => Crate::Git(GitRepo {
                 url: "https://gitlab.com/dummy_org/dummy"
                     .to_string(),
-                sha: Some("9823f01cf4948a41279f6a3febcf793130cab4f6".to_string())
+                sha: Some("9823f01cf4948a41279f6a3febcf793130cab4f6".to_string()),
+                subpath: None,
             }),
             "dummy 0.1.0 (git+https://gitlab.com/dummy_org/dummy?branch=dummybranch#9823f01cf4948a41279f6a3febcf793130cab4f6)" => Crate::Git(GitRepo {
                 url: "https://gitlab.com/dummy_org/dummy"
                     .to_string(),
-                sha: Some("9823f01cf4948a41279f6a3febcf793130cab4f6".to_string())
+                sha: Some("9823f01cf4948a41279f6a3febcf793130cab4f6".to_string()),
+                subpath: None,
             }),
             "dummy 0.1.0 (git+https://gitlab.com/dummy_org/dummy)" => Crate::Git(GitRepo {
                 url: "https://gitlab.com/dummy_org/dummy"
                     .to_string(),
-                sha: None
+                sha: None,
+                subpath: None,
             }),
             "dummy 0.1.0 (git+https://gitlab.com/dummy_org/dummy?branch=dummybranch)" => Crate::Git(GitRepo {
                 url: "https://gitlab.com/dummy_org/dummy?branch=dummybranch"
                     .to_string(),
-                sha: None
+                sha: None,
+                subpath: None,
             }),
         }
 
@@ -285,10 +362,63 @@ mod tests {
             &format!("path/{}", utf8_percent_encode("path/with:stange?characters", &NON_ALPHANUMERIC)) => Crate::Path("path/with:stange?characters".to_string()),
             "gh/org/user" => Crate::GitHub(GitHubRepo{org: "org".to_string(), name: "user".to_string(), sha: None}),
             "gh/org/user/sha" => Crate::GitHub(GitHubRepo{org: "org".to_string(), name: "user".to_string(), sha: Some("sha".to_string())}),
-            "git/url" => Crate::Git(GitRepo{url: "url".to_string(), sha: None}),
-            &format!("git/{}", utf8_percent_encode("url/with:stange?characters", &NON_ALPHANUMERIC)) => Crate::Git(GitRepo{url: "url/with:stange?characters".to_string(), sha: None}),
-            "git/url/sha" => Crate::Git(GitRepo{url: "url".to_string(), sha: Some("sha".to_string())}),
+            "git/url" => Crate::Git(GitRepo{url: "url".to_string(), sha: None, subpath: None}),
+            &format!("git/{}", utf8_percent_encode("url/with:stange?characters", &NON_ALPHANUMERIC)) => Crate::Git(GitRepo{url: "url/with:stange?characters".to_string(), sha: None, subpath: None}),
+            "git/url/sha" => Crate::Git(GitRepo{url: "url".to_string(), sha: Some("sha".to_string()), subpath: None}),
+            "gitsub/url/crates%2Fcore" => Crate::Git(GitRepo{url: "url".to_string(), sha: None, subpath: Some("crates/core".to_string())}),
+            "gitsub/url/crates%2Fcore/sha" => Crate::Git(GitRepo{url: "url".to_string(), sha: Some("sha".to_string()), subpath: Some("crates/core".to_string())}),
             "reg/name/version" => Crate::Registry(RegistryCrate{name: "name".to_string(), version: "version".to_string()}),
         }
     }
+
+    #[test]
+    fn test_unversioned_id() {
+        let reg = |version: &str| {
+            Crate::Registry(RegistryCrate {
+                name: "syn".to_string(),
+                version: version.to_string(),
+            })
+        };
+        assert_eq!(reg("1.0.0").unversioned_id(), "reg/syn");
+        assert_eq!(
+            reg("1.0.0").unversioned_id(),
+            reg("0.15.0").unversioned_id()
+        );
+
+        let gh = Crate::GitHub(GitHubRepo {
+            org: "org".to_string(),
+            name: "user".to_string(),
+            sha: Some("sha".to_string()),
+        });
+        assert_eq!(gh.unversioned_id(), "gh/org/user");
+
+        let sub = Crate::Git(GitRepo {
+            url: "url".to_string(),
+            sha: Some("sha".to_string()),
+            subpath: Some("crates/core".to_string()),
+        });
+        assert_eq!(sub.unversioned_id(), "gitsub/url/crates%2Fcore");
+
+        let local = Crate::Local("build-fail".to_string());
+        assert_eq!(local.unversioned_id(), local.id());
+    }
+
+    #[test]
+    fn test_git_subpath() {
+        let krate = Crate::git_subpath("https://example.com/repo", "crates/core/").unwrap();
+        assert_eq!(krate.subpath(), Some("crates/core"));
+        assert_eq!(Crate::Local("build-fail".to_string()).subpath(), None);
+
+        for invalid in &[
+            "",
+            "/crates/core",
+            "crates/../..",
+            "crates//core",
+            "./crates",
+        ] {
+            assert!(Crate::git_subpath("https://example.com/repo", invalid).is_err());
+        }
+        assert!(Crate::from_str("gitsub/url/..%2Fcore").is_err());
+        assert!(Crate::from_str("gitsub/url/core/sha/extra").is_err());
+    }
 }
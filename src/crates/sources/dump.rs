@@ -0,0 +1,270 @@
+//! Metadata of the crates published on crates.io which isn't available in the index, extracted
+//! from the database dump crates.io publishes every day.
+
+use crate::config::RegistryConfig;
+use crate::dirs::WORK_DIR;
+use crate::prelude::*;
+use crate::utils;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use flate2::read::GzDecoder;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::PathBuf;
+use tar::Archive;
+
+const DUMP_URL: &str = "https://static.crates.io/db-dump.tar.gz";
+
+lazy_static! {
+    static ref METADATA_CACHE: PathBuf = WORK_DIR.join("crates.io-metadata.json");
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct CrateMetadata {
+    pub(crate) downloads: u64,
+    /// Downloads in the last 90 days.
+    pub(crate) recent_downloads: u64,
+    /// Slugs of the categories, like `network-programming` or `parsing`.
+    pub(crate) categories: Vec<String>,
+    pub(crate) keywords: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MetadataCache {
+    fetched_at: DateTime<Utc>,
+    crates: HashMap<String, CrateMetadata>,
+}
+
+#[derive(Deserialize)]
+struct CrateRow {
+    id: u64,
+    name: String,
+    /// Older dumps store the downloads in the crates table instead of in `crate_downloads.csv`.
+    #[serde(default)]
+    downloads: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct DownloadsRow {
+    crate_id: u64,
+    downloads: u64,
+}
+
+#[derive(Deserialize)]
+struct CategoryRow {
+    id: u64,
+    slug: String,
+}
+
+#[derive(Deserialize)]
+struct KeywordRow {
+    id: u64,
+    keyword: String,
+}
+
+#[derive(Deserialize)]
+struct CrateCategoryRow {
+    crate_id: u64,
+    category_id: u64,
+}
+
+#[derive(Deserialize)]
+struct CrateKeywordRow {
+    crate_id: u64,
+    keyword_id: u64,
+}
+
+/// Tables of the dump needed to build the metadata, which can appear in any order in the archive.
+#[derive(Default)]
+struct Tables {
+    crates: Vec<CrateRow>,
+    downloads: Vec<DownloadsRow>,
+    recent_downloads: Vec<DownloadsRow>,
+    categories: Vec<CategoryRow>,
+    keywords: Vec<KeywordRow>,
+    crates_categories: Vec<CrateCategoryRow>,
+    crates_keywords: Vec<CrateKeywordRow>,
+}
+
+impl Tables {
+    fn into_metadata(self) -> HashMap<String, CrateMetadata> {
+        let mut by_id = self
+            .crates
+            .into_iter()
+            .map(|row| {
+                let metadata = CrateMetadata {
+                    downloads: row.downloads.unwrap_or(0),
+                    ..CrateMetadata::default()
+                };
+                (row.id, (row.name, metadata))
+            })
+            .collect::<HashMap<_, _>>();
+
+        for row in self.downloads {
+            if let Some((_, metadata)) = by_id.get_mut(&row.crate_id) {
+                metadata.downloads = row.downloads;
+            }
+        }
+        for row in self.recent_downloads {
+            if let Some((_, metadata)) = by_id.get_mut(&row.crate_id) {
+                metadata.recent_downloads = row.downloads;
+            }
+        }
+
+        let categories = self
+            .categories
+            .into_iter()
+            .map(|row| (row.id, row.slug))
+            .collect::<HashMap<_, _>>();
+        for row in self.crates_categories {
+            if let (Some((_, metadata)), Some(slug)) = (
+                by_id.get_mut(&row.crate_id),
+                categories.get(&row.category_id),
+            ) {
+                metadata.categories.push(slug.clone());
+            }
+        }
+
+        let keywords = self
+            .keywords
+            .into_iter()
+            .map(|row| (row.id, row.keyword))
+            .collect::<HashMap<_, _>>();
+        for row in self.crates_keywords {
+            if let (Some((_, metadata)), Some(keyword)) =
+                (by_id.get_mut(&row.crate_id), keywords.get(&row.keyword_id))
+            {
+                metadata.keywords.push(keyword.clone());
+            }
+        }
+
+        by_id.into_iter().map(|(_, entry)| entry).collect()
+    }
+}
+
+fn read_table<T: DeserializeOwned, R: Read>(table: R) -> Fallible<Vec<T>> {
+    let mut reader = ::csv::Reader::from_reader(table);
+    let mut rows = Vec::new();
+    for row in reader.deserialize() {
+        rows.push(row?);
+    }
+    Ok(rows)
+}
+
+/// Extract the metadata of every crate from the tarball of the database dump.
+fn parse_dump<R: Read>(tarball: R) -> Fallible<HashMap<String, CrateMetadata>> {
+    let mut tables = Tables::default();
+    let mut archive = Archive::new(GzDecoder::new(tarball));
+    for entry in archive.entries()? {
+        let entry = entry?;
+        let file_name = match entry.path()?.file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => continue,
+        };
+        match file_name.as_str() {
+            "crates.csv" => tables.crates = read_table(entry)?,
+            "crate_downloads.csv" => tables.downloads = read_table(entry)?,
+            "recent_crate_downloads.csv" => tables.recent_downloads = read_table(entry)?,
+            "categories.csv" => tables.categories = read_table(entry)?,
+            "keywords.csv" => tables.keywords = read_table(entry)?,
+            "crates_categories.csv" => tables.crates_categories = read_table(entry)?,
+            "crates_keywords.csv" => tables.crates_keywords = read_table(entry)?,
+            _ => {}
+        }
+    }
+
+    if tables.crates.is_empty() {
+        bail!("the crates.io database dump doesn't contain any crate");
+    }
+    Ok(tables.into_metadata())
+}
+
+/// Load the metadata of the crates.io crates from its cache, downloading the database dump again
+/// only if the cache is older than the downloads ranking is allowed to be.
+pub(crate) fn crates_metadata(config: &RegistryConfig) -> Fallible<HashMap<String, CrateMetadata>> {
+    let max_age = ChronoDuration::hours(config.downloads_max_age as i64);
+    if METADATA_CACHE.exists() {
+        let cache: MetadataCache = serde_json::from_reader(File::open(&*METADATA_CACHE)?)?;
+        if Utc::now() - cache.fetched_at < max_age {
+            info!(
+                "using the crates.io metadata fetched at {}",
+                cache.fetched_at
+            );
+            return Ok(cache.crates);
+        }
+    }
+
+    info!("downloading the crates.io database dump from {}", DUMP_URL);
+    let resp = utils::http::get_sync(DUMP_URL)
+        .with_context(|_| format!("failed to download the database dump from {}", DUMP_URL))?;
+    let cache = MetadataCache {
+        fetched_at: Utc::now(),
+        crates: parse_dump(resp)?,
+    };
+    fs::create_dir_all(&*WORK_DIR)?;
+    serde_json::to_writer(File::create(&*METADATA_CACHE)?, &cache)?;
+    Ok(cache.crates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_dump, CrateMetadata};
+    use flate2::{write::GzEncoder, Compression};
+    use tar::{Builder, Header};
+
+    #[test]
+    fn test_parse_dump() {
+        let mut builder = Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        for (name, content) in &[
+            ("crates_keywords.csv", "crate_id,keyword_id\n1,10\n"),
+            (
+                "crates.csv",
+                "id,name,description\n1,tokio,async\n2,heapless,no alloc\n",
+            ),
+            ("crate_downloads.csv", "crate_id,downloads\n1,5000\n2,300\n"),
+            ("recent_crate_downloads.csv", "crate_id,downloads\n1,1000\n"),
+            ("categories.csv", "category,id,slug\nEmbedded,20,embedded\n"),
+            (
+                "crates_categories.csv",
+                "category_id,crate_id\n20,2\n20,3\n",
+            ),
+            ("keywords.csv", "id,keyword\n10,async\n"),
+            ("versions.csv", "id,crate_id,num\n1,1,1.0.0\n"),
+        ] {
+            let mut header = Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(
+                    &mut header,
+                    format!("2026-10-14/data/{}", name),
+                    content.as_bytes(),
+                )
+                .unwrap();
+        }
+        let tarball = builder.into_inner().unwrap().finish().unwrap();
+
+        let metadata = parse_dump(tarball.as_slice()).unwrap();
+        assert_eq!(metadata.len(), 2);
+        assert_eq!(
+            metadata["tokio"],
+            CrateMetadata {
+                downloads: 5000,
+                recent_downloads: 1000,
+                categories: Vec::new(),
+                keywords: vec!["async".into()],
+            }
+        );
+        assert_eq!(
+            metadata["heapless"],
+            CrateMetadata {
+                downloads: 300,
+                recent_downloads: 0,
+                categories: vec!["embedded".into()],
+                keywords: Vec::new(),
+            }
+        );
+    }
+}
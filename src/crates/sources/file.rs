@@ -0,0 +1,167 @@
+use crate::crates::{lists::List, Crate, GitHubRepo, GitRepo, RegistryCrate};
+use crate::prelude::*;
+use std::path::{Path, PathBuf};
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ListFile {
+    crates: Vec<ListEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ListEntry {
+    Registry {
+        name: String,
+        version: String,
+    },
+    Git {
+        git: String,
+        #[serde(default)]
+        subpath: Option<String>,
+    },
+    Path {
+        path: PathBuf,
+    },
+}
+
+/// List of crates curated by hand, read from a TOML or JSON file:
+///
+/// ```toml
+/// [[crates]]
+/// name = "lazy_static"
+/// version = "1.4.0"
+///
+/// [[crates]]
+/// git = "https://github.com/rust-lang/cargo"
+/// subpath = "crates/cargo-util"
+///
+/// [[crates]]
+/// path = "../my-crate"
+/// ```
+///
+/// Relative paths are resolved from the directory containing the file.
+pub(crate) struct FileList {
+    source: PathBuf,
+}
+
+impl FileList {
+    pub(crate) fn new(source: &Path) -> Self {
+        FileList {
+            source: source.to_path_buf(),
+        }
+    }
+
+    fn parse(&self, content: &str) -> Fallible<Vec<Crate>> {
+        let is_json = self.source.extension().map_or(false, |ext| ext == "json");
+        let file: ListFile = if is_json {
+            serde_json::from_str(content)?
+        } else {
+            toml::from_str(content)?
+        };
+
+        let base = self.source.parent().unwrap_or_else(|| Path::new(""));
+        file.crates
+            .into_iter()
+            .map(|entry| {
+                Ok(match entry {
+                    ListEntry::Registry { name, version } => {
+                        Crate::Registry(RegistryCrate { name, version })
+                    }
+                    ListEntry::Git { git, subpath } => match subpath {
+                        Some(subpath) => Crate::git_subpath(&git, &subpath)?,
+                        None if git.starts_with("https://github.com/") => {
+                            Crate::GitHub(git.trim_end_matches('/').parse::<GitHubRepo>()?)
+                        }
+                        None => Crate::Git(GitRepo {
+                            url: git,
+                            sha: None,
+                            subpath: None,
+                        }),
+                    },
+                    ListEntry::Path { path } => {
+                        let path = base.join(path);
+                        let path = path
+                            .to_str()
+                            .ok_or_else(|| err_msg("invalid UTF-8 in the path of a crate"))?;
+                        Crate::Path(path.to_string())
+                    }
+                })
+            })
+            .collect()
+    }
+}
+
+impl List for FileList {
+    const NAME: &'static str = "file";
+
+    fn fetch(&self) -> Fallible<Vec<Crate>> {
+        info!("loading the list of crates in {}", self.source.display());
+        let content = ::std::fs::read_to_string(&self.source)
+            .with_context(|_| format!("failed to read {}", self.source.display()))?;
+        Ok(self
+            .parse(&content)
+            .with_context(|_| format!("invalid list of crates in {}", self.source.display()))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FileList;
+    use crate::crates::lists::List;
+    use crate::crates::{Crate, GitHubRepo, GitRepo, RegistryCrate};
+    use std::path::Path;
+
+    #[test]
+    fn test_parse() {
+        let list = FileList::new(Path::new("lists/curated.toml"));
+        let crates = list
+            .parse(
+                r#"
+                [[crates]]
+                name = "lazy_static"
+                version = "1.4.0"
+
+                [[crates]]
+                git = "https://github.com/rust-lang/crater/"
+
+                [[crates]]
+                git = "https://gitlab.com/org/repo"
+                subpath = "crates/core"
+
+                [[crates]]
+                path = "my-crate"
+                "#,
+            )
+            .unwrap();
+        assert_eq!(
+            crates,
+            vec![
+                Crate::Registry(RegistryCrate {
+                    name: "lazy_static".into(),
+                    version: "1.4.0".into(),
+                }),
+                Crate::GitHub(GitHubRepo {
+                    org: "rust-lang".into(),
+                    name: "crater".into(),
+                    sha: None,
+                }),
+                Crate::Git(GitRepo {
+                    url: "https://gitlab.com/org/repo".into(),
+                    sha: None,
+                    subpath: Some("crates/core".into()),
+                }),
+                Crate::Path("lists/my-crate".into()),
+            ]
+        );
+
+        let list = FileList::new(Path::new("curated.json"));
+        let crates = list
+            .parse(r#"{"crates": [{"name": "syn", "version": "1.0.0"}]}"#)
+            .unwrap();
+        assert_eq!(crates.len(), 1);
+
+        assert!(list.parse(r#"{"crates": [{"name": "syn"}]}"#).is_err());
+        assert!(list.fetch().is_err());
+    }
+}
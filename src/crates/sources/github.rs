@@ -1,10 +1,8 @@
-use crate::crates::{lists::List, Crate};
+use crate::config::{default_github_url, GitHubConfig};
+use crate::crates::{lists::List, Crate, GitRepo};
 use crate::prelude::*;
-use std::borrow::Cow;
 use std::str::FromStr;
 
-static CACHED_LIST: &str =
-    "https://raw.githubusercontent.com/rust-lang/rust-repos/master/data/github.csv";
 const DUMMY_ORG: &str = "ghost";
 const DUMMY_NAME: &str = "missing";
 
@@ -16,13 +14,33 @@ struct ListRepo {
 }
 
 pub(crate) struct GitHubList {
-    source: Cow<'static, str>,
+    source: String,
+    url: String,
 }
 
-impl Default for GitHubList {
-    fn default() -> Self {
+impl GitHubList {
+    pub(crate) fn new(config: &GitHubConfig) -> Self {
         GitHubList {
-            source: CACHED_LIST.into(),
+            source: config.repos_list.clone(),
+            url: config.url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Repositories hosted on a GitHub Enterprise instance are added as plain git repositories,
+    /// as the rest of Crater assumes `Crate::GitHub` lives on github.com.
+    fn repo(&self, org: &str, name: &str) -> Crate {
+        if self.url == default_github_url() {
+            Crate::GitHub(GitHubRepo {
+                org: org.to_string(),
+                name: name.to_string(),
+                sha: None,
+            })
+        } else {
+            Crate::Git(GitRepo {
+                url: format!("{}/{}/{}", self.url, org, name),
+                sha: None,
+                subpath: None,
+            })
         }
     }
 }
@@ -52,11 +70,7 @@ impl List for GitHubList {
             let trailing = name_parts.next();
 
             if let (Some(org), Some(name), None) = (org, name, trailing) {
-                list.push(Crate::GitHub(GitHubRepo {
-                    org: org.to_string(),
-                    name: name.to_string(),
-                    sha: None,
-                }));
+                list.push(self.repo(org, name));
             } else {
                 warn!("skipping malformed repo name: {}", line.name);
             }
@@ -78,6 +92,10 @@ impl GitHubRepo {
         format!("{}/{}", self.org, self.name)
     }
 
+    pub(crate) fn clone_url(&self) -> String {
+        format!("https://github.com/{}", self.slug())
+    }
+
     pub(crate) fn dummy() -> GitHubRepo {
         GitHubRepo {
             org: DUMMY_ORG.to_string(),
@@ -120,9 +138,37 @@ impl FromStr for GitHubRepo {
 
 #[cfg(test)]
 mod tests {
-    use super::GitHubRepo;
+    use super::{GitHubList, GitHubRepo};
+    use crate::config::GitHubConfig;
+    use crate::crates::{Crate, GitRepo};
     use std::str::FromStr;
 
+    #[test]
+    fn test_list_repo() {
+        let list = GitHubList::new(&GitHubConfig::default());
+        assert_eq!(
+            list.repo("rust-lang", "crater"),
+            Crate::GitHub(GitHubRepo {
+                org: "rust-lang".to_string(),
+                name: "crater".to_string(),
+                sha: None,
+            })
+        );
+
+        let list = GitHubList::new(&GitHubConfig {
+            url: "https://ghe.example.com/".to_string(),
+            ..GitHubConfig::default()
+        });
+        assert_eq!(
+            list.repo("tools", "builder"),
+            Crate::Git(GitRepo {
+                url: "https://ghe.example.com/tools/builder".to_string(),
+                sha: None,
+                subpath: None,
+            })
+        );
+    }
+
     #[test]
     fn test_from_str() {
         assert_eq!(
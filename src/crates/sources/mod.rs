@@ -1,3 +1,5 @@
+pub(in crate::crates) mod dump;
+pub(in crate::crates) mod file;
 pub(in crate::crates) mod github;
 pub(in crate::crates) mod local;
 pub(in crate::crates) mod registry;
@@ -1,62 +1,385 @@
+use crate::config::RegistryConfig;
 use crate::crates::{lists::List, Crate};
 use crate::dirs::WORK_DIR;
 use crate::prelude::*;
+use crate::utils;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
 use crates_index::Index;
-use std::collections::HashMap;
-use std::fs::{self};
+use crossbeam_utils::thread::scope;
+use http::{Method, StatusCode};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
 
-pub(crate) struct RegistryList;
+/// Number of crates ranked by their download count, as crates.io only returns them in pages of
+/// 100 crates. The other crates are ranked by the number of crates depending on them.
+const DOWNLOADS_RANKED: usize = 10_000;
+const DOWNLOADS_PAGE_SIZE: usize = 100;
+/// Interval between the requests to the crates.io API, to respect its crawler policy.
+const DOWNLOADS_INTERVAL: Duration = Duration::from_secs(1);
 
-impl List for RegistryList {
-    const NAME: &'static str = "registry";
+const SPARSE_INDEX_URL: &str = "https://index.crates.io";
+/// Number of threads downloading the index files of the crates from the sparse index.
+const SPARSE_INDEX_THREADS: usize = 16;
 
-    fn fetch(&self) -> Fallible<Vec<Crate>> {
-        let mut list = Vec::new();
-        let mut counts = HashMap::new();
+lazy_static! {
+    static ref GIT_INDEX_DIR: PathBuf = WORK_DIR.join("crates.io-index");
+    static ref DOWNLOADS_CACHE: PathBuf = WORK_DIR.join("crates.io-downloads.json");
+    static ref DEPENDENCIES_CACHE: PathBuf = WORK_DIR.join("crates.io-dependencies.json");
+}
 
-        fs::create_dir_all(&*WORK_DIR)?;
-        let index = Index::new(WORK_DIR.join("crates.io-index"));
+#[derive(Deserialize)]
+struct CratesResponse {
+    crates: Vec<CratesResponseItem>,
+}
+
+#[derive(Deserialize)]
+struct CratesResponseItem {
+    name: String,
+}
+
+/// Cached names of the most downloaded crates, which are refreshed less often than the index.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct DownloadsCache {
+    fetched_at: DateTime<Utc>,
+    crates: Vec<String>,
+}
+
+/// Line of the index file of a crate, describing one of its versions.
+#[derive(Deserialize)]
+struct IndexEntry {
+    vers: String,
+    deps: Vec<IndexDependency>,
+    yanked: bool,
+}
+
+#[derive(Deserialize)]
+struct IndexDependency {
+    name: String,
+    /// Name of the crate, if the dependency was renamed.
+    package: Option<String>,
+}
+
+/// Latest non-yanked version of a crate, with the names of the crates it depends on.
+struct LatestVersion {
+    name: String,
+    version: String,
+    dependencies: Vec<String>,
+}
+
+pub(crate) struct RegistryList {
+    sparse_index: bool,
+    downloads_max_age: ChronoDuration,
+}
+
+impl RegistryList {
+    pub(crate) fn new(config: &RegistryConfig) -> Self {
+        RegistryList {
+            sparse_index: config.sparse_index,
+            downloads_max_age: ChronoDuration::hours(config.downloads_max_age as i64),
+        }
+    }
+
+    /// Get the names of the most downloaded crates from crates.io, starting from the most
+    /// downloaded one.
+    fn fetch_downloads_ranking(&self) -> Fallible<Vec<String>> {
+        let mut ranking = Vec::new();
+        for page in 1..=DOWNLOADS_RANKED / DOWNLOADS_PAGE_SIZE {
+            let url = format!(
+                "https://crates.io/api/v1/crates?sort=downloads&per_page={}&page={}",
+                DOWNLOADS_PAGE_SIZE, page
+            );
+            let resp: CratesResponse = utils::http::get_sync(&url)?.json()?;
+            let last_page = resp.crates.len() < DOWNLOADS_PAGE_SIZE;
+            ranking.extend(resp.crates.into_iter().map(|krate| krate.name));
+            if last_page {
+                break;
+            }
+
+            thread::sleep(DOWNLOADS_INTERVAL);
+        }
+
+        Ok(ranking)
+    }
+
+    /// Load the downloads ranking from its cache, fetching it again only if it's too old.
+    fn downloads_ranking(&self) -> Fallible<Vec<String>> {
+        if DOWNLOADS_CACHE.exists() {
+            let cache: DownloadsCache = serde_json::from_reader(File::open(&*DOWNLOADS_CACHE)?)?;
+            if Utc::now() - cache.fetched_at < self.downloads_max_age {
+                info!(
+                    "using the downloads ranking fetched at {}",
+                    cache.fetched_at
+                );
+                return Ok(cache.crates);
+            }
+        }
+
+        let cache = DownloadsCache {
+            fetched_at: Utc::now(),
+            crates: self.fetch_downloads_ranking()?,
+        };
+        serde_json::to_writer(File::create(&*DOWNLOADS_CACHE)?, &cache)?;
+        Ok(cache.crates)
+    }
+
+    fn fetch_git_index(&self) -> Fallible<Vec<LatestVersion>> {
+        let index = Index::new(GIT_INDEX_DIR.clone());
         index.retrieve_or_update().to_failure()?;
 
+        let mut latest = Vec::new();
         for krate in index.crates() {
             // The versions() method returns the list of published versions starting from the
-            // first one, so its output is reversed to check the latest first
-            for version in krate.versions().iter().rev() {
-                // Try every version until we find a non-yanked one. If all the versions are
-                // yanked the crate is automatically skipped
-                if !version.is_yanked() {
-                    // Increment the counters of this crate's dependencies
-                    for dependency in version.dependencies() {
-                        let count = counts.entry(dependency.name().to_string()).or_insert(0);
-                        *count += 1;
-                    }
+            // first one, so its output is reversed to check the latest first. If all the
+            // versions are yanked the crate is automatically skipped
+            if let Some(version) = krate.versions().iter().rev().find(|v| !v.is_yanked()) {
+                latest.push(LatestVersion {
+                    name: krate.name().to_string(),
+                    version: version.version().to_string(),
+                    dependencies: version
+                        .dependencies()
+                        .iter()
+                        .map(|dep| dep.name().to_string())
+                        .collect(),
+                });
+            }
+        }
+
+        Ok(latest)
+    }
+
+    /// Fetch the crates from the sparse index. As the sparse index can't list its crates, only
+    /// the crates in the downloads ranking are fetched.
+    fn fetch_sparse_index(&self, names: &[String]) -> Fallible<Vec<LatestVersion>> {
+        if names.is_empty() {
+            bail!("the sparse index can only be used if the downloads ranking is available");
+        }
 
-                    list.push(Crate::Registry(RegistryCrate {
-                        name: krate.name().to_string(),
-                        version: version.version().to_string(),
-                    }));
-                    break;
+        let chunk_size = (names.len() + SPARSE_INDEX_THREADS - 1) / SPARSE_INDEX_THREADS;
+        scope(|scope| -> Fallible<Vec<LatestVersion>> {
+            let mut threads = Vec::new();
+            for (i, chunk) in names.chunks(chunk_size).enumerate() {
+                let join = scope.builder().name(format!("sparse-index-{}", i)).spawn(
+                    move || -> Fallible<Vec<LatestVersion>> {
+                        let mut latest = Vec::new();
+                        for name in chunk {
+                            latest.extend(fetch_sparse_index_file(name)?);
+                        }
+                        Ok(latest)
+                    },
+                )?;
+                threads.push(join);
+            }
+
+            let mut latest = Vec::new();
+            for thread in threads {
+                match thread.join() {
+                    Ok(res) => latest.append(&mut res?),
+                    Err(panic) => {
+                        utils::report_panic(&panic);
+                        bail!("a thread fetching the sparse index panicked");
+                    }
                 }
             }
-        }
+            Ok(latest)
+        })
+    }
+}
+
+impl List for RegistryList {
+    const NAME: &'static str = "registry";
+
+    fn fetch(&self) -> Fallible<Vec<Crate>> {
+        fs::create_dir_all(&*WORK_DIR)?;
+
+        // Without the ranking the list is still usable, just ordered by reverse dependencies
+        let downloads = match self.downloads_ranking() {
+            Ok(downloads) => downloads,
+            Err(err) => {
+                utils::report_failure(&err.context("failed to fetch the crates.io downloads"));
+                Vec::new()
+            }
+        };
 
-        // Ensure the list is sorted by popularity
-        list.sort_by(|a, b| {
-            if let (Crate::Registry(ref a), Crate::Registry(ref b)) = (a, b) {
-                let count_a = counts.get(&a.name).cloned().unwrap_or(0);
-                let count_b = counts.get(&b.name).cloned().unwrap_or(0);
-                count_b.cmp(&count_a)
-            } else {
-                panic!("non-registry crate produced in the registry list");
+        let latest = if self.sparse_index {
+            self.fetch_sparse_index(&downloads)?
+        } else {
+            self.fetch_git_index()?
+        };
+
+        let mut counts = HashMap::new();
+        let mut dependencies = BTreeMap::new();
+        let mut list = Vec::new();
+        for krate in latest {
+            // Increment the counters of this crate's dependencies
+            for dependency in &krate.dependencies {
+                *counts.entry(dependency.clone()).or_insert(0) += 1;
             }
-        });
 
+            list.push(Crate::Registry(RegistryCrate {
+                name: krate.name.clone(),
+                version: krate.version,
+            }));
+            dependencies.insert(krate.name, krate.dependencies);
+        }
+
+        // Stored to select the reverse dependencies of a crate without another copy of the index
+        serde_json::to_writer(File::create(&*DEPENDENCIES_CACHE)?, &dependencies)?;
+
+        sort_by_popularity(&mut list, &downloads, &counts);
         Ok(list)
     }
 }
 
+/// Path of the index file of a crate, relative to the root of the index.
+fn index_file_path(name: &str) -> String {
+    let name = name.to_lowercase();
+    match name.len() {
+        1 => format!("1/{}", name),
+        2 => format!("2/{}", name),
+        3 => format!("3/{}/{}", &name[..1], name),
+        _ => format!("{}/{}/{}", &name[..2], &name[2..4], name),
+    }
+}
+
+/// Find the latest non-yanked version of a crate in its index file.
+fn parse_index_file(name: &str, content: &str) -> Fallible<Option<LatestVersion>> {
+    let mut latest = None;
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        let entry: IndexEntry = serde_json::from_str(line)
+            .with_context(|_| format!("invalid index file of the {} crate", name))?;
+        // Versions are listed in the order they were published
+        if !entry.yanked {
+            latest = Some(entry);
+        }
+    }
+
+    Ok(latest.map(|entry| LatestVersion {
+        name: name.to_string(),
+        version: entry.vers,
+        dependencies: entry
+            .deps
+            .into_iter()
+            .map(|dep| dep.package.unwrap_or(dep.name))
+            .collect(),
+    }))
+}
+
+fn fetch_sparse_index_file(name: &str) -> Fallible<Option<LatestVersion>> {
+    let url = format!("{}/{}", SPARSE_INDEX_URL, index_file_path(name));
+    let mut resp = utils::http::prepare_sync(Method::GET, &url).send()?;
+    match resp.status() {
+        StatusCode::OK => parse_index_file(name, &resp.text()?),
+        // The crate was deleted after the downloads ranking was fetched
+        StatusCode::NOT_FOUND => Ok(None),
+        status => bail!("request to {} returned status code {}", url, status),
+    }
+}
+
+/// Sort the crates starting from the most downloaded ones, and then from the ones with the most
+/// reverse dependencies.
+fn sort_by_popularity(list: &mut [Crate], downloads: &[String], counts: &HashMap<String, u32>) {
+    let ranks = downloads
+        .iter()
+        .enumerate()
+        .map(|(rank, name)| (name.as_str(), rank))
+        .collect::<HashMap<_, _>>();
+
+    list.sort_by_cached_key(|krate| {
+        if let Crate::Registry(ref krate) = krate {
+            let rank = ranks
+                .get(krate.name.as_str())
+                .cloned()
+                .unwrap_or(std::usize::MAX);
+            let count = counts.get(&krate.name).cloned().unwrap_or(0);
+            (rank, std::cmp::Reverse(count))
+        } else {
+            panic!("non-registry crate produced in the registry list");
+        }
+    });
+}
+
+/// Get the names of the crates the latest version of each registry crate depends on, as of the
+/// last time the registry list was updated.
+pub(crate) fn dependencies() -> Fallible<HashMap<String, Vec<String>>> {
+    if !DEPENDENCIES_CACHE.exists() {
+        bail!("the registry list was never fetched, run `crater create-lists` first");
+    }
+
+    Ok(serde_json::from_reader(File::open(&*DEPENDENCIES_CACHE)?)?)
+}
+
+/// Get the names of the crates whose latest version depends on `name`, as of the last time the
+/// registry list was updated.
+pub(crate) fn reverse_dependencies(name: &str) -> Fallible<HashSet<String>> {
+    Ok(dependencies()?
+        .into_iter()
+        .filter(|(_, deps)| deps.iter().any(|dep| dep == name))
+        .map(|(krate, _)| krate)
+        .collect())
+}
+
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize, Clone)]
 pub struct RegistryCrate {
     pub name: String,
     pub version: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{index_file_path, parse_index_file, sort_by_popularity, RegistryCrate};
+    use crate::crates::Crate;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_sort_by_popularity() {
+        let reg = |name: &str| {
+            Crate::Registry(RegistryCrate {
+                name: name.into(),
+                version: "1.0.0".into(),
+            })
+        };
+        let mut list = vec![reg("a"), reg("b"), reg("c"), reg("d"), reg("e")];
+        let downloads = vec!["d".to_string(), "b".to_string()];
+        let counts = vec![
+            ("a".to_string(), 1),
+            ("c".to_string(), 5),
+            ("b".to_string(), 10),
+        ]
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+
+        sort_by_popularity(&mut list, &downloads, &counts);
+        assert_eq!(list, vec![reg("d"), reg("b"), reg("c"), reg("a"), reg("e")]);
+    }
+
+    #[test]
+    fn test_index_file_path() {
+        assert_eq!(index_file_path("a"), "1/a");
+        assert_eq!(index_file_path("cc"), "2/cc");
+        assert_eq!(index_file_path("syn"), "3/s/syn");
+        assert_eq!(index_file_path("Serde_JSON"), "se/rd/serde_json");
+    }
+
+    #[test]
+    fn test_parse_index_file() {
+        let content = r#"
+{"name":"foo","vers":"0.1.0","deps":[],"yanked":false}
+{"name":"foo","vers":"0.2.0","deps":[{"name":"bar"},{"name":"s","package":"serde"}],"yanked":false}
+{"name":"foo","vers":"0.3.0","deps":[],"yanked":true}
+"#;
+        let latest = parse_index_file("foo", content).unwrap().unwrap();
+        assert_eq!(latest.version, "0.2.0");
+        assert_eq!(
+            latest.dependencies,
+            vec!["bar".to_string(), "serde".to_string()]
+        );
+
+        let yanked = r#"{"name":"foo","vers":"0.1.0","deps":[],"yanked":true}"#;
+        assert!(parse_index_file("foo", yanked).unwrap().is_none());
+        assert!(parse_index_file("foo", "not json").is_err());
+    }
+}
@@ -12,6 +12,8 @@ enum MigrationKind {
     Code(Box<dyn Fn(&Transaction) -> ::rusqlite::Result<()>>),
 }
 
+/// Migrations of the SQLite schema. The PostgreSQL backend has its own migrations, in
+/// `postgres.rs`, which must be kept in sync with these.
 fn migrations() -> Vec<(&'static str, MigrationKind)> {
     let mut migrations = Vec::new();
 
@@ -363,6 +365,350 @@ fn migrations() -> Vec<(&'static str, MigrationKind)> {
         })),
     ));
 
+    migrations.push((
+        "add_experiment_crates_lease",
+        MigrationKind::SQL(
+            "
+            ALTER TABLE experiment_crates ADD COLUMN lease_expires_at DATETIME;
+            ALTER TABLE experiment_crates ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0;
+            ",
+        ),
+    ));
+
+    migrations.push((
+        "add_experiment_field_cargo_config",
+        MigrationKind::SQL(
+            "
+            ALTER TABLE experiments ADD COLUMN cargo_config TEXT;
+            ",
+        ),
+    ));
+
+    migrations.push((
+        "create_triage_decisions",
+        MigrationKind::SQL(
+            "
+            CREATE TABLE triage_decisions (
+                experiment TEXT NOT NULL,
+                crate TEXT NOT NULL,
+                decision TEXT NOT NULL,
+                triager TEXT NOT NULL,
+                created_at DATETIME NOT NULL,
+
+                PRIMARY KEY (experiment, crate) ON CONFLICT REPLACE,
+                FOREIGN KEY (experiment) REFERENCES experiments(name) ON DELETE CASCADE
+            );
+            ",
+        ),
+    ));
+
+    migrations.push((
+        "add_results_log_streamed",
+        MigrationKind::SQL(
+            "
+            ALTER TABLE results ADD COLUMN log_streamed BOOLEAN NOT NULL DEFAULT 0;
+            ",
+        ),
+    ));
+
+    migrations.push((
+        "add_experiment_field_progress_commented_at",
+        MigrationKind::SQL(
+            "
+            ALTER TABLE experiments ADD COLUMN progress_commented_at DATETIME;
+            ",
+        ),
+    ));
+
+    migrations.push((
+        "add_experiment_field_expected_regressions",
+        MigrationKind::SQL(
+            "
+            ALTER TABLE experiments ADD COLUMN expected_regressions TEXT NOT NULL DEFAULT '[]';
+            ",
+        ),
+    ));
+
+    migrations.push((
+        "create_crate_notes",
+        MigrationKind::SQL(
+            "
+            CREATE TABLE crate_notes (
+                crate TEXT NOT NULL,
+                note TEXT NOT NULL,
+                author TEXT NOT NULL,
+                updated_at DATETIME NOT NULL,
+
+                PRIMARY KEY (crate) ON CONFLICT REPLACE
+            );
+            ",
+        ),
+    ));
+
+    migrations.push((
+        "add_results_memory_limit",
+        MigrationKind::SQL(
+            "
+            ALTER TABLE results ADD COLUMN memory_limit INTEGER;
+            ",
+        ),
+    ));
+
+    migrations.push((
+        "add_experiment_field_env",
+        MigrationKind::SQL(
+            "
+            ALTER TABLE experiments ADD COLUMN env TEXT NOT NULL DEFAULT '{}';
+            ",
+        ),
+    ));
+
+    migrations.push((
+        "add_experiment_field_deny_lints",
+        MigrationKind::SQL(
+            "
+            ALTER TABLE experiments ADD COLUMN deny_lints TEXT NOT NULL DEFAULT '[]';
+            ",
+        ),
+    ));
+
+    migrations.push((
+        "add_experiment_field_cargo_update",
+        MigrationKind::SQL(
+            "
+            ALTER TABLE experiments ADD COLUMN cargo_update INTEGER NOT NULL DEFAULT 0;
+            ",
+        ),
+    ));
+
+    migrations.push((
+        "add_experiment_field_failure_threshold",
+        MigrationKind::SQL(
+            "
+            ALTER TABLE experiments ADD COLUMN failure_threshold TEXT;
+            ",
+        ),
+    ));
+
+    migrations.push((
+        "create_result_submissions",
+        MigrationKind::SQL(
+            "
+            CREATE TABLE result_submissions (
+                experiment TEXT NOT NULL,
+                idempotency_key TEXT NOT NULL,
+                created_at DATETIME NOT NULL,
+
+                PRIMARY KEY (experiment, idempotency_key),
+                FOREIGN KEY (experiment) REFERENCES experiments(name) ON DELETE CASCADE
+            );
+            ",
+        ),
+    ));
+
+    migrations.push((
+        "add_experiment_field_reuse_baseline",
+        MigrationKind::SQL(
+            "
+            ALTER TABLE experiments ADD COLUMN reuse_baseline INTEGER NOT NULL DEFAULT 0;
+            ",
+        ),
+    ));
+
+    migrations.push((
+        "create_baseline_bank",
+        MigrationKind::SQL(
+            "
+            CREATE TABLE baseline_bank (
+                toolchain TEXT NOT NULL,
+                mode TEXT NOT NULL,
+                cap_lints TEXT NOT NULL,
+                crate TEXT NOT NULL,
+                result TEXT NOT NULL,
+                log BLOB NOT NULL,
+                encoding TEXT NOT NULL,
+                experiment TEXT NOT NULL,
+                banked_at DATETIME NOT NULL,
+
+                PRIMARY KEY (toolchain, mode, cap_lints, crate) ON CONFLICT REPLACE
+            );
+            ",
+        ),
+    ));
+
+    migrations.push((
+        "add_results_sandbox_counters",
+        MigrationKind::SQL(
+            "
+            ALTER TABLE results ADD COLUMN sandbox_counters TEXT;
+            ",
+        ),
+    ));
+
+    migrations.push((
+        "add_experiment_canary",
+        MigrationKind::SQL(
+            "
+            ALTER TABLE experiments ADD COLUMN canary INTEGER NOT NULL DEFAULT 0;
+            ALTER TABLE experiment_crates ADD COLUMN held INTEGER NOT NULL DEFAULT 0;
+            ",
+        ),
+    ));
+
+    migrations.push((
+        "add_experiment_field_test_runner",
+        MigrationKind::SQL(
+            "
+            ALTER TABLE experiments ADD COLUMN test_runner TEXT NOT NULL DEFAULT 'cargo';
+            ",
+        ),
+    ));
+
+    migrations.push((
+        "add_experiment_field_namespace",
+        MigrationKind::SQL(
+            "
+            ALTER TABLE experiments ADD COLUMN namespace TEXT;
+            ",
+        ),
+    ));
+
+    migrations.push((
+        "add_experiment_survey",
+        MigrationKind::SQL(
+            "
+            ALTER TABLE experiments ADD COLUMN survey INTEGER NOT NULL DEFAULT 0;
+            ",
+        ),
+    ));
+
+    migrations.push((
+        "add_experiment_approved_by",
+        MigrationKind::SQL(
+            "
+            ALTER TABLE experiments ADD COLUMN approved_by TEXT;
+            ",
+        ),
+    ));
+
+    migrations.push((
+        "add_sandbox_image",
+        MigrationKind::SQL(
+            "
+            ALTER TABLE results ADD COLUMN sandbox_image TEXT;
+
+            CREATE TABLE baseline_bank_new (
+                toolchain TEXT NOT NULL,
+                mode TEXT NOT NULL,
+                cap_lints TEXT NOT NULL,
+                crate TEXT NOT NULL,
+                sandbox_image TEXT NOT NULL DEFAULT '',
+                result TEXT NOT NULL,
+                log BLOB NOT NULL,
+                encoding TEXT NOT NULL,
+                experiment TEXT NOT NULL,
+                banked_at DATETIME NOT NULL,
+
+                PRIMARY KEY (toolchain, mode, cap_lints, crate, sandbox_image) ON CONFLICT REPLACE
+            );
+
+            INSERT INTO baseline_bank_new (
+                toolchain, mode, cap_lints, crate, result, log, encoding, experiment, banked_at
+            ) SELECT
+                toolchain, mode, cap_lints, crate, result, log, encoding, experiment, banked_at
+            FROM baseline_bank;
+
+            DROP TABLE baseline_bank;
+            ALTER TABLE baseline_bank_new RENAME TO baseline_bank;
+            ",
+        ),
+    ));
+
+    migrations.push((
+        "create_log_uploads",
+        MigrationKind::SQL(
+            "
+            CREATE TABLE log_uploads (
+                experiment TEXT NOT NULL,
+                upload_id TEXT NOT NULL,
+                position INTEGER NOT NULL,
+                data BLOB NOT NULL,
+                created_at DATETIME NOT NULL,
+
+                PRIMARY KEY (experiment, upload_id, position),
+                FOREIGN KEY (experiment) REFERENCES experiments(name) ON DELETE CASCADE
+            );
+            ",
+        ),
+    ));
+
+    migrations.push((
+        "add_experiment_field_parent",
+        MigrationKind::SQL(
+            "
+            ALTER TABLE experiments ADD COLUMN parent TEXT;
+            ",
+        ),
+    ));
+
+    migrations.push((
+        "create_ecosystem_canary",
+        MigrationKind::SQL(
+            "
+            CREATE TABLE ecosystem_canary (
+                experiment TEXT PRIMARY KEY NOT NULL,
+                nightly TEXT NOT NULL,
+                sample_offset INTEGER NOT NULL,
+                created_at DATETIME NOT NULL,
+                baseline_failure_rate REAL,
+                failure_rate REAL,
+                checked_at DATETIME
+            );
+            ",
+        ),
+    ));
+
+    migrations.push((
+        "create_dependency_graphs",
+        MigrationKind::SQL(
+            "
+            CREATE TABLE dependency_graphs (
+                experiment TEXT NOT NULL,
+                crate TEXT NOT NULL,
+                toolchain TEXT NOT NULL,
+                graph TEXT NOT NULL,
+
+                PRIMARY KEY (experiment, crate, toolchain) ON CONFLICT REPLACE,
+                FOREIGN KEY (experiment) REFERENCES experiments(name) ON DELETE CASCADE
+            );
+            ",
+        ),
+    ));
+
+    migrations.push((
+        "create_build_durations",
+        MigrationKind::SQL(
+            "
+            CREATE TABLE build_durations (
+                crate TEXT NOT NULL,
+                duration INTEGER NOT NULL,
+                recorded_at DATETIME NOT NULL
+            );
+
+            CREATE INDEX build_durations__crate ON build_durations (crate);
+            ",
+        ),
+    ));
+
+    migrations.push((
+        "add_experiment_field_sandbox_image",
+        MigrationKind::SQL(
+            "
+            ALTER TABLE experiments ADD COLUMN sandbox_image TEXT;
+            ",
+        ),
+    ));
+
     migrations
 }
 
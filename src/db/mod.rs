@@ -1,40 +1,76 @@
 mod migrations;
+mod postgres;
+mod sqlite;
+mod types;
 
+pub use self::types::{FromSql, Row, RowIndex, ToSql};
+
+use self::postgres::PostgresConnection;
+use self::sqlite::SqliteConnection;
+use crate::config::{Config, DatabaseBackend};
 use crate::dirs::WORK_DIR;
 use crate::prelude::*;
-use r2d2::{CustomizeConnection, Pool};
+use r2d2::Pool;
+use r2d2_postgres::PostgresConnectionManager;
 use r2d2_sqlite::SqliteConnectionManager;
-use rusqlite::types::ToSql;
-use rusqlite::{Connection, Row, Transaction};
 use std::sync::Arc;
 use std::time::Instant;
 use tempfile::NamedTempFile;
 
 static LEGACY_DATABASE_PATHS: &[&str] = &["server.db"];
 static DATABASE_PATH: &str = "crater.db";
+static DATABASE_URL_ENV: &str = "CRATER_DATABASE_URL";
+#[cfg(test)]
+static TEST_DATABASE_URL_ENV: &str = "CRATER_TEST_DATABASE_URL";
 
-#[derive(Debug)]
-struct ConnectionCustomizer;
+/// Connection to one of the database backends, selected by the `database.backend` option of
+/// `config.toml`. The queries are written in the SQLite dialect, which the other backends
+/// translate to their own.
+pub trait Connection {
+    fn execute(&self, sql: &str, params: &[&dyn ToSql], cached: bool) -> Fallible<usize>;
+    fn exists(&self, sql: &str, params: &[&dyn ToSql]) -> Fallible<bool>;
+    /// Call `f` with every row returned by the query, stopping at the first error.
+    fn query_each(
+        &self,
+        sql: &str,
+        params: &[&dyn ToSql],
+        f: &mut dyn FnMut(&Row) -> Fallible<()>,
+    ) -> Fallible<()>;
+}
 
-impl CustomizeConnection<Connection, ::rusqlite::Error> for ConnectionCustomizer {
-    fn on_acquire(&self, conn: &mut Connection) -> Result<(), ::rusqlite::Error> {
-        conn.execute(
-            "PRAGMA foreign_keys = ON;",
-            ::std::iter::empty::<&dyn ToSql>(),
-        )?;
-        Ok(())
-    }
+#[derive(Clone)]
+enum Backend {
+    Sqlite(Pool<SqliteConnectionManager>),
+    Postgres(Pool<PostgresConnectionManager>),
 }
 
 #[derive(Clone)]
 pub struct Database {
-    pool: Pool<SqliteConnectionManager>,
+    backend: Backend,
     // The tempfile is stored here to drop it after all the connections are closed
     tempfile: Option<Arc<NamedTempFile>>,
 }
 
 impl Database {
-    pub fn open() -> Fallible<Self> {
+    pub fn open(config: &Config) -> Fallible<Self> {
+        match config.database.backend {
+            DatabaseBackend::Sqlite => Database::open_sqlite(),
+            DatabaseBackend::Postgres => {
+                let url = std::env::var(DATABASE_URL_ENV).with_context(|_| {
+                    format!(
+                        "the PostgreSQL database requires {} to be set",
+                        DATABASE_URL_ENV
+                    )
+                })?;
+                Ok(Database {
+                    backend: Backend::Postgres(postgres::pool(&url, config.database.pool_size)?),
+                    tempfile: None,
+                })
+            }
+        }
+    }
+
+    fn open_sqlite() -> Fallible<Self> {
         let path = WORK_DIR.join(DATABASE_PATH);
         if !path.exists() {
             // If the database doesn't exist check if it's present in a legacy path
@@ -55,27 +91,38 @@ impl Database {
 
         let path = WORK_DIR.join(DATABASE_PATH);
         std::fs::create_dir_all(&*WORK_DIR)?;
-        Database::new(SqliteConnectionManager::file(path), None)
+        Database::new_sqlite(SqliteConnectionManager::file(path), None)
     }
 
     #[cfg(test)]
     pub fn temp() -> Fallible<Self> {
         let tempfile = NamedTempFile::new()?;
-        Database::new(
+        Database::new_sqlite(
             SqliteConnectionManager::file(tempfile.path()),
             Some(tempfile),
         )
     }
 
-    fn new(conn: SqliteConnectionManager, tempfile: Option<NamedTempFile>) -> Fallible<Self> {
-        let pool = Pool::builder()
-            .connection_customizer(Box::new(ConnectionCustomizer))
-            .build(conn)?;
-
-        migrations::execute(&mut pool.get()? as &mut Connection)?;
+    /// Open the PostgreSQL database set in `CRATER_TEST_DATABASE_URL`, wiping its content, or
+    /// return `None` if the variable is not set.
+    #[cfg(test)]
+    pub fn temp_postgres() -> Fallible<Option<Self>> {
+        let url = match std::env::var(TEST_DATABASE_URL_ENV) {
+            Ok(url) => url,
+            Err(_) => return Ok(None),
+        };
+        Ok(Some(Database {
+            backend: Backend::Postgres(postgres::temp_pool(&url)?),
+            tempfile: None,
+        }))
+    }
 
+    fn new_sqlite(
+        conn: SqliteConnectionManager,
+        tempfile: Option<NamedTempFile>,
+    ) -> Fallible<Self> {
         Ok(Database {
-            pool,
+            backend: Backend::Sqlite(sqlite::pool(conn)?),
             tempfile: tempfile.map(Arc::new),
         })
     }
@@ -84,92 +131,109 @@ impl Database {
         &self,
         f: F,
     ) -> Fallible<T> {
-        let mut conn = self.pool.get()?;
-        let handle = TransactionHandle {
-            transaction: conn.transaction()?,
-        };
-
-        match f(&handle) {
-            Ok(res) => {
-                handle.commit()?;
-                Ok(res)
+        match &self.backend {
+            Backend::Sqlite(pool) => {
+                let mut conn = pool.get()?;
+                let handle = TransactionHandle {
+                    transaction: Transaction::Sqlite(conn.transaction()?),
+                };
+                handle.run(f)
             }
-            Err(err) => {
-                handle.rollback()?;
-                Err(err)
+            Backend::Postgres(pool) => {
+                let conn = pool.get()?;
+                let handle = TransactionHandle {
+                    transaction: Transaction::Postgres(postgres::transaction(&conn)?),
+                };
+                handle.run(f)
             }
         }
     }
 }
 
+enum Transaction<'a> {
+    Sqlite(rusqlite::Transaction<'a>),
+    Postgres(::postgres::transaction::Transaction<'a>),
+}
+
 pub struct TransactionHandle<'a> {
     transaction: Transaction<'a>,
 }
 
 impl<'a> TransactionHandle<'a> {
+    fn run<T, F: FnOnce(&TransactionHandle) -> Fallible<T>>(self, f: F) -> Fallible<T> {
+        match f(&self) {
+            Ok(res) => {
+                self.commit()?;
+                Ok(res)
+            }
+            Err(err) => {
+                self.rollback()?;
+                Err(err)
+            }
+        }
+    }
+
     pub fn commit(self) -> Fallible<()> {
-        self.transaction.commit()?;
+        match self.transaction {
+            Transaction::Sqlite(t) => t.commit()?,
+            Transaction::Postgres(t) => t.commit()?,
+        }
         Ok(())
     }
 
     pub fn rollback(self) -> Fallible<()> {
-        self.transaction.rollback()?;
+        match self.transaction {
+            Transaction::Sqlite(t) => t.rollback()?,
+            Transaction::Postgres(t) => {
+                t.set_rollback();
+                t.finish()?;
+            }
+        }
         Ok(())
     }
 }
 
 pub trait QueryUtils {
-    fn with_conn<T, F: FnOnce(&Connection) -> Fallible<T>>(&self, f: F) -> Fallible<T>;
+    fn with_conn<T, F: FnOnce(&dyn Connection) -> Fallible<T>>(&self, f: F) -> Fallible<T>;
 
     fn exists(&self, sql: &str, params: &[&dyn ToSql]) -> Fallible<bool> {
-        self.with_conn(|conn| {
-            self.trace(sql, || {
-                let mut prepared = conn.prepare(sql)?;
-                Ok(prepared.exists(params)?)
-            })
-        })
+        self.with_conn(|conn| self.trace(sql, || conn.exists(sql, params)))
     }
 
     fn execute(&self, sql: &str, params: &[&dyn ToSql]) -> Fallible<usize> {
-        self.with_conn(|conn| {
-            self.trace(sql, || {
-                let mut prepared = conn.prepare(sql)?;
-                let changes = prepared.execute(params)?;
-                Ok(changes)
-            })
-        })
+        self.with_conn(|conn| self.trace(sql, || conn.execute(sql, params, false)))
     }
 
     fn execute_cached(&self, sql: &str, params: &[&dyn ToSql]) -> Fallible<usize> {
-        self.with_conn(|conn| {
-            self.trace(sql, || {
-                let mut prepared = conn.prepare_cached(sql)?;
-                let changes = prepared.execute(params)?;
-                Ok(changes)
-            })
-        })
+        self.with_conn(|conn| self.trace(sql, || conn.execute(sql, params, true)))
     }
 
     fn get_row<T, P>(
         &self,
         sql: &str,
         params: P,
-        func: impl FnMut(&Row) -> T,
+        mut func: impl FnMut(&Row) -> T,
     ) -> Fallible<Option<T>>
     where
         P: IntoIterator,
         P::Item: ToSql,
     {
+        let params = params.into_iter().collect::<Vec<_>>();
+        let params = params
+            .iter()
+            .map(|param| param as &dyn ToSql)
+            .collect::<Vec<_>>();
+
         self.with_conn(|conn| {
             self.trace(sql, || {
-                let mut prepared = conn.prepare(sql)?;
-                let mut iter = prepared.query_map(params, func)?;
-
-                if let Some(item) = iter.next() {
-                    Ok(Some(item?))
-                } else {
-                    Ok(None)
-                }
+                let mut result = None;
+                conn.query_each(sql, &params, &mut |row| {
+                    if result.is_none() {
+                        result = Some(func(row));
+                    }
+                    Ok(())
+                })?;
+                Ok(result)
             })
         })
     }
@@ -178,18 +242,15 @@ pub trait QueryUtils {
         &self,
         sql: &str,
         params: &[&dyn ToSql],
-        func: F,
+        mut func: F,
     ) -> Fallible<Vec<T>> {
         self.with_conn(|conn| {
             self.trace(sql, || {
-                let mut prepared = conn.prepare(sql)?;
-                let rows = prepared.query_map(params, func)?;
-
                 let mut results = Vec::new();
-                for row in rows {
-                    results.push(row?);
-                }
-
+                conn.query_each(sql, params, &mut |row| {
+                    results.push(func(row));
+                    Ok(())
+                })?;
                 Ok(results)
             })
         })
@@ -204,13 +265,19 @@ pub trait QueryUtils {
 }
 
 impl QueryUtils for Database {
-    fn with_conn<T, F: FnOnce(&Connection) -> Fallible<T>>(&self, f: F) -> Fallible<T> {
-        f(&self.pool.get()? as &Connection)
+    fn with_conn<T, F: FnOnce(&dyn Connection) -> Fallible<T>>(&self, f: F) -> Fallible<T> {
+        match &self.backend {
+            Backend::Sqlite(pool) => f(&SqliteConnection(&pool.get()?)),
+            Backend::Postgres(pool) => f(&PostgresConnection(&*pool.get()?)),
+        }
     }
 }
 
 impl<'a> QueryUtils for TransactionHandle<'a> {
-    fn with_conn<T, F: FnOnce(&Connection) -> Fallible<T>>(&self, f: F) -> Fallible<T> {
-        f(&self.transaction)
+    fn with_conn<T, F: FnOnce(&dyn Connection) -> Fallible<T>>(&self, f: F) -> Fallible<T> {
+        match &self.transaction {
+            Transaction::Sqlite(t) => f(&SqliteConnection(t)),
+            Transaction::Postgres(t) => f(&PostgresConnection(t)),
+        }
     }
 }
@@ -0,0 +1,553 @@
+//! PostgreSQL backend, for servers whose agents record their progress faster than a single SQLite
+//! file can handle. The queries are written for SQLite, and translated to the PostgreSQL dialect
+//! right before being executed, so that the rest of Crater doesn't have to care about which
+//! backend is used.
+
+use crate::db::{Connection, Row, ToSql};
+use crate::prelude::*;
+use postgres::transaction::{Config as TransactionConfig, IsolationLevel, Transaction};
+use postgres::types::ToSql as PgToSql;
+use postgres::GenericConnection;
+use r2d2::Pool;
+use r2d2_postgres::{PostgresConnectionManager, TlsMode};
+use regex::{Captures, Regex};
+use std::collections::HashSet;
+use std::fmt::Write;
+
+/// Primary keys of the tables created with `ON CONFLICT REPLACE` on SQLite, where inserting a row
+/// replaces the one with the same key. PostgreSQL needs an explicit `ON CONFLICT` clause instead.
+static REPLACED_TABLES: &[(&str, &[&str])] = &[
+    ("results", &["experiment", "crate", "toolchain"]),
+    ("dependency_graphs", &["experiment", "crate", "toolchain"]),
+    ("saved_names", &["issue"]),
+    ("crates", &["crate", "list"]),
+    ("try_builds", &["pr", "repo"]),
+    ("agent_capabilities", &["agent_name", "capability"]),
+    ("triage_decisions", &["experiment", "crate"]),
+    ("crate_notes", &["crate"]),
+    (
+        "baseline_bank",
+        &["toolchain", "mode", "cap_lints", "crate", "sandbox_image"],
+    ),
+];
+
+lazy_static! {
+    static ref INSERT: Regex =
+        Regex::new(r"(?is)^\s*INSERT\s+(?:OR\s+(REPLACE|IGNORE)\s+)?INTO\s+(\w+)\s*\(([^)]*)\)")
+            .unwrap();
+    static ref GLOB: Regex = Regex::new(r"(?i)\bGLOB\s+'([^']*)'").unwrap();
+}
+
+/// Migrations of the PostgreSQL schema. The database starts from the schema SQLite ended up with
+/// after its own migrations, so every migration added there must be added here too. Booleans are
+/// stored as integers like on SQLite, and every table has a `rowid` column, which some queries
+/// sort by.
+fn migrations() -> Vec<(&'static str, &'static str)> {
+    let mut migrations = Vec::new();
+
+    migrations.push((
+        "initial",
+        "
+        CREATE TABLE experiments (
+            rowid BIGSERIAL,
+            name TEXT PRIMARY KEY,
+            mode TEXT NOT NULL,
+            cap_lints TEXT NOT NULL,
+
+            toolchain_start TEXT NOT NULL,
+            toolchain_end TEXT NOT NULL,
+
+            created_at TIMESTAMPTZ NOT NULL,
+            started_at TIMESTAMPTZ,
+            completed_at TIMESTAMPTZ,
+
+            priority INTEGER NOT NULL,
+            status TEXT NOT NULL,
+            report_url TEXT,
+            github_issue TEXT,
+            github_issue_url TEXT,
+            github_issue_number INTEGER,
+            assigned_to TEXT,
+            ignore_blacklist INTEGER NOT NULL DEFAULT 0,
+            requirement TEXT DEFAULT 'linux',
+            cargo_config TEXT,
+            progress_commented_at TIMESTAMPTZ,
+            expected_regressions TEXT NOT NULL DEFAULT '[]',
+            env TEXT NOT NULL DEFAULT '{}',
+            deny_lints TEXT NOT NULL DEFAULT '[]',
+            cargo_update INTEGER NOT NULL DEFAULT 0,
+            failure_threshold TEXT,
+            reuse_baseline INTEGER NOT NULL DEFAULT 0,
+            canary INTEGER NOT NULL DEFAULT 0,
+            test_runner TEXT NOT NULL DEFAULT 'cargo',
+            namespace TEXT,
+            survey INTEGER NOT NULL DEFAULT 0,
+            approved_by TEXT
+        );
+
+        CREATE TABLE experiment_crates (
+            rowid BIGSERIAL,
+            experiment TEXT NOT NULL,
+            crate TEXT NOT NULL,
+            skipped INTEGER NOT NULL DEFAULT 0,
+            status TEXT NOT NULL DEFAULT 'queued',
+            assigned_to TEXT,
+            lease_expires_at TIMESTAMPTZ,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            held INTEGER NOT NULL DEFAULT 0,
+
+            FOREIGN KEY (experiment) REFERENCES experiments(name) ON DELETE CASCADE
+        );
+
+        CREATE INDEX experiment_crates__experiment_skipped
+        ON experiment_crates (experiment, skipped);
+
+        CREATE TABLE results (
+            rowid BIGSERIAL,
+            experiment TEXT NOT NULL,
+            crate TEXT NOT NULL,
+            toolchain TEXT NOT NULL,
+            result TEXT NOT NULL,
+            log BYTEA NOT NULL,
+            encoding TEXT NOT NULL DEFAULT 'plain',
+            log_streamed INTEGER NOT NULL DEFAULT 0,
+            memory_limit BIGINT,
+            sandbox_counters TEXT,
+
+            PRIMARY KEY (experiment, crate, toolchain),
+            FOREIGN KEY (experiment) REFERENCES experiments(name) ON DELETE CASCADE
+        );
+
+        CREATE INDEX results__experiment ON results (experiment);
+
+        CREATE TABLE agents (
+            rowid BIGSERIAL,
+            name TEXT PRIMARY KEY,
+            last_heartbeat TIMESTAMPTZ,
+            git_revision TEXT
+        );
+
+        CREATE TABLE saved_names (
+            rowid BIGSERIAL,
+            issue INTEGER PRIMARY KEY,
+            experiment TEXT NOT NULL
+        );
+
+        CREATE TABLE crates (
+            rowid BIGSERIAL,
+            crate TEXT NOT NULL,
+            list TEXT NOT NULL,
+            loaded_at TIMESTAMPTZ NOT NULL,
+
+            PRIMARY KEY (crate, list)
+        );
+
+        CREATE INDEX crates__list ON crates (list);
+
+        CREATE TABLE try_builds (
+            rowid BIGSERIAL,
+            pr INTEGER NOT NULL,
+            repo TEXT NOT NULL,
+            merge_sha TEXT NOT NULL,
+            base_sha TEXT NOT NULL,
+
+            PRIMARY KEY (pr, repo)
+        );
+
+        CREATE TABLE agent_capabilities (
+            rowid BIGSERIAL,
+            agent_name TEXT NOT NULL,
+            capability TEXT NOT NULL,
+
+            PRIMARY KEY (agent_name, capability),
+            FOREIGN KEY (agent_name) REFERENCES agents(name) ON DELETE CASCADE
+        );
+
+        CREATE INDEX agent__name ON agent_capabilities (agent_name);
+
+        CREATE TABLE triage_decisions (
+            rowid BIGSERIAL,
+            experiment TEXT NOT NULL,
+            crate TEXT NOT NULL,
+            decision TEXT NOT NULL,
+            triager TEXT NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL,
+
+            PRIMARY KEY (experiment, crate),
+            FOREIGN KEY (experiment) REFERENCES experiments(name) ON DELETE CASCADE
+        );
+
+        CREATE TABLE crate_notes (
+            rowid BIGSERIAL,
+            crate TEXT NOT NULL,
+            note TEXT NOT NULL,
+            author TEXT NOT NULL,
+            updated_at TIMESTAMPTZ NOT NULL,
+
+            PRIMARY KEY (crate)
+        );
+
+        CREATE TABLE result_submissions (
+            rowid BIGSERIAL,
+            experiment TEXT NOT NULL,
+            idempotency_key TEXT NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL,
+
+            PRIMARY KEY (experiment, idempotency_key),
+            FOREIGN KEY (experiment) REFERENCES experiments(name) ON DELETE CASCADE
+        );
+
+        CREATE TABLE baseline_bank (
+            rowid BIGSERIAL,
+            toolchain TEXT NOT NULL,
+            mode TEXT NOT NULL,
+            cap_lints TEXT NOT NULL,
+            crate TEXT NOT NULL,
+            result TEXT NOT NULL,
+            log BYTEA NOT NULL,
+            encoding TEXT NOT NULL,
+            experiment TEXT NOT NULL,
+            banked_at TIMESTAMPTZ NOT NULL,
+
+            PRIMARY KEY (toolchain, mode, cap_lints, crate)
+        );
+        ",
+    ));
+
+    migrations.push((
+        "add_sandbox_image",
+        "
+        ALTER TABLE results ADD COLUMN sandbox_image TEXT;
+
+        ALTER TABLE baseline_bank ADD COLUMN sandbox_image TEXT NOT NULL DEFAULT '';
+        ALTER TABLE baseline_bank DROP CONSTRAINT baseline_bank_pkey;
+        ALTER TABLE baseline_bank
+        ADD PRIMARY KEY (toolchain, mode, cap_lints, crate, sandbox_image);
+        ",
+    ));
+
+    migrations.push((
+        "create_log_uploads",
+        "
+        CREATE TABLE log_uploads (
+            rowid BIGSERIAL,
+            experiment TEXT NOT NULL,
+            upload_id TEXT NOT NULL,
+            position BIGINT NOT NULL,
+            data BYTEA NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL,
+
+            PRIMARY KEY (experiment, upload_id, position),
+            FOREIGN KEY (experiment) REFERENCES experiments(name) ON DELETE CASCADE
+        );
+        ",
+    ));
+
+    migrations.push((
+        "add_experiment_field_parent",
+        "
+        ALTER TABLE experiments ADD COLUMN parent TEXT;
+        ",
+    ));
+
+    migrations.push((
+        "create_ecosystem_canary",
+        "
+        CREATE TABLE ecosystem_canary (
+            rowid BIGSERIAL,
+            experiment TEXT PRIMARY KEY NOT NULL,
+            nightly TEXT NOT NULL,
+            sample_offset BIGINT NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL,
+            baseline_failure_rate DOUBLE PRECISION,
+            failure_rate DOUBLE PRECISION,
+            checked_at TIMESTAMPTZ
+        );
+        ",
+    ));
+
+    migrations.push((
+        "create_dependency_graphs",
+        "
+        CREATE TABLE dependency_graphs (
+            rowid BIGSERIAL,
+            experiment TEXT NOT NULL,
+            crate TEXT NOT NULL,
+            toolchain TEXT NOT NULL,
+            graph TEXT NOT NULL,
+
+            PRIMARY KEY (experiment, crate, toolchain),
+            FOREIGN KEY (experiment) REFERENCES experiments(name) ON DELETE CASCADE
+        );
+        ",
+    ));
+
+    migrations.push((
+        "create_build_durations",
+        "
+        CREATE TABLE build_durations (
+            rowid BIGSERIAL,
+            crate TEXT NOT NULL,
+            duration BIGINT NOT NULL,
+            recorded_at TIMESTAMPTZ NOT NULL
+        );
+
+        CREATE INDEX build_durations__crate ON build_durations (crate);
+        ",
+    ));
+
+    migrations.push((
+        "add_experiment_field_sandbox_image",
+        "
+        ALTER TABLE experiments ADD COLUMN sandbox_image TEXT;
+        ",
+    ));
+
+    migrations
+}
+
+fn migrate(conn: &postgres::Connection) -> Fallible<()> {
+    conn.batch_execute("CREATE TABLE IF NOT EXISTS migrations (name TEXT PRIMARY KEY);")?;
+    let executed_migrations = conn
+        .query("SELECT name FROM migrations;", &[])?
+        .iter()
+        .map(|row| row.get("name"))
+        .collect::<HashSet<String>>();
+
+    for (name, sql) in migrations() {
+        if !executed_migrations.contains(name) {
+            let t = conn.transaction()?;
+            t.batch_execute(sql)
+                .with_context(|_| format!("error running migration: {}", name))?;
+            t.execute("INSERT INTO migrations (name) VALUES ($1);", &[&name])?;
+            t.commit()?;
+
+            info!("executed migration: {}", name);
+        }
+    }
+
+    Ok(())
+}
+
+pub(super) fn pool(url: &str, size: u32) -> Fallible<Pool<PostgresConnectionManager>> {
+    let manager = PostgresConnectionManager::new(url, TlsMode::None)?;
+    let pool = Pool::builder().max_size(size).build(manager)?;
+
+    migrate(&pool.get()?)?;
+
+    Ok(pool)
+}
+
+/// Create a pool for a database only used by the tests, dropping everything it contains.
+#[cfg(test)]
+pub(super) fn temp_pool(url: &str) -> Fallible<Pool<PostgresConnectionManager>> {
+    let conn = postgres::Connection::connect(url, postgres::TlsMode::None)?;
+    conn.batch_execute("DROP SCHEMA public CASCADE; CREATE SCHEMA public;")?;
+    pool(url, 4)
+}
+
+/// Start a serializable transaction: SQLite only allows one writer at a time, and some of the
+/// transactions (like leasing crates to agents) rely on it. Transactions conflicting with each
+/// other fail, and agents retry the requests whose transaction failed.
+pub(super) fn transaction(conn: &postgres::Connection) -> Fallible<Transaction<'_>> {
+    let mut config = TransactionConfig::new();
+    config.isolation_level(IsolationLevel::Serializable);
+    Ok(conn.transaction_with(&config)?)
+}
+
+pub(super) struct PostgresConnection<'a>(pub(super) &'a dyn GenericConnection);
+
+impl Connection for PostgresConnection<'_> {
+    fn execute(&self, sql: &str, params: &[&dyn ToSql], cached: bool) -> Fallible<usize> {
+        let sql = translate(sql);
+        with_params(params, |params| {
+            let changes = if cached {
+                self.0.prepare_cached(&sql)?.execute(params)?
+            } else {
+                self.0.execute(&sql, params)?
+            };
+            Ok(changes as usize)
+        })
+    }
+
+    fn exists(&self, sql: &str, params: &[&dyn ToSql]) -> Fallible<bool> {
+        let sql = translate(sql);
+        with_params(params, |params| Ok(!self.0.query(&sql, params)?.is_empty()))
+    }
+
+    fn query_each(
+        &self,
+        sql: &str,
+        params: &[&dyn ToSql],
+        f: &mut dyn FnMut(&Row) -> Fallible<()>,
+    ) -> Fallible<()> {
+        let sql = translate(sql);
+        let rows = with_params(params, |params| Ok(self.0.query(&sql, params)?))?;
+        for row in rows.iter() {
+            f(&Row::Postgres(&row))?;
+        }
+        Ok(())
+    }
+}
+
+fn with_params<T, F>(params: &[&dyn ToSql], f: F) -> Fallible<T>
+where
+    F: FnOnce(&[&dyn PgToSql]) -> Fallible<T>,
+{
+    let values = params
+        .iter()
+        .map(|param| param.to_postgres())
+        .collect::<Vec<_>>();
+    let params = values
+        .iter()
+        .map(|value| &**value as &dyn PgToSql)
+        .collect::<Vec<_>>();
+    f(&params)
+}
+
+/// Translate a query written for SQLite to the PostgreSQL dialect.
+fn translate(sql: &str) -> String {
+    let sql = translate_placeholders(sql);
+    let sql = GLOB.replace_all(&sql, |caps: &Captures| {
+        format!("~ '{}'", glob_to_regex(&caps[1]))
+    });
+    translate_insert(&sql)
+}
+
+/// Replace the `?NNN` and `?` placeholders with `$NNN` ones. Like on SQLite, the number of `?`
+/// is one more than the largest number assigned to the previous placeholders.
+fn translate_placeholders(sql: &str) -> String {
+    let mut result = String::with_capacity(sql.len());
+    let mut chars = sql.chars().peekable();
+    let mut in_string = false;
+    let mut largest = 0;
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_string = !in_string;
+                result.push(c);
+            }
+            '?' if !in_string => {
+                let mut digits = String::new();
+                while let Some(&digit) = chars.peek() {
+                    if !digit.is_ascii_digit() {
+                        break;
+                    }
+                    digits.push(digit);
+                    chars.next();
+                }
+                let number = digits.parse().unwrap_or(largest + 1);
+                largest = largest.max(number);
+                write!(result, "${}", number).unwrap();
+            }
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// Add the `ON CONFLICT` clause giving `INSERT OR IGNORE`, `INSERT OR REPLACE` and the insertions
+/// into the tables in `REPLACED_TABLES` the same behavior they have on SQLite.
+fn translate_insert(sql: &str) -> String {
+    let caps = match INSERT.captures(sql) {
+        Some(caps) => caps,
+        None => return sql.into(),
+    };
+    let table = &caps[2];
+    let columns = &caps[3];
+
+    let ignore = caps
+        .get(1)
+        .map_or(false, |or| or.as_str().eq_ignore_ascii_case("IGNORE"));
+    let conflict = if ignore {
+        "DO NOTHING".to_string()
+    } else if let Some((_, key)) = REPLACED_TABLES.iter().find(|(name, _)| *name == table) {
+        let updates = columns
+            .split(',')
+            .map(|column| column.trim())
+            .filter(|column| !key.contains(column))
+            .map(|column| format!("{} = EXCLUDED.{}", column, column))
+            .collect::<Vec<_>>();
+        if updates.is_empty() {
+            format!("({}) DO NOTHING", key.join(", "))
+        } else {
+            format!("({}) DO UPDATE SET {}", key.join(", "), updates.join(", "))
+        }
+    } else {
+        return sql.into();
+    };
+
+    let rest = sql[caps.get(0).unwrap().end()..].trim_end();
+    format!(
+        "INSERT INTO {} ({}){} ON CONFLICT {};",
+        table,
+        columns,
+        rest.trim_end_matches(';'),
+        conflict
+    )
+}
+
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    let mut in_class = false;
+    for c in glob.chars() {
+        match c {
+            ']' if in_class => {
+                in_class = false;
+                regex.push(c);
+            }
+            _ if in_class => regex.push(c),
+            '[' => {
+                in_class = true;
+                regex.push(c);
+            }
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::translate;
+
+    #[test]
+    fn test_translate() {
+        assert_eq!(
+            translate("SELECT * FROM results WHERE experiment = ?1 AND crate IN (?, ?);"),
+            "SELECT * FROM results WHERE experiment = $1 AND crate IN ($2, $3);"
+        );
+        assert_eq!(
+            translate("SELECT '?' FROM crates WHERE list = ?2 OR list = ?1 OR crate = ?;"),
+            "SELECT '?' FROM crates WHERE list = $2 OR list = $1 OR crate = $3;"
+        );
+        assert_eq!(
+            translate("SELECT toolchain FROM results WHERE toolchain GLOB '1.[0-9]*.[0-9]*';"),
+            "SELECT toolchain FROM results WHERE toolchain ~ '^1\\.[0-9].*\\.[0-9].*$';"
+        );
+        assert_eq!(
+            translate(
+                "INSERT OR REPLACE INTO try_builds (repo, pr, base_sha, merge_sha) \
+                 VALUES (?1, ?2, ?3, ?4);"
+            ),
+            "INSERT INTO try_builds (repo, pr, base_sha, merge_sha) VALUES ($1, $2, $3, $4) \
+             ON CONFLICT (pr, repo) DO UPDATE SET \
+             base_sha = EXCLUDED.base_sha, merge_sha = EXCLUDED.merge_sha;"
+        );
+        assert_eq!(
+            translate("INSERT INTO crates (crate, list) VALUES (?1, ?2)"),
+            "INSERT INTO crates (crate, list) VALUES ($1, $2) ON CONFLICT (crate, list) DO NOTHING;"
+        );
+        assert_eq!(
+            translate("INSERT OR IGNORE INTO agents (name) VALUES (?1);"),
+            "INSERT INTO agents (name) VALUES ($1) ON CONFLICT DO NOTHING;"
+        );
+        assert_eq!(
+            translate("INSERT INTO agents (name) VALUES (?1);"),
+            "INSERT INTO agents (name) VALUES ($1);"
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use crate::db::{migrations, Connection, Row, ToSql};
+use crate::prelude::*;
+use r2d2::{CustomizeConnection, Pool};
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::types::{ToSql as SqliteToSql, ToSqlOutput};
+
+#[derive(Debug)]
+struct ConnectionCustomizer;
+
+impl CustomizeConnection<rusqlite::Connection, ::rusqlite::Error> for ConnectionCustomizer {
+    fn on_acquire(&self, conn: &mut rusqlite::Connection) -> Result<(), ::rusqlite::Error> {
+        conn.execute(
+            "PRAGMA foreign_keys = ON;",
+            ::std::iter::empty::<&dyn SqliteToSql>(),
+        )?;
+        Ok(())
+    }
+}
+
+pub(super) fn pool(manager: SqliteConnectionManager) -> Fallible<Pool<SqliteConnectionManager>> {
+    let pool = Pool::builder()
+        .connection_customizer(Box::new(ConnectionCustomizer))
+        .build(manager)?;
+
+    migrations::execute(&mut pool.get()? as &mut rusqlite::Connection)?;
+
+    Ok(pool)
+}
+
+pub(super) struct SqliteConnection<'a>(pub(super) &'a rusqlite::Connection);
+
+impl Connection for SqliteConnection<'_> {
+    fn execute(&self, sql: &str, params: &[&dyn ToSql], cached: bool) -> Fallible<usize> {
+        with_params(params, |params| {
+            let changes = if cached {
+                self.0.prepare_cached(sql)?.execute(params)?
+            } else {
+                self.0.prepare(sql)?.execute(params)?
+            };
+            Ok(changes)
+        })
+    }
+
+    fn exists(&self, sql: &str, params: &[&dyn ToSql]) -> Fallible<bool> {
+        with_params(params, |params| Ok(self.0.prepare(sql)?.exists(params)?))
+    }
+
+    fn query_each(
+        &self,
+        sql: &str,
+        params: &[&dyn ToSql],
+        f: &mut dyn FnMut(&Row) -> Fallible<()>,
+    ) -> Fallible<()> {
+        with_params(params, |params| {
+            let mut prepared = self.0.prepare(sql)?;
+            for res in prepared.query_map(params, |row| f(&Row::Sqlite(row)))? {
+                res??;
+            }
+            Ok(())
+        })
+    }
+}
+
+fn with_params<T, F>(params: &[&dyn ToSql], f: F) -> Fallible<T>
+where
+    F: FnOnce(&[&dyn SqliteToSql]) -> Fallible<T>,
+{
+    let values = params
+        .iter()
+        .map(|param| param.to_sqlite())
+        .collect::<rusqlite::Result<Vec<ToSqlOutput>>>()?;
+    let params = values
+        .iter()
+        .map(|value| value as &dyn SqliteToSql)
+        .collect::<Vec<_>>();
+    f(&params)
+}
@@ -0,0 +1,235 @@
+//! Conversions between the Rust values and the values stored by the database backends. The
+//! queries only use the types supported by both SQLite and PostgreSQL: booleans are stored as
+//! integers by SQLite, so they're converted from and into integers on PostgreSQL too.
+
+use crate::prelude::*;
+use chrono::{DateTime, Utc};
+use postgres::rows::Row as PgRow;
+use postgres::to_sql_checked;
+use postgres::types::{IsNull, ToSql as PgToSql, Type, BOOL, INT2, INT4, INT8};
+use rusqlite::types::{ToSql as SqliteToSql, ToSqlOutput, Value};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+/// Index of a column, either its position or its name.
+pub trait RowIndex: rusqlite::RowIndex + postgres::rows::RowIndex + fmt::Debug + Copy {}
+
+impl<T: rusqlite::RowIndex + postgres::rows::RowIndex + fmt::Debug + Copy> RowIndex for T {}
+
+/// Row returned by a query, through which the values of its columns are retrieved.
+pub enum Row<'a> {
+    Sqlite(&'a rusqlite::Row<'a, 'a>),
+    Postgres(&'a PgRow<'a>),
+}
+
+impl<'a> Row<'a> {
+    pub fn get<I: RowIndex, T: FromSql>(&self, idx: I) -> T {
+        self.get_checked(idx)
+            .unwrap_or_else(|err| panic!("failed to get column {:?}: {}", idx, err))
+    }
+
+    pub fn get_checked<I: RowIndex, T: FromSql>(&self, idx: I) -> Fallible<T> {
+        match self {
+            Row::Sqlite(row) => T::from_sqlite(row, idx),
+            Row::Postgres(row) => T::from_postgres(row, idx),
+        }
+    }
+}
+
+/// Value which can be passed as a parameter of the queries.
+pub trait ToSql {
+    fn to_sqlite(&self) -> rusqlite::Result<ToSqlOutput<'_>>;
+    fn to_postgres(&self) -> Box<dyn PgToSql + '_>;
+}
+
+impl<T: ToSql + ?Sized> ToSql for &T {
+    fn to_sqlite(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        (**self).to_sqlite()
+    }
+
+    fn to_postgres(&self) -> Box<dyn PgToSql + '_> {
+        (**self).to_postgres()
+    }
+}
+
+impl<T: ToSql> ToSql for Option<T> {
+    fn to_sqlite(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        match self {
+            Some(value) => value.to_sqlite(),
+            None => Ok(ToSqlOutput::Owned(Value::Null)),
+        }
+    }
+
+    fn to_postgres(&self) -> Box<dyn PgToSql + '_> {
+        match self {
+            Some(value) => value.to_postgres(),
+            None => Box::new(PgNull),
+        }
+    }
+}
+
+macro_rules! to_sql_values {
+    ($($ty:ty => |$value:ident| $pg:expr,)*) => {
+        $(
+            impl ToSql for $ty {
+                fn to_sqlite(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+                    SqliteToSql::to_sql(self)
+                }
+
+                fn to_postgres(&self) -> Box<dyn PgToSql + '_> {
+                    let $value = self;
+                    Box::new($pg)
+                }
+            }
+        )*
+    }
+}
+
+to_sql_values! {
+    bool => |value| PgInteger(i64::from(*value)),
+    i8 => |value| PgInteger(i64::from(*value)),
+    i16 => |value| PgInteger(i64::from(*value)),
+    i32 => |value| PgInteger(i64::from(*value)),
+    i64 => |value| PgInteger(*value),
+    u8 => |value| PgInteger(i64::from(*value)),
+    u16 => |value| PgInteger(i64::from(*value)),
+    u32 => |value| PgInteger(i64::from(*value)),
+    f64 => |value| *value,
+    str => |value| value,
+    String => |value| value.as_str(),
+    [u8] => |value| value,
+    Vec<u8> => |value| value.as_slice(),
+    DateTime<Utc> => |value| *value,
+}
+
+/// Integer parameter, encoded as the integer type PostgreSQL expects. SQLite doesn't care about
+/// the size of the integers, so the queries don't either.
+#[derive(Debug)]
+struct PgInteger(i64);
+
+impl PgToSql for PgInteger {
+    fn to_sql(&self, ty: &Type, out: &mut Vec<u8>) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        if *ty == INT2 {
+            PgToSql::to_sql(&i16::try_from(self.0)?, ty, out)
+        } else if *ty == INT4 {
+            PgToSql::to_sql(&i32::try_from(self.0)?, ty, out)
+        } else if *ty == BOOL {
+            PgToSql::to_sql(&(self.0 != 0), ty, out)
+        } else {
+            PgToSql::to_sql(&self.0, ty, out)
+        }
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == INT2 || *ty == INT4 || *ty == INT8 || *ty == BOOL
+    }
+
+    to_sql_checked!();
+}
+
+/// `NULL` parameter, which unlike `Option::<T>::None` is accepted for columns of any type.
+#[derive(Debug)]
+struct PgNull;
+
+impl PgToSql for PgNull {
+    fn to_sql(&self, _: &Type, _: &mut Vec<u8>) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        Ok(IsNull::Yes)
+    }
+
+    fn accepts(_: &Type) -> bool {
+        true
+    }
+
+    to_sql_checked!();
+}
+
+/// Value which can be retrieved from the columns of a row.
+pub trait FromSql: Sized {
+    fn from_sqlite<I: RowIndex>(row: &rusqlite::Row, idx: I) -> Fallible<Self>;
+    fn from_postgres<I: RowIndex>(row: &PgRow, idx: I) -> Fallible<Self>;
+}
+
+impl<T: FromSql> FromSql for Option<T> {
+    fn from_sqlite<I: RowIndex>(row: &rusqlite::Row, idx: I) -> Fallible<Self> {
+        match row.get_checked::<_, Value>(idx)? {
+            Value::Null => Ok(None),
+            _ => Ok(Some(T::from_sqlite(row, idx)?)),
+        }
+    }
+
+    fn from_postgres<I: RowIndex>(row: &PgRow, idx: I) -> Fallible<Self> {
+        match row.get_bytes(idx) {
+            None => Ok(None),
+            Some(_) => Ok(Some(T::from_postgres(row, idx)?)),
+        }
+    }
+}
+
+impl FromSql for bool {
+    fn from_sqlite<I: RowIndex>(row: &rusqlite::Row, idx: I) -> Fallible<Self> {
+        Ok(row.get_checked(idx)?)
+    }
+
+    fn from_postgres<I: RowIndex>(row: &PgRow, idx: I) -> Fallible<Self> {
+        // Expressions like `COUNT(*) > 0` return booleans instead of integers on PostgreSQL
+        if let Some(Ok(value)) = row.get_opt::<_, bool>(idx) {
+            return Ok(value);
+        }
+        Ok(postgres_integer(row, idx)? != 0)
+    }
+}
+
+macro_rules! from_sql_integers {
+    ($($ty:ty),*) => {
+        $(
+            impl FromSql for $ty {
+                fn from_sqlite<I: RowIndex>(row: &rusqlite::Row, idx: I) -> Fallible<Self> {
+                    Ok(row.get_checked(idx)?)
+                }
+
+                fn from_postgres<I: RowIndex>(row: &PgRow, idx: I) -> Fallible<Self> {
+                    Ok(<$ty>::try_from(postgres_integer(row, idx)?)?)
+                }
+            }
+        )*
+    }
+}
+
+from_sql_integers!(i8, i16, i32, i64, u8, u16, u32);
+
+macro_rules! from_sql_values {
+    ($($ty:ty),*) => {
+        $(
+            impl FromSql for $ty {
+                fn from_sqlite<I: RowIndex>(row: &rusqlite::Row, idx: I) -> Fallible<Self> {
+                    Ok(row.get_checked(idx)?)
+                }
+
+                fn from_postgres<I: RowIndex>(row: &PgRow, idx: I) -> Fallible<Self> {
+                    match row.get_opt(idx) {
+                        Some(value) => Ok(value?),
+                        None => bail!("missing column {:?}", idx),
+                    }
+                }
+            }
+        )*
+    }
+}
+
+from_sql_values!(f64, String, Vec<u8>, DateTime<Utc>);
+
+/// Retrieve an integer column of any size, as `COUNT(*)` returns a `BIGINT` while most of the
+/// columns are `INTEGER`s.
+fn postgres_integer<I: RowIndex>(row: &PgRow, idx: I) -> Fallible<i64> {
+    if let Some(Ok(value)) = row.get_opt::<_, i64>(idx) {
+        return Ok(value);
+    }
+    if let Some(Ok(value)) = row.get_opt::<_, i32>(idx) {
+        return Ok(value.into());
+    }
+    match row.get_opt::<_, i16>(idx) {
+        Some(value) => Ok(value?.into()),
+        None => bail!("missing column {:?}", idx),
+    }
+}
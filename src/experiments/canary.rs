@@ -0,0 +1,81 @@
+use crate::crates::Crate;
+
+/// Number of crates built first when the experiment has none of the built-in canaries.
+const FALLBACK_SIZE: usize = 20;
+
+/// Popular and stable crates of the registry, building on every platform without any system
+/// dependency: if most of them fail something is wrong with the experiment's setup.
+static CANARY_CRATES: &[&str] = &[
+    "anyhow",
+    "bitflags",
+    "byteorder",
+    "cfg-if",
+    "either",
+    "hex",
+    "itoa",
+    "lazy_static",
+    "log",
+    "memchr",
+    "once_cell",
+    "proc-macro2",
+    "quote",
+    "rand",
+    "regex",
+    "semver",
+    "serde",
+    "smallvec",
+    "syn",
+    "thiserror",
+];
+
+/// Select the crates of the experiment built before the others, when the experiment runs a
+/// canary. The built-in canaries are used when the experiment contains them, otherwise the first
+/// crates of the list are picked.
+pub(crate) fn select(crates: &[Crate]) -> Vec<Crate> {
+    let canaries = crates
+        .iter()
+        .filter(|krate| match krate {
+            Crate::Registry(details) => CANARY_CRATES.contains(&details.name.as_str()),
+            _ => false,
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+
+    if canaries.is_empty() {
+        crates.iter().take(FALLBACK_SIZE).cloned().collect()
+    } else {
+        canaries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::select;
+    use crate::crates::{Crate, GitHubRepo, RegistryCrate};
+
+    #[test]
+    fn test_select() {
+        let registry = |name: &str| {
+            Crate::Registry(RegistryCrate {
+                name: name.into(),
+                version: "1.0.0".into(),
+            })
+        };
+        let github = Crate::GitHub(GitHubRepo {
+            org: "brson".into(),
+            name: "hello-rs".into(),
+            sha: None,
+        });
+
+        let crates = vec![
+            registry("foo"),
+            registry("serde"),
+            github.clone(),
+            registry("log"),
+        ];
+        assert_eq!(select(&crates), vec![registry("serde"), registry("log")]);
+
+        let crates = vec![registry("foo"), github];
+        assert_eq!(select(&crates), crates);
+    }
+}
@@ -0,0 +1,284 @@
+//! Incremental experiments only build the crates that changed since a parent experiment ran, and
+//! inherit the results of the parent for all the other crates.
+
+use crate::crates::Crate;
+use crate::db::{Database, QueryUtils};
+use crate::experiments::Experiment;
+use crate::prelude::*;
+use crate::results::{bank::is_reusable, DatabaseDB, ReadResults};
+use crate::toolchain::Toolchain;
+use crate::utils;
+use crossbeam_utils::thread::scope;
+use std::collections::HashMap;
+use std::process::Command;
+
+/// Number of git repositories whose HEAD is resolved at the same time.
+const RESOLVE_THREADS: usize = 16;
+
+/// Source of a crate built from a git repository, along with the commit it was built from.
+fn git_source(krate: &Crate) -> Option<(String, Option<&str>)> {
+    match krate {
+        Crate::GitHub(repo) => Some((repo.clone_url(), repo.sha.as_deref())),
+        Crate::Git(repo) => Some((repo.url.clone(), repo.sha.as_deref())),
+        _ => None,
+    }
+}
+
+/// Find the crates that didn't change since the parent experiment built them, returning for each
+/// of them the crate built by the parent. Registry crates changed if their version did, and git
+/// repositories if their HEAD moved away from the commit built by the parent. Local crates can't
+/// be compared, and they're always built again like the crates without a sensible result in the
+/// parent (for example because the build timed out).
+pub(crate) fn unchanged(
+    db: &Database,
+    parent: &Experiment,
+    crates: &[Crate],
+) -> Fallible<HashMap<Crate, Crate>> {
+    let results = DatabaseDB::new(db);
+    let mut built = HashMap::new();
+    for (i, toolchain) in parent.tested_toolchains().iter().enumerate() {
+        let mut reusable = HashMap::new();
+        for (krate, result) in results.load_all_results(parent, toolchain)? {
+            if is_reusable(&result) && (i == 0 || built.contains_key(&krate.unversioned_id())) {
+                reusable.insert(krate.unversioned_id(), krate);
+            }
+        }
+        built = reusable;
+    }
+
+    let mut unchanged = HashMap::new();
+    let mut unresolved = Vec::new();
+    for krate in crates {
+        let parent_crate = match built.get(&krate.unversioned_id()) {
+            Some(parent_crate) => parent_crate,
+            None => continue,
+        };
+        match (git_source(krate), git_source(parent_crate)) {
+            (Some((_, Some(sha))), Some((_, Some(parent_sha)))) => {
+                if sha == parent_sha {
+                    unchanged.insert(krate.clone(), parent_crate.clone());
+                }
+            }
+            (Some((url, None)), Some((_, Some(_)))) => unresolved.push((krate, parent_crate, url)),
+            (None, None) => {
+                if let Crate::Registry(_) = krate {
+                    if krate == parent_crate {
+                        unchanged.insert(krate.clone(), parent_crate.clone());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let urls = unresolved
+        .iter()
+        .map(|(_, _, url)| url.clone())
+        .collect::<Vec<_>>();
+    let heads = remote_heads(&urls)?;
+    for (krate, parent_crate, url) in unresolved {
+        let parent_sha = git_source(parent_crate).and_then(|(_, sha)| sha);
+        if heads.get(&url).map(|head| head.as_str()) == parent_sha {
+            unchanged.insert(krate.clone(), parent_crate.clone());
+        }
+    }
+
+    Ok(unchanged)
+}
+
+/// Resolve the commit the HEAD of each git repository points to, in parallel. Repositories that
+/// can't be reached are missing from the returned map.
+fn remote_heads(urls: &[String]) -> Fallible<HashMap<String, String>> {
+    if urls.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let chunk_size = (urls.len() + RESOLVE_THREADS - 1) / RESOLVE_THREADS;
+    scope(|scope| -> Fallible<HashMap<String, String>> {
+        let handles = urls
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .filter_map(|url| remote_head(url).map(|head| (url.clone(), head)))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut heads = HashMap::new();
+        for handle in handles {
+            match handle.join() {
+                Ok(resolved) => heads.extend(resolved),
+                Err(panic) => {
+                    utils::report_panic(&panic);
+                    bail!("a thread resolving the HEAD of git repositories panicked");
+                }
+            }
+        }
+        Ok(heads)
+    })
+}
+
+fn remote_head(url: &str) -> Option<String> {
+    let output = Command::new("git")
+        .args(&["ls-remote", url, "HEAD"])
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .output();
+    match output {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .split_whitespace()
+            .next()
+            .map(|sha| sha.to_string()),
+        Ok(output) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            warn!("failed to resolve the HEAD of {}: {}", url, stderr.trim());
+            None
+        }
+        Err(err) => {
+            warn!("failed to resolve the HEAD of {}: {}", url, err);
+            None
+        }
+    }
+}
+
+/// Copy the results of a crate, along with its dependency graphs, from the parent experiment into
+/// the experiment called `name`. The result of each toolchain of the parent becomes the result of
+/// the toolchain in the same position of the experiment.
+pub(crate) fn inherit<T: QueryUtils>(
+    db: &T,
+    name: &str,
+    toolchains: &[Toolchain; 2],
+    parent: &Experiment,
+    krate: &Crate,
+) -> Fallible<()> {
+    for (parent_toolchain, toolchain) in parent.tested_toolchains().iter().zip(toolchains) {
+        db.execute(
+            "INSERT INTO results \
+             (experiment, crate, toolchain, result, log, encoding, memory_limit, \
+             sandbox_counters, sandbox_image) \
+             SELECT ?1, crate, ?2, result, log, encoding, memory_limit, sandbox_counters, \
+             sandbox_image FROM results WHERE experiment = ?3 AND crate = ?4 AND toolchain = ?5;",
+            &[
+                &name,
+                &toolchain.to_string(),
+                &parent.name,
+                &krate.id(),
+                &parent_toolchain.to_string(),
+            ],
+        )?;
+        db.execute(
+            "INSERT INTO dependency_graphs (experiment, crate, toolchain, graph) \
+             SELECT ?1, crate, ?2, graph FROM dependency_graphs \
+             WHERE experiment = ?3 AND crate = ?4 AND toolchain = ?5;",
+            &[
+                &name,
+                &toolchain.to_string(),
+                &parent.name,
+                &krate.id(),
+                &parent_toolchain.to_string(),
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{inherit, unchanged};
+    use crate::actions::{Action, ActionsCtx, CreateExperiment};
+    use crate::config::Config;
+    use crate::crates::{Crate, RegistryCrate};
+    use crate::db::Database;
+    use crate::experiments::Experiment;
+    use crate::results::{
+        DatabaseDB, EncodingType, FailureReason, ReadResults, TestResult, WriteResults,
+    };
+
+    #[test]
+    fn test_inherit_unchanged() {
+        let db = Database::temp().unwrap();
+        let config = Config::default();
+        let ctx = ActionsCtx::new(&db, &config);
+        let results = DatabaseDB::new(&db);
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+
+        CreateExperiment::dummy("parent").apply(&ctx).unwrap();
+        let parent = Experiment::get(&db, "parent").unwrap().unwrap();
+        CreateExperiment::dummy("child").apply(&ctx).unwrap();
+        let child = Experiment::get(&db, "child").unwrap().unwrap();
+
+        let reg = |name: &str, version: &str| {
+            Crate::Registry(RegistryCrate {
+                name: name.into(),
+                version: version.into(),
+            })
+        };
+        let timeout = TestResult::BuildFail(FailureReason::Timeout);
+        for (krate, res) in &[
+            (reg("same", "1.0.0"), TestResult::TestPass),
+            (reg("bumped", "1.0.0"), TestResult::TestPass),
+            (reg("timeout", "1.0.0"), timeout),
+            (Crate::Local("local".into()), TestResult::TestPass),
+        ] {
+            for tc in &parent.toolchains {
+                results
+                    .record_result(
+                        &parent,
+                        tc,
+                        krate,
+                        None,
+                        &config,
+                        EncodingType::Plain,
+                        || Ok(res.clone()),
+                    )
+                    .unwrap();
+            }
+        }
+        // Crates without a result on both toolchains have to be built again
+        results
+            .record_result(
+                &parent,
+                &parent.toolchains[0],
+                &reg("half", "1.0.0"),
+                None,
+                &config,
+                EncodingType::Plain,
+                || Ok(TestResult::TestPass),
+            )
+            .unwrap();
+
+        let crates = vec![
+            reg("same", "1.0.0"),
+            reg("bumped", "1.1.0"),
+            reg("timeout", "1.0.0"),
+            reg("half", "1.0.0"),
+            reg("new", "1.0.0"),
+            Crate::Local("local".into()),
+        ];
+        let unchanged = unchanged(&db, &parent, &crates).unwrap();
+        assert_eq!(unchanged.len(), 1);
+        assert_eq!(
+            unchanged.get(&reg("same", "1.0.0")),
+            Some(&reg("same", "1.0.0"))
+        );
+
+        inherit(
+            &db,
+            &child.name,
+            &child.toolchains,
+            &parent,
+            &reg("same", "1.0.0"),
+        )
+        .unwrap();
+        for tc in &child.toolchains {
+            assert_eq!(
+                results
+                    .load_test_result(&child, tc, &reg("same", "1.0.0"))
+                    .unwrap(),
+                Some(TestResult::TestPass)
+            );
+        }
+    }
+}
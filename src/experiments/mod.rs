@@ -1,38 +1,60 @@
 use crate::config::Config;
-use crate::crates::Crate;
-use crate::db::{Database, QueryUtils};
+use crate::crates::{expr::CrateExpr, Crate};
+use crate::db::{Database, QueryUtils, Row};
 use crate::prelude::*;
 use crate::results::TestResult;
 use crate::toolchain::Toolchain;
 use crate::utils;
-use chrono::{DateTime, Utc};
-use rusqlite::Row;
-use std::collections::HashSet;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{BTreeMap, HashSet};
 use std::fmt;
+use std::path::PathBuf;
 use std::str::FromStr;
 use url::Url;
 
+pub(crate) mod canary;
+pub(crate) mod incremental;
+mod queue;
+
+pub use self::queue::CrateQueue;
+
 //sqlite limit is ignored if the expression evaluates to a negative value
 static FULL_LIST: i32 = -1;
-static SQL_VARIABLE_LIMIT: usize = 500;
 
 string_enum!(pub enum Status {
     Queued => "queued",
     Running => "running",
+    Paused => "paused",
     NeedsReport => "needs-report",
     Failed => "failed",
     GeneratingReport => "generating-report",
     ReportFailed => "report-failed",
+    AwaitingApproval => "awaiting-approval",
     Completed => "completed",
 });
 
+string_enum!(pub enum TestRunner {
+    Cargo => "cargo",
+    Nextest => "nextest",
+});
+
+impl Default for TestRunner {
+    fn default() -> Self {
+        TestRunner::Cargo
+    }
+}
+
 string_enum!(pub enum Mode {
     BuildAndTest => "build-and-test",
     BuildOnly => "build-only",
     CheckOnly => "check-only",
+    CheckThenTest => "check-then-test",
     Clippy => "clippy",
     Rustdoc => "rustdoc",
+    RustdocStrict => "rustdoc-strict",
     UnstableFeatures => "unstable-features",
+    Msrv => "msrv",
+    Miri => "miri",
 });
 
 string_enum!(pub enum CapLints {
@@ -53,6 +75,12 @@ pub enum CrateSelect {
     Dummy,
     Random(u32),
     List(HashSet<String>),
+    /// Crates listed in a TOML or JSON file, see `FileList` for the format.
+    File(PathBuf),
+    /// Registry crates depending on a crate, optionally limited to the most popular ones.
+    ReverseDeps(String, Option<u32>),
+    /// Registry crates matching an expression on their crates.io metadata, see `CrateExpr`.
+    Expr(String),
 }
 
 impl FromStr for CrateSelect {
@@ -80,6 +108,24 @@ impl FromStr for CrateSelect {
                 CrateSelect::List(list)
             }
 
+            s if s.starts_with("file:") => CrateSelect::File(PathBuf::from(&s["file:".len()..])),
+
+            s if s.starts_with("rdeps:") => {
+                let mut parts = s["rdeps:".len()..].splitn(2, ':');
+                let name = parts.next().unwrap_or("");
+                if name.is_empty() {
+                    bail!("missing the name of the crate in {}", s);
+                }
+                let limit = parts.next().map(|n| n.parse()).transpose()?;
+                CrateSelect::ReverseDeps(name.to_string(), limit)
+            }
+
+            s if s.starts_with("expr:") => {
+                let expr = &s["expr:".len()..];
+                expr.parse::<CrateExpr>()?;
+                CrateSelect::Expr(expr.to_string())
+            }
+
             "full" => CrateSelect::Full,
             "demo" => CrateSelect::Demo,
             "local" => CrateSelect::Local,
@@ -100,6 +146,10 @@ impl fmt::Display for CrateSelect {
             CrateSelect::Top(n) => write!(f, "top-{}", n),
             CrateSelect::Local => write!(f, "local"),
             CrateSelect::Random(n) => write!(f, "random-{}", n),
+            CrateSelect::File(path) => write!(f, "file:{}", path.display()),
+            CrateSelect::ReverseDeps(name, None) => write!(f, "rdeps:{}", name),
+            CrateSelect::ReverseDeps(name, Some(n)) => write!(f, "rdeps:{}:{}", name, n),
+            CrateSelect::Expr(expr) => write!(f, "expr:{}", expr),
             CrateSelect::List(list) => {
                 let mut first = true;
                 write!(f, "list:")?;
@@ -169,6 +219,67 @@ impl FromStr for DeferredCrateSelect {
 
 impl_serde_from_parse!(CrateSelect, expecting = "A valid value of `CrateSelect`");
 
+/// Number of regressions after which a running experiment is paused, either as an absolute count
+/// (`50`) or as a percentage of the crates compared so far (`5%`).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FailureThreshold {
+    Regressions(u32),
+    Percent(u32),
+}
+
+/// Minimum number of compared crates before a percentage threshold is checked, as the first few
+/// results are not representative of the whole experiment.
+const FAILURE_THRESHOLD_MIN_COMPARED: u32 = 100;
+
+impl FailureThreshold {
+    /// Check whether `regressed` regressions out of `compared` crates exceed the threshold.
+    pub fn exceeded(self, regressed: u32, compared: u32) -> bool {
+        match self {
+            FailureThreshold::Regressions(max) => regressed >= max,
+            FailureThreshold::Percent(percent) => {
+                compared >= FAILURE_THRESHOLD_MIN_COMPARED
+                    && u64::from(regressed) * 100 >= u64::from(percent) * u64::from(compared)
+            }
+        }
+    }
+}
+
+impl FromStr for FailureThreshold {
+    type Err = failure::Error;
+
+    fn from_str(s: &str) -> Fallible<Self> {
+        let threshold = if s.ends_with('%') {
+            FailureThreshold::Percent(s[..s.len() - 1].parse()?)
+        } else {
+            FailureThreshold::Regressions(s.parse()?)
+        };
+
+        match threshold {
+            FailureThreshold::Regressions(0) | FailureThreshold::Percent(0) => {
+                bail!("the failure threshold must be greater than zero")
+            }
+            FailureThreshold::Percent(p) if p > 100 => {
+                bail!("the failure threshold can't be more than 100%")
+            }
+            _ => Ok(threshold),
+        }
+    }
+}
+
+impl fmt::Display for FailureThreshold {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FailureThreshold::Regressions(n) => write!(f, "{}", n),
+            FailureThreshold::Percent(p) => write!(f, "{}%", p),
+        }
+    }
+}
+
+impl_serde_from_parse!(
+    FailureThreshold,
+    expecting = "a number of regressions or a percentage"
+);
+
 #[cfg_attr(test, derive(Debug, PartialEq, Eq))]
 #[derive(Clone, Serialize, Deserialize)]
 pub enum Assignee {
@@ -258,11 +369,93 @@ pub struct Experiment {
     pub status: Status,
     pub assigned_to: Option<Assignee>,
     pub report_url: Option<String>,
+    /// User who signed off the report, if reports have to be approved before being published.
+    #[serde(default)]
+    pub approved_by: Option<String>,
     pub ignore_blacklist: bool,
     pub requirement: Option<String>,
+    #[serde(default)]
+    pub cargo_config: Option<String>,
+    /// Patterns matching the logs of regressions caused on purpose by the experiment.
+    #[serde(default)]
+    pub expected_regressions: Vec<String>,
+    /// Environment variables changing the behavior of the toolchains, set in every build.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Lints whose warnings fail the build, even if the lints are capped.
+    #[serde(default)]
+    pub deny_lints: Vec<String>,
+    /// Whether the dependencies of the crates are updated to their latest compatible versions
+    /// before building them, ignoring their lockfiles.
+    #[serde(default)]
+    pub cargo_update: bool,
+    /// Regressions after which the experiment is paused, waiting for a human to resume it.
+    #[serde(default)]
+    pub failure_threshold: Option<FailureThreshold>,
+    /// Whether the results of the start toolchain are copied from the baseline bank when
+    /// available, instead of building the crates again.
+    #[serde(default)]
+    pub reuse_baseline: bool,
+    /// Whether a small set of well-known crates is built first, queuing the rest of the crates
+    /// only if their results look sane.
+    #[serde(default)]
+    pub canary: bool,
+    /// Tool running the tests of the crates.
+    #[serde(default)]
+    pub test_runner: TestRunner,
+    /// Namespace of the team owning the experiment, or `None` for the default namespace.
+    #[serde(default)]
+    pub namespace: Option<String>,
+    /// Whether the experiment measures the ecosystem with a single toolchain, instead of
+    /// comparing two toolchains. The toolchain is stored in both slots of `toolchains`.
+    #[serde(default)]
+    pub survey: bool,
+    /// Experiment this one was derived from: only the crates that changed since the parent ran
+    /// are built, and the results of the other crates are copied from the parent.
+    #[serde(default)]
+    pub parent: Option<String>,
+    /// Sandbox image the crates are built in, instead of the one of the workspace of the agents.
+    #[serde(default)]
+    pub sandbox_image: Option<String>,
 }
 
 impl Experiment {
+    #[cfg(test)]
+    pub fn dummy(name: &str) -> Self {
+        use crate::toolchain::{MAIN_TOOLCHAIN, TEST_TOOLCHAIN};
+
+        Experiment {
+            name: name.to_string(),
+            toolchains: [MAIN_TOOLCHAIN.clone(), TEST_TOOLCHAIN.clone()],
+            mode: Mode::BuildAndTest,
+            cap_lints: CapLints::Forbid,
+            priority: 0,
+            created_at: Utc::now(),
+            started_at: None,
+            completed_at: None,
+            github_issue: None,
+            status: Status::Queued,
+            assigned_to: None,
+            report_url: None,
+            approved_by: None,
+            ignore_blacklist: false,
+            requirement: None,
+            cargo_config: None,
+            expected_regressions: Vec::new(),
+            env: BTreeMap::new(),
+            deny_lints: Vec::new(),
+            cargo_update: false,
+            failure_threshold: None,
+            reuse_baseline: false,
+            canary: false,
+            test_runner: TestRunner::Cargo,
+            namespace: None,
+            survey: false,
+            parent: None,
+            sandbox_image: None,
+        }
+    }
+
     pub fn exists(db: &Database, name: &str) -> Fallible<bool> {
         Ok(db.exists("SELECT rowid FROM experiments WHERE name = ?1;", &[&name])?)
     }
@@ -279,6 +472,24 @@ impl Experiment {
             .collect::<Fallible<_>>()
     }
 
+    /// Number of experiments of the namespace which are queued, running or paused, counted
+    /// against the quota of the namespace.
+    pub fn active_in_namespace(db: &Database, namespace: &str) -> Fallible<u32> {
+        Ok(db
+            .get_row(
+                "SELECT COUNT(*) AS count FROM experiments \
+                 WHERE namespace = ?1 AND status IN (?2, ?3, ?4);",
+                &[
+                    &namespace,
+                    &Status::Queued.to_str(),
+                    &Status::Running.to_str(),
+                    &Status::Paused.to_str(),
+                ],
+                |r| r.get("count"),
+            )?
+            .unwrap())
+    }
+
     pub fn run_by(db: &Database, assignee: &Assignee) -> Fallible<Option<Experiment>> {
         let record = db.get_row(
             "SELECT * FROM experiments \
@@ -322,22 +533,22 @@ impl Experiment {
         // Get an experiment whose requirements are met by this agent, preferring (in order of
         // importance):
         //    - experiments that were explicitly assigned to us.
-        //    - distributed experiments.
         //    - experiments with a higher priority.
+        //    - experiments with fewer crates being built, so that agents are shared fairly
+        //      between the experiments of the same priority instead of a huge experiment
+        //      starving the ones queued after it. Agents pick the next experiment after every
+        //      chunk of crates, so they're rebalanced as they go.
+        //    - distributed experiments, so that the experiments already started are completed
+        //      before new ones are started. They're not preferred over the experiments with
+        //      fewer crates being built, as otherwise the first experiment of a priority would
+        //      get every agent until it's done.
         //    - older experiments.
-        Experiment::next_inner(db, Some(assignee), assignee)
-            .and_then(|ex| {
-                ex.map_or_else(
-                    || Experiment::next_inner(db, Some(&Assignee::Distributed), assignee),
-                    |exp| Ok(Some(exp)),
-                )
-            })
-            .and_then(|ex| {
-                ex.map_or_else(
-                    || Experiment::next_inner(db, None, assignee),
-                    |exp| Ok(Some(exp)),
-                )
-            })
+        Experiment::next_inner(db, Some(assignee), assignee).and_then(|ex| {
+            ex.map_or_else(
+                || Experiment::next_inner(db, None, assignee),
+                |exp| Ok(Some(exp)),
+            )
+        })
     }
 
     pub fn next(db: &Database, assignee: &Assignee) -> Fallible<Option<(bool, Experiment)>> {
@@ -389,12 +600,12 @@ impl Experiment {
                     const AGENT_QUERY: &str = r#"
                         SELECT *
                         FROM   experiments ex
-                        WHERE  ( ex.status = "queued" 
-                                OR ( status = "running"
+                        WHERE  ( ex.status = 'queued' 
+                                OR ( status = 'running'
                                             AND ( SELECT COUNT (*)
                                                   FROM  experiment_crates ex_crates
                                                   WHERE ex_crates.experiment = ex.name
-                                                          AND ( status = "queued") 
+                                                          AND ( status = 'queued') 
                                                           AND ( skipped = 0) 
                                                   > 0 ) ) )
                                 AND ( ex.assigned_to = ?1 ) 
@@ -403,6 +614,10 @@ impl Experiment {
                                                             FROM   agent_capabilities
                                                             WHERE  agent_name = ?2) )
                         ORDER  BY ex.priority DESC,
+                                  ( SELECT COUNT (*)
+                                    FROM  experiment_crates ex_crates
+                                    WHERE ex_crates.experiment = ex.name
+                                            AND ( status = 'running') ),
                                   ex.created_at
                         LIMIT  1;
                     "#;
@@ -418,12 +633,12 @@ impl Experiment {
                     const CLI_QUERY: &str = r#"
                         SELECT     *
                         FROM       experiments ex
-                        WHERE      ( ex.status = "queued" 
-                                        OR ( status = "running"
+                        WHERE      ( ex.status = 'queued' 
+                                        OR ( status = 'running'
                                             AND ( SELECT COUNT (*)
                                                   FROM  experiment_crates ex_crates
                                                   WHERE ex_crates.experiment = ex.name
-                                                          AND ( status = "queued") 
+                                                          AND ( status = 'queued') 
                                                           AND ( skipped = 0) 
                                                   > 0 ) ) )
                                    AND ( ex.assigned_to IS NULL OR ex.assigned_to = ?1 )
@@ -440,25 +655,33 @@ impl Experiment {
             const AGENT_UNASSIGNED_QUERY: &str = r#"
                 SELECT *
                 FROM   experiments ex
-                WHERE  ( ex.status = "queued"
-                        OR ( status = "running"
+                WHERE  ( ex.status = 'queued'
+                        OR ( status = 'running'
                                             AND ( SELECT COUNT (*)
                                                   FROM  experiment_crates ex_crates
                                                   WHERE ex_crates.experiment = ex.name
-                                                          AND ( status = "queued") 
+                                                          AND ( status = 'queued') 
                                                           AND ( skipped = 0) 
                                                   > 0 ) ) )
-                        AND ( ex.assigned_to IS NULL ) 
+                        AND ( ex.assigned_to IS NULL OR ex.assigned_to = ?2 )
                         AND ( ex.requirement IS NULL
                             OR ex.requirement IN (  SELECT capability
                                                     FROM   agent_capabilities
                                                     WHERE  agent_name = ?1) )
                 ORDER  BY ex.priority DESC,
+                          ( SELECT COUNT (*)
+                            FROM  experiment_crates ex_crates
+                            WHERE ex_crates.experiment = ex.name
+                                    AND ( status = 'running') ),
+                          ex.assigned_to IS NULL,
                           ex.created_at
                 LIMIT  1;
             "#;
 
-            (AGENT_UNASSIGNED_QUERY, vec![agent_name])
+            (
+                AGENT_UNASSIGNED_QUERY,
+                vec![agent_name, Assignee::Distributed.to_string()],
+            )
         };
 
         if let Some(record) = db.get_row(query, params.as_slice(), |r| {
@@ -470,6 +693,31 @@ impl Experiment {
         }
     }
 
+    /// Find the last experiment of the same mode completed before this one was created, which is
+    /// usually the previous run of the same kind of experiment.
+    pub fn previous(&self, db: &Database) -> Fallible<Option<Experiment>> {
+        let record = db
+            .query(
+                "SELECT * FROM experiments \
+                 WHERE name != ?1 AND mode = ?2 AND status = ?3 AND completed_at < ?4 \
+                 ORDER BY completed_at DESC LIMIT 1;",
+                &[
+                    &self.name,
+                    &self.mode.to_str(),
+                    &Status::Completed.to_str(),
+                    &self.created_at,
+                ],
+                |r| ExperimentDBRecord::from_row(r),
+            )?
+            .pop();
+
+        if let Some(record) = record {
+            Ok(Some(record.into_experiment()?))
+        } else {
+            Ok(None)
+        }
+    }
+
     pub fn get(db: &Database, name: &str) -> Fallible<Option<Experiment>> {
         let record = db.get_row(
             "SELECT * FROM experiments WHERE name = ?1;",
@@ -486,21 +734,7 @@ impl Experiment {
 
     pub fn report_failure(&mut self, db: &Database, agent: &Assignee) -> Fallible<()> {
         // Mark all the running crates from this agent as failed as well if the experiment failed
-        db.execute(
-            "
-            UPDATE experiment_crates 
-            SET assigned_to = NULL, status = ?1 \
-            WHERE experiment = ?2 AND status = ?3 \
-            AND assigned_to = ?4
-            ",
-            &[
-                &Status::Failed.to_string(),
-                &self.name,
-                &Status::Running.to_string(),
-                &agent.to_string(),
-            ],
-        )?;
-
+        self.queue().fail_leased_by(db, agent)?;
         self.set_status(db, Status::Failed)
     }
 
@@ -523,7 +757,9 @@ impl Experiment {
             }
             // Check if the old status was "running" and there is no completed date
             (Status::Running, new_status)
-                if self.completed_at.is_none() && new_status != Status::Failed =>
+                if self.completed_at.is_none()
+                    && new_status != Status::Failed
+                    && new_status != Status::Paused =>
             {
                 db.execute(
                     "UPDATE experiments SET completed_at = ?1 WHERE name = ?2;",
@@ -533,17 +769,7 @@ impl Experiment {
             }
             // Queue again failed crates
             (Status::Failed, Status::Queued) => {
-                db.execute(
-                    "UPDATE experiment_crates 
-                    SET status = ?1 \
-                    WHERE experiment = ?2 AND status = ?3
-                    ",
-                    &[
-                        &Status::Queued.to_string(),
-                        &self.name,
-                        &Status::Failed.to_string(),
-                    ],
-                )?;
+                self.queue().requeue_dead_letters(db)?;
             }
             _ => (),
         }
@@ -574,6 +800,34 @@ impl Experiment {
         Ok(())
     }
 
+    pub fn set_approved_by(&mut self, db: &Database, user: Option<&str>) -> Fallible<()> {
+        db.execute(
+            "UPDATE experiments SET approved_by = ?1 WHERE name = ?2;",
+            &[&user, &self.name.as_str()],
+        )?;
+        self.approved_by = user.map(|user| user.to_string());
+        Ok(())
+    }
+
+    /// Get when the latest progress update was posted on the tracking issue, if any.
+    pub fn progress_commented_at(&self, db: &Database) -> Fallible<Option<DateTime<Utc>>> {
+        Ok(db
+            .get_row(
+                "SELECT progress_commented_at FROM experiments WHERE name = ?1;",
+                &[&self.name.as_str()],
+                |r| r.get("progress_commented_at"),
+            )?
+            .and_then(|at| at))
+    }
+
+    pub fn set_progress_commented_at(&self, db: &Database, at: DateTime<Utc>) -> Fallible<()> {
+        db.execute(
+            "UPDATE experiments SET progress_commented_at = ?1 WHERE name = ?2;",
+            &[&at, &self.name.as_str()],
+        )?;
+        Ok(())
+    }
+
     pub fn raw_progress(&self, db: &Database) -> Fallible<(u32, u32)> {
         let results_len: u32 = db
             .get_row(
@@ -592,7 +846,10 @@ impl Experiment {
             )?
             .unwrap();
 
-        Ok((results_len, crates_len * 2))
+        Ok((
+            results_len,
+            crates_len * self.tested_toolchains().len() as u32,
+        ))
     }
 
     pub fn get_result_counts(&self, db: &Database) -> Fallible<Vec<(TestResult, u32)>> {
@@ -638,68 +895,54 @@ impl Experiment {
         }
     }
 
+    /// Toolchains the crates are built with, which is only the first one for surveys.
+    pub fn tested_toolchains(&self) -> &[Toolchain] {
+        if self.survey {
+            &self.toolchains[..1]
+        } else {
+            &self.toolchains
+        }
+    }
+
+    pub fn queue(&self) -> CrateQueue {
+        CrateQueue::new(&self.name)
+    }
+
     pub fn get_uncompleted_crates(
-        &self,
+        &mut self,
         db: &Database,
         config: &Config,
         assigned_to: &Assignee,
     ) -> Fallible<Vec<Crate>> {
-        let limit = self.crate_list_size(config);
-        let assigned_to = assigned_to.to_string();
-
-        db.transaction(|transaction| {
-            //get the first 'limit' queued crates from the experiment crates list
-            let mut params: Vec<&dyn rusqlite::types::ToSql> = Vec::new();
-            let crates = transaction
-                .query(
-                    "SELECT crate FROM experiment_crates WHERE experiment = ?1
-                     AND status = ?2 AND skipped = 0 LIMIT ?3;",
-                    &[&self.name, &Status::Queued.to_string(), &limit],
-                    |r| r.get("crate"),
-                )?
-                .into_iter()
-                .collect::<Vec<String>>();
-
-            crates.iter().for_each(|krate| params.push(krate));
-            let params_header: &[&dyn rusqlite::types::ToSql] = &[&assigned_to, &self.name];
-            //SQLite cannot handle queries with more than 999 variables
-            for params in params.chunks(SQL_VARIABLE_LIMIT) {
-                let params = [params_header, params].concat();
-                let update_query = &[
-                    "
-                    UPDATE experiment_crates 
-                    SET assigned_to = ?1, status = \"running\" \
-                    WHERE experiment = ?2 
-                    AND crate IN ("
-                        .to_string(),
-                    "?,".repeat(params.len() - 3),
-                    "?)".to_string(),
-                ]
-                .join("");
-
-                //update the status of the previously selected crates to 'Running'
-                transaction.execute(update_query, &params)?;
+        let crates = self.queue().lease(
+            db,
+            assigned_to,
+            self.crate_list_size(config),
+            Duration::seconds(config.server.distributed.lease_timeout as i64),
+            config.server.distributed.max_attempts,
+        )?;
+
+        // Give up on the experiment if the only crates left are the ones in the dead letters, so
+        // that they can be queued again by retrying it.
+        if crates.is_empty() && self.status == Status::Running && self.queue().is_drained(db)? {
+            let dead = self.queue().dead_letters(db)?;
+            if !dead.is_empty() {
+                warn!(
+                    "giving up on experiment {}, {} crates failed too many times",
+                    self.name,
+                    dead.len()
+                );
+                self.set_status(db, Status::Failed)?;
             }
-            crates
-                .iter()
-                .map(|krate| Ok(krate.parse()?))
-                .collect::<Fallible<Vec<Crate>>>()
-        })
+        }
+
+        Ok(crates)
     }
 
-    pub fn get_running_crates(
-        &self,
-        db: &Database,
-        assigned_to: &Assignee,
-    ) -> Fallible<Vec<Crate>> {
+    pub fn get_completed_crates(&self, db: &Database) -> Fallible<Vec<Crate>> {
         db.query(
-            "SELECT crate FROM experiment_crates WHERE experiment = ?1 \
-             AND status = ?2 AND assigned_to = ?3",
-            &[
-                &self.name,
-                &Status::Running.to_string(),
-                &assigned_to.to_string(),
-            ],
+            "SELECT crate FROM experiment_crates WHERE experiment = ?1 AND status = ?2",
+            &[&self.name, &Status::Completed.to_string()],
             |r| {
                 let value: String = r.get("crate");
                 Ok(value.parse()?)
@@ -708,6 +951,14 @@ impl Experiment {
         .into_iter()
         .collect::<Fallible<Vec<Crate>>>()
     }
+
+    pub fn get_running_crates(
+        &self,
+        db: &Database,
+        assigned_to: &Assignee,
+    ) -> Fallible<Vec<Crate>> {
+        self.queue().leased_by(db, assigned_to)
+    }
 }
 
 struct ExperimentDBRecord {
@@ -726,8 +977,22 @@ struct ExperimentDBRecord {
     status: String,
     assigned_to: Option<String>,
     report_url: Option<String>,
+    approved_by: Option<String>,
     ignore_blacklist: bool,
     requirement: Option<String>,
+    cargo_config: Option<String>,
+    expected_regressions: String,
+    env: String,
+    deny_lints: String,
+    cargo_update: bool,
+    failure_threshold: Option<String>,
+    reuse_baseline: bool,
+    canary: bool,
+    test_runner: String,
+    namespace: Option<String>,
+    survey: bool,
+    parent: Option<String>,
+    sandbox_image: Option<String>,
 }
 
 impl ExperimentDBRecord {
@@ -748,8 +1013,22 @@ impl ExperimentDBRecord {
             github_issue_number: row.get("github_issue_number"),
             assigned_to: row.get("assigned_to"),
             report_url: row.get("report_url"),
+            approved_by: row.get("approved_by"),
             ignore_blacklist: row.get("ignore_blacklist"),
             requirement: row.get("requirement"),
+            cargo_config: row.get("cargo_config"),
+            expected_regressions: row.get("expected_regressions"),
+            env: row.get("env"),
+            deny_lints: row.get("deny_lints"),
+            cargo_update: row.get("cargo_update"),
+            failure_threshold: row.get("failure_threshold"),
+            reuse_baseline: row.get("reuse_baseline"),
+            canary: row.get("canary"),
+            test_runner: row.get("test_runner"),
+            namespace: row.get("namespace"),
+            survey: row.get("survey"),
+            parent: row.get("parent"),
+            sandbox_image: row.get("sandbox_image"),
         }
     }
 
@@ -783,8 +1062,25 @@ impl ExperimentDBRecord {
             },
             status: self.status.parse()?,
             report_url: self.report_url,
+            approved_by: self.approved_by,
             ignore_blacklist: self.ignore_blacklist,
             requirement: self.requirement,
+            cargo_config: self.cargo_config,
+            expected_regressions: serde_json::from_str(&self.expected_regressions)?,
+            env: serde_json::from_str(&self.env)?,
+            deny_lints: serde_json::from_str(&self.deny_lints)?,
+            cargo_update: self.cargo_update,
+            failure_threshold: self
+                .failure_threshold
+                .map(|threshold| threshold.parse())
+                .transpose()?,
+            reuse_baseline: self.reuse_baseline,
+            canary: self.canary,
+            test_runner: self.test_runner.parse()?,
+            namespace: self.namespace,
+            survey: self.survey,
+            parent: self.parent,
+            sandbox_image: self.sandbox_image,
         })
     }
 }
@@ -792,7 +1088,8 @@ impl ExperimentDBRecord {
 #[cfg(test)]
 mod tests {
     use super::{
-        Assignee, AssigneeParseError, CrateSelect, DeferredCrateSelect, Experiment, Status,
+        Assignee, AssigneeParseError, CrateSelect, DeferredCrateSelect, Experiment,
+        FailureThreshold, Status,
     };
     use crate::actions::{Action, ActionsCtx, CreateExperiment};
     use crate::agent::Capabilities;
@@ -819,6 +1116,22 @@ mod tests {
                 "list:brson/hello-rs,lazy_static",
                 CrateSelect::List(demo_crates.clone()),
             ),
+            (
+                "file:lists/curated.toml",
+                CrateSelect::File("lists/curated.toml".into()),
+            ),
+            (
+                "rdeps:serde",
+                CrateSelect::ReverseDeps("serde".into(), None),
+            ),
+            (
+                "rdeps:serde:500",
+                CrateSelect::ReverseDeps("serde".into(), Some(500)),
+            ),
+            (
+                "expr:downloads>10000 && !category('embedded')",
+                CrateSelect::Expr("downloads>10000 && !category('embedded')".into()),
+            ),
         ];
 
         for (s, output) in suite.into_iter() {
@@ -829,6 +1142,13 @@ mod tests {
             );
         }
 
+        for s in &["rdeps:serde", "rdeps:serde:500", "expr:dependents > 100"] {
+            assert_eq!(CrateSelect::from_str(s).unwrap().to_string(), *s);
+        }
+        assert!(CrateSelect::from_str("rdeps:").is_err());
+        assert!(CrateSelect::from_str("rdeps:serde:many").is_err());
+        assert!(CrateSelect::from_str("expr:downloads>").is_err());
+
         assert_eq!(
             DeferredCrateSelect::from_str("http://git.io/Jes7o").unwrap(),
             DeferredCrateSelect::Indirect("http://git.io/Jes7o".parse().unwrap()),
@@ -850,6 +1170,26 @@ mod tests {
         assert_eq!(list, CrateSelect::List(demo_crates));
     }
 
+    #[test]
+    fn test_failure_threshold() {
+        let regressions = FailureThreshold::from_str("50").unwrap();
+        assert_eq!(regressions, FailureThreshold::Regressions(50));
+        assert_eq!(regressions.to_string(), "50");
+        assert!(!regressions.exceeded(49, 60));
+        assert!(regressions.exceeded(50, 60));
+
+        let percent = FailureThreshold::from_str("5%").unwrap();
+        assert_eq!(percent, FailureThreshold::Percent(5));
+        assert_eq!(percent.to_string(), "5%");
+        assert!(!percent.exceeded(4, 99));
+        assert!(!percent.exceeded(9, 200));
+        assert!(percent.exceeded(10, 200));
+
+        for invalid in &["", "0", "0%", "101%", "-1", "5.5%", "%"] {
+            assert!(FailureThreshold::from_str(invalid).is_err(), "{}", invalid);
+        }
+    }
+
     #[test]
     fn test_assignee_parsing() {
         assert_eq!(
@@ -880,10 +1220,21 @@ mod tests {
 
     #[test]
     fn test_assigning_experiment() {
-        let db = Database::temp().unwrap();
+        check_assigning_experiment(&Database::temp().unwrap());
+    }
+
+    #[test]
+    fn test_assigning_experiment_postgres() {
+        // The queries are written for SQLite, and translated when running on PostgreSQL
+        if let Some(db) = Database::temp_postgres().unwrap() {
+            check_assigning_experiment(&db);
+        }
+    }
+
+    fn check_assigning_experiment(db: &Database) {
         let config = Config::load().unwrap();
 
-        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+        crate::crates::lists::setup_test_lists(db, &config).unwrap();
 
         let mut tokens = Tokens::default();
         tokens.agents.insert("token1".into(), "agent-1".into());
@@ -898,7 +1249,7 @@ mod tests {
         let _ = Agents::new(db.clone(), &tokens).unwrap();
 
         let config = Config::default();
-        let ctx = ActionsCtx::new(&db, &config);
+        let ctx = ActionsCtx::new(db, &config);
 
         CreateExperiment::dummy("test").apply(&ctx).unwrap();
 
@@ -907,32 +1258,125 @@ mod tests {
         create_important.apply(&ctx).unwrap();
 
         // Test the important experiment is correctly assigned
-        let (new, ex) = Experiment::next(&db, &agent1).unwrap().unwrap();
+        let (new, ex) = Experiment::next(db, &agent1).unwrap().unwrap();
         assert!(new);
         assert_eq!(ex.name.as_str(), "important");
         assert_eq!(ex.status, Status::Running);
         assert_eq!(ex.assigned_to.unwrap(), Assignee::Distributed);
 
         // Test the same experiment is returned to the agent
-        let (new, mut ex) = Experiment::next(&db, &agent1).unwrap().unwrap();
+        let (new, mut ex) = Experiment::next(db, &agent1).unwrap().unwrap();
         assert!(!new);
         assert_eq!(ex.name.as_str(), "important");
 
         //Mark the experiment as completed, otherwise agent2 will still pick it as has uncompleted crates
-        ex.set_status(&db, Status::Completed).unwrap();
+        ex.set_status(db, Status::Completed).unwrap();
 
         // Test the less important experiment is assigned to the next agent
-        let (new, mut ex) = Experiment::next(&db, &agent2).unwrap().unwrap();
+        let (new, mut ex) = Experiment::next(db, &agent2).unwrap().unwrap();
         assert!(new);
         assert_eq!(ex.name.as_str(), "test");
         assert_eq!(ex.status, Status::Running);
         assert_eq!(ex.assigned_to.clone().unwrap(), Assignee::Distributed);
 
         //Mark the experiment as completed, otherwise agent3 will still pick it as has uncompleted crates
-        ex.set_status(&db, Status::Completed).unwrap();
+        ex.set_status(db, Status::Completed).unwrap();
 
         // Test no other experiment is available for the other agents
-        assert!(Experiment::next(&db, &agent3).unwrap().is_none());
+        assert!(Experiment::next(db, &agent3).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_assigning_experiment_fair_share() {
+        let db = Database::temp().unwrap();
+        let config = Config::load().unwrap();
+
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+
+        let mut tokens = Tokens::default();
+        tokens.agents.insert("token1".into(), "agent-1".into());
+        tokens.agents.insert("token2".into(), "agent-2".into());
+        tokens.agents.insert("token3".into(), "agent-3".into());
+
+        let agent1 = Assignee::Agent("agent-1".to_string());
+        let agent2 = Assignee::Agent("agent-2".to_string());
+        let agent3 = Assignee::Agent("agent-3".to_string());
+
+        // Populate the `agents` table
+        let _ = Agents::new(db.clone(), &tokens).unwrap();
+
+        let config = Config::default();
+        let ctx = ActionsCtx::new(&db, &config);
+
+        CreateExperiment::dummy("huge").apply(&ctx).unwrap();
+        CreateExperiment::dummy("urgent").apply(&ctx).unwrap();
+
+        // The older experiment is picked first
+        let (_, mut ex) = Experiment::next(&db, &agent1).unwrap().unwrap();
+        assert_eq!(ex.name.as_str(), "huge");
+        assert!(!ex
+            .get_uncompleted_crates(&db, &config, &agent1)
+            .unwrap()
+            .is_empty());
+
+        // The next agent works on the experiment of the same priority nobody is working on, even
+        // if the older one still has crates left
+        let (new, mut ex) = Experiment::next(&db, &agent2).unwrap().unwrap();
+        assert!(new);
+        assert_eq!(ex.name.as_str(), "urgent");
+        assert!(!ex
+            .get_uncompleted_crates(&db, &config, &agent2)
+            .unwrap()
+            .is_empty());
+
+        // With both experiments being worked on, the older one is preferred again
+        let (_, ex) = Experiment::next(&db, &agent3).unwrap().unwrap();
+        assert_eq!(ex.name.as_str(), "huge");
+    }
+
+    #[test]
+    fn test_assigning_experiment_prefers_distributed() {
+        let db = Database::temp().unwrap();
+        let config = Config::load().unwrap();
+
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+
+        let mut tokens = Tokens::default();
+        tokens.agents.insert("token1".into(), "agent-1".into());
+        tokens.agents.insert("token2".into(), "agent-2".into());
+
+        let agent1 = Assignee::Agent("agent-1".to_string());
+        let agent2 = Assignee::Agent("agent-2".to_string());
+
+        // Populate the `agents` table
+        let _ = Agents::new(db.clone(), &tokens).unwrap();
+
+        let config = Config::default();
+        let ctx = ActionsCtx::new(&db, &config);
+
+        CreateExperiment::dummy("queued").apply(&ctx).unwrap();
+        CreateExperiment::dummy("started").apply(&ctx).unwrap();
+
+        // Start the newer experiment, without any crate being built right now
+        let mut started = Experiment::get(&db, "started").unwrap().unwrap();
+        started.set_status(&db, Status::Running).unwrap();
+        started
+            .set_assigned_to(&db, Some(&Assignee::Distributed))
+            .unwrap();
+
+        // The experiment already started is completed first, even if it's newer
+        let (new, mut ex) = Experiment::next(&db, &agent1).unwrap().unwrap();
+        assert!(!new);
+        assert_eq!(ex.name.as_str(), "started");
+        assert!(!ex
+            .get_uncompleted_crates(&db, &config, &agent1)
+            .unwrap()
+            .is_empty());
+
+        // But not when its crates are already being built by other agents
+        let (new, ex) = Experiment::next(&db, &agent2).unwrap().unwrap();
+        assert!(new);
+        assert_eq!(ex.name.as_str(), "queued");
     }
 
     #[test]
@@ -1049,7 +1493,7 @@ mod tests {
 
         // Create a dummy experiment
         CreateExperiment::dummy("dummy").apply(&ctx).unwrap();
-        let ex = Experiment::get(&db, "dummy").unwrap().unwrap();
+        let mut ex = Experiment::get(&db, "dummy").unwrap().unwrap();
         let crates = ex
             .get_uncompleted_crates(&db, &config, &Assignee::CLI)
             .unwrap();
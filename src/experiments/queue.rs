@@ -0,0 +1,592 @@
+use crate::crates::Crate;
+use crate::db::{Database, QueryUtils, ToSql};
+use crate::experiments::{Assignee, Status};
+use crate::prelude::*;
+use chrono::{Duration, Utc};
+
+//SQLite cannot handle queries with more than 999 variables
+static SQL_VARIABLE_LIMIT: usize = 500;
+
+/// Queue of the crates of an experiment, backed by the `experiment_crates` table.
+///
+/// Crates are leased to an assignee for a limited amount of time: if the lease is not renewed
+/// before it expires the crates become visible again, and they're given to the next assignee
+/// asking for them. Crates whose lease expired too many times are moved to the dead letters
+/// (marked as failed), and they're queued again only when the experiment is retried.
+pub struct CrateQueue<'a> {
+    experiment: &'a str,
+}
+
+impl<'a> CrateQueue<'a> {
+    pub fn new(experiment: &'a str) -> Self {
+        CrateQueue { experiment }
+    }
+
+    pub fn enqueue<T: QueryUtils>(&self, db: &T, krate: &Crate, skipped: bool) -> Fallible<()> {
+        db.execute(
+            "INSERT INTO experiment_crates (experiment, crate, skipped, status) \
+             VALUES (?1, ?2, ?3, ?4);",
+            &[
+                &self.experiment,
+                &krate.id(),
+                &skipped,
+                &Status::Queued.to_string(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Add a crate whose results are already known to the queue, without ever leasing it.
+    pub fn enqueue_completed<T: QueryUtils>(&self, db: &T, krate: &Crate) -> Fallible<()> {
+        db.execute(
+            "INSERT INTO experiment_crates (experiment, crate, skipped, status) \
+             VALUES (?1, ?2, 0, ?3);",
+            &[
+                &self.experiment,
+                &krate.id(),
+                &Status::Completed.to_string(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Hold back all the crates except the provided ones, which are the only ones leased until
+    /// the crates are released.
+    pub fn hold_except<T: QueryUtils>(&self, db: &T, canaries: &[Crate]) -> Fallible<()> {
+        // `NOT IN ()` is not valid SQL on every backend
+        if canaries.is_empty() {
+            db.execute(
+                "UPDATE experiment_crates SET held = 1 WHERE experiment = ?1;",
+                &[&self.experiment],
+            )?;
+            return Ok(());
+        }
+
+        let canaries = canaries.iter().map(|krate| krate.id()).collect::<Vec<_>>();
+        let params_header: &[&dyn ToSql] = &[&self.experiment];
+        let params = canaries
+            .iter()
+            .map(|krate| krate as &dyn ToSql)
+            .collect::<Vec<_>>();
+        let query = format!(
+            "UPDATE experiment_crates SET held = 1 WHERE experiment = ?1 AND crate NOT IN ({});",
+            vec!["?"; params.len()].join(","),
+        );
+        db.execute(&query, &[params_header, &params[..]].concat())?;
+        Ok(())
+    }
+
+    /// Release all the held crates, returning how many of them were released.
+    pub fn release<T: QueryUtils>(&self, db: &T) -> Fallible<usize> {
+        Ok(db.execute(
+            "UPDATE experiment_crates SET held = 0 WHERE experiment = ?1 AND held = 1;",
+            &[&self.experiment],
+        )?)
+    }
+
+    /// Check whether some crates are held and all the crates not held were processed.
+    pub fn is_canary_drained(&self, db: &Database) -> Fallible<bool> {
+        let holding = db.exists(
+            "SELECT rowid FROM experiment_crates WHERE experiment = ?1 AND held = 1;",
+            &[&self.experiment],
+        )?;
+        let pending = db.exists(
+            "SELECT rowid FROM experiment_crates WHERE experiment = ?1 \
+             AND skipped = 0 AND held = 0 AND (status = ?2 OR status = ?3);",
+            &[
+                &self.experiment,
+                &Status::Queued.to_string(),
+                &Status::Running.to_string(),
+            ],
+        )?;
+        Ok(holding && !pending)
+    }
+
+    /// Lease up to `limit` crates (all of them if it's negative) to the assignee, including the
+    /// crates whose previous lease expired. The results of a previous lease are removed, so that
+    /// both toolchains of a crate are built by the same assignee.
+    pub fn lease(
+        &self,
+        db: &Database,
+        assignee: &Assignee,
+        limit: i32,
+        timeout: Duration,
+        max_attempts: u32,
+    ) -> Fallible<Vec<Crate>> {
+        let now = Utc::now();
+        let expires_at = now + timeout;
+        let assignee = assignee.to_string();
+        // PostgreSQL doesn't accept negative limits like SQLite does
+        let limit = if limit < 0 {
+            i64::max_value()
+        } else {
+            i64::from(limit)
+        };
+
+        db.transaction(|t| {
+            let dead = t.execute(
+                "UPDATE experiment_crates SET status = ?1, assigned_to = NULL, \
+                 lease_expires_at = NULL \
+                 WHERE experiment = ?2 AND status = ?3 AND lease_expires_at < ?4 \
+                 AND attempts >= ?5;",
+                &[
+                    &Status::Failed.to_string(),
+                    &self.experiment,
+                    &Status::Running.to_string(),
+                    &now,
+                    &max_attempts,
+                ],
+            )?;
+            if dead > 0 {
+                warn!(
+                    "{} crates of experiment {} moved to the dead letters",
+                    dead, self.experiment
+                );
+            }
+
+            let crates = t
+                .query(
+                    "SELECT crate FROM experiment_crates WHERE experiment = ?1 \
+                     AND skipped = 0 AND held = 0 \
+                     AND (status = ?2 OR (status = ?3 AND lease_expires_at < ?4)) LIMIT ?5;",
+                    &[
+                        &self.experiment,
+                        &Status::Queued.to_string(),
+                        &Status::Running.to_string(),
+                        &now,
+                        &limit,
+                    ],
+                    |r| r.get("crate"),
+                )?
+                .into_iter()
+                .collect::<Vec<String>>();
+
+            let running = Status::Running.to_string();
+            let params_header: &[&dyn ToSql] =
+                &[&assignee, &running, &expires_at, &self.experiment];
+            let delete_header: &[&dyn ToSql] = &[&self.experiment];
+            let params = crates
+                .iter()
+                .map(|krate| krate as &dyn ToSql)
+                .collect::<Vec<_>>();
+            for params in params.chunks(SQL_VARIABLE_LIMIT) {
+                let query = format!(
+                    "UPDATE experiment_crates \
+                     SET assigned_to = ?1, status = ?2, lease_expires_at = ?3, \
+                     attempts = attempts + 1 \
+                     WHERE experiment = ?4 AND crate IN ({}?);",
+                    "?,".repeat(params.len() - 1),
+                );
+                t.execute(&query, &[params_header, params].concat())?;
+
+                let query = format!(
+                    "DELETE FROM results WHERE experiment = ?1 AND crate IN ({}?);",
+                    "?,".repeat(params.len() - 1),
+                );
+                t.execute(&query, &[delete_header, params].concat())?;
+            }
+
+            crates
+                .iter()
+                .map(|krate| Ok(krate.parse()?))
+                .collect::<Fallible<Vec<Crate>>>()
+        })
+    }
+
+    /// Get the crates currently leased to the assignee.
+    pub fn leased_by(&self, db: &Database, assignee: &Assignee) -> Fallible<Vec<Crate>> {
+        db.query(
+            "SELECT crate FROM experiment_crates WHERE experiment = ?1 \
+             AND status = ?2 AND assigned_to = ?3",
+            &[
+                &self.experiment,
+                &Status::Running.to_string(),
+                &assignee.to_string(),
+            ],
+            |r| {
+                let value: String = r.get("crate");
+                Ok(value.parse()?)
+            },
+        )?
+        .into_iter()
+        .collect::<Fallible<Vec<Crate>>>()
+    }
+
+    /// Extend the lease of all the crates leased to the assignee.
+    pub fn renew(&self, db: &Database, assignee: &Assignee, timeout: Duration) -> Fallible<()> {
+        db.execute(
+            "UPDATE experiment_crates SET lease_expires_at = ?1 \
+             WHERE experiment = ?2 AND status = ?3 AND assigned_to = ?4;",
+            &[
+                &(Utc::now() + timeout),
+                &self.experiment,
+                &Status::Running.to_string(),
+                &assignee.to_string(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Expire the leases of all the crates leased to the assignee, so that they're given to the
+    /// next assignee asking for them. Returns how many leases were expired.
+    pub fn expire_leased_by(&self, db: &Database, assignee: &Assignee) -> Fallible<usize> {
+        Ok(db.execute(
+            "UPDATE experiment_crates SET assigned_to = NULL, lease_expires_at = ?1 \
+             WHERE experiment = ?2 AND status = ?3 AND assigned_to = ?4;",
+            &[
+                &Utc::now(),
+                &self.experiment,
+                &Status::Running.to_string(),
+                &assignee.to_string(),
+            ],
+        )?)
+    }
+
+    /// Acknowledge the crate as completed, if the results of all the `toolchains` tested by the
+    /// experiment were recorded.
+    pub fn ack(&self, db: &Database, krate: &Crate, toolchains: u32) -> Fallible<bool> {
+        let changes = db.execute(
+            "UPDATE experiment_crates SET status = ?1, lease_expires_at = NULL \
+             WHERE experiment = ?2 AND crate = ?3 \
+             AND ( (SELECT COUNT(*) FROM results WHERE experiment = ?2 AND crate = ?3) >= ?4 )",
+            &[
+                &Status::Completed.to_string(),
+                &self.experiment,
+                &krate.id(),
+                &toolchains,
+            ],
+        )?;
+        Ok(changes > 0)
+    }
+
+    /// Move all the crates leased to the assignee to the dead letters.
+    pub fn fail_leased_by(&self, db: &Database, assignee: &Assignee) -> Fallible<()> {
+        db.execute(
+            "UPDATE experiment_crates SET assigned_to = NULL, lease_expires_at = NULL, \
+             status = ?1 WHERE experiment = ?2 AND status = ?3 AND assigned_to = ?4;",
+            &[
+                &Status::Failed.to_string(),
+                &self.experiment,
+                &Status::Running.to_string(),
+                &assignee.to_string(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Queue again all the crates in the dead letters.
+    pub fn requeue_dead_letters(&self, db: &Database) -> Fallible<()> {
+        db.execute(
+            "UPDATE experiment_crates SET status = ?1, attempts = 0 \
+             WHERE experiment = ?2 AND status = ?3;",
+            &[
+                &Status::Queued.to_string(),
+                &self.experiment,
+                &Status::Failed.to_string(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Queue again all the crates processed by the assignee, including the ones it's currently
+    /// processing, removing their results. Returns the queued crates.
+    pub fn requeue_processed_by(&self, db: &Database, assignee: &Assignee) -> Fallible<Vec<Crate>> {
+        let assignee = assignee.to_string();
+        db.transaction(|t| {
+            let crates = t
+                .query(
+                    "SELECT crate FROM experiment_crates WHERE experiment = ?1 \
+                     AND skipped = 0 AND assigned_to = ?2;",
+                    &[&self.experiment, &assignee],
+                    |r| r.get("crate"),
+                )?
+                .into_iter()
+                .collect::<Vec<String>>();
+
+            for krate in &crates {
+                t.execute(
+                    "DELETE FROM results WHERE experiment = ?1 AND crate = ?2;",
+                    &[&self.experiment, krate],
+                )?;
+            }
+            t.execute(
+                "UPDATE experiment_crates SET status = ?1, assigned_to = NULL, \
+                 lease_expires_at = NULL, attempts = 0 \
+                 WHERE experiment = ?2 AND skipped = 0 AND assigned_to = ?3;",
+                &[&Status::Queued.to_string(), &self.experiment, &assignee],
+            )?;
+
+            crates
+                .iter()
+                .map(|krate| Ok(krate.parse()?))
+                .collect::<Fallible<Vec<Crate>>>()
+        })
+    }
+
+    pub fn dead_letters(&self, db: &Database) -> Fallible<Vec<Crate>> {
+        db.query(
+            "SELECT crate FROM experiment_crates WHERE experiment = ?1 AND status = ?2;",
+            &[&self.experiment, &Status::Failed.to_string()],
+            |r| {
+                let value: String = r.get("crate");
+                Ok(value.parse()?)
+            },
+        )?
+        .into_iter()
+        .collect::<Fallible<Vec<Crate>>>()
+    }
+
+    /// Check whether all the crates not in the dead letters were completed. Held crates are
+    /// ignored, as they can't be leased anyway.
+    pub fn is_drained(&self, db: &Database) -> Fallible<bool> {
+        Ok(!db.exists(
+            "SELECT rowid FROM experiment_crates WHERE experiment = ?1 \
+             AND skipped = 0 AND held = 0 AND (status = ?2 OR status = ?3);",
+            &[
+                &self.experiment,
+                &Status::Queued.to_string(),
+                &Status::Running.to_string(),
+            ],
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CrateQueue;
+    use crate::actions::{Action, ActionsCtx, CreateExperiment};
+    use crate::config::Config;
+    use crate::db::{Database, QueryUtils};
+    use crate::experiments::Assignee;
+    use chrono::Duration;
+
+    fn agent(name: &str) -> Assignee {
+        Assignee::Agent(name.to_string())
+    }
+
+    #[test]
+    fn test_lease_and_ack() {
+        let db = Database::temp().unwrap();
+        let config = Config::default();
+        let ctx = ActionsCtx::new(&db, &config);
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+        CreateExperiment::dummy("dummy").apply(&ctx).unwrap();
+
+        let queue = CrateQueue::new("dummy");
+        let first = queue
+            .lease(&db, &agent("a"), 1, Duration::hours(1), 3)
+            .unwrap();
+        assert_eq!(first.len(), 1);
+        assert_eq!(queue.leased_by(&db, &agent("a")).unwrap(), first);
+
+        // Leased crates are not visible to other agents
+        let rest = queue
+            .lease(&db, &agent("b"), -1, Duration::hours(1), 3)
+            .unwrap();
+        assert!(!rest.contains(&first[0]));
+        assert!(queue
+            .lease(&db, &agent("c"), -1, Duration::hours(1), 3)
+            .unwrap()
+            .is_empty());
+
+        // Crates without both results can't be acknowledged
+        assert!(!queue.ack(&db, &first[0], 2).unwrap());
+        assert!(!queue.is_drained(&db).unwrap());
+    }
+
+    #[test]
+    fn test_visibility_timeout() {
+        let db = Database::temp().unwrap();
+        let config = Config::default();
+        let ctx = ActionsCtx::new(&db, &config);
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+        CreateExperiment::dummy("dummy").apply(&ctx).unwrap();
+
+        let queue = CrateQueue::new("dummy");
+        let expired = Duration::seconds(-1);
+
+        // Expired leases are given to the next agent
+        let crates = queue.lease(&db, &agent("a"), -1, expired, 2).unwrap();
+        assert!(!crates.is_empty());
+        assert_eq!(
+            queue.lease(&db, &agent("b"), -1, expired, 2).unwrap(),
+            crates
+        );
+        assert!(queue.leased_by(&db, &agent("a")).unwrap().is_empty());
+
+        // Renewing the lease keeps the crates assigned
+        queue.renew(&db, &agent("b"), Duration::hours(1)).unwrap();
+        assert!(queue
+            .lease(&db, &agent("c"), -1, expired, 2)
+            .unwrap()
+            .is_empty());
+
+        // After too many attempts the crates are moved to the dead letters
+        queue.renew(&db, &agent("b"), expired).unwrap();
+        assert!(queue
+            .lease(&db, &agent("c"), -1, expired, 2)
+            .unwrap()
+            .is_empty());
+        assert_eq!(queue.dead_letters(&db).unwrap(), crates);
+        assert!(queue.is_drained(&db).unwrap());
+
+        queue.requeue_dead_letters(&db).unwrap();
+        assert!(queue.dead_letters(&db).unwrap().is_empty());
+        assert_eq!(
+            queue.lease(&db, &agent("c"), -1, expired, 2).unwrap(),
+            crates
+        );
+    }
+
+    #[test]
+    fn test_expire_leased_by() {
+        let db = Database::temp().unwrap();
+        let config = Config::default();
+        let ctx = ActionsCtx::new(&db, &config);
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+        CreateExperiment::dummy("dummy").apply(&ctx).unwrap();
+
+        let queue = CrateQueue::new("dummy");
+        let crates = queue
+            .lease(&db, &agent("a"), -1, Duration::hours(1), 2)
+            .unwrap();
+        assert!(!crates.is_empty());
+        assert_eq!(queue.expire_leased_by(&db, &agent("b")).unwrap(), 0);
+
+        // The crates of the dead agent are given to the next one
+        assert_eq!(
+            queue.expire_leased_by(&db, &agent("a")).unwrap(),
+            crates.len()
+        );
+        assert!(queue.leased_by(&db, &agent("a")).unwrap().is_empty());
+        assert_eq!(
+            queue
+                .lease(&db, &agent("b"), -1, Duration::hours(1), 2)
+                .unwrap(),
+            crates
+        );
+    }
+
+    #[test]
+    fn test_requeue_processed_by() {
+        let db = Database::temp().unwrap();
+        let config = Config::default();
+        let ctx = ActionsCtx::new(&db, &config);
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+        CreateExperiment::dummy("dummy").apply(&ctx).unwrap();
+
+        let queue = CrateQueue::new("dummy");
+        let bad = queue
+            .lease(&db, &agent("bad"), 1, Duration::hours(1), 3)
+            .unwrap();
+        let good = queue
+            .lease(&db, &agent("good"), -1, Duration::hours(1), 3)
+            .unwrap();
+
+        // The bad agent completed its crate before being found out
+        for tc in &["stable", "beta"] {
+            db.execute(
+                "INSERT INTO results (experiment, crate, toolchain, result, log, encoding) \
+                 VALUES ('dummy', ?1, ?2, 'test-pass', '', 'plain');",
+                &[&bad[0].id(), tc],
+            )
+            .unwrap();
+        }
+        assert!(queue.ack(&db, &bad[0], 2).unwrap());
+
+        assert_eq!(queue.requeue_processed_by(&db, &agent("bad")).unwrap(), bad);
+        assert!(!db
+            .exists("SELECT rowid FROM results WHERE experiment = 'dummy';", &[])
+            .unwrap());
+        assert_eq!(queue.leased_by(&db, &agent("good")).unwrap(), good);
+        assert_eq!(
+            queue
+                .lease(&db, &agent("other"), -1, Duration::hours(1), 3)
+                .unwrap(),
+            bad
+        );
+    }
+
+    #[test]
+    fn test_lease_removes_partial_results() {
+        let db = Database::temp().unwrap();
+        let config = Config::default();
+        let ctx = ActionsCtx::new(&db, &config);
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+        CreateExperiment::dummy("dummy").apply(&ctx).unwrap();
+
+        let queue = CrateQueue::new("dummy");
+        let crates = queue
+            .lease(&db, &agent("a"), 1, Duration::seconds(-1), 3)
+            .unwrap();
+
+        // The first agent only sent the result of one toolchain before its lease expired
+        db.execute(
+            "INSERT INTO results (experiment, crate, toolchain, result, log, encoding) \
+             VALUES ('dummy', ?1, 'stable', 'test-pass', '', 'plain');",
+            &[&crates[0].id()],
+        )
+        .unwrap();
+        let count = || -> u32 {
+            db.get_row(
+                "SELECT COUNT(*) AS count FROM results WHERE experiment = ?1;",
+                &[&"dummy"],
+                |r| r.get("count"),
+            )
+            .unwrap()
+            .unwrap()
+        };
+        assert_eq!(count(), 1);
+
+        // Giving the crate to another agent discards that result
+        assert_eq!(
+            queue
+                .lease(&db, &agent("b"), 1, Duration::hours(1), 3)
+                .unwrap(),
+            crates
+        );
+        assert_eq!(count(), 0);
+    }
+
+    #[test]
+    fn test_hold_and_release() {
+        let db = Database::temp().unwrap();
+        let config = Config::default();
+        let ctx = ActionsCtx::new(&db, &config);
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+        CreateExperiment::dummy("dummy").apply(&ctx).unwrap();
+
+        let queue = CrateQueue::new("dummy");
+        let all = queue
+            .lease(&db, &agent("a"), -1, Duration::seconds(-1), 3)
+            .unwrap();
+        assert!(all.len() > 1);
+        queue.requeue_processed_by(&db, &agent("a")).unwrap();
+        assert!(!queue.is_canary_drained(&db).unwrap());
+
+        // Only the canaries are leased while the other crates are held
+        queue.hold_except(&db, &all[..1]).unwrap();
+        assert_eq!(
+            queue
+                .lease(&db, &agent("b"), -1, Duration::hours(1), 3)
+                .unwrap(),
+            &all[..1]
+        );
+        assert!(!queue.is_canary_drained(&db).unwrap());
+        queue.fail_leased_by(&db, &agent("b")).unwrap();
+        assert!(queue.is_canary_drained(&db).unwrap());
+
+        assert_eq!(queue.release(&db).unwrap(), all.len() - 1);
+        assert!(!queue.is_canary_drained(&db).unwrap());
+        assert_eq!(
+            queue
+                .lease(&db, &agent("c"), -1, Duration::hours(1), 3)
+                .unwrap(),
+            &all[1..]
+        );
+
+        // Without canaries every crate is held
+        queue.hold_except(&db, &[]).unwrap();
+        assert_eq!(queue.release(&db).unwrap(), all.len());
+    }
+}
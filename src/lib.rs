@@ -22,9 +22,11 @@ pub mod results;
 pub mod runner;
 pub mod server;
 pub mod toolchain;
+pub mod triage;
 
 pub(crate) static GIT_REVISION: Option<&str> = include!(concat!(env!("OUT_DIR"), "/sha"));
 pub(crate) static CRATER_REPO_URL: &str = "https://github.com/rust-lang/crater";
+pub(crate) static HOST_TARGET: &str = env!("CRATER_HOST_TARGET");
 
 lazy_static::lazy_static! {
     pub static ref USER_AGENT: String = format!(
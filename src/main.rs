@@ -30,6 +30,9 @@ fn main() {
     }
     rustwide::logging::init_with(env.build());
 
+    // Do this before the builds start, as changing the environment is not thread safe.
+    utils::env::isolate_builds();
+
     let success = match panic::catch_unwind(main_) {
         Ok(Ok(())) => true,
         Ok(Err(e)) => {
@@ -53,5 +56,6 @@ fn main() {
 }
 
 fn main_() -> Fallible<()> {
+    utils::workspace::select_container_runtime(&crater::dirs::WORK_DIR)?;
     cli::Crater::from_args().run()
 }
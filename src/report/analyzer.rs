@@ -1,10 +1,10 @@
-use super::{Comparison, CrateResult, RawTestResults};
+use super::{Comparison, CrateResult, IceSignature, RawTestResults};
 use crate::crates::Crate;
 use crate::results::{
-    FailureReason,
-    TestResult::{self, BuildFail},
+    DiagnosticCode, FailureReason, Lints,
+    TestResult::{self, BuildFail, LintWarnings},
 };
-use indexmap::IndexMap;
+use indexmap::{IndexMap, IndexSet};
 use std::collections::BTreeSet;
 
 pub enum ToolchainSelect {
@@ -33,6 +33,67 @@ pub struct TestResults {
     pub info: IndexMap<Comparison, u32>,
 }
 
+impl TestResults {
+    /// Results shared by the most root crates of a category (the ones not failing because of a
+    /// dependency), along with the number of crates with each of them, largest group first.
+    pub fn error_groups(&self, comparison: Comparison) -> Vec<(TestResult, usize)> {
+        let mut groups = match self.categories.get(&comparison) {
+            Some(ReportCrates::Complete { results, .. }) => results
+                .iter()
+                .map(|(res, crates)| (res.clone(), crates.len()))
+                .collect::<Vec<_>>(),
+            _ => Vec::new(),
+        };
+        groups.sort_by(|a, b| b.1.cmp(&a.1));
+        groups
+    }
+
+    /// Root crates of a category that crashed the compiler, grouped by the signature of the
+    /// crash, largest group first.
+    pub fn ice_groups(&self, comparison: Comparison) -> Vec<(IceSignature, Vec<CrateResult>)> {
+        let mut groups = IndexMap::new();
+        if let Some(ReportCrates::Complete { results, .. }) = self.categories.get(&comparison) {
+            for krate in results.values().flatten() {
+                if let Some(ice) = &krate.ice {
+                    groups
+                        .entry(ice.clone())
+                        .or_insert_with(Vec::new)
+                        .push(krate.clone());
+                }
+            }
+        }
+
+        let mut groups = groups.into_iter().collect::<Vec<_>>();
+        groups.sort_by(|a, b| b.1.len().cmp(&a.1.len()));
+        groups
+    }
+
+    /// Root crates of a category grouped by the codes of the errors in their logs, largest group
+    /// first. Crates emitting multiple error codes are present in multiple groups.
+    pub fn error_code_groups(
+        &self,
+        comparison: Comparison,
+    ) -> Vec<(DiagnosticCode, Vec<CrateResult>)> {
+        let mut groups = IndexMap::new();
+        if let Some(ReportCrates::Complete { results, .. }) = self.categories.get(&comparison) {
+            // Crates failing with multiple errors are present in multiple results
+            let crates = results.values().flatten().collect::<IndexSet<_>>();
+            for krate in crates {
+                for code in &krate.error_codes {
+                    groups
+                        .entry(code.clone())
+                        .or_insert_with(Vec::new)
+                        .push(krate.clone());
+                }
+            }
+        }
+
+        let mut groups = groups.into_iter().collect::<Vec<_>>();
+        groups.sort_by(|a, b| b.1.len().cmp(&a.1.len()));
+        groups
+    }
+}
+
 fn analyze_detailed(toolchain: usize, crates: Vec<CrateResult>) -> ReportCrates {
     let mut tree = IndexMap::new();
     let mut results = IndexMap::new();
@@ -54,20 +115,29 @@ fn analyze_detailed(toolchain: usize, crates: Vec<CrateResult>) -> ReportCrates
 
     for krate in root {
         // record results only for root crates
-        if let BuildFail(FailureReason::CompilerError(codes)) =
-            krate.runs[toolchain].clone().unwrap().res
-        {
-            for code in codes {
-                results
-                    .entry(BuildFail(FailureReason::CompilerError(btreeset![code])))
-                    .or_insert_with(Vec::new)
-                    .push(krate.clone())
+        match krate.runs[toolchain].clone().unwrap().res {
+            BuildFail(FailureReason::CompilerError(codes)) => {
+                for code in codes {
+                    results
+                        .entry(BuildFail(FailureReason::CompilerError(btreeset![code])))
+                        .or_insert_with(Vec::new)
+                        .push(krate.clone())
+                }
             }
-        } else {
-            results
-                .entry(krate.runs[toolchain].as_ref().unwrap().res.clone())
-                .or_insert_with(Vec::new)
-                .push(krate)
+            LintWarnings(Lints(lints)) => {
+                // Only the lints emitted with a single toolchain explain the change
+                let other = match krate.runs[1 - toolchain].as_ref().map(|run| &run.res) {
+                    Some(LintWarnings(Lints(other))) => other.clone(),
+                    _ => BTreeSet::new(),
+                };
+                for lint in lints.difference(&other) {
+                    results
+                        .entry(LintWarnings(Lints(btreeset![lint.clone()])))
+                        .or_insert_with(Vec::new)
+                        .push(krate.clone())
+                }
+            }
+            res => results.entry(res).or_insert_with(Vec::new).push(krate),
         }
     }
 
@@ -82,6 +152,7 @@ pub fn analyze_report(test: RawTestResults) -> TestResults {
             .or_insert_with(Vec::new)
             .push(krate);
     }
+    comparison.sort_by(|a, _, b, _| a.report_order().cmp(&b.report_order()));
 
     let info = comparison
         .iter()
@@ -107,11 +178,12 @@ mod tests {
     use super::*;
     use crate::config::Config;
     use crate::crates::{Crate, RegistryCrate};
-    use crate::experiments::{CapLints, Experiment, Mode, Status};
+    use crate::experiments::{CapLints, Experiment, Mode, Status, TestRunner};
     use crate::report::{generate_report, Comparison};
-    use crate::results::{DummyDB, FailureReason::*};
+    use crate::results::{DummyDB, EncodedLog, FailureReason::*};
     use crate::toolchain::{MAIN_TOOLCHAIN, TEST_TOOLCHAIN};
     use failure::Fallible;
+    use std::collections::BTreeMap;
 
     #[test]
     fn test_report_analysis() -> Fallible<()> {
@@ -164,8 +236,22 @@ mod tests {
             status: Status::GeneratingReport,
             assigned_to: None,
             report_url: None,
+            approved_by: None,
             ignore_blacklist: false,
             requirement: None,
+            cargo_config: None,
+            expected_regressions: Vec::new(),
+            env: BTreeMap::new(),
+            deny_lints: Vec::new(),
+            cargo_update: false,
+            failure_threshold: None,
+            reuse_baseline: false,
+            canary: false,
+            test_runner: TestRunner::Cargo,
+            namespace: None,
+            survey: false,
+            parent: None,
+            sandbox_image: None,
         };
 
         let crates = record_crates! {db, ex,
@@ -253,9 +339,237 @@ mod tests {
         categories.insert(Comparison::Fixed, fixed);
         categories.insert(Comparison::SameTestPass, test_pass);
 
+        // Regressions and fixes are always shown first
+        assert_eq!(
+            analyzed.categories.keys().cloned().collect::<Vec<_>>(),
+            vec![
+                Comparison::Regressed,
+                Comparison::Fixed,
+                Comparison::SameTestPass
+            ]
+        );
+
         let expected = TestResults { categories, info };
         assert_eq!(expected, analyzed);
 
+        assert_eq!(
+            analyzed.error_groups(Comparison::Regressed),
+            vec![
+                (
+                    TestResult::BuildFail(CompilerError(btreeset!["002".parse()?])),
+                    2
+                ),
+                (
+                    TestResult::BuildFail(CompilerError(btreeset!["001".parse()?])),
+                    1
+                ),
+                (TestResult::BuildFail(Unknown), 1),
+            ]
+        );
+        assert!(analyzed.error_groups(Comparison::SameTestPass).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lint_warnings_analysis() -> Fallible<()> {
+        let lints = |names: &[&str]| -> Fallible<TestResult> {
+            let lints = names
+                .iter()
+                .map(|name| name.parse())
+                .collect::<Fallible<_>>()?;
+            Ok(TestResult::LintWarnings(Lints(lints)))
+        };
+
+        let config = Config::default();
+        let mut db = DummyDB::default();
+        let ex = Experiment {
+            mode: Mode::Clippy,
+            cap_lints: CapLints::Warn,
+            status: Status::GeneratingReport,
+            ..Experiment::dummy("foo")
+        };
+
+        let mut crates = Vec::new();
+        for (name, start, end) in vec![
+            (
+                "new",
+                TestResult::TestPass,
+                lints(&["clippy::a", "clippy::b"])?,
+            ),
+            (
+                "changed",
+                lints(&["clippy::a"])?,
+                lints(&["clippy::a", "clippy::c"])?,
+            ),
+            (
+                "fixed",
+                lints(&["clippy::a", "clippy::b"])?,
+                lints(&["clippy::b"])?,
+            ),
+        ] {
+            let krate = Crate::Registry(RegistryCrate {
+                name: name.into(),
+                version: "0.0.1".into(),
+            });
+            db.add_dummy_result(&ex, krate.clone(), MAIN_TOOLCHAIN.clone(), start);
+            db.add_dummy_result(&ex, krate.clone(), TEST_TOOLCHAIN.clone(), end);
+            crates.push(krate);
+        }
+
+        let analyzed = analyze_report(generate_report(&db, &config, &ex, &crates)?);
+        assert_eq!(analyzed.info.get(&Comparison::NewWarnings), Some(&2));
+        assert_eq!(analyzed.info.get(&Comparison::FixedWarnings), Some(&1));
+
+        // Crates are only grouped by the lints emitted with a single toolchain
+        assert_eq!(
+            analyzed.error_groups(Comparison::NewWarnings),
+            vec![
+                (lints(&["clippy::a"])?, 1),
+                (lints(&["clippy::b"])?, 1),
+                (lints(&["clippy::c"])?, 1),
+            ]
+        );
+        assert_eq!(
+            analyzed.error_groups(Comparison::FixedWarnings),
+            vec![(lints(&["clippy::a"])?, 1)]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ice_groups() -> Fallible<()> {
+        let config = Config::default();
+        let mut db = DummyDB::default();
+        let ex = Experiment {
+            status: Status::GeneratingReport,
+            ..Experiment::dummy("foo")
+        };
+
+        let ice = |item: &str| {
+            format!(
+                "error: internal compiler error: broken MIR in `{}`\n\
+                 query stack during panic:\n\
+                 #0 [mir_borrowck] borrow-checking `{}`\n\
+                 end of query stack\n",
+                item, item
+            )
+        };
+        let mut crates = Vec::new();
+        for (name, end, log) in vec![
+            ("a", TestResult::BuildFail(ICE), ice("a::foo")),
+            ("b", TestResult::BuildFail(ICE), ice("b::bar")),
+            (
+                "c",
+                TestResult::BuildFail(ICE),
+                "error: internal compiler error: oops".into(),
+            ),
+            ("d", TestResult::BuildFail(Unknown), ice("d::baz")),
+        ] {
+            let krate = Crate::Registry(RegistryCrate {
+                name: name.into(),
+                version: "0.0.1".into(),
+            });
+            db.add_dummy_result(
+                &ex,
+                krate.clone(),
+                MAIN_TOOLCHAIN.clone(),
+                TestResult::TestPass,
+            );
+            db.add_dummy_result(&ex, krate.clone(), TEST_TOOLCHAIN.clone(), end);
+            db.add_dummy_log(
+                &ex,
+                krate.clone(),
+                TEST_TOOLCHAIN.clone(),
+                EncodedLog::Plain(log.into_bytes()),
+            );
+            crates.push(krate);
+        }
+
+        let analyzed = analyze_report(generate_report(&db, &config, &ex, &crates)?);
+        let groups = analyzed
+            .ice_groups(Comparison::Regressed)
+            .into_iter()
+            .map(|(ice, crates)| (ice.message, ice.queries, crates.len()))
+            .collect::<Vec<_>>();
+        // Crates crashing in the same way are grouped even if the crash mentions their own items
+        assert_eq!(
+            groups,
+            vec![
+                (
+                    "broken MIR in `_`".to_string(),
+                    vec!["mir_borrowck".to_string()],
+                    2
+                ),
+                ("oops".to_string(), Vec::new(), 1),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_error_code_groups() -> Fallible<()> {
+        let config = Config::default();
+        let mut db = DummyDB::default();
+        let ex = Experiment {
+            status: Status::GeneratingReport,
+            ..Experiment::dummy("foo")
+        };
+
+        let mut crates = Vec::new();
+        for (name, start, log) in vec![
+            (
+                "a",
+                TestResult::TestPass,
+                "error[E0793]: reference to packed field",
+            ),
+            (
+                "b",
+                TestResult::TestPass,
+                "error[E0793]: x\nerror[E0308]: mismatched types",
+            ),
+            (
+                "c",
+                TestResult::TestPass,
+                "error[E0793]: reference to packed field",
+            ),
+            (
+                "d",
+                TestResult::BuildFail(Unknown),
+                "error[E0308]: mismatched types",
+            ),
+            ("e", TestResult::TestPass, "error: could not compile `e`"),
+        ] {
+            let krate = Crate::Registry(RegistryCrate {
+                name: name.into(),
+                version: "0.0.1".into(),
+            });
+            let end = TestResult::BuildFail(Unknown);
+            db.add_dummy_result(&ex, krate.clone(), MAIN_TOOLCHAIN.clone(), start);
+            db.add_dummy_result(&ex, krate.clone(), TEST_TOOLCHAIN.clone(), end);
+            db.add_dummy_log(
+                &ex,
+                krate.clone(),
+                TEST_TOOLCHAIN.clone(),
+                EncodedLog::Plain(log.as_bytes().to_vec()),
+            );
+            crates.push(krate);
+        }
+
+        let analyzed = analyze_report(generate_report(&db, &config, &ex, &crates)?);
+        let groups = analyzed
+            .error_code_groups(Comparison::Regressed)
+            .into_iter()
+            .map(|(code, crates)| (code.to_string(), crates.len()))
+            .collect::<Vec<_>>();
+        // Only regressed crates are grouped, a crate once for each of its error codes
+        assert_eq!(
+            groups,
+            vec![("E0793".to_string(), 3), ("E0308".to_string(), 1)]
+        );
+
         Ok(())
     }
 }
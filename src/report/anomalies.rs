@@ -0,0 +1,96 @@
+use std::collections::HashSet;
+
+/// The end log must be this many times longer than the start log to be flagged.
+const LOG_GROWTH_FACTOR: usize = 10;
+/// Logs shorter than this are never flagged as too long, as small changes would trigger it.
+const MIN_LOG_SIZE: usize = 16 * 1024;
+/// The end log must contain this many times the warnings of the start log to be flagged.
+const WARNINGS_GROWTH_FACTOR: usize = 10;
+/// Minimum number of new warnings needed to flag a warnings explosion.
+const MIN_NEW_WARNINGS: usize = 50;
+/// Maximum number of new panic messages listed for each crate.
+const MAX_PANICS: usize = 3;
+
+fn count_warnings(log: &str) -> usize {
+    log.lines().filter(|line| line.contains("warning:")).count()
+}
+
+fn panics(log: &str) -> impl Iterator<Item = &str> {
+    log.lines()
+        .filter(|line| line.contains("panicked at"))
+        .map(|line| line.trim_start_matches("[INFO] ").trim())
+}
+
+/// Compare the logs of a crate on the start and end toolchains, looking for changes that don't
+/// affect the outcome of the build but are still worth a look: much longer logs, an explosion of
+/// warnings and panics not present in the start log (usually coming from build scripts).
+pub(super) fn detect(start: &str, end: &str) -> Vec<String> {
+    let mut anomalies = Vec::new();
+
+    if end.len() >= MIN_LOG_SIZE && end.len() >= start.len().max(1) * LOG_GROWTH_FACTOR {
+        anomalies.push(format!(
+            "log grew from {} to {} bytes",
+            start.len(),
+            end.len()
+        ));
+    }
+
+    let (start_warnings, end_warnings) = (count_warnings(start), count_warnings(end));
+    if end_warnings >= start_warnings + MIN_NEW_WARNINGS
+        && end_warnings >= start_warnings.max(1) * WARNINGS_GROWTH_FACTOR
+    {
+        anomalies.push(format!(
+            "warnings went from {} to {}",
+            start_warnings, end_warnings
+        ));
+    }
+
+    let known = panics(start).collect::<HashSet<_>>();
+    let mut seen = HashSet::new();
+    for panic in panics(end).filter(|panic| !known.contains(panic)) {
+        if seen.len() == MAX_PANICS {
+            break;
+        }
+        if seen.insert(panic) {
+            anomalies.push(format!("new panic: {}", panic));
+        }
+    }
+
+    anomalies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::detect;
+
+    #[test]
+    fn test_detect() {
+        let start = "[INFO] Compiling foo\n[INFO] Finished\n";
+        assert!(detect(start, start).is_empty());
+
+        // Small logs are never considered too long
+        let end = start.repeat(20);
+        assert!(detect(start, &end).is_empty());
+
+        let end = start.repeat(1000);
+        assert_eq!(
+            detect(start, &end),
+            vec![format!(
+                "log grew from {} to {} bytes",
+                start.len(),
+                end.len()
+            )]
+        );
+
+        let end = "[INFO] warning: unused variable\n".repeat(60);
+        assert_eq!(detect(start, &end), vec!["warnings went from 0 to 60"]);
+
+        let panic = "[INFO] thread 'main' panicked at 'no libfoo', build.rs:3:5\n";
+        let end = format!("{}{}{}", start, panic, panic);
+        assert_eq!(
+            detect(start, &end),
+            vec!["new panic: thread 'main' panicked at 'no libfoo', build.rs:3:5"]
+        );
+        assert!(detect(&end, &end).is_empty());
+    }
+}
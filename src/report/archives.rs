@@ -4,6 +4,7 @@ use crate::experiments::Experiment;
 use crate::prelude::*;
 use crate::report::{compare, ReportWriter};
 use crate::results::{EncodedLog, EncodingType, ReadResults};
+use crate::toolchain::Toolchain;
 use flate2::{write::GzEncoder, Compression};
 use indexmap::IndexMap;
 use tar::{Builder as TarBuilder, Header as TarHeader};
@@ -34,7 +35,7 @@ pub fn write_logs_archives<DB: ReadResults, W: ReportWriter>(
         let res2 = db.load_test_result(ex, &ex.toolchains[1], krate)?;
         let comparison = compare(config, krate, res1.as_ref(), res2.as_ref());
 
-        for tc in &ex.toolchains {
+        for tc in ex.tested_toolchains() {
             let log = db
                 .load_log(ex, tc, krate)
                 .and_then(|c| c.ok_or_else(|| err_msg("missing logs")))
@@ -104,9 +105,39 @@ pub fn write_logs_archives<DB: ReadResults, W: ReportWriter>(
     Ok(archives)
 }
 
+/// Load the plain logs of a crate on the toolchains of the experiment, skipping the missing ones.
+pub fn load_crate_logs<DB: ReadResults>(
+    db: &DB,
+    ex: &Experiment,
+    krate: &Crate,
+) -> Fallible<Vec<(Toolchain, Vec<u8>)>> {
+    let mut logs = Vec::new();
+    for tc in ex.tested_toolchains() {
+        if let Some(log) = db.load_log(ex, tc, krate)? {
+            logs.push((tc.clone(), log.to_plain()?));
+        }
+    }
+    Ok(logs)
+}
+
+/// Pack the logs of a single crate in a gzipped tarball, as `<crate>/<toolchain>.txt`.
+pub fn crate_logs_archive(krate: &Crate, logs: &[(Toolchain, Vec<u8>)]) -> Fallible<Vec<u8>> {
+    let mut archive = TarBuilder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    for (tc, log) in logs {
+        let mut header = TarHeader::new_gnu();
+        header.set_size(log.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+
+        let path = format!("{}/{}.txt", krate.id(), tc.to_path_component());
+        archive.append_data(&mut header, &path, log.as_slice())?;
+    }
+    Ok(archive.into_inner()?.finish()?)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::write_logs_archives;
+    use super::{crate_logs_archive, load_crate_logs, write_logs_archives};
     use crate::actions::{Action, ActionsCtx, CreateExperiment};
     use crate::config::Config;
     use crate::db::Database;
@@ -277,4 +308,56 @@ mod tests {
             format!("test-pass/{}/{}.txt", crate2.id(), ex.toolchains[1]) => "tc2 crate2",
         });
     }
+
+    #[test]
+    fn test_crate_logs_archive() {
+        rustwide::logging::init();
+
+        let config = Config::default();
+        let db = Database::temp().unwrap();
+        let ctx = ActionsCtx::new(&db, &config);
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+
+        CreateExperiment::dummy("dummy").apply(&ctx).unwrap();
+        let ex = Experiment::get(&db, "dummy").unwrap().unwrap();
+        let krate = &ex.get_crates(&db).unwrap()[0];
+
+        // Only the logs available are returned
+        let results = DatabaseDB::new(&db);
+        results
+            .record_result(
+                &ex,
+                &ex.toolchains[1],
+                krate,
+                None,
+                &config,
+                EncodingType::Gzip,
+                || {
+                    info!("tc2 log");
+                    Ok(TestResult::TestPass)
+                },
+            )
+            .unwrap();
+        let logs = load_crate_logs(&results, &ex, krate).unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].0, ex.toolchains[1]);
+        assert!(String::from_utf8_lossy(&logs[0].1).contains("tc2 log"));
+
+        let content = crate_logs_archive(krate, &logs).unwrap();
+        let mut archive = Archive::new(GzDecoder::new(content.as_slice()));
+        let mut entries = archive.entries().unwrap();
+        let mut entry = entries.next().unwrap().unwrap();
+        assert_eq!(
+            entry.path().unwrap().to_string_lossy(),
+            format!(
+                "{}/{}.txt",
+                krate.id(),
+                ex.toolchains[1].to_path_component()
+            )
+        );
+        let mut log = String::new();
+        entry.read_to_string(&mut log).unwrap();
+        assert!(log.contains("tc2 log"));
+        assert!(entries.next().is_none());
+    }
 }
@@ -0,0 +1,121 @@
+use crate::prelude::*;
+use crate::report::storage::{content_encoding, upload_with_retries};
+use crate::report::ReportWriter;
+use crate::results::EncodingType;
+use crate::utils::http::prepare_sync;
+use http::Method;
+use mime::Mime;
+use std::borrow::Cow;
+use std::fmt::{self, Display};
+use std::io;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// Writer uploading the reports as block blobs to a container of Azure Blob Storage,
+/// authenticating with a shared access signature. The container must allow anonymous read
+/// access to its blobs for the reports to be public.
+pub struct AzureWriter {
+    account: String,
+    container: String,
+    prefix: PathBuf,
+    sas_token: String,
+}
+
+impl AzureWriter {
+    pub fn create(
+        account: String,
+        container: String,
+        prefix: PathBuf,
+        sas_token: String,
+    ) -> AzureWriter {
+        AzureWriter {
+            account,
+            container,
+            prefix,
+            sas_token,
+        }
+    }
+
+    fn blob_url(&self, path: &Path) -> Fallible<Url> {
+        let mut url = Url::parse(&format!("https://{}.blob.core.windows.net", self.account))?;
+        {
+            let name = self.prefix.join(path);
+            let mut segments = url
+                .path_segments_mut()
+                .map_err(|_| err_msg("invalid Azure account name"))?;
+            segments.pop_if_empty().push(&self.container);
+            for component in name.components() {
+                segments.push(&component.as_os_str().to_string_lossy());
+            }
+        }
+        url.set_query(Some(self.sas_token.trim_start_matches('?')));
+        Ok(url)
+    }
+}
+
+impl ReportWriter for AzureWriter {
+    fn write_bytes<P: AsRef<Path>>(
+        &self,
+        path: P,
+        s: Vec<u8>,
+        mime: &Mime,
+        encoding_type: EncodingType,
+    ) -> Fallible<()> {
+        let url = self.blob_url(path.as_ref())?;
+        upload_with_retries("Azure", path.as_ref(), || {
+            let mut req = prepare_sync(Method::PUT, url.as_str())
+                .header("x-ms-blob-type", "BlockBlob")
+                .header("x-ms-blob-content-type", mime.to_string());
+            if let Some(encoding) = content_encoding(encoding_type) {
+                req = req.header("x-ms-blob-content-encoding", encoding);
+            }
+            Ok(req.body(s.clone()).send()?)
+        })
+    }
+
+    fn write_string<P: AsRef<Path>>(&self, path: P, s: Cow<str>, mime: &Mime) -> Fallible<()> {
+        self.write_bytes(path, s.into_owned().into_bytes(), mime, EncodingType::Plain)
+    }
+
+    fn copy<P: AsRef<Path>, R: io::Read>(&self, r: &mut R, path: P, mime: &Mime) -> Fallible<()> {
+        let mut bytes = Vec::new();
+        io::copy(r, &mut bytes)?;
+        self.write_bytes(path, bytes, mime, EncodingType::Plain)
+    }
+}
+
+impl Display for AzureWriter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        format_args!(
+            "https://{}.blob.core.windows.net/{}/{}",
+            self.account,
+            self.container,
+            self.prefix.display()
+        )
+        .fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AzureWriter;
+    use std::path::Path;
+
+    #[test]
+    fn test_blob_url() {
+        let writer = AzureWriter::create(
+            "crater".into(),
+            "reports".into(),
+            "ns/ex name".into(),
+            "?sv=2019-12-12&sig=secret".into(),
+        );
+        assert_eq!(
+            writer
+                .blob_url(Path::new("reg/foo/log.txt"))
+                .unwrap()
+                .as_str(),
+            "https://crater.blob.core.windows.net/reports/ns/ex%20name/reg/foo/log.txt\
+             ?sv=2019-12-12&sig=secret"
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use crate::crates::Crate;
+use crate::experiments::Experiment;
+use crate::prelude::*;
+use crate::results::{ReadResults, TestResult};
+use std::collections::HashMap;
+
+/// Crates that built on the baseline toolchain of the previous experiment, but don't build on the
+/// baseline of this one anymore (for example because a dependency was yanked). They're not
+/// regressions of the experiment, but they explain why fewer crates can be compared between runs.
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(super) struct BaselineBreakage {
+    pub(super) previous: String,
+    pub(super) crates: Vec<BrokenOnBaseline>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(super) struct BrokenOnBaseline {
+    #[serde(rename = "crate")]
+    pub(super) krate: Crate,
+    pub(super) before: TestResult,
+    pub(super) after: TestResult,
+}
+
+fn stopped_building(before: &TestResult, after: &TestResult) -> bool {
+    let built = match before {
+        TestResult::TestPass
+        | TestResult::TestSkipped
+        | TestResult::TestFail(_)
+        | TestResult::LintWarnings(_) => true,
+        _ => false,
+    };
+    let broken = match after {
+        TestResult::BuildFail(_) | TestResult::BrokenCrate(_) => true,
+        _ => false,
+    };
+    built && broken
+}
+
+/// Compare the baseline results of the experiment with the ones of the previous experiment of the
+/// same mode, if there is one.
+pub(super) fn find<DB: ReadResults>(
+    db: &DB,
+    ex: &Experiment,
+    crates: &[Crate],
+) -> Fallible<Option<BaselineBreakage>> {
+    let previous = match db.load_previous_experiment(ex)? {
+        Some(previous) => previous,
+        None => return Ok(None),
+    };
+
+    // Registry crates are usually updated between two runs, so they're matched without version
+    let before = db
+        .load_all_results(&previous, &previous.toolchains[0])?
+        .into_iter()
+        .map(|(krate, res)| (krate.unversioned_id(), res))
+        .collect::<HashMap<_, _>>();
+
+    let mut broken = Vec::new();
+    for krate in crates {
+        let before = match before.get(&krate.unversioned_id()) {
+            Some(before) => before,
+            None => continue,
+        };
+        if let Some(after) = db.load_test_result(ex, &ex.toolchains[0], krate)? {
+            if stopped_building(before, &after) {
+                broken.push(BrokenOnBaseline {
+                    krate: krate.clone(),
+                    before: before.clone(),
+                    after,
+                });
+            }
+        }
+    }
+    broken.sort_by_key(|broken| broken.krate.id());
+
+    Ok(Some(BaselineBreakage {
+        previous: previous.name,
+        crates: broken,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::find;
+    use crate::crates::{Crate, RegistryCrate};
+    use crate::experiments::{Experiment, Status};
+    use crate::results::{DummyDB, FailureReason, TestResult};
+    use crate::toolchain::{Toolchain, MAIN_TOOLCHAIN, TEST_TOOLCHAIN};
+
+    fn experiment(name: &str, toolchains: [Toolchain; 2]) -> Experiment {
+        Experiment {
+            toolchains,
+            status: Status::Completed,
+            ..Experiment::dummy(name)
+        }
+    }
+
+    fn reg(name: &str, version: &str) -> Crate {
+        Crate::Registry(RegistryCrate {
+            name: name.into(),
+            version: version.into(),
+        })
+    }
+
+    #[test]
+    fn test_find() {
+        let previous = experiment("previous", [MAIN_TOOLCHAIN.clone(), TEST_TOOLCHAIN.clone()]);
+        let ex = experiment("current", [TEST_TOOLCHAIN.clone(), MAIN_TOOLCHAIN.clone()]);
+
+        let mut db = DummyDB::default();
+        assert!(find(&db, &ex, &[]).unwrap().is_none());
+        db.add_dummy_previous(&ex, previous.clone());
+
+        let build_fail = TestResult::BuildFail(FailureReason::Unknown);
+        for (before, after, res_before, res_after) in vec![
+            // Registry crates match even if a new version was released in the meantime
+            (
+                reg("syn", "1.0.0"),
+                reg("syn", "1.0.1"),
+                TestResult::TestPass,
+                build_fail.clone(),
+            ),
+            (
+                reg("quote", "1.0.0"),
+                reg("quote", "1.0.0"),
+                TestResult::TestPass,
+                TestResult::TestPass,
+            ),
+            (
+                reg("rand", "0.7.0"),
+                reg("rand", "0.7.0"),
+                build_fail.clone(),
+                build_fail.clone(),
+            ),
+        ] {
+            db.add_dummy_result(&previous, before, MAIN_TOOLCHAIN.clone(), res_before);
+            db.add_dummy_result(&ex, after, TEST_TOOLCHAIN.clone(), res_after);
+        }
+
+        let crates = [
+            reg("syn", "1.0.1"),
+            reg("quote", "1.0.0"),
+            reg("rand", "0.7.0"),
+        ];
+        let breakage = find(&db, &ex, &crates).unwrap().unwrap();
+        assert_eq!(breakage.previous, "previous");
+        assert_eq!(breakage.crates.len(), 1);
+        assert_eq!(breakage.crates[0].krate, reg("syn", "1.0.1"));
+        assert_eq!(breakage.crates[0].before, TestResult::TestPass);
+        assert_eq!(breakage.crates[0].after, build_fail);
+    }
+}
@@ -0,0 +1,96 @@
+use crate::config::Config;
+use crate::crates::Crate;
+use crate::experiments::Experiment;
+use crate::prelude::*;
+use crate::report::diff::classify;
+use crate::report::Comparison;
+use crate::results::ReadResults;
+
+/// List the regressed crates of the experiment depending, directly or transitively, on the
+/// package `name` (at `version` if one is provided), according to the dependency graphs resolved
+/// when the crates were built with the end toolchain.
+pub fn regressed_dependents<DB: ReadResults>(
+    db: &DB,
+    config: &Config,
+    ex: &Experiment,
+    name: &str,
+    version: Option<&str>,
+) -> Fallible<Vec<Crate>> {
+    let graphs = db.load_dependency_graphs(ex, &ex.toolchains[1])?;
+    let mut dependents = classify(db, config, ex)?
+        .into_iter()
+        .filter(|(_, comp)| *comp == Comparison::Regressed)
+        .filter(|(krate, _)| {
+            graphs
+                .get(krate)
+                .map_or(false, |graph| graph.depends_on(name, version))
+        })
+        .map(|(krate, _)| krate)
+        .collect::<Vec<_>>();
+    dependents.sort_by(|a, b| a.id().cmp(&b.id()));
+    Ok(dependents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::regressed_dependents;
+    use crate::actions::{Action, ActionsCtx, CreateExperiment};
+    use crate::config::Config;
+    use crate::crates::{Crate, RegistryCrate};
+    use crate::db::Database;
+    use crate::experiments::Experiment;
+    use crate::results::{DependencyGraph, DummyDB, FailureReason, TestResult};
+    use crate::toolchain::{MAIN_TOOLCHAIN, TEST_TOOLCHAIN};
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn test_regressed_dependents() {
+        let db = Database::temp().unwrap();
+        let config = Config::default();
+        let ctx = ActionsCtx::new(&db, &config);
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+        CreateExperiment::dummy("dummy").apply(&ctx).unwrap();
+        let ex = Experiment::get(&db, "dummy").unwrap().unwrap();
+
+        let krate = |name: &str| {
+            Crate::Registry(RegistryCrate {
+                name: name.into(),
+                version: "1.0.0".into(),
+            })
+        };
+        let graph = |name: &str, deps: &[&str]| {
+            let mut graph = DependencyGraph::default();
+            let root = format!("{} 1.0.0", name);
+            graph.roots.insert(root.clone());
+            for dep in deps {
+                graph.packages.insert(dep.to_string(), BTreeSet::new());
+            }
+            let deps = deps.iter().map(|dep| dep.to_string()).collect();
+            graph.packages.insert(root, deps);
+            graph
+        };
+        let fail = TestResult::BuildFail(FailureReason::Unknown);
+
+        let mut results = DummyDB::default();
+        for (name, end, deps) in vec![
+            ("old-libc", fail.clone(), vec!["libc 0.2.65"]),
+            ("new-libc", fail.clone(), vec!["libc 0.2.66"]),
+            ("no-libc", fail.clone(), vec!["serde 1.0.104"]),
+            ("fine", TestResult::TestPass, vec!["libc 0.2.66"]),
+        ] {
+            let start = TestResult::TestPass;
+            results.add_dummy_result(&ex, krate(name), MAIN_TOOLCHAIN.clone(), start);
+            results.add_dummy_result(&ex, krate(name), TEST_TOOLCHAIN.clone(), end);
+            let graph = graph(name, &deps);
+            results.add_dummy_dependency_graph(&ex, krate(name), TEST_TOOLCHAIN.clone(), graph);
+        }
+
+        let dependents = |version| regressed_dependents(&results, &config, &ex, "libc", version);
+        assert_eq!(
+            dependents(None).unwrap(),
+            vec![krate("new-libc"), krate("old-libc")]
+        );
+        assert_eq!(dependents(Some("0.2.66")).unwrap(), vec![krate("new-libc")]);
+        assert!(dependents(Some("0.3.0")).unwrap().is_empty());
+    }
+}
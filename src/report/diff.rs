@@ -0,0 +1,209 @@
+use crate::config::Config;
+use crate::crates::Crate;
+use crate::experiments::Experiment;
+use crate::prelude::*;
+use crate::report::{
+    compare, crate_to_name, crate_to_url, expected_regressions, is_expected, Comparison,
+};
+use crate::results::ReadResults;
+use indexmap::IndexMap;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Crate classified differently by two experiments.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ClassificationChange {
+    #[serde(rename = "crate")]
+    pub krate: Crate,
+    pub before: Comparison,
+    pub after: Comparison,
+}
+
+/// Crates whose classification changed between two experiments, for example between an
+/// experiment with a broken toolchain and the one testing its fix.
+#[derive(Debug, Serialize)]
+pub struct ExperimentDiff {
+    pub before: String,
+    pub after: String,
+    /// Number of crates completed in both experiments.
+    pub compared: usize,
+    pub changes: Vec<ClassificationChange>,
+}
+
+impl ExperimentDiff {
+    pub fn render_markdown(&self) -> Fallible<String> {
+        let mut rendered = String::new();
+        writeln!(
+            &mut rendered,
+            "# Crater diff between {} and {}\n",
+            self.before, self.after
+        )?;
+        writeln!(
+            &mut rendered,
+            "{} crates compared, {} changed classification",
+            self.compared,
+            self.changes.len()
+        )?;
+
+        let mut transitions = IndexMap::new();
+        for change in &self.changes {
+            transitions
+                .entry((change.before, change.after))
+                .or_insert_with(Vec::new)
+                .push(&change.krate);
+        }
+        let mut transitions = transitions.into_iter().collect::<Vec<_>>();
+        transitions.sort_by(|a, b| b.1.len().cmp(&a.1.len()));
+
+        for ((before, after), crates) in transitions {
+            writeln!(
+                &mut rendered,
+                "\n### {} → {} ({})",
+                before.to_str(),
+                after.to_str(),
+                crates.len()
+            )?;
+            for krate in crates {
+                writeln!(
+                    &mut rendered,
+                    "* [{}]({})",
+                    crate_to_name(krate)?,
+                    crate_to_url(krate)?
+                )?;
+            }
+        }
+
+        Ok(rendered)
+    }
+}
+
+/// Classify all the crates completed on both toolchains of the experiment.
+pub(super) fn classify<DB: ReadResults>(
+    db: &DB,
+    config: &Config,
+    ex: &Experiment,
+) -> Fallible<HashMap<Crate, Comparison>> {
+    let patterns = expected_regressions(ex);
+    let start = db
+        .load_all_results(ex, &ex.toolchains[0])?
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+
+    let mut classified = HashMap::new();
+    for (krate, end) in db.load_all_results(ex, &ex.toolchains[1])? {
+        if let Some(start) = start.get(&krate) {
+            let mut comp = compare(config, &krate, Some(start), Some(&end));
+            if comp == Comparison::Regressed && is_expected(db, ex, &krate, &patterns) {
+                comp = Comparison::ExpectedRegression;
+            }
+            classified.insert(krate, comp);
+        }
+    }
+    Ok(classified)
+}
+
+/// Compare the classification of the crates present in both experiments. Crates tested by only
+/// one of the experiments are ignored, as there is nothing to compare them with.
+pub fn diff_experiments<DB: ReadResults>(
+    db: &DB,
+    config: &Config,
+    before: &Experiment,
+    after: &Experiment,
+) -> Fallible<ExperimentDiff> {
+    let old = classify(db, config, before)?;
+    let new = classify(db, config, after)?;
+
+    let mut compared = 0;
+    let mut changes = Vec::new();
+    for (krate, after) in new {
+        if let Some(&before) = old.get(&krate) {
+            compared += 1;
+            if before != after {
+                changes.push(ClassificationChange {
+                    krate,
+                    before,
+                    after,
+                });
+            }
+        }
+    }
+    changes.sort_by(|a, b| a.krate.id().cmp(&b.krate.id()));
+
+    Ok(ExperimentDiff {
+        before: before.name.clone(),
+        after: after.name.clone(),
+        compared,
+        changes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff_experiments, ClassificationChange};
+    use crate::actions::{Action, ActionsCtx, CreateExperiment};
+    use crate::config::Config;
+    use crate::crates::{Crate, RegistryCrate};
+    use crate::db::Database;
+    use crate::experiments::Experiment;
+    use crate::report::Comparison;
+    use crate::results::{DummyDB, FailureReason, TestResult};
+    use crate::toolchain::{MAIN_TOOLCHAIN, TEST_TOOLCHAIN};
+
+    #[test]
+    fn test_diff_experiments() {
+        let db = Database::temp().unwrap();
+        let config = Config::default();
+        let ctx = ActionsCtx::new(&db, &config);
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+        CreateExperiment::dummy("before").apply(&ctx).unwrap();
+        CreateExperiment::dummy("after").apply(&ctx).unwrap();
+        let before = Experiment::get(&db, "before").unwrap().unwrap();
+        let after = Experiment::get(&db, "after").unwrap().unwrap();
+
+        let krate = |name: &str| {
+            Crate::Registry(RegistryCrate {
+                name: name.into(),
+                version: "1.0.0".into(),
+            })
+        };
+        let fail = TestResult::BuildFail(FailureReason::Unknown);
+
+        let mut results = DummyDB::default();
+        for (name, old, new) in vec![
+            ("fixed", fail.clone(), TestResult::TestPass),
+            ("same", fail.clone(), fail.clone()),
+            ("broken", TestResult::TestPass, fail.clone()),
+        ] {
+            for (ex, end) in vec![(&before, old), (&after, new)] {
+                let start = TestResult::TestPass;
+                results.add_dummy_result(ex, krate(name), MAIN_TOOLCHAIN.clone(), start);
+                results.add_dummy_result(ex, krate(name), TEST_TOOLCHAIN.clone(), end);
+            }
+        }
+        // Crates tested by only one of the experiments are not compared
+        let start = TestResult::TestPass;
+        results.add_dummy_result(&after, krate("new"), MAIN_TOOLCHAIN.clone(), start);
+        results.add_dummy_result(&after, krate("new"), TEST_TOOLCHAIN.clone(), fail);
+
+        let diff = diff_experiments(&results, &config, &before, &after).unwrap();
+        assert_eq!(diff.compared, 3);
+        assert_eq!(
+            diff.changes,
+            vec![
+                ClassificationChange {
+                    krate: krate("broken"),
+                    before: Comparison::SameTestPass,
+                    after: Comparison::Regressed,
+                },
+                ClassificationChange {
+                    krate: krate("fixed"),
+                    before: Comparison::Regressed,
+                    after: Comparison::SameTestPass,
+                },
+            ]
+        );
+
+        let markdown = diff.render_markdown().unwrap();
+        assert!(markdown.contains("### regressed → test-pass (1)"));
+    }
+}
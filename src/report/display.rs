@@ -14,6 +14,17 @@ impl ResultName for FailureReason {
             FailureReason::Timeout => "timed out".into(),
             FailureReason::OOM => "OOM".into(),
             FailureReason::ICE => "ICE".into(),
+            FailureReason::FetchFailed => "fetch failed".into(),
+            FailureReason::RequiresNightly => "requires nightly".into(),
+            FailureReason::ProcessLimit => "process limit".into(),
+            FailureReason::Network => "network error".into(),
+            FailureReason::NetworkBlocked => "network access blocked".into(),
+            FailureReason::Docker => "docker error".into(),
+            FailureReason::DiskLimit => "disk limit exceeded".into(),
+            FailureReason::ClippyICE => "clippy ICE".into(),
+            FailureReason::MiriUB => "undefined behavior".into(),
+            FailureReason::MiriUnsupported => "unsupported by miri".into(),
+            FailureReason::LinkerError(_) => "linker error".into(),
             FailureReason::CompilerError(_) => "compiler error".into(),
             FailureReason::DependsOn(_) => "faulty deps".into(),
         }
@@ -22,10 +33,21 @@ impl ResultName for FailureReason {
     fn long_name(&self) -> String {
         match self {
             FailureReason::CompilerError(_) | FailureReason::DependsOn(_) => self.to_string(),
+            FailureReason::LinkerError(kind) => format!("linker error ({})", kind),
             FailureReason::Unknown
             | FailureReason::Timeout
             | FailureReason::OOM
-            | FailureReason::ICE => self.name(),
+            | FailureReason::ICE
+            | FailureReason::FetchFailed
+            | FailureReason::RequiresNightly
+            | FailureReason::ProcessLimit
+            | FailureReason::Network
+            | FailureReason::NetworkBlocked
+            | FailureReason::Docker
+            | FailureReason::DiskLimit
+            | FailureReason::ClippyICE
+            | FailureReason::MiriUB
+            | FailureReason::MiriUnsupported => self.name(),
         }
     }
 }
@@ -51,10 +73,12 @@ impl ResultName for TestResult {
             TestResult::BrokenCrate(reason) => reason.name(),
             TestResult::BuildFail(reason) => format!("build {}", reason.name()),
             TestResult::TestFail(reason) => format!("test {}", reason.name()),
+            TestResult::LintWarnings(_) => "lint warnings".into(),
             TestResult::TestSkipped => "test skipped".into(),
             TestResult::TestPass => "test passed".into(),
             TestResult::Error => "error".into(),
             TestResult::Skipped => "skipped".into(),
+            TestResult::NotApplicable => "not applicable".into(),
         }
     }
 
@@ -63,10 +87,12 @@ impl ResultName for TestResult {
             TestResult::BuildFail(reason) => format!("build {}", reason.long_name()),
             TestResult::TestFail(reason) => format!("test {}", reason.long_name()),
             TestResult::BrokenCrate(reason) => reason.long_name(),
+            TestResult::LintWarnings(lints) => format!("lint warnings ({})", lints),
             TestResult::TestSkipped
             | TestResult::TestPass
             | TestResult::Error
-            | TestResult::Skipped => self.name(),
+            | TestResult::Skipped
+            | TestResult::NotApplicable => self.name(),
         }
     }
 }
@@ -89,6 +115,7 @@ impl ResultColor for Comparison {
             Comparison::Skipped => Color::Striped("#494b4a", "#555555"),
             Comparison::Unknown => Color::Single("#494b4a"),
             Comparison::SameBuildFail => Color::Single("#65461e"),
+            Comparison::RequiresNightly => Color::Striped("#65461e", "#494b4a"),
             Comparison::SameTestFail => Color::Single("#788843"),
             Comparison::SameTestSkipped => Color::Striped("#72a156", "#80b65f"),
             Comparison::SameTestPass => Color::Single("#72a156"),
@@ -96,6 +123,13 @@ impl ResultColor for Comparison {
             Comparison::Broken => Color::Single("#44176e"),
             Comparison::SpuriousRegressed => Color::Striped("#db3026", "#d5433b"),
             Comparison::SpuriousFixed => Color::Striped("#5630db", "#5d3dcf"),
+            Comparison::ExpectedRegression => Color::Striped("#db3026", "#494b4a"),
+            Comparison::NotApplicable => Color::Striped("#494b4a", "#3f4140"),
+            Comparison::NewWarnings => Color::Single("#d7a626"),
+            Comparison::FixedWarnings => Color::Striped("#72a156", "#d7a626"),
+            Comparison::ClippyIce => Color::Striped("#db3026", "#d7a626"),
+            Comparison::MiriUB => Color::Striped("#db3026", "#44176e"),
+            Comparison::MiriUnsupported => Color::Striped("#788843", "#494b4a"),
         }
     }
 }
@@ -107,8 +141,10 @@ impl ResultColor for TestResult {
             TestResult::BuildFail(_) => Color::Single("#db3026"),
             TestResult::TestFail(_) => Color::Single("#65461e"),
             TestResult::TestSkipped | TestResult::TestPass => Color::Single("#62a156"),
+            TestResult::LintWarnings(_) => Color::Single("#d7a626"),
             TestResult::Error => Color::Single("#d77026"),
             TestResult::Skipped => Color::Single("#494b4a"),
+            TestResult::NotApplicable => Color::Single("#3f4140"),
         }
     }
 }
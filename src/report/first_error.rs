@@ -0,0 +1,130 @@
+use crate::report::ice::strip_prefixes;
+use std::fmt;
+
+/// Errors printed by cargo and rustc after the actual ones, which don't explain the failure.
+const SUMMARIES: &[&str] = &[
+    "aborting due to",
+    "could not compile",
+    "Could not compile",
+    "build failed",
+    "test failed",
+];
+/// Number of lines after the message of an error searched for its location.
+const LOCATION_LINES: usize = 3;
+
+/// First error printed in a build log, which is usually enough to tell why the build failed
+/// without opening the log.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct FirstError {
+    /// Code of the error, like `E0308`, if it has one.
+    pub code: Option<String>,
+    pub message: String,
+    /// File, line and column the error points to, like `src/lib.rs:12:5`.
+    pub location: Option<String>,
+}
+
+impl fmt::Display for FirstError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.code {
+            Some(code) => write!(f, "error[{}]: {}", code, self.message)?,
+            None => write!(f, "error: {}", self.message)?,
+        }
+        if let Some(location) = &self.location {
+            write!(f, " at {}", location)?;
+        }
+        Ok(())
+    }
+}
+
+/// Split a line like `error[E0308]: mismatched types` into the code and the message.
+fn parse_error(line: &str) -> Option<(Option<String>, &str)> {
+    if !line.starts_with("error") {
+        return None;
+    }
+    let rest = &line["error".len()..];
+    if rest.starts_with('[') {
+        let end = rest.find("]: ")?;
+        Some((Some(rest[1..end].to_string()), rest[end + 3..].trim()))
+    } else if rest.starts_with(": ") {
+        Some((None, rest[2..].trim()))
+    } else {
+        None
+    }
+}
+
+/// Find the first error printed in a log, skipping the summaries printed after it.
+pub(super) fn extract(log: &str) -> Option<FirstError> {
+    let lines = log.lines().map(strip_prefixes).collect::<Vec<_>>();
+    for (i, line) in lines.iter().enumerate() {
+        let (code, message) = match parse_error(line) {
+            Some(error) => error,
+            None => continue,
+        };
+        if SUMMARIES.iter().any(|summary| message.starts_with(summary)) {
+            continue;
+        }
+
+        // The location follows the message, unless the error doesn't point to any code
+        let location = lines[i + 1..]
+            .iter()
+            .take(LOCATION_LINES)
+            .take_while(|line| parse_error(line).is_none() && !line.starts_with("warning"))
+            .find(|line| line.starts_with("--> "))
+            .map(|line| line["--> ".len()..].trim().to_string());
+        return Some(FirstError {
+            code,
+            message: message.to_string(),
+            location,
+        });
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{extract, FirstError};
+
+    #[test]
+    fn test_extract() {
+        let log = concat!(
+            "[INFO] [stderr]    Compiling foo v0.1.0 (/opt/rustwide/workdir)\n",
+            "[INFO] [stderr] warning: unused variable: `x`\n",
+            "[INFO] [stderr]  --> src/main.rs:2:9\n",
+            "[INFO] [stderr] error[E0308]: mismatched types\n",
+            "[INFO] [stderr]   --> src/lib.rs:12:5\n",
+            "[INFO] [stderr]    |\n",
+            "[INFO] [stderr] error[E0425]: cannot find value `y` in this scope\n",
+            "[INFO] [stderr] error: aborting due to 2 previous errors\n",
+            "[INFO] [stderr] error: could not compile `foo`.\n",
+        );
+        let error = extract(log).unwrap();
+        assert_eq!(
+            error,
+            FirstError {
+                code: Some("E0308".into()),
+                message: "mismatched types".into(),
+                location: Some("src/lib.rs:12:5".into()),
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "error[E0308]: mismatched types at src/lib.rs:12:5"
+        );
+
+        let log = concat!(
+            "[INFO] [stderr] error: failed to run custom build command for `openssl-sys v0.9.58`\n",
+            "[INFO] [stderr] \n",
+            "[INFO] [stderr] Caused by:\n",
+        );
+        assert_eq!(
+            extract(log).unwrap().to_string(),
+            "error: failed to run custom build command for `openssl-sys v0.9.58`"
+        );
+
+        assert_eq!(
+            extract("[INFO] [stderr] error: could not compile `foo`.\n"),
+            None
+        );
+        assert_eq!(extract("[INFO] [stderr] errors: 0\n"), None);
+    }
+}
@@ -0,0 +1,164 @@
+use crate::prelude::*;
+use crate::report::storage::{content_encoding, upload_with_retries};
+use crate::report::ReportWriter;
+use crate::results::EncodingType;
+use crate::utils::http::prepare_sync;
+use http::header::{AUTHORIZATION, CONTENT_TYPE};
+use http::Method;
+use mime::Mime;
+use std::borrow::Cow;
+use std::fmt::{self, Display};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use url::Url;
+
+const UPLOAD_URL: &str = "https://storage.googleapis.com/upload/storage/v1/b";
+const METADATA_TOKEN_URL: &str =
+    "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+
+/// Tokens fetched from the metadata server are refreshed this long before they expire, to avoid
+/// uploads started right before the expiration from failing.
+const TOKEN_EXPIRY_MARGIN: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Deserialize)]
+struct MetadataToken {
+    access_token: String,
+    expires_in: u64,
+}
+
+enum GcsToken {
+    Static(String),
+    Metadata(Mutex<Option<(String, Instant)>>),
+}
+
+impl GcsToken {
+    fn get(&self) -> Fallible<String> {
+        match self {
+            GcsToken::Static(token) => Ok(token.clone()),
+            GcsToken::Metadata(cache) => {
+                let mut cache = cache.lock().unwrap();
+                match &*cache {
+                    Some((token, expires)) if Instant::now() < *expires => Ok(token.clone()),
+                    _ => {
+                        let (token, expires) = fetch_metadata_token()?;
+                        *cache = Some((token.clone(), expires));
+                        Ok(token)
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn fetch_metadata_token() -> Fallible<(String, Instant)> {
+    let token: MetadataToken = prepare_sync(Method::GET, METADATA_TOKEN_URL)
+        .header("Metadata-Flavor", "Google")
+        .send()?
+        .error_for_status()?
+        .json()?;
+    let lifetime = Duration::from_secs(token.expires_in);
+    let expires = Instant::now() + lifetime - TOKEN_EXPIRY_MARGIN.min(lifetime);
+    Ok((token.access_token, expires))
+}
+
+/// Writer uploading the reports to a Google Cloud Storage bucket through its JSON API. The
+/// uploaded objects are made public with the `publicRead` ACL, which requires the bucket to use
+/// fine-grained access control.
+pub struct GcsWriter {
+    bucket: String,
+    prefix: PathBuf,
+    token: GcsToken,
+}
+
+impl GcsWriter {
+    /// Create a writer authenticating with the provided OAuth access token, or with the tokens of
+    /// the service account of the machine if no token is provided.
+    pub fn create(bucket: String, prefix: PathBuf, access_token: Option<String>) -> GcsWriter {
+        GcsWriter {
+            bucket,
+            prefix,
+            token: match access_token {
+                Some(token) => GcsToken::Static(token),
+                None => GcsToken::Metadata(Mutex::new(None)),
+            },
+        }
+    }
+
+    fn upload_url(&self, path: &Path, encoding_type: EncodingType) -> Fallible<Url> {
+        let mut url = Url::parse(&format!("{}/{}/o", UPLOAD_URL, self.bucket))?;
+        url.query_pairs_mut()
+            .append_pair("uploadType", "media")
+            .append_pair("name", &self.prefix.join(path).to_string_lossy())
+            .append_pair("predefinedAcl", "publicRead");
+        if let Some(encoding) = content_encoding(encoding_type) {
+            url.query_pairs_mut()
+                .append_pair("contentEncoding", encoding);
+        }
+        Ok(url)
+    }
+}
+
+impl ReportWriter for GcsWriter {
+    fn write_bytes<P: AsRef<Path>>(
+        &self,
+        path: P,
+        s: Vec<u8>,
+        mime: &Mime,
+        encoding_type: EncodingType,
+    ) -> Fallible<()> {
+        let url = self.upload_url(path.as_ref(), encoding_type)?;
+        upload_with_retries("GCS", path.as_ref(), || {
+            Ok(prepare_sync(Method::POST, url.as_str())
+                .header(AUTHORIZATION, format!("Bearer {}", self.token.get()?))
+                .header(CONTENT_TYPE, mime.to_string())
+                .body(s.clone())
+                .send()?)
+        })
+    }
+
+    fn write_string<P: AsRef<Path>>(&self, path: P, s: Cow<str>, mime: &Mime) -> Fallible<()> {
+        self.write_bytes(path, s.into_owned().into_bytes(), mime, EncodingType::Plain)
+    }
+
+    fn copy<P: AsRef<Path>, R: io::Read>(&self, r: &mut R, path: P, mime: &Mime) -> Fallible<()> {
+        let mut bytes = Vec::new();
+        io::copy(r, &mut bytes)?;
+        self.write_bytes(path, bytes, mime, EncodingType::Plain)
+    }
+}
+
+impl Display for GcsWriter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        format_args!("gs://{}/{}", self.bucket, self.prefix.display()).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GcsWriter;
+    use crate::results::EncodingType;
+    use std::path::Path;
+
+    #[test]
+    fn test_upload_url() {
+        let writer = GcsWriter::create("reports".into(), "ex name".into(), Some("token".into()));
+        assert_eq!(
+            writer
+                .upload_url(Path::new("index.html"), EncodingType::Plain)
+                .unwrap()
+                .as_str(),
+            "https://storage.googleapis.com/upload/storage/v1/b/reports/o?uploadType=media\
+             &name=ex+name%2Findex.html&predefinedAcl=publicRead"
+        );
+        assert_eq!(
+            writer
+                .upload_url(Path::new("reg/foo/log.txt"), EncodingType::Gzip)
+                .unwrap()
+                .as_str(),
+            "https://storage.googleapis.com/upload/storage/v1/b/reports/o?uploadType=media\
+             &name=ex+name%2Freg%2Ffoo%2Flog.txt&predefinedAcl=publicRead&contentEncoding=gzip"
+        );
+    }
+}
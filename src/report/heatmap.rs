@@ -0,0 +1,228 @@
+use crate::crates::Crate;
+use crate::db::{Database, QueryUtils};
+use crate::experiments::Status;
+use crate::prelude::*;
+use crate::results::TestResult;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Maximum number of crates shown in the heat map, to keep the page loadable.
+const MAX_CRATES: usize = 500;
+
+/// Outcome of a crate on a release, simplified for the heat map.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Outcome {
+    Pass,
+    Fail,
+    Broken,
+}
+
+impl Outcome {
+    fn from_result(res: &TestResult) -> Option<Outcome> {
+        match res {
+            TestResult::TestPass | TestResult::TestSkipped | TestResult::LintWarnings(_) => {
+                Some(Outcome::Pass)
+            }
+            TestResult::BuildFail(reason) | TestResult::TestFail(reason) => {
+                // Spurious failures don't say anything about the compatibility of the crate
+                if reason.is_spurious() {
+                    None
+                } else {
+                    Some(Outcome::Fail)
+                }
+            }
+            TestResult::BrokenCrate(_) => Some(Outcome::Broken),
+            TestResult::Error | TestResult::Skipped | TestResult::NotApplicable => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReleaseSummary {
+    pub release: String,
+    pub crates: usize,
+    pub passed: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CrateRow {
+    pub krate: String,
+    /// Outcome on each release, in the same order as `HeatMap::releases`.
+    pub outcomes: Vec<Option<Outcome>>,
+}
+
+/// Outcome of every crate on every stable release tested by the completed experiments. Only the
+/// crates whose outcome changed between releases are listed, while the summaries include all of
+/// them.
+#[derive(Debug, Serialize)]
+pub struct HeatMap {
+    pub releases: Vec<ReleaseSummary>,
+    pub crates: Vec<CrateRow>,
+    pub truncated: bool,
+}
+
+/// Minor version of a stable release toolchain (like `1.70.0`), ignoring custom toolchains.
+pub(crate) fn stable_release(toolchain: &str) -> Option<u32> {
+    let parts = toolchain.split('.').collect::<Vec<_>>();
+    if parts.len() != 3 || parts[0] != "1" {
+        return None;
+    }
+    if !parts
+        .iter()
+        .all(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_digit()))
+    {
+        return None;
+    }
+    parts[1].parse().ok()
+}
+
+impl HeatMap {
+    /// Build the heat map from results ordered from the oldest to the newest experiment, so that
+    /// the most recent result of a crate on a release is the one shown.
+    fn from_results<I: IntoIterator<Item = (Crate, String, TestResult)>>(results: I) -> Self {
+        let mut minors = BTreeSet::new();
+        let mut outcomes: BTreeMap<String, BTreeMap<u32, Outcome>> = BTreeMap::new();
+        for (krate, toolchain, res) in results {
+            let minor = match stable_release(&toolchain) {
+                Some(minor) => minor,
+                None => continue,
+            };
+            if let Some(outcome) = Outcome::from_result(&res) {
+                minors.insert(minor);
+                // Registry crates are updated between runs, so they're matched without version
+                outcomes
+                    .entry(krate.unversioned_id())
+                    .or_insert_with(BTreeMap::new)
+                    .insert(minor, outcome);
+            }
+        }
+
+        let mut releases = minors
+            .iter()
+            .map(|minor| ReleaseSummary {
+                release: format!("1.{}", minor),
+                crates: 0,
+                passed: 0,
+            })
+            .collect::<Vec<_>>();
+        let mut crates = Vec::new();
+        let mut truncated = false;
+        for (krate, by_release) in outcomes {
+            let row = minors
+                .iter()
+                .map(|minor| by_release.get(minor).cloned())
+                .collect::<Vec<_>>();
+            for (summary, outcome) in releases.iter_mut().zip(&row) {
+                if let Some(outcome) = outcome {
+                    summary.crates += 1;
+                    if *outcome == Outcome::Pass {
+                        summary.passed += 1;
+                    }
+                }
+            }
+
+            let changed = by_release
+                .values()
+                .any(|o| Some(o) != by_release.values().next());
+            if changed {
+                if crates.len() < MAX_CRATES {
+                    crates.push(CrateRow {
+                        krate,
+                        outcomes: row,
+                    });
+                } else {
+                    truncated = true;
+                }
+            }
+        }
+
+        HeatMap {
+            releases,
+            crates,
+            truncated,
+        }
+    }
+}
+
+/// Load the heat map from the results of all the completed experiments.
+pub fn load(db: &Database) -> Fallible<HeatMap> {
+    let rows = db.query(
+        "SELECT results.crate, results.toolchain, results.result FROM results \
+         INNER JOIN experiments ON experiments.name = results.experiment \
+         WHERE experiments.status = ?1 AND results.toolchain GLOB '1.[0-9]*.[0-9]*' \
+         ORDER BY experiments.created_at;",
+        &[&Status::Completed.to_str()],
+        |row| -> (String, String, String) {
+            (row.get("crate"), row.get("toolchain"), row.get("result"))
+        },
+    )?;
+
+    // Rows that can't be parsed anymore (for example after a crate source was removed) are skipped
+    let results = rows.into_iter().filter_map(|(krate, toolchain, res)| {
+        Some((krate.parse().ok()?, toolchain, res.parse().ok()?))
+    });
+    Ok(HeatMap::from_results(results))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{stable_release, HeatMap, Outcome};
+    use crate::crates::{Crate, RegistryCrate};
+    use crate::results::{FailureReason, TestResult};
+
+    #[test]
+    fn test_stable_release() {
+        assert_eq!(stable_release("1.70.0"), Some(70));
+        assert_eq!(stable_release("1.78.1"), Some(78));
+        assert_eq!(stable_release("stable"), None);
+        assert_eq!(stable_release("nightly-2020-01-01"), None);
+        assert_eq!(stable_release("1.70.0+rustflags=-Zfoo"), None);
+        assert_eq!(stable_release("1.70"), None);
+    }
+
+    #[test]
+    fn test_from_results() {
+        let reg = |name: &str, version: &str| {
+            Crate::Registry(RegistryCrate {
+                name: name.into(),
+                version: version.into(),
+            })
+        };
+        let fail = TestResult::BuildFail(FailureReason::Unknown);
+
+        let heatmap = HeatMap::from_results(vec![
+            (reg("syn", "1.0.0"), "1.70.0".into(), TestResult::TestPass),
+            (reg("syn", "1.0.1"), "1.71.0".into(), fail.clone()),
+            (reg("quote", "1.0.0"), "1.70.0".into(), TestResult::TestPass),
+            (reg("quote", "1.0.0"), "1.72.0".into(), TestResult::TestPass),
+            // Newer results replace the older ones
+            (reg("rand", "0.7.0"), "1.71.0".into(), fail.clone()),
+            (reg("rand", "0.7.1"), "1.71.0".into(), TestResult::TestPass),
+            // Spurious failures and custom toolchains are ignored
+            (
+                reg("rand", "0.7.1"),
+                "1.72.0".into(),
+                TestResult::BuildFail(FailureReason::OOM),
+            ),
+            (reg("rand", "0.7.1"), "nightly".into(), fail.clone()),
+        ]);
+
+        let releases = heatmap
+            .releases
+            .iter()
+            .map(|r| (r.release.as_str(), r.crates, r.passed))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            releases,
+            vec![("1.70", 2, 2), ("1.71", 2, 1), ("1.72", 1, 1)]
+        );
+
+        assert_eq!(heatmap.crates.len(), 1);
+        assert_eq!(heatmap.crates[0].krate, "reg/syn");
+        assert_eq!(
+            heatmap.crates[0].outcomes,
+            vec![Some(Outcome::Pass), Some(Outcome::Fail), None]
+        );
+        assert!(!heatmap.truncated);
+    }
+}
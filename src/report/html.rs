@@ -2,8 +2,8 @@ use crate::assets;
 use crate::experiments::Experiment;
 use crate::prelude::*;
 use crate::report::{
-    analyzer::ReportCrates, archives::Archive, Color, Comparison, CrateResult, ReportWriter,
-    ResultColor, ResultName, TestResults,
+    analyzer::ReportCrates, archives::Archive, survey::Survey, Color, Comparison, CrateResult,
+    ReportWriter, ResultColor, ResultName, TestResults,
 };
 use crate::results::EncodingType;
 use indexmap::IndexMap;
@@ -36,8 +36,8 @@ enum ReportCratesHTML {
 }
 
 impl CurrentPage {
-    fn navbar(&self) -> Vec<NavbarItem> {
-        vec![
+    fn navbar(&self, ex: &Experiment) -> Vec<NavbarItem> {
+        let mut items = vec![
             NavbarItem {
                 label: "Summary",
                 url: "index.html",
@@ -53,7 +53,12 @@ impl CurrentPage {
                 url: "downloads.html",
                 active: *self == CurrentPage::Downloads,
             },
-        ]
+        ];
+        // Surveys don't compare toolchains, so there is no full list of results to show
+        if ex.survey {
+            items.retain(|item| item.url != "full.html");
+        }
+        items
     }
 }
 
@@ -62,6 +67,10 @@ struct ResultsContext<'a> {
     ex: &'a Experiment,
     nav: Vec<NavbarItem>,
     categories: Vec<(Comparison, ReportCratesHTML)>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    ices: Vec<IceGroupHTML>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    error_codes: Vec<ErrorCodeGroupHTML>,
     info: IndexMap<Comparison, u32>,
     full: bool,
     crates_count: usize,
@@ -70,6 +79,14 @@ struct ResultsContext<'a> {
     result_names: Vec<String>,
 }
 
+#[derive(Serialize)]
+struct SurveyContext<'a> {
+    ex: &'a Experiment,
+    nav: Vec<NavbarItem>,
+    crates_count: usize,
+    survey: &'a Survey,
+}
+
 #[derive(Serialize)]
 struct DownloadsContext<'a> {
     ex: &'a Experiment,
@@ -87,6 +104,27 @@ struct CrateResultHTML {
     #[serde(skip_serializing_if = "Option::is_none")]
     status: Option<String>,
     runs: [Option<BuildTestResultHTML>; 2],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    anomalies: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    first_error: Option<String>,
+}
+
+/// Regressed crates crashing the compiler in the same way.
+#[derive(Serialize)]
+struct IceGroupHTML {
+    message: String,
+    queries: Vec<String>,
+    crates: Vec<CrateResultHTML>,
+}
+
+/// Regressed crates emitting the same error code.
+#[derive(Serialize)]
+struct ErrorCodeGroupHTML {
+    code: String,
+    crates: Vec<CrateResultHTML>,
 }
 
 // Map TestResult to usize to avoid the presence of special characters in html
@@ -135,6 +173,9 @@ fn write_report<W: ReportWriter>(
             status: result.status.map(|status| status.to_string()),
             res: result.res,
             runs,
+            note: result.note.clone(),
+            anomalies: result.anomalies.clone(),
+            first_error: result.first_error.as_ref().map(|error| error.to_string()),
         }
     };
 
@@ -204,6 +245,25 @@ fn write_report<W: ReportWriter>(
         })
         .collect();
 
+    let ices = res
+        .ice_groups(Comparison::Regressed)
+        .into_iter()
+        .map(|(ice, crates)| IceGroupHTML {
+            message: ice.message,
+            queries: ice.queries,
+            crates: crates.into_iter().map(&mut to_html_crate_result).collect(),
+        })
+        .collect();
+
+    let error_codes = res
+        .error_code_groups(Comparison::Regressed)
+        .into_iter()
+        .map(|(code, crates)| ErrorCodeGroupHTML {
+            code: code.to_string(),
+            crates: crates.into_iter().map(&mut to_html_crate_result).collect(),
+        })
+        .collect();
+
     let context = ResultsContext {
         ex,
         nav: if full {
@@ -211,8 +271,10 @@ fn write_report<W: ReportWriter>(
         } else {
             CurrentPage::Summary
         }
-        .navbar(),
+        .navbar(ex),
         categories,
+        ices,
+        error_codes,
         info: res.info.clone(),
         full,
         crates_count,
@@ -245,7 +307,7 @@ fn write_downloads<W: ReportWriter>(
 ) -> Fallible<()> {
     let context = DownloadsContext {
         ex,
-        nav: CurrentPage::Downloads.navbar(),
+        nav: CurrentPage::Downloads.navbar(ex),
         crates_count,
         available_archives,
     };
@@ -265,6 +327,27 @@ fn write_downloads<W: ReportWriter>(
     Ok(())
 }
 
+fn write_assets<W: ReportWriter>(dest: &W) -> Fallible<()> {
+    let js_in = assets::load("report.js")?;
+    let css_in = assets::load("report.css")?;
+
+    info!("copying static assets");
+    dest.write_bytes(
+        "report.js",
+        js_in.content()?.into_owned(),
+        js_in.mime(),
+        EncodingType::Plain,
+    )?;
+    dest.write_bytes(
+        "report.css",
+        css_in.content()?.into_owned(),
+        css_in.mime(),
+        EncodingType::Plain,
+    )?;
+
+    Ok(())
+}
+
 pub fn write_html_report<W: ReportWriter>(
     ex: &Experiment,
     crates_count: usize,
@@ -273,8 +356,6 @@ pub fn write_html_report<W: ReportWriter>(
     dest: &W,
     output_templates: bool,
 ) -> Fallible<()> {
-    let js_in = assets::load("report.js")?;
-    let css_in = assets::load("report.css")?;
     write_report(
         ex,
         crates_count,
@@ -294,20 +375,38 @@ pub fn write_html_report<W: ReportWriter>(
         output_templates,
     )?;
     write_downloads(ex, crates_count, available_archives, dest, output_templates)?;
+    write_assets(dest)
+}
 
-    info!("copying static assets");
-    dest.write_bytes(
-        "report.js",
-        js_in.content()?.into_owned(),
-        js_in.mime(),
-        EncodingType::Plain,
-    )?;
-    dest.write_bytes(
-        "report.css",
-        css_in.content()?.into_owned(),
-        css_in.mime(),
-        EncodingType::Plain,
-    )?;
+/// Write the HTML report of a survey experiment, where the summary is replaced by the statistics
+/// of the survey.
+pub fn write_survey_report<W: ReportWriter>(
+    ex: &Experiment,
+    crates_count: usize,
+    survey: &Survey,
+    available_archives: Vec<Archive>,
+    dest: &W,
+    output_templates: bool,
+) -> Fallible<()> {
+    let context = SurveyContext {
+        ex,
+        nav: CurrentPage::Summary.navbar(ex),
+        crates_count,
+        survey,
+    };
 
-    Ok(())
+    info!("generating index.html");
+    let html = minifier::html::minify(&assets::render_template("report/survey.html", &context)?);
+    dest.write_string("index.html", html.into(), &mime::TEXT_HTML)?;
+
+    if output_templates {
+        dest.write_string(
+            "index.html.context.json",
+            serde_json::to_string(&context)?.into(),
+            &mime::APPLICATION_JSON,
+        )?;
+    }
+
+    write_downloads(ex, crates_count, available_archives, dest, output_templates)?;
+    write_assets(dest)
 }
@@ -0,0 +1,141 @@
+/// Printed by rustc before the message of an explicit or delayed bug.
+const ICE_MARKER: &str = "internal compiler error: ";
+const PANIC_MARKER: &str = "panicked at ";
+const QUERY_STACK_START: &str = "query stack during panic:";
+const QUERY_STACK_END: &str = "end of query stack";
+/// Only the innermost queries are kept, as the outer ones are the same for most crashes.
+const MAX_QUERIES: usize = 5;
+
+/// What identifies a compiler crash, regardless of the crate triggering it: the panic message
+/// (with the names of the items it mentions stripped) and the queries being executed.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct IceSignature {
+    pub message: String,
+    pub queries: Vec<String>,
+}
+
+/// Strip the prefixes added to each line by rustwide.
+pub(super) fn strip_prefixes(line: &str) -> &str {
+    line.trim_start_matches("[INFO] ")
+        .trim_start_matches("[stderr] ")
+        .trim()
+}
+
+/// Replace the text between backticks (names of items and types) with a placeholder, so that
+/// the same crash on different crates gets the same message.
+fn normalize(message: &str) -> String {
+    let mut normalized = String::new();
+    for (i, part) in message.split('`').enumerate() {
+        if i % 2 == 0 {
+            normalized.push_str(part);
+        } else {
+            normalized.push_str("`_`");
+        }
+    }
+    normalized.trim().to_string()
+}
+
+/// Message of a panic, which is quoted on the same line in older compilers and printed on the
+/// following line (after the location) in newer ones.
+fn panic_message(rest: &str, next: Option<&str>) -> Option<String> {
+    if rest.starts_with('\'') {
+        let end = rest.rfind("', ").unwrap_or(rest.len());
+        Some(rest[1..end.max(1)].to_string())
+    } else {
+        next.map(strip_prefixes).map(String::from)
+    }
+}
+
+/// Name of the query in a frame of the query stack, like `typeck` in
+/// ``#0 [typeck] type-checking `foo::bar` ``.
+fn query_name(frame: &str) -> Option<String> {
+    let start = frame.find('[')?;
+    let end = start + frame[start..].find(']')?;
+    Some(frame[start + 1..end].to_string())
+}
+
+/// Find the signature of the compiler crash reported in a log, if any.
+pub(super) fn extract(log: &str) -> Option<IceSignature> {
+    let lines = log.lines().collect::<Vec<_>>();
+    let mut message = None;
+    let mut panic = None;
+    let mut queries = Vec::new();
+
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        if message.is_none() {
+            if let Some(idx) = line.find(ICE_MARKER) {
+                message = Some(normalize(&line[idx + ICE_MARKER.len()..]));
+            }
+        }
+        if panic.is_none() && line.contains("thread 'rustc'") {
+            if let Some(idx) = line.find(PANIC_MARKER) {
+                let rest = &line[idx + PANIC_MARKER.len()..];
+                panic = panic_message(rest, lines.get(i + 1).cloned()).map(|m| normalize(&m));
+            }
+        }
+        if queries.is_empty() && line.contains(QUERY_STACK_START) {
+            for frame in &lines[i + 1..] {
+                i += 1;
+                if frame.contains(QUERY_STACK_END) {
+                    break;
+                }
+                if queries.len() < MAX_QUERIES {
+                    queries.extend(query_name(strip_prefixes(frame)));
+                }
+            }
+        }
+        i += 1;
+    }
+
+    // The ICE message is more specific than the panic, which is often just `Box<dyn Any>`
+    Some(IceSignature {
+        message: message.or(panic)?,
+        queries,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{extract, IceSignature};
+
+    #[test]
+    fn test_extract() {
+        assert_eq!(
+            extract("[INFO] [stderr] error[E0308]: mismatched types"),
+            None
+        );
+
+        let log = concat!(
+            "[INFO] [stderr]    Compiling foo v0.1.0\n",
+            "[INFO] [stderr] error: internal compiler error: compiler/rustc_mir/src/lib.rs:12:5: ",
+            "broken MIR in `foo::bar`\n",
+            "[INFO] [stderr] thread 'rustc' panicked at 'Box<dyn Any>', compiler/lib.rs:1:1\n",
+            "[INFO] [stderr] query stack during panic:\n",
+            "[INFO] [stderr] #0 [mir_borrowck] borrow-checking `foo::bar`\n",
+            "[INFO] [stderr] #1 [analysis] running analysis passes on this crate\n",
+            "[INFO] [stderr] end of query stack\n",
+        );
+        assert_eq!(
+            extract(log),
+            Some(IceSignature {
+                message: "compiler/rustc_mir/src/lib.rs:12:5: broken MIR in `_`".into(),
+                queries: vec!["mir_borrowck".into(), "analysis".into()],
+            })
+        );
+
+        // Newer compilers print the panic message after its location
+        let log = concat!(
+            "[INFO] [stderr] thread 'rustc' panicked at compiler/rustc_hir/src/lib.rs:3:9:\n",
+            "[INFO] [stderr] no entry found for key\n",
+        );
+        assert_eq!(
+            extract(log),
+            Some(IceSignature {
+                message: "no entry found for key".into(),
+                queries: Vec::new(),
+            })
+        );
+    }
+}
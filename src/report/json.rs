@@ -0,0 +1,152 @@
+//! Machine-readable export of the results of an experiment, written as `export.json` next to the
+//! HTML report. Unlike `results.json`, which mirrors the internal types of crater, the format is
+//! documented in `docs/report-json.md` and versioned: fields can be added to it as long as the
+//! existing ones keep their meaning, while any other change requires bumping `SCHEMA_VERSION`.
+
+use crate::experiments::Experiment;
+use crate::prelude::*;
+use crate::report::{BuildTestResult, FirstError, RawTestResults, ReportWriter};
+use crate::toolchain::Toolchain;
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+
+const SCHEMA_VERSION: u32 = 1;
+const EXPORT_FILE: &str = "export.json";
+
+#[derive(Serialize)]
+struct Export<'a> {
+    version: u32,
+    experiment: ExportedExperiment<'a>,
+    toolchains: Vec<ExportedToolchain<'a>>,
+    crates: Vec<ExportedCrate<'a>>,
+}
+
+#[derive(Serialize)]
+struct ExportedExperiment<'a> {
+    name: &'a str,
+    mode: &'static str,
+    cap_lints: &'static str,
+    created_at: DateTime<Utc>,
+    started_at: Option<DateTime<Utc>>,
+    completed_at: Option<DateTime<Utc>>,
+    github_issue: Option<&'a str>,
+    namespace: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct ExportedToolchain<'a> {
+    name: String,
+    rustflags: Option<&'a str>,
+    env: &'a BTreeMap<String, String>,
+    cargo_config: &'a BTreeMap<String, String>,
+    patches: Vec<ExportedPatch<'a>>,
+}
+
+#[derive(Serialize)]
+struct ExportedPatch<'a> {
+    name: &'a str,
+    repo: &'a str,
+    branch: &'a str,
+}
+
+#[derive(Serialize)]
+struct ExportedCrate<'a> {
+    id: String,
+    name: &'a str,
+    url: &'a str,
+    result: &'static str,
+    runs: Vec<Option<ExportedRun>>,
+    owners: &'a [String],
+    note: Option<&'a str>,
+    first_error: Option<&'a FirstError>,
+}
+
+#[derive(Serialize)]
+struct ExportedRun {
+    toolchain: String,
+    result: String,
+    reason: Option<String>,
+    log: String,
+}
+
+impl<'a> ExportedToolchain<'a> {
+    fn new(tc: &'a Toolchain) -> Self {
+        ExportedToolchain {
+            name: tc.to_string(),
+            rustflags: tc.rustflags.as_deref(),
+            env: &tc.env,
+            cargo_config: &tc.cargo_config,
+            patches: tc
+                .patches
+                .iter()
+                .map(|patch| ExportedPatch {
+                    name: &patch.name,
+                    repo: &patch.repo,
+                    branch: &patch.branch,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl ExportedRun {
+    fn new(tc: &Toolchain, run: &BuildTestResult) -> Self {
+        // Results are serialized as `kind:reason`, for example `build-fail:oom`
+        let result = run.res.to_string();
+        let reason = result.find(':').map(|idx| result[idx + 1..].to_string());
+        ExportedRun {
+            toolchain: tc.to_string(),
+            result: run.res.kind().to_string(),
+            reason,
+            log: format!("{}/log.txt", run.log),
+        }
+    }
+}
+
+fn export<'a>(ex: &'a Experiment, raw: &'a RawTestResults) -> Export<'a> {
+    Export {
+        version: SCHEMA_VERSION,
+        experiment: ExportedExperiment {
+            name: &ex.name,
+            mode: ex.mode.to_str(),
+            cap_lints: ex.cap_lints.to_str(),
+            created_at: ex.created_at,
+            started_at: ex.started_at,
+            completed_at: ex.completed_at,
+            github_issue: ex.github_issue.as_ref().map(|i| i.html_url.as_str()),
+            namespace: ex.namespace.as_deref(),
+        },
+        toolchains: ex.toolchains.iter().map(ExportedToolchain::new).collect(),
+        crates: raw
+            .crates
+            .iter()
+            .map(|krate| ExportedCrate {
+                id: krate.krate.id(),
+                name: &krate.name,
+                url: &krate.url,
+                result: krate.res.to_str(),
+                runs: ex
+                    .toolchains
+                    .iter()
+                    .zip(&krate.runs)
+                    .map(|(tc, run)| run.as_ref().map(|run| ExportedRun::new(tc, run)))
+                    .collect(),
+                owners: &krate.owners,
+                note: krate.note.as_deref(),
+                first_error: krate.first_error.as_ref(),
+            })
+            .collect(),
+    }
+}
+
+pub(super) fn write_export<W: ReportWriter>(
+    ex: &Experiment,
+    raw: &RawTestResults,
+    dest: &W,
+) -> Fallible<()> {
+    dest.write_string(
+        EXPORT_FILE,
+        serde_json::to_string(&export(ex, raw))?.into(),
+        &mime::APPLICATION_JSON,
+    )
+}
@@ -2,9 +2,12 @@ use crate::crates::Crate;
 use crate::experiments::Experiment;
 use crate::prelude::*;
 use crate::report::analyzer::{ReportConfig, ReportCrates, ToolchainSelect};
+use crate::report::baseline::BaselineBreakage;
 use crate::report::{
-    crate_to_url, BuildTestResult, Comparison, CrateResult, ReportWriter, ResultName, TestResults,
+    crate_to_name, crate_to_path_fragment, crate_to_url, BuildTestResult, Comparison, CrateResult,
+    IceSignature, ReportWriter, ResultName, SanitizationContext, TestResults,
 };
+use crate::results::DiagnosticCode;
 use crate::utils::serialize::to_vec;
 use indexmap::{IndexMap, IndexSet};
 use std::fmt::Write;
@@ -26,6 +29,14 @@ struct ResultsContext<'a> {
     ex: &'a Experiment,
     categories: Vec<(Comparison, ReportCratesMD)>,
     info: IndexMap<Comparison, u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    anomalies: Vec<CrateResult>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    ices: Vec<(IceSignature, Vec<CrateResult>)>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    error_codes: Vec<(DiagnosticCode, usize)>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    baseline: Option<&'a BaselineBreakage>,
     full: bool,
     crates_count: usize,
 }
@@ -69,6 +80,12 @@ fn write_crate(
         .map(|status| format!(" ({})", status.to_string()))
         .unwrap_or_default();
 
+    let owners = if !is_child && !krate.owners.is_empty() {
+        format!(" (cc {})", krate.owners.join(" "))
+    } else {
+        String::new()
+    };
+
     if let ReportConfig::Complete(toolchain) = comparison.report_config() {
         let (conj, run) = match toolchain {
             ToolchainSelect::Start => ("from", 0),
@@ -77,7 +94,7 @@ fn write_crate(
 
         writeln!(
             &mut rendered,
-            "{}[{}{}]({}) {} {} **{}** [start]({}/log.txt) | [end]({}/log.txt)",
+            "{}[{}{}]({}) {} {} **{}** [start]({}/log.txt) | [end]({}/log.txt){}",
             prefix,
             krate.name,
             status_warning,
@@ -86,7 +103,8 @@ fn write_crate(
             conj,
             runs[run],
             runs[1],
-            runs[3]
+            runs[3],
+            owners
         )?;
     } else {
         writeln!(
@@ -102,6 +120,15 @@ fn write_crate(
         )?;
     };
 
+    if !is_child {
+        if let Some(note) = &krate.note {
+            writeln!(&mut rendered, "  * **note:** {}", note)?;
+        }
+        for anomaly in &krate.anomalies {
+            writeln!(&mut rendered, "  * **anomaly:** {}", anomaly)?;
+        }
+    }
+
     Ok(())
 }
 
@@ -111,6 +138,27 @@ fn render_markdown(context: &ResultsContext) -> Fallible<String> {
     //add title
     writeln!(&mut rendered, "# Crater report for {}\n\n", context.ex.name)?;
 
+    // summarize the most relevant results before listing them
+    let count = |comparison| context.info.get(&comparison).cloned().unwrap_or(0);
+    writeln!(
+        &mut rendered,
+        "{} crates tested: {} regressed, {} fixed",
+        context.crates_count,
+        count(Comparison::Regressed),
+        count(Comparison::Fixed)
+    )?;
+    if let Some(baseline) = context
+        .baseline
+        .filter(|baseline| !baseline.crates.is_empty())
+    {
+        writeln!(
+            &mut rendered,
+            "\n{} crates stopped building on the baseline since `{}`",
+            baseline.crates.len(),
+            baseline.previous
+        )?;
+    }
+
     for (comparison, results) in context.categories.iter() {
         writeln!(&mut rendered, "\n### {}", comparison.to_string())?;
         match results {
@@ -142,6 +190,73 @@ fn render_markdown(context: &ResultsContext) -> Fallible<String> {
         }
     }
 
+    // the same compiler crash often hits many crates, which are hard to spot in the list above
+    if !context.ices.is_empty() {
+        writeln!(&mut rendered, "\n### regressions by ICE")?;
+        for (ice, crates) in &context.ices {
+            writeln!(
+                &mut rendered,
+                "* **{} crates**: {}",
+                crates.len(),
+                ice.message
+            )?;
+            if !ice.queries.is_empty() {
+                writeln!(
+                    &mut rendered,
+                    "  * query stack: {}",
+                    ice.queries.join(" < ")
+                )?;
+            }
+            for krate in crates {
+                write_crate(&mut rendered, krate, Comparison::Regressed, true)?;
+            }
+        }
+    }
+
+    // a new error hitting many crates is easier to spot when counted by error code
+    if !context.error_codes.is_empty() {
+        writeln!(&mut rendered, "\n### regressions by error code")?;
+        for (code, count) in &context.error_codes {
+            writeln!(&mut rendered, "* **{}**: {} crates", code, count)?;
+        }
+    }
+
+    // crates with anomalies are usually in categories hidden from the summary
+    if !context.anomalies.is_empty() {
+        writeln!(&mut rendered, "\n### log anomalies")?;
+        for krate in &context.anomalies {
+            write_crate(&mut rendered, krate, krate.res, false)?;
+        }
+    }
+
+    // crates that broke on the baseline are not regressions, so they wouldn't show up otherwise
+    if let Some(baseline) = context
+        .baseline
+        .filter(|baseline| !baseline.crates.is_empty())
+    {
+        writeln!(
+            &mut rendered,
+            "\n### broken on the baseline since {}",
+            baseline.previous
+        )?;
+        for broken in &baseline.crates {
+            let log = crate_to_path_fragment(
+                &context.ex.toolchains[0],
+                &broken.krate,
+                SanitizationContext::Url,
+            );
+            writeln!(
+                &mut rendered,
+                "* [{}]({}) was **{}**, now **{}** [start]({}/log.txt)",
+                crate_to_name(&broken.krate)?,
+                crate_to_url(&broken.krate)?,
+                broken.before.long_name(),
+                broken.after.long_name(),
+                log.to_str().unwrap().replace(r"\", "/")
+            )?;
+        }
+    }
+
     Ok(rendered)
 }
 
@@ -149,6 +264,7 @@ fn write_report<W: ReportWriter>(
     ex: &Experiment,
     crates_count: usize,
     res: &TestResults,
+    baseline: Option<&BaselineBreakage>,
     full: bool,
     to: &str,
     dest: &W,
@@ -182,10 +298,31 @@ fn write_report<W: ReportWriter>(
         })
         .collect();
 
+    // anomalies are only detected in categories without dependency trees
+    let anomalies = res
+        .categories
+        .values()
+        .filter_map(|crates| match crates {
+            ReportCrates::Plain(crates) => Some(crates),
+            ReportCrates::Complete { .. } => None,
+        })
+        .flatten()
+        .filter(|krate| !krate.anomalies.is_empty())
+        .cloned()
+        .collect();
+
     let context = ResultsContext {
         ex,
         categories,
         info: res.info.clone(),
+        anomalies,
+        ices: res.ice_groups(Comparison::Regressed),
+        error_codes: res
+            .error_code_groups(Comparison::Regressed)
+            .into_iter()
+            .map(|(code, crates)| (code, crates.len()))
+            .collect(),
+        baseline,
         full,
         crates_count,
     };
@@ -209,6 +346,7 @@ pub fn write_markdown_report<W: ReportWriter>(
     ex: &Experiment,
     crates_count: usize,
     res: &TestResults,
+    baseline: Option<&BaselineBreakage>,
     dest: &W,
     output_templates: bool,
 ) -> Fallible<()> {
@@ -216,6 +354,7 @@ pub fn write_markdown_report<W: ReportWriter>(
         ex,
         crates_count,
         res,
+        baseline,
         false,
         "markdown.md",
         dest,
@@ -4,32 +4,58 @@ use crate::dirs::WORK_DIR;
 use crate::experiments::Experiment;
 use crate::prelude::*;
 use crate::report::analyzer::{analyze_report, ReportConfig, ToolchainSelect};
-use crate::results::{EncodedLog, EncodingType, FailureReason, ReadResults, TestResult};
+use crate::results::{
+    DatabaseDB, DiagnosticCode, EncodedLog, EncodingType, FailureReason, ReadResults,
+    SandboxCounters, TestResult,
+};
 use crate::toolchain::Toolchain;
 use crate::utils;
 use crates_index::Index;
+use crossbeam_utils::thread::scope;
 use mime::{self, Mime};
 use percent_encoding::{utf8_percent_encode, AsciiSet};
+use regex::Regex;
 use std::borrow::Cow;
-#[cfg(test)]
-use std::cell::RefCell;
-#[cfg(test)]
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::convert::AsRef;
 use std::fmt::{self, Display};
 use std::fs::{self, File};
 use std::io::{self, Read};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(test)]
+use std::sync::Mutex;
 
 mod analyzer;
+mod anomalies;
 mod archives;
+mod azure;
+mod baseline;
+mod dependents;
+mod diff;
 mod display;
+mod first_error;
+mod gcs;
+pub(crate) mod heatmap;
 mod html;
+mod ice;
+mod json;
 mod markdown;
+mod owners;
 mod s3;
+mod storage;
+mod survey;
 
+pub use self::archives::{crate_logs_archive, load_crate_logs};
+pub use self::azure::AzureWriter;
+pub use self::dependents::regressed_dependents;
+pub use self::diff::{diff_experiments, ClassificationChange, ExperimentDiff};
 pub use self::display::{Color, ResultColor, ResultName};
+pub use self::first_error::FirstError;
+pub use self::gcs::GcsWriter;
+pub use self::ice::IceSignature;
 pub use self::s3::{get_client_for_bucket, S3Prefix, S3Writer};
+pub use self::storage::ReportStorage;
 pub use analyzer::TestResults;
 
 pub(crate) const REPORT_ENCODE_SET: AsciiSet = percent_encoding::CONTROLS
@@ -59,6 +85,18 @@ pub struct CrateResult {
     status: Option<CrateVersionStatus>,
     pub res: Comparison,
     runs: [Option<BuildTestResult>; 2],
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    owners: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    anomalies: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ice: Option<IceSignature>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    error_codes: BTreeSet<DiagnosticCode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    first_error: Option<FirstError>,
 }
 
 string_enum!(enum CrateVersionStatus {
@@ -76,11 +114,19 @@ string_enum!(pub enum Comparison {
     Error => "error",
     Broken => "broken",
     SameBuildFail => "build-fail",
+    RequiresNightly => "requires-nightly",
     SameTestFail => "test-fail",
     SameTestSkipped => "test-skipped",
     SameTestPass => "test-pass",
     SpuriousRegressed => "spurious-regressed",
     SpuriousFixed => "spurious-fixed",
+    ExpectedRegression => "expected-regression",
+    NotApplicable => "not-applicable",
+    NewWarnings => "new-warnings",
+    FixedWarnings => "fixed-warnings",
+    ClippyIce => "clippy-ice",
+    MiriUB => "miri-ub",
+    MiriUnsupported => "miri-unsupported",
 });
 
 impl Comparison {
@@ -91,30 +137,56 @@ impl Comparison {
             | Comparison::Unknown
             | Comparison::Error
             | Comparison::SpuriousRegressed
-            | Comparison::SpuriousFixed => true,
+            | Comparison::SpuriousFixed
+            | Comparison::ExpectedRegression
+            | Comparison::NewWarnings
+            | Comparison::FixedWarnings
+            | Comparison::ClippyIce
+            | Comparison::MiriUB
+            | Comparison::MiriUnsupported => true,
             Comparison::Skipped
+            | Comparison::NotApplicable
             | Comparison::Broken
             | Comparison::SameBuildFail
+            | Comparison::RequiresNightly
             | Comparison::SameTestFail
             | Comparison::SameTestSkipped
             | Comparison::SameTestPass => false,
         }
     }
 
+    /// Position of the category in the reports: regressions and fixes are shown before
+    /// everything else, as they're the most relevant results of an experiment.
+    pub fn report_order(self) -> u8 {
+        match self {
+            Comparison::Regressed => 0,
+            Comparison::Fixed => 1,
+            _ => 2,
+        }
+    }
+
     pub fn report_config(self) -> ReportConfig {
         match self {
             Comparison::Regressed => ReportConfig::Complete(ToolchainSelect::End),
             Comparison::Fixed => ReportConfig::Complete(ToolchainSelect::Start),
+            Comparison::NewWarnings => ReportConfig::Complete(ToolchainSelect::End),
+            Comparison::FixedWarnings => ReportConfig::Complete(ToolchainSelect::Start),
             Comparison::Unknown
             | Comparison::Error
             | Comparison::SpuriousRegressed
             | Comparison::SpuriousFixed
+            | Comparison::ExpectedRegression
             | Comparison::Skipped
+            | Comparison::NotApplicable
             | Comparison::Broken
             | Comparison::SameBuildFail
+            | Comparison::RequiresNightly
             | Comparison::SameTestFail
             | Comparison::SameTestSkipped
-            | Comparison::SameTestPass => ReportConfig::Simple,
+            | Comparison::SameTestPass
+            | Comparison::ClippyIce
+            | Comparison::MiriUB
+            | Comparison::MiriUnsupported => ReportConfig::Simple,
         }
     }
 }
@@ -124,6 +196,9 @@ impl Comparison {
 struct BuildTestResult {
     res: TestResult,
     log: String,
+    /// Operations denied by the sandbox while building the crate, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sandbox: Option<SandboxCounters>,
 }
 
 /// The type of sanitization required for a string.
@@ -177,6 +252,9 @@ fn crate_to_path_fragment(
         Crate::Git(ref repo) => {
             path.push("git");
             path.push(dest.sanitize(&repo.url).into_owned());
+            if let Some(ref subpath) = repo.subpath {
+                path.push(dest.sanitize(subpath).into_owned());
+            }
         }
     }
 
@@ -211,6 +289,9 @@ fn get_crate_version_status(index: &Index, krate: &Crate) -> Fallible<Option<Cra
     }
 }
 
+/// Number of crates whose results are loaded at once while generating the report.
+const RESULTS_CHUNK_SIZE: usize = 1000;
+
 pub fn generate_report<DB: ReadResults>(
     db: &DB,
     config: &Config,
@@ -221,52 +302,102 @@ pub fn generate_report<DB: ReadResults>(
     let index = Index::new(WORK_DIR.join("crates.io-index"));
     //crate ids are unique so unstable sort is equivalent to stable sort but is generally faster
     crates.sort_unstable_by_key(|a| a.id());
-    let res = crates
-        .iter()
-        .map(|krate| {
-            // Any errors here will turn into unknown results
-            let crate_results = ex.toolchains.iter().map(|tc| -> Fallible<BuildTestResult> {
-                let res = db
-                    .load_test_result(ex, tc, &krate)?
-                    .ok_or_else(|| err_msg("no result"))?;
-
-                Ok(BuildTestResult {
-                    res,
-                    log: crate_to_path_fragment(tc, &krate, SanitizationContext::Url)
-                        .to_str()
-                        .unwrap()
-                        .replace(r"\", "/"), // Normalize paths in reports generated on Windows
+    let expected = expected_regressions(ex);
+    let mut res = Vec::with_capacity(crates.len());
+    // The results are loaded a chunk of crates at a time: querying them crate by crate is much
+    // slower, and loading all of them at once takes too much memory on the full runs
+    for chunk in crates.chunks(RESULTS_CHUNK_SIZE) {
+        let results = ex
+            .toolchains
+            .iter()
+            .map(|tc| db.load_results_chunk(ex, tc, chunk))
+            .collect::<Fallible<Vec<_>>>()?;
+        let chunk_res = chunk
+            .iter()
+            .map(|krate| {
+                // Any errors here will turn into unknown results
+                let crate_results = ex.toolchains.iter().zip(&results).map(
+                    |(tc, results)| -> Fallible<BuildTestResult> {
+                        let (res, sandbox) = results
+                            .get(krate)
+                            .cloned()
+                            .ok_or_else(|| err_msg("no result"))?;
+
+                        Ok(BuildTestResult {
+                            res,
+                            // Normalize paths in reports generated on Windows
+                            log: crate_to_path_fragment(tc, &krate, SanitizationContext::Url)
+                                .to_str()
+                                .unwrap()
+                                .replace(r"\", "/"),
+                            sandbox,
+                        })
+                    },
+                );
+                // Convert errors to Nones
+                let mut crate_results = crate_results.map(|r| r.ok()).collect::<Vec<_>>();
+                let crate2 = crate_results.pop().unwrap();
+                let crate1 = crate_results.pop().unwrap();
+                let mut comp = compare(
+                    config,
+                    &krate,
+                    crate1.as_ref().map(|b| &b.res),
+                    crate2.as_ref().map(|b| &b.res),
+                );
+                if comp == Comparison::Regressed && is_expected(db, ex, krate, &expected) {
+                    comp = Comparison::ExpectedRegression;
+                }
+                let anomalies = if config.logs.detect_anomalies {
+                    detect_anomalies(db, ex, krate, comp)
+                } else {
+                    Vec::new()
+                };
+                let ice = detect_ice(db, ex, krate, crate2.as_ref().map(|b| &b.res));
+                let (error_codes, first_error) = if comp == Comparison::Regressed {
+                    detect_errors(db, ex, krate)
+                } else {
+                    (BTreeSet::new(), None)
+                };
+
+                Ok(CrateResult {
+                    name: crate_to_name(&krate)?,
+                    url: crate_to_url(&krate)?,
+                    status: get_crate_version_status(&index, &krate)
+                        .unwrap_or(Some(CrateVersionStatus::MissingFromIndex)),
+                    krate: krate.clone(),
+                    res: comp,
+                    runs: [crate1, crate2],
+                    owners: Vec::new(),
+                    note: db.load_crate_note(&krate).unwrap_or_else(|err| {
+                        utils::report_failure(&err);
+                        None
+                    }),
+                    anomalies,
+                    ice,
+                    error_codes,
+                    first_error,
                 })
-            });
-            // Convert errors to Nones
-            let mut crate_results = crate_results.map(|r| r.ok()).collect::<Vec<_>>();
-            let crate2 = crate_results.pop().unwrap();
-            let crate1 = crate_results.pop().unwrap();
-            let comp = compare(
-                config,
-                &krate,
-                crate1.as_ref().map(|b| &b.res),
-                crate2.as_ref().map(|b| &b.res),
-            );
-
-            Ok(CrateResult {
-                name: crate_to_name(&krate)?,
-                url: crate_to_url(&krate)?,
-                status: get_crate_version_status(&index, &krate)
-                    .unwrap_or(Some(CrateVersionStatus::MissingFromIndex)),
-                krate: krate.clone(),
-                res: comp,
-                runs: [crate1, crate2],
             })
-        })
-        .collect::<Fallible<Vec<_>>>()?;
+            .collect::<Fallible<Vec<_>>>()?;
+        res.extend(chunk_res);
+    }
+
+    if config.owners.enabled {
+        info!("looking up the owners of the regressed crates");
+        owners::fill_owners(
+            &mut res,
+            std::time::Duration::from_millis(config.owners.request_interval),
+        );
+    }
 
     Ok(RawTestResults { crates: res })
 }
 
 const PROGRESS_FRACTION: usize = 10; // write progress every ~1/N crates
 
-fn write_logs<DB: ReadResults, W: ReportWriter>(
+/// Write the logs of all the crates, splitting them between `reports.threads` threads. Each thread
+/// loads a single log at a time, so the memory usage doesn't grow with the number of crates.
+fn write_logs<DB: ReadResults + Sync, W: ReportWriter + Sync>(
     db: &DB,
     ex: &Experiment,
     crates: &[Crate],
@@ -275,44 +406,135 @@ fn write_logs<DB: ReadResults, W: ReportWriter>(
 ) -> Fallible<()> {
     let num_crates = crates.len();
     let progress_every = (num_crates / PROGRESS_FRACTION) + 1;
-    for (i, krate) in crates.iter().enumerate() {
-        if i % progress_every == 0 {
-            info!("wrote logs for {}/{} crates", i, num_crates)
+    let written = AtomicUsize::new(0);
+    let chunk_size = num_crates / config.reports.threads.max(1) + 1;
+
+    scope(|scope| -> Fallible<()> {
+        let mut threads = Vec::new();
+        for (i, chunk) in crates.chunks(chunk_size).enumerate() {
+            let written = &written;
+            let join = scope.builder().name(format!("report-logs-{}", i)).spawn(
+                move || -> Fallible<()> {
+                    for krate in chunk {
+                        let done = written.fetch_add(1, Ordering::SeqCst);
+                        if done % progress_every == 0 {
+                            info!("wrote logs for {}/{} crates", done, num_crates)
+                        }
+
+                        if config.should_skip(krate) {
+                            continue;
+                        }
+
+                        for tc in ex.tested_toolchains() {
+                            write_log(db, ex, tc, krate, dest, config)?;
+                        }
+                    }
+                    Ok(())
+                },
+            )?;
+            threads.push(join);
         }
 
-        if config.should_skip(krate) {
-            continue;
+        for thread in threads {
+            match thread.join() {
+                Ok(res) => res?,
+                Err(panic) => {
+                    utils::report_panic(&panic);
+                    bail!("a thread writing the logs panicked");
+                }
+            }
         }
+        Ok(())
+    })
+}
 
-        for tc in &ex.toolchains {
-            let log_path =
-                crate_to_path_fragment(tc, krate, SanitizationContext::Path).join("log.txt");
-            let content = db
-                .load_log(ex, tc, krate)
-                .and_then(|c| c.ok_or_else(|| err_msg("missing logs")))
-                .with_context(|_| format!("failed to read log of {} on {}", krate, tc.to_string()));
-            let content = match content {
-                Ok(c) => c,
-                Err(e) => {
-                    utils::report_failure(&e);
-                    continue;
-                }
-            };
+/// Write the log of a crate on a toolchain. Missing logs are reported and otherwise ignored.
+fn write_log<DB: ReadResults, W: ReportWriter>(
+    db: &DB,
+    ex: &Experiment,
+    tc: &Toolchain,
+    krate: &Crate,
+    dest: &W,
+    config: &Config,
+) -> Fallible<()> {
+    let log_path = crate_to_path_fragment(tc, krate, SanitizationContext::Path).join("log.txt");
+    let content = db
+        .load_log(ex, tc, krate)
+        .and_then(|c| c.ok_or_else(|| err_msg("missing logs")))
+        .and_then(|c| match config.logs.report_compression {
+            Some(encoding) => c.reencode(encoding),
+            None => Ok(c),
+        })
+        .with_context(|_| format!("failed to read log of {} on {}", krate, tc.to_string()));
+    let content = match content {
+        Ok(c) => c,
+        Err(e) => {
+            utils::report_failure(&e);
+            return Ok(());
+        }
+    };
 
-            match content {
-                EncodedLog::Plain(data) => {
-                    dest.write_bytes(log_path, data, &mime::TEXT_PLAIN_UTF_8, EncodingType::Plain)
-                }
-                EncodedLog::Gzip(data) => {
-                    dest.write_bytes(log_path, data, &mime::TEXT_PLAIN_UTF_8, EncodingType::Gzip)
-                }
-            }?;
+    let encoding = content.get_encoding_type();
+    dest.write_bytes(
+        log_path,
+        content.into_bytes(),
+        &mime::TEXT_PLAIN_UTF_8,
+        encoding,
+    )
+}
+
+/// Upload the logs of the results recorded since the last call, while the experiment is running,
+/// so that generating the report at the end doesn't need to upload all of them at once. Returns
+/// the number of logs written.
+pub fn stream_logs<W: ReportWriter>(
+    db: &DatabaseDB,
+    ex: &Experiment,
+    dest: &W,
+    config: &Config,
+) -> Fallible<usize> {
+    let mut written = 0;
+    for (krate, tc) in db.unstreamed_logs(ex)? {
+        if !config.should_skip(&krate) {
+            write_log(db, ex, &tc, &krate, dest, config)?;
+            written += 1;
         }
+        db.mark_log_streamed(ex, &tc, &krate)?;
     }
-    Ok(())
+    Ok(written)
 }
 
-pub fn gen<DB: ReadResults, W: ReportWriter + Display>(
+pub fn gen<DB: ReadResults + Sync, W: ReportWriter + Display + Sync>(
+    db: &DB,
+    ex: &Experiment,
+    crates: &[Crate],
+    dest: &W,
+    config: &Config,
+    output_templates: bool,
+) -> Fallible<TestResults> {
+    let res = gen_without_logs(db, ex, crates, dest, config, output_templates)?;
+    info!("writing logs");
+    write_logs(db, ex, crates, dest, config)?;
+
+    Ok(res)
+}
+
+/// Number of tested crates shown in the reports, optionally leaving out the ones that can't be
+/// built on a stable baseline.
+fn crates_count(config: &Config, crates: &[Crate], res: &TestResults) -> usize {
+    let nightly_only = if config.reports.exclude_nightly_only {
+        res.info
+            .get(&Comparison::RequiresNightly)
+            .cloned()
+            .unwrap_or(0) as usize
+    } else {
+        0
+    };
+    crates.len() - nightly_only
+}
+
+/// Generate the full report, except for the logs of each crate, which are expected to already be
+/// present in the destination (for example because they were streamed with `stream_logs`).
+pub fn gen_without_logs<DB: ReadResults, W: ReportWriter + Display>(
     db: &DB,
     ex: &Experiment,
     crates: &[Crate],
@@ -323,57 +545,147 @@ pub fn gen<DB: ReadResults, W: ReportWriter + Display>(
     let raw = generate_report(db, config, ex, crates)?;
 
     info!("writing results to {}", dest);
-    info!("writing metadata");
-    dest.write_string(
-        "results.json",
-        serde_json::to_string(&raw)?.into(),
-        &mime::APPLICATION_JSON,
-    )?;
-    dest.write_string(
-        "config.json",
-        serde_json::to_string(&ex)?.into(),
-        &mime::APPLICATION_JSON,
-    )?;
-    dest.write_string(
-        "retry-regressed-list.txt",
-        gen_retry_list(&raw).into(),
-        &mime::TEXT_PLAIN_UTF_8,
-    )?;
+    write_metadata(ex, &raw, dest)?;
+
+    // Surveys don't compare toolchains, so most of the regular report doesn't apply to them
+    if ex.survey {
+        let survey = survey::Survey::new(ex, &raw);
+        let res = analyze_report(raw);
+        let crates_count = crates_count(config, crates, &res);
+        info!("writing archives");
+        let available_archives = archives::write_logs_archives(db, ex, crates, dest, config)?;
+        survey::write_survey_report(
+            ex,
+            crates_count,
+            &survey,
+            available_archives,
+            dest,
+            output_templates,
+        )?;
+        return Ok(res);
+    }
 
     let res = analyze_report(raw);
+    let crates_count = crates_count(config, crates, &res);
+    info!("comparing the baseline with the previous experiment");
+    let baseline = baseline::find(db, ex, crates)?;
+    if let Some(baseline) = &baseline {
+        dest.write_string(
+            "baseline-breakage.json",
+            serde_json::to_string(baseline)?.into(),
+            &mime::APPLICATION_JSON,
+        )?;
+    }
     info!("writing archives");
     let available_archives = archives::write_logs_archives(db, ex, crates, dest, config)?;
     info!("writing html files");
     html::write_html_report(
         ex,
-        crates.len(),
+        crates_count,
         &res,
         available_archives,
         dest,
         output_templates,
     )?;
     info!("writing markdown files");
-    markdown::write_markdown_report(ex, crates.len(), &res, dest, output_templates)?;
+    markdown::write_markdown_report(
+        ex,
+        crates_count,
+        &res,
+        baseline.as_ref(),
+        dest,
+        output_templates,
+    )?;
+
+    Ok(res)
+}
+
+/// Generate a report of the crates completed so far, while the experiment is still running. The
+/// archives are not included, and only the logs of the crates shown in the summary are written.
+pub fn gen_partial<DB: ReadResults + Sync, W: ReportWriter + Display + Sync>(
+    db: &DB,
+    ex: &Experiment,
+    crates: &[Crate],
+    dest: &W,
+    config: &Config,
+) -> Fallible<TestResults> {
+    let raw = generate_report(db, config, ex, crates)?;
+
+    info!("writing partial results to {}", dest);
+    write_metadata(ex, &raw, dest)?;
+
+    let summary_crates = raw
+        .crates
+        .iter()
+        .filter(|krate| krate.res.show_in_summary())
+        .map(|krate| krate.krate.clone())
+        .collect::<Vec<_>>();
+
+    let survey = if ex.survey {
+        Some(survey::Survey::new(ex, &raw))
+    } else {
+        None
+    };
+    let res = analyze_report(raw);
+    let crates_count = crates_count(config, crates, &res);
+    if let Some(survey) = &survey {
+        survey::write_survey_report(ex, crates_count, survey, Vec::new(), dest, false)?;
+    } else {
+        info!("writing html files");
+        html::write_html_report(ex, crates_count, &res, Vec::new(), dest, false)?;
+        info!("writing markdown files");
+        markdown::write_markdown_report(ex, crates_count, &res, None, dest, false)?;
+    }
     info!("writing logs");
-    write_logs(db, ex, crates, dest, config)?;
+    write_logs(db, ex, &summary_crates, dest, config)?;
 
     Ok(res)
 }
 
-/// Generates a list of regressed crate names that can be passed to crater via
-/// `crates=list:...` to retry those.
-fn gen_retry_list(res: &RawTestResults) -> String {
+fn write_metadata<W: ReportWriter + Display>(
+    ex: &Experiment,
+    raw: &RawTestResults,
+    dest: &W,
+) -> Fallible<()> {
+    info!("writing metadata");
+    dest.write_string(
+        "results.json",
+        serde_json::to_string(raw)?.into(),
+        &mime::APPLICATION_JSON,
+    )?;
+    json::write_export(ex, raw, dest)?;
+    dest.write_string(
+        "config.json",
+        serde_json::to_string(ex)?.into(),
+        &mime::APPLICATION_JSON,
+    )?;
+    dest.write_string(
+        "retry-regressed-list.txt",
+        gen_retry_list(raw, Comparison::Regressed).into(),
+        &mime::TEXT_PLAIN_UTF_8,
+    )?;
+    dest.write_string(
+        "fixed-list.txt",
+        gen_retry_list(raw, Comparison::Fixed).into(),
+        &mime::TEXT_PLAIN_UTF_8,
+    )?;
+    Ok(())
+}
+
+/// Generates a list of crate names with the provided comparison (usually regressed) that can be
+/// passed to crater via `crates=list:...` to retry those.
+fn gen_retry_list(res: &RawTestResults, comparison: Comparison) -> String {
     use std::fmt::Write;
 
     let mut out = String::new();
 
-    let regressed_crates = res
+    let crates = res
         .crates
         .iter()
-        .filter(|crate_res| crate_res.res == Comparison::Regressed)
+        .filter(|crate_res| crate_res.res == comparison)
         .map(|crate_res| &crate_res.krate);
 
-    for krate in regressed_crates {
+    for krate in crates {
         match krate {
             Crate::Registry(details) => writeln!(out, "{}", details.name).unwrap(),
             Crate::GitHub(repo) => writeln!(out, "{}/{}", repo.org, repo.name).unwrap(),
@@ -397,15 +709,16 @@ fn crate_to_name(c: &Crate) -> Fallible<String> {
         Crate::Local(ref name) => format!("{} (local)", name),
         Crate::Path(ref path) => utf8_percent_encode(path, &REPORT_ENCODE_SET).to_string(),
         Crate::Git(ref repo) => {
+            let mut name = utf8_percent_encode(&repo.url, &REPORT_ENCODE_SET).to_string();
+            if let Some(ref subpath) = repo.subpath {
+                name.push('.');
+                name.extend(utf8_percent_encode(subpath, &REPORT_ENCODE_SET));
+            }
             if let Some(ref sha) = repo.sha {
-                format!(
-                    "{}.{}",
-                    utf8_percent_encode(&repo.url, &REPORT_ENCODE_SET),
-                    sha
-                )
-            } else {
-                utf8_percent_encode(&repo.url, &REPORT_ENCODE_SET).to_string()
+                name.push('.');
+                name.push_str(sha);
             }
+            name
         }
     })
 }
@@ -444,10 +757,44 @@ fn compare(
     match (r1, r2) {
         (Some(res1), Some(res2)) => match (res1, res2) {
             // ICE -> ICE is not a regression, but anything else to an ICE is.
-            (BuildFail(FailureReason::ICE), BuildFail(FailureReason::ICE)) => {
+            (BuildFail(FailureReason::ICE), BuildFail(FailureReason::ICE))
+            | (BuildFail(FailureReason::ClippyICE), BuildFail(FailureReason::ClippyICE)) => {
                 Comparison::SameBuildFail
             }
             (BuildFail(_), BuildFail(FailureReason::ICE)) => Comparison::Regressed,
+            (BuildFail(_), BuildFail(FailureReason::ClippyICE))
+            | (TestPass, BuildFail(FailureReason::ClippyICE))
+            | (LintWarnings(_), BuildFail(FailureReason::ClippyICE)) => Comparison::ClippyIce,
+
+            // Lints emitted only by the second toolchain are reported even if others were fixed
+            (LintWarnings(lints1), LintWarnings(lints2)) => {
+                if !lints2.0.is_subset(&lints1.0) {
+                    Comparison::NewWarnings
+                } else if lints1 != lints2 {
+                    Comparison::FixedWarnings
+                } else {
+                    Comparison::SameTestPass
+                }
+            }
+            (TestPass, LintWarnings(_)) => Comparison::NewWarnings,
+            (LintWarnings(_), TestPass) => Comparison::FixedWarnings,
+            // Miri failures are reported on their own, as they're not always regressions of rustc
+            (TestPass, TestFail(FailureReason::MiriUB)) => Comparison::MiriUB,
+            (TestFail(ref reason), TestFail(FailureReason::MiriUB))
+                if *reason != FailureReason::MiriUB =>
+            {
+                Comparison::MiriUB
+            }
+            (TestPass, TestFail(FailureReason::MiriUnsupported)) => Comparison::MiriUnsupported,
+            (TestFail(ref reason), TestFail(FailureReason::MiriUnsupported))
+                if *reason != FailureReason::MiriUnsupported =>
+            {
+                Comparison::MiriUnsupported
+            }
+            // Crates using nightly features can't be built on a stable baseline at all
+            (BuildFail(FailureReason::RequiresNightly), BuildFail(_)) => {
+                Comparison::RequiresNightly
+            }
 
             (BuildFail(_), BuildFail(_)) => Comparison::SameBuildFail,
             (TestFail(_), TestFail(_)) => Comparison::SameTestFail,
@@ -461,6 +808,7 @@ fn compare(
             }
             (BuildFail(ref reason), TestSkipped)
             | (BuildFail(ref reason), TestPass)
+            | (BuildFail(ref reason), LintWarnings(_))
             | (TestFail(ref reason), TestPass)
                 if reason.is_spurious() =>
             {
@@ -469,6 +817,7 @@ fn compare(
             (BuildFail(_), TestFail(_))
             | (BuildFail(_), TestSkipped)
             | (BuildFail(_), TestPass)
+            | (BuildFail(_), LintWarnings(_))
             | (TestFail(_), TestPass) => Comparison::Fixed,
 
             (TestFail(reason1), BuildFail(reason2))
@@ -478,6 +827,7 @@ fn compare(
             }
             (TestPass, TestFail(reason))
             | (TestPass, BuildFail(reason))
+            | (LintWarnings(_), BuildFail(reason))
             | (TestSkipped, BuildFail(reason))
             | (TestFail(_), BuildFail(reason))
                 if reason.is_spurious() =>
@@ -486,16 +836,23 @@ fn compare(
             }
             (TestPass, TestFail(_))
             | (TestPass, BuildFail(_))
+            | (LintWarnings(_), BuildFail(_))
             | (TestSkipped, BuildFail(_))
             | (TestFail(_), BuildFail(_)) => Comparison::Regressed,
 
             (Error, _) | (_, Error) => Comparison::Error,
             (Skipped, _) | (_, Skipped) => Comparison::Skipped,
+            (NotApplicable, _) | (_, NotApplicable) => Comparison::NotApplicable,
             (BrokenCrate(_), _) | (_, BrokenCrate(_)) => Comparison::Broken,
+            // Lints are only recorded by the clippy mode, which doesn't run the tests
             (TestFail(_), TestSkipped)
             | (TestPass, TestSkipped)
             | (TestSkipped, TestFail(_))
-            | (TestSkipped, TestPass) => {
+            | (TestSkipped, TestPass)
+            | (LintWarnings(_), TestFail(_))
+            | (LintWarnings(_), TestSkipped)
+            | (TestFail(_), LintWarnings(_))
+            | (TestSkipped, LintWarnings(_)) => {
                 panic!("can't compare {} and {}", res1, res2);
             }
         },
@@ -504,6 +861,191 @@ fn compare(
     }
 }
 
+/// Compare the results of a single crate on the two toolchains of the experiment.
+pub(crate) fn compare_crate<DB: ReadResults>(
+    db: &DB,
+    config: &Config,
+    ex: &Experiment,
+    krate: &Crate,
+) -> Fallible<Comparison> {
+    let start = db.load_test_result(ex, &ex.toolchains[0], krate)?;
+    let end = db.load_test_result(ex, &ex.toolchains[1], krate)?;
+    let comp = compare(config, krate, start.as_ref(), end.as_ref());
+    if comp == Comparison::Regressed && is_expected(db, ex, krate, &expected_regressions(ex)) {
+        Ok(Comparison::ExpectedRegression)
+    } else {
+        Ok(comp)
+    }
+}
+
+/// Compare the results of all the crates already completed on both toolchains of the experiment,
+/// returning the comparison of each crate along with its result on the second toolchain.
+pub(crate) fn compare_completed<DB: ReadResults>(
+    db: &DB,
+    config: &Config,
+    ex: &Experiment,
+) -> Fallible<Vec<(Comparison, TestResult)>> {
+    let patterns = expected_regressions(ex);
+    let start = db
+        .load_all_results(ex, &ex.toolchains[0])?
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+
+    let mut compared = Vec::new();
+    for (krate, end) in db.load_all_results(ex, &ex.toolchains[1])? {
+        if let Some(start) = start.get(&krate) {
+            let mut comp = compare(config, &krate, Some(start), Some(&end));
+            if comp == Comparison::Regressed && is_expected(db, ex, &krate, &patterns) {
+                comp = Comparison::ExpectedRegression;
+            }
+            compared.push((comp, end));
+        }
+    }
+    Ok(compared)
+}
+
+/// Compile the patterns of the expected regressions of the experiment. Invalid patterns are
+/// rejected when they're added, so they're only reported and ignored here.
+fn expected_regressions(ex: &Experiment) -> Vec<Regex> {
+    ex.expected_regressions
+        .iter()
+        .filter_map(|pattern| match Regex::new(pattern) {
+            Ok(regex) => Some(regex),
+            Err(err) => {
+                warn!("invalid expected regression pattern {:?}: {}", pattern, err);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Look for unusual changes between the logs of crates whose outcome didn't change, which
+/// wouldn't be noticed otherwise.
+fn detect_anomalies<DB: ReadResults>(
+    db: &DB,
+    ex: &Experiment,
+    krate: &Crate,
+    comparison: Comparison,
+) -> Vec<String> {
+    match comparison {
+        Comparison::SameTestPass | Comparison::SameTestSkipped | Comparison::SameTestFail => {}
+        _ => return Vec::new(),
+    }
+
+    let load = |tc| -> Fallible<Option<String>> {
+        Ok(match db.load_log(ex, tc, krate)? {
+            Some(log) => Some(String::from_utf8_lossy(&log.to_plain()?).into_owned()),
+            None => None,
+        })
+    };
+    match (load(&ex.toolchains[0]), load(&ex.toolchains[1])) {
+        (Ok(Some(start)), Ok(Some(end))) => anomalies::detect(&start, &end),
+        (Err(err), _) | (_, Err(err)) => {
+            utils::report_failure(&err);
+            Vec::new()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Extract the signature of the compiler crash from the log of the end toolchain, if the crate
+/// crashed the compiler there.
+fn detect_ice<DB: ReadResults>(
+    db: &DB,
+    ex: &Experiment,
+    krate: &Crate,
+    end: Option<&TestResult>,
+) -> Option<IceSignature> {
+    match end? {
+        TestResult::BuildFail(FailureReason::ICE)
+        | TestResult::TestFail(FailureReason::ICE)
+        | TestResult::BuildFail(FailureReason::ClippyICE) => {}
+        _ => return None,
+    }
+
+    let load = || -> Fallible<Option<String>> {
+        Ok(match db.load_log(ex, &ex.toolchains[1], krate)? {
+            Some(log) => Some(String::from_utf8_lossy(&log.to_plain()?).into_owned()),
+            None => None,
+        })
+    };
+    match load() {
+        Ok(log) => ice::extract(&log?),
+        Err(err) => {
+            utils::report_failure(&err);
+            None
+        }
+    }
+}
+
+lazy_static! {
+    static ref ERROR_CODE: Regex = Regex::new(r"error\[(E[0-9]{4})\]").unwrap();
+}
+
+/// Extract the codes of the errors (like `error[E0308]`) printed in a build log.
+fn error_codes(log: &str) -> BTreeSet<DiagnosticCode> {
+    ERROR_CODE
+        .captures_iter(log)
+        .map(|captures| DiagnosticCode::from(captures[1].to_string()))
+        .collect()
+}
+
+/// Extract the codes of the errors emitted by the end toolchain and the first of those errors from
+/// its log. The log is parsed instead of relying on the result, as the codes are also useful for
+/// crates whose result doesn't carry them (for example test failures caused by doctests not
+/// compiling anymore).
+fn detect_errors<DB: ReadResults>(
+    db: &DB,
+    ex: &Experiment,
+    krate: &Crate,
+) -> (BTreeSet<DiagnosticCode>, Option<FirstError>) {
+    let log = db
+        .load_log(ex, &ex.toolchains[1], krate)
+        .and_then(|log| log.map(|log| log.to_plain()).transpose());
+    match log {
+        Ok(Some(log)) => {
+            let log = String::from_utf8_lossy(&log);
+            (error_codes(&log), first_error::extract(&log))
+        }
+        Ok(None) => (BTreeSet::new(), None),
+        Err(err) => {
+            utils::report_failure(&err);
+            (BTreeSet::new(), None)
+        }
+    }
+}
+
+/// Check whether the log of the crate on the end toolchain matches any of the patterns.
+fn is_expected<DB: ReadResults>(
+    db: &DB,
+    ex: &Experiment,
+    krate: &Crate,
+    patterns: &[Regex],
+) -> bool {
+    if patterns.is_empty() {
+        return false;
+    }
+
+    let log = match db.load_log(ex, &ex.toolchains[1], krate) {
+        Ok(Some(log)) => log,
+        Ok(None) => return false,
+        Err(err) => {
+            utils::report_failure(&err);
+            return false;
+        }
+    };
+    match log.to_plain() {
+        Ok(log) => {
+            let log = String::from_utf8_lossy(&log);
+            patterns.iter().any(|pattern| pattern.is_match(&log))
+        }
+        Err(err) => {
+            utils::report_failure(&err);
+            false
+        }
+    }
+}
+
 pub trait ReportWriter {
     fn write_bytes<P: AsRef<Path>>(
         &self,
@@ -537,9 +1079,14 @@ impl ReportWriter for FileWriter {
         path: P,
         b: Vec<u8>,
         _: &Mime,
-        _: EncodingType,
+        encoding_type: EncodingType,
     ) -> Fallible<()> {
         self.create_prefix(path.as_ref())?;
+        // There is no web server setting the encoding of local reports, so they're decompressed
+        let b = match encoding_type {
+            EncodingType::Plain => b,
+            _ => EncodedLog::from_encoded(b, encoding_type).to_plain()?,
+        };
         fs::write(&self.0.join(path.as_ref()), &b)?;
         Ok(())
     }
@@ -566,18 +1113,26 @@ impl Display for FileWriter {
 #[cfg(test)]
 #[derive(Default)]
 pub struct DummyWriter {
-    results: RefCell<HashMap<(PathBuf, Mime), Vec<u8>>>,
+    results: Mutex<HashMap<(PathBuf, Mime), Vec<u8>>>,
 }
 
 #[cfg(test)]
 impl DummyWriter {
     pub fn get<P: AsRef<Path>>(&self, path: P, mime: &Mime) -> Vec<u8> {
         self.results
-            .borrow()
+            .lock()
+            .unwrap()
             .get(&(path.as_ref().to_path_buf(), mime.clone()))
             .unwrap()
             .clone()
     }
+
+    pub fn contains<P: AsRef<Path>>(&self, path: P, mime: &Mime) -> bool {
+        self.results
+            .lock()
+            .unwrap()
+            .contains_key(&(path.as_ref().to_path_buf(), mime.clone()))
+    }
 }
 
 #[cfg(test)]
@@ -590,13 +1145,14 @@ impl ReportWriter for DummyWriter {
         _: EncodingType,
     ) -> Fallible<()> {
         self.results
-            .borrow_mut()
+            .lock()
+            .unwrap()
             .insert((path.as_ref().to_path_buf(), mime.clone()), b);
         Ok(())
     }
 
     fn write_string<P: AsRef<Path>>(&self, path: P, s: Cow<str>, mime: &Mime) -> Fallible<()> {
-        self.results.borrow_mut().insert(
+        self.results.lock().unwrap().insert(
             (path.as_ref().to_path_buf(), mime.clone()),
             s.bytes().collect(),
         );
@@ -608,7 +1164,8 @@ impl ReportWriter for DummyWriter {
         r.read_to_end(&mut buffer)?;
 
         self.results
-            .borrow_mut()
+            .lock()
+            .unwrap()
             .insert((path.as_ref().to_path_buf(), mime.clone()), buffer);
         Ok(())
     }
@@ -624,13 +1181,14 @@ impl Display for DummyWriter {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{Config, CrateConfig};
+    use crate::config::{Config, CrateConfig, NetworkMode};
     use crate::crates::{Crate, GitHubRepo, RegistryCrate};
     use crate::dirs::WORK_DIR;
-    use crate::experiments::{CapLints, Experiment, Mode, Status};
+    use crate::experiments::{CapLints, Experiment, Mode, Status, TestRunner};
     use crate::results::{BrokenReason, DummyDB, FailureReason, TestResult};
     use crate::toolchain::{MAIN_TOOLCHAIN, TEST_TOOLCHAIN};
     use crates_index::Index;
+    use std::collections::BTreeMap;
 
     #[test]
     fn test_crate_to_path_fragment() {
@@ -766,7 +1324,7 @@ mod tests {
 
     #[test]
     fn test_compare() {
-        use crate::results::{FailureReason::*, TestResult::*};
+        use crate::results::{FailureReason::*, Lints, TestResult::*};
 
         macro_rules! test_compare {
             ($cmp:ident, $config:expr, $reg:expr, [$($a:expr, $b:expr => $c:ident;)*]) => {
@@ -789,6 +1347,8 @@ mod tests {
             name: "lazy_static".into(),
             version: "1.0".into(),
         });
+        let lints =
+            |names: &[&str]| Lints(names.iter().map(|name| name.parse().unwrap()).collect());
 
         test_compare!(
             compare,
@@ -815,6 +1375,32 @@ mod tests {
                 BuildFail(OOM), BuildFail(ICE) => Regressed;
                 BuildFail(ICE), BuildFail(ICE) => SameBuildFail;
 
+                // Lints emitted by clippy
+                TestPass, LintWarnings(lints(&["clippy::a"])) => NewWarnings;
+                LintWarnings(lints(&["clippy::a"])), TestPass => FixedWarnings;
+                LintWarnings(lints(&["clippy::a"])), LintWarnings(lints(&["clippy::a"])) => SameTestPass;
+                LintWarnings(lints(&["clippy::a"])), LintWarnings(lints(&["clippy::b"])) => NewWarnings;
+                LintWarnings(lints(&["clippy::a", "clippy::b"])), LintWarnings(lints(&["clippy::b"])) => FixedWarnings;
+                LintWarnings(lints(&["clippy::a"])), BuildFail(Unknown) => Regressed;
+                BuildFail(Unknown), LintWarnings(lints(&["clippy::a"])) => Fixed;
+                TestPass, BuildFail(ClippyICE) => ClippyIce;
+                LintWarnings(lints(&["clippy::a"])), BuildFail(ClippyICE) => ClippyIce;
+                BuildFail(ClippyICE), BuildFail(ClippyICE) => SameBuildFail;
+                BuildFail(ClippyICE), TestPass => Fixed;
+
+                // Failures detected by miri
+                TestPass, TestFail(MiriUB) => MiriUB;
+                TestFail(Unknown), TestFail(MiriUB) => MiriUB;
+                TestFail(MiriUB), TestFail(MiriUB) => SameTestFail;
+                TestFail(MiriUB), TestPass => Fixed;
+                TestPass, TestFail(MiriUnsupported) => MiriUnsupported;
+                TestFail(MiriUnsupported), TestFail(MiriUnsupported) => SameTestFail;
+
+                // Crates using nightly features
+                BuildFail(RequiresNightly), BuildFail(RequiresNightly) => RequiresNightly;
+                BuildFail(RequiresNightly), BuildFail(Unknown) => RequiresNightly;
+                BuildFail(RequiresNightly), TestPass => Fixed;
+
                 // Spurious fixes/regressions
                 BuildFail(OOM), TestFail(Unknown) => SpuriousFixed;
                 BuildFail(Unknown), TestFail(OOM) => SpuriousFixed;
@@ -826,6 +1412,8 @@ mod tests {
                 TestSkipped, BuildFail(OOM) => SpuriousRegressed;
                 TestFail(OOM), BuildFail(Unknown) => SpuriousRegressed;
                 TestFail(Unknown), BuildFail(OOM) => SpuriousRegressed;
+                TestPass, BuildFail(FetchFailed) => SpuriousRegressed;
+                BuildFail(FetchFailed), TestPass => SpuriousFixed;
 
                 // Errors
                 Error, TestPass => Error;
@@ -848,6 +1436,11 @@ mod tests {
                 TestFail(Unknown), Skipped => Skipped;
                 BuildFail(Unknown), Skipped => Skipped;
 
+                // Not applicable
+                NotApplicable, NotApplicable => NotApplicable;
+                TestPass, NotApplicable => NotApplicable;
+                BuildFail(Unknown), NotApplicable => NotApplicable;
+
 
                 // Broken
                 BrokenCrate(BrokenReason::Unknown), TestPass => Broken;
@@ -870,6 +1463,13 @@ mod tests {
                 skip_tests: false,
                 quiet: false,
                 broken: false,
+                targets: Vec::new(),
+                skip_targets: Vec::new(),
+                network: NetworkMode::Disabled,
+                memory_limit: None,
+                timeout: None,
+                disk_limit: None,
+                sandbox_image: None,
             },
         );
         assert_eq!(compare(&config, &reg, None, None), Comparison::Skipped);
@@ -903,8 +1503,22 @@ mod tests {
             status: Status::GeneratingReport,
             assigned_to: None,
             report_url: None,
+            approved_by: None,
             ignore_blacklist: false,
             requirement: None,
+            cargo_config: None,
+            expected_regressions: Vec::new(),
+            env: BTreeMap::new(),
+            deny_lints: Vec::new(),
+            cargo_update: false,
+            failure_threshold: None,
+            reuse_baseline: false,
+            canary: false,
+            test_runner: TestRunner::Cargo,
+            namespace: None,
+            survey: false,
+            parent: None,
+            sandbox_image: None,
         };
 
         let mut db = DummyDB::default();
@@ -957,6 +1571,7 @@ mod tests {
             TEST_TOOLCHAIN.clone(),
             EncodedLog::Plain(b"beta log".to_vec()),
         );
+        db.add_dummy_note(reg.clone(), "maintainer aware, fix in git");
 
         let writer = DummyWriter::default();
         gen(&db, &ex, &[gh, reg], &writer, &config, false).unwrap();
@@ -988,6 +1603,7 @@ mod tests {
             "https://github.com/brson/hello-rs/tree/f00"
         );
         assert_eq!(gh_result.res, Comparison::Regressed);
+        assert_eq!(gh_result.note, None);
         assert_eq!(
             (&gh_result.runs[0]).as_ref().unwrap().res,
             TestResult::TestPass
@@ -1011,6 +1627,10 @@ mod tests {
             "https://crates.io/crates/syn/1.0.0"
         );
         assert_eq!(reg_result.res, Comparison::Regressed);
+        assert_eq!(
+            reg_result.note.as_ref().map(|n| n.as_str()),
+            Some("maintainer aware, fix in git")
+        );
         assert_eq!(
             (&reg_result.runs[0]).as_ref().unwrap().res,
             TestResult::TestPass
@@ -1028,9 +1648,221 @@ mod tests {
             "beta/reg/syn-1.0.0"
         );
 
+        let export: serde_json::Value =
+            serde_json::from_slice(&writer.get("export.json", &mime::APPLICATION_JSON)).unwrap();
+        assert_eq!(export["version"], 1);
+        assert_eq!(export["experiment"]["name"], "foo");
+        assert_eq!(export["toolchains"][1]["name"], "beta");
+        let exported = &export["crates"][1];
+        assert_eq!(exported["name"], "syn-1.0.0");
+        assert_eq!(exported["result"], "regressed");
+        assert_eq!(exported["runs"][0]["result"], "test-pass");
+        assert_eq!(exported["runs"][0]["reason"], serde_json::Value::Null);
+        assert_eq!(exported["runs"][1]["result"], "build-fail");
+        assert_eq!(exported["runs"][1]["reason"], "unknown");
+        assert_eq!(exported["runs"][1]["log"], "beta/reg/syn-1.0.0/log.txt");
+        assert_eq!(exported["note"], "maintainer aware, fix in git");
+
         assert_eq!(
             writer.get("retry-regressed-list.txt", &mime::TEXT_PLAIN_UTF_8),
             b"brson/hello-rs\nsyn\n",
         );
+        assert_eq!(writer.get("fixed-list.txt", &mime::TEXT_PLAIN_UTF_8), b"");
+    }
+
+    #[test]
+    fn test_partial_report_generation() {
+        let config = Config::default();
+
+        let regressed = Crate::Registry(RegistryCrate {
+            name: "syn".into(),
+            version: "1.0.0".into(),
+        });
+        let passing = Crate::Registry(RegistryCrate {
+            name: "quote".into(),
+            version: "1.0.0".into(),
+        });
+
+        let ex = Experiment {
+            status: Status::Running,
+            ..Experiment::dummy("foo")
+        };
+
+        let mut db = DummyDB::default();
+        for (krate, end_result) in &[
+            (&regressed, TestResult::BuildFail(FailureReason::Unknown)),
+            (&passing, TestResult::TestPass),
+        ] {
+            db.add_dummy_result(
+                &ex,
+                (*krate).clone(),
+                MAIN_TOOLCHAIN.clone(),
+                TestResult::TestPass,
+            );
+            db.add_dummy_result(
+                &ex,
+                (*krate).clone(),
+                TEST_TOOLCHAIN.clone(),
+                end_result.clone(),
+            );
+            for tc in &[MAIN_TOOLCHAIN.clone(), TEST_TOOLCHAIN.clone()] {
+                db.add_dummy_log(
+                    &ex,
+                    (*krate).clone(),
+                    tc.clone(),
+                    EncodedLog::Plain(b"log".to_vec()),
+                );
+            }
+        }
+
+        let writer = DummyWriter::default();
+        gen_partial(&db, &ex, &[regressed, passing], &writer, &config).unwrap();
+
+        let result: RawTestResults =
+            serde_json::from_slice(&writer.get("results.json", &mime::APPLICATION_JSON)).unwrap();
+        assert_eq!(result.crates.len(), 2);
+
+        // Only the logs of the crates in the summary are written
+        assert!(writer.contains("beta/reg/syn-1.0.0/log.txt", &mime::TEXT_PLAIN_UTF_8));
+        assert!(!writer.contains("beta/reg/quote-1.0.0/log.txt", &mime::TEXT_PLAIN_UTF_8));
+        assert!(writer.contains("index.html", &mime::TEXT_HTML));
+    }
+
+    #[test]
+    fn test_compare_completed() {
+        let config = Config::default();
+        let ex = Experiment {
+            status: Status::Running,
+            ..Experiment::dummy("foo")
+        };
+
+        let mut db = DummyDB::default();
+        let fail = TestResult::BuildFail(FailureReason::Unknown);
+        db.add_dummy_result(
+            &ex,
+            Crate::Local("build-fail".into()),
+            MAIN_TOOLCHAIN.clone(),
+            TestResult::TestPass,
+        );
+        db.add_dummy_result(
+            &ex,
+            Crate::Local("build-fail".into()),
+            TEST_TOOLCHAIN.clone(),
+            fail.clone(),
+        );
+        // Crates completed only on one toolchain are ignored
+        db.add_dummy_result(
+            &ex,
+            Crate::Local("test-pass".into()),
+            MAIN_TOOLCHAIN.clone(),
+            TestResult::TestPass,
+        );
+
+        assert_eq!(
+            compare_completed(&db, &config, &ex).unwrap(),
+            vec![(Comparison::Regressed, fail)]
+        );
+    }
+
+    #[test]
+    fn test_expected_regressions() {
+        let config = Config::default();
+
+        let expected = Crate::Local("build-fail".into());
+        let unexpected = Crate::Local("test-fail".into());
+
+        let ex = Experiment {
+            status: Status::GeneratingReport,
+            expected_regressions: vec![r"error: the `\w+` lint".to_string()],
+            ..Experiment::dummy("foo")
+        };
+
+        let mut db = DummyDB::default();
+        for (krate, log) in &[
+            (&expected, "error: the `foo` lint was removed"),
+            (&unexpected, "error[E0425]: cannot find value `x`"),
+        ] {
+            db.add_dummy_result(
+                &ex,
+                (*krate).clone(),
+                MAIN_TOOLCHAIN.clone(),
+                TestResult::TestPass,
+            );
+            db.add_dummy_result(
+                &ex,
+                (*krate).clone(),
+                TEST_TOOLCHAIN.clone(),
+                TestResult::BuildFail(FailureReason::Unknown),
+            );
+            db.add_dummy_log(
+                &ex,
+                (*krate).clone(),
+                TEST_TOOLCHAIN.clone(),
+                EncodedLog::Plain(log.as_bytes().to_vec()),
+            );
+        }
+
+        let raw =
+            generate_report(&db, &config, &ex, &[expected.clone(), unexpected.clone()]).unwrap();
+        let result_of = |krate: &Crate| {
+            raw.crates
+                .iter()
+                .find(|c| &c.krate == krate)
+                .map(|c| c.res)
+                .unwrap()
+        };
+        assert_eq!(result_of(&expected), Comparison::ExpectedRegression);
+        assert_eq!(result_of(&unexpected), Comparison::Regressed);
+
+        assert_eq!(
+            compare_crate(&db, &config, &ex, &expected).unwrap(),
+            Comparison::ExpectedRegression
+        );
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let log = "[INFO] [stderr] error[E0308]: mismatched types\n\
+                   [INFO] [stderr] error[E0425]: cannot find value `foo` in this scope\n\
+                   [INFO] [stderr] error[E0308]: mismatched types\n\
+                   [INFO] [stderr] warning[E0000]: not an error\n\
+                   [INFO] [stderr] error: aborting due to 3 previous errors\n";
+        assert_eq!(
+            error_codes(log),
+            vec!["E0308", "E0425"]
+                .into_iter()
+                .map(|code| DiagnosticCode::from(code.into()))
+                .collect::<BTreeSet<_>>()
+        );
+        assert!(error_codes("error: could not compile `foo`").is_empty());
+    }
+    #[test]
+    fn test_compressed_logs() {
+        let mut config = Config::default();
+        config.logs.report_compression = Some(EncodingType::Gzip);
+        let ex = Experiment {
+            status: Status::Running,
+            ..Experiment::dummy("foo")
+        };
+        let krate = Crate::Local("build-pass".into());
+        let log = EncodedLog::from_plain_slice(b"compressed log", EncodingType::Zstd).unwrap();
+        let mut db = DummyDB::default();
+        db.add_dummy_log(&ex, krate.clone(), MAIN_TOOLCHAIN.clone(), log);
+
+        // Logs are converted to the compression of the reports
+        let writer = DummyWriter::default();
+        write_log(&db, &ex, &MAIN_TOOLCHAIN, &krate, &writer, &config).unwrap();
+        let uploaded = writer.get("stable/local/build-pass/log.txt", &mime::TEXT_PLAIN_UTF_8);
+        assert_eq!(
+            EncodedLog::Gzip(uploaded).to_plain().unwrap(),
+            b"compressed log"
+        );
+
+        // Local reports are decompressed
+        let dir = tempfile::tempdir().unwrap();
+        let writer = FileWriter::create(dir.path().into()).unwrap();
+        write_log(&db, &ex, &MAIN_TOOLCHAIN, &krate, &writer, &config).unwrap();
+        let written = fs::read(dir.path().join("stable/local/build-pass/log.txt")).unwrap();
+        assert_eq!(written, b"compressed log");
     }
 }
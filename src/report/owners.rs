@@ -0,0 +1,89 @@
+use crate::crates::Crate;
+use crate::prelude::*;
+use crate::report::{Comparison, CrateResult};
+use crate::utils;
+use std::thread;
+use std::time::Duration;
+
+#[derive(Deserialize)]
+struct OwnersResponse {
+    users: Vec<Owner>,
+}
+
+#[derive(Deserialize)]
+struct Owner {
+    login: String,
+    kind: String,
+}
+
+impl Owner {
+    fn mention(&self) -> Option<String> {
+        match self.kind.as_str() {
+            "user" => Some(format!("@{}", self.login)),
+            // Teams are represented as `github:org:team`
+            "team" => {
+                let mut parts = self.login.splitn(3, ':');
+                match (parts.next(), parts.next(), parts.next()) {
+                    (Some("github"), Some(org), Some(team)) => Some(format!("@{}/{}", org, team)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Get the GitHub handles of the owners of a crate, ready to be mentioned in a comment.
+fn lookup(krate: &Crate) -> Fallible<Vec<String>> {
+    match krate {
+        Crate::Registry(details) => {
+            let url = format!("https://crates.io/api/v1/crates/{}/owners", details.name);
+            let resp: OwnersResponse = utils::http::get_sync(&url)?.json()?;
+            Ok(resp.users.iter().filter_map(Owner::mention).collect())
+        }
+        Crate::GitHub(repo) => Ok(vec![format!("@{}", repo.org)]),
+        Crate::Local(_) | Crate::Git(_) | Crate::Path(_) => Ok(Vec::new()),
+    }
+}
+
+/// Look up the owners of every regressed crate, waiting `interval` between the requests sent to
+/// crates.io to respect its rate limits. Failed lookups are logged and otherwise ignored.
+pub(super) fn fill_owners(crates: &mut [CrateResult], interval: Duration) {
+    let regressed = crates
+        .iter_mut()
+        .filter(|result| result.res == Comparison::Regressed);
+
+    for result in regressed {
+        match lookup(&result.krate) {
+            Ok(owners) => result.owners = owners,
+            Err(err) => utils::report_failure(
+                &err.context(format!("failed to look up the owners of {}", result.krate)),
+            ),
+        }
+
+        if let Crate::Registry(_) = result.krate {
+            thread::sleep(interval);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Owner;
+
+    #[test]
+    fn test_owner_mention() {
+        let owner = |login: &str, kind: &str| Owner {
+            login: login.into(),
+            kind: kind.into(),
+        };
+
+        assert_eq!(owner("foo", "user").mention(), Some("@foo".into()));
+        assert_eq!(
+            owner("github:rust-lang:libs", "team").mention(),
+            Some("@rust-lang/libs".into())
+        );
+        assert_eq!(owner("gitlab:foo:bar", "team").mention(), None);
+        assert_eq!(owner("foo", "robot").mention(), None);
+    }
+}
@@ -1,4 +1,5 @@
 use crate::prelude::*;
+use crate::report::storage::content_encoding;
 use crate::report::ReportWriter;
 use crate::results::EncodingType;
 use mime::Mime;
@@ -60,10 +61,10 @@ impl FromStr for S3Prefix {
 
 pub struct S3Writer {
     prefix: S3Prefix,
-    client: Box<dyn S3>,
+    client: Box<dyn S3 + Send + Sync>,
 }
 
-pub fn get_client_for_bucket(bucket: &str) -> Fallible<Box<dyn S3>> {
+pub fn get_client_for_bucket(bucket: &str) -> Fallible<Box<dyn S3 + Send + Sync>> {
     let make_client = |region| -> Fallible<S3Client> {
         let credentials = DefaultCredentialsProvider::new().unwrap();
         Ok(S3Client::new_with(HttpClient::new()?, credentials, region))
@@ -87,7 +88,7 @@ pub fn get_client_for_bucket(bucket: &str) -> Fallible<Box<dyn S3>> {
 const S3RETRIES: u64 = 4;
 
 impl S3Writer {
-    pub fn create(client: Box<dyn S3>, prefix: S3Prefix) -> Fallible<S3Writer> {
+    pub fn create(client: Box<dyn S3 + Send + Sync>, prefix: S3Prefix) -> Fallible<S3Writer> {
         Ok(S3Writer { prefix, client })
     }
 }
@@ -113,10 +114,7 @@ impl ReportWriter for S3Writer {
                     .to_string_lossy()
                     .into(),
                 content_type: Some(mime.to_string()),
-                content_encoding: match encoding_type {
-                    EncodingType::Plain => None,
-                    EncodingType::Gzip => Some("gzip".into()),
-                },
+                content_encoding: content_encoding(encoding_type).map(Into::into),
                 ..Default::default()
             };
             match self.client.put_object(req).sync() {
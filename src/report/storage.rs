@@ -0,0 +1,126 @@
+//! Services the reports can be uploaded to. All of them implement `ReportWriter`, and
+//! `ReportStorage` dispatches to the one selected by the `reports.storage` option of
+//! `config.toml`, so that the code generating reports doesn't have to know where they end up.
+
+use crate::prelude::*;
+use crate::report::{AzureWriter, GcsWriter, ReportWriter, S3Writer};
+use crate::results::EncodingType;
+use http::StatusCode;
+use mime::Mime;
+use reqwest::Response;
+use std::borrow::Cow;
+use std::fmt::{self, Display};
+use std::io::Read;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+const UPLOAD_RETRIES: u64 = 4;
+
+#[derive(Debug, Fail)]
+pub enum StorageError {
+    #[fail(display = "{} returned status code {}: {}", service, status, body)]
+    BadStatus {
+        service: &'static str,
+        status: StatusCode,
+        body: String,
+    },
+}
+
+pub enum ReportStorage {
+    S3(S3Writer),
+    Gcs(GcsWriter),
+    Azure(AzureWriter),
+}
+
+impl ReportWriter for ReportStorage {
+    fn write_bytes<P: AsRef<Path>>(
+        &self,
+        path: P,
+        b: Vec<u8>,
+        mime: &Mime,
+        encoding_type: EncodingType,
+    ) -> Fallible<()> {
+        match self {
+            ReportStorage::S3(writer) => writer.write_bytes(path, b, mime, encoding_type),
+            ReportStorage::Gcs(writer) => writer.write_bytes(path, b, mime, encoding_type),
+            ReportStorage::Azure(writer) => writer.write_bytes(path, b, mime, encoding_type),
+        }
+    }
+
+    fn write_string<P: AsRef<Path>>(&self, path: P, s: Cow<str>, mime: &Mime) -> Fallible<()> {
+        match self {
+            ReportStorage::S3(writer) => writer.write_string(path, s, mime),
+            ReportStorage::Gcs(writer) => writer.write_string(path, s, mime),
+            ReportStorage::Azure(writer) => writer.write_string(path, s, mime),
+        }
+    }
+
+    fn copy<P: AsRef<Path>, R: Read>(&self, r: &mut R, path: P, mime: &Mime) -> Fallible<()> {
+        match self {
+            ReportStorage::S3(writer) => writer.copy(r, path, mime),
+            ReportStorage::Gcs(writer) => writer.copy(r, path, mime),
+            ReportStorage::Azure(writer) => writer.copy(r, path, mime),
+        }
+    }
+}
+
+impl Display for ReportStorage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReportStorage::S3(writer) => writer.fmt(f),
+            ReportStorage::Gcs(writer) => writer.fmt(f),
+            ReportStorage::Azure(writer) => writer.fmt(f),
+        }
+    }
+}
+
+/// Value of the `Content-Encoding` of the uploaded files.
+pub(super) fn content_encoding(encoding_type: EncodingType) -> Option<&'static str> {
+    match encoding_type {
+        EncodingType::Plain => None,
+        EncodingType::Gzip => Some("gzip"),
+        EncodingType::Zstd => Some("zstd"),
+    }
+}
+
+/// Send an upload request to one of the HTTP based services, retrying it a few times before
+/// giving up, like the uploads to S3.
+pub(super) fn upload_with_retries<F>(
+    service: &'static str,
+    path: &Path,
+    mut send: F,
+) -> Fallible<()>
+where
+    F: FnMut() -> Fallible<Response>,
+{
+    let mut retry = 0;
+    loop {
+        let res = send().and_then(|mut resp| {
+            if resp.status().is_success() {
+                Ok(())
+            } else {
+                Err(StorageError::BadStatus {
+                    service,
+                    status: resp.status(),
+                    body: resp.text().unwrap_or_default(),
+                }
+                .into())
+            }
+        });
+        match res {
+            Err(err) if retry < UPLOAD_RETRIES => {
+                retry += 1;
+                warn!(
+                    "retry ({}/{}) {} upload to {:?}: {}",
+                    retry, UPLOAD_RETRIES, service, path, err
+                );
+                thread::sleep(Duration::from_secs(2 * retry));
+            }
+            res => {
+                res.with_context(|_| format!("{} failure to upload {:?}", service, path))?;
+                return Ok(());
+            }
+        }
+    }
+}
@@ -0,0 +1,248 @@
+//! Reports of survey experiments, which test the ecosystem with a single toolchain. As there is no
+//! baseline to compare with, the report counts the results of the crates instead of listing the
+//! regressions, and is also written as `survey.json` for further analysis.
+
+use crate::experiments::Experiment;
+use crate::prelude::*;
+use crate::report::{archives::Archive, html, RawTestResults, ReportWriter};
+use crate::results::{FailureReason, TestResult};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+const SURVEY_FILE: &str = "survey.json";
+
+/// Number of entries of each ranking included in the report.
+const TOP_ENTRIES: usize = 20;
+
+#[derive(Debug, Serialize)]
+pub struct Survey {
+    pub toolchain: String,
+    /// Number of crates with a result, which are fewer than the crates of the experiment in
+    /// partial reports.
+    pub completed: usize,
+    /// Number of crates with each result, like `build-fail`.
+    pub results: BTreeMap<&'static str, usize>,
+    /// Number of crates failing for each reason, like `build-fail:oom`.
+    pub failure_reasons: BTreeMap<String, usize>,
+    /// Error codes emitted by the most crates failing to compile.
+    pub error_codes: Vec<(String, usize)>,
+    /// Dependencies causing the most crates to fail.
+    pub broken_dependencies: Vec<(String, usize)>,
+}
+
+impl Survey {
+    pub(super) fn new(ex: &Experiment, raw: &RawTestResults) -> Self {
+        let mut survey = Survey {
+            toolchain: ex.toolchains[0].to_string(),
+            completed: 0,
+            results: BTreeMap::new(),
+            failure_reasons: BTreeMap::new(),
+            error_codes: Vec::new(),
+            broken_dependencies: Vec::new(),
+        };
+        let mut error_codes = HashMap::new();
+        let mut dependencies = HashMap::new();
+
+        // Surveys only have results for their first toolchain
+        for res in raw.crates.iter().filter_map(|krate| krate.runs[0].as_ref()) {
+            survey.completed += 1;
+            *survey.results.entry(res.res.kind()).or_insert(0) += 1;
+
+            if let TestResult::BuildFail(reason) | TestResult::TestFail(reason) = &res.res {
+                let name = format!("{}:{}", res.res.kind(), reason_name(reason));
+                *survey.failure_reasons.entry(name).or_insert(0) += 1;
+                match reason {
+                    FailureReason::CompilerError(codes) => {
+                        for code in codes {
+                            *error_codes.entry(code.to_string()).or_insert(0) += 1;
+                        }
+                    }
+                    FailureReason::DependsOn(deps) => {
+                        for dep in deps {
+                            *dependencies.entry(dep.to_string()).or_insert(0) += 1;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        survey.error_codes = top(error_codes);
+        survey.broken_dependencies = top(dependencies);
+        survey
+    }
+
+    fn render_markdown(&self, ex: &Experiment) -> Fallible<String> {
+        let mut rendered = String::new();
+        writeln!(&mut rendered, "# Crater survey for {}\n", ex.name)?;
+        writeln!(
+            &mut rendered,
+            "{} crates tested with `{}`.",
+            self.completed, self.toolchain
+        )?;
+
+        writeln!(&mut rendered, "\n### results")?;
+        for (name, count) in &self.results {
+            writeln!(
+                &mut rendered,
+                "* **{}**: {} crates ({:.1}%)",
+                name,
+                count,
+                *count as f64 * 100.0 / self.completed as f64
+            )?;
+        }
+
+        let reasons = self
+            .failure_reasons
+            .iter()
+            .map(|(name, count)| (name.clone(), *count));
+        let rankings = vec![
+            ("failure reasons", reasons.collect::<Vec<_>>()),
+            ("most common error codes", self.error_codes.clone()),
+            (
+                "dependencies breaking the most crates",
+                self.broken_dependencies.clone(),
+            ),
+        ];
+        for (title, entries) in rankings {
+            if entries.is_empty() {
+                continue;
+            }
+            writeln!(&mut rendered, "\n### {}", title)?;
+            for (name, count) in entries {
+                writeln!(&mut rendered, "* **{}**: {} crates", name, count)?;
+            }
+        }
+
+        Ok(rendered)
+    }
+}
+
+/// Name of the failure reason, without the details of the reasons listing error codes or crates,
+/// which are counted separately.
+fn reason_name(reason: &FailureReason) -> String {
+    match reason {
+        FailureReason::CompilerError(_) => "compiler-error".into(),
+        FailureReason::DependsOn(_) => "depends-on".into(),
+        other => other.to_string(),
+    }
+}
+
+fn top(counts: HashMap<String, usize>) -> Vec<(String, usize)> {
+    let mut counts = counts.into_iter().collect::<Vec<_>>();
+    // Ties are sorted by name to keep the report stable
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts.truncate(TOP_ENTRIES);
+    counts
+}
+
+pub(super) fn write_survey_report<W: ReportWriter>(
+    ex: &Experiment,
+    crates_count: usize,
+    survey: &Survey,
+    available_archives: Vec<Archive>,
+    dest: &W,
+    output_templates: bool,
+) -> Fallible<()> {
+    dest.write_string(
+        SURVEY_FILE,
+        serde_json::to_string(survey)?.into(),
+        &mime::APPLICATION_JSON,
+    )?;
+    info!("writing html files");
+    html::write_survey_report(
+        ex,
+        crates_count,
+        survey,
+        available_archives,
+        dest,
+        output_templates,
+    )?;
+    info!("writing markdown files");
+    dest.write_string(
+        "markdown.md",
+        survey.render_markdown(ex)?.into(),
+        &mime::TEXT_PLAIN,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::actions::{Action, ActionsCtx, CreateExperiment};
+    use crate::config::Config;
+    use crate::crates::{Crate, RegistryCrate};
+    use crate::db::Database;
+    use crate::experiments::Experiment;
+    use crate::report::{gen_without_logs, DummyWriter};
+    use crate::results::{DiagnosticCode, DummyDB, FailureReason, TestResult};
+    use crate::toolchain::MAIN_TOOLCHAIN;
+
+    #[test]
+    fn test_survey_report() {
+        let db = Database::temp().unwrap();
+        let config = Config::default();
+        let ctx = ActionsCtx::new(&db, &config);
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+        CreateExperiment {
+            toolchains: [MAIN_TOOLCHAIN.clone(), MAIN_TOOLCHAIN.clone()],
+            survey: true,
+            ..CreateExperiment::dummy("survey")
+        }
+        .apply(&ctx)
+        .unwrap();
+        let ex = Experiment::get(&db, "survey").unwrap().unwrap();
+
+        let krate = |name: &str| {
+            Crate::Registry(RegistryCrate {
+                name: name.into(),
+                version: "1.0.0".into(),
+            })
+        };
+        let codes = |codes: &[&str]| {
+            FailureReason::CompilerError(
+                codes
+                    .iter()
+                    .map(|code| DiagnosticCode::from(code.to_string()))
+                    .collect(),
+            )
+        };
+        let deps = |name: &str| FailureReason::DependsOn(vec![krate(name)].into_iter().collect());
+
+        let mut results = DummyDB::default();
+        for (name, res) in vec![
+            ("pass", TestResult::TestPass),
+            ("e0308", TestResult::BuildFail(codes(&["E0308"]))),
+            ("both", TestResult::BuildFail(codes(&["E0308", "E0599"]))),
+            ("dep", TestResult::BuildFail(deps("both"))),
+            ("oom", TestResult::TestFail(FailureReason::OOM)),
+        ] {
+            results.add_dummy_result(&ex, krate(name), MAIN_TOOLCHAIN.clone(), res);
+        }
+        let crates = ["pass", "e0308", "both", "dep", "oom", "missing"]
+            .iter()
+            .map(|name| krate(name))
+            .collect::<Vec<_>>();
+
+        let writer = DummyWriter::default();
+        gen_without_logs(&results, &ex, &crates, &writer, &config, false).unwrap();
+
+        let survey: serde_json::Value =
+            serde_json::from_slice(&writer.get("survey.json", &mime::APPLICATION_JSON)).unwrap();
+        assert_eq!(survey["completed"], 5);
+        assert_eq!(survey["results"]["build-fail"], 3);
+        assert_eq!(survey["results"]["test-pass"], 1);
+        assert_eq!(survey["failure_reasons"]["build-fail:compiler-error"], 2);
+        assert_eq!(survey["failure_reasons"]["test-fail:oom"], 1);
+        assert_eq!(
+            survey["error_codes"],
+            serde_json::json!([["E0308", 2], ["E0599", 1]])
+        );
+        assert_eq!(survey["broken_dependencies"][0][0], "both-1.0.0");
+
+        let markdown = writer.get("markdown.md", &mime::TEXT_PLAIN);
+        let markdown = String::from_utf8(markdown).unwrap();
+        assert!(markdown.contains("* **build-fail**: 3 crates (60.0%)"));
+        assert!(markdown.contains("### most common error codes\n* **E0308**: 2 crates"));
+    }
+}
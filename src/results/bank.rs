@@ -0,0 +1,267 @@
+use crate::crates::Crate;
+use crate::db::{Database, QueryUtils};
+use crate::experiments::Experiment;
+use crate::prelude::*;
+use crate::report::heatmap::stable_release;
+use crate::results::TestResult;
+use crate::toolchain::Toolchain;
+use chrono::{NaiveDate, Utc};
+
+/// Whether the toolchain always refers to the same compiler: stable releases, dated nightlies or
+/// betas and the CI builds of a commit do, while the channels themselves change over time.
+fn is_pinned(toolchain: &str) -> bool {
+    if stable_release(toolchain).is_some() {
+        return true;
+    }
+    if let Some(idx) = toolchain.find('#') {
+        let sha = &toolchain[idx + 1..];
+        return match &toolchain[..idx] {
+            "master" | "try" | "master-alt" | "try-alt" => {
+                sha.len() == 40 && sha.chars().all(|c| c.is_ascii_hexdigit())
+            }
+            _ => false,
+        };
+    }
+    let mut parts = toolchain.splitn(2, '-');
+    match (parts.next(), parts.next()) {
+        (Some("nightly"), Some(date)) | (Some("beta"), Some(date)) => {
+            NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok()
+        }
+        _ => false,
+    }
+}
+
+/// Whether the results of a toolchain in an experiment are comparable with the results of other
+/// experiments on the same toolchain. Only pinned toolchains are banked, and only if the
+/// experiment doesn't change how the crates are built.
+fn is_bankable(ex: &Experiment, toolchain: &Toolchain) -> bool {
+    // Flags and patches are part of the name of the toolchain
+    is_pinned(&toolchain.to_string())
+        && ex.env.is_empty()
+        && ex.cargo_config.is_none()
+        && ex.deny_lints.is_empty()
+        && !ex.cargo_update
+}
+
+/// Whether a result says something about the crate itself rather than about the machine or the
+/// experiment that produced it.
+pub(crate) fn is_reusable(res: &TestResult) -> bool {
+    match res {
+        TestResult::BuildFail(reason) | TestResult::TestFail(reason) => !reason.is_spurious(),
+        TestResult::TestPass
+        | TestResult::TestSkipped
+        | TestResult::LintWarnings(_)
+        | TestResult::BrokenCrate(_) => true,
+        TestResult::Error | TestResult::Skipped | TestResult::NotApplicable => false,
+    }
+}
+
+/// Store the results of the bankable toolchains of a completed experiment in the baseline bank,
+/// replacing the results banked by older experiments. Results are banked along with the digest of
+/// the sandbox image they were built in, as a different image can change the outcome of a build.
+/// Returns the number of banked results.
+pub fn record(db: &Database, ex: &Experiment) -> Fallible<usize> {
+    let mut banked = 0;
+    for toolchain in ex.toolchains.iter().filter(|tc| is_bankable(ex, tc)) {
+        let rows = db.query(
+            "SELECT crate, result, log, encoding, sandbox_image FROM results \
+             WHERE experiment = ?1 AND toolchain = ?2;",
+            &[&ex.name, &toolchain.to_string()],
+            |row| -> (String, String, Vec<u8>, String, Option<String>) {
+                (
+                    row.get("crate"),
+                    row.get("result"),
+                    row.get("log"),
+                    row.get("encoding"),
+                    row.get("sandbox_image"),
+                )
+            },
+        )?;
+
+        for (krate, res, log, encoding, sandbox_image) in rows {
+            // Git repositories and local crates can change without their ID changing
+            match krate.parse() {
+                Ok(Crate::Registry(_)) => {}
+                _ => continue,
+            }
+            match res.parse() {
+                Ok(parsed) if is_reusable(&parsed) => {}
+                _ => continue,
+            }
+
+            db.execute(
+                "INSERT INTO baseline_bank \
+                 (toolchain, mode, cap_lints, crate, sandbox_image, result, log, encoding, \
+                 experiment, banked_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10);",
+                &[
+                    &toolchain.to_string(),
+                    &ex.mode.to_str(),
+                    &ex.cap_lints.to_str(),
+                    &krate,
+                    &sandbox_image.unwrap_or_default(),
+                    &res,
+                    &log,
+                    &encoding,
+                    &ex.name,
+                    &Utc::now(),
+                ],
+            )?;
+            banked += 1;
+        }
+    }
+
+    Ok(banked)
+}
+
+/// Copy the banked result of a crate on the start toolchain of an experiment into the results of
+/// the experiment, returning it. Nothing is copied if the experiment didn't opt into reusing the
+/// banked baseline, or if no result is banked for the same mode, lints cap and sandbox image. The
+/// results built in an unknown sandbox image are only reused by builders not knowing theirs.
+pub fn reuse(
+    db: &Database,
+    ex: &Experiment,
+    krate: &Crate,
+    sandbox_image: Option<&str>,
+) -> Fallible<Option<TestResult>> {
+    let toolchain = &ex.toolchains[0];
+    if !ex.reuse_baseline || !is_bankable(ex, toolchain) {
+        return Ok(None);
+    }
+
+    let banked = db.get_row(
+        "SELECT result, log, encoding FROM baseline_bank \
+         WHERE toolchain = ?1 AND mode = ?2 AND cap_lints = ?3 AND crate = ?4 \
+         AND sandbox_image = ?5;",
+        &[
+            &toolchain.to_string(),
+            &ex.mode.to_str(),
+            &ex.cap_lints.to_str(),
+            &krate.id(),
+            &sandbox_image.unwrap_or(""),
+        ],
+        |row| -> (String, Vec<u8>, String) {
+            (row.get("result"), row.get("log"), row.get("encoding"))
+        },
+    )?;
+    let (res, log, encoding) = match banked {
+        Some(banked) => banked,
+        None => return Ok(None),
+    };
+
+    db.execute(
+        "INSERT INTO results \
+         (experiment, crate, toolchain, result, log, encoding, sandbox_image) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7);",
+        &[
+            &ex.name,
+            &krate.id(),
+            &toolchain.to_string(),
+            &res,
+            &log,
+            &encoding,
+            &sandbox_image,
+        ],
+    )?;
+    Ok(Some(res.parse()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_pinned, record, reuse};
+    use crate::actions::{Action, ActionsCtx, CreateExperiment};
+    use crate::config::Config;
+    use crate::crates::{Crate, RegistryCrate};
+    use crate::db::Database;
+    use crate::experiments::Experiment;
+    use crate::results::{
+        DatabaseDB, EncodingType, FailureReason, ReadResults, TestResult, WriteResults,
+    };
+    use crate::toolchain::Toolchain;
+
+    #[test]
+    fn test_bank() {
+        let db = Database::temp().unwrap();
+        let config = Config::default();
+        let ctx = ActionsCtx::new(&db, &config);
+        let image = "sha256:0123456789abcdef";
+        let results = DatabaseDB::new(&db).with_sandbox_image(Some(image.into()));
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+
+        let stable: Toolchain = "1.70.0".parse().unwrap();
+        let create = |name: &str, reuse_baseline: bool| {
+            let mut create = CreateExperiment::dummy(name);
+            create.toolchains = [stable.clone(), "beta".parse().unwrap()];
+            create.reuse_baseline = reuse_baseline;
+            create.apply(&ctx).unwrap();
+            Experiment::get(&db, name).unwrap().unwrap()
+        };
+        let reg = |name: &str| {
+            Crate::Registry(RegistryCrate {
+                name: name.into(),
+                version: "1.0.0".into(),
+            })
+        };
+
+        let old = create("old", false);
+        for (krate, res) in &[
+            (reg("pass"), TestResult::TestPass),
+            (reg("oom"), TestResult::BuildFail(FailureReason::OOM)),
+            (Crate::Local("local".into()), TestResult::TestPass),
+        ] {
+            results
+                .record_result(
+                    &old,
+                    &stable,
+                    krate,
+                    None,
+                    &config,
+                    EncodingType::Plain,
+                    || Ok(res.clone()),
+                )
+                .unwrap();
+        }
+        // Only the non-spurious results of registry crates are banked
+        assert_eq!(record(&db, &old).unwrap(), 1);
+
+        let new = create("new", true);
+        assert_eq!(
+            reuse(&db, &new, &reg("pass"), Some(image)).unwrap(),
+            Some(TestResult::TestPass)
+        );
+        assert_eq!(
+            results.get_result(&new, &stable, &reg("pass")).unwrap(),
+            Some(TestResult::TestPass)
+        );
+        assert!(results
+            .load_log(&new, &stable, &reg("pass"))
+            .unwrap()
+            .is_some());
+        assert_eq!(reuse(&db, &new, &reg("oom"), Some(image)).unwrap(), None);
+
+        // Results built in another sandbox image are not comparable
+        let rebuilt = create("rebuilt", true);
+        assert_eq!(
+            reuse(&db, &rebuilt, &reg("pass"), Some("sha256:fedcba9876543210")).unwrap(),
+            None
+        );
+        assert_eq!(reuse(&db, &rebuilt, &reg("pass"), None).unwrap(), None);
+
+        // Experiments have to opt into reusing the banked results
+        let other = create("other", false);
+        assert_eq!(reuse(&db, &other, &reg("pass"), Some(image)).unwrap(), None);
+    }
+
+    #[test]
+    fn test_is_pinned() {
+        assert!(is_pinned("1.70.0"));
+        assert!(is_pinned("nightly-2023-06-01"));
+        assert!(is_pinned("beta-2023-06-01"));
+        assert!(!is_pinned("stable"));
+        assert!(!is_pinned("nightly"));
+        assert!(!is_pinned("nightly-2023-06-01+rustflags=-Zfoo"));
+        assert!(is_pinned("master#0123456789abcdef0123456789abcdef01234567"));
+        assert!(!is_pinned(
+            "master#0123456789abcdef0123456789abcdef01234567+rustflags=-Zfoo"
+        ));
+    }
+}
@@ -1,13 +1,22 @@
 use crate::config::Config;
 use crate::crates::Crate;
-use crate::db::{Database, QueryUtils};
-use crate::experiments::{Experiment, Status};
+use crate::db::{Database, QueryUtils, ToSql};
+use crate::experiments::Experiment;
 use crate::prelude::*;
 use crate::results::{
-    DeleteResults, EncodedLog, EncodingType, ReadResults, TestResult, WriteResults,
+    durations, log_output, DeleteResults, DependencyGraph, EncodedLog, EncodingType, ReadResults,
+    RecordSandboxImage, Redactor, SandboxCounters, TestResult, WriteResults,
 };
 use crate::toolchain::Toolchain;
+use crate::utils::hex::sha1_hex;
+use chrono::Utc;
+use rand::{thread_rng, Rng};
 use rustwide::logging::{self, LogStorage};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+//SQLite cannot handle queries with more than 999 variables
+static SQL_VARIABLE_LIMIT: usize = 500;
 
 #[derive(Deserialize)]
 pub struct TaskResult {
@@ -15,56 +24,191 @@ pub struct TaskResult {
     pub krate: Crate,
     pub toolchain: Toolchain,
     pub result: TestResult,
+    /// Log of the build encoded in base64, empty when it was sent as a chunked upload instead.
+    #[serde(default)]
     pub log: String,
+    /// Compression applied to the log by the agent, if any.
+    #[serde(default)]
+    pub encoding: Option<EncodingType>,
+    /// Chunked upload containing the log, used by the agents for the biggest logs.
+    #[serde(default)]
+    pub upload: Option<String>,
+    /// Hex-encoded SHA-1 of the log as sent by the agent, to detect corrupted uploads.
+    #[serde(default)]
+    pub checksum: Option<String>,
+    /// Memory limit of the sandbox, sent only when the build ran out of memory.
+    #[serde(default)]
+    pub memory_limit: Option<u64>,
+    /// Operations denied by the sandbox, sent only when the log reported some.
+    #[serde(default)]
+    pub sandbox_counters: Option<SandboxCounters>,
+    /// Digest of the sandbox image the crate was built in, if the agent knows it.
+    #[serde(default)]
+    pub sandbox_image: Option<String>,
+    /// Dependency graph resolved by cargo, if the agent managed to query it.
+    #[serde(default)]
+    pub dependency_graph: Option<DependencyGraph>,
+    /// Seconds the build took, sent by the agents measuring it.
+    #[serde(default)]
+    pub duration: Option<u64>,
 }
 
 #[derive(Deserialize)]
 pub struct ProgressData {
     pub results: Vec<TaskResult>,
     pub version: Option<(Crate, Crate)>,
+    /// Key generated by the agent for each submission, and sent again when the submission is
+    /// retried after a network error.
+    #[serde(default, rename = "idempotency-key")]
+    pub idempotency_key: Option<String>,
 }
 
 pub struct DatabaseDB<'a> {
     db: &'a Database,
+    sandbox_image: Option<String>,
 }
 
 impl<'a> DatabaseDB<'a> {
     pub fn new(db: &'a Database) -> Self {
-        DatabaseDB { db }
+        DatabaseDB {
+            db,
+            sandbox_image: None,
+        }
+    }
+
+    /// Set the digest of the sandbox image the results recorded through this instance are built
+    /// in, which is also the image the reused baseline results must have been built in.
+    pub fn with_sandbox_image(mut self, sandbox_image: Option<String>) -> Self {
+        self.sandbox_image = sandbox_image;
+        self
     }
 
+    /// Store the results of a submission, returning `false` without storing anything if a
+    /// submission with the same idempotency key was already stored for the experiment.
     pub fn store(
         &self,
         ex: &Experiment,
         data: &ProgressData,
+        config: &Config,
         encoding_type: EncodingType,
-    ) -> Fallible<()> {
+    ) -> Fallible<bool> {
+        if let Some(key) = &data.idempotency_key {
+            if self.db.exists(
+                "SELECT rowid FROM result_submissions \
+                 WHERE experiment = ?1 AND idempotency_key = ?2;",
+                &[&ex.name, key],
+            )? {
+                return Ok(false);
+            }
+        }
+
         for result in &data.results {
-            self.store_result(
+            let log = self.received_log(ex, result)?.reencode(encoding_type)?;
+            self.insert_into_results(
                 ex,
                 &result.krate,
                 &result.toolchain,
                 &result.result,
-                &base64::decode(&result.log).with_context(|_| "invalid base64 log provided")?,
-                encoding_type,
+                log,
+                result.memory_limit,
+                result.sandbox_counters.as_ref(),
+                result.sandbox_image.as_ref().map(|image| image.as_str()),
             )?;
+            if let Some(graph) = &result.dependency_graph {
+                self.record_dependency_graph(ex, &result.toolchain, &result.krate, graph)?;
+            }
+            if let Some(duration) = result.duration {
+                let duration = Duration::from_secs(duration);
+                durations::record(self.db, config, &result.krate, &result.result, duration)?;
+            }
+            if let Some(upload) = &result.upload {
+                self.db.execute(
+                    "DELETE FROM log_uploads WHERE experiment = ?1 AND upload_id = ?2;",
+                    &[&ex.name, upload],
+                )?;
+            }
+            self.trim_success_logs(ex, &result.krate, config)?;
 
             if let Some((old, new)) = &data.version {
                 self.update_crate_version(ex, old, new)?;
             }
 
-            self.mark_crate_as_completed(ex, &result.krate)?;
+            let toolchains = ex.tested_toolchains().len() as u32;
+            ex.queue().ack(self.db, &result.krate, toolchains)?;
         }
 
-        Ok(())
+        if let Some(key) = &data.idempotency_key {
+            self.db.execute(
+                "INSERT INTO result_submissions (experiment, idempotency_key, created_at) \
+                 VALUES (?1, ?2, ?3);",
+                &[&ex.name, key, &Utc::now()],
+            )?;
+        }
+
+        Ok(true)
     }
 
-    fn mark_crate_as_completed(&self, ex: &Experiment, krate: &Crate) -> Fallible<usize> {
+    /// Decode the log of a result, either included in the submission or uploaded in chunks
+    /// before it, and check it wasn't corrupted along the way.
+    fn received_log(&self, ex: &Experiment, result: &TaskResult) -> Fallible<EncodedLog> {
+        let data = if let Some(upload) = &result.upload {
+            self.db
+                .query(
+                    "SELECT data FROM log_uploads WHERE experiment = ?1 AND upload_id = ?2 \
+                     ORDER BY position;",
+                    &[&ex.name, upload],
+                    |row| row.get::<_, Vec<u8>>("data"),
+                )?
+                .concat()
+        } else {
+            base64::decode(&result.log).with_context(|_| "invalid base64 log provided")?
+        };
+
+        if let Some(checksum) = &result.checksum {
+            if !checksum.eq_ignore_ascii_case(&sha1_hex(&data)) {
+                bail!(
+                    "corrupted log received for {} on {}",
+                    result.krate,
+                    result.toolchain
+                );
+            }
+        }
+
+        Ok(EncodedLog::from_encoded(
+            data,
+            result.encoding.unwrap_or(EncodingType::Plain),
+        ))
+    }
+
+    /// Append a chunk to a log uploaded in multiple requests, returning how many bytes of the
+    /// upload were received so far. Chunks not starting where the previous one ended are
+    /// ignored, so that agents can resume an interrupted upload from the returned offset.
+    pub fn append_log_chunk(
+        &self,
+        ex: &Experiment,
+        upload: &str,
+        offset: u64,
+        chunk: &[u8],
+    ) -> Fallible<u64> {
+        let received = self
+            .db
+            .get_row(
+                "SELECT COALESCE(SUM(LENGTH(data)), 0) AS received FROM log_uploads \
+                 WHERE experiment = ?1 AND upload_id = ?2;",
+                &[&ex.name, upload],
+                |row| row.get::<_, i64>("received"),
+            )?
+            .unwrap_or(0) as u64;
+        if offset != received || chunk.is_empty() {
+            return Ok(received);
+        }
+
         self.db.execute(
-            "UPDATE experiment_crates SET status = ?1 WHERE experiment = ?2 AND crate = ?3 \
-             AND ( (SELECT COUNT(*) FROM results WHERE experiment = ?2 AND crate = ?3) > 1 )",
-            &[&Status::Completed.to_string(), &ex.name, &krate.id()],
-        )
+            "INSERT OR IGNORE INTO log_uploads \
+             (experiment, upload_id, position, data, created_at) VALUES (?1, ?2, ?3, ?4, ?5);",
+            &[&ex.name, &upload, &(offset as i64), &chunk, &Utc::now()],
+        )?;
+        Ok(received + chunk.len() as u64)
     }
 
     fn store_result(
@@ -74,10 +218,22 @@ impl<'a> DatabaseDB<'a> {
         toolchain: &Toolchain,
         res: &TestResult,
         log: &[u8],
+        memory_limit: Option<u64>,
+        sandbox_counters: Option<&SandboxCounters>,
+        sandbox_image: Option<&str>,
         desired_encoding_type: EncodingType,
     ) -> Fallible<()> {
         let encoded_log = EncodedLog::from_plain_slice(log, desired_encoding_type)?;
-        self.insert_into_results(ex, krate, toolchain, res, encoded_log)?;
+        self.insert_into_results(
+            ex,
+            krate,
+            toolchain,
+            res,
+            encoded_log,
+            memory_limit,
+            sandbox_counters,
+            sandbox_image,
+        )?;
         Ok(())
     }
 
@@ -88,10 +244,15 @@ impl<'a> DatabaseDB<'a> {
         toolchain: &Toolchain,
         res: &TestResult,
         log: EncodedLog,
+        memory_limit: Option<u64>,
+        sandbox_counters: Option<&SandboxCounters>,
+        sandbox_image: Option<&str>,
     ) -> Fallible<usize> {
+        let sandbox_counters = sandbox_counters.map(serde_json::to_string).transpose()?;
         self.db.execute(
-            "INSERT INTO results (experiment, crate, toolchain, result, log, encoding) \
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6);",
+            "INSERT INTO results \
+             (experiment, crate, toolchain, result, log, encoding, memory_limit, \
+             sandbox_counters, sandbox_image) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9);",
             &[
                 &ex.name,
                 &krate.id(),
@@ -99,9 +260,97 @@ impl<'a> DatabaseDB<'a> {
                 &res.to_string(),
                 &log.as_slice(),
                 &log.get_encoding_type().to_str(),
+                &memory_limit.map(|limit| limit as i64),
+                &sandbox_counters,
+                &sandbox_image,
             ],
         )
     }
+
+    /// Trim the logs of a crate once it succeeded on all the toolchains, as those logs are rarely
+    /// consulted. The logs of a random sample of the crates are kept intact, to allow auditing.
+    fn trim_success_logs(&self, ex: &Experiment, krate: &Crate, config: &Config) -> Fallible<()> {
+        let max_size = match config.logs.success_max_size {
+            Some(max_size) => max_size.to_bytes(),
+            None => return Ok(()),
+        };
+
+        for tc in &ex.toolchains {
+            match self.load_test_result(ex, tc, krate)? {
+                Some(TestResult::TestPass) | Some(TestResult::TestSkipped) => {}
+                _ => return Ok(()),
+            }
+        }
+        if thread_rng().gen::<f64>() < config.logs.success_sample_rate {
+            return Ok(());
+        }
+
+        for tc in &ex.toolchains {
+            let log = match self.load_log(ex, tc, krate)? {
+                Some(log) => log,
+                None => continue,
+            };
+            let plain = log.to_plain()?;
+            if plain.len() <= max_size {
+                continue;
+            }
+
+            let trimmed =
+                EncodedLog::from_plain_slice(&trim_log(&plain, max_size), log.get_encoding_type())?;
+            // The trimmed log replaces the one already streamed to the report bucket
+            self.db.execute(
+                "UPDATE results SET log = ?1, log_streamed = 0 \
+                 WHERE experiment = ?2 AND toolchain = ?3 AND crate = ?4;",
+                &[&trimmed.as_slice(), &ex.name, &tc.to_string(), &krate.id()],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Get the results whose log wasn't streamed to the report bucket yet. Storing a result again
+    /// resets its streamed state, as the inserted row replaces the existing one.
+    pub fn unstreamed_logs(&self, ex: &Experiment) -> Fallible<Vec<(Crate, Toolchain)>> {
+        let rows = self.db.query(
+            "SELECT crate, toolchain FROM results WHERE experiment = ?1 AND log_streamed = 0;",
+            &[&ex.name],
+            |row| -> (String, String) { (row.get("crate"), row.get("toolchain")) },
+        )?;
+
+        rows.into_iter()
+            .map(|(krate, toolchain)| Ok((krate.parse()?, toolchain.parse()?)))
+            .collect()
+    }
+
+    pub fn mark_log_streamed(
+        &self,
+        ex: &Experiment,
+        toolchain: &Toolchain,
+        krate: &Crate,
+    ) -> Fallible<()> {
+        self.db.execute(
+            "UPDATE results SET log_streamed = 1 \
+             WHERE experiment = ?1 AND toolchain = ?2 AND crate = ?3;",
+            &[&ex.name, &toolchain.to_string(), &krate.id()],
+        )?;
+        Ok(())
+    }
+}
+
+/// Keep only the end of a log, which contains the summary of the build and the tests, starting
+/// from the first full line.
+fn trim_log(log: &[u8], max_size: usize) -> Vec<u8> {
+    let mut start = log.len() - max_size;
+    if let Some(newline) = log[start..].iter().position(|&b| b == b'\n') {
+        start += newline + 1;
+    }
+
+    let mut trimmed = format!(
+        "[crater] log trimmed to its last {} bytes, as the crate succeeded on all toolchains\n",
+        log.len() - start
+    )
+    .into_bytes();
+    trimmed.extend_from_slice(&log[start..]);
+    trimmed
 }
 
 impl<'a> ReadResults for DatabaseDB<'a> {
@@ -119,12 +368,7 @@ impl<'a> ReadResults for DatabaseDB<'a> {
             |row| {
                 let log: Vec<u8> = row.get("log");
                 let encoding: String = row.get("encoding");
-                let encoding = encoding.parse().unwrap();
-
-                match encoding {
-                    EncodingType::Plain => EncodedLog::Plain(log),
-                    EncodingType::Gzip => EncodedLog::Gzip(log),
-                }
+                EncodedLog::from_encoded(log, encoding.parse().unwrap())
             },
         )
     }
@@ -152,6 +396,103 @@ impl<'a> ReadResults for DatabaseDB<'a> {
             Ok(None)
         }
     }
+
+    fn load_crate_note(&self, krate: &Crate) -> Fallible<Option<String>> {
+        crate::triage::load_note(self.db, krate)
+    }
+
+    fn load_previous_experiment(&self, ex: &Experiment) -> Fallible<Option<Experiment>> {
+        ex.previous(self.db)
+    }
+
+    fn load_all_results(
+        &self,
+        ex: &Experiment,
+        toolchain: &Toolchain,
+    ) -> Fallible<Vec<(Crate, TestResult)>> {
+        let rows = self.db.query(
+            "SELECT crate, result FROM results WHERE experiment = ?1 AND toolchain = ?2;",
+            &[&ex.name, &toolchain.to_string()],
+            |row| -> (String, String) { (row.get("crate"), row.get("result")) },
+        )?;
+
+        rows.into_iter()
+            .map(|(krate, result)| Ok((krate.parse()?, result.parse()?)))
+            .collect()
+    }
+
+    fn load_sandbox_counters(
+        &self,
+        ex: &Experiment,
+        toolchain: &Toolchain,
+    ) -> Fallible<HashMap<Crate, SandboxCounters>> {
+        let rows = self.db.query(
+            "SELECT crate, sandbox_counters FROM results \
+             WHERE experiment = ?1 AND toolchain = ?2 AND sandbox_counters IS NOT NULL;",
+            &[&ex.name, &toolchain.to_string()],
+            |row| -> (String, String) { (row.get("crate"), row.get("sandbox_counters")) },
+        )?;
+
+        rows.into_iter()
+            .map(|(krate, counters)| Ok((krate.parse()?, serde_json::from_str(&counters)?)))
+            .collect()
+    }
+
+    fn load_results_chunk(
+        &self,
+        ex: &Experiment,
+        toolchain: &Toolchain,
+        crates: &[Crate],
+    ) -> Fallible<HashMap<Crate, (TestResult, Option<SandboxCounters>)>> {
+        let toolchain = toolchain.to_string();
+        let params_header: &[&dyn ToSql] = &[&ex.name, &toolchain];
+        let ids = crates.iter().map(|krate| krate.id()).collect::<Vec<_>>();
+
+        let mut results = HashMap::new();
+        for ids in ids.chunks(SQL_VARIABLE_LIMIT) {
+            let params = ids.iter().map(|id| id as &dyn ToSql).collect::<Vec<_>>();
+            let query = format!(
+                "SELECT crate, result, sandbox_counters FROM results \
+                 WHERE experiment = ?1 AND toolchain = ?2 AND crate IN ({}?);",
+                "?,".repeat(params.len() - 1),
+            );
+            let rows = self.db.query(
+                &query,
+                &[params_header, &params[..]].concat(),
+                |row| -> (String, String, Option<String>) {
+                    (
+                        row.get("crate"),
+                        row.get("result"),
+                        row.get("sandbox_counters"),
+                    )
+                },
+            )?;
+            for (krate, result, counters) in rows {
+                let counters = match counters {
+                    Some(counters) => Some(serde_json::from_str(&counters)?),
+                    None => None,
+                };
+                results.insert(krate.parse()?, (result.parse()?, counters));
+            }
+        }
+        Ok(results)
+    }
+
+    fn load_dependency_graphs(
+        &self,
+        ex: &Experiment,
+        toolchain: &Toolchain,
+    ) -> Fallible<HashMap<Crate, DependencyGraph>> {
+        let rows = self.db.query(
+            "SELECT crate, graph FROM dependency_graphs WHERE experiment = ?1 AND toolchain = ?2;",
+            &[&ex.name, &toolchain.to_string()],
+            |row| -> (String, String) { (row.get("crate"), row.get("graph")) },
+        )?;
+
+        rows.into_iter()
+            .map(|(krate, graph)| Ok((krate.parse()?, serde_json::from_str(&graph)?)))
+            .collect()
+    }
 }
 
 impl<'a> WriteResults for DatabaseDB<'a> {
@@ -161,7 +502,12 @@ impl<'a> WriteResults for DatabaseDB<'a> {
         toolchain: &Toolchain,
         krate: &Crate,
     ) -> Fallible<Option<TestResult>> {
-        self.load_test_result(ex, toolchain, krate)
+        let res = self.load_test_result(ex, toolchain, krate)?;
+        if res.is_none() && *toolchain == ex.toolchains[0] {
+            let sandbox_image = self.sandbox_image.as_ref().map(|image| image.as_str());
+            return crate::results::bank::reuse(self.db, ex, krate, sandbox_image);
+        }
+        Ok(res)
     }
 
     fn update_crate_version(&self, ex: &Experiment, old: &Crate, new: &Crate) -> Fallible<()> {
@@ -172,6 +518,26 @@ impl<'a> WriteResults for DatabaseDB<'a> {
         Ok(())
     }
 
+    fn record_dependency_graph(
+        &self,
+        ex: &Experiment,
+        toolchain: &Toolchain,
+        krate: &Crate,
+        graph: &DependencyGraph,
+    ) -> Fallible<()> {
+        self.db.execute(
+            "INSERT INTO dependency_graphs (experiment, crate, toolchain, graph) \
+             VALUES (?1, ?2, ?3, ?4);",
+            &[
+                &ex.name,
+                &krate.id(),
+                &toolchain.to_string(),
+                &serde_json::to_string(graph)?,
+            ],
+        )?;
+        Ok(())
+    }
+
     fn record_result<F>(
         &self,
         ex: &Experiment,
@@ -186,24 +552,54 @@ impl<'a> WriteResults for DatabaseDB<'a> {
         F: FnOnce() -> Fallible<TestResult>,
     {
         let storage = existing_logs.unwrap_or_else(|| LogStorage::from(config));
+        let start = Instant::now();
         let result = logging::capture(&storage, f)?;
-        let output = storage.to_string();
+        let duration = start.elapsed();
+        let output = log_output(&storage, config);
+        let output = Redactor::for_config(config)?.redact(&output).into_owned();
         self.store_result(
             ex,
             krate,
             toolchain,
             &result,
             output.as_bytes(),
-            encoding_type,
+            result.memory_limit(config, krate),
+            SandboxCounters::from_log(&output).as_ref(),
+            self.sandbox_image.as_ref().map(|image| image.as_str()),
+            config.logs.compression.unwrap_or(encoding_type),
         )?;
+        durations::record(self.db, config, krate, &result, duration)?;
+        self.trim_success_logs(ex, krate, config)?;
         Ok(result)
     }
+
+    fn learned_timeout(
+        &self,
+        _ex: &Experiment,
+        krate: &Crate,
+        config: &Config,
+    ) -> Fallible<Option<Duration>> {
+        durations::learned_timeout(self.db, config, krate)
+    }
+}
+
+impl<'a> RecordSandboxImage for DatabaseDB<'a> {
+    fn for_sandbox_image(&self, sandbox_image: Option<String>) -> Self {
+        DatabaseDB {
+            db: self.db,
+            sandbox_image,
+        }
+    }
 }
 
 impl<'a> DeleteResults for DatabaseDB<'a> {
     fn delete_all_results(&self, ex: &Experiment) -> Fallible<()> {
         self.db
             .execute("DELETE FROM results WHERE experiment = ?1;", &[&ex.name])?;
+        self.db.execute(
+            "DELETE FROM dependency_graphs WHERE experiment = ?1;",
+            &[&ex.name],
+        )?;
         Ok(())
     }
 
@@ -212,24 +608,31 @@ impl<'a> DeleteResults for DatabaseDB<'a> {
             "DELETE FROM results WHERE experiment = ?1 AND toolchain = ?2 AND crate = ?3;",
             &[&ex.name, &tc.to_string(), &krate.id()],
         )?;
+        self.db.execute(
+            "DELETE FROM dependency_graphs \
+             WHERE experiment = ?1 AND toolchain = ?2 AND crate = ?3;",
+            &[&ex.name, &tc.to_string(), &krate.id()],
+        )?;
         Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{DatabaseDB, ProgressData, TaskResult};
+    use super::{trim_log, DatabaseDB, ProgressData, TaskResult};
     use crate::actions::{Action, ActionsCtx, CreateExperiment};
     use crate::config::Config;
     use crate::crates::{Crate, RegistryCrate};
-    use crate::db::Database;
+    use crate::db::{Database, QueryUtils};
     use crate::experiments::Experiment;
     use crate::prelude::*;
     use crate::results::{
-        DeleteResults, EncodedLog, EncodingType, FailureReason, ReadResults, TestResult,
-        WriteResults,
+        DeleteResults, DependencyGraph, EncodedLog, EncodingType, FailureReason, ReadResults,
+        TestResult, WriteResults,
     };
-    use crate::toolchain::{MAIN_TOOLCHAIN, TEST_TOOLCHAIN};
+    use crate::toolchain::{Toolchain, MAIN_TOOLCHAIN, TEST_TOOLCHAIN};
+    use crate::utils::hex::sha1_hex;
+    use crate::utils::size::Size;
 
     use std::collections::BTreeSet;
 
@@ -335,7 +738,9 @@ mod tests {
             .unwrap();
         assert!(String::from_utf8_lossy(match result_var {
             EncodedLog::Plain(ref data) => data,
-            EncodedLog::Gzip(_) => panic!("The encoded log should not be Gzipped."),
+            EncodedLog::Gzip(_) | EncodedLog::Zstd(_) => {
+                panic!("The encoded log should not be compressed.")
+            }
         })
         .contains("hello world"));
 
@@ -415,6 +820,12 @@ mod tests {
             version: "1.2".into(),
         });
 
+        let mut graph = DependencyGraph::default();
+        graph.roots.insert("lazy_static 1.2.0".into());
+        graph
+            .packages
+            .insert("lazy_static 1.2.0".into(), BTreeSet::new());
+
         // Store a result and versions
         results
             .store(
@@ -425,9 +836,19 @@ mod tests {
                         toolchain: MAIN_TOOLCHAIN.clone(),
                         result: TestResult::TestPass,
                         log: base64::encode("foo"),
+                        memory_limit: None,
+                        sandbox_counters: None,
+                        sandbox_image: None,
+                        dependency_graph: Some(graph.clone()),
+                        duration: None,
+                        encoding: None,
+                        upload: None,
+                        checksum: None,
                     }],
                     version: Some((krate.clone(), updated.clone())),
+                    idempotency_key: None,
                 },
+                &config,
                 EncodingType::Plain,
             )
             .unwrap();
@@ -442,6 +863,11 @@ mod tests {
                 .unwrap(),
             Some(TestResult::TestPass)
         );
+        let graphs = results
+            .load_dependency_graphs(&ex, &MAIN_TOOLCHAIN)
+            .unwrap();
+        assert_eq!(graphs.len(), 1);
+        assert_eq!(graphs[&updated], graph);
 
         assert_eq!(
             results.load_log(&ex, &MAIN_TOOLCHAIN, &krate).unwrap(),
@@ -454,4 +880,369 @@ mod tests {
             None
         );
     }
+
+    #[test]
+    fn test_idempotency_key() {
+        let db = Database::temp().unwrap();
+        let results = DatabaseDB::new(&db);
+        let config = Config::default();
+        let ctx = ActionsCtx::new(&db, &config);
+
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+
+        // Create a dummy experiment to attach the results to
+        CreateExperiment::dummy("dummy").apply(&ctx).unwrap();
+        let ex = Experiment::get(&db, "dummy").unwrap().unwrap();
+
+        let krate = Crate::Local("build-pass".into());
+        let store = |key: &str, result| {
+            results
+                .store(
+                    &ex,
+                    &ProgressData {
+                        results: vec![TaskResult {
+                            krate: krate.clone(),
+                            toolchain: MAIN_TOOLCHAIN.clone(),
+                            result,
+                            log: base64::encode("foo"),
+                            memory_limit: None,
+                            sandbox_counters: None,
+                            sandbox_image: None,
+                            dependency_graph: None,
+                            duration: None,
+                            encoding: None,
+                            upload: None,
+                            checksum: None,
+                        }],
+                        version: None,
+                        idempotency_key: Some(key.into()),
+                    },
+                    &config,
+                    EncodingType::Plain,
+                )
+                .unwrap()
+        };
+
+        assert!(store("first", TestResult::TestPass));
+        // Retried submissions are ignored, even if their content changed
+        assert!(!store("first", TestResult::TestSkipped));
+        assert_eq!(
+            results
+                .load_test_result(&ex, &MAIN_TOOLCHAIN, &krate)
+                .unwrap(),
+            Some(TestResult::TestPass)
+        );
+
+        assert!(store("second", TestResult::TestSkipped));
+        assert_eq!(
+            results
+                .load_test_result(&ex, &MAIN_TOOLCHAIN, &krate)
+                .unwrap(),
+            Some(TestResult::TestSkipped)
+        );
+    }
+
+    #[test]
+    fn test_streamed_logs() {
+        let db = Database::temp().unwrap();
+        let results = DatabaseDB::new(&db);
+        let config = Config::default();
+        let ctx = ActionsCtx::new(&db, &config);
+
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+
+        // Create a dummy experiment to attach the results to
+        CreateExperiment::dummy("dummy").apply(&ctx).unwrap();
+        let ex = Experiment::get(&db, "dummy").unwrap().unwrap();
+
+        let krate = Crate::Local("build-pass".into());
+        let store = || {
+            results
+                .store(
+                    &ex,
+                    &ProgressData {
+                        results: vec![TaskResult {
+                            krate: krate.clone(),
+                            toolchain: MAIN_TOOLCHAIN.clone(),
+                            result: TestResult::TestPass,
+                            log: base64::encode("foo"),
+                            memory_limit: None,
+                            sandbox_counters: None,
+                            sandbox_image: None,
+                            dependency_graph: None,
+                            duration: None,
+                            encoding: None,
+                            upload: None,
+                            checksum: None,
+                        }],
+                        version: None,
+                        idempotency_key: None,
+                    },
+                    &config,
+                    EncodingType::Plain,
+                )
+                .unwrap();
+        };
+
+        store();
+        assert_eq!(
+            results.unstreamed_logs(&ex).unwrap(),
+            vec![(krate.clone(), MAIN_TOOLCHAIN.clone())]
+        );
+
+        results
+            .mark_log_streamed(&ex, &MAIN_TOOLCHAIN, &krate)
+            .unwrap();
+        assert!(results.unstreamed_logs(&ex).unwrap().is_empty());
+
+        // Storing the result again requires the new log to be streamed
+        store();
+        assert_eq!(results.unstreamed_logs(&ex).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_chunked_upload() {
+        let db = Database::temp().unwrap();
+        let results = DatabaseDB::new(&db);
+        let config = Config::default();
+        let ctx = ActionsCtx::new(&db, &config);
+
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+
+        // Create a dummy experiment to attach the results to
+        CreateExperiment::dummy("dummy").apply(&ctx).unwrap();
+        let ex = Experiment::get(&db, "dummy").unwrap().unwrap();
+
+        let log = EncodedLog::from_plain_slice(b"hello world", EncodingType::Gzip).unwrap();
+        let (first, second) = log.as_slice().split_at(5);
+        let total = log.as_slice().len() as u64;
+
+        assert_eq!(results.append_log_chunk(&ex, "up", 0, first).unwrap(), 5);
+        // Chunks sent again after a lost response, or out of order, are ignored
+        assert_eq!(results.append_log_chunk(&ex, "up", 0, first).unwrap(), 5);
+        assert_eq!(results.append_log_chunk(&ex, "up", 7, second).unwrap(), 5);
+        assert_eq!(
+            results.append_log_chunk(&ex, "up", 5, second).unwrap(),
+            total
+        );
+
+        let krate = Crate::Local("build-pass".into());
+        let store = |checksum: &str| {
+            results.store(
+                &ex,
+                &ProgressData {
+                    results: vec![TaskResult {
+                        krate: krate.clone(),
+                        toolchain: MAIN_TOOLCHAIN.clone(),
+                        result: TestResult::TestPass,
+                        log: String::new(),
+                        memory_limit: None,
+                        sandbox_counters: None,
+                        sandbox_image: None,
+                        dependency_graph: None,
+                        duration: None,
+                        encoding: Some(EncodingType::Gzip),
+                        upload: Some("up".into()),
+                        checksum: Some(checksum.into()),
+                    }],
+                    version: None,
+                    idempotency_key: None,
+                },
+                &config,
+                EncodingType::Plain,
+            )
+        };
+
+        // Corrupted logs are rejected
+        assert!(store(&sha1_hex(b"something else")).is_err());
+        assert!(results
+            .load_log(&ex, &MAIN_TOOLCHAIN, &krate)
+            .unwrap()
+            .is_none());
+
+        assert!(store(&sha1_hex(log.as_slice())).unwrap());
+        assert_eq!(
+            results.load_log(&ex, &MAIN_TOOLCHAIN, &krate).unwrap(),
+            Some(EncodedLog::Plain(b"hello world".to_vec()))
+        );
+
+        // The upload is discarded once the result is stored
+        assert_eq!(results.append_log_chunk(&ex, "up", 0, &[]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        rustwide::logging::init();
+
+        let db = Database::temp().unwrap();
+        let results = DatabaseDB::new(&db);
+        let config = Config::default();
+        let ctx = ActionsCtx::new(&db, &config);
+
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+
+        // Create a dummy experiment to attach the results to
+        CreateExperiment::dummy("dummy").apply(&ctx).unwrap();
+        let ex = Experiment::get(&db, "dummy").unwrap().unwrap();
+
+        let krate = Crate::Local("build-pass".into());
+        let memory_limit = |tc: &Toolchain| -> Option<i64> {
+            db.get_row(
+                "SELECT memory_limit FROM results WHERE experiment = ?1 AND toolchain = ?2;",
+                &[&ex.name, &tc.to_string()],
+                |row| row.get("memory_limit"),
+            )
+            .unwrap()
+            .unwrap()
+        };
+
+        // The memory limit is only recorded for builds that ran out of memory
+        for (tc, result) in &[
+            (&*MAIN_TOOLCHAIN, TestResult::TestPass),
+            (&*TEST_TOOLCHAIN, TestResult::BuildFail(FailureReason::OOM)),
+        ] {
+            results
+                .record_result(&ex, tc, &krate, None, &config, EncodingType::Plain, || {
+                    Ok(result.clone())
+                })
+                .unwrap();
+        }
+        assert_eq!(memory_limit(&MAIN_TOOLCHAIN), None);
+        assert_eq!(
+            memory_limit(&TEST_TOOLCHAIN),
+            Some(config.oom_memory_limit(&krate).to_bytes() as i64)
+        );
+    }
+
+    #[test]
+    fn test_sandbox_counters() {
+        rustwide::logging::init();
+
+        let db = Database::temp().unwrap();
+        let results = DatabaseDB::new(&db);
+        let config = Config::default();
+        let ctx = ActionsCtx::new(&db, &config);
+
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+
+        // Create a dummy experiment to attach the results to
+        CreateExperiment::dummy("dummy").apply(&ctx).unwrap();
+        let ex = Experiment::get(&db, "dummy").unwrap().unwrap();
+
+        let krate = Crate::Local("build-pass".into());
+        results
+            .record_result(
+                &ex,
+                &MAIN_TOOLCHAIN,
+                &krate,
+                None,
+                &config,
+                EncodingType::Plain,
+                || {
+                    warn!("touch: cannot touch '/opt/rustwide/workdir/foo': Read-only file system");
+                    Ok(TestResult::TestPass)
+                },
+            )
+            .unwrap();
+        results
+            .record_result(
+                &ex,
+                &TEST_TOOLCHAIN,
+                &krate,
+                None,
+                &config,
+                EncodingType::Plain,
+                || Ok(TestResult::TestPass),
+            )
+            .unwrap();
+
+        let counters = results.load_sandbox_counters(&ex, &MAIN_TOOLCHAIN).unwrap();
+        assert_eq!(counters.len(), 1);
+        assert_eq!(counters[&krate].writes, 1);
+        assert_eq!(counters[&krate].network, 0);
+        assert!(results
+            .load_sandbox_counters(&ex, &TEST_TOOLCHAIN)
+            .unwrap()
+            .is_empty());
+
+        let missing = Crate::Local("missing".into());
+        let chunk = results
+            .load_results_chunk(&ex, &MAIN_TOOLCHAIN, &[krate.clone(), missing])
+            .unwrap();
+        assert_eq!(chunk.len(), 1);
+        assert_eq!(chunk[&krate].0, TestResult::TestPass);
+        assert_eq!(chunk[&krate].1.as_ref().map(|c| c.writes), Some(1));
+        let chunk = results
+            .load_results_chunk(&ex, &TEST_TOOLCHAIN, &[krate.clone()])
+            .unwrap();
+        assert_eq!(chunk[&krate], (TestResult::TestPass, None));
+    }
+
+    #[test]
+    fn test_trim_success_logs() {
+        rustwide::logging::init();
+
+        let db = Database::temp().unwrap();
+        let results = DatabaseDB::new(&db);
+        let mut config = Config::default();
+        config.logs.success_max_size = Some(Size::Bytes(8));
+        let ctx = ActionsCtx::new(&db, &config);
+
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+
+        // Create a dummy experiment to attach the results to
+        CreateExperiment::dummy("dummy").apply(&ctx).unwrap();
+        let ex = Experiment::get(&db, "dummy").unwrap().unwrap();
+
+        let record = |krate: &Crate, tc: &Toolchain, result: TestResult| {
+            results
+                .record_result(&ex, tc, krate, None, &config, EncodingType::Plain, || {
+                    info!("a long line");
+                    info!("the end");
+                    Ok(result)
+                })
+                .unwrap();
+        };
+        let log = |krate: &Crate, tc: &Toolchain| {
+            let log = results.load_log(&ex, tc, krate).unwrap().unwrap();
+            String::from_utf8(log.to_plain().unwrap()).unwrap()
+        };
+
+        // Logs are only trimmed when the crate succeeded on all the toolchains
+        let pass = Crate::Local("build-pass".into());
+        record(&pass, &MAIN_TOOLCHAIN, TestResult::TestPass);
+        assert!(log(&pass, &MAIN_TOOLCHAIN).contains("a long line"));
+        record(&pass, &TEST_TOOLCHAIN, TestResult::TestSkipped);
+        for tc in &[&*MAIN_TOOLCHAIN, &*TEST_TOOLCHAIN] {
+            let log = log(&pass, tc);
+            assert!(log.starts_with("[crater] log trimmed"));
+            assert!(!log.contains("a long line"));
+        }
+
+        let fail = Crate::Local("build-fail".into());
+        record(&fail, &MAIN_TOOLCHAIN, TestResult::TestPass);
+        record(
+            &fail,
+            &TEST_TOOLCHAIN,
+            TestResult::BuildFail(FailureReason::Unknown),
+        );
+        for tc in &[&*MAIN_TOOLCHAIN, &*TEST_TOOLCHAIN] {
+            assert!(log(&fail, tc).contains("a long line"));
+        }
+    }
+
+    #[test]
+    fn test_trim_log() {
+        assert_eq!(
+            trim_log(b"first\nsecond\nthird\n", 10),
+            b"[crater] log trimmed to its last 6 bytes, as the crate succeeded on all toolchains\n\
+              third\n"
+                .to_vec()
+        );
+        assert_eq!(
+            trim_log(b"first\n", 0),
+            b"[crater] log trimmed to its last 0 bytes, as the crate succeeded on all toolchains\n"
+                .to_vec()
+        );
+    }
 }
@@ -0,0 +1,147 @@
+use cargo_metadata::{Metadata, PackageId};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Dependency graph resolved by cargo when a crate was built, condensed from the output of
+/// `cargo metadata` to the packages and the edges between them. Packages are identified by their
+/// name and version, like `serde 1.0.104`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DependencyGraph {
+    /// The package or the packages of the workspace being tested.
+    pub roots: BTreeSet<String>,
+    /// Direct dependencies of every package of the graph, including the build and the dev ones.
+    pub packages: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl DependencyGraph {
+    /// Condense the output of `cargo metadata`, returning `None` if cargo didn't resolve the
+    /// dependencies (for example because of `--no-deps`).
+    pub(crate) fn from_metadata(metadata: &Metadata) -> Option<Self> {
+        let resolve = metadata.resolve.as_ref()?;
+        let names = metadata
+            .packages
+            .iter()
+            .map(|pkg| (&pkg.id, format!("{} {}", pkg.name, pkg.version)))
+            .collect::<HashMap<&PackageId, String>>();
+        let name = |id: &PackageId| names.get(id).cloned().unwrap_or_else(|| id.repr.clone());
+
+        Some(DependencyGraph {
+            roots: metadata.workspace_members.iter().map(&name).collect(),
+            packages: resolve
+                .nodes
+                .iter()
+                .map(|node| {
+                    (
+                        name(&node.id),
+                        node.dependencies.iter().map(&name).collect(),
+                    )
+                })
+                .collect(),
+        })
+    }
+
+    /// Whether the crate depends, directly or transitively, on the package `name`, at `version`
+    /// if one is provided.
+    pub fn depends_on(&self, name: &str, version: Option<&str>) -> bool {
+        self.packages
+            .keys()
+            .filter(|pkg| !self.roots.contains(*pkg))
+            .any(|pkg| {
+                let mut parts = pkg.splitn(2, ' ');
+                parts.next() == Some(name)
+                    && version.map_or(true, |version| parts.next() == Some(version))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DependencyGraph;
+    use cargo_metadata::Metadata;
+    use serde_json::json;
+
+    #[test]
+    fn test_from_metadata() {
+        let metadata: Metadata = serde_json::from_value(json!({
+            "packages": [
+                {
+                    "name": "foo",
+                    "version": "0.1.0",
+                    "id": "foo 0.1.0 (path+file:///opt/rustwide/workdir)",
+                    "license": null,
+                    "license_file": null,
+                    "description": null,
+                    "source": null,
+                    "dependencies": [],
+                    "targets": [],
+                    "features": {},
+                    "manifest_path": "/opt/rustwide/workdir/Cargo.toml",
+                    "metadata": null,
+                    "authors": [],
+                    "categories": [],
+                    "keywords": [],
+                    "readme": null,
+                    "repository": null,
+                    "edition": "2018",
+                    "links": null,
+                },
+                {
+                    "name": "libc",
+                    "version": "0.2.66",
+                    "id": "libc 0.2.66 (registry+https://github.com/rust-lang/crates.io-index)",
+                    "license": null,
+                    "license_file": null,
+                    "description": null,
+                    "source": "registry+https://github.com/rust-lang/crates.io-index",
+                    "dependencies": [],
+                    "targets": [],
+                    "features": {},
+                    "manifest_path": "/opt/rustwide/cargo-home/libc/Cargo.toml",
+                    "metadata": null,
+                    "authors": [],
+                    "categories": [],
+                    "keywords": [],
+                    "readme": null,
+                    "repository": null,
+                    "edition": "2015",
+                    "links": null,
+                },
+            ],
+            "workspace_members": ["foo 0.1.0 (path+file:///opt/rustwide/workdir)"],
+            "resolve": {
+                "nodes": [
+                    {
+                        "id": "foo 0.1.0 (path+file:///opt/rustwide/workdir)",
+                        "dependencies": [
+                            "libc 0.2.66 (registry+https://github.com/rust-lang/crates.io-index)",
+                        ],
+                        "deps": [],
+                        "features": [],
+                    },
+                    {
+                        "id": "libc 0.2.66 (registry+https://github.com/rust-lang/crates.io-index)",
+                        "dependencies": [],
+                        "deps": [],
+                        "features": [],
+                    },
+                ],
+                "root": "foo 0.1.0 (path+file:///opt/rustwide/workdir)",
+            },
+            "target_directory": "/opt/rustwide/target",
+            "version": 1,
+            "workspace_root": "/opt/rustwide/workdir",
+        }))
+        .unwrap();
+
+        let graph = DependencyGraph::from_metadata(&metadata).unwrap();
+        assert_eq!(graph.roots.iter().collect::<Vec<_>>(), vec!["foo 0.1.0"]);
+        assert_eq!(graph.packages.len(), 2);
+        assert!(graph.packages["foo 0.1.0"].contains("libc 0.2.66"));
+        assert!(graph.packages["libc 0.2.66"].is_empty());
+
+        assert!(graph.depends_on("libc", None));
+        assert!(graph.depends_on("libc", Some("0.2.66")));
+        assert!(!graph.depends_on("libc", Some("0.2.65")));
+        // The crate being tested isn't a dependency of itself
+        assert!(!graph.depends_on("foo", None));
+    }
+}
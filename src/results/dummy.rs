@@ -1,7 +1,7 @@
 use crate::crates::Crate;
 use crate::experiments::Experiment;
 use crate::prelude::*;
-use crate::results::{EncodedLog, ReadResults, TestResult};
+use crate::results::{DependencyGraph, EncodedLog, ReadResults, SandboxCounters, TestResult};
 use crate::toolchain::Toolchain;
 use std::collections::HashMap;
 
@@ -9,11 +9,14 @@ use std::collections::HashMap;
 struct DummyData {
     logs: HashMap<(Crate, Toolchain), EncodedLog>,
     results: HashMap<(Crate, Toolchain), TestResult>,
+    dependency_graphs: HashMap<(Crate, Toolchain), DependencyGraph>,
 }
 
 #[derive(Default)]
 pub struct DummyDB {
     experiments: HashMap<String, DummyData>,
+    notes: HashMap<Crate, String>,
+    previous: HashMap<String, Experiment>,
 }
 
 impl DummyDB {
@@ -32,6 +35,14 @@ impl DummyDB {
             .insert((krate, tc), log);
     }
 
+    pub fn add_dummy_note(&mut self, krate: Crate, note: &str) {
+        self.notes.insert(krate, note.to_string());
+    }
+
+    pub fn add_dummy_previous(&mut self, ex: &Experiment, previous: Experiment) {
+        self.previous.insert(ex.name.to_string(), previous);
+    }
+
     pub fn add_dummy_result(
         &mut self,
         ex: &Experiment,
@@ -45,6 +56,20 @@ impl DummyDB {
             .results
             .insert((krate, tc), res);
     }
+
+    pub fn add_dummy_dependency_graph(
+        &mut self,
+        ex: &Experiment,
+        krate: Crate,
+        tc: Toolchain,
+        graph: DependencyGraph,
+    ) {
+        self.experiments
+            .entry(ex.name.to_string())
+            .or_insert_with(DummyData::default)
+            .dependency_graphs
+            .insert((krate, tc), graph);
+    }
 }
 
 impl ReadResults for DummyDB {
@@ -73,4 +98,73 @@ impl ReadResults for DummyDB {
             .get(&(krate.clone(), toolchain.clone()))
             .cloned())
     }
+
+    fn load_crate_note(&self, krate: &Crate) -> Fallible<Option<String>> {
+        Ok(self.notes.get(krate).cloned())
+    }
+
+    fn load_previous_experiment(&self, ex: &Experiment) -> Fallible<Option<Experiment>> {
+        Ok(self.previous.get(&ex.name).cloned())
+    }
+
+    fn load_all_results(
+        &self,
+        ex: &Experiment,
+        toolchain: &Toolchain,
+    ) -> Fallible<Vec<(Crate, TestResult)>> {
+        let data = match self.experiments.get(&ex.name) {
+            Some(data) => data,
+            None => return Ok(Vec::new()),
+        };
+        Ok(data
+            .results
+            .iter()
+            .filter(|((_, tc), _)| tc == toolchain)
+            .map(|((krate, _), res)| (krate.clone(), res.clone()))
+            .collect())
+    }
+
+    fn load_sandbox_counters(
+        &self,
+        _ex: &Experiment,
+        _toolchain: &Toolchain,
+    ) -> Fallible<HashMap<Crate, SandboxCounters>> {
+        Ok(HashMap::new())
+    }
+
+    fn load_results_chunk(
+        &self,
+        ex: &Experiment,
+        toolchain: &Toolchain,
+        crates: &[Crate],
+    ) -> Fallible<HashMap<Crate, (TestResult, Option<SandboxCounters>)>> {
+        let data = match self.experiments.get(&ex.name) {
+            Some(data) => data,
+            None => return Ok(HashMap::new()),
+        };
+        Ok(crates
+            .iter()
+            .filter_map(|krate| {
+                let res = data.results.get(&(krate.clone(), toolchain.clone()))?;
+                Some((krate.clone(), (res.clone(), None)))
+            })
+            .collect())
+    }
+
+    fn load_dependency_graphs(
+        &self,
+        ex: &Experiment,
+        toolchain: &Toolchain,
+    ) -> Fallible<HashMap<Crate, DependencyGraph>> {
+        let data = match self.experiments.get(&ex.name) {
+            Some(data) => data,
+            None => return Ok(HashMap::new()),
+        };
+        Ok(data
+            .dependency_graphs
+            .iter()
+            .filter(|((_, tc), _)| tc == toolchain)
+            .map(|((krate, _), graph)| (krate.clone(), graph.clone()))
+            .collect())
+    }
 }
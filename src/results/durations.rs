@@ -0,0 +1,156 @@
+//! History of how long the builds of each crate took, from which the timeouts of the heaviest
+//! crates are learned instead of making them fit in the default timeout.
+
+use crate::config::{AdaptiveTimeoutsConfig, Config};
+use crate::crates::Crate;
+use crate::db::QueryUtils;
+use crate::prelude::*;
+use crate::results::TestResult;
+use chrono::Utc;
+use std::time::Duration;
+
+/// Key of the history of a crate. All the versions of a registry crate share it, as publishing a
+/// new version seldom changes how long the crate takes to build.
+fn history_key(krate: &Crate) -> String {
+    match krate {
+        Crate::Registry(details) => format!("reg/{}", details.name),
+        other => other.id(),
+    }
+}
+
+/// Record the duration of a build, if it ran to completion. Only the latest builds of each crate
+/// are kept.
+pub(crate) fn record<T: QueryUtils>(
+    db: &T,
+    config: &Config,
+    krate: &Crate,
+    result: &TestResult,
+    duration: Duration,
+) -> Fallible<()> {
+    // Failed builds can stop at any point, and skipped ones don't build anything
+    match result {
+        TestResult::TestPass | TestResult::LintWarnings(_) => {}
+        TestResult::TestFail(reason) if !reason.is_spurious() => {}
+        _ => return Ok(()),
+    }
+
+    let key = history_key(krate);
+    db.execute(
+        "INSERT INTO build_durations (crate, duration, recorded_at) VALUES (?1, ?2, ?3);",
+        &[&key, &(duration.as_secs() as i64), &Utc::now()],
+    )?;
+    db.execute(
+        "DELETE FROM build_durations WHERE crate = ?1 AND rowid NOT IN \
+         (SELECT rowid FROM build_durations WHERE crate = ?1 \
+         ORDER BY recorded_at DESC, rowid DESC LIMIT ?2);",
+        &[&key, &i64::from(config.adaptive_timeouts.samples)],
+    )?;
+    Ok(())
+}
+
+/// Scale the median of the durations (in seconds), clamping the result to the bounds of the
+/// configuration. There's no timeout if not enough durations were recorded.
+fn timeout_from(durations: &mut [u64], config: &AdaptiveTimeoutsConfig) -> Option<Duration> {
+    if durations.is_empty() || durations.len() < config.min_samples as usize {
+        return None;
+    }
+    durations.sort();
+    let mid = durations.len() / 2;
+    let median = if durations.len() % 2 == 0 {
+        (durations[mid - 1] + durations[mid]) as f64 / 2.0
+    } else {
+        durations[mid] as f64
+    };
+
+    let timeout = Duration::from_secs((median * config.multiplier).ceil() as u64);
+    Some(
+        timeout
+            .max(config.min.to_duration())
+            .min(config.max.to_duration()),
+    )
+}
+
+/// Timeout of the builds of a crate learned from its history, if adaptive timeouts are enabled.
+pub(crate) fn learned_timeout<T: QueryUtils>(
+    db: &T,
+    config: &Config,
+    krate: &Crate,
+) -> Fallible<Option<Duration>> {
+    if !config.adaptive_timeouts.enabled {
+        return Ok(None);
+    }
+    let mut durations = db.query(
+        "SELECT duration FROM build_durations WHERE crate = ?1;",
+        &[&history_key(krate)],
+        |row| row.get::<_, i64>("duration") as u64,
+    )?;
+    Ok(timeout_from(&mut durations, &config.adaptive_timeouts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{learned_timeout, record, timeout_from};
+    use crate::config::{AdaptiveTimeoutsConfig, Config};
+    use crate::crates::{Crate, RegistryCrate};
+    use crate::db::Database;
+    use crate::results::{FailureReason, TestResult};
+    use crate::utils::timeout::Timeout;
+    use std::time::Duration;
+
+    #[test]
+    fn test_timeout_from() {
+        let config = AdaptiveTimeoutsConfig {
+            min: Timeout::Minutes(5),
+            ..AdaptiveTimeoutsConfig::default()
+        };
+        let minutes = |m| Some(Duration::from_secs(m * 60));
+
+        assert_eq!(timeout_from(&mut [], &config), None);
+        assert_eq!(timeout_from(&mut [600, 600], &config), None);
+        assert_eq!(timeout_from(&mut [1200, 300, 600], &config), minutes(30));
+        assert_eq!(
+            timeout_from(&mut [300, 900, 400, 600], &config),
+            minutes(25)
+        );
+        // The timeout is clamped to the bounds
+        assert_eq!(timeout_from(&mut [10, 20, 30], &config), minutes(5));
+        assert_eq!(timeout_from(&mut [3600, 3600, 3600], &config), minutes(120));
+    }
+
+    #[test]
+    fn test_learned_timeout() {
+        let db = Database::temp().unwrap();
+        let mut config = Config::default();
+        config.adaptive_timeouts.samples = 3;
+        let krate = |version: &str| {
+            Crate::Registry(RegistryCrate {
+                name: "stm32f4".into(),
+                version: version.into(),
+            })
+        };
+        let minutes = |m| Duration::from_secs(m * 60);
+
+        let fail = TestResult::BuildFail(FailureReason::Unknown);
+        record(&db, &config, &krate("0.1.0"), &fail, minutes(1)).unwrap();
+        for (version, duration) in &[("0.1.0", 10), ("0.1.0", 20), ("0.2.0", 30), ("0.2.0", 40)] {
+            let result = TestResult::TestPass;
+            record(&db, &config, &krate(version), &result, minutes(*duration)).unwrap();
+        }
+
+        // Disabled by default
+        assert_eq!(
+            learned_timeout(&db, &config, &krate("0.3.0")).unwrap(),
+            None
+        );
+
+        // Only the last three successful builds of any version are kept
+        config.adaptive_timeouts.enabled = true;
+        assert_eq!(
+            learned_timeout(&db, &config, &krate("0.3.0")).unwrap(),
+            Some(minutes(90))
+        );
+
+        let other = Crate::Local("other".into());
+        assert_eq!(learned_timeout(&db, &config, &other).unwrap(), None);
+    }
+}
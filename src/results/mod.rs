@@ -1,20 +1,29 @@
+pub(crate) mod bank;
 mod db;
+mod dependencies;
 #[cfg(test)]
 mod dummy;
+pub(crate) mod durations;
+mod redact;
+pub(crate) mod sandbox;
 use crate::config::Config;
 use crate::crates::Crate;
 use crate::experiments::Experiment;
 use crate::prelude::*;
 
 pub use crate::results::db::{DatabaseDB, ProgressData};
+pub use crate::results::dependencies::DependencyGraph;
 #[cfg(test)]
 pub use crate::results::dummy::DummyDB;
+pub use crate::results::redact::Redactor;
+pub use crate::results::sandbox::SandboxCounters;
 use crate::toolchain::Toolchain;
 use flate2::read::GzDecoder;
 use flate2::write::GzEncoder;
 use flate2::Compression;
 use rustwide::logging::LogStorage;
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashMap};
+use std::time::Duration;
 use std::{fmt, io::Read, io::Write, str::FromStr};
 
 pub trait ReadResults {
@@ -30,6 +39,31 @@ pub trait ReadResults {
         toolchain: &Toolchain,
         krate: &Crate,
     ) -> Fallible<Option<TestResult>>;
+    fn load_crate_note(&self, krate: &Crate) -> Fallible<Option<String>>;
+    fn load_previous_experiment(&self, ex: &Experiment) -> Fallible<Option<Experiment>>;
+    fn load_all_results(
+        &self,
+        ex: &Experiment,
+        toolchain: &Toolchain,
+    ) -> Fallible<Vec<(Crate, TestResult)>>;
+    fn load_sandbox_counters(
+        &self,
+        ex: &Experiment,
+        toolchain: &Toolchain,
+    ) -> Fallible<HashMap<Crate, SandboxCounters>>;
+    /// Results of the provided crates only, along with the operations denied by the sandbox, so
+    /// that the results of large experiments can be read a chunk of crates at a time.
+    fn load_results_chunk(
+        &self,
+        ex: &Experiment,
+        toolchain: &Toolchain,
+        crates: &[Crate],
+    ) -> Fallible<HashMap<Crate, (TestResult, Option<SandboxCounters>)>>;
+    fn load_dependency_graphs(
+        &self,
+        ex: &Experiment,
+        toolchain: &Toolchain,
+    ) -> Fallible<HashMap<Crate, DependencyGraph>>;
 }
 
 pub trait WriteResults {
@@ -40,6 +74,20 @@ pub trait WriteResults {
         krate: &Crate,
     ) -> Fallible<Option<TestResult>>;
     fn update_crate_version(&self, ex: &Experiment, old: &Crate, new: &Crate) -> Fallible<()>;
+    fn record_dependency_graph(
+        &self,
+        ex: &Experiment,
+        toolchain: &Toolchain,
+        krate: &Crate,
+        graph: &DependencyGraph,
+    ) -> Fallible<()>;
+    /// Timeout of the builds of the crate learned from the duration of its previous builds.
+    fn learned_timeout(
+        &self,
+        ex: &Experiment,
+        krate: &Crate,
+        config: &Config,
+    ) -> Fallible<Option<Duration>>;
     fn record_result<F>(
         &self,
         ex: &Experiment,
@@ -54,23 +102,65 @@ pub trait WriteResults {
         F: FnOnce() -> Fallible<TestResult>;
 }
 
+/// Results databases recording the digest of the sandbox image the results were built in.
+pub trait RecordSandboxImage {
+    /// Copy of this instance recording the results as built in the sandbox image with the
+    /// provided digest, for the crates built in another image than the one of the workspace.
+    fn for_sandbox_image(&self, sandbox_image: Option<String>) -> Self;
+}
+
 pub trait DeleteResults {
     fn delete_all_results(&self, ex: &Experiment) -> Fallible<()>;
     fn delete_result(&self, ex: &Experiment, toolchain: &Toolchain, krate: &Crate) -> Fallible<()>;
 }
 
+/// Message logged by rustwide when it stops recording a build log, because it exceeded the
+/// `build-log-max-size` or `build-log-max-lines` limits of the sandbox.
+const RUSTWIDE_TRUNCATION: &str = "too much data in the log, truncating it";
+
+/// Text of a build log, ending with a marker explaining that the rest of the output was discarded
+/// if the log exceeded the limits of the sandbox.
+pub(crate) fn log_output(storage: &LogStorage, config: &Config) -> String {
+    mark_truncation(storage.to_string(), config)
+}
+
+fn mark_truncation(mut output: String, config: &Config) -> String {
+    if output.contains(RUSTWIDE_TRUNCATION) {
+        if !output.ends_with('\n') {
+            output.push('\n');
+        }
+        output.push_str(&format!(
+            "[crater] the log was truncated here, as it exceeded the limit of {} or {} lines; \
+             the rest of the output of the build was discarded\n",
+            config.sandbox.build_log_max_size, config.sandbox.build_log_max_lines,
+        ));
+    }
+    output
+}
+
 string_enum!(pub enum EncodingType {
     Plain => "plain",
     Gzip => "gzip",
+    Zstd => "zstd",
 });
 
 #[derive(Clone, PartialEq, Debug)]
 pub enum EncodedLog {
     Plain(Vec<u8>),
     Gzip(Vec<u8>),
+    Zstd(Vec<u8>),
 }
 
 impl EncodedLog {
+    /// Wrap data already encoded with `encoding`, for example when loading it from the database.
+    pub fn from_encoded(data: Vec<u8>, encoding: EncodingType) -> EncodedLog {
+        match encoding {
+            EncodingType::Plain => EncodedLog::Plain(data),
+            EncodingType::Gzip => EncodedLog::Gzip(data),
+            EncodingType::Zstd => EncodedLog::Zstd(data),
+        }
+    }
+
     pub fn to_plain(&self) -> Fallible<Vec<u8>> {
         match self {
             EncodedLog::Plain(data) => Ok(data.to_vec()),
@@ -80,6 +170,7 @@ impl EncodedLog {
                 decoded_log.read_to_end(&mut new_log)?;
                 Ok(new_log)
             }
+            EncodedLog::Zstd(data) => Ok(zstd::stream::decode_all(data.as_slice())?),
         }
     }
 
@@ -87,6 +178,7 @@ impl EncodedLog {
         match self {
             EncodedLog::Plain(_) => EncodingType::Plain,
             EncodedLog::Gzip(_) => EncodingType::Gzip,
+            EncodedLog::Zstd(_) => EncodingType::Zstd,
         }
     }
 
@@ -94,6 +186,24 @@ impl EncodedLog {
         match self {
             EncodedLog::Plain(data) => data,
             EncodedLog::Gzip(data) => data,
+            EncodedLog::Zstd(data) => data,
+        }
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        match self {
+            EncodedLog::Plain(data) => data,
+            EncodedLog::Gzip(data) => data,
+            EncodedLog::Zstd(data) => data,
+        }
+    }
+
+    /// Encode the log with another compression, unless it already uses it.
+    pub fn reencode(self, desired_encoding: EncodingType) -> Fallible<EncodedLog> {
+        if self.get_encoding_type() == desired_encoding {
+            Ok(self)
+        } else {
+            EncodedLog::from_plain_slice(&self.to_plain()?, desired_encoding)
         }
     }
 
@@ -105,6 +215,8 @@ impl EncodedLog {
                 let encoded_log = encoded_log.finish()?;
                 Ok(EncodedLog::Gzip(encoded_log))
             }
+            // Level 0 selects the default compression level of zstd
+            EncodingType::Zstd => Ok(EncodedLog::Zstd(zstd::stream::encode_all(data, 0)?)),
             EncodingType::Plain => Ok(EncodedLog::Plain(data.to_vec())),
         }
     }
@@ -130,7 +242,7 @@ macro_rules! test_result_enum {
 
                 if parts.len() == 1 {
                     match parts[0] {
-                        $($with_reason_repr => Ok($name::$with_reason_name($reason::Unknown)),)*
+                        $($with_reason_repr => Ok($name::$with_reason_name($reason::default())),)*
                         $($reasonless_repr => Ok($name::$reasonless_name),)*
                         other => Err(TestResultParseError::UnknownResult(other.into()).into()),
                     }
@@ -152,6 +264,16 @@ macro_rules! test_result_enum {
                 }
             }
         }
+
+        impl $name {
+            /// Name of the result, without its failure reason.
+            pub fn kind(&self) -> &'static str {
+                match self {
+                    $($name::$with_reason_name(_) => $with_reason_repr,)*
+                    $($name::$reasonless_name => $reasonless_repr,)*
+                }
+            }
+        }
     }
 }
 
@@ -197,12 +319,40 @@ pub enum FailureReason {
     OOM,
     Timeout,
     ICE,
+    FetchFailed,
+    /// The crate uses `#![feature]`, which is rejected outside of the nightly channel.
+    RequiresNightly,
+    /// The build or the tests couldn't spawn more processes or threads inside the sandbox.
+    ProcessLimit,
+    /// Downloading something from the network failed or timed out.
+    Network,
+    /// The build tried to reach the network, which is disabled in the sandbox.
+    NetworkBlocked,
+    /// The docker daemon failed to create the container of the sandbox.
+    Docker,
+    /// The target directory grew past the disk limit, and the build was aborted.
+    DiskLimit,
+    /// Clippy itself crashed while linting the crate.
+    ClippyICE,
+    /// Miri detected undefined behavior while running the tests.
+    MiriUB,
+    /// The tests performed an operation Miri can't interpret (like calling foreign functions).
+    MiriUnsupported,
+    /// The crate compiled, but linking it failed.
+    LinkerError(LinkerFailure),
     CompilerError(BTreeSet<DiagnosticCode>),
     DependsOn(BTreeSet<Crate>),
 }
 
 impl Fail for FailureReason {}
 
+// Results stored before failure reasons were introduced don't have one
+impl Default for FailureReason {
+    fn default() -> Self {
+        FailureReason::Unknown
+    }
+}
+
 impl ::std::fmt::Display for FailureReason {
     fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
         match self {
@@ -210,6 +360,17 @@ impl ::std::fmt::Display for FailureReason {
             FailureReason::OOM => write!(f, "oom"),
             FailureReason::Timeout => write!(f, "timeout"),
             FailureReason::ICE => write!(f, "ice"),
+            FailureReason::FetchFailed => write!(f, "fetch-failed"),
+            FailureReason::RequiresNightly => write!(f, "requires-nightly"),
+            FailureReason::ProcessLimit => write!(f, "process-limit"),
+            FailureReason::Network => write!(f, "network"),
+            FailureReason::NetworkBlocked => write!(f, "network-access-blocked"),
+            FailureReason::Docker => write!(f, "docker"),
+            FailureReason::DiskLimit => write!(f, "disk-limit-exceeded"),
+            FailureReason::ClippyICE => write!(f, "clippy-ice"),
+            FailureReason::MiriUB => write!(f, "miri-ub"),
+            FailureReason::MiriUnsupported => write!(f, "miri-unsupported"),
+            FailureReason::LinkerError(kind) => write!(f, "linker-error({})", kind),
             FailureReason::CompilerError(codes) => write!(
                 f,
                 "compiler-error({})",
@@ -239,6 +400,7 @@ impl ::std::str::FromStr for FailureReason {
             let prefix = &s[..idx];
             let contents = s[idx + 1..s.len() - 1].split(", ");
             match prefix {
+                "linker-error" => Ok(FailureReason::LinkerError(s[idx + 1..s.len() - 1].parse()?)),
                 "compiler-error" => Ok(FailureReason::CompilerError(
                     contents
                         .map(|st| DiagnosticCode {
@@ -261,6 +423,16 @@ impl ::std::str::FromStr for FailureReason {
                 "oom" => Ok(FailureReason::OOM),
                 "timeout" => Ok(FailureReason::Timeout),
                 "ice" => Ok(FailureReason::ICE),
+                "fetch-failed" => Ok(FailureReason::FetchFailed),
+                "requires-nightly" => Ok(FailureReason::RequiresNightly),
+                "process-limit" => Ok(FailureReason::ProcessLimit),
+                "network" => Ok(FailureReason::Network),
+                "network-access-blocked" => Ok(FailureReason::NetworkBlocked),
+                "docker" => Ok(FailureReason::Docker),
+                "disk-limit-exceeded" => Ok(FailureReason::DiskLimit),
+                "clippy-ice" => Ok(FailureReason::ClippyICE),
+                "miri-ub" => Ok(FailureReason::MiriUB),
+                "miri-unsupported" => Ok(FailureReason::MiriUnsupported),
                 _ => bail!("unexpected value"),
             }
         }
@@ -270,15 +442,35 @@ impl ::std::str::FromStr for FailureReason {
 impl FailureReason {
     pub(crate) fn is_spurious(&self) -> bool {
         match *self {
-            FailureReason::OOM | FailureReason::Timeout => true,
+            FailureReason::OOM
+            | FailureReason::Timeout
+            | FailureReason::FetchFailed
+            | FailureReason::ProcessLimit
+            | FailureReason::Network
+            | FailureReason::Docker
+            | FailureReason::DiskLimit => true,
             FailureReason::CompilerError(_)
             | FailureReason::DependsOn(_)
             | FailureReason::Unknown
-            | FailureReason::ICE => false,
+            | FailureReason::ICE
+            | FailureReason::ClippyICE
+            | FailureReason::MiriUB
+            | FailureReason::MiriUnsupported
+            | FailureReason::LinkerError(_)
+            | FailureReason::RequiresNightly
+            | FailureReason::NetworkBlocked => false,
         }
     }
 }
 
+string_enum!(pub enum LinkerFailure {
+    MissingLibrary => "missing-library",
+    Relocation => "relocation",
+    UndefinedSymbol => "undefined-symbol",
+    Lld => "lld",
+    Other => "other",
+});
+
 string_enum!(pub enum BrokenReason {
     Unknown => "unknown",
     CargoToml => "cargo-toml",
@@ -286,22 +478,79 @@ string_enum!(pub enum BrokenReason {
     MissingGitRepository => "missing-git-repository",
 });
 
+impl Default for BrokenReason {
+    fn default() -> Self {
+        BrokenReason::Unknown
+    }
+}
+
+/// Lints emitted by a crate that built successfully, recorded by the `clippy` mode so that the
+/// lints emitted with the two toolchains can be compared.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Hash)]
+pub struct Lints(pub BTreeSet<DiagnosticCode>);
+
+impl fmt::Display for Lints {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let lints = self
+            .0
+            .iter()
+            .map(|lint| lint.to_string())
+            .collect::<Vec<_>>();
+        write!(f, "{}", lints.join(", "))
+    }
+}
+
+impl FromStr for Lints {
+    type Err = ::failure::Error;
+
+    fn from_str(s: &str) -> Fallible<Lints> {
+        Ok(Lints(
+            s.split(", ")
+                .filter(|lint| !lint.is_empty())
+                .map(|lint| lint.parse())
+                .collect::<Fallible<_>>()?,
+        ))
+    }
+}
+
 test_result_enum!(pub enum TestResult {
     with_reason {
         BrokenCrate(BrokenReason) => "broken",
         BuildFail(FailureReason) => "build-fail",
         TestFail(FailureReason) => "test-fail",
+        LintWarnings(Lints) => "lint-warnings",
     }
     without_reason {
         TestSkipped => "test-skipped",
         TestPass => "test-pass",
         Skipped => "skipped",
+        NotApplicable => "not-applicable",
         Error => "error",
     }
 });
 
 impl_serde_from_parse!(TestResult, expecting = "a test result");
 
+impl TestResult {
+    /// Whether the build was killed for exceeding the memory limit of the sandbox.
+    pub(crate) fn is_oom(&self) -> bool {
+        match self {
+            TestResult::BuildFail(FailureReason::OOM)
+            | TestResult::TestFail(FailureReason::OOM) => true,
+            _ => false,
+        }
+    }
+
+    /// Memory limit to record along with the result, only present if the build ran out of memory.
+    pub(crate) fn memory_limit(&self, config: &Config, krate: &Crate) -> Option<u64> {
+        if self.is_oom() {
+            Some(config.oom_memory_limit(krate).to_bytes() as u64)
+        } else {
+            None
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::crates::*;
@@ -312,6 +561,7 @@ mod tests {
     fn test_test_result_parsing() {
         use super::{
             FailureReason::*,
+            LinkerFailure, Lints,
             TestResult::{self, *},
         };
 
@@ -343,7 +593,20 @@ mod tests {
             "build-fail:compiler-error(001)" => BuildFail(CompilerError(btreeset!["001".parse().unwrap()])),
             "build-fail:oom" => BuildFail(OOM),
             "build-fail:ice" => BuildFail(ICE),
+            "build-fail:fetch-failed" => BuildFail(FetchFailed),
+            "build-fail:requires-nightly" => BuildFail(RequiresNightly),
+            "test-fail:process-limit" => TestFail(ProcessLimit),
+            "build-fail:network" => BuildFail(Network),
+            "test-fail:network-access-blocked" => TestFail(NetworkBlocked),
+            "test-fail:docker" => TestFail(Docker),
+            "build-fail:disk-limit-exceeded" => BuildFail(DiskLimit),
             "test-fail:timeout" => TestFail(Timeout),
+            "build-fail:clippy-ice" => BuildFail(ClippyICE),
+            "test-fail:miri-ub" => TestFail(MiriUB),
+            "test-fail:miri-unsupported" => TestFail(MiriUnsupported),
+            "build-fail:linker-error(missing-library)" => BuildFail(LinkerError(LinkerFailure::MissingLibrary)),
+            "test-fail:linker-error(lld)" => TestFail(LinkerError(LinkerFailure::Lld)),
+            "lint-warnings:clippy::needless_return, clippy::redundant_clone" => LintWarnings(Lints(btreeset!["clippy::needless_return".parse().unwrap(), "clippy::redundant_clone".parse().unwrap()])),
             "test-pass" => TestPass,
             "error" => Error,
             "build-fail:depends-on(reg/clint/0.2.1)" => BuildFail(DependsOn(btreeset![Crate::Registry(RegistryCrate{name: "clint".to_string(), version: "0.2.1".to_string()})])),
@@ -356,5 +619,39 @@ mod tests {
         );
         assert!(TestResult::from_str("error:oom").is_err());
         assert!(TestResult::from_str("build-fail:pleasedonotaddthis").is_err());
+
+        assert_eq!(BuildFail(OOM).kind(), "build-fail");
+        assert_eq!(TestPass.kind(), "test-pass");
+    }
+
+    #[test]
+    fn test_encoded_log() {
+        use super::{EncodedLog, EncodingType};
+
+        let log = b"hello world\n".repeat(100);
+        for &encoding in &[EncodingType::Plain, EncodingType::Gzip, EncodingType::Zstd] {
+            let encoded = EncodedLog::from_plain_slice(&log, encoding).unwrap();
+            assert_eq!(encoded.get_encoding_type(), encoding);
+            assert_eq!(encoded.to_plain().unwrap(), log);
+
+            let reencoded = encoded.reencode(EncodingType::Zstd).unwrap();
+            assert_eq!(reencoded.get_encoding_type(), EncodingType::Zstd);
+            assert_eq!(reencoded.to_plain().unwrap(), log);
+        }
+    }
+
+    #[test]
+    fn test_mark_truncation() {
+        use super::{mark_truncation, RUSTWIDE_TRUNCATION};
+        use crate::config::Config;
+
+        let config = Config::default();
+        assert_eq!(mark_truncation("foo\n".into(), &config), "foo\n");
+
+        let truncated = mark_truncation(format!("foo\n[WARN] {}", RUSTWIDE_TRUNCATION), &config);
+        assert!(truncated.ends_with(
+            "\n[crater] the log was truncated here, as it exceeded the limit of 1M or 1000 lines; \
+             the rest of the output of the build was discarded\n"
+        ));
     }
 }
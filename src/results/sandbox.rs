@@ -0,0 +1,76 @@
+/// Messages printed when a process inside the sandbox tried to reach the network, which is
+/// disabled there.
+pub(crate) const NETWORK_MESSAGES: &[&str] = &[
+    "Network is unreachable",
+    "Could not resolve host",
+    "Couldn't resolve host",
+    "Temporary failure in name resolution",
+    "failed to lookup address information",
+];
+/// Message of `EPERM`, returned by the seccomp profile of docker for the denied syscalls.
+const SYSCALL_MESSAGE: &str = "Operation not permitted";
+/// Message of `EROFS`, returned when writing to the read-only mounts of the sandbox (everything
+/// outside of the target directory).
+const WRITE_MESSAGE: &str = "Read-only file system";
+
+/// Number of operations denied by the sandbox during a build, counting the lines of the log
+/// reporting them. Processes seldom print the errors they handle, so those are lower bounds.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SandboxCounters {
+    pub network: u32,
+    pub syscalls: u32,
+    pub writes: u32,
+}
+
+impl SandboxCounters {
+    /// Count the denials reported in the log of a build, returning `None` if there are none.
+    pub(crate) fn from_log(log: &str) -> Option<Self> {
+        let mut counters = SandboxCounters::default();
+        for line in log.lines() {
+            if NETWORK_MESSAGES.iter().any(|msg| line.contains(msg)) {
+                counters.network += 1;
+            }
+            if line.contains(SYSCALL_MESSAGE) {
+                counters.syscalls += 1;
+            }
+            if line.contains(WRITE_MESSAGE) {
+                counters.writes += 1;
+            }
+        }
+
+        if counters == SandboxCounters::default() {
+            None
+        } else {
+            Some(counters)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SandboxCounters;
+
+    #[test]
+    fn test_from_log() {
+        assert_eq!(
+            SandboxCounters::from_log("Compiling foo v0.1.0\nFinished"),
+            None
+        );
+
+        let log = concat!(
+            "[INFO] running `cargo build`\n",
+            "error: failed to connect: Network is unreachable (os error 101)\n",
+            "thread 'main' panicked at 'ptrace: Operation not permitted (os error 1)'\n",
+            "error: could not write `/opt/rustwide/cargo-home/foo`: Read-only file system\n",
+            "curl: (6) Could not resolve host: example.com\n",
+        );
+        assert_eq!(
+            SandboxCounters::from_log(log),
+            Some(SandboxCounters {
+                network: 2,
+                syscalls: 1,
+                writes: 1,
+            })
+        );
+    }
+}
@@ -0,0 +1,191 @@
+//! Copy of the artifacts of the builds out of their target directory before it's cleaned up, for
+//! the tools built on top of crater needing the binaries or the documentation produced by an
+//! experiment. The artifacts of each build are copied to
+//! `<artifacts.dir>/<experiment>/<toolchain>/<crate>`, replacing the ones of its previous builds.
+
+use crate::config::{Artifact, Config};
+use crate::crates::Crate;
+use crate::prelude::*;
+use crate::toolchain::Toolchain;
+use crate::utils;
+use percent_encoding::utf8_percent_encode;
+use remove_dir_all::remove_dir_all;
+use rustwide::Build;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// Profiles whose directories contain the binaries and libraries of the local packages.
+const PROFILES: &[&str] = &["debug", "release"];
+
+/// File of the target directory the JSON messages emitted by cargo are appended to.
+const MESSAGES_FILE: &str = "crater-messages.json";
+
+fn enabled(config: &Config, artifact: Artifact) -> bool {
+    config.artifacts.dir.is_some() && config.artifacts.kinds.contains(&artifact)
+}
+
+/// Whether the documentation must be kept in the target directory until it's copied.
+pub(super) fn keep_docs(config: &Config) -> bool {
+    enabled(config, Artifact::Docs)
+}
+
+/// Whether the JSON messages emitted by cargo must be recorded with `record_messages`.
+pub(super) fn keep_messages(config: &Config) -> bool {
+    enabled(config, Artifact::JsonMessages)
+}
+
+/// Append the JSON messages emitted by cargo to the ones of the build.
+pub(super) fn record_messages(build: &Build, messages: &[String]) -> Fallible<()> {
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(build.host_target_dir().join(MESSAGES_FILE))?;
+    for message in messages {
+        writeln!(file, "{}", message)?;
+    }
+    Ok(())
+}
+
+/// Copy the selected artifacts of the build to the artifacts directory, if one is configured.
+pub(super) fn copy(
+    config: &Config,
+    ex: &str,
+    toolchain: &Toolchain,
+    krate: &Crate,
+    build: &Build,
+) -> Fallible<()> {
+    let dir = match &config.artifacts.dir {
+        Some(dir) if !config.artifacts.kinds.is_empty() => dir,
+        _ => return Ok(()),
+    };
+    let dest = dir
+        .join(utf8_percent_encode(ex, &utils::FILENAME_ENCODE_SET).to_string())
+        .join(toolchain.to_path_component())
+        .join(utf8_percent_encode(&krate.id(), &utils::FILENAME_ENCODE_SET).to_string());
+    if dest.exists() {
+        remove_dir_all(&dest)?;
+    }
+
+    let copied = copy_to(&build.host_target_dir(), &config.artifacts.kinds, &dest)?;
+    info!("copied {} artifacts to {}", copied, dest.display());
+    Ok(())
+}
+
+/// Copy the `kinds` of artifacts found in the `target` directory to `dest`, keeping their path
+/// relative to the target directory. Returns how many files were copied.
+fn copy_to(target: &Path, kinds: &[Artifact], dest: &Path) -> Fallible<usize> {
+    let mut files = Vec::new();
+    for kind in kinds {
+        match kind {
+            Artifact::Binaries | Artifact::Rlibs => {
+                for profile in PROFILES {
+                    let entries = match fs::read_dir(target.join(profile)) {
+                        Ok(entries) => entries,
+                        Err(_) => continue,
+                    };
+                    for entry in entries {
+                        let path = entry?.path();
+                        if path.is_file() && is_artifact(&path, *kind)? {
+                            files.push(path);
+                        }
+                    }
+                }
+            }
+            Artifact::Docs => {
+                for entry in WalkDir::new(target.join("doc")) {
+                    match entry {
+                        Ok(entry) if entry.file_type().is_file() => files.push(entry.into_path()),
+                        Ok(_) => {}
+                        // The documentation wasn't built
+                        Err(_) => break,
+                    }
+                }
+            }
+            Artifact::JsonMessages => {
+                let messages = target.join(MESSAGES_FILE);
+                if messages.is_file() {
+                    files.push(messages);
+                }
+            }
+        }
+    }
+
+    for file in &files {
+        let relative = file.strip_prefix(target)?;
+        let copy = dest.join(relative);
+        fs::create_dir_all(copy.parent().unwrap_or(dest))?;
+        fs::copy(file, &copy).with_context(|_| format!("failed to copy {}", file.display()))?;
+    }
+    Ok(files.len())
+}
+
+fn is_artifact(path: &Path, kind: Artifact) -> Fallible<bool> {
+    let extension = path.extension().and_then(|ext| ext.to_str());
+    Ok(match kind {
+        Artifact::Rlibs => extension == Some("rlib"),
+        Artifact::Binaries if cfg!(windows) => extension == Some("exe"),
+        Artifact::Binaries => extension.is_none() && is_executable(path)?,
+        _ => false,
+    })
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> Fallible<bool> {
+    use std::os::unix::fs::PermissionsExt;
+    Ok(fs::metadata(path)?.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(_path: &Path) -> Fallible<bool> {
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{copy_to, MESSAGES_FILE};
+    use crate::config::Artifact;
+    use std::fs;
+    use std::path::Path;
+
+    fn executable(path: &Path) {
+        fs::write(path, "").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_copy_to() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target");
+        fs::create_dir_all(target.join("debug").join("deps")).unwrap();
+        fs::create_dir_all(target.join("doc").join("foo")).unwrap();
+        executable(&target.join("debug").join("foo"));
+        fs::write(target.join("debug").join("libfoo.rlib"), "").unwrap();
+        fs::write(target.join("debug").join("foo.d"), "").unwrap();
+        fs::write(target.join("debug").join("deps").join("libbar.rlib"), "").unwrap();
+        fs::write(target.join("doc").join("foo").join("index.html"), "").unwrap();
+        fs::write(target.join(MESSAGES_FILE), "{}\n").unwrap();
+
+        let dest = dir.path().join("rlibs");
+        assert_eq!(copy_to(&target, &[Artifact::Rlibs], &dest).unwrap(), 1);
+        assert!(dest.join("debug").join("libfoo.rlib").is_file());
+
+        let dest = dir.path().join("docs");
+        let kinds = [Artifact::Docs, Artifact::JsonMessages];
+        assert_eq!(copy_to(&target, &kinds, &dest).unwrap(), 2);
+        assert!(dest.join("doc").join("foo").join("index.html").is_file());
+        assert!(dest.join(MESSAGES_FILE).is_file());
+
+        #[cfg(unix)]
+        {
+            let dest = dir.path().join("binaries");
+            assert_eq!(copy_to(&target, &[Artifact::Binaries], &dest).unwrap(), 1);
+            assert!(dest.join("debug").join("foo").is_file());
+        }
+    }
+}
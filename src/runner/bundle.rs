@@ -0,0 +1,107 @@
+use crate::config::Config;
+use crate::crates::Crate;
+use crate::prelude::*;
+use crate::runner::fetch;
+use crate::toolchain::{CratePatch, Toolchain};
+use chrono::{DateTime, Utc};
+use rustwide::cmd::Command;
+use rustwide::Workspace;
+use std::fs;
+use std::path::Path;
+
+/// Name of the file containing the metadata of a bundle, placed in its root.
+pub const BUNDLE_METADATA_FILE: &str = "crater-bundle.json";
+
+/// Details about a crate source prepared with `prepare_into`.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct BundleMetadata {
+    pub krate: Crate,
+    pub toolchain: Toolchain,
+    pub git_sha: Option<String>,
+    pub lockfile_generated: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Fetch the crate, apply the toolchain's patches and freeze its lockfile into `dest`, without
+/// building it. The result is exactly the source that would be built by an experiment.
+pub fn prepare_into(
+    workspace: &Workspace,
+    config: &Config,
+    krate: &Crate,
+    toolchain: &Toolchain,
+    dest: &Path,
+) -> Fallible<BundleMetadata> {
+    if dest.exists() && fs::read_dir(dest)?.next().is_some() {
+        bail!("destination directory {} is not empty", dest.display());
+    }
+
+    let rustwide_crate = krate.to_rustwide();
+    fetch::fetch(workspace, krate, &config.fetch)?;
+    rustwide_crate
+        .copy_source_to(workspace, dest)
+        .with_context(|_| format!("failed to copy the source of {}", krate))?;
+
+    let manifest = dest.join("Cargo.toml");
+    if !manifest.is_file() {
+        bail!("missing Cargo.toml in the source of {}", krate);
+    }
+    if !toolchain.patches.is_empty() {
+        apply_patches(&manifest, &toolchain.patches)?;
+    }
+
+    toolchain.install(workspace)?;
+    let lockfile_generated = !dest.join("Cargo.lock").exists();
+    if lockfile_generated {
+        Command::new(workspace, toolchain.cargo())
+            .args(&["generate-lockfile", "--manifest-path"])
+            .arg(&manifest)
+            .run()
+            .with_context(|_| format!("failed to generate the lockfile of {}", krate))?;
+    }
+    Command::new(workspace, toolchain.cargo())
+        .args(&["fetch", "--locked", "--manifest-path"])
+        .arg(&manifest)
+        .run()
+        .with_context(|_| format!("failed to fetch the dependencies of {}", krate))?;
+
+    let metadata = BundleMetadata {
+        krate: krate.clone(),
+        toolchain: toolchain.clone(),
+        git_sha: rustwide_crate.git_commit(workspace),
+        lockfile_generated,
+        created_at: Utc::now(),
+    };
+    fs::write(
+        dest.join(BUNDLE_METADATA_FILE),
+        serde_json::to_vec_pretty(&metadata)?,
+    )?;
+
+    Ok(metadata)
+}
+
+fn apply_patches(manifest: &Path, patches: &[CratePatch]) -> Fallible<()> {
+    let mut parsed: toml::Value = toml::from_str(&fs::read_to_string(manifest)?)?;
+
+    let table = parsed
+        .as_table_mut()
+        .ok_or_else(|| err_msg("invalid Cargo.toml"))?
+        .entry("patch".to_string())
+        .or_insert_with(|| toml::Value::Table(Default::default()))
+        .as_table_mut()
+        .ok_or_else(|| err_msg("invalid [patch] section in Cargo.toml"))?
+        .entry("crates-io".to_string())
+        .or_insert_with(|| toml::Value::Table(Default::default()))
+        .as_table_mut()
+        .ok_or_else(|| err_msg("invalid [patch.crates-io] section in Cargo.toml"))?;
+
+    for patch in patches {
+        let mut details = toml::value::Table::new();
+        details.insert("git".into(), toml::Value::String(patch.repo.clone()));
+        details.insert("branch".into(), toml::Value::String(patch.branch.clone()));
+        table.insert(patch.name.clone(), toml::Value::Table(details));
+    }
+
+    fs::write(manifest, toml::to_string(&parsed)?)?;
+    Ok(())
+}
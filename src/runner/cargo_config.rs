@@ -0,0 +1,69 @@
+use crate::prelude::*;
+use rustwide::Build;
+use std::fs;
+use toml::Value;
+
+/// Write the cargo configuration of the experiment in the source directory of the build, merging
+/// it with the configuration shipped by the crate. Keys set by the experiment take precedence.
+pub(super) fn inject(build: &Build, config: &str) -> Fallible<()> {
+    let dir = build.host_source_dir().join(".cargo");
+
+    // Older versions of Cargo only load `.cargo/config`, and newer ones prefer it over
+    // `.cargo/config.toml` when both are present.
+    let path = if !dir.join("config").exists() && dir.join("config.toml").exists() {
+        dir.join("config.toml")
+    } else {
+        dir.join("config")
+    };
+
+    let mut merged: Value = config.parse()?;
+    if path.exists() {
+        let existing: Value = fs::read_to_string(&path)?
+            .parse()
+            .with_context(|_| format!("failed to parse {}", path.display()))?;
+        merged = merge(existing, merged);
+    }
+
+    fs::create_dir_all(&dir)?;
+    fs::write(&path, toml::to_string(&merged)?)?;
+    Ok(())
+}
+
+fn merge(base: Value, overlay: Value) -> Value {
+    match (base, overlay) {
+        (Value::Table(mut base), Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                let value = match base.remove(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => value,
+                };
+                base.insert(key, value);
+            }
+            Value::Table(base)
+        }
+        (_, overlay) => overlay,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::merge;
+    use toml::Value;
+
+    #[test]
+    fn test_merge() {
+        let base: Value = "[build]\nrustflags = [\"-Copt-level=3\"]\ntarget-dir = \"foo\"\n\n\
+                           [net]\nretry = 2\n"
+            .parse()
+            .unwrap();
+        let overlay: Value = "[net]\nretry = 5\n\n[profile.dev]\ndebug = 0\n"
+            .parse()
+            .unwrap();
+        let expected: Value = "[build]\nrustflags = [\"-Copt-level=3\"]\ntarget-dir = \"foo\"\n\n\
+                               [net]\nretry = 5\n\n[profile.dev]\ndebug = 0\n"
+            .parse()
+            .unwrap();
+
+        assert_eq!(merge(base, overlay), expected);
+    }
+}
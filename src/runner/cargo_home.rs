@@ -0,0 +1,86 @@
+//! Every build gets its own cargo home, inside its target directory. The cargo home of the
+//! workspace is shared by all the builds running at the same time, and cargo takes its locks
+//! (and writes some of its state) there even when every dependency is already fetched, which
+//! occasionally made concurrent builds interfere with each other. The caches of the shared cargo
+//! home are linked into the one of the build, so nothing has to be fetched again.
+
+use crate::prelude::*;
+use rustwide::Build;
+use std::fs;
+use std::path::Path;
+
+/// Where rustwide mounts the cargo home of the workspace and the target directory of the build
+/// in the sandbox.
+const SANDBOX_SHARED_DIR: &str = "/opt/rustwide/cargo-home";
+const SANDBOX_TARGET_DIR: &str = "/opt/rustwide/target";
+
+/// Name of the cargo home of the build in its target directory.
+const DIR_NAME: &str = "crater-cargo-home";
+
+/// Content of the shared cargo home linked into the one of the build.
+const SHARED: &[&str] = &["registry", "git", "bin", "config", "config.toml"];
+
+/// Create the cargo home of the build, returning its path inside the sandbox.
+pub(super) fn prepare(build: &Build) -> Fallible<String> {
+    let shared = crate::dirs::WORK_DIR.join("cargo-home");
+    create(&shared, &build.host_target_dir().join(DIR_NAME))?;
+    Ok(format!("{}/{}", SANDBOX_TARGET_DIR, DIR_NAME))
+}
+
+fn create(shared: &Path, dest: &Path) -> Fallible<()> {
+    fs::create_dir_all(dest)?;
+    for name in SHARED {
+        let link = dest.join(name);
+        if !shared.join(name).exists() || fs::symlink_metadata(&link).is_ok() {
+            continue;
+        }
+        // The links are resolved inside the sandbox, where the shared cargo home is mounted
+        // read-only
+        std::os::unix::fs::symlink(format!("{}/{}", SANDBOX_SHARED_DIR, name), &link)
+            .with_context(|_| format!("failed to create {}", link.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::create;
+    use std::fs;
+
+    #[test]
+    fn test_create() {
+        let dir = tempfile::tempdir().unwrap();
+        let shared = dir.path().join("cargo-home");
+        fs::create_dir_all(shared.join("registry")).unwrap();
+        fs::write(shared.join("config.toml"), "").unwrap();
+        fs::write(shared.join(".package-cache"), "").unwrap();
+
+        let dest = dir.path().join("target").join("crater-cargo-home");
+        create(&shared, &dest).unwrap();
+        // Creating it again for the next command of the build is fine
+        create(&shared, &dest).unwrap();
+
+        let mut linked = fs::read_dir(&dest)
+            .unwrap()
+            .map(|entry| {
+                let entry = entry.unwrap();
+                let target = fs::read_link(entry.path()).unwrap();
+                (entry.file_name(), target)
+            })
+            .collect::<Vec<_>>();
+        linked.sort();
+        assert_eq!(
+            linked,
+            vec![
+                (
+                    "config.toml".into(),
+                    "/opt/rustwide/cargo-home/config.toml".into()
+                ),
+                (
+                    "registry".into(),
+                    "/opt/rustwide/cargo-home/registry".into()
+                ),
+            ]
+        );
+    }
+}
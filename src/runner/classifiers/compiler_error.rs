@@ -0,0 +1,39 @@
+use crate::results::FailureReason;
+use crate::runner::classifiers::{BuildOutput, Classifier};
+
+pub(super) struct CompilerErrors;
+
+impl Classifier for CompilerErrors {
+    fn classify(&self, output: &BuildOutput) -> Option<FailureReason> {
+        if output.error_codes.is_empty() {
+            None
+        } else {
+            Some(FailureReason::CompilerError(output.error_codes.clone()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CompilerErrors;
+    use crate::results::{DiagnosticCode, FailureReason};
+    use crate::runner::classifiers::{BuildOutput, Classifier};
+
+    #[test]
+    fn test_compiler_errors() {
+        let mut output = BuildOutput::default();
+        assert_eq!(CompilerErrors.classify(&output), None);
+
+        output
+            .error_codes
+            .insert(DiagnosticCode::from("E0425".into()));
+        assert_eq!(
+            CompilerErrors.classify(&output),
+            Some(FailureReason::CompilerError(
+                vec![DiagnosticCode::from("E0425".into())]
+                    .into_iter()
+                    .collect()
+            ))
+        );
+    }
+}
@@ -0,0 +1,36 @@
+use crate::results::FailureReason;
+use crate::runner::classifiers::{BuildOutput, Classifier};
+
+pub(super) struct FailedDependencies;
+
+impl Classifier for FailedDependencies {
+    fn classify(&self, output: &BuildOutput) -> Option<FailureReason> {
+        if output.failed_deps.is_empty() {
+            None
+        } else {
+            Some(FailureReason::DependsOn(output.failed_deps.clone()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FailedDependencies;
+    use crate::crates::Crate;
+    use crate::results::FailureReason;
+    use crate::runner::classifiers::{BuildOutput, Classifier};
+
+    #[test]
+    fn test_failed_dependencies() {
+        let mut output = BuildOutput::default();
+        assert_eq!(FailedDependencies.classify(&output), None);
+
+        output.failed_deps.insert(Crate::Local("dep".into()));
+        assert_eq!(
+            FailedDependencies.classify(&output),
+            Some(FailureReason::DependsOn(
+                vec![Crate::Local("dep".into())].into_iter().collect()
+            ))
+        );
+    }
+}
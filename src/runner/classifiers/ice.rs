@@ -0,0 +1,71 @@
+use crate::results::FailureReason;
+use crate::runner::classifiers::{BuildOutput, Classifier};
+
+/// Printed by the panic hook of Clippy, asking to report the crash to its own issue tracker
+/// instead of rustc's one.
+const CLIPPY_BUG_REPORT: &str = "https://github.com/rust-lang/rust-clippy/issues";
+
+pub(super) struct ClippyCrash;
+
+impl Classifier for ClippyCrash {
+    fn classify(&self, output: &BuildOutput) -> Option<FailureReason> {
+        if output.ice
+            && output
+                .lines
+                .iter()
+                .any(|line| line.contains(CLIPPY_BUG_REPORT))
+        {
+            Some(FailureReason::ClippyICE)
+        } else {
+            None
+        }
+    }
+}
+
+pub(super) struct CompilerCrash;
+
+impl Classifier for CompilerCrash {
+    fn classify(&self, output: &BuildOutput) -> Option<FailureReason> {
+        if output.ice {
+            Some(FailureReason::ICE)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClippyCrash, CompilerCrash};
+    use crate::results::FailureReason;
+    use crate::runner::classifiers::{BuildOutput, Classifier};
+
+    #[test]
+    fn test_ice() {
+        let mut output = BuildOutput::default();
+        assert_eq!(CompilerCrash.classify(&output), None);
+
+        output.ice = true;
+        assert_eq!(CompilerCrash.classify(&output), Some(FailureReason::ICE));
+    }
+
+    #[test]
+    fn test_clippy_ice() {
+        let mut output = BuildOutput::default();
+        output.ice = true;
+        output
+            .lines
+            .push("note: the compiler unexpectedly panicked. this is a bug.".into());
+        assert_eq!(ClippyCrash.classify(&output), None);
+
+        output.lines.push(
+            "note: we would appreciate a bug report: \
+             https://github.com/rust-lang/rust-clippy/issues/new"
+                .into(),
+        );
+        assert_eq!(
+            ClippyCrash.classify(&output),
+            Some(FailureReason::ClippyICE)
+        );
+    }
+}
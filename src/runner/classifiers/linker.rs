@@ -0,0 +1,125 @@
+use crate::results::{FailureReason, LinkerFailure};
+use crate::runner::classifiers::{BuildOutput, Classifier};
+
+/// Printed by rustc when the linker exited with an error, followed by the output of the linker.
+const LINKER_FAILED: &str = "error: linking with `";
+
+/// Parts of the output of the linker identifying the failure, in order of precedence. The
+/// messages of GNU ld, lld and the macOS linker are all included.
+const KINDS: &[(LinkerFailure, &[&str])] = &[
+    (
+        LinkerFailure::MissingLibrary,
+        &[
+            "cannot find -l",
+            "unable to find library -l",
+            "library not found for -l",
+        ],
+    ),
+    (
+        LinkerFailure::Relocation,
+        &[
+            "relocation R_",
+            "recompile with -fPIC",
+            "relocation overflow",
+        ],
+    ),
+    (
+        LinkerFailure::UndefinedSymbol,
+        &[
+            "undefined reference to",
+            "undefined symbol:",
+            "Undefined symbols for architecture",
+        ],
+    ),
+    (LinkerFailure::Lld, &["rust-lld", "ld.lld"]),
+];
+
+pub(super) struct LinkerError;
+
+impl Classifier for LinkerError {
+    fn classify(&self, output: &BuildOutput) -> Option<FailureReason> {
+        let failed = !output.linker_errors.is_empty()
+            || output.lines.iter().any(|line| line.contains(LINKER_FAILED));
+        if !failed {
+            return None;
+        }
+
+        let text = output
+            .linker_errors
+            .iter()
+            .chain(output.lines.iter())
+            .collect::<Vec<_>>();
+        let kind = KINDS
+            .iter()
+            .find(|(_, patterns)| {
+                text.iter()
+                    .any(|line| patterns.iter().any(|pattern| line.contains(pattern)))
+            })
+            .map(|(kind, _)| *kind)
+            .unwrap_or(LinkerFailure::Other);
+        Some(FailureReason::LinkerError(kind))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LinkerError;
+    use crate::results::{FailureReason, LinkerFailure};
+    use crate::runner::classifiers::{BuildOutput, Classifier};
+
+    fn linker_output(note: &str) -> BuildOutput {
+        let mut output = BuildOutput::default();
+        output.linker_errors.push(format!(
+            "error: linking with `cc` failed: exit status: 1\n  |\n  = note: {}",
+            note
+        ));
+        output
+    }
+
+    #[test]
+    fn test_linker_error() {
+        let mut output = BuildOutput::default();
+        output
+            .lines
+            .push("error: could not compile `foo` due to previous error".into());
+        assert_eq!(LinkerError.classify(&output), None);
+
+        for (note, kind) in &[
+            (
+                "/usr/bin/ld: cannot find -lssl",
+                LinkerFailure::MissingLibrary,
+            ),
+            (
+                "relocation R_X86_64_32 against `.rodata' can not be used; recompile with -fPIC",
+                LinkerFailure::Relocation,
+            ),
+            (
+                "foo.o: undefined reference to `bar'",
+                LinkerFailure::UndefinedSymbol,
+            ),
+            ("rust-lld: error: duplicate symbol: foo", LinkerFailure::Lld),
+            (
+                "collect2: error: ld returned 1 exit status",
+                LinkerFailure::Other,
+            ),
+        ] {
+            assert_eq!(
+                LinkerError.classify(&linker_output(note)),
+                Some(FailureReason::LinkerError(*kind))
+            );
+        }
+
+        // Without JSON messages the error is only present in the lines
+        let mut output = BuildOutput::default();
+        output
+            .lines
+            .push("error: linking with `cc` failed: exit status: 1".into());
+        output
+            .lines
+            .push("  = note: /usr/bin/ld: cannot find -lz".into());
+        assert_eq!(
+            LinkerError.classify(&output),
+            Some(FailureReason::LinkerError(LinkerFailure::MissingLibrary))
+        );
+    }
+}
@@ -0,0 +1,71 @@
+use crate::results::FailureReason;
+use crate::runner::classifiers::{BuildOutput, Classifier};
+
+/// Prefix of the errors emitted by Miri when the interpreted program has undefined behavior.
+const UB_ERROR: &str = "error: Undefined Behavior:";
+/// Prefix of the errors emitted by Miri when the interpreted program does something it doesn't
+/// support yet, which says nothing about the correctness of the program.
+const UNSUPPORTED_ERROR: &str = "error: unsupported operation:";
+
+pub(super) struct UndefinedBehavior;
+
+impl Classifier for UndefinedBehavior {
+    fn classify(&self, output: &BuildOutput) -> Option<FailureReason> {
+        if output.lines.iter().any(|line| line.contains(UB_ERROR)) {
+            Some(FailureReason::MiriUB)
+        } else {
+            None
+        }
+    }
+}
+
+pub(super) struct UnsupportedOperation;
+
+impl Classifier for UnsupportedOperation {
+    fn classify(&self, output: &BuildOutput) -> Option<FailureReason> {
+        if output
+            .lines
+            .iter()
+            .any(|line| line.contains(UNSUPPORTED_ERROR))
+        {
+            Some(FailureReason::MiriUnsupported)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{UndefinedBehavior, UnsupportedOperation};
+    use crate::results::FailureReason;
+    use crate::runner::classifiers::{BuildOutput, Classifier};
+
+    #[test]
+    fn test_miri() {
+        let mut output = BuildOutput::default();
+        output.lines.push("test foo ... FAILED".into());
+        assert_eq!(UndefinedBehavior.classify(&output), None);
+        assert_eq!(UnsupportedOperation.classify(&output), None);
+
+        output.lines.push(
+            "error: unsupported operation: can't call foreign function: CCRandomGenerateBytes"
+                .into(),
+        );
+        assert_eq!(UndefinedBehavior.classify(&output), None);
+        assert_eq!(
+            UnsupportedOperation.classify(&output),
+            Some(FailureReason::MiriUnsupported)
+        );
+
+        output.lines.push(
+            "error: Undefined Behavior: dereferencing pointer failed: null pointer is a \
+             dangling pointer"
+                .into(),
+        );
+        assert_eq!(
+            UndefinedBehavior.classify(&output),
+            Some(FailureReason::MiriUB)
+        );
+    }
+}
@@ -0,0 +1,112 @@
+//! Classification of the failures of the cargo invocations.
+//!
+//! Each failure category is detected by its own `Classifier`, looking at the output collected
+//! while cargo was running. Embedders can add their own classifiers with `register`, which are
+//! tried before the built-in ones.
+
+mod compiler_error;
+mod dependencies;
+mod ice;
+mod linker;
+mod miri;
+mod network;
+mod nextest;
+mod nightly;
+mod oom;
+mod processes;
+
+use crate::crates::Crate;
+use crate::prelude::*;
+use crate::results::{DiagnosticCode, FailureReason};
+use std::collections::BTreeSet;
+use std::sync::RwLock;
+
+/// Output of a failed cargo invocation, collected while it was running.
+#[derive(Debug, Default)]
+pub struct BuildOutput {
+    /// Lines printed by cargo not containing compiler messages.
+    pub lines: Vec<String>,
+    /// Codes of the errors emitted while compiling the crate being tested.
+    pub error_codes: BTreeSet<DiagnosticCode>,
+    /// Whether the compiler crashed while compiling the crate being tested.
+    pub ice: bool,
+    /// Messages emitted when linking the crate being tested failed, including the output of the
+    /// linker.
+    pub linker_errors: Vec<String>,
+    /// Dependencies of the crate being tested which failed to compile.
+    pub failed_deps: BTreeSet<Crate>,
+    /// Whether the build ran without network access, so that network errors were caused by the
+    /// sandbox blocking it.
+    pub network_disabled: bool,
+}
+
+pub trait Classifier: Send + Sync {
+    /// Return why the build failed, if this classifier recognizes the failure.
+    fn classify(&self, output: &BuildOutput) -> Option<FailureReason>;
+}
+
+/// Built-in classifiers, in order of precedence. Running out of memory often causes other errors
+/// to be reported as well (for example rustc being killed while compiling a crate), so it's
+/// checked first.
+static BUILTIN: &[&dyn Classifier] = &[
+    &oom::OutOfMemory,
+    &processes::ProcessLimit,
+    &network::NetworkBlocked,
+    &network::NetworkError,
+    &nextest::TestTimeout,
+    &ice::ClippyCrash,
+    &ice::CompilerCrash,
+    &miri::UndefinedBehavior,
+    &miri::UnsupportedOperation,
+    &nightly::RequiresNightly,
+    &linker::LinkerError,
+    &dependencies::FailedDependencies,
+    &compiler_error::CompilerErrors,
+];
+
+lazy_static! {
+    static ref CUSTOM: RwLock<Vec<Box<dyn Classifier>>> = RwLock::new(Vec::new());
+}
+
+/// Register a custom classifier, used for all the following builds.
+pub fn register(classifier: Box<dyn Classifier>) {
+    CUSTOM.write().unwrap().push(classifier);
+}
+
+/// Find why the build failed, using the first classifier recognizing the failure.
+pub fn classify(output: &BuildOutput) -> Option<FailureReason> {
+    for classifier in CUSTOM.read().unwrap().iter() {
+        if let Some(reason) = classifier.classify(output) {
+            return Some(reason);
+        }
+    }
+    for classifier in BUILTIN {
+        if let Some(reason) = classifier.classify(output) {
+            return Some(reason);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{classify, BuildOutput};
+    use crate::results::{DiagnosticCode, FailureReason};
+
+    #[test]
+    fn test_precedence() {
+        assert_eq!(classify(&BuildOutput::default()), None);
+
+        let mut output = BuildOutput::default();
+        output
+            .error_codes
+            .insert(DiagnosticCode::from("E0425".into()));
+        output.ice = true;
+        assert_eq!(classify(&output), Some(FailureReason::ICE));
+
+        output
+            .lines
+            .push("process didn't exit successfully: `rustc` (signal: 9, SIGKILL: kill)".into());
+        assert_eq!(classify(&output), Some(FailureReason::OOM));
+    }
+}
@@ -0,0 +1,96 @@
+use crate::results::sandbox::NETWORK_MESSAGES as BLOCKED_MESSAGES;
+use crate::results::FailureReason;
+use crate::runner::classifiers::{BuildOutput, Classifier};
+
+/// Parts of the messages printed by cargo, curl and git when a download failed because of the
+/// network or a timeout of the registry, rather than because the resource doesn't exist.
+const NETWORK_MESSAGES: &[&str] = &[
+    "spurious network error",
+    "Couldn't resolve host",
+    "Temporary failure in name resolution",
+    "Connection reset by peer",
+    "Connection timed out",
+    "Operation timed out",
+    "Timeout was reached",
+    "failed to download from",
+];
+
+/// Without network access in the sandbox, a failure mentioning the network is caused by the
+/// build trying to reach it (usually from a build script downloading something), which will keep
+/// failing no matter how many times it's retried.
+pub(super) struct NetworkBlocked;
+
+impl Classifier for NetworkBlocked {
+    fn classify(&self, output: &BuildOutput) -> Option<FailureReason> {
+        let blocked = output.network_disabled
+            && output
+                .lines
+                .iter()
+                .any(|line| BLOCKED_MESSAGES.iter().any(|msg| line.contains(msg)));
+        if blocked {
+            Some(FailureReason::NetworkBlocked)
+        } else {
+            None
+        }
+    }
+}
+
+pub(super) struct NetworkError;
+
+impl Classifier for NetworkError {
+    fn classify(&self, output: &BuildOutput) -> Option<FailureReason> {
+        let failed = output
+            .lines
+            .iter()
+            .any(|line| NETWORK_MESSAGES.iter().any(|msg| line.contains(msg)));
+        if failed {
+            Some(FailureReason::Network)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NetworkBlocked, NetworkError};
+    use crate::results::FailureReason;
+    use crate::runner::classifiers::{BuildOutput, Classifier};
+
+    #[test]
+    fn test_network_error() {
+        let mut output = BuildOutput::default();
+        output
+            .lines
+            .push("error: failed to download `foo v0.1.0`".into());
+        assert_eq!(NetworkError.classify(&output), None);
+
+        for line in &[
+            "warning: spurious network error (2 tries remaining): [28] Timeout was reached",
+            "Caused by: [6] Couldn't resolve host name (Could not resolve host: static.crates.io)",
+            "error: failed to download from `https://static.crates.io/crates/foo/0.1.0/download`",
+        ] {
+            let mut output = BuildOutput::default();
+            output.lines.push(line.to_string());
+            assert_eq!(NetworkError.classify(&output), Some(FailureReason::Network));
+        }
+    }
+
+    #[test]
+    fn test_network_blocked() {
+        let mut output = BuildOutput::default();
+        output
+            .lines
+            .push("curl: (6) Could not resolve host: github.com".into());
+        assert_eq!(NetworkBlocked.classify(&output), None);
+
+        output.network_disabled = true;
+        assert_eq!(
+            NetworkBlocked.classify(&output),
+            Some(FailureReason::NetworkBlocked)
+        );
+
+        output.lines = vec!["error[E0425]: cannot find value `x` in this scope".into()];
+        assert_eq!(NetworkBlocked.classify(&output), None);
+    }
+}
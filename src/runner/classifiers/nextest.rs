@@ -0,0 +1,51 @@
+use crate::results::FailureReason;
+use crate::runner::classifiers::{BuildOutput, Classifier};
+
+/// Status printed by nextest for a test killed after its slow timeout.
+const TIMEOUT_STATUS: &str = "TIMEOUT [";
+/// Status printed by nextest for a test failing on its last attempt.
+const FAIL_STATUS: &str = "FAIL [";
+
+/// Tests killed by nextest, when no other test failed: a regression in a hanging test is as
+/// likely to be spurious as a timeout of the whole build.
+pub(super) struct TestTimeout;
+
+impl Classifier for TestTimeout {
+    fn classify(&self, output: &BuildOutput) -> Option<FailureReason> {
+        let has_status = |status: &str| {
+            output
+                .lines
+                .iter()
+                .any(|line| line.trim_start().starts_with(status))
+        };
+        if has_status(TIMEOUT_STATUS) && !has_status(FAIL_STATUS) {
+            Some(FailureReason::Timeout)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TestTimeout;
+    use crate::results::FailureReason;
+    use crate::runner::classifiers::{BuildOutput, Classifier};
+
+    #[test]
+    fn test_test_timeout() {
+        let mut output = BuildOutput::default();
+        output
+            .lines
+            .push("        PASS [   0.004s] foo tests::quick".into());
+        output
+            .lines
+            .push("     TIMEOUT [ 300.002s] foo tests::hang".into());
+        assert_eq!(TestTimeout.classify(&output), Some(FailureReason::Timeout));
+
+        output
+            .lines
+            .push("        FAIL [   0.012s] foo tests::broken".into());
+        assert_eq!(TestTimeout.classify(&output), None);
+    }
+}
@@ -0,0 +1,44 @@
+use crate::results::{DiagnosticCode, FailureReason};
+use crate::runner::classifiers::{BuildOutput, Classifier};
+
+/// Error emitted by stable and beta compilers when a crate uses `#![feature]`.
+const FEATURE_ON_STABLE: &str = "E0554";
+
+pub(super) struct RequiresNightly;
+
+impl Classifier for RequiresNightly {
+    fn classify(&self, output: &BuildOutput) -> Option<FailureReason> {
+        if output
+            .error_codes
+            .contains(&DiagnosticCode::from(FEATURE_ON_STABLE.into()))
+        {
+            Some(FailureReason::RequiresNightly)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RequiresNightly;
+    use crate::results::{DiagnosticCode, FailureReason};
+    use crate::runner::classifiers::{BuildOutput, Classifier};
+
+    #[test]
+    fn test_requires_nightly() {
+        let mut output = BuildOutput::default();
+        output
+            .error_codes
+            .insert(DiagnosticCode::from("E0425".into()));
+        assert_eq!(RequiresNightly.classify(&output), None);
+
+        output
+            .error_codes
+            .insert(DiagnosticCode::from("E0554".into()));
+        assert_eq!(
+            RequiresNightly.classify(&output),
+            Some(FailureReason::RequiresNightly)
+        );
+    }
+}
@@ -0,0 +1,43 @@
+use crate::results::FailureReason;
+use crate::runner::classifiers::{BuildOutput, Classifier};
+
+/// Message printed by cargo when one of the processes it spawned was killed with SIGKILL, which
+/// inside the sandbox is only sent by the kernel when the memory limit is exceeded.
+const SIGKILL_MESSAGE: &str = "(signal: 9, SIGKILL: kill)";
+
+pub(super) struct OutOfMemory;
+
+impl Classifier for OutOfMemory {
+    fn classify(&self, output: &BuildOutput) -> Option<FailureReason> {
+        if output
+            .lines
+            .iter()
+            .any(|line| line.contains(SIGKILL_MESSAGE))
+        {
+            Some(FailureReason::OOM)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OutOfMemory;
+    use crate::results::FailureReason;
+    use crate::runner::classifiers::{BuildOutput, Classifier};
+
+    #[test]
+    fn test_oom() {
+        let mut output = BuildOutput::default();
+        output.lines.push("error: could not compile `foo`.".into());
+        assert_eq!(OutOfMemory.classify(&output), None);
+
+        output.lines.push(
+            "process didn't exit successfully: `rustc --crate-name foo` \
+             (signal: 9, SIGKILL: kill)"
+                .into(),
+        );
+        assert_eq!(OutOfMemory.classify(&output), Some(FailureReason::OOM));
+    }
+}
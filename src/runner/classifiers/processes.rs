@@ -0,0 +1,53 @@
+use crate::results::FailureReason;
+use crate::runner::classifiers::{BuildOutput, Classifier};
+
+/// Description of `EAGAIN`, returned by `fork` and `clone` when no more processes or threads can
+/// be created by the user or inside the cgroup of the sandbox.
+const EAGAIN_MESSAGE: &str = "Resource temporarily unavailable";
+/// Parts of the messages printed when spawning a process or a thread failed, by cargo, the shell
+/// and the standard library.
+const SPAWN_MESSAGES: &[&str] = &["fork", "spawn", "thread", "could not execute process"];
+
+pub(super) struct ProcessLimit;
+
+impl Classifier for ProcessLimit {
+    fn classify(&self, output: &BuildOutput) -> Option<FailureReason> {
+        let hit_limit = output.lines.iter().any(|line| {
+            line.contains(EAGAIN_MESSAGE) && SPAWN_MESSAGES.iter().any(|msg| line.contains(msg))
+        });
+        if hit_limit {
+            Some(FailureReason::ProcessLimit)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProcessLimit;
+    use crate::results::FailureReason;
+    use crate::runner::classifiers::{BuildOutput, Classifier};
+
+    #[test]
+    fn test_process_limit() {
+        let mut output = BuildOutput::default();
+        output
+            .lines
+            .push("error: failed to read `/dev/foo`: Resource temporarily unavailable".into());
+        assert_eq!(ProcessLimit.classify(&output), None);
+
+        for line in &[
+            "thread 'main' panicked at 'failed to spawn thread: Os { code: 11, kind: WouldBlock, \
+             message: \"Resource temporarily unavailable\" }'",
+            "build.sh: fork: retry: Resource temporarily unavailable",
+        ] {
+            let mut output = BuildOutput::default();
+            output.lines.push(line.to_string());
+            assert_eq!(
+                ProcessLimit.classify(&output),
+                Some(FailureReason::ProcessLimit)
+            );
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! The sandbox can't limit the size of the directories mounted into it, so the disk limit of the
+//! builds is enforced by checking the size of the target directory while the build runs. The
+//! containers using the directory are killed as soon as it grows past the limit, which stops the
+//! build before it fills the disk shared with the other builds running on the agent. The
+//! directory is left alone until the build is cleaned up, as removing the files of a build still
+//! running makes it fail in confusing ways.
+
+use crate::prelude::*;
+use crate::utils::size::Size;
+use crate::utils::workspace::{dir_size, kill_containers_using};
+use crossbeam_utils::thread::scope;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Run `f` while checking the size of `dir` every `interval`, returning its result along with
+/// whether the directory exceeded the limit.
+fn watch_with_interval<T, F: FnOnce() -> T>(
+    dir: &Path,
+    limit: Size,
+    interval: Duration,
+    f: F,
+) -> (T, bool) {
+    let exceeded = AtomicBool::new(false);
+    let (stop_send, stop_recv) = mpsc::channel::<()>();
+
+    let result = scope(|scope| {
+        let exceeded = &exceeded;
+        let watcher = scope.spawn(move || loop {
+            match stop_recv.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => {}
+                Ok(()) | Err(RecvTimeoutError::Disconnected) => return,
+            }
+
+            match dir_size(dir) {
+                Ok(size) if size > limit.to_bytes() as u64 => {
+                    error!(
+                        "the build was aborted after its target directory exceeded the disk \
+                         limit of {}",
+                        limit
+                    );
+                    exceeded.store(true, Ordering::SeqCst);
+                    if let Err(err) = kill_containers_using(dir) {
+                        warn!("failed to kill the build using {}: {}", dir.display(), err);
+                    }
+                    return;
+                }
+                Ok(_) => {}
+                // Files are created and removed while the build runs, so this can happen
+                Err(err) => debug!("failed to check the size of {}: {}", dir.display(), err),
+            }
+        });
+
+        let result = f();
+        // The watcher stops as soon as the sender is dropped
+        drop(stop_send);
+        if watcher.join().is_err() {
+            warn!("the disk limit watcher panicked");
+        }
+        result
+    });
+
+    (result, exceeded.load(Ordering::SeqCst))
+}
+
+/// Run `f` while enforcing the disk limit on `dir`, if there is one. The returned flag tells
+/// whether the limit was exceeded, in which case the build was killed.
+pub(super) fn watch<T, F: FnOnce() -> T>(dir: &Path, limit: Option<Size>, f: F) -> (T, bool) {
+    match limit {
+        Some(limit) => watch_with_interval(dir, limit, CHECK_INTERVAL, f),
+        None => (f(), false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::watch_with_interval;
+    use crate::utils::size::Size;
+    use std::fs;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_watch() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target");
+        fs::create_dir_all(&target).unwrap();
+        fs::write(target.join("small"), [0; 100]).unwrap();
+
+        let interval = Duration::from_millis(10);
+        let (result, exceeded) = watch_with_interval(&target, Size::Kilobytes(1), interval, || {
+            thread::sleep(Duration::from_millis(50));
+            42
+        });
+        assert_eq!(result, 42);
+        assert!(!exceeded);
+        assert!(target.exists());
+
+        let (_, exceeded) = watch_with_interval(&target, Size::Kilobytes(1), interval, || {
+            fs::write(target.join("big"), [0; 2048]).unwrap();
+            thread::sleep(Duration::from_millis(100));
+        });
+        assert!(exceeded);
+        // The files are kept until the build is cleaned up
+        assert!(target.join("big").exists());
+    }
+}
@@ -0,0 +1,108 @@
+//! Custom DNS servers and `/etc/hosts` entries of the sandbox, for the builds with network access
+//! that must reach internal mirrors. Docker doesn't manage the files of the container mounted by
+//! the user, so crater writes its own in the work directory and mounts them in the sandbox.
+
+use crate::config::Config;
+use crate::prelude::*;
+use rustwide::cmd::{MountKind, SandboxBuilder};
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+const DIR: &str = "sandbox-dns";
+const DEFAULT_HOSTS: &str = "127.0.0.1\tlocalhost\n::1\tlocalhost ip6-localhost ip6-loopback\n";
+
+fn dir() -> PathBuf {
+    crate::dirs::WORK_DIR.join(DIR)
+}
+
+/// Write the files mounted in the sandbox by `mount`. This must be called before any build is
+/// started.
+pub(super) fn prepare(config: &Config) -> Fallible<()> {
+    let dir = dir();
+    fs::create_dir_all(&dir)?;
+    if !config.sandbox.dns.is_empty() {
+        fs::write(dir.join("resolv.conf"), resolv_conf(&config.sandbox.dns)?)?;
+    }
+    if !config.sandbox.extra_hosts.is_empty() {
+        fs::write(dir.join("hosts"), hosts(&config.sandbox.extra_hosts)?)?;
+    }
+    Ok(())
+}
+
+/// Mount the DNS settings in the sandbox, replacing the ones Docker copies from the host.
+pub(super) fn mount(mut sandbox: SandboxBuilder, config: &Config) -> SandboxBuilder {
+    let dir = dir();
+    if !config.sandbox.dns.is_empty() {
+        let resolv_conf = dir.join("resolv.conf");
+        sandbox = sandbox.mount(
+            &resolv_conf,
+            Path::new("/etc/resolv.conf"),
+            MountKind::ReadOnly,
+        );
+    }
+    if !config.sandbox.extra_hosts.is_empty() {
+        let hosts = dir.join("hosts");
+        sandbox = sandbox.mount(&hosts, Path::new("/etc/hosts"), MountKind::ReadOnly);
+    }
+    sandbox
+}
+
+fn resolv_conf(servers: &[String]) -> Fallible<String> {
+    let mut content = String::new();
+    for server in servers {
+        let ip: IpAddr = server
+            .parse()
+            .with_context(|_| format!("invalid DNS server: {}", server))?;
+        content.push_str(&format!("nameserver {}\n", ip));
+    }
+    Ok(content)
+}
+
+/// Content of `/etc/hosts`, with the extra entries written as `name:ip` like the `--add-host`
+/// flag of Docker.
+fn hosts(entries: &[String]) -> Fallible<String> {
+    let mut content = DEFAULT_HOSTS.to_string();
+    for entry in entries {
+        let mut parts = entry.splitn(2, ':');
+        let name = parts.next().unwrap_or("");
+        let ip = parts.next().and_then(|ip| ip.parse::<IpAddr>().ok());
+        match ip {
+            Some(ip) if !name.is_empty() => content.push_str(&format!("{}\t{}\n", ip, name)),
+            _ => bail!("invalid extra host (expected `name:ip`): {}", entry),
+        }
+    }
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hosts, resolv_conf, DEFAULT_HOSTS};
+
+    #[test]
+    fn test_resolv_conf() {
+        assert_eq!(
+            resolv_conf(&["10.0.0.2".into(), "fd00::53".into()]).unwrap(),
+            "nameserver 10.0.0.2\nnameserver fd00::53\n"
+        );
+        assert!(resolv_conf(&["dns.internal".into()]).is_err());
+    }
+
+    #[test]
+    fn test_hosts() {
+        assert_eq!(
+            hosts(&[
+                "mirror.internal:10.0.0.5".into(),
+                "v6.internal:fd00::5".into()
+            ])
+            .unwrap(),
+            format!(
+                "{}10.0.0.5\tmirror.internal\nfd00::5\tv6.internal\n",
+                DEFAULT_HOSTS
+            )
+        );
+        assert!(hosts(&["mirror.internal".into()]).is_err());
+        assert!(hosts(&[":10.0.0.5".into()]).is_err());
+        assert!(hosts(&["mirror.internal:mirror".into()]).is_err());
+    }
+}
@@ -0,0 +1,309 @@
+use crate::config::FetchConfig;
+use crate::crates::{Crate, RegistryCrate};
+use crate::prelude::*;
+use crate::results::{FailureReason, TestResult};
+use crate::runner::test::detect_broken;
+use crate::runner::OverrideResult;
+use crate::utils;
+use failure::Error;
+use http::Method;
+use rand::{thread_rng, Rng};
+use rustwide::{PrepareError, Workspace};
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Fetch the source of a crate. Registry crates missing from the local cache are first looked up in
+/// the shared cache, if one is configured, and uploaded to it after being downloaded from
+/// crates.io, so that each crate is downloaded only once by the whole fleet of agents.
+pub(super) fn fetch(workspace: &Workspace, krate: &Crate, config: &FetchConfig) -> Fallible<()> {
+    let cached = match krate {
+        Crate::Registry(details) => {
+            registry_cache_path(details).exists() || fetch_from_shared_cache(config, details)
+        }
+        _ => true,
+    };
+
+    if let Some(depth) = config.git_depth {
+        shallow_clone(krate, depth);
+    }
+
+    fetch_with_retries(workspace, krate, config)?;
+
+    if let (Crate::Registry(details), false) = (krate, cached) {
+        upload_to_shared_cache(config, details);
+    }
+    Ok(())
+}
+
+/// Fetch the source of a crate, retrying network failures with exponential backoff. If all the
+/// retries fail registry crates are downloaded from the configured mirrors, and if those fail too
+/// the crate is marked as `build-fail:fetch-failed`.
+fn fetch_with_retries(workspace: &Workspace, krate: &Crate, config: &FetchConfig) -> Fallible<()> {
+    let rustwide_crate = krate.to_rustwide();
+
+    let mut attempt = 0;
+    let err = loop {
+        let err = match detect_broken(rustwide_crate.fetch(workspace)) {
+            Ok(()) => return Ok(()),
+            Err(err) => err,
+        };
+        if !is_retryable(&err) {
+            return Err(err);
+        }
+        if attempt >= config.retries {
+            break err;
+        }
+
+        let delay = backoff(config.backoff, attempt);
+        warn!(
+            "failed to fetch {} (attempt {}/{}), retrying in {:?}",
+            krate,
+            attempt + 1,
+            config.retries + 1,
+            delay
+        );
+        utils::report_failure(&err);
+        thread::sleep(delay);
+        attempt += 1;
+    };
+
+    if let Crate::Registry(details) = krate {
+        for mirror in &config.mirrors {
+            match fetch_from_mirror(mirror, details) {
+                // The crate is now in the cache, so rustwide won't download it again
+                Ok(()) => return detect_broken(rustwide_crate.fetch(workspace)),
+                Err(mirror_err) => {
+                    warn!("failed to fetch {} from mirror {}", krate, mirror);
+                    utils::report_failure(&mirror_err);
+                }
+            }
+        }
+    }
+
+    Err(err
+        .context(OverrideResult(TestResult::BuildFail(
+            FailureReason::FetchFailed,
+        )))
+        .into())
+}
+
+/// Broken crates and missing files are not going to be fixed by retrying.
+fn is_retryable(err: &Error) -> bool {
+    for cause in err.iter_chain() {
+        if cause.downcast_ref::<OverrideResult>().is_some()
+            || cause.downcast_ref::<PrepareError>().is_some()
+        {
+            return false;
+        }
+        if let Some(err) = cause.downcast_ref::<reqwest::Error>() {
+            if err.status().map(|s| s.is_client_error()).unwrap_or(false) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+pub(super) fn backoff(base: u64, attempt: u32) -> Duration {
+    let delay = Duration::from_secs(base.saturating_mul(1 << attempt.min(16)));
+    let delay = std::cmp::min(delay, MAX_BACKOFF);
+    let jitter = thread_rng().gen_range(0, delay.as_millis() as u64 / 2 + 1);
+    delay + Duration::from_millis(jitter)
+}
+
+/// Path of the crate tarball in the rustwide cache, which is located inside the work directory.
+fn registry_cache_path(krate: &RegistryCrate) -> PathBuf {
+    crate::dirs::WORK_DIR
+        .join("cache")
+        .join("reg")
+        .join(&krate.name)
+        .join(format!("{}-{}.crate", krate.name, krate.version))
+}
+
+/// Path of a bare clone of a git repository in the rustwide cache, which is named after the URL
+/// of the repository.
+fn git_cache_path(url: &str) -> PathBuf {
+    let mut escaped = String::new();
+    for byte in url.bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.' => {
+                escaped.push(byte as char)
+            }
+            other => escaped.push_str(&format!("%{:02X}", other)),
+        }
+    }
+    crate::dirs::WORK_DIR
+        .join("cache")
+        .join("git-repos")
+        .join(escaped)
+}
+
+/// Clone a git repository missing from the rustwide cache with a limited history, before rustwide
+/// fetches it. Failures are only reported, as rustwide then clones the whole repository itself.
+fn shallow_clone(krate: &Crate, depth: u32) {
+    let url = match krate {
+        Crate::GitHub(repo) => repo.clone_url(),
+        Crate::Git(repo) => repo.url.clone(),
+        _ => return,
+    };
+    let dest = git_cache_path(&url);
+    if dest.exists() {
+        return;
+    }
+
+    info!("cloning {} with a depth of {}", url, depth);
+    // Cloning in a temporary directory prevents rustwide from seeing a partial clone
+    let tmp = dest.with_extension("part");
+    let _ = fs::remove_dir_all(&tmp);
+    let res = Command::new("git")
+        .args(&["clone", "--bare", "--depth", &depth.to_string(), &url])
+        .arg(&tmp)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .status();
+    match res {
+        Ok(status) if status.success() => {
+            if let Err(err) = fs::rename(&tmp, &dest) {
+                warn!("failed to move the shallow clone of {}: {}", url, err);
+            }
+        }
+        Ok(status) => warn!("shallow clone of {} failed with {}", url, status),
+        Err(err) => warn!("failed to run git to clone {}: {}", url, err),
+    }
+    let _ = fs::remove_dir_all(&tmp);
+}
+
+/// URL of a crate tarball on a mirror or on the shared cache, which use the same layout.
+fn crate_url(base: &str, krate: &RegistryCrate) -> String {
+    format!(
+        "{0}/{1}/{1}-{2}.crate",
+        base.trim_end_matches('/'),
+        krate.name,
+        krate.version
+    )
+}
+
+fn fetch_from_mirror(mirror: &str, krate: &RegistryCrate) -> Fallible<()> {
+    let url = crate_url(mirror, krate);
+    info!("fetching {} from {}", krate.name, url);
+    download(&url, krate)
+}
+
+/// Download a crate from the shared cache into the local cache, returning whether it was found.
+fn fetch_from_shared_cache(config: &FetchConfig, krate: &RegistryCrate) -> bool {
+    let cache = match &config.shared_cache {
+        Some(cache) => cache,
+        None => return false,
+    };
+    match download(&crate_url(cache, krate), krate) {
+        Ok(()) => true,
+        Err(err) => {
+            debug!(
+                "{} {} is not in the shared cache: {}",
+                krate.name, krate.version, err
+            );
+            false
+        }
+    }
+}
+
+/// Upload a crate from the local cache to the shared cache. Failures are only reported, as the
+/// crate can still be downloaded from crates.io by the other agents.
+fn upload_to_shared_cache(config: &FetchConfig, krate: &RegistryCrate) {
+    let cache = match &config.shared_cache {
+        Some(cache) if config.shared_cache_upload => cache,
+        _ => return,
+    };
+    if let Err(err) = upload(&crate_url(cache, krate), krate) {
+        warn!("failed to upload {} to the shared cache", krate.name);
+        utils::report_failure(&err);
+    }
+}
+
+fn upload(url: &str, krate: &RegistryCrate) -> Fallible<()> {
+    let file = File::open(registry_cache_path(krate))?;
+    let resp = utils::http::prepare_sync(Method::PUT, url)
+        .body(file)
+        .send()?;
+    if !resp.status().is_success() {
+        bail!(
+            "uploading to {} returned status code {}",
+            url,
+            resp.status()
+        );
+    }
+    Ok(())
+}
+
+fn download(url: &str, krate: &RegistryCrate) -> Fallible<()> {
+    let dest = registry_cache_path(krate);
+    let tmp = dest.with_extension("crate.part");
+    fs::create_dir_all(dest.parent().unwrap())?;
+
+    let mut resp = utils::http::get_sync(&url)?;
+    resp.copy_to(&mut BufWriter::new(File::create(&tmp)?))?;
+    fs::rename(&tmp, &dest)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{backoff, crate_url, git_cache_path, is_retryable, MAX_BACKOFF};
+    use crate::crates::RegistryCrate;
+    use crate::results::TestResult;
+    use crate::runner::OverrideResult;
+    use failure::err_msg;
+    use std::time::Duration;
+
+    #[test]
+    fn test_backoff() {
+        for attempt in 0..4 {
+            let expected = Duration::from_secs(2 << attempt);
+            let delay = backoff(2, attempt);
+            assert!(delay >= expected);
+            assert!(delay <= expected + expected / 2);
+        }
+
+        let delay = backoff(2, 30);
+        assert!(delay >= MAX_BACKOFF);
+        assert!(delay <= MAX_BACKOFF + MAX_BACKOFF / 2);
+    }
+
+    #[test]
+    fn test_crate_url() {
+        let krate = RegistryCrate {
+            name: "syn".into(),
+            version: "1.0.0".into(),
+        };
+        assert_eq!(
+            crate_url("http://cache.internal/", &krate),
+            "http://cache.internal/syn/syn-1.0.0.crate"
+        );
+    }
+
+    #[test]
+    fn test_git_cache_path() {
+        assert_eq!(
+            git_cache_path("https://github.com/rust-lang/crater")
+                .file_name()
+                .unwrap(),
+            "https%3A%2F%2Fgithub.com%2Frust-lang%2Fcrater"
+        );
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        assert!(is_retryable(&err_msg("connection reset")));
+        assert!(!is_retryable(
+            &err_msg("broken")
+                .context(OverrideResult(TestResult::Error))
+                .into()
+        ));
+    }
+}
@@ -256,7 +256,7 @@ pub(super) fn build_graph(ex: &Experiment, crates: &[Crate], config: &Config) ->
 
     for krate in crates {
         if !ex.ignore_blacklist && config.should_skip(krate) {
-            for tc in &ex.toolchains {
+            for tc in ex.tested_toolchains() {
                 let id = graph.add_task(
                     Task {
                         krate: krate.clone(),
@@ -269,6 +269,21 @@ pub(super) fn build_graph(ex: &Experiment, crates: &[Crate], config: &Config) ->
             continue;
         }
 
+        // Crates restricted to other platforms are not built at all on this agent.
+        if !config.is_applicable(krate, crate::HOST_TARGET) {
+            for tc in ex.tested_toolchains() {
+                let id = graph.add_task(
+                    Task {
+                        krate: krate.clone(),
+                        step: TaskStep::NotApplicable { tc: tc.clone() },
+                    },
+                    &[],
+                );
+                graph.add_crate(&[id]);
+            }
+            continue;
+        }
+
         let prepare_id = graph.add_task(
             Task {
                 krate: krate.clone(),
@@ -278,8 +293,33 @@ pub(super) fn build_graph(ex: &Experiment, crates: &[Crate], config: &Config) ->
         );
 
         let quiet = config.is_quiet(krate);
+
+        // In check-then-test mode both toolchains are checked first, and the full builds are
+        // executed only after both check results are available.
+        let mut build_deps = vec![prepare_id];
+        if ex.mode == Mode::CheckThenTest
+            && (ex.ignore_blacklist || !config.should_skip_tests(krate))
+        {
+            build_deps = ex
+                .tested_toolchains()
+                .iter()
+                .map(|tc| {
+                    graph.add_task(
+                        Task {
+                            krate: krate.clone(),
+                            step: TaskStep::PreliminaryCheck {
+                                tc: tc.clone(),
+                                quiet,
+                            },
+                        },
+                        &[prepare_id],
+                    )
+                })
+                .collect();
+        }
+
         let mut builds = Vec::new();
-        for tc in &ex.toolchains {
+        for tc in ex.tested_toolchains() {
             let build_id = graph.add_task(
                 Task {
                     krate: krate.clone(),
@@ -304,18 +344,48 @@ pub(super) fn build_graph(ex: &Experiment, crates: &[Crate], config: &Config) ->
                             tc: tc.clone(),
                             quiet,
                         },
+                        Mode::CheckThenTest
+                            if !ex.ignore_blacklist && config.should_skip_tests(krate) =>
+                        {
+                            TaskStep::BuildOnly {
+                                tc: tc.clone(),
+                                quiet,
+                            }
+                        }
+                        Mode::CheckThenTest => TaskStep::TestIfChanged {
+                            tc: tc.clone(),
+                            quiet,
+                        },
                         Mode::Clippy => TaskStep::Clippy {
                             tc: tc.clone(),
                             quiet,
                         },
-                        Mode::Rustdoc => TaskStep::Rustdoc {
+                        Mode::Rustdoc | Mode::RustdocStrict => TaskStep::Rustdoc {
                             tc: tc.clone(),
                             quiet,
                         },
                         Mode::UnstableFeatures => TaskStep::UnstableFeatures { tc: tc.clone() },
+                        Mode::Msrv if *tc == ex.toolchains[1] => TaskStep::Msrv {
+                            tc: tc.clone(),
+                            quiet,
+                        },
+                        Mode::Msrv => TaskStep::BuildOnly {
+                            tc: tc.clone(),
+                            quiet,
+                        },
+                        Mode::Miri if !ex.ignore_blacklist && config.should_skip_tests(krate) => {
+                            TaskStep::BuildOnly {
+                                tc: tc.clone(),
+                                quiet,
+                            }
+                        }
+                        Mode::Miri => TaskStep::Miri {
+                            tc: tc.clone(),
+                            quiet,
+                        },
                     },
                 },
-                &[prepare_id],
+                &build_deps,
             );
 
             builds.push(build_id);
@@ -1,22 +1,41 @@
+mod artifacts;
+mod bundle;
+mod cargo_config;
+// The Windows sandbox keeps using the shared cargo home
+#[cfg(unix)]
+mod cargo_home;
+pub mod classifiers;
+mod disk_limit;
+mod dns;
+mod fetch;
 mod graph;
+mod msrv;
+mod nextest;
+mod output;
+mod sandbox_images;
 mod tasks;
 mod test;
 mod unstable_features;
 mod worker;
 
+pub use self::bundle::{prepare_into, BundleMetadata, BUNDLE_METADATA_FILE};
+
 use crate::config::Config;
 use crate::crates::Crate;
-use crate::experiments::{Experiment, Mode};
+use crate::experiments::{Experiment, Mode, TestRunner};
 use crate::prelude::*;
-use crate::results::{TestResult, WriteResults};
+use crate::results::{RecordSandboxImage, TestResult, WriteResults};
 use crate::runner::graph::build_graph;
+use crate::runner::sandbox_images::SandboxImages;
 use crate::runner::worker::{DiskSpaceWatcher, Worker};
+use crate::toolchain::Toolchain;
+use cargo_metadata::PackageId;
 use crossbeam_utils::thread::{scope, ScopedJoinHandle};
 use rustwide::logging::LogStorage;
 use rustwide::Workspace;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
-use std::sync::Mutex;
+use std::sync::{Mutex, RwLock};
 use std::thread;
 use std::time::Duration;
 
@@ -29,10 +48,19 @@ pub struct OverrideResult(TestResult);
 
 struct RunnerStateInner {
     prepare_logs: HashMap<Crate, LogStorage>,
+    check_results: HashMap<(Crate, Toolchain), (TestResult, LogStorage)>,
+    /// The local packages of a crate are the same on every toolchain, so they're only queried
+    /// once per crate instead of starting a container to run `cargo metadata` for every build.
+    local_packages: HashMap<Crate, HashSet<PackageId>>,
+    /// Timeouts learned by the server, queried once per crate for the same reason.
+    timeouts: HashMap<Crate, Option<Duration>>,
 }
 
 struct RunnerState {
     inner: Mutex<RunnerStateInner>,
+    /// Held for reading by every build, and for writing by the heavyweight retries so that they
+    /// run alone on the agent.
+    heavyweight_lane: RwLock<()>,
 }
 
 impl RunnerState {
@@ -40,7 +68,11 @@ impl RunnerState {
         RunnerState {
             inner: Mutex::new(RunnerStateInner {
                 prepare_logs: HashMap::new(),
+                check_results: HashMap::new(),
+                local_packages: HashMap::new(),
+                timeouts: HashMap::new(),
             }),
+            heavyweight_lane: RwLock::new(()),
         }
     }
 
@@ -49,7 +81,7 @@ impl RunnerState {
     }
 }
 
-pub fn run_ex<DB: WriteResults + Sync>(
+pub fn run_ex<DB: WriteResults + RecordSandboxImage + Sync>(
     ex: &Experiment,
     workspace: &Workspace,
     crates: &[Crate],
@@ -57,20 +89,43 @@ pub fn run_ex<DB: WriteResults + Sync>(
     threads_count: usize,
     config: &Config,
 ) -> Fallible<()> {
+    // Docker containers on macOS run in a Linux VM, so builds wouldn't test macOS at all
+    if cfg!(target_os = "macos") && !config.sandbox.allow_macos_linux_vm {
+        bail!(
+            "running builds on macOS is not supported, as there is no native sandbox for it \
+             (set `sandbox.allow-macos-linux-vm` to build in the Linux VM of Docker anyway)"
+        );
+    }
     if !rustwide::cmd::docker_running(workspace) {
         return Err(err_msg("docker is not running"));
     }
+    crate::utils::cgroup::check_resource_limits();
 
     info!("computing the tasks graph...");
     let graph = Mutex::new(build_graph(ex, crates, config));
 
     info!("preparing the execution...");
-    for tc in &ex.toolchains {
+    for (i, tc) in ex.tested_toolchains().iter().enumerate() {
+        // In MSRV mode the second toolchain is replaced by the MSRV of each crate, which is
+        // installed when the crate is built
+        if ex.mode == Mode::Msrv && i == 1 {
+            continue;
+        }
         tc.install(workspace)?;
         if ex.mode == Mode::Clippy {
             tc.add_component(workspace, "clippy")?;
         }
+        // Miri needs the source of the standard library to build its own sysroot
+        if ex.mode == Mode::Miri {
+            tc.add_component(workspace, "miri")?;
+            tc.add_component(workspace, "rust-src")?;
+        }
+    }
+    if ex.test_runner == TestRunner::Nextest {
+        nextest::install()?;
     }
+    dns::prepare(config)?;
+    let images = SandboxImages::new(ex, crates, config, workspace, db)?;
 
     info!("running tasks in {} threads...", threads_count);
 
@@ -83,12 +138,11 @@ pub fn run_ex<DB: WriteResults + Sync>(
         .map(|i| {
             Worker::new(
                 format!("worker-{}", i),
-                workspace,
+                &images,
                 ex,
                 config,
                 &graph,
                 &state,
-                db,
                 &parked_threads,
             )
         })
@@ -128,7 +182,7 @@ pub fn run_ex<DB: WriteResults + Sync>(
 
     // Only the root node must be present
     let mut g = graph.lock().unwrap();
-    assert!(g.next_task(ex, db, "master").is_finished());
+    assert!(g.next_task(ex, &images, "master").is_finished());
     assert_eq!(g.pending_crates_count(), 0);
 
     Ok(())
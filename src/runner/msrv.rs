@@ -0,0 +1,139 @@
+use crate::crates::Crate;
+use crate::prelude::*;
+use crate::results::{EncodingType, TestResult, WriteResults};
+use crate::runner::tasks::TaskCtx;
+use crate::runner::test;
+use crate::toolchain::Toolchain;
+use rustwide::{Toolchain as RustwideToolchain, Workspace};
+use std::fs;
+use std::sync::Mutex;
+use toml::Value;
+
+lazy_static! {
+    // rustup doesn't like installing the same toolchain from multiple threads at the same time
+    static ref INSTALL_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// Read the minimum supported Rust version declared in the `rust-version` field of a manifest.
+/// Versions inherited from the workspace are not supported, and are treated as missing.
+fn declared_msrv(manifest: &str) -> Fallible<Option<String>> {
+    let manifest: Value = manifest.parse()?;
+    Ok(manifest
+        .get("package")
+        .and_then(|package| package.get("rust-version"))
+        .and_then(Value::as_str)
+        .map(|version| {
+            // `1.56` means 1.56.0, while rustup would install the latest 1.56.x release
+            if version.split('.').count() == 2 {
+                format!("{}.0", version)
+            } else {
+                version.to_string()
+            }
+        }))
+}
+
+/// Get the toolchain matching the MSRV declared by the crate, carrying over the flags and the
+/// patches of the experiment toolchain it replaces.
+fn msrv_toolchain(
+    workspace: &Workspace,
+    krate: &Crate,
+    base: &Toolchain,
+) -> Fallible<Option<Toolchain>> {
+    let dir = tempfile::tempdir()?;
+    krate.to_rustwide().copy_source_to(workspace, dir.path())?;
+    let manifest_dir = dir.path().join(krate.subpath().unwrap_or(""));
+    let manifest = fs::read_to_string(manifest_dir.join("Cargo.toml"))?;
+
+    Ok(declared_msrv(&manifest)?.map(|version| Toolchain {
+        source: RustwideToolchain::dist(&version),
+        ..base.clone()
+    }))
+}
+
+/// Build the crate with the toolchain matching its declared MSRV, recording the result as the
+/// result of the current toolchain of the experiment. Crates without a declared MSRV are marked
+/// as not applicable.
+pub(super) fn run_msrv<DB: WriteResults>(ctx: &TaskCtx<DB>) -> Fallible<()> {
+    if let Some(res) = ctx
+        .db
+        .get_result(ctx.experiment, ctx.toolchain, ctx.krate)?
+    {
+        info!("skipping crate {}. existing result: {}", ctx.krate, res);
+        return Ok(());
+    }
+
+    let log_storage = ctx
+        .state
+        .lock()
+        .prepare_logs
+        .get(&ctx.krate)
+        .map(|s| s.duplicate());
+    ctx.db.record_result(
+        ctx.experiment,
+        ctx.toolchain,
+        ctx.krate,
+        log_storage,
+        ctx.config,
+        EncodingType::Plain,
+        || {
+            let msrv = match msrv_toolchain(ctx.workspace, ctx.krate, ctx.toolchain)? {
+                Some(msrv) => msrv,
+                None => {
+                    warn!(
+                        "{} doesn't declare its minimum supported rust version",
+                        ctx.krate
+                    );
+                    return Ok(TestResult::NotApplicable);
+                }
+            };
+
+            info!(
+                "building {} against its declared msrv {} for {}",
+                ctx.krate,
+                msrv.to_string(),
+                ctx.experiment.name
+            );
+            {
+                let _lock = INSTALL_LOCK.lock().unwrap();
+                msrv.install(ctx.workspace)?;
+            }
+
+            let msrv_ctx = TaskCtx {
+                toolchain: &msrv,
+                ..*ctx
+            };
+            test::run_in_build(&msrv_ctx, test::test_build_only)
+        },
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::declared_msrv;
+
+    #[test]
+    fn test_declared_msrv() {
+        let manifest = |extra: &str| {
+            format!(
+                "[package]\nname = \"foo\"\nversion = \"0.1.0\"\n{}\n[dependencies]\n",
+                extra
+            )
+        };
+
+        assert_eq!(declared_msrv(&manifest("")).unwrap(), None);
+        assert_eq!(
+            declared_msrv(&manifest("rust-version = \"1.56\"")).unwrap(),
+            Some("1.56.0".into())
+        );
+        assert_eq!(
+            declared_msrv(&manifest("rust-version = \"1.60.1\"")).unwrap(),
+            Some("1.60.1".into())
+        );
+        assert_eq!(
+            declared_msrv(&manifest("[package.rust-version]\nworkspace = true")).unwrap(),
+            None
+        );
+        assert!(declared_msrv("[package").is_err());
+    }
+}
@@ -0,0 +1,93 @@
+use crate::prelude::*;
+use crate::utils;
+use flate2::read::GzDecoder;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tar::Archive;
+
+const RELEASE_URL: &str = "https://get.nexte.st/latest/linux";
+const BINARY: &str = "cargo-nextest";
+
+/// Name of the nextest configuration written by crater in the cargo home.
+const TOOL_CONFIG: &str = "crater-nextest.toml";
+
+/// Tests running for more than 5 minutes are killed, instead of waiting for the timeout of the
+/// whole build.
+const TOOL_CONFIG_CONTENT: &str = "\
+[profile.default]
+slow-timeout = { period = \"60s\", terminate-after = 5 }
+retries = 2
+fail-fast = false
+";
+
+/// Arguments passed to cargo to run the tests with nextest. The cargo home is mounted in the
+/// sandbox by rustwide, which also adds its `bin` directory to the `PATH`.
+pub(super) const CARGO_ARGS: &[&str] = &[
+    "nextest",
+    "run",
+    "--frozen",
+    "--tool-config-file",
+    "crater:/opt/rustwide/cargo-home/crater-nextest.toml",
+];
+
+fn cargo_home() -> PathBuf {
+    crate::dirs::WORK_DIR.join("cargo-home")
+}
+
+/// Install the latest release of cargo-nextest in the cargo home of the workspace, unless it's
+/// already there, and write the configuration used by crater.
+pub(super) fn install() -> Fallible<()> {
+    let home = cargo_home();
+    let bin = home.join("bin");
+    if !bin.join(BINARY).exists() {
+        info!("installing cargo-nextest from {}", RELEASE_URL);
+        fs::create_dir_all(&bin)?;
+        unpack(utils::http::get_sync(RELEASE_URL)?, &bin)?;
+        if !bin.join(BINARY).exists() {
+            bail!(
+                "the cargo-nextest release doesn't contain the {} binary",
+                BINARY
+            );
+        }
+    }
+    fs::write(home.join(TOOL_CONFIG), TOOL_CONFIG_CONTENT)?;
+    Ok(())
+}
+
+fn unpack<R: std::io::Read>(tarball: R, dest: &Path) -> Fallible<()> {
+    let mut archive = Archive::new(GzDecoder::new(tarball));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.path()?.file_name() == Some(BINARY.as_ref()) {
+            entry.unpack(dest.join(BINARY))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{unpack, BINARY};
+    use flate2::{write::GzEncoder, Compression};
+    use tar::{Builder, Header};
+
+    #[test]
+    fn test_unpack() {
+        let mut builder = Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        for name in &["README.md", BINARY] {
+            let mut header = Header::new_gnu();
+            header.set_size(4);
+            header.set_mode(0o755);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, &b"test"[..])
+                .unwrap();
+        }
+        let tarball = builder.into_inner().unwrap().finish().unwrap();
+
+        let dest = tempfile::tempdir().unwrap();
+        unpack(tarball.as_slice(), dest.path()).unwrap();
+        assert_eq!(std::fs::read(dest.path().join(BINARY)).unwrap(), b"test");
+        assert!(!dest.path().join("README.md").exists());
+    }
+}
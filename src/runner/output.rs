@@ -0,0 +1,294 @@
+use crate::crates::Crate;
+use crate::results::DiagnosticCode;
+use crate::runner::classifiers::BuildOutput;
+use cargo_metadata::diagnostic::DiagnosticLevel;
+use cargo_metadata::{Message, PackageId};
+use std::collections::{BTreeSet, HashSet};
+use std::convert::TryFrom;
+
+/// Plain lines kept in memory for the classifiers. Test suites can print a lot, so once twice this
+/// amount is reached the oldest lines are discarded.
+const MAX_LINES: usize = 10_000;
+
+/// What to do with a line printed by cargo, before it's stored in the log.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum LineAction {
+    Keep,
+    Remove,
+    /// Replace the JSON message with the diagnostic rendered by the compiler.
+    Replace(String),
+}
+
+/// Collects everything needed to classify the result of a cargo invocation from its output, one
+/// line at a time. It doesn't depend on the sandbox, so scripted outputs can be fed to it in the
+/// tests.
+pub(super) struct OutputCollector<'a> {
+    local_packages: &'a HashSet<PackageId>,
+    deny_lints: &'a [String],
+    pub(super) output: BuildOutput,
+    pub(super) warnings: usize,
+    pub(super) denied_lints: BTreeSet<DiagnosticCode>,
+    /// Clippy lints emitted at least once by the crate being tested.
+    pub(super) clippy_lints: BTreeSet<DiagnosticCode>,
+}
+
+impl<'a> OutputCollector<'a> {
+    pub(super) fn new(local_packages: &'a HashSet<PackageId>, deny_lints: &'a [String]) -> Self {
+        OutputCollector {
+            local_packages,
+            deny_lints,
+            output: BuildOutput::default(),
+            warnings: 0,
+            denied_lints: BTreeSet::new(),
+            clippy_lints: BTreeSet::new(),
+        }
+    }
+
+    /// Record a line not containing a compiler message.
+    pub(super) fn push_line(&mut self, line: &str) {
+        if self.output.lines.len() >= MAX_LINES * 2 {
+            self.output.lines.drain(..MAX_LINES);
+        }
+        self.output.lines.push(line.to_string());
+    }
+
+    pub(super) fn process_line(&mut self, line: &str) -> LineAction {
+        // Avoid trying to deserialize non JSON output
+        if !line.starts_with('{') {
+            self.push_line(line);
+            return LineAction::Keep;
+        }
+
+        let message = match serde_json::from_str(line) {
+            Ok(message) => message,
+            Err(_) => return LineAction::Keep,
+        };
+
+        let compiler_message = match message {
+            Message::CompilerMessage(compiler_message) => compiler_message,
+            _ => return LineAction::Remove,
+        };
+
+        let inner_message = compiler_message.message;
+        let pkgid = &compiler_message.package_id;
+        // the only local crate in a well defined job is the crate currently being tested
+        let local = self.local_packages.contains(pkgid);
+        match inner_message.level {
+            DiagnosticLevel::Error if local => {
+                if let Some(code) = &inner_message.code {
+                    let code = DiagnosticCode::from(code.code.clone());
+                    self.output.error_codes.insert(code);
+                }
+                // The output of the linker is only included in the rendered message
+                if inner_message.message.starts_with("linking with `") {
+                    let text = inner_message.rendered.as_ref();
+                    let text = text.unwrap_or(&inner_message.message);
+                    self.output.linker_errors.push(text.clone());
+                }
+            }
+            DiagnosticLevel::Ice if local => self.output.ice = true,
+            DiagnosticLevel::Warning if local => {
+                self.warnings += 1;
+                // Lints are reported as warnings when capped, even if they're denied
+                if let Some(code) = &inner_message.code {
+                    if self.deny_lints.contains(&code.code) {
+                        let code = DiagnosticCode::from(code.code.clone());
+                        self.denied_lints.insert(code);
+                    }
+                    if code.code.starts_with("clippy::") {
+                        let code = DiagnosticCode::from(code.code.clone());
+                        self.clippy_lints.insert(code);
+                    }
+                }
+            }
+            // The notes printed after a crash tell which tool crashed
+            DiagnosticLevel::Note => self.push_line(&inner_message.message),
+            // If the error is in a crate that is not local then it's referred to a dependency
+            // of the current crate
+            DiagnosticLevel::Error | DiagnosticLevel::Ice => {
+                if let Ok(krate) = Crate::try_from(pkgid) {
+                    self.output.failed_deps.insert(krate);
+                }
+            }
+            _ => (),
+        }
+
+        LineAction::Replace(inner_message.rendered.unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LineAction, OutputCollector, MAX_LINES};
+    use crate::crates::{Crate, RegistryCrate};
+    use crate::results::{DiagnosticCode, FailureReason, LinkerFailure};
+    use crate::runner::classifiers;
+    use cargo_metadata::PackageId;
+    use serde_json::json;
+    use std::collections::HashSet;
+
+    const LOCAL: &str = "foo 0.1.0 (path+file:///opt/rustwide/workdir)";
+    const DEPENDENCY: &str = "bar 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)";
+
+    fn message(package: &str, level: &str, code: Option<&str>, rendered: &str) -> String {
+        json!({
+            "reason": "compiler-message",
+            "package_id": package,
+            "target": {
+                "kind": ["lib"],
+                "crate_types": ["lib"],
+                "name": "foo",
+                "src_path": "/opt/rustwide/workdir/src/lib.rs",
+                "edition": "2018",
+                "doctest": true,
+            },
+            "message": {
+                "message": rendered,
+                "code": code.map(|code| json!({ "code": code, "explanation": null })),
+                "level": level,
+                "spans": [],
+                "children": [],
+                "rendered": rendered,
+            },
+        })
+        .to_string()
+    }
+
+    fn local_packages() -> HashSet<PackageId> {
+        let mut packages = HashSet::new();
+        packages.insert(PackageId { repr: LOCAL.into() });
+        packages
+    }
+
+    #[test]
+    fn test_scripted_output() {
+        let local = local_packages();
+        let deny_lints = vec!["unused_imports".to_string()];
+        let mut collector = OutputCollector::new(&local, &deny_lints);
+
+        let script = vec![
+            "   Compiling foo v0.1.0 (/opt/rustwide/workdir)".to_string(),
+            message(
+                LOCAL,
+                "warning",
+                Some("dead_code"),
+                "warning: unused function",
+            ),
+            message(
+                LOCAL,
+                "warning",
+                Some("unused_imports"),
+                "warning: unused import",
+            ),
+            message(
+                LOCAL,
+                "warning",
+                Some("clippy::needless_return"),
+                "warning: return",
+            ),
+            message(
+                DEPENDENCY,
+                "warning",
+                Some("clippy::redundant_clone"),
+                "warning: clone",
+            ),
+            message(
+                LOCAL,
+                "error",
+                Some("E0425"),
+                "error[E0425]: cannot find value",
+            ),
+            message(
+                DEPENDENCY,
+                "error",
+                Some("E0308"),
+                "error[E0308]: mismatched types",
+            ),
+            json!({ "reason": "build-finished", "success": false }).to_string(),
+        ];
+        let actions = script
+            .iter()
+            .map(|line| collector.process_line(line))
+            .collect::<Vec<_>>();
+
+        assert_eq!(actions[0], LineAction::Keep);
+        assert_eq!(
+            actions[5],
+            LineAction::Replace("error[E0425]: cannot find value".into())
+        );
+        assert_eq!(actions[7], LineAction::Remove);
+
+        assert_eq!(collector.warnings, 3);
+        assert_eq!(
+            collector.clippy_lints,
+            vec![DiagnosticCode::from("clippy::needless_return".into())]
+                .into_iter()
+                .collect()
+        );
+        assert_eq!(
+            collector.denied_lints,
+            vec![DiagnosticCode::from("unused_imports".into())]
+                .into_iter()
+                .collect()
+        );
+        assert_eq!(
+            collector.output.lines,
+            vec!["   Compiling foo v0.1.0 (/opt/rustwide/workdir)".to_string()]
+        );
+
+        // The failed dependency takes precedence over the errors of the crate
+        let dependency = Crate::Registry(RegistryCrate {
+            name: "bar".into(),
+            version: "1.0.0".into(),
+        });
+        assert_eq!(
+            classifiers::classify(&collector.output),
+            Some(FailureReason::DependsOn(
+                vec![dependency].into_iter().collect()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_lines_limit() {
+        let local = local_packages();
+        let mut collector = OutputCollector::new(&local, &[]);
+        for i in 0..MAX_LINES * 2 + 1 {
+            collector.push_line(&i.to_string());
+        }
+        assert_eq!(collector.output.lines.len(), MAX_LINES + 1);
+        assert_eq!(collector.output.lines[0], MAX_LINES.to_string());
+    }
+
+    #[test]
+    fn test_scripted_ice() {
+        let local = local_packages();
+        let mut collector = OutputCollector::new(&local, &[]);
+
+        collector.process_line(&message(LOCAL, "error: internal compiler error", None, ""));
+        collector.process_line("thread 'rustc' panicked at 'no entry found for key'");
+        assert!(collector.output.ice);
+        assert_eq!(
+            classifiers::classify(&collector.output),
+            Some(FailureReason::ICE)
+        );
+    }
+
+    #[test]
+    fn test_scripted_linker_error() {
+        let local = local_packages();
+        let mut collector = OutputCollector::new(&local, &[]);
+
+        collector.process_line(&message(
+            LOCAL,
+            "error",
+            None,
+            "linking with `cc` failed: exit status: 1\n  = note: /usr/bin/ld: cannot find -lssl",
+        ));
+        assert_eq!(collector.output.linker_errors.len(), 1);
+        assert_eq!(
+            classifiers::classify(&collector.output),
+            Some(FailureReason::LinkerError(LinkerFailure::MissingLibrary))
+        );
+    }
+}
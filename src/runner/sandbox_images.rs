@@ -0,0 +1,190 @@
+//! Sandbox images selected by an experiment, or by the configuration of some crates, instead of
+//! the one of the workspace. Rustwide builds every crate in the image of its workspace, so a
+//! workspace sharing the same directory (and so the toolchains, caches and build directories) is
+//! created for every other image, along with a results database recording the digest of the image.
+
+use crate::config::Config;
+use crate::crates::Crate;
+use crate::experiments::Experiment;
+use crate::prelude::*;
+use crate::results::{DependencyGraph, EncodingType, RecordSandboxImage, TestResult, WriteResults};
+use crate::toolchain::Toolchain;
+use crate::utils::workspace::{builder, sandbox_image, sandbox_image_digest};
+use rustwide::logging::LogStorage;
+use rustwide::Workspace;
+use std::collections::HashMap;
+use std::time::Duration;
+
+pub(super) struct SandboxImages<'a, DB: WriteResults> {
+    workspace: &'a Workspace,
+    db: &'a DB,
+    config: &'a Config,
+    others: HashMap<String, (Workspace, DB)>,
+}
+
+impl<'a, DB: WriteResults + RecordSandboxImage> SandboxImages<'a, DB> {
+    /// Prepare the images the crates are built in, pulling the ones coming from a registry.
+    pub(super) fn new(
+        ex: &Experiment,
+        crates: &[Crate],
+        config: &'a Config,
+        workspace: &'a Workspace,
+        db: &'a DB,
+    ) -> Fallible<Self> {
+        let mut others = HashMap::new();
+        for krate in crates {
+            let image = match image_of(ex, krate, config) {
+                Some(image) if !others.contains_key(image) => image,
+                _ => continue,
+            };
+            info!("preparing the sandbox image {}", image);
+            let other = builder()
+                .fast_init(true)
+                .sandbox_image(sandbox_image(image, false)?)
+                .init()?;
+            let digest = sandbox_image_digest(Some(image));
+            others.insert(image.to_string(), (other, db.for_sandbox_image(digest)));
+        }
+
+        Ok(SandboxImages {
+            workspace,
+            db,
+            config,
+            others,
+        })
+    }
+}
+
+impl<'a, DB: WriteResults> SandboxImages<'a, DB> {
+    pub(super) fn default_workspace(&self) -> &'a Workspace {
+        self.workspace
+    }
+
+    /// Workspace of the image the crate is built in, if it's not the one of the workspace.
+    pub(super) fn workspace(&self, ex: &Experiment, krate: &Crate) -> Option<&Workspace> {
+        self.get(ex, krate).map(|(workspace, _)| workspace)
+    }
+
+    fn db(&self, ex: &Experiment, krate: &Crate) -> &DB {
+        self.get(ex, krate).map(|(_, db)| db).unwrap_or(self.db)
+    }
+
+    fn get(&self, ex: &Experiment, krate: &Crate) -> Option<&(Workspace, DB)> {
+        image_of(ex, krate, self.config).and_then(|image| self.others.get(image))
+    }
+}
+
+/// The results of each crate are recorded (and the banked results looked up) with the digest of
+/// the image the crate is built in.
+impl<'a, DB: WriteResults> WriteResults for SandboxImages<'a, DB> {
+    fn get_result(
+        &self,
+        ex: &Experiment,
+        toolchain: &Toolchain,
+        krate: &Crate,
+    ) -> Fallible<Option<TestResult>> {
+        self.db(ex, krate).get_result(ex, toolchain, krate)
+    }
+
+    fn update_crate_version(&self, ex: &Experiment, old: &Crate, new: &Crate) -> Fallible<()> {
+        self.db(ex, old).update_crate_version(ex, old, new)
+    }
+
+    fn record_dependency_graph(
+        &self,
+        ex: &Experiment,
+        toolchain: &Toolchain,
+        krate: &Crate,
+        graph: &DependencyGraph,
+    ) -> Fallible<()> {
+        self.db(ex, krate)
+            .record_dependency_graph(ex, toolchain, krate, graph)
+    }
+
+    fn learned_timeout(
+        &self,
+        ex: &Experiment,
+        krate: &Crate,
+        config: &Config,
+    ) -> Fallible<Option<Duration>> {
+        self.db(ex, krate).learned_timeout(ex, krate, config)
+    }
+
+    fn record_result<F>(
+        &self,
+        ex: &Experiment,
+        toolchain: &Toolchain,
+        krate: &Crate,
+        existing_logs: Option<LogStorage>,
+        config: &Config,
+        encoding_type: EncodingType,
+        f: F,
+    ) -> Fallible<TestResult>
+    where
+        F: FnOnce() -> Fallible<TestResult>,
+    {
+        self.db(ex, krate).record_result(
+            ex,
+            toolchain,
+            krate,
+            existing_logs,
+            config,
+            encoding_type,
+            f,
+        )
+    }
+}
+
+/// Image the crate is built in, if it's not the one of the workspace: the one of its
+/// configuration, or else the one of the experiment.
+fn image_of<'c>(ex: &'c Experiment, krate: &Crate, config: &'c Config) -> Option<&'c str> {
+    config
+        .sandbox_image(krate)
+        .or_else(|| ex.sandbox_image.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::image_of;
+    use crate::actions::{Action, ActionsCtx, CreateExperiment};
+    use crate::config::{Config, CrateConfig};
+    use crate::crates::{Crate, RegistryCrate};
+    use crate::db::Database;
+    use crate::experiments::Experiment;
+
+    #[test]
+    fn test_image_of() {
+        let db = Database::temp().unwrap();
+        let mut config = Config::default();
+        let ctx = ActionsCtx::new(&db, &config);
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+        CreateExperiment::dummy("foo").apply(&ctx).unwrap();
+        let mut ex = Experiment::get(&db, "foo").unwrap().unwrap();
+
+        let registry = |name: &str| {
+            Crate::Registry(RegistryCrate {
+                name: name.into(),
+                version: "1.0.0".into(),
+            })
+        };
+        let mut crate_config: CrateConfig = toml::from_str("").unwrap();
+        crate_config.sandbox_image = Some("crates-build-env-bar".into());
+        config.crates.insert("bar".into(), crate_config);
+
+        assert_eq!(image_of(&ex, &registry("foo"), &config), None);
+        assert_eq!(
+            image_of(&ex, &registry("bar"), &config),
+            Some("crates-build-env-bar")
+        );
+
+        ex.sandbox_image = Some("crates-build-env-foo".into());
+        assert_eq!(
+            image_of(&ex, &registry("foo"), &config),
+            Some("crates-build-env-foo")
+        );
+        assert_eq!(
+            image_of(&ex, &registry("bar"), &config),
+            Some("crates-build-env-bar")
+        );
+    }
+}
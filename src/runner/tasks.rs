@@ -3,7 +3,7 @@ use crate::crates::{Crate, GitHubRepo};
 use crate::experiments::Experiment;
 use crate::prelude::*;
 use crate::results::{EncodingType, TestResult, WriteResults};
-use crate::runner::test::detect_broken;
+use crate::runner::{fetch, msrv};
 use crate::runner::{test, RunnerState};
 use crate::toolchain::Toolchain;
 use crate::utils;
@@ -16,18 +16,22 @@ use std::fmt;
 pub(super) struct TaskCtx<'ctx, DB: WriteResults + 'ctx> {
     pub(super) build_dir: &'ctx Mutex<BuildDirectory>,
     pub(super) config: &'ctx Config,
+    pub(super) workspace: &'ctx Workspace,
     pub(super) db: &'ctx DB,
     pub(super) experiment: &'ctx Experiment,
     pub(super) toolchain: &'ctx Toolchain,
     pub(super) krate: &'ctx Crate,
     pub(super) state: &'ctx RunnerState,
     pub(super) quiet: bool,
+    /// Whether the build is a retry running alone on the agent, with increased resources.
+    pub(super) heavyweight: bool,
 }
 
 impl<'ctx, DB: WriteResults + 'ctx> TaskCtx<'ctx, DB> {
     fn new(
         build_dir: &'ctx Mutex<BuildDirectory>,
         config: &'ctx Config,
+        workspace: &'ctx Workspace,
         db: &'ctx DB,
         experiment: &'ctx Experiment,
         toolchain: &'ctx Toolchain,
@@ -38,12 +42,14 @@ impl<'ctx, DB: WriteResults + 'ctx> TaskCtx<'ctx, DB> {
         TaskCtx {
             build_dir,
             config,
+            workspace,
             db,
             experiment,
             toolchain,
             krate,
             state,
             quiet,
+            heavyweight: false,
         }
     }
 }
@@ -52,12 +58,17 @@ pub(super) enum TaskStep {
     Prepare,
     Cleanup,
     Skip { tc: Toolchain },
+    NotApplicable { tc: Toolchain },
     BuildAndTest { tc: Toolchain, quiet: bool },
     BuildOnly { tc: Toolchain, quiet: bool },
     CheckOnly { tc: Toolchain, quiet: bool },
+    PreliminaryCheck { tc: Toolchain, quiet: bool },
+    TestIfChanged { tc: Toolchain, quiet: bool },
     Clippy { tc: Toolchain, quiet: bool },
     Rustdoc { tc: Toolchain, quiet: bool },
     UnstableFeatures { tc: Toolchain },
+    Msrv { tc: Toolchain, quiet: bool },
+    Miri { tc: Toolchain, quiet: bool },
 }
 
 impl fmt::Debug for TaskStep {
@@ -66,12 +77,21 @@ impl fmt::Debug for TaskStep {
             TaskStep::Prepare => ("prepare", false, None),
             TaskStep::Cleanup => ("cleanup", false, None),
             TaskStep::Skip { ref tc } => ("skip", false, Some(tc)),
+            TaskStep::NotApplicable { ref tc } => ("not applicable", false, Some(tc)),
             TaskStep::BuildAndTest { ref tc, quiet } => ("build and test", quiet, Some(tc)),
             TaskStep::BuildOnly { ref tc, quiet } => ("build", quiet, Some(tc)),
             TaskStep::CheckOnly { ref tc, quiet } => ("check", quiet, Some(tc)),
+            TaskStep::PreliminaryCheck { ref tc, quiet } => ("preliminary check", quiet, Some(tc)),
+            TaskStep::TestIfChanged { ref tc, quiet } => {
+                ("build and test if changed", quiet, Some(tc))
+            }
             TaskStep::Clippy { ref tc, quiet } => ("clippy", quiet, Some(tc)),
             TaskStep::Rustdoc { ref tc, quiet } => ("doc", quiet, Some(tc)),
             TaskStep::UnstableFeatures { ref tc } => ("find unstable features on", false, Some(tc)),
+            TaskStep::Msrv { ref tc, quiet } => {
+                ("build with the declared msrv instead of", quiet, Some(tc))
+            }
+            TaskStep::Miri { ref tc, quiet } => ("build and test with miri", quiet, Some(tc)),
         };
 
         write!(f, "{}", name)?;
@@ -108,12 +128,17 @@ impl Task {
             TaskStep::Prepare => true,
             // Build tasks should only be executed if there are no results for them
             TaskStep::Skip { ref tc }
+            | TaskStep::NotApplicable { ref tc }
             | TaskStep::BuildAndTest { ref tc, .. }
             | TaskStep::BuildOnly { ref tc, .. }
             | TaskStep::CheckOnly { ref tc, .. }
+            | TaskStep::PreliminaryCheck { ref tc, .. }
+            | TaskStep::TestIfChanged { ref tc, .. }
             | TaskStep::Clippy { ref tc, .. }
             | TaskStep::Rustdoc { ref tc, .. }
-            | TaskStep::UnstableFeatures { ref tc } => {
+            | TaskStep::UnstableFeatures { ref tc }
+            | TaskStep::Msrv { ref tc, .. }
+            | TaskStep::Miri { ref tc, .. } => {
                 db.get_result(ex, tc, &self.krate).unwrap_or(None).is_none()
             }
         }
@@ -131,12 +156,17 @@ impl Task {
         match self.step {
             TaskStep::Prepare | TaskStep::Cleanup => {}
             TaskStep::Skip { ref tc }
+            | TaskStep::NotApplicable { ref tc }
             | TaskStep::BuildAndTest { ref tc, .. }
             | TaskStep::BuildOnly { ref tc, .. }
             | TaskStep::CheckOnly { ref tc, .. }
+            | TaskStep::PreliminaryCheck { ref tc, .. }
+            | TaskStep::TestIfChanged { ref tc, .. }
             | TaskStep::Clippy { ref tc, .. }
             | TaskStep::Rustdoc { ref tc, .. }
-            | TaskStep::UnstableFeatures { ref tc } => {
+            | TaskStep::UnstableFeatures { ref tc }
+            | TaskStep::Msrv { ref tc, .. }
+            | TaskStep::Miri { ref tc, .. } => {
                 let log_storage = state
                     .lock()
                     .prepare_logs
@@ -164,7 +194,7 @@ impl Task {
     pub(super) fn run<'ctx, 's: 'ctx, DB: WriteResults>(
         &'s self,
         config: &'ctx Config,
-        workspace: &Workspace,
+        workspace: &'ctx Workspace,
         build_dir: &'ctx Mutex<BuildDirectory>,
         ex: &'ctx Experiment,
         db: &'ctx DB,
@@ -187,15 +217,71 @@ impl Task {
                 TaskStep::Rustdoc { ref tc, quiet } => {
                     ("documenting", test::test_rustdoc, tc, quiet)
                 }
+                TaskStep::Miri { ref tc, quiet } => {
+                    ("testing with miri", test::test_miri, tc, quiet)
+                }
                 TaskStep::UnstableFeatures { ref tc } => (
                     "checking unstable",
                     crate::runner::unstable_features::find_unstable_features,
                     tc,
                     false,
                 ),
+                TaskStep::PreliminaryCheck { ref tc, quiet } => {
+                    let ctx = TaskCtx::new(
+                        build_dir,
+                        config,
+                        workspace,
+                        db,
+                        ex,
+                        tc,
+                        &self.krate,
+                        state,
+                        quiet,
+                    );
+                    test::run_preliminary_check(&ctx)?;
+                    return Ok(());
+                }
+                TaskStep::Msrv { ref tc, quiet } => {
+                    let ctx = TaskCtx::new(
+                        build_dir,
+                        config,
+                        workspace,
+                        db,
+                        ex,
+                        tc,
+                        &self.krate,
+                        state,
+                        quiet,
+                    );
+                    msrv::run_msrv(&ctx)?;
+                    return Ok(());
+                }
+                TaskStep::TestIfChanged { ref tc, quiet } => {
+                    let ctx = TaskCtx::new(
+                        build_dir,
+                        config,
+                        workspace,
+                        db,
+                        ex,
+                        tc,
+                        &self.krate,
+                        state,
+                        quiet,
+                    );
+                    test::run_test_if_changed(&ctx)?;
+                    return Ok(());
+                }
                 TaskStep::Cleanup => {
                     // Remove stored logs
-                    state.lock().prepare_logs.remove(&self.krate);
+                    let mut state = state.lock();
+                    state.prepare_logs.remove(&self.krate);
+                    state.local_packages.remove(&self.krate);
+                    state.timeouts.remove(&self.krate);
+                    for tc in &ex.toolchains {
+                        state
+                            .check_results
+                            .remove(&(self.krate.clone(), tc.clone()));
+                    }
                     return Ok(());
                 }
                 TaskStep::Prepare => {
@@ -206,7 +292,7 @@ impl Task {
                         .insert(self.krate.clone(), storage.clone());
                     logging::capture(&storage, || {
                         let rustwide_crate = self.krate.to_rustwide();
-                        detect_broken(rustwide_crate.fetch(workspace))?;
+                        fetch::fetch(workspace, &self.krate, &config.fetch)?;
 
                         if let Crate::GitHub(repo) = &self.krate {
                             if let Some(sha) = rustwide_crate.git_commit(workspace) {
@@ -251,11 +337,27 @@ impl Task {
                     )?;
                     return Ok(());
                 }
+                TaskStep::NotApplicable { ref tc } => {
+                    db.record_result(
+                        ex,
+                        tc,
+                        &self.krate,
+                        None,
+                        config,
+                        EncodingType::Plain,
+                        || {
+                            warn!("crate not applicable to {}", crate::HOST_TARGET);
+                            Ok(TestResult::NotApplicable)
+                        },
+                    )?;
+                    return Ok(());
+                }
             };
 
         let ctx = TaskCtx::new(
             build_dir,
             config,
+            workspace,
             db,
             ex,
             toolchain,
@@ -1,22 +1,123 @@
-use crate::crates::Crate;
+use crate::config::{NetworkMode, HEAVYWEIGHT_SCALE};
+use crate::experiments::{Mode, TestRunner};
 use crate::prelude::*;
-use crate::results::DiagnosticCode;
-use crate::results::{BrokenReason, EncodingType, FailureReason, TestResult, WriteResults};
+use crate::results::{
+    BrokenReason, DependencyGraph, DiagnosticCode, EncodingType, FailureReason, Lints, TestResult,
+    WriteResults,
+};
+use crate::runner::artifacts;
+use crate::runner::cargo_config;
+#[cfg(unix)]
+use crate::runner::cargo_home;
+use crate::runner::classifiers;
+use crate::runner::disk_limit;
+use crate::runner::dns;
+use crate::runner::fetch;
+use crate::runner::nextest;
+use crate::runner::output::{LineAction, OutputCollector};
 use crate::runner::tasks::TaskCtx;
 use crate::runner::OverrideResult;
-use cargo_metadata::diagnostic::DiagnosticLevel;
-use cargo_metadata::{Message, Metadata, PackageId};
+use crate::utils;
+use crate::utils::size::Size;
+use cargo_metadata::{Metadata, PackageId};
 use failure::Error;
 use remove_dir_all::remove_dir_all;
-use rustwide::cmd::{CommandError, ProcessLinesActions, SandboxBuilder};
+use rustwide::cmd::{Command, CommandError, ProcessLinesActions, SandboxBuilder};
+use rustwide::logging::{self, LogStorage};
 use rustwide::{Build, PrepareError};
 use std::collections::{BTreeSet, HashSet};
-use std::convert::TryFrom;
+use std::time::Duration;
+
+/// Lints denied in the `rustdoc-strict` mode, on top of the ones denied by the experiment.
+const STRICT_RUSTDOC_LINTS: &[&str] = &["rustdoc::broken_intra_doc_links"];
+
+/// Default timeouts of the commands executed by rustwide, scaled for the heavyweight retries. The
+/// first one can be replaced by the `timeout` of the crate in the configuration.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+const DEFAULT_NO_OUTPUT_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Phase of the build in which a failure happened.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum BuildPhase {
+    Build,
+    Test,
+}
+
+/// Structured outcome of building (and possibly testing) a crate, converted into a `TestResult`
+/// when it's recorded.
+#[derive(Debug)]
+enum BuildOutcome {
+    Success {
+        warnings: usize,
+        tested: bool,
+    },
+    Failure {
+        phase: BuildPhase,
+        reason: FailureReason,
+    },
+}
+
+impl BuildOutcome {
+    fn failure(phase: BuildPhase, err: &Error) -> Self {
+        BuildOutcome::Failure {
+            phase,
+            reason: failure_reason(err),
+        }
+    }
+}
+
+impl From<BuildOutcome> for TestResult {
+    fn from(outcome: BuildOutcome) -> TestResult {
+        match outcome {
+            BuildOutcome::Success { warnings, tested } => {
+                info!("the crate emitted {} warnings", warnings);
+                if tested {
+                    TestResult::TestPass
+                } else {
+                    TestResult::TestSkipped
+                }
+            }
+            BuildOutcome::Failure {
+                phase: BuildPhase::Build,
+                reason,
+            } => TestResult::BuildFail(reason),
+            BuildOutcome::Failure {
+                phase: BuildPhase::Test,
+                reason,
+            } => TestResult::TestFail(reason),
+        }
+    }
+}
+
+fn is_oom(cause: &(dyn Fail + 'static)) -> bool {
+    match cause.downcast_ctx() {
+        Some(&CommandError::SandboxOOM) => true,
+        _ => false,
+    }
+}
+
+/// Whether the build failed because the kernel killed it when it ran out of memory, which Docker
+/// doesn't always report. `oom_kills` is the number of OOM kills counted before the build.
+fn is_oom_kill(err: &Error, oom_kills: Option<u64>) -> bool {
+    err.iter_chain().any(|cause| match cause.downcast_ctx() {
+        Some(&CommandError::ExecutionFailed(status)) => {
+            utils::cgroup::is_oom_kill(status, oom_kills)
+        }
+        _ => false,
+    })
+}
 
 fn failure_reason(err: &Error) -> FailureReason {
+    // Running out of memory often causes other errors to be reported as well (for example rustc
+    // being killed while compiling a crate), so Docker reporting it takes precedence over
+    // everything else.
+    if err.iter_chain().any(is_oom) {
+        return FailureReason::OOM;
+    }
+
     for cause in err.iter_chain() {
-        if let Some(&CommandError::SandboxOOM) = cause.downcast_ctx() {
-            return FailureReason::OOM;
+        if let Some(&CommandError::SandboxContainerCreate(_)) = cause.downcast_ctx() {
+            return FailureReason::Docker;
         } else if let Some(&CommandError::NoOutputFor(_)) = cause.downcast_ctx() {
             return FailureReason::Timeout;
         } else if let Some(&CommandError::Timeout(_)) = cause.downcast_ctx() {
@@ -74,102 +175,198 @@ fn get_local_packages(build_env: &Build) -> Fallible<HashSet<PackageId>> {
         .collect::<HashSet<_>>())
 }
 
+fn get_dependency_graph(build_env: &Build) -> Fallible<Option<DependencyGraph>> {
+    Ok(build_env
+        .cargo()
+        .args(&["metadata", "--format-version=1"])
+        .log_output(false)
+        .run_capture()?
+        .stdout_lines()
+        .iter()
+        .filter_map(|line| serde_json::from_str::<Metadata>(line).ok())
+        .find_map(|metadata| DependencyGraph::from_metadata(&metadata)))
+}
+
+/// Record the dependencies resolved by cargo along with the result, so that the crates depending
+/// on a given package can be found long after the experiment ran. Failing to query them doesn't
+/// affect the build.
+fn record_dependency_graph<DB: WriteResults>(ctx: &TaskCtx<DB>, build_env: &Build) {
+    let graph = match get_dependency_graph(build_env) {
+        Ok(Some(graph)) => graph,
+        Ok(None) => return,
+        Err(err) => {
+            warn!(
+                "failed to resolve the dependency graph of {}: {}",
+                ctx.krate, err
+            );
+            return;
+        }
+    };
+    if let Err(err) =
+        ctx.db
+            .record_dependency_graph(ctx.experiment, ctx.toolchain, ctx.krate, &graph)
+    {
+        warn!(
+            "failed to record the dependency graph of {}: {}",
+            ctx.krate, err
+        );
+    }
+}
+
+/// Warnings emitted by the crate being tested during a successful cargo invocation.
+struct Warnings {
+    count: usize,
+    clippy_lints: BTreeSet<DiagnosticCode>,
+}
+
 fn run_cargo<DB: WriteResults>(
     ctx: &TaskCtx<DB>,
     build_env: &Build,
     args: &[&str],
     check_errors: bool,
     local_packages_id: &HashSet<PackageId>,
-) -> Fallible<()> {
-    let mut rustflags = format!("--cap-lints={}", ctx.experiment.cap_lints.to_str());
-    if let Some(ref tc_rustflags) = ctx.toolchain.rustflags {
-        rustflags.push(' ');
-        rustflags.push_str(tc_rustflags);
-    }
-
+) -> Fallible<Warnings> {
     let rustflags_env = if let Some(&"doc") = args.get(0) {
         "RUSTDOCFLAGS"
     } else {
         "RUSTFLAGS"
     };
 
-    let mut did_ice = false;
-    let mut error_codes = BTreeSet::new();
-    let mut deps = BTreeSet::new();
+    let mut rustflags = format!("--cap-lints={}", ctx.experiment.cap_lints.to_str());
+    // Flags set through the environment of the toolchain are appended, to keep `--cap-lints`
+    for tc_rustflags in ctx
+        .toolchain
+        .rustflags
+        .iter()
+        .chain(ctx.toolchain.env.get(rustflags_env))
+    {
+        rustflags.push(' ');
+        rustflags.push_str(tc_rustflags);
+    }
 
-    let mut detect_error = |line: &str, actions: &mut ProcessLinesActions| {
-        // Avoid trying to deserialize non JSON output
-        if !line.starts_with('{') {
-            return;
+    // Packages in a subdirectory of the source are built from the root, so that the cargo
+    // configuration injected there still applies
+    let manifest_path = ctx
+        .krate
+        .subpath()
+        .map(|subpath| format!("{}/Cargo.toml", subpath));
+    let mut args = args.to_vec();
+    if let Some(manifest_path) = &manifest_path {
+        // `cargo miri setup` only builds the sysroot, and doesn't accept a manifest
+        if args[..] != ["miri", "setup"] {
+            let end = args
+                .iter()
+                .position(|arg| *arg == "--")
+                .unwrap_or(args.len());
+            args.splice(end..end, vec!["--manifest-path", manifest_path.as_str()]);
         }
+    }
 
-        let message = match serde_json::from_str(line) {
-            Ok(message) => message,
-            Err(_) => return,
-        };
-
-        match message {
-            Message::CompilerMessage(compiler_message) => {
-                let inner_message = compiler_message.message;
-                match (inner_message.level, &compiler_message.package_id) {
-                    // the only local crate in a well defined job is the crate currently being tested
-                    (DiagnosticLevel::Error, pkgid) if local_packages_id.contains(pkgid) => {
-                        if let Some(code) = inner_message.code {
-                            error_codes.insert(DiagnosticCode::from(code.code));
-                        }
-                    }
-                    (DiagnosticLevel::Ice, pkgid) if local_packages_id.contains(pkgid) => {
-                        did_ice = true
-                    }
-                    // If the error is in a crate that is not local then it's referred to a dependency
-                    // of the current crate
-                    (DiagnosticLevel::Error, pkgid) => {
-                        if let Ok(krate) = Crate::try_from(pkgid) {
-                            deps.insert(krate);
-                        }
-                    }
-                    (DiagnosticLevel::Ice, pkgid) => {
-                        if let Ok(krate) = Crate::try_from(pkgid) {
-                            deps.insert(krate);
-                        }
-                    }
-                    _ => (),
-                }
+    let mut deny_lints = ctx.experiment.deny_lints.clone();
+    if ctx.experiment.mode == Mode::RustdocStrict {
+        deny_lints.extend(STRICT_RUSTDOC_LINTS.iter().map(|lint| lint.to_string()));
+    }
 
-                actions.replace_with_lines(inner_message.rendered.unwrap_or_default().split('\n'));
-            }
-            _ => actions.remove_line(),
+    let mut collector = OutputCollector::new(local_packages_id, &deny_lints);
+    collector.output.network_disabled = ctx.config.network_mode(&ctx.krate) != NetworkMode::Enabled;
+    let keep_messages = check_errors && artifacts::keep_messages(ctx.config);
+    let mut messages = Vec::new();
+    let mut detect_error = |line: &str, actions: &mut ProcessLinesActions| {
+        // Without JSON messages the output is only collected for the classifiers
+        if !check_errors {
+            collector.push_line(line);
+            return;
+        }
+        if keep_messages && line.starts_with('{') {
+            messages.push(line.to_string());
+        }
+        match collector.process_line(line) {
+            LineAction::Keep => {}
+            LineAction::Remove => actions.remove_line(),
+            LineAction::Replace(rendered) => actions.replace_with_lines(rendered.split('\n')),
         }
     };
 
     let mut command = build_env
         .cargo()
-        .args(args)
+        .args(&args)
         .env("CARGO_INCREMENTAL", "0")
         .env("RUST_BACKTRACE", "full")
         .env(rustflags_env, rustflags);
-
-    if check_errors {
-        command = command.process_lines(&mut detect_error);
+    #[cfg(unix)]
+    {
+        command = command.env("CARGO_HOME", cargo_home::prepare(build_env)?);
     }
+    // Tests of the crates running localhost services often bind fixed ports, colliding with each
+    // other when they run in parallel
+    if ctx.config.network_mode(&ctx.krate) == NetworkMode::Localhost {
+        command = command
+            .env("RUST_TEST_THREADS", "1")
+            .env("NEXTEST_TEST_THREADS", "1");
+    }
+    // The environment of the experiment overrides the defaults above
+    for (name, value) in &ctx.experiment.env {
+        command = command.env(name, value);
+    }
+    // The configuration and the environment of the toolchain only apply to its builds, and
+    // override the ones of the experiment
+    for (name, value) in ctx.toolchain.cargo_config_env() {
+        command = command.env(name, value);
+    }
+    for (name, value) in &ctx.toolchain.env {
+        if name != rustflags_env {
+            command = command.env(name, value);
+        }
+    }
+
+    command = command.process_lines(&mut detect_error);
 
+    let timeout = build_timeout(ctx);
+    if ctx.heavyweight {
+        let scale = HEAVYWEIGHT_SCALE as u32;
+        command = command
+            .timeout(Some(timeout.unwrap_or(DEFAULT_TIMEOUT) * scale))
+            .no_output_timeout(Some(DEFAULT_NO_OUTPUT_TIMEOUT * scale));
+    } else if let Some(timeout) = timeout {
+        command = command.timeout(Some(timeout));
+    }
     if ctx.quiet {
         command = command.no_output_timeout(None);
     }
 
-    match command.run() {
-        Ok(()) => Ok(()),
-        Err(e) => {
-            if did_ice {
-                Err(e.context(FailureReason::ICE).into())
-            } else if !deps.is_empty() {
-                Err(e.context(FailureReason::DependsOn(deps)).into())
-            } else if !error_codes.is_empty() {
-                Err(e.context(FailureReason::CompilerError(error_codes)).into())
-            } else {
-                Err(e.into())
-            }
+    let oom_kills = utils::cgroup::oom_kills();
+    let disk_limit = ctx.config.disk_limit(&ctx.krate);
+    let (result, disk_limit_exceeded) =
+        disk_limit::watch(&build_env.host_target_dir(), disk_limit, || command.run());
+    if keep_messages {
+        artifacts::record_messages(build_env, &messages)?;
+    }
+    match result {
+        _ if disk_limit_exceeded => Err(err_msg("disk limit exceeded")
+            .context(FailureReason::DiskLimit)
+            .into()),
+        Ok(()) if !collector.denied_lints.is_empty() => {
+            let lints = collector
+                .denied_lints
+                .iter()
+                .map(|lint| lint.to_string())
+                .collect::<Vec<_>>();
+            error!("the build emitted the denied lints {}", lints.join(", "));
+            Err(err_msg("denied lints emitted")
+                .context(FailureReason::CompilerError(collector.denied_lints))
+                .into())
         }
+        Ok(()) => Ok(Warnings {
+            count: collector.warnings,
+            clippy_lints: collector.clippy_lints,
+        }),
+        Err(e) => match classifiers::classify(&collector.output) {
+            Some(reason) => Err(e.context(reason).into()),
+            // Being killed with SIGKILL is a hint of running out of memory, only used when
+            // nothing more specific explains the failure
+            None if is_oom_kill(&e, oom_kills) => Err(e.context(FailureReason::OOM).into()),
+            None => Err(e.into()),
+        },
     }
 }
 
@@ -205,58 +402,330 @@ pub(super) fn run_test<DB: WriteResults>(
                     ctx.toolchain.to_string(),
                     ctx.experiment.name
                 );
-                let sandbox = SandboxBuilder::new()
-                    .memory_limit(Some(ctx.config.sandbox.memory_limit.to_bytes()))
-                    .enable_networking(false);
+                run_with_retries(ctx, test_fn)
+            },
+        )?;
+    }
+    Ok(())
+}
 
-                let krate = &ctx.krate.to_rustwide();
-                let mut build_dir = ctx.build_dir.lock().unwrap();
-                let mut build = build_dir.build(&ctx.toolchain, krate, sandbox);
+/// Run the build, running it again if it failed for one of the spurious reasons listed in the
+/// retry policy, so that network and docker hiccups don't end up in the reports. The output of
+/// all the attempts is kept in the log.
+fn run_with_retries<DB: WriteResults>(
+    ctx: &TaskCtx<DB>,
+    test_fn: fn(&TaskCtx<DB>, &Build, &HashSet<PackageId>) -> Fallible<TestResult>,
+) -> Fallible<TestResult> {
+    let policy = &ctx.config.retry;
+    let mut attempt = 0;
+    loop {
+        let result = run_in_build(ctx, test_fn)?;
+        let reason = match result {
+            TestResult::BuildFail(ref reason) | TestResult::TestFail(ref reason)
+                if attempt < policy.max_retries && policy.is_retryable(reason) =>
+            {
+                reason.clone()
+            }
+            _ => return retry_heavyweight(ctx, test_fn, result),
+        };
 
-                for patch in ctx.toolchain.patches.iter() {
-                    build = build.patch_with_git(&patch.name, &patch.repo, &patch.branch);
-                }
+        let delay = fetch::backoff(policy.backoff, attempt);
+        warn!(
+            "{} failed with {} (attempt {}/{}), retrying in {:?}",
+            ctx.krate,
+            reason,
+            attempt + 1,
+            policy.max_retries + 1,
+            delay
+        );
+        std::thread::sleep(delay);
+        attempt += 1;
+    }
+}
 
-                detect_broken(build.run(|build| {
-                    let local_packages_id = get_local_packages(build)?;
-                    test_fn(ctx, build, &local_packages_id)
-                }))
+/// Run the builds that ran out of memory or time once more, alone on the agent and with more
+/// resources, before recording their result: they were likely starved by the other builds.
+fn retry_heavyweight<DB: WriteResults>(
+    ctx: &TaskCtx<DB>,
+    test_fn: fn(&TaskCtx<DB>, &Build, &HashSet<PackageId>) -> Fallible<TestResult>,
+    result: TestResult,
+) -> Fallible<TestResult> {
+    match result {
+        TestResult::BuildFail(ref reason) | TestResult::TestFail(ref reason)
+            if !ctx.heavyweight && ctx.config.retry.needs_heavyweight(reason) =>
+        {
+            log_oom(ctx, &result);
+            warn!(
+                "{} failed with {}, retrying with {}x the resources",
+                ctx.krate, reason, HEAVYWEIGHT_SCALE
+            );
+            let ctx = TaskCtx {
+                heavyweight: true,
+                ..*ctx
+            };
+            let result = run_in_build(&ctx, test_fn)?;
+            log_oom(&ctx, &result);
+            Ok(result)
+        }
+        _ => {
+            log_oom(ctx, &result);
+            Ok(result)
+        }
+    }
+}
+
+/// Timeout of the builds of the crate: the one set in the configuration if there is one, otherwise
+/// the one learned from the duration of its previous builds.
+fn build_timeout<DB: WriteResults>(ctx: &TaskCtx<DB>) -> Option<Duration> {
+    if let Some(timeout) = ctx.config.build_timeout(&ctx.krate) {
+        return Some(timeout);
+    }
+    if let Some(timeout) = ctx.state.lock().timeouts.get(&ctx.krate) {
+        return *timeout;
+    }
+
+    let timeout = match ctx
+        .db
+        .learned_timeout(ctx.experiment, ctx.krate, ctx.config)
+    {
+        Ok(timeout) => timeout,
+        Err(err) => {
+            warn!(
+                "failed to query the learned timeout of {}: {}",
+                ctx.krate, err
+            );
+            None
+        }
+    };
+    ctx.state.lock().timeouts.insert(ctx.krate.clone(), timeout);
+    timeout
+}
+
+fn memory_limit<DB: WriteResults>(ctx: &TaskCtx<DB>) -> Size {
+    let limit = ctx.config.memory_limit(&ctx.krate);
+    if ctx.heavyweight {
+        limit.scaled(HEAVYWEIGHT_SCALE)
+    } else {
+        limit
+    }
+}
+
+/// Record the memory limit in the log of builds killed for exceeding it, to make tuning the limit
+/// easier.
+fn log_oom<DB: WriteResults>(ctx: &TaskCtx<DB>, result: &TestResult) {
+    if result.is_oom() {
+        error!(
+            "the build was killed after exceeding the memory limit of {}",
+            memory_limit(ctx)
+        );
+    }
+}
+
+/// Update the dependencies of the crate to their latest compatible versions, and fetch them.
+///
+/// This runs on the host (like the lockfile generation done by rustwide) since it needs network
+/// access, which is disabled inside the sandbox.
+fn update_dependencies<DB: WriteResults>(ctx: &TaskCtx<DB>, build: &Build) -> Fallible<()> {
+    let mut manifest = build.host_source_dir();
+    if let Some(subpath) = ctx.krate.subpath() {
+        manifest.push(subpath);
+    }
+    manifest.push("Cargo.toml");
+    Command::new(ctx.workspace, ctx.toolchain.cargo())
+        .args(&["update", "--manifest-path"])
+        .arg(&manifest)
+        .run()
+        .with_context(|_| format!("failed to update the dependencies of {}", ctx.krate))?;
+    Command::new(ctx.workspace, ctx.toolchain.cargo())
+        .args(&["fetch", "--locked", "--manifest-path"])
+        .arg(&manifest)
+        .run()
+        .with_context(|_| format!("failed to fetch the dependencies of {}", ctx.krate))?;
+    Ok(())
+}
+
+pub(super) fn run_in_build<DB: WriteResults, T>(
+    ctx: &TaskCtx<DB>,
+    test_fn: fn(&TaskCtx<DB>, &Build, &HashSet<PackageId>) -> Fallible<T>,
+) -> Fallible<T> {
+    // Builds only have network access if their crate is allowed to in the configuration, to keep
+    // the results reproducible and to prevent build scripts from leaking secrets of the agent.
+    // The sandbox still has a loopback interface, which is all the crates in the `localhost`
+    // network mode need to run their tests
+    let network = ctx.config.network_mode(&ctx.krate) == NetworkMode::Enabled;
+    let mut sandbox = SandboxBuilder::new()
+        .memory_limit(Some(memory_limit(ctx).to_bytes()))
+        .enable_networking(network);
+    if network {
+        sandbox = dns::mount(sandbox, ctx.config);
+    }
+
+    let (_shared, _exclusive);
+    if ctx.heavyweight {
+        _exclusive = ctx.state.heavyweight_lane.write().unwrap();
+    } else {
+        _shared = ctx.state.heavyweight_lane.read().unwrap();
+    }
+
+    let krate = &ctx.krate.to_rustwide();
+    let mut build_dir = ctx.build_dir.lock().unwrap();
+    let mut build = build_dir.build(&ctx.toolchain, krate, sandbox);
+
+    for patch in ctx.toolchain.patches.iter() {
+        build = build.patch_with_git(&patch.name, &patch.repo, &patch.branch);
+    }
+
+    detect_broken(build.run(|build| {
+        if ctx.experiment.cargo_update {
+            update_dependencies(ctx, build)?;
+        }
+        if let Some(cargo_config) = &ctx.experiment.cargo_config {
+            cargo_config::inject(build, cargo_config)?;
+        }
+
+        let cached = ctx.state.lock().local_packages.get(&ctx.krate).cloned();
+        let local_packages_id = match cached {
+            Some(local_packages_id) => local_packages_id,
+            None => {
+                let local_packages_id = get_local_packages(build)?;
+                ctx.state
+                    .lock()
+                    .local_packages
+                    .insert(ctx.krate.clone(), local_packages_id.clone());
+                local_packages_id
+            }
+        };
+        record_dependency_graph(ctx, build);
+        let result = test_fn(ctx, build, &local_packages_id);
+        // The artifacts of the failed builds are copied too, as they can help debugging them
+        let copied = artifacts::copy(
+            ctx.config,
+            &ctx.experiment.name,
+            &ctx.toolchain,
+            &ctx.krate,
+            build,
+        );
+        if let Err(err) = copied {
+            warn!("failed to copy the artifacts of the build: {}", err);
+        }
+        result
+    }))
+}
+
+/// Run `cargo check` without recording the result, storing it (along with its log) so that the
+/// following `TestIfChanged` step can decide whether a full build is needed.
+pub(super) fn run_preliminary_check<DB: WriteResults>(ctx: &TaskCtx<DB>) -> Fallible<()> {
+    let storage = ctx
+        .state
+        .lock()
+        .prepare_logs
+        .get(&ctx.krate)
+        .map(|s| s.duplicate())
+        .unwrap_or_else(|| LogStorage::from(ctx.config));
+
+    let result = logging::capture(&storage, || {
+        info!(
+            "checking {} against {} for {}",
+            ctx.krate,
+            ctx.toolchain.to_string(),
+            ctx.experiment.name
+        );
+        run_with_retries(ctx, test_check_only)
+    })?;
+
+    ctx.state.lock().check_results.insert(
+        (ctx.krate.clone(), ctx.toolchain.clone()),
+        (result, storage),
+    );
+    Ok(())
+}
+
+/// Reuse the preliminary check result if it's the same on both toolchains, otherwise execute a
+/// full build and test of the crate.
+pub(super) fn run_test_if_changed<DB: WriteResults>(ctx: &TaskCtx<DB>) -> Fallible<()> {
+    if let Some(res) = ctx
+        .db
+        .get_result(ctx.experiment, ctx.toolchain, ctx.krate)?
+    {
+        info!("skipping crate {}. existing result: {}", ctx.krate, res);
+        return Ok(());
+    }
+
+    let unchanged = {
+        let state = ctx.state.lock();
+        let results = ctx
+            .experiment
+            .toolchains
+            .iter()
+            .map(|tc| state.check_results.get(&(ctx.krate.clone(), tc.clone())))
+            .collect::<Option<Vec<_>>>();
+
+        match results {
+            Some(ref results) if results.iter().all(|(res, _)| *res == results[0].0) => state
+                .check_results
+                .get(&(ctx.krate.clone(), ctx.toolchain.clone()))
+                .map(|(res, storage)| (res.clone(), storage.duplicate())),
+            _ => None,
+        }
+    };
+
+    if let Some((check_result, storage)) = unchanged {
+        ctx.db.record_result(
+            ctx.experiment,
+            ctx.toolchain,
+            ctx.krate,
+            Some(storage),
+            ctx.config,
+            EncodingType::Plain,
+            || {
+                info!(
+                    "check result of {} is the same on all toolchains, skipping the full build",
+                    ctx.krate
+                );
+                Ok(match check_result {
+                    // A passing check doesn't say anything about the tests
+                    TestResult::TestPass => TestResult::TestSkipped,
+                    other => other,
+                })
             },
         )?;
+        Ok(())
+    } else {
+        run_test("testing", ctx, test_build_and_test)
     }
-    Ok(())
 }
 
 fn build<DB: WriteResults>(
     ctx: &TaskCtx<DB>,
     build_env: &Build,
     local_packages_id: &HashSet<PackageId>,
-) -> Fallible<()> {
-    run_cargo(
+) -> Fallible<usize> {
+    let mut warnings = run_cargo(
         ctx,
         build_env,
         &["build", "--frozen", "--message-format=json"],
         true,
         local_packages_id,
-    )?;
-    run_cargo(
+    )?
+    .count;
+    warnings += run_cargo(
         ctx,
         build_env,
         &["test", "--frozen", "--no-run", "--message-format=json"],
         true,
         local_packages_id,
-    )?;
-    Ok(())
+    )?
+    .count;
+    Ok(warnings)
 }
 
 fn test<DB: WriteResults>(ctx: &TaskCtx<DB>, build_env: &Build) -> Fallible<()> {
-    run_cargo(
-        ctx,
-        build_env,
-        &["test", "--frozen"],
-        false,
-        &HashSet::new(),
-    )
+    let args: &[&str] = match ctx.experiment.test_runner {
+        TestRunner::Cargo => &["test", "--frozen"],
+        TestRunner::Nextest => nextest::CARGO_ARGS,
+    };
+    run_cargo(ctx, build_env, args, false, &HashSet::new())?;
+    Ok(())
 }
 
 pub(super) fn test_build_and_test<DB: WriteResults>(
@@ -264,19 +733,52 @@ pub(super) fn test_build_and_test<DB: WriteResults>(
     build_env: &Build,
     local_packages_id: &HashSet<PackageId>,
 ) -> Fallible<TestResult> {
-    let build_r = build(ctx, build_env, local_packages_id);
-    let test_r = if build_r.is_ok() {
-        Some(test(ctx, build_env))
-    } else {
-        None
+    let outcome = match build(ctx, build_env, local_packages_id) {
+        Err(err) => BuildOutcome::failure(BuildPhase::Build, &err),
+        Ok(warnings) => match test(ctx, build_env) {
+            Err(err) => BuildOutcome::failure(BuildPhase::Test, &err),
+            Ok(()) => BuildOutcome::Success {
+                warnings,
+                tested: true,
+            },
+        },
     };
 
-    Ok(match (build_r, test_r) {
-        (Err(err), None) => TestResult::BuildFail(failure_reason(&err)),
-        (Ok(_), Some(Err(err))) => TestResult::TestFail(failure_reason(&err)),
-        (Ok(_), Some(Ok(_))) => TestResult::TestPass,
-        (_, _) => unreachable!(),
-    })
+    Ok(outcome.into())
+}
+
+/// Build the crate normally, and then run its tests interpreted by Miri, which fails them when
+/// undefined behavior is detected.
+pub(super) fn test_miri<DB: WriteResults>(
+    ctx: &TaskCtx<DB>,
+    build_env: &Build,
+    local_packages_id: &HashSet<PackageId>,
+) -> Fallible<TestResult> {
+    let outcome = match build(ctx, build_env, local_packages_id) {
+        Err(err) => BuildOutcome::failure(BuildPhase::Build, &err),
+        Ok(warnings) => match miri_test(ctx, build_env) {
+            Err(err) => BuildOutcome::failure(BuildPhase::Test, &err),
+            Ok(()) => BuildOutcome::Success {
+                warnings,
+                tested: true,
+            },
+        },
+    };
+
+    Ok(outcome.into())
+}
+
+fn miri_test<DB: WriteResults>(ctx: &TaskCtx<DB>, build_env: &Build) -> Fallible<()> {
+    // The sysroot is built offline from the `rust-src` component
+    run_cargo(ctx, build_env, &["miri", "setup"], false, &HashSet::new())?;
+    run_cargo(
+        ctx,
+        build_env,
+        &["miri", "test", "--frozen"],
+        false,
+        &HashSet::new(),
+    )?;
+    Ok(())
 }
 
 pub(super) fn test_build_only<DB: WriteResults>(
@@ -284,11 +786,15 @@ pub(super) fn test_build_only<DB: WriteResults>(
     build_env: &Build,
     local_packages_id: &HashSet<PackageId>,
 ) -> Fallible<TestResult> {
-    if let Err(err) = build(ctx, build_env, local_packages_id) {
-        Ok(TestResult::BuildFail(failure_reason(&err)))
-    } else {
-        Ok(TestResult::TestSkipped)
-    }
+    let outcome = match build(ctx, build_env, local_packages_id) {
+        Err(err) => BuildOutcome::failure(BuildPhase::Build, &err),
+        Ok(warnings) => BuildOutcome::Success {
+            warnings,
+            tested: false,
+        },
+    };
+
+    Ok(outcome.into())
 }
 
 pub(super) fn test_check_only<DB: WriteResults>(
@@ -315,12 +821,14 @@ pub(super) fn test_check_only<DB: WriteResults>(
     }
 }
 
+/// Run Clippy on the crate, recording the lints it emitted so that they can be compared between
+/// the two toolchains.
 pub(super) fn test_clippy_only<DB: WriteResults>(
     ctx: &TaskCtx<DB>,
     build_env: &Build,
     local_packages_id: &HashSet<PackageId>,
 ) -> Fallible<TestResult> {
-    if let Err(err) = run_cargo(
+    match run_cargo(
         ctx,
         build_env,
         &[
@@ -333,9 +841,9 @@ pub(super) fn test_clippy_only<DB: WriteResults>(
         true,
         local_packages_id,
     ) {
-        Ok(TestResult::BuildFail(failure_reason(&err)))
-    } else {
-        Ok(TestResult::TestPass)
+        Err(err) => Ok(TestResult::BuildFail(failure_reason(&err))),
+        Ok(ref warnings) if warnings.clippy_lints.is_empty() => Ok(TestResult::TestPass),
+        Ok(warnings) => Ok(TestResult::LintWarnings(Lints(warnings.clippy_lints))),
     }
 }
 
@@ -359,8 +867,10 @@ pub(super) fn test_rustdoc<DB: WriteResults>(
     );
 
     // Make sure to remove the built documentation
-    // There is no point in storing it after the build is done
-    remove_dir_all(&build_env.host_target_dir().join("doc"))?;
+    // There is no point in storing it after the build is done, unless it's copied out
+    if !artifacts::keep_docs(ctx.config) {
+        remove_dir_all(&build_env.host_target_dir().join("doc"))?;
+    }
 
     if let Err(err) = res {
         Ok(TestResult::BuildFail(failure_reason(&err)))
@@ -3,9 +3,10 @@ use crate::experiments::Experiment;
 use crate::prelude::*;
 use crate::results::{BrokenReason, TestResult, WriteResults};
 use crate::runner::graph::{TasksGraph, WalkResult};
+use crate::runner::sandbox_images::SandboxImages;
 use crate::runner::{OverrideResult, RunnerState};
 use crate::utils;
-use rustwide::{BuildDirectory, Workspace};
+use rustwide::BuildDirectory;
 use std::collections::HashMap;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
@@ -17,13 +18,12 @@ use std::time::Duration;
 
 pub(super) struct Worker<'a, DB: WriteResults + Sync> {
     name: String,
-    workspace: &'a Workspace,
+    images: &'a SandboxImages<'a, DB>,
     build_dir: Mutex<BuildDirectory>,
     ex: &'a Experiment,
     config: &'a Config,
     graph: &'a Mutex<TasksGraph>,
     state: &'a RunnerState,
-    db: &'a DB,
     parked_threads: &'a Mutex<HashMap<thread::ThreadId, thread::Thread>>,
     target_dir_cleanup: AtomicBool,
 }
@@ -31,23 +31,21 @@ pub(super) struct Worker<'a, DB: WriteResults + Sync> {
 impl<'a, DB: WriteResults + Sync> Worker<'a, DB> {
     pub(super) fn new(
         name: String,
-        workspace: &'a Workspace,
+        images: &'a SandboxImages<'a, DB>,
         ex: &'a Experiment,
         config: &'a Config,
         graph: &'a Mutex<TasksGraph>,
         state: &'a RunnerState,
-        db: &'a DB,
         parked_threads: &'a Mutex<HashMap<thread::ThreadId, thread::Thread>>,
     ) -> Self {
         Worker {
-            build_dir: Mutex::new(workspace.build_dir(&name)),
+            build_dir: Mutex::new(images.default_workspace().build_dir(&name)),
             name,
-            workspace,
+            images,
             ex,
             config,
             graph,
             state,
-            db,
             parked_threads,
             target_dir_cleanup: AtomicBool::new(false),
         }
@@ -61,22 +59,34 @@ impl<'a, DB: WriteResults + Sync> Worker<'a, DB> {
         // This uses a `loop` instead of a `while let` to avoid locking the graph too much
         loop {
             self.maybe_cleanup_target_dir()?;
-            let walk_result = self
-                .graph
-                .lock()
-                .unwrap()
-                .next_task(self.ex, self.db, &self.name);
+            let walk_result =
+                self.graph
+                    .lock()
+                    .unwrap()
+                    .next_task(self.ex, self.images, &self.name);
             match walk_result {
                 WalkResult::Task(id, task) => {
                     info!("running task: {:?}", task);
-                    let res = task.run(
-                        self.config,
-                        self.workspace,
-                        &self.build_dir,
-                        self.ex,
-                        self.db,
-                        self.state,
-                    );
+                    // The build directory of another workspace is at the same path, but builds
+                    // in the sandbox image of that workspace
+                    let res = match self.images.workspace(self.ex, &task.krate) {
+                        Some(workspace) => task.run(
+                            self.config,
+                            workspace,
+                            &Mutex::new(workspace.build_dir(&self.name)),
+                            self.ex,
+                            self.images,
+                            self.state,
+                        ),
+                        None => task.run(
+                            self.config,
+                            self.images.default_workspace(),
+                            &self.build_dir,
+                            self.ex,
+                            self.images,
+                            self.state,
+                        ),
+                    };
                     if let Err(e) = res {
                         error!("task failed, marking childs as failed too: {:?}", task);
                         utils::report_failure(&e);
@@ -97,7 +107,7 @@ impl<'a, DB: WriteResults + Sync> Worker<'a, DB> {
                         self.graph.lock().unwrap().mark_as_failed(
                             id,
                             self.ex,
-                            self.db,
+                            self.images,
                             self.state,
                             self.config,
                             &e,
@@ -3,9 +3,11 @@ use crate::db::{Database, QueryUtils};
 use crate::experiments::{Assignee, Experiment};
 use crate::prelude::*;
 use crate::server::tokens::Tokens;
+use crate::server::Data;
 use chrono::Duration;
 use chrono::{DateTime, Utc};
 use std::collections::HashSet;
+use std::sync::Arc;
 
 /// Number of seconds without an heartbeat after an agent should be considered unreachable.
 const INACTIVE_AFTER: i64 = 300;
@@ -179,6 +181,34 @@ impl Agents {
     }
 }
 
+/// Give the crates leased to the agents which stopped sending heartbeats (for example because
+/// they crashed) to the other agents, without waiting for their leases to expire.
+pub(super) fn reassign_dead(data: Arc<Data>) -> Fallible<()> {
+    let timeout = Duration::seconds(data.config.server.distributed.dead_agent_timeout as i64);
+    for agent in data.agents.all()? {
+        let dead = agent
+            .last_heartbeat()
+            .map_or(false, |heartbeat| *heartbeat < Utc::now() - timeout);
+        let ex = match agent.assigned_experiment() {
+            Some(ex) if dead => ex,
+            _ => continue,
+        };
+
+        let assignee = Assignee::Agent(agent.name().to_string());
+        let expired = ex.queue().expire_leased_by(&data.db, &assignee)?;
+        if expired > 0 {
+            warn!(
+                "agent {} stopped sending heartbeats, giving its {} crates of experiment {} to \
+                 the other agents",
+                agent.name(),
+                expired,
+                ex.name
+            );
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::{AgentStatus, Agents};
@@ -268,7 +298,7 @@ mod tests {
 
         // Create a new experiment and assign it to the agent
         CreateExperiment::dummy("dummy").apply(&ctx).unwrap();
-        let (_new, ex) = Experiment::next(&db, &Assignee::Agent("agent".to_string()))
+        let (_new, mut ex) = Experiment::next(&db, &Assignee::Agent("agent".to_string()))
             .unwrap()
             .unwrap();
         ex.get_uncompleted_crates(&db, &config, &Assignee::Agent("agent".to_string()))
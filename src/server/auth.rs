@@ -5,7 +5,7 @@ use crate::server::{Data, HttpError};
 use http::header::{HeaderMap, AUTHORIZATION, USER_AGENT};
 use regex::Regex;
 use rust_team_data::v1 as team_data;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 use warp::{self, Filter, Rejection};
 
@@ -17,6 +17,7 @@ lazy_static! {
 #[derive(Copy, Clone)]
 pub enum TokenType {
     Agent,
+    Triager,
 }
 
 pub struct AuthDetails {
@@ -62,6 +63,7 @@ fn check_auth(data: &Data, headers: &HeaderMap, token_type: TokenType) -> Option
             if let Some(token) = parse_token(authorization) {
                 let tokens = match token_type {
                     TokenType::Agent => &data.tokens.agents,
+                    TokenType::Triager => &data.tokens.triagers,
                 };
 
                 if let Some(name) = tokens.get(token) {
@@ -89,12 +91,23 @@ pub fn auth_filter(
     })
 }
 
+/// Permission level of an user interacting with the bot.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    /// Can only query the status of experiments.
+    Guest,
+    /// Can create experiments, and change the ones created in the same issue.
+    Trusted,
+    /// Can change every experiment and manage the bot.
+    Admin,
+}
+
 #[derive(Debug, Clone)]
 pub struct ACL {
-    cached_usernames: Arc<RwLock<HashSet<String>>>,
+    cached_usernames: Arc<RwLock<HashMap<String, Role>>>,
     rust_teams: bool,
-    users: Vec<String>,
-    teams: Vec<(String, String)>,
+    users: Vec<(String, Role)>,
+    teams: Vec<(String, String, Role)>,
 }
 
 impl ACL {
@@ -102,18 +115,32 @@ impl ACL {
         let mut users = Vec::new();
         let mut teams = Vec::new();
 
-        for item in &config.server.bot_acl.github {
+        let items = config
+            .server
+            .bot_acl
+            .github
+            .iter()
+            .map(|item| (item, Role::Trusted))
+            .chain(
+                config
+                    .server
+                    .bot_acl
+                    .admins
+                    .iter()
+                    .map(|item| (item, Role::Admin)),
+            );
+        for (item, role) in items {
             if let Some(middle) = item.find('/') {
                 let org = item[..middle].to_string();
                 let team = item[middle + 1..].to_string();
-                teams.push((org, team));
+                teams.push((org, team, role));
             } else {
-                users.push(item.clone());
+                users.push((item.clone(), role));
             }
         }
 
         let acl = ACL {
-            cached_usernames: Arc::new(RwLock::new(HashSet::new())),
+            cached_usernames: Arc::new(RwLock::new(HashMap::new())),
             rust_teams: config.server.bot_acl.rust_teams,
             users,
             teams,
@@ -124,17 +151,17 @@ impl ACL {
     }
 
     pub fn refresh_cache(&self, github: &GitHubApi) -> Fallible<()> {
-        // A new HashSet is created instead of clearing the old one
+        // A new HashMap is created instead of clearing the old one
         // This is done because if an error occurs the old cache is not flushed
-        let mut new_cache = HashSet::new();
+        let mut new_cache = HashMap::new();
 
-        for user in &self.users {
-            new_cache.insert(user.clone());
+        for &(ref user, role) in &self.users {
+            grant(&mut new_cache, user, role);
         }
 
         let mut orgs = HashMap::new();
-        for &(ref org, ref team) in &self.teams {
-            if let Err(err) = self.load_team(github, &mut new_cache, &mut orgs, org, team) {
+        for &(ref org, ref team, role) in &self.teams {
+            if let Err(err) = self.load_team(github, &mut new_cache, &mut orgs, org, team, role) {
                 warn!(
                     "failed to authorize members of {}/{} to use the bot",
                     org, team
@@ -153,10 +180,11 @@ impl ACL {
     fn load_team(
         &self,
         github: &GitHubApi,
-        new_cache: &mut HashSet<String>,
+        new_cache: &mut HashMap<String, Role>,
         orgs: &mut HashMap<String, HashMap<String, usize>>,
         org: &str,
         team: &str,
+        role: Role,
     ) -> Fallible<()> {
         // Cache the list of teams in an org
         if !orgs.contains_key(org) {
@@ -169,27 +197,45 @@ impl ACL {
                 .ok_or_else(|| err_msg(format!("team {}/{} doesn't exist", org, team)))?,
         )?;
         for member in &members {
-            new_cache.insert(member.clone());
+            grant(new_cache, member, role);
         }
 
         Ok(())
     }
 
-    pub fn allowed(&self, username: &str, user_id: usize) -> Fallible<bool> {
-        if self.rust_teams {
+    pub fn role(&self, username: &str, user_id: usize) -> Fallible<Role> {
+        let cached = self
+            .cached_usernames
+            .read()
+            .unwrap()
+            .get(username)
+            .cloned()
+            .unwrap_or(Role::Guest);
+
+        if cached < Role::Trusted && self.rust_teams {
             let url = format!("{}/permissions/crater.json", team_data::BASE_URL);
             let members: team_data::Permission = crate::utils::http::get_sync(&url)?.json()?;
             if members.github_ids.iter().any(|id| *id == user_id) {
-                return Ok(true);
+                return Ok(Role::Trusted);
             }
         }
-        Ok(self.cached_usernames.read().unwrap().contains(username))
+
+        Ok(cached)
+    }
+}
+
+/// Give the role to the user, unless it already has an higher one.
+fn grant(cache: &mut HashMap<String, Role>, username: &str, role: Role) {
+    let entry = cache.entry(username.to_string()).or_insert(role);
+    if *entry < role {
+        *entry = role;
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{git_revision, parse_token};
+    use super::{git_revision, grant, parse_token, Role};
+    use std::collections::HashMap;
 
     #[test]
     fn test_parse_token() {
@@ -223,4 +269,21 @@ mod tests {
         assert!(git_revision("crater/0000000 (foo bar!) ").is_none());
         assert!(git_revision("crate/0000000").is_none());
     }
+
+    #[test]
+    fn test_grant_keeps_highest_role() {
+        let mut cache = HashMap::new();
+
+        grant(&mut cache, "foo", Role::Trusted);
+        assert_eq!(cache["foo"], Role::Trusted);
+
+        grant(&mut cache, "foo", Role::Admin);
+        assert_eq!(cache["foo"], Role::Admin);
+
+        // Being a member of a less privileged team doesn't downgrade the user
+        grant(&mut cache, "foo", Role::Trusted);
+        assert_eq!(cache["foo"], Role::Admin);
+
+        assert_eq!(cache.get("bar"), None);
+    }
 }
@@ -0,0 +1,112 @@
+use crate::db::QueryUtils;
+use crate::experiments::{Experiment, Status};
+use crate::prelude::*;
+use crate::results::TestResult;
+use crate::server::messages::Message;
+use crate::server::Data;
+
+/// Whether the results of a toolchain on the canary crates look sane, that is at least one of the
+/// crates didn't fail to build.
+fn is_sane(results: &[TestResult]) -> bool {
+    results.iter().any(|result| match result {
+        TestResult::BuildFail(_) | TestResult::Error => false,
+        _ => true,
+    })
+}
+
+/// Queue the rest of the crates of the experiment once all its canary crates were built. If every
+/// canary crate failed on one of the toolchains the setup of the experiment is probably wrong (for
+/// example a toolchain that doesn't work), so the experiment is paused before wasting days of
+/// compute on it. The crates are queued anyway, so an operator can just resume the experiment.
+pub(super) fn check(data: &Data, ex: &mut Experiment) -> Fallible<()> {
+    if !ex.canary || ex.status != Status::Running || !ex.queue().is_canary_drained(&data.db)? {
+        return Ok(());
+    }
+
+    // Only the canary crates have results at this point, as the other ones are still held
+    let mut broken = Vec::new();
+    for toolchain in &ex.toolchains {
+        let results = data
+            .db
+            .query(
+                "SELECT result FROM results WHERE experiment = ?1 AND toolchain = ?2;",
+                &[&ex.name, &toolchain.to_string()],
+                |row| row.get::<_, String>("result"),
+            )?
+            .iter()
+            .map(|result| Ok(result.parse()?))
+            .collect::<Fallible<Vec<TestResult>>>()?;
+        if !is_sane(&results) {
+            broken.push(format!("`{}`", toolchain));
+        }
+    }
+
+    let released = ex.queue().release(&data.db)?;
+    if broken.is_empty() {
+        info!(
+            "canary of experiment {} succeeded, queued the remaining {} crates",
+            ex.name, released
+        );
+        if let Some(ref github_issue) = ex.github_issue {
+            Message::new()
+                .line(
+                    "hatched_chick",
+                    format!(
+                        "The canary crates of experiment **`{}`** built fine, the remaining \
+                         {} crates are now queued.",
+                        ex.name, released
+                    ),
+                )
+                .send(&github_issue.api_url, data)?;
+        }
+        return Ok(());
+    }
+
+    warn!(
+        "pausing experiment {}: all its canary crates failed on {}",
+        ex.name,
+        broken.join(", ")
+    );
+    ex.set_status(&data.db, Status::Paused)?;
+
+    if let Some(ref github_issue) = ex.github_issue {
+        Message::new()
+            .line(
+                "rotating_light",
+                format!(
+                    "Experiment **`{}`** paused: all its canary crates failed on {}, so the \
+                     setup of the experiment is probably broken.",
+                    ex.name,
+                    broken.join(" and ")
+                ),
+            )
+            .line(
+                "hammer_and_wrench",
+                "If the failures are legitimate use the `retry` command to build the remaining \
+                 crates, otherwise `abort` the experiment.",
+            )
+            .send(&github_issue.api_url, data)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_sane;
+    use crate::results::{FailureReason, TestResult};
+
+    #[test]
+    fn test_is_sane() {
+        assert!(!is_sane(&[]));
+        assert!(!is_sane(&[
+            TestResult::Error,
+            TestResult::BuildFail(FailureReason::Unknown),
+        ]));
+        assert!(is_sane(&[
+            TestResult::BuildFail(FailureReason::Unknown),
+            TestResult::TestFail(FailureReason::Unknown),
+        ]));
+        assert!(is_sane(&[TestResult::TestPass]));
+    }
+}
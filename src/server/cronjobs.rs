@@ -1,23 +1,45 @@
 use crate::actions::{Action, ActionsCtx, UpdateLists};
 use crate::prelude::*;
 use crate::server::Data;
+use crate::server::{agents, ecosystem_canary, progress};
 use crate::utils;
 use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 
 const DAY: Duration = Duration::from_secs(60 * 60 * 24);
+const HOUR: Duration = Duration::from_secs(60 * 60);
+const TEN_MINUTES: Duration = Duration::from_secs(60 * 10);
+const MINUTE: Duration = Duration::from_secs(60);
+
 struct JobDescription {
     name: &'static str,
     interval: Duration,
     exec: fn(Arc<Data>) -> Fallible<()>,
 }
 
-static JOBS: &[JobDescription] = &[JobDescription {
-    name: "crates lists update",
-    interval: DAY,
-    exec: update_crates as fn(Arc<Data>) -> Fallible<()>,
-}];
+static JOBS: &[JobDescription] = &[
+    JobDescription {
+        name: "crates lists update",
+        interval: DAY,
+        exec: update_crates as fn(Arc<Data>) -> Fallible<()>,
+    },
+    JobDescription {
+        name: "progress comments",
+        interval: TEN_MINUTES,
+        exec: progress::post_progress_comments as fn(Arc<Data>) -> Fallible<()>,
+    },
+    JobDescription {
+        name: "dead agents reassignment",
+        interval: MINUTE,
+        exec: agents::reassign_dead as fn(Arc<Data>) -> Fallible<()>,
+    },
+    JobDescription {
+        name: "ecosystem canary",
+        interval: HOUR,
+        exec: ecosystem_canary::run as fn(Arc<Data>) -> Fallible<()>,
+    },
+];
 
 pub fn spawn(data: Data) {
     let data = Arc::new(data);
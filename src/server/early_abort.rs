@@ -0,0 +1,158 @@
+use crate::db::QueryUtils;
+use crate::experiments::{Experiment, Status};
+use crate::prelude::*;
+use crate::results::{EncodedLog, TestResult};
+use crate::server::messages::Message;
+use crate::server::Data;
+use regex::Regex;
+
+lazy_static! {
+    /// Lines printed by cargo for every failed build, regardless of the cause of the failure.
+    static ref GENERIC_ERROR: Regex =
+        Regex::new(r"could not compile|aborting due to|build failed|warnings? emitted").unwrap();
+    /// Quoted parts of the errors, which usually contain the crate name or the rustc invocation.
+    static ref QUOTED: Regex = Regex::new(r"`[^`]*`").unwrap();
+}
+
+/// Extract the lines of a log explaining why the build failed, normalized so that the same error
+/// happening on two different crates results in the same line.
+fn error_lines(log: &str) -> Vec<String> {
+    log.lines()
+        .filter(|line| {
+            let lower = line.to_lowercase();
+            (lower.contains("error")
+                || lower.contains("panicked")
+                || lower.contains("didn't exit successfully"))
+                && !GENERIC_ERROR.is_match(&lower)
+        })
+        .map(|line| QUOTED.replace_all(line.trim(), "`...`").into_owned())
+        .collect()
+}
+
+/// Find an error present in all the provided logs, if any.
+fn common_error(logs: &[String]) -> Option<String> {
+    let (first, rest) = logs.split_first()?;
+    let rest = rest.iter().map(|log| error_lines(log)).collect::<Vec<_>>();
+    error_lines(first)
+        .into_iter()
+        .find(|line| rest.iter().all(|lines| lines.contains(line)))
+}
+
+/// Pause the experiment if the first crates built with its second toolchain all failed with the
+/// same error, which usually means the toolchain itself is broken (for example rustc crashing at
+/// startup). The check is only done once, when the configured number of crates is reached, so an
+/// experiment resumed by an operator is not paused again.
+pub(super) fn check(data: &Data, ex: &mut Experiment, received: u32) -> Fallible<()> {
+    let threshold = data.config.server.early_abort.crates;
+    if threshold == 0 || ex.status != Status::Running {
+        return Ok(());
+    }
+
+    let toolchain = ex.toolchains[1].to_string();
+    let completed: u32 = data
+        .db
+        .get_row(
+            "SELECT COUNT(*) AS count FROM results WHERE experiment = ?1 AND toolchain = ?2;",
+            &[&ex.name, &toolchain],
+            |r| r.get("count"),
+        )?
+        .unwrap();
+    if completed < threshold || completed.saturating_sub(received) >= threshold {
+        return Ok(());
+    }
+
+    let rows = data.db.query(
+        "SELECT result, log, encoding FROM results WHERE experiment = ?1 AND toolchain = ?2 \
+         ORDER BY rowid LIMIT ?3;",
+        &[&ex.name, &toolchain, &i64::from(threshold)],
+        |row| -> (String, Vec<u8>, String) {
+            (row.get("result"), row.get("log"), row.get("encoding"))
+        },
+    )?;
+
+    let mut logs = Vec::new();
+    for (result, log, encoding) in rows {
+        match result.parse()? {
+            TestResult::BuildFail(_) | TestResult::Error => {}
+            _ => return Ok(()),
+        }
+        let log = EncodedLog::from_encoded(log, encoding.parse()?);
+        logs.push(String::from_utf8_lossy(&log.to_plain()?).into_owned());
+    }
+
+    let error = match common_error(&logs) {
+        Some(error) => error,
+        None => return Ok(()),
+    };
+
+    warn!(
+        "pausing experiment {}: the first {} crates failed on {} with: {}",
+        ex.name, threshold, toolchain, error
+    );
+    ex.set_status(&data.db, Status::Paused)?;
+
+    if let Some(ref github_issue) = ex.github_issue {
+        Message::new()
+            .line(
+                "rotating_light",
+                format!(
+                    "Experiment **`{}`** paused: the first {} crates all failed on `{}` with \
+                     the same error, `{}`.",
+                    ex.name, threshold, toolchain, error
+                ),
+            )
+            .line(
+                "hammer_and_wrench",
+                "If the toolchain is fine use the `retry` command to resume the experiment.",
+            )
+            .send(&github_issue.api_url, data)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{common_error, error_lines};
+
+    #[test]
+    fn test_error_lines() {
+        let log = "[INFO] [stderr]    Compiling foo v0.1.0\n\
+                   [INFO] [stderr] error: could not compile `foo`.\n\
+                   [INFO] [stderr] Caused by:\n\
+                   [INFO] [stderr]   process didn't exit successfully: `rustc --crate-name foo` \
+                   (signal: 11, SIGSEGV: invalid memory reference)\n";
+        assert_eq!(
+            error_lines(log),
+            vec!["[INFO] [stderr]   process didn't exit successfully: `...` \
+                 (signal: 11, SIGSEGV: invalid memory reference)"
+                .to_string()]
+        );
+    }
+
+    #[test]
+    fn test_common_error() {
+        let segfault = |krate: &str| {
+            format!(
+                "[INFO] [stderr] error: could not compile `{0}`.\n\
+                 [INFO] [stderr] process didn't exit successfully: `rustc --crate-name {0}` \
+                 (signal: 11, SIGSEGV: invalid memory reference)\n",
+                krate
+            )
+        };
+        let type_error = "[INFO] [stderr] error[E0308]: mismatched types\n\
+                          [INFO] [stderr] error: aborting due to previous error\n"
+            .to_string();
+
+        assert_eq!(common_error(&[]), None);
+        assert_eq!(
+            common_error(&[segfault("foo"), segfault("bar")]),
+            Some(
+                "[INFO] [stderr] process didn't exit successfully: `...` \
+                 (signal: 11, SIGSEGV: invalid memory reference)"
+                    .to_string()
+            )
+        );
+        assert_eq!(common_error(&[segfault("foo"), type_error]), None);
+    }
+}
@@ -0,0 +1,341 @@
+//! Continuous mode testing a small rotating sample of the most downloaded crates on every new
+//! nightly, comparing it with the nightly tested before. A jump of the failure rate is reported
+//! as soon as the experiment finishes, days before somebody would request a full run.
+
+use crate::actions::{Action, ActionsCtx, CreateExperiment};
+use crate::config::EcosystemCanaryConfig;
+use crate::crates::lists::{List, RegistryList};
+use crate::crates::{Crate, RegistryCrate};
+use crate::db::{Database, QueryUtils};
+use crate::experiments::{CapLints, CrateSelect, Experiment, Mode, Status, TestRunner};
+use crate::prelude::*;
+use crate::results::TestResult;
+use crate::server::messages::Message;
+use crate::server::Data;
+use crate::utils;
+use chrono::{Duration, NaiveDate, Utc};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+const MANIFEST_URL: &str = "https://static.rust-lang.org/dist/channel-rust-nightly.toml";
+const NAME_PREFIX: &str = "ecosystem-canary-";
+/// The canary experiments are never urgent, so they only run when no other experiment is queued.
+const PRIORITY: i32 = -10;
+
+/// Date of the nightly described by the release manifest.
+fn parse_manifest_date(manifest: &str) -> Option<NaiveDate> {
+    manifest
+        .lines()
+        .find(|line| line.starts_with("date = "))
+        .and_then(|line| {
+            line["date = ".len()..]
+                .trim()
+                .trim_matches('"')
+                .parse()
+                .ok()
+        })
+}
+
+fn latest_nightly() -> Fallible<NaiveDate> {
+    let manifest = utils::http::get_sync(MANIFEST_URL)?.text()?;
+    parse_manifest_date(&manifest).ok_or_else(|| {
+        err_msg(format!(
+            "missing the date of the nightly in {}",
+            MANIFEST_URL
+        ))
+    })
+}
+
+/// Pick `count` crates out of the most downloaded ones, starting from `offset` and wrapping
+/// around, so that consecutive samples rotate through all of them.
+fn sample(popular: &[String], offset: usize, count: usize) -> Vec<String> {
+    if popular.is_empty() {
+        return Vec::new();
+    }
+    popular
+        .iter()
+        .cycle()
+        .skip(offset % popular.len())
+        .take(count.min(popular.len()))
+        .cloned()
+        .collect()
+}
+
+/// Fraction of the crates which failed to build or to test. Spurious failures (like timeouts)
+/// vary too much between runs to be compared, so they're not counted as failures.
+fn failure_rate(results: &[TestResult]) -> Option<f64> {
+    if results.is_empty() {
+        return None;
+    }
+    let failed = results
+        .iter()
+        .filter(|result| match result {
+            TestResult::BuildFail(reason) | TestResult::TestFail(reason) => !reason.is_spurious(),
+            _ => false,
+        })
+        .count();
+    Some(failed as f64 / results.len() as f64)
+}
+
+fn toolchain_failure_rate(
+    db: &Database,
+    ex: &Experiment,
+    toolchain: &str,
+) -> Fallible<Option<f64>> {
+    let results = db
+        .query(
+            "SELECT result FROM results WHERE experiment = ?1 AND toolchain = ?2;",
+            &[&ex.name, &toolchain],
+            |row| row.get::<_, String>("result"),
+        )?
+        .iter()
+        .map(|result| Ok(result.parse()?))
+        .collect::<Fallible<Vec<TestResult>>>()?;
+    Ok(failure_rate(&results))
+}
+
+/// API URL of an issue written as `org/repo#number`.
+fn issue_api_url(api_url: &str, issue: &str) -> Fallible<String> {
+    let mut parts = issue.splitn(2, '#');
+    match (parts.next(), parts.next().map(|n| n.parse::<u32>())) {
+        (Some(repo), Some(Ok(number))) if repo.contains('/') => {
+            Ok(format!("{}/repos/{}/issues/{}", api_url, repo, number))
+        }
+        _ => bail!("invalid issue for the ecosystem canary alerts: {}", issue),
+    }
+}
+
+/// Record the failure rates of the canary experiments which finished running, alerting if the
+/// new nightly fails noticeably more often than the previous one on the same crates.
+fn check_finished(data: &Data, config: &EcosystemCanaryConfig) -> Fallible<()> {
+    let pending = data.db.query(
+        "SELECT experiment FROM ecosystem_canary WHERE checked_at IS NULL;",
+        &[],
+        |row| row.get::<_, String>("experiment"),
+    )?;
+
+    for name in pending {
+        let ex = match Experiment::get(&data.db, &name)? {
+            Some(ex) => ex,
+            None => {
+                data.db.execute(
+                    "UPDATE ecosystem_canary SET checked_at = ?1 WHERE experiment = ?2;",
+                    &[&Utc::now(), &name],
+                )?;
+                continue;
+            }
+        };
+        match ex.status {
+            Status::Queued | Status::Running | Status::Paused | Status::Failed => continue,
+            Status::NeedsReport
+            | Status::GeneratingReport
+            | Status::ReportFailed
+            | Status::AwaitingApproval
+            | Status::Completed => {}
+        }
+
+        let before = toolchain_failure_rate(&data.db, &ex, &ex.toolchains[0].to_string())?;
+        let after = toolchain_failure_rate(&data.db, &ex, &ex.toolchains[1].to_string())?;
+        data.db.execute(
+            "UPDATE ecosystem_canary SET baseline_failure_rate = ?1, failure_rate = ?2, \
+             checked_at = ?3 WHERE experiment = ?4;",
+            &[&before, &after, &Utc::now(), &name],
+        )?;
+
+        let (before, after) = match (before, after) {
+            (Some(before), Some(after)) => (before, after),
+            _ => continue,
+        };
+        info!(
+            "ecosystem canary {}: failure rate of {:.1}% on {}, {:.1}% on {}",
+            ex.name,
+            before * 100.0,
+            ex.toolchains[0],
+            after * 100.0,
+            ex.toolchains[1]
+        );
+        if (after - before) * 100.0 <= f64::from(config.threshold) {
+            continue;
+        }
+
+        warn!(
+            "the failure rate of the ecosystem canary jumped from {:.1}% to {:.1}% on {}",
+            before * 100.0,
+            after * 100.0,
+            ex.toolchains[1]
+        );
+        if let Some(issue) = &config.issue {
+            let mut message = Message::new().line(
+                "rotating_light",
+                format!(
+                    "The failure rate of the ecosystem canary jumped from **{:.1}%** on `{}` to \
+                     **{:.1}%** on `{}` (experiment **`{}`**).",
+                    before * 100.0,
+                    ex.toolchains[0],
+                    after * 100.0,
+                    ex.toolchains[1],
+                    ex.name
+                ),
+            );
+            if let Some(report_url) = &ex.report_url {
+                message = message.line("newspaper", format!("[Open the report]({}).", report_url));
+            }
+            message.send(&issue_api_url(&data.config.github.api_url, issue)?, data)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Create the canary experiment of the latest nightly, unless it was already tested or the
+/// previous canary experiment is still running.
+fn start_next(data: &Data, config: &EcosystemCanaryConfig) -> Fallible<()> {
+    let last = data.db.get_row(
+        "SELECT experiment, nightly, sample_offset FROM ecosystem_canary \
+         ORDER BY created_at DESC LIMIT 1;",
+        &[],
+        |row| {
+            (
+                row.get::<_, String>("experiment"),
+                row.get::<_, String>("nightly"),
+                row.get::<_, i64>("sample_offset"),
+            )
+        },
+    )?;
+    if let Some((name, _, _)) = &last {
+        if let Some(ex) = Experiment::get(&data.db, name)? {
+            if let Status::Queued | Status::Running | Status::Paused = ex.status {
+                return Ok(());
+            }
+        }
+    }
+
+    let date = latest_nightly()?;
+    let nightly = format!("nightly-{}", date);
+    let (baseline, offset) = match last {
+        Some((_, ref last_nightly, _)) if *last_nightly == nightly => return Ok(()),
+        Some((_, last_nightly, offset)) => (last_nightly, offset as usize + config.crates),
+        None => (format!("nightly-{}", date - Duration::days(1)), 0),
+    };
+
+    let popular = RegistryList::get(&data.db)?
+        .into_iter()
+        .filter_map(|krate| match krate {
+            Crate::Registry(RegistryCrate { name, .. }) => Some(name),
+            _ => None,
+        })
+        .take(config.pool)
+        .collect::<Vec<_>>();
+    if popular.is_empty() {
+        bail!("the registry list is empty, the ecosystem canary can't pick any crate");
+    }
+    let offset = offset % popular.len();
+
+    let name = format!("{}{}", NAME_PREFIX, date);
+    info!(
+        "starting the ecosystem canary {}, comparing {} with {}",
+        name, nightly, baseline
+    );
+    CreateExperiment {
+        name: name.clone(),
+        toolchains: [baseline.parse()?, nightly.parse()?],
+        mode: Mode::BuildAndTest,
+        crates: CrateSelect::List(
+            sample(&popular, offset, config.crates)
+                .into_iter()
+                .collect(),
+        ),
+        cap_lints: CapLints::Forbid,
+        priority: PRIORITY,
+        github_issue: None,
+        ignore_blacklist: false,
+        assign: None,
+        requirement: None,
+        cargo_config: None,
+        expected_regressions: Vec::new(),
+        env: BTreeMap::new(),
+        deny_lints: Vec::new(),
+        cargo_update: false,
+        failure_threshold: None,
+        // The results of the previous nightly were usually banked by the last canary
+        reuse_baseline: true,
+        canary: false,
+        test_runner: TestRunner::Cargo,
+        namespace: None,
+        survey: false,
+        parent: None,
+        sandbox_image: None,
+    }
+    .apply(&ActionsCtx::new(&data.db, &data.config))?;
+
+    data.db.execute(
+        "INSERT INTO ecosystem_canary (experiment, nightly, sample_offset, created_at) \
+         VALUES (?1, ?2, ?3, ?4);",
+        &[&name, &nightly, &(offset as i64), &Utc::now()],
+    )?;
+    Ok(())
+}
+
+pub(super) fn run(data: Arc<Data>) -> Fallible<()> {
+    let config = &data.config.server.ecosystem_canary;
+    if config.crates == 0 {
+        return Ok(());
+    }
+
+    check_finished(&data, config)?;
+    start_next(&data, config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{failure_rate, issue_api_url, parse_manifest_date, sample};
+    use crate::results::{FailureReason, TestResult};
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_parse_manifest_date() {
+        let manifest = "manifest-version = \"2\"\ndate = \"2026-10-14\"\n[pkg.cargo]\n";
+        assert_eq!(
+            parse_manifest_date(manifest),
+            Some(NaiveDate::from_ymd(2026, 10, 14))
+        );
+        assert_eq!(parse_manifest_date("manifest-version = \"2\"\n"), None);
+    }
+
+    #[test]
+    fn test_sample() {
+        let popular = ["a", "b", "c", "d", "e"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(sample(&popular, 0, 2), vec!["a", "b"]);
+        assert_eq!(sample(&popular, 4, 3), vec!["e", "a", "b"]);
+        assert_eq!(sample(&popular, 7, 2), vec!["c", "d"]);
+        assert_eq!(sample(&popular, 0, 10).len(), 5);
+        assert!(sample(&[], 3, 2).is_empty());
+    }
+
+    #[test]
+    fn test_failure_rate() {
+        assert_eq!(failure_rate(&[]), None);
+        assert_eq!(
+            failure_rate(&[
+                TestResult::TestPass,
+                TestResult::BuildFail(FailureReason::Unknown),
+                TestResult::TestFail(FailureReason::Timeout),
+                TestResult::TestSkipped,
+            ]),
+            Some(0.25)
+        );
+    }
+
+    #[test]
+    fn test_issue_api_url() {
+        assert_eq!(
+            issue_api_url("https://api.github.com", "rust-lang/crater#42").unwrap(),
+            "https://api.github.com/repos/rust-lang/crater/issues/42"
+        );
+        assert!(issue_api_url("https://api.github.com", "rust-lang/crater").is_err());
+        assert!(issue_api_url("https://api.github.com", "crater#42").is_err());
+    }
+}
@@ -0,0 +1,240 @@
+use crate::config::EmailDigestConfig;
+use crate::experiments::Experiment;
+use crate::prelude::*;
+use crate::report::{Comparison, TestResults};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Minimal SMTP client, only able to send plain text messages through relays that don't require
+/// authentication (like a local MTA).
+struct SmtpConnection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl SmtpConnection {
+    fn connect(server: &str) -> Fallible<Self> {
+        let stream = TcpStream::connect(server)
+            .with_context(|_| format!("failed to connect to the SMTP server {}", server))?;
+        stream.set_read_timeout(Some(SMTP_TIMEOUT))?;
+        stream.set_write_timeout(Some(SMTP_TIMEOUT))?;
+
+        let mut conn = SmtpConnection {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+        };
+        conn.expect_reply('2')?;
+        Ok(conn)
+    }
+
+    /// Read a (possibly multiline) reply, failing if its code doesn't start with `class`.
+    fn expect_reply(&mut self, class: char) -> Fallible<()> {
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                bail!("the SMTP server closed the connection");
+            }
+            let line = line.trim_end();
+            if !line.starts_with(class) {
+                bail!("unexpected reply from the SMTP server: {}", line);
+            }
+            // The last line of a reply has a space after the code instead of a dash
+            if line.chars().nth(3) != Some('-') {
+                return Ok(());
+            }
+        }
+    }
+
+    fn command(&mut self, command: &str, class: char) -> Fallible<()> {
+        write!(self.writer, "{}\r\n", command)?;
+        self.writer.flush()?;
+        self.expect_reply(class)
+    }
+}
+
+/// Render the digest of a completed experiment, returning its subject and its body.
+fn render_digest(
+    ex: &Experiment,
+    res: &TestResults,
+    report_url: &str,
+    error_groups: usize,
+) -> (String, String) {
+    let count = |comparison| res.info.get(&comparison).cloned().unwrap_or(0);
+    let subject = format!(
+        "[crater] {} completed: {} regressed, {} fixed",
+        ex.name,
+        count(Comparison::Regressed),
+        count(Comparison::Fixed)
+    );
+
+    let mut body = format!(
+        "Experiment {} ({} mode, {} against {}) is completed.\n\n",
+        ex.name,
+        ex.mode.to_str(),
+        ex.toolchains[0],
+        ex.toolchains[1]
+    );
+    for (comparison, count) in &res.info {
+        body.push_str(&format!("{}: {}\n", comparison.to_string(), count));
+    }
+
+    let groups = res.error_groups(Comparison::Regressed);
+    if !groups.is_empty() && error_groups > 0 {
+        body.push_str("\nMost common errors of the regressed crates:\n");
+        for (result, count) in groups.iter().take(error_groups) {
+            body.push_str(&format!("{}: {} crates\n", result, count));
+        }
+    }
+
+    body.push_str(&format!("\nFull report: {}\n", report_url));
+    (subject, body)
+}
+
+fn send(config: &EmailDigestConfig, server: &str, subject: &str, body: &str) -> Fallible<()> {
+    let mut conn = SmtpConnection::connect(server)?;
+    conn.command("EHLO crater", '2')?;
+    conn.command(&format!("MAIL FROM:<{}>", config.from), '2')?;
+    for to in &config.to {
+        conn.command(&format!("RCPT TO:<{}>", to), '2')?;
+    }
+    conn.command("DATA", '3')?;
+
+    let mut message = format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n",
+        config.from,
+        config.to.join(", "),
+        subject
+    );
+    for line in body.lines() {
+        // A line containing only a dot ends the message, so leading dots are doubled
+        if line.starts_with('.') {
+            message.push('.');
+        }
+        message.push_str(line);
+        message.push_str("\r\n");
+    }
+    message.push('.');
+    conn.command(&message, '2')?;
+
+    conn.command("QUIT", '2')?;
+    Ok(())
+}
+
+/// Send the digest of a completed experiment to the configured recipients, if the digests are
+/// enabled.
+pub fn send_digest(
+    config: &EmailDigestConfig,
+    ex: &Experiment,
+    res: &TestResults,
+    report_url: &str,
+) -> Fallible<()> {
+    let server = match &config.smtp_server {
+        Some(server) if !config.to.is_empty() => server,
+        _ => return Ok(()),
+    };
+
+    let (subject, body) = render_digest(ex, res, report_url, config.error_groups);
+    send(config, server, &subject, &body)
+        .with_context(|_| format!("failed to send the digest of {}", ex.name))?;
+    info!("sent the digest of {} to {}", ex.name, config.to.join(", "));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_digest, send};
+    use crate::actions::{Action, ActionsCtx, CreateExperiment};
+    use crate::config::{Config, EmailDigestConfig};
+    use crate::db::Database;
+    use crate::experiments::Experiment;
+    use crate::report::{Comparison, TestResults};
+    use indexmap::IndexMap;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_render_digest() {
+        let db = Database::temp().unwrap();
+        let config = Config::default();
+        let ctx = ActionsCtx::new(&db, &config);
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+        CreateExperiment::dummy("dummy").apply(&ctx).unwrap();
+        let ex = Experiment::get(&db, "dummy").unwrap().unwrap();
+
+        let mut info = IndexMap::new();
+        info.insert(Comparison::Regressed, 2);
+        info.insert(Comparison::SameTestPass, 40);
+        let res = TestResults {
+            categories: IndexMap::new(),
+            info,
+        };
+
+        let (subject, body) = render_digest(&ex, &res, "https://example.com/dummy", 5);
+        assert_eq!(subject, "[crater] dummy completed: 2 regressed, 0 fixed");
+        assert!(body.contains("regressed: 2\n"));
+        assert!(body.contains("test-pass: 40\n"));
+        assert!(!body.contains("Most common errors"));
+        assert!(body.ends_with("Full report: https://example.com/dummy\n"));
+    }
+
+    #[test]
+    fn test_send() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let server = listener.local_addr().unwrap().to_string();
+
+        let fake_server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut received = Vec::new();
+
+            stream.write_all(b"220 localhost ready\r\n").unwrap();
+            let mut in_data = false;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let line = line.trim_end().to_string();
+                let reply: &[u8] = if in_data {
+                    if line == "." {
+                        in_data = false;
+                        b"250 queued\r\n"
+                    } else {
+                        received.push(line);
+                        continue;
+                    }
+                } else if line == "DATA" {
+                    in_data = true;
+                    b"354 go ahead\r\n"
+                } else if line == "EHLO crater" {
+                    b"250-localhost\r\n250 8BITMIME\r\n"
+                } else if line == "QUIT" {
+                    stream.write_all(b"221 bye\r\n").unwrap();
+                    return received;
+                } else {
+                    received.push(line);
+                    b"250 ok\r\n"
+                };
+                stream.write_all(reply).unwrap();
+            }
+        });
+
+        let config = EmailDigestConfig {
+            smtp_server: Some(server.clone()),
+            from: "crater@example.com".into(),
+            to: vec!["a@example.com".into(), "b@example.com".into()],
+            error_groups: 5,
+        };
+        send(&config, &server, "hello", "first line\n.second line").unwrap();
+
+        let received = fake_server.join().unwrap();
+        assert_eq!(received[0], "MAIL FROM:<crater@example.com>");
+        assert_eq!(received[1], "RCPT TO:<a@example.com>");
+        assert_eq!(received[2], "RCPT TO:<b@example.com>");
+        assert!(received.contains(&"Subject: hello".to_string()));
+        assert!(received.contains(&"To: a@example.com, b@example.com".to_string()));
+        assert!(received.ends_with(&["first line".to_string(), "..second line".to_string()]));
+    }
+}
@@ -0,0 +1,82 @@
+use crate::db::QueryUtils;
+use crate::experiments::{Experiment, Status};
+use crate::prelude::*;
+use crate::report::{self, Comparison};
+use crate::results::{DatabaseDB, ProgressData};
+use crate::server::messages::Message;
+use crate::server::Data;
+use std::collections::HashSet;
+
+/// Pause the experiment if its regressions exceeded the failure threshold it declared, so that a
+/// catastrophic toolchain doesn't keep the agents busy until someone notices it. The comparisons
+/// are only computed when the received results contain a new regression, and the threshold is
+/// removed once it's hit so the experiment can be resumed by an operator.
+pub(super) fn check(data: &Data, ex: &mut Experiment, received: &ProgressData) -> Fallible<()> {
+    let threshold = match ex.failure_threshold {
+        Some(threshold) if ex.status == Status::Running => threshold,
+        _ => return Ok(()),
+    };
+
+    let db = DatabaseDB::new(&data.db);
+    let crates = received
+        .results
+        .iter()
+        .map(|result| &result.krate)
+        .collect::<HashSet<_>>();
+    let mut new_regression = false;
+    for krate in crates {
+        if report::compare_crate(&db, &data.config, ex, krate)? == Comparison::Regressed {
+            new_regression = true;
+            break;
+        }
+    }
+    if !new_regression {
+        return Ok(());
+    }
+
+    let compared = report::compare_completed(&db, &data.config, ex)?;
+    let regressed = compared
+        .iter()
+        .filter(|(comparison, _)| *comparison == Comparison::Regressed)
+        .count() as u32;
+    if !threshold.exceeded(regressed, compared.len() as u32) {
+        return Ok(());
+    }
+
+    warn!(
+        "pausing experiment {}: {} regressions out of {} crates exceed its threshold of {}",
+        ex.name,
+        regressed,
+        compared.len(),
+        threshold
+    );
+    data.db.execute(
+        "UPDATE experiments SET failure_threshold = NULL WHERE name = ?1;",
+        &[&ex.name],
+    )?;
+    ex.failure_threshold = None;
+    ex.set_status(&data.db, Status::Paused)?;
+
+    if let Some(ref github_issue) = ex.github_issue {
+        Message::new()
+            .line(
+                "rotating_light",
+                format!(
+                    "Experiment **`{}`** paused: {} crates regressed out of the {} compared so \
+                     far, exceeding the failure threshold of {}.",
+                    ex.name,
+                    regressed,
+                    compared.len(),
+                    threshold
+                ),
+            )
+            .line(
+                "hammer_and_wrench",
+                "The threshold was removed: use the `retry` command to resume the experiment, or \
+                 `abort` to stop it.",
+            )
+            .send(&github_issue.api_url, data)?;
+    }
+
+    Ok(())
+}
@@ -1,12 +1,24 @@
+use crate::config::GitHubConfig;
 use crate::prelude::*;
-use crate::server::tokens::Tokens;
+use crate::server::tokens::{GitHubAppTokens, Tokens};
 use crate::utils;
-use http::header::AUTHORIZATION;
+use chrono::{DateTime, Duration, Utc};
+use http::header::{ACCEPT, AUTHORIZATION};
 use http::Method;
 use http::StatusCode;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
 use reqwest::RequestBuilder;
 use serde_json::json;
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// The endpoints of GitHub Apps still require opting into their preview.
+const APP_PREVIEW: &str = "application/vnd.github.machine-man-preview+json";
+/// GitHub refuses the JWTs valid for more than ten minutes, including the backdating.
+const JWT_LIFETIME_SECS: i64 = 9 * 60;
+/// Installation tokens are refreshed this long before they expire, to avoid requests started
+/// right before the expiration from failing.
+const TOKEN_EXPIRY_MARGIN_SECS: i64 = 5 * 60;
 
 #[derive(Debug, Fail)]
 pub enum GitHubError {
@@ -26,39 +38,149 @@ pub trait GitHub {
     fn get_pr_head_sha(&self, repo: &str, pr: i32) -> Fallible<String>;
 }
 
+#[derive(Serialize)]
+struct JwtClaims {
+    iat: i64,
+    exp: i64,
+    iss: String,
+}
+
+#[derive(Deserialize)]
+struct InstallationToken {
+    token: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// Bot authenticated as a GitHub App, acting through the short-lived tokens of its installation.
+struct GitHubApp {
+    id: u64,
+    installation_id: u64,
+    key: EncodingKey,
+    token: Mutex<Option<(String, DateTime<Utc>)>>,
+}
+
+impl GitHubApp {
+    fn new(tokens: &GitHubAppTokens) -> Fallible<Self> {
+        let pem = std::fs::read(&tokens.private_key).with_context(|_| {
+            format!(
+                "failed to read the private key of the GitHub App at {}",
+                tokens.private_key.display()
+            )
+        })?;
+        Ok(GitHubApp {
+            id: tokens.app_id,
+            installation_id: tokens.installation_id,
+            key: EncodingKey::from_rsa_pem(&pem)?,
+            token: Mutex::new(None),
+        })
+    }
+
+    /// Request authenticated as the app itself, which is only allowed on the endpoints of apps.
+    fn request(&self, method: Method, url: &str) -> Fallible<RequestBuilder> {
+        let now = Utc::now().timestamp();
+        let claims = JwtClaims {
+            // Backdated to allow for the clock of GitHub drifting from ours
+            iat: now - 60,
+            exp: now + JWT_LIFETIME_SECS,
+            iss: self.id.to_string(),
+        };
+        let jwt = jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &self.key)?;
+        Ok(utils::http::prepare_sync(method, url)
+            .header(AUTHORIZATION, format!("Bearer {}", jwt))
+            .header(ACCEPT, APP_PREVIEW))
+    }
+
+    /// Token of the installation, refreshed when it's about to expire.
+    fn installation_token(&self, api_url: &str) -> Fallible<String> {
+        let mut cache = self.token.lock().unwrap();
+        if let Some((token, expires)) = &*cache {
+            if Utc::now() + Duration::seconds(TOKEN_EXPIRY_MARGIN_SECS) < *expires {
+                return Ok(token.clone());
+            }
+        }
+
+        let url = format!(
+            "{}/app/installations/{}/access_tokens",
+            api_url, self.installation_id
+        );
+        let mut response = self.request(Method::POST, &url)?.send()?;
+        if response.status() != StatusCode::CREATED {
+            let error: Error = response.json()?;
+            return Err(GitHubError::RequestFailed(response.status(), error.message).into());
+        }
+        let token: InstallationToken = response.json()?;
+        debug!(
+            "refreshed the GitHub App token, expiring at {}",
+            token.expires_at
+        );
+        *cache = Some((token.token.clone(), token.expires_at));
+        Ok(token.token)
+    }
+}
+
+enum GitHubToken {
+    Personal(String),
+    App(GitHubApp),
+}
+
 #[derive(Clone)]
 pub struct GitHubApi {
-    token: String,
+    token: Arc<GitHubToken>,
+    api_url: String,
 }
 
 impl GitHubApi {
-    pub fn new(tokens: &Tokens) -> Self {
-        GitHubApi {
-            token: tokens.bot.api_token.clone(),
-        }
+    pub fn new(tokens: &Tokens, config: &GitHubConfig) -> Fallible<Self> {
+        let token = match &tokens.bot.app {
+            Some(app) => GitHubToken::App(GitHubApp::new(app)?),
+            None if !tokens.bot.api_token.is_empty() => {
+                GitHubToken::Personal(tokens.bot.api_token.clone())
+            }
+            None => bail!("either the api-token or the app of the bot must be configured"),
+        };
+        Ok(GitHubApi {
+            token: Arc::new(token),
+            api_url: config.api_url.trim_end_matches('/').to_string(),
+        })
     }
 
-    fn build_request(&self, method: Method, url: &str) -> RequestBuilder {
-        let url = if !url.starts_with("https://") {
-            format!("https://api.github.com/{}", url)
+    fn url(&self, url: &str) -> String {
+        if !url.starts_with("https://") && !url.starts_with("http://") {
+            format!("{}/{}", self.api_url, url)
         } else {
             url.to_string()
-        };
+        }
+    }
 
-        utils::http::prepare_sync(method, &url)
-            .header(AUTHORIZATION, format!("token {}", self.token))
+    fn build_request(&self, method: Method, url: &str) -> Fallible<RequestBuilder> {
+        let token = match &*self.token {
+            GitHubToken::Personal(token) => token.clone(),
+            GitHubToken::App(app) => app.installation_token(&self.api_url)?,
+        };
+        Ok(utils::http::prepare_sync(method, &self.url(url))
+            .header(AUTHORIZATION, format!("token {}", token)))
     }
 }
 
 impl GitHub for GitHubApi {
     fn username(&self) -> Fallible<String> {
-        let response: User = self.build_request(Method::GET, "user").send()?.json()?;
+        if let GitHubToken::App(app) = &*self.token {
+            // Installation tokens can't access `/user`, so the bot is named after the app instead
+            let response: App = app
+                .request(Method::GET, &self.url("app"))?
+                .send()?
+                .error_for_status()?
+                .json()?;
+            return Ok(response.slug);
+        }
+
+        let response: User = self.build_request(Method::GET, "user")?.send()?.json()?;
         Ok(response.login)
     }
 
     fn post_comment(&self, issue_url: &str, body: &str) -> Fallible<()> {
         let mut response = self
-            .build_request(Method::POST, &format!("{}/comments", issue_url))
+            .build_request(Method::POST, &format!("{}/comments", issue_url))?
             .json(&json!({
                 "body": body,
             }))
@@ -74,7 +196,7 @@ impl GitHub for GitHubApi {
 
     fn list_labels(&self, issue_url: &str) -> Fallible<Vec<Label>> {
         let mut response = self
-            .build_request(Method::GET, &format!("{}/labels", issue_url))
+            .build_request(Method::GET, &format!("{}/labels", issue_url))?
             .send()?;
 
         if response.status() == StatusCode::OK {
@@ -87,7 +209,7 @@ impl GitHub for GitHubApi {
 
     fn add_label(&self, issue_url: &str, label: &str) -> Fallible<()> {
         let mut response = self
-            .build_request(Method::POST, &format!("{}/labels", issue_url))
+            .build_request(Method::POST, &format!("{}/labels", issue_url))?
             .json(&json!([label]))
             .send()?;
 
@@ -101,7 +223,7 @@ impl GitHub for GitHubApi {
 
     fn remove_label(&self, issue_url: &str, label: &str) -> Fallible<()> {
         let mut response = self
-            .build_request(Method::DELETE, &format!("{}/labels/{}", issue_url, label))
+            .build_request(Method::DELETE, &format!("{}/labels/{}", issue_url, label))?
             .send()?;
 
         if response.status() == StatusCode::OK {
@@ -114,7 +236,7 @@ impl GitHub for GitHubApi {
 
     fn list_teams(&self, org: &str) -> Fallible<HashMap<String, usize>> {
         let mut response = self
-            .build_request(Method::GET, &format!("orgs/{}/teams", org))
+            .build_request(Method::GET, &format!("orgs/{}/teams", org))?
             .send()?;
 
         if response.status() == StatusCode::OK {
@@ -128,7 +250,7 @@ impl GitHub for GitHubApi {
 
     fn team_members(&self, team: usize) -> Fallible<Vec<String>> {
         let mut response = self
-            .build_request(Method::GET, &format!("teams/{}/members", team))
+            .build_request(Method::GET, &format!("teams/{}/members", team))?
             .send()?;
 
         if response.status() == StatusCode::OK {
@@ -142,7 +264,7 @@ impl GitHub for GitHubApi {
 
     fn get_commit(&self, repo: &str, sha: &str) -> Fallible<Commit> {
         let commit = self
-            .build_request(Method::GET, &format!("repos/{}/commits/{}", repo, sha))
+            .build_request(Method::GET, &format!("repos/{}/commits/{}", repo, sha))?
             .send()?
             .error_for_status()?
             .json()?;
@@ -151,7 +273,7 @@ impl GitHub for GitHubApi {
 
     fn get_pr_head_sha(&self, repo: &str, pr: i32) -> Fallible<String> {
         let pr: PullRequestData = self
-            .build_request(Method::GET, &format!("repos/{}/pulls/{}", repo, pr))
+            .build_request(Method::GET, &format!("repos/{}/pulls/{}", repo, pr))?
             .send()?
             .error_for_status()?
             .json()?;
@@ -170,6 +292,11 @@ pub struct User {
     pub login: String,
 }
 
+#[derive(Deserialize)]
+pub struct App {
+    pub slug: String,
+}
+
 #[derive(Deserialize)]
 pub struct EventIssueComment {
     pub action: String,
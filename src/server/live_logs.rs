@@ -0,0 +1,106 @@
+use crate::crates::Crate;
+use crate::toolchain::Toolchain;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Logs of the builds still running on the agents, uploaded in chunks while the builds run and
+/// discarded as soon as their result is received. They're only kept in memory, as they're just
+/// meant to show what a build that has been running for a long time is doing.
+#[derive(Clone, Default)]
+pub struct LiveLogs {
+    logs: Arc<Mutex<HashMap<(String, Crate, Toolchain), String>>>,
+}
+
+impl LiveLogs {
+    pub fn new() -> Self {
+        LiveLogs::default()
+    }
+
+    /// Write a chunk of the log at `offset`, replacing anything already present after it so that
+    /// chunks sent again by the agent are not duplicated. Returns `false` if the offset is past
+    /// the end of the log, which means the agent has to send the log again from the start.
+    pub fn append(
+        &self,
+        ex: &str,
+        krate: &Crate,
+        toolchain: &Toolchain,
+        offset: usize,
+        chunk: &str,
+    ) -> bool {
+        let mut logs = self.logs.lock().unwrap();
+        let log = logs
+            .entry((ex.to_string(), krate.clone(), toolchain.clone()))
+            .or_insert_with(String::new);
+        if offset > log.len() || !log.is_char_boundary(offset) {
+            return false;
+        }
+        log.truncate(offset);
+        log.push_str(chunk);
+        true
+    }
+
+    /// Logs of the builds of the crate still running, along with their toolchain.
+    pub fn get(&self, ex: &str, krate: &Crate) -> Vec<(Toolchain, String)> {
+        let logs = self.logs.lock().unwrap();
+        let mut result = logs
+            .iter()
+            .filter(|((name, k, _), _)| name == ex && k == krate)
+            .map(|((_, _, tc), log)| (tc.clone(), log.clone()))
+            .collect::<Vec<_>>();
+        result.sort_by_key(|(tc, _)| tc.to_string());
+        result
+    }
+
+    pub fn remove(&self, ex: &str, krate: &Crate, toolchain: &Toolchain) {
+        self.logs
+            .lock()
+            .unwrap()
+            .remove(&(ex.to_string(), krate.clone(), toolchain.clone()));
+    }
+
+    /// Discard all the logs of an experiment, including the ones of builds that will never
+    /// complete because their agent died.
+    pub fn remove_experiment(&self, ex: &str) {
+        self.logs
+            .lock()
+            .unwrap()
+            .retain(|(name, _, _), _| name != ex);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LiveLogs;
+    use crate::crates::Crate;
+    use crate::toolchain::{MAIN_TOOLCHAIN, TEST_TOOLCHAIN};
+
+    #[test]
+    fn test_append() {
+        let logs = LiveLogs::new();
+        let krate = Crate::Local("build-pass".into());
+        let tc = &*MAIN_TOOLCHAIN;
+
+        assert!(logs.append("ex", &krate, tc, 0, "foo\n"));
+        assert!(logs.append("ex", &krate, tc, 4, "bar\n"));
+        // Chunks sent again replace the previous ones
+        assert!(logs.append("ex", &krate, tc, 4, "baz\n"));
+        // Chunks following missing ones are rejected
+        assert!(!logs.append("ex", &krate, tc, 42, "qux\n"));
+        assert!(logs.append("ex", &krate, &TEST_TOOLCHAIN, 0, "beta\n"));
+        assert!(logs.append("other", &krate, tc, 0, "other\n"));
+
+        assert_eq!(
+            logs.get("ex", &krate),
+            vec![
+                (TEST_TOOLCHAIN.clone(), "beta\n".to_string()),
+                (MAIN_TOOLCHAIN.clone(), "foo\nbaz\n".to_string()),
+            ]
+        );
+
+        logs.remove("ex", &krate, &TEST_TOOLCHAIN);
+        assert_eq!(logs.get("ex", &krate).len(), 1);
+        logs.remove_experiment("ex");
+        assert!(logs.get("ex", &krate).is_empty());
+        assert_eq!(logs.get("other", &krate).len(), 1);
+    }
+}
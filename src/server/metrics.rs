@@ -5,8 +5,8 @@ use crate::server::agents::Agent;
 use chrono::{DateTime, Utc};
 use prometheus::proto::{Metric, MetricFamily};
 use prometheus::{
-    IntCounterVec, IntGauge, IntGaugeVec, __register_counter_vec, __register_gauge,
-    __register_gauge_vec,
+    __register_counter_vec, __register_gauge, __register_gauge_vec, IntCounterVec, IntGauge,
+    IntGaugeVec,
 };
 
 const JOBS_METRIC: &str = "crater_completed_jobs_total";
@@ -219,7 +219,7 @@ mod tests {
         }
         .apply(&ctx)
         .unwrap();
-        let ex = Experiment::next(&db, &assignee).unwrap().unwrap().1;
+        let mut ex = Experiment::next(&db, &assignee).unwrap().unwrap().1;
         ex.get_uncompleted_crates(&db, &config, &assignee).unwrap();
         METRICS.update_agent_status(&db, &agent_list_ref).unwrap();
 
@@ -1,10 +1,17 @@
 pub mod agents;
 pub mod api_types;
 mod auth;
+mod canary;
 mod cronjobs;
+mod early_abort;
+mod ecosystem_canary;
+mod email;
+mod failure_threshold;
 mod github;
+mod live_logs;
 mod messages;
 mod metrics;
+mod progress;
 mod reports;
 mod routes;
 pub mod tokens;
@@ -16,6 +23,7 @@ use crate::prelude::*;
 use crate::server::agents::Agents;
 use crate::server::auth::ACL;
 use crate::server::github::{GitHub, GitHubApi};
+use crate::server::live_logs::LiveLogs;
 use crate::server::tokens::Tokens;
 use http::{self, header::HeaderValue, Response};
 use hyper::Body;
@@ -45,14 +53,15 @@ pub struct Data {
     pub agents: Agents,
     pub db: Database,
     pub reports_worker: reports::ReportsWorker,
+    pub live_logs: LiveLogs,
     pub acl: ACL,
     pub metrics: Metrics,
 }
 
 pub fn run(config: Config) -> Fallible<()> {
-    let db = Database::open()?;
+    let db = Database::open(&config)?;
     let tokens = tokens::Tokens::load()?;
-    let github = GitHubApi::new(&tokens);
+    let github = GitHubApi::new(&tokens, &config.github)?;
     let agents = Agents::new(db.clone(), &tokens)?;
     let bot_username = github.username()?;
     let acl = ACL::new(&config, &github)?;
@@ -68,6 +77,7 @@ pub fn run(config: Config) -> Fallible<()> {
         agents,
         db,
         reports_worker: reports::ReportsWorker::new(),
+        live_logs: LiveLogs::new(),
         acl,
         metrics,
     };
@@ -75,6 +85,9 @@ pub fn run(config: Config) -> Fallible<()> {
     let mutex = Arc::new(Mutex::new(data.clone()));
 
     data.reports_worker.spawn(data.clone());
+    if data.config.server.stream_logs.enabled {
+        reports::spawn_logs_streamer(data.clone());
+    }
     cronjobs::spawn(data.clone());
 
     info!("running server...");
@@ -87,8 +100,12 @@ pub fn run(config: Config) -> Fallible<()> {
                 .and(warp::path("webhooks").and(routes::webhooks::routes(data.clone())))
                 .or(warp::path("agent-api").and(routes::agent::routes(data.clone(), mutex)))
                 .unify()
+                .or(warp::path("triage-api").and(routes::triage::routes(data.clone())))
+                .unify()
                 .or(warp::path("metrics").and(routes::metrics::routes(data.clone())))
                 .unify()
+                .or(warp::path("api").and(routes::api::routes(data.clone())))
+                .unify()
                 .or(routes::ui::routes(data))
                 .unify(),
         )
@@ -0,0 +1,146 @@
+use crate::experiments::{Experiment, Status};
+use crate::prelude::*;
+use crate::report::{self, Comparison};
+use crate::results::{DatabaseDB, DiagnosticCode, FailureReason, ReadResults, TestResult};
+use crate::server::messages::Message;
+use crate::server::Data;
+use chrono::{Duration, Utc};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Find the error code shared by the most results, along with the number of results with it.
+fn top_error(results: &[TestResult]) -> Option<(&DiagnosticCode, usize)> {
+    let mut counts = BTreeMap::new();
+    for result in results {
+        match result {
+            TestResult::BuildFail(FailureReason::CompilerError(codes))
+            | TestResult::TestFail(FailureReason::CompilerError(codes)) => {
+                for code in codes {
+                    *counts.entry(code).or_insert(0) += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Ties are broken by picking the lowest code, to avoid the comments changing randomly
+    counts
+        .into_iter()
+        .fold(None, |best, (code, count)| match best {
+            Some((_, best_count)) if best_count >= count => best,
+            _ => Some((code, count)),
+        })
+}
+
+fn progress_message(data: &Data, ex: &Experiment) -> Fallible<Message> {
+    let results = DatabaseDB::new(&data.db);
+
+    let mut regressed = 0;
+    let mut failures = Vec::new();
+    for krate in ex.get_completed_crates(&data.db)? {
+        if report::compare_crate(&results, &data.config, ex, &krate)? == Comparison::Regressed {
+            regressed += 1;
+            if let Some(res) = results.load_test_result(ex, &ex.toolchains[1], &krate)? {
+                failures.push(res);
+            }
+        }
+    }
+
+    let mut progress = format!(
+        "Experiment **`{}`** is {}% done, {} regressions so far",
+        ex.name,
+        ex.progress(&data.db)?,
+        regressed
+    );
+    if let Some((code, count)) = top_error(&failures) {
+        progress.push_str(&format!(", top error: {} in {} crates", code, count));
+    }
+
+    let mut message = Message::new().line("hourglass", progress);
+    if let Some(ref report_url) = ex.report_url {
+        message = message.line(
+            "newspaper",
+            format!("[Open the partial report]({}).", report_url),
+        );
+    }
+    Ok(message)
+}
+
+/// Post a progress update on the tracking issue of the running experiments which didn't receive
+/// one in the configured interval.
+pub(super) fn post_progress_comments(data: Arc<Data>) -> Fallible<()> {
+    let every = data.config.server.progress_comments.every_hours;
+    if every == 0 {
+        return Ok(());
+    }
+    let every = Duration::hours(i64::from(every));
+
+    for ex in Experiment::unfinished(&data.db)? {
+        let github_issue = match ex.github_issue {
+            Some(ref github_issue) if ex.status == Status::Running => github_issue,
+            _ => continue,
+        };
+
+        let last = ex.progress_commented_at(&data.db)?.or(ex.started_at);
+        if let Some(last) = last {
+            if Utc::now() - last < every {
+                continue;
+            }
+        }
+
+        info!("posting a progress update for experiment {}", ex.name);
+        progress_message(&data, &ex)?.send(&github_issue.api_url, &data)?;
+        ex.set_progress_commented_at(&data.db, Utc::now())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::top_error;
+    use crate::results::{DiagnosticCode, FailureReason, TestResult};
+
+    #[test]
+    fn test_top_error() {
+        let compiler_error = |codes: &[&str]| {
+            TestResult::BuildFail(FailureReason::CompilerError(
+                codes
+                    .iter()
+                    .map(|code| DiagnosticCode::from(code.to_string()))
+                    .collect(),
+            ))
+        };
+
+        assert_eq!(top_error(&[]), None);
+        assert_eq!(
+            top_error(&[TestResult::BuildFail(FailureReason::Unknown)]),
+            None
+        );
+
+        let results = vec![
+            compiler_error(&["E0308", "E0425"]),
+            compiler_error(&["E0425"]),
+            TestResult::TestFail(FailureReason::CompilerError(
+                vec![DiagnosticCode::from("E0308".into())]
+                    .into_iter()
+                    .collect(),
+            )),
+            compiler_error(&["E0599"]),
+        ];
+        // E0308 and E0425 are tied, so the lowest one is picked
+        assert_eq!(
+            top_error(&results),
+            Some((&DiagnosticCode::from("E0308".into()), 2))
+        );
+
+        let results = vec![
+            compiler_error(&["E0599"]),
+            compiler_error(&["E0599", "E0308"]),
+        ];
+        assert_eq!(
+            top_error(&results),
+            Some((&DiagnosticCode::from("E0599".into()), 2))
+        );
+    }
+}
@@ -1,30 +1,130 @@
+use crate::config::StorageBackend;
 use crate::experiments::{Experiment, Status};
 use crate::prelude::*;
 use crate::report::{self, Comparison, TestResults};
-use crate::results::DatabaseDB;
+use crate::results::{bank, DatabaseDB};
+use crate::server::email;
 use crate::server::messages::{Label, Message};
 use crate::server::Data;
-use crate::utils;
+use crate::utils::{self, hex};
+use hmac::{Hmac, Mac};
 use rusoto_core::request::HttpClient;
 use rusoto_s3::S3Client;
+use sha1::Sha1;
+use std::collections::HashSet;
 use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 
 // Automatically wake up the reports generator thread every 10 minutes to check for new jobs
 const AUTOMATIC_THREAD_WAKEUP: u64 = 600;
+// Upload the logs received for running experiments every minute
+const STREAMING_INTERVAL: u64 = 60;
 
-fn generate_report(data: &Data, ex: &Experiment, results: &DatabaseDB) -> Fallible<TestResults> {
-    let client = S3Client::new_with(
-        HttpClient::new()?,
-        data.tokens.reports_bucket.to_aws_credentials(),
-        data.tokens.reports_bucket.region.to_region()?,
+fn report_writer(data: &Data, ex: &Experiment) -> Fallible<report::ReportStorage> {
+    let bucket = &data.tokens.reports_bucket;
+    Ok(match data.config.reports.storage {
+        StorageBackend::S3 => {
+            let client = S3Client::new_with(
+                HttpClient::new()?,
+                bucket.to_aws_credentials(),
+                bucket.region.to_region()?,
+            );
+            let dest = format!("s3://{}/{}", bucket.bucket, report_path(data, ex));
+            report::ReportStorage::S3(report::S3Writer::create(Box::new(client), dest.parse()?)?)
+        }
+        StorageBackend::Gcs => report::ReportStorage::Gcs(report::GcsWriter::create(
+            bucket.bucket.clone(),
+            report_path(data, ex).into(),
+            bucket.gcs.access_token.clone(),
+        )),
+        StorageBackend::Azure => {
+            let azure = bucket.azure.as_ref().ok_or_else(|| {
+                err_msg("missing the `reports-bucket.azure` credentials in tokens.toml")
+            })?;
+            report::ReportStorage::Azure(report::AzureWriter::create(
+                azure.account.clone(),
+                bucket.bucket.clone(),
+                report_path(data, ex).into(),
+                azure.sas_token.clone(),
+            ))
+        }
+    })
+}
+
+pub fn report_url(data: &Data, ex: &Experiment) -> String {
+    let base_url = data
+        .tokens
+        .reports_bucket
+        .public_url
+        .replace("{bucket}", &data.tokens.reports_bucket.bucket);
+    format!("{}/{}/index.html", base_url, report_path(data, ex))
+}
+
+/// Path of the reports of the experiment in the bucket. The reports of each namespace are stored
+/// in a directory named after it, keeping the index of every team separate.
+fn report_path(data: &Data, ex: &Experiment) -> String {
+    let path = match &ex.namespace {
+        Some(namespace) => format!("{}/{}", namespace, ex.name),
+        None => ex.name.clone(),
+    };
+    if is_draft(data, ex) {
+        format!("drafts/{}/{}", draft_key(data, ex), path)
+    } else {
+        path
+    }
+}
+
+/// Whether the reports of the experiment are drafts, which are only published once signed off.
+pub fn is_draft(data: &Data, ex: &Experiment) -> bool {
+    data.config.server.sign_off.enabled && ex.approved_by.is_none()
+}
+
+/// Drafts are stored in a directory named after a key derived from the webhooks secret, so that
+/// their location can't be guessed by who doesn't have access to the draft URL.
+fn draft_key(data: &Data, ex: &Experiment) -> String {
+    let mut mac = Hmac::<Sha1>::new_varkey(data.tokens.bot.webhooks_secret.as_bytes()).unwrap();
+    mac.input(ex.name.as_bytes());
+    hex::to_hex(&mac.result().code())
+}
+
+/// URL of the draft report of the experiment, if it's waiting to be signed off.
+pub fn draft_url(data: &Data, ex: &Experiment) -> Option<String> {
+    if is_draft(data, ex) {
+        Some(report_url(data, ex))
+    } else {
+        None
+    }
+}
+
+/// Sign off the draft report of the experiment, which is then generated again at its public
+/// location and announced like the reports of the experiments without sign-off.
+pub fn approve_report(data: &Data, ex: &mut Experiment, user: &str) -> Fallible<()> {
+    if ex.status != Status::AwaitingApproval {
+        bail!("the report of **`{}`** is not awaiting sign-off!", ex.name);
+    }
+
+    ex.set_approved_by(&data.db, Some(user))?;
+    ex.set_status(&data.db, Status::NeedsReport)?;
+    data.reports_worker.wake();
+    info!(
+        "report of the experiment {} signed off by {}",
+        ex.name, user
     );
-    let dest = format!("s3://{}/{}", data.tokens.reports_bucket.bucket, &ex.name);
-    let writer = report::S3Writer::create(Box::new(client), dest.parse()?)?;
+    Ok(())
+}
+
+fn generate_report(data: &Data, ex: &Experiment, results: &DatabaseDB) -> Fallible<TestResults> {
+    let writer = report_writer(data, ex)?;
 
     let crates = ex.get_crates(&data.db)?;
-    let res = report::gen(results, &ex, &crates, &writer, &data.config, false)?;
+    let res = if data.config.server.stream_logs.enabled {
+        // Only the logs received since the last run of the streaming thread need to be uploaded
+        report::stream_logs(results, ex, &writer, &data.config)?;
+        report::gen_without_logs(results, &ex, &crates, &writer, &data.config, false)?
+    } else {
+        report::gen(results, &ex, &crates, &writer, &data.config, false)?
+    };
 
     //remove metrics about completed experiments
     data.metrics.on_complete_experiment(&ex.name)?;
@@ -32,7 +132,24 @@ fn generate_report(data: &Data, ex: &Experiment, results: &DatabaseDB) -> Fallib
     Ok(res)
 }
 
-fn reports_thread(data: &Data, wakes: &mpsc::Receiver<()>) -> Fallible<()> {
+fn generate_partial_report(data: &Data, ex: &mut Experiment, results: &DatabaseDB) -> Fallible<()> {
+    let writer = report_writer(data, ex)?;
+
+    let crates = ex.get_completed_crates(&data.db)?;
+    report::gen_partial(results, ex, &crates, &writer, &data.config)?;
+
+    // The URL of drafts is only shown to the people allowed to sign them off
+    if !is_draft(data, ex) {
+        ex.set_report_url(&data.db, &report_url(data, ex))?;
+    }
+    Ok(())
+}
+
+fn reports_thread(
+    data: &Data,
+    wakes: &mpsc::Receiver<()>,
+    partials: &Mutex<HashSet<String>>,
+) -> Fallible<()> {
     let timeout = Duration::from_secs(AUTOMATIC_THREAD_WAKEUP);
     let results = DatabaseDB::new(&data.db);
 
@@ -40,6 +157,29 @@ fn reports_thread(data: &Data, wakes: &mpsc::Receiver<()>) -> Fallible<()> {
         let mut ex = match Experiment::first_by_status(&data.db, Status::NeedsReport)? {
             Some(ex) => ex,
             None => {
+                // Partial reports are only generated when no full report is waiting
+                let partial = {
+                    let mut partials = partials.lock().unwrap();
+                    let name = partials.iter().next().cloned();
+                    if let Some(ref name) = name {
+                        partials.remove(name);
+                    }
+                    name
+                };
+                if let Some(name) = partial {
+                    if let Some(mut ex) = Experiment::get(&data.db, &name)? {
+                        // The full report will be generated soon if the experiment completed
+                        if ex.status == Status::Running {
+                            info!("generating partial report for experiment {}...", name);
+                            if let Err(err) = generate_partial_report(data, &mut ex, &results) {
+                                error!("failed to generate the partial report of {}", name);
+                                utils::report_failure(&err);
+                            }
+                        }
+                    }
+                    continue;
+                }
+
                 // This will sleep AUTOMATIC_THREAD_WAKEUP seconds *or* until a wake is received
                 if let Err(mpsc::RecvTimeoutError::Disconnected) = wakes.recv_timeout(timeout) {
                     thread::sleep(timeout);
@@ -78,13 +218,33 @@ fn reports_thread(data: &Data, wakes: &mpsc::Receiver<()>) -> Fallible<()> {
 
                 continue;
             }
+            Ok(_) if is_draft(data, &ex) => {
+                ex.set_status(&data.db, Status::AwaitingApproval)?;
+                info!(
+                    "draft report for the experiment {} generated successfully!",
+                    name
+                );
+
+                if let Some(ref github_issue) = ex.github_issue {
+                    Message::new()
+                        .line(
+                            "memo",
+                            format!(
+                                "Experiment **`{}`** is completed, and its report is waiting to \
+                                 be signed off before being published.",
+                                name
+                            ),
+                        )
+                        .line(
+                            "key",
+                            "An admin of the bot can approve it with the `approve-report` \
+                             command after reviewing the draft.",
+                        )
+                        .send(&github_issue.api_url, data)?;
+                }
+            }
             Ok(res) => {
-                let base_url = data
-                    .tokens
-                    .reports_bucket
-                    .public_url
-                    .replace("{bucket}", &data.tokens.reports_bucket.bucket);
-                let report_url = format!("{}/{}/index.html", base_url, name);
+                let report_url = report_url(data, &ex);
 
                 ex.set_status(&data.db, Status::Completed)?;
                 ex.set_report_url(&data.db, &report_url)?;
@@ -94,19 +254,21 @@ fn reports_thread(data: &Data, wakes: &mpsc::Receiver<()>) -> Fallible<()> {
                     res.info.get(&Comparison::Regressed).unwrap_or(&0),
                     res.info.get(&Comparison::Fixed).unwrap_or(&0),
                 );
+                let total = res.info.values().sum::<u32>();
+                // Surveys have no baseline, so nothing can regress or be fixed
+                let summary = if ex.survey {
+                    format!(" {} crates surveyed", total)
+                } else {
+                    format!(
+                        " {} regressed and {} fixed ({} total)",
+                        regressed, fixed, total
+                    )
+                };
 
                 if let Some(ref github_issue) = ex.github_issue {
                     Message::new()
                         .line("tada", format!("Experiment **`{}`** is completed!", name))
-                        .line(
-                            "bar_chart",
-                            format!(
-                                " {} regressed and {} fixed ({} total)",
-                                regressed,
-                                fixed,
-                                res.info.values().sum::<u32>(),
-                            ),
-                        )
+                        .line("bar_chart", summary)
                         .line(
                             "newspaper",
                             format!("[Open the full report]({}).", report_url),
@@ -122,21 +284,70 @@ fn reports_thread(data: &Data, wakes: &mpsc::Receiver<()>) -> Fallible<()> {
                         .set_label(Label::ExperimentCompleted)
                         .send(&github_issue.api_url, data)?;
                 }
+
+                let digest = &data.config.server.email_digest;
+                if let Err(err) = email::send_digest(digest, &ex, &res, &report_url) {
+                    utils::report_failure(&err);
+                }
+
+                match bank::record(&data.db, &ex) {
+                    Ok(0) => {}
+                    Ok(banked) => info!("banked {} baseline results of {}", banked, name),
+                    Err(err) => utils::report_failure(&err),
+                }
+            }
+        }
+    }
+}
+
+fn streaming_thread(data: &Data) -> Fallible<()> {
+    let results = DatabaseDB::new(&data.db);
+
+    loop {
+        for ex in Experiment::unfinished(&data.db)? {
+            if ex.status != Status::Running {
+                continue;
+            }
+
+            let writer = report_writer(data, &ex)?;
+            let written = report::stream_logs(&results, &ex, &writer, &data.config)?;
+            if written > 0 {
+                info!("streamed {} logs of experiment {}", written, ex.name);
             }
         }
+
+        thread::sleep(Duration::from_secs(STREAMING_INTERVAL));
     }
 }
 
+/// Spawn the thread uploading the logs of running experiments to the report bucket as soon as
+/// they're received, leaving only the rest of the report to be generated at the end.
+pub fn spawn_logs_streamer(data: Data) {
+    thread::spawn(move || loop {
+        let result = streaming_thread(&data).with_context(|_| "the logs streaming thread crashed");
+        if let Err(e) = result {
+            utils::report_failure(&e);
+        }
+
+        warn!("the logs streaming thread will be respawned in one minute");
+        thread::sleep(Duration::from_secs(60));
+    });
+}
+
 #[derive(Clone, Default)]
-pub struct ReportsWorker(Arc<Mutex<Option<mpsc::Sender<()>>>>);
+pub struct ReportsWorker {
+    waker: Arc<Mutex<Option<mpsc::Sender<()>>>>,
+    partials: Arc<Mutex<HashSet<String>>>,
+}
 
 impl ReportsWorker {
     pub fn new() -> Self {
-        ReportsWorker(Arc::new(Mutex::new(None)))
+        ReportsWorker::default()
     }
 
     pub fn spawn(&self, data: Data) {
-        let waker = self.0.clone();
+        let waker = self.waker.clone();
+        let partials = self.partials.clone();
         thread::spawn(move || {
             // Set up a new waker channel
             let (wake_send, wake_recv) = mpsc::channel();
@@ -146,7 +357,7 @@ impl ReportsWorker {
             }
 
             loop {
-                let result = reports_thread(&data.clone(), &wake_recv)
+                let result = reports_thread(&data.clone(), &wake_recv, &partials)
                     .with_context(|_| "the reports generator thread crashed");
                 if let Err(e) = result {
                     utils::report_failure(&e);
@@ -161,7 +372,7 @@ impl ReportsWorker {
     pub fn wake(&self) {
         // We don't really care if the wake fails: the reports generator thread wakes up on its own
         // every few minutes, so this just speeds up the process
-        if let Some(waker) = self.0.lock().ok().as_ref().and_then(|opt| opt.as_ref()) {
+        if let Some(waker) = self.waker.lock().ok().as_ref().and_then(|opt| opt.as_ref()) {
             if waker.send(()).is_err() {
                 warn!("can't wake the reports generator, will have to wait");
             }
@@ -169,4 +380,11 @@ impl ReportsWorker {
             warn!("no report generator to wake up!");
         }
     }
+
+    /// Queue the generation of a partial report for a running experiment. Multiple requests for
+    /// the same experiment are merged if the report wasn't generated yet.
+    pub fn request_partial(&self, experiment: &str) {
+        self.partials.lock().unwrap().insert(experiment.to_string());
+        self.wake();
+    }
 }
@@ -1,15 +1,22 @@
 use crate::agent::Capabilities;
+use crate::crates::Crate;
 use crate::experiments::{Assignee, Experiment, Status};
 use crate::prelude::*;
-use crate::results::{DatabaseDB, EncodingType, ProgressData};
+use crate::results::{DatabaseDB, EncodingType, ProgressData, WriteResults};
 use crate::server::api_types::{AgentConfig, ApiResponse};
 use crate::server::auth::{auth_filter, AuthDetails, TokenType};
+use crate::server::canary;
+use crate::server::early_abort;
+use crate::server::failure_threshold;
 use crate::server::messages::Message;
 use crate::server::{Data, HttpError};
+use crate::toolchain::Toolchain;
+use crate::utils::hex::sha1_hex;
+use chrono::Duration;
 use failure::Compat;
 use http::{Response, StatusCode};
 use hyper::Body;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
 use warp::{self, Filter, Rejection};
 
@@ -60,6 +67,38 @@ pub fn routes(
         .and(auth_filter(data.clone(), TokenType::Agent))
         .map(endpoint_record_progress);
 
+    let banked_result = warp::post2()
+        .and(warp::path("banked-result"))
+        .and(warp::path::end())
+        .and(warp::body::json())
+        .and(mutex_filter.clone())
+        .and(auth_filter(data.clone(), TokenType::Agent))
+        .map(endpoint_banked_result);
+
+    let learned_timeout = warp::post2()
+        .and(warp::path("learned-timeout"))
+        .and(warp::path::end())
+        .and(warp::body::json())
+        .and(data_filter.clone())
+        .and(auth_filter(data.clone(), TokenType::Agent))
+        .map(endpoint_learned_timeout);
+
+    let stream_log = warp::post2()
+        .and(warp::path("stream-log"))
+        .and(warp::path::end())
+        .and(warp::body::json())
+        .and(data_filter.clone())
+        .and(auth_filter(data.clone(), TokenType::Agent))
+        .map(endpoint_stream_log);
+
+    let upload_log_chunk = warp::post2()
+        .and(warp::path("upload-log-chunk"))
+        .and(warp::path::end())
+        .and(warp::body::json())
+        .and(data_filter.clone())
+        .and(auth_filter(data.clone(), TokenType::Agent))
+        .map(endpoint_upload_log_chunk);
+
     let heartbeat = warp::post2()
         .and(warp::path("heartbeat"))
         .and(warp::path::end())
@@ -84,6 +123,14 @@ pub fn routes(
                 .unify()
                 .or(record_progress)
                 .unify()
+                .or(banked_result)
+                .unify()
+                .or(learned_timeout)
+                .unify()
+                .or(stream_log)
+                .unify()
+                .or(upload_log_chunk)
+                .unify()
                 .or(heartbeat)
                 .unify()
                 .or(error)
@@ -117,7 +164,7 @@ fn endpoint_next_experiment(
     //we need to make sure that Experiment::next executes uninterrupted
     let data = mutex.lock().unwrap();
     let next = Experiment::next(&data.db, &Assignee::Agent(auth.name.clone()))?;
-    let result = if let Some((new, ex)) = next {
+    let result = if let Some((new, mut ex)) = next {
         if new {
             if let Some(ref github_issue) = ex.github_issue {
                 Message::new()
@@ -148,8 +195,31 @@ fn endpoint_next_experiment(
     Ok(ApiResponse::Success { result }.into_response()?)
 }
 
+/// Discard the results of crates not leased to the agent anymore, for example because its lease
+/// expired and the crate was given to another agent. Both toolchains of a crate are then always
+/// built by the same agent, with the same source and lockfile.
+fn discard_stale_results(progress: &mut ProgressData, leased: &HashSet<Crate>, agent: &str) {
+    let renamed = progress.version.as_ref().and_then(|(old, new)| {
+        if leased.contains(old) {
+            Some(new.clone())
+        } else {
+            None
+        }
+    });
+    progress.results.retain(|result| {
+        let valid = leased.contains(&result.krate) || renamed.as_ref() == Some(&result.krate);
+        if !valid {
+            warn!(
+                "discarding result of {} from agent {}, as the crate is not leased to it",
+                result.krate, agent
+            );
+        }
+        valid
+    });
+}
+
 fn endpoint_record_progress(
-    result: ExperimentData<ProgressData>,
+    mut result: ExperimentData<ProgressData>,
     mutex: Arc<Mutex<Data>>,
     auth: AuthDetails,
 ) -> Fallible<Response<Body>> {
@@ -162,28 +232,219 @@ fn endpoint_record_progress(
         ex.name, auth.name,
     );
 
+    let leased = ex
+        .queue()
+        .leased_by(&data.db, &Assignee::Agent(auth.name.clone()))?
+        .into_iter()
+        .collect::<HashSet<_>>();
+    discard_stale_results(&mut result.data, &leased, &auth.name);
+
+    let db = DatabaseDB::new(&data.db);
+    let encoding = data.config.logs.compression.unwrap_or(EncodingType::Gzip);
+    if !db.store(&ex, &result.data, &data.config, encoding)? {
+        // The agent didn't receive the response to the submission, which was already counted
+        warn!(
+            "ignoring submission already received on experiment {} from agent {}",
+            ex.name, auth.name
+        );
+        return Ok(ApiResponse::Success { result: true }.into_response()?);
+    }
+
     data.metrics
         .record_completed_jobs(&auth.name, &ex.name, result.data.results.len() as i64);
+    for task in &result.data.results {
+        data.live_logs
+            .remove(&ex.name, &task.krate, &task.toolchain);
+    }
+    if let Some((old, _)) = &result.data.version {
+        for tc in &ex.toolchains {
+            data.live_logs.remove(&ex.name, old, tc);
+        }
+    }
 
-    let db = DatabaseDB::new(&data.db);
-    db.store(&ex, &result.data, EncodingType::Gzip)?;
+    let received = result
+        .data
+        .results
+        .iter()
+        .filter(|result| result.toolchain == ex.toolchains[1])
+        .count();
+    early_abort::check(&data, &mut ex, received as u32)?;
+    failure_threshold::check(&data, &mut ex, &result.data)?;
+    canary::check(&data, &mut ex)?;
 
     let (completed, all) = ex.raw_progress(&data.db)?;
     if completed == all {
         ex.set_status(&data.db, Status::NeedsReport)?;
+        data.live_logs.remove_experiment(&ex.name);
         info!("experiment {} completed, marked as needs-report", ex.name);
         data.reports_worker.wake(); // Ensure the reports worker is awake
+    } else {
+        // Each crate has a result for every toolchain tested by the experiment
+        let every = data.config.server.partial_reports.every * ex.tested_toolchains().len() as u32;
+        let before = completed.saturating_sub(result.data.results.len() as u32);
+        if every != 0 && completed / every > before / every {
+            data.reports_worker.request_partial(&ex.name);
+        }
     }
 
     Ok(ApiResponse::Success { result: true }.into_response()?)
 }
 
+#[derive(Deserialize)]
+pub struct BankedResultRequest {
+    #[serde(rename = "crate")]
+    krate: Crate,
+    /// Digest of the sandbox image of the agent, which the banked result must have been built in.
+    #[serde(default)]
+    sandbox_image: Option<String>,
+}
+
+/// Copy the banked baseline result of a crate into the experiment, if the experiment reuses the
+/// banked baseline, so the agent doesn't have to build the crate with the start toolchain.
+fn endpoint_banked_result(
+    request: ExperimentData<BankedResultRequest>,
+    mutex: Arc<Mutex<Data>>,
+    auth: AuthDetails,
+) -> Fallible<Response<Body>> {
+    let data = mutex.lock().unwrap();
+    let ex = Experiment::get(&data.db, &request.experiment_name)?
+        .ok_or_else(|| err_msg("no experiment run by this agent"))?;
+
+    let krate = &request.data.krate;
+    let leased = ex
+        .queue()
+        .leased_by(&data.db, &Assignee::Agent(auth.name))?
+        .contains(krate);
+    let result = if leased {
+        let db = DatabaseDB::new(&data.db).with_sandbox_image(request.data.sandbox_image.clone());
+        db.get_result(&ex, &ex.toolchains[0], krate)?
+    } else {
+        None
+    };
+
+    Ok(ApiResponse::Success { result }.into_response()?)
+}
+
+#[derive(Deserialize)]
+pub struct LearnedTimeoutRequest {
+    #[serde(rename = "crate")]
+    krate: Crate,
+}
+
+/// Seconds the builds of a crate are allowed to take, learned from the duration of its previous
+/// builds. Nothing is returned if adaptive timeouts are disabled or the history is too short.
+fn endpoint_learned_timeout(
+    request: ExperimentData<LearnedTimeoutRequest>,
+    data: Arc<Data>,
+    _auth: AuthDetails,
+) -> Fallible<Response<Body>> {
+    let ex = Experiment::get(&data.db, &request.experiment_name)?
+        .ok_or_else(|| err_msg("no experiment run by this agent"))?;
+
+    let result = DatabaseDB::new(&data.db)
+        .learned_timeout(&ex, &request.data.krate, &data.config)?
+        .map(|timeout| timeout.as_secs());
+    Ok(ApiResponse::Success { result }.into_response()?)
+}
+
+#[derive(Deserialize)]
+pub struct StreamLogRequest {
+    #[serde(rename = "crate")]
+    krate: Crate,
+    toolchain: Toolchain,
+    offset: usize,
+    chunk: String,
+}
+
+/// Store a chunk of the log of a build still running on the agent, so that it can be tailed
+/// before its result is recorded.
+fn endpoint_stream_log(
+    request: ExperimentData<StreamLogRequest>,
+    data: Arc<Data>,
+    auth: AuthDetails,
+) -> Fallible<Response<Body>> {
+    let ex = Experiment::get(&data.db, &request.experiment_name)?
+        .ok_or_else(|| err_msg("no experiment run by this agent"))?;
+
+    let chunk = &request.data;
+    let leased = ex
+        .queue()
+        .leased_by(&data.db, &Assignee::Agent(auth.name))?
+        .contains(&chunk.krate);
+    let result = leased
+        && data.live_logs.append(
+            &ex.name,
+            &chunk.krate,
+            &chunk.toolchain,
+            chunk.offset,
+            &chunk.chunk,
+        );
+
+    Ok(ApiResponse::Success { result }.into_response()?)
+}
+
+#[derive(Deserialize)]
+pub struct LogChunkRequest {
+    /// Crate whose log is uploaded, which must be leased to the agent uploading it.
+    #[serde(rename = "crate")]
+    krate: Crate,
+    upload: String,
+    offset: u64,
+    chunk: String,
+    checksum: String,
+}
+
+/// Store a chunk of a log too big to be sent along with its result, returning how many bytes of
+/// the upload were received so far. Corrupted chunks are discarded, and sent again by the agent.
+fn endpoint_upload_log_chunk(
+    request: ExperimentData<LogChunkRequest>,
+    data: Arc<Data>,
+    auth: AuthDetails,
+) -> Fallible<Response<Body>> {
+    let ex = Experiment::get(&data.db, &request.experiment_name)?
+        .ok_or_else(|| err_msg("no experiment run by this agent"))?;
+
+    let upload = &request.data;
+    let leased = ex
+        .queue()
+        .leased_by(&data.db, &Assignee::Agent(auth.name.clone()))?;
+    if !leased.contains(&upload.krate) {
+        bail!(
+            "refusing the log of {} from agent {}, as the crate is not leased to it",
+            upload.krate,
+            auth.name
+        );
+    }
+
+    let mut chunk =
+        base64::decode(&upload.chunk).with_context(|_| "invalid base64 chunk provided")?;
+    if !upload.checksum.eq_ignore_ascii_case(&sha1_hex(&chunk)) {
+        warn!(
+            "discarding corrupted chunk of upload {} from agent {}",
+            upload.upload, auth.name
+        );
+        chunk.clear();
+    }
+
+    let result =
+        DatabaseDB::new(&data.db).append_log_chunk(&ex, &upload.upload, upload.offset, &chunk)?;
+    Ok(ApiResponse::Success { result }.into_response()?)
+}
+
 fn endpoint_heartbeat(data: Arc<Data>, auth: AuthDetails) -> Fallible<Response<Body>> {
     if let Some(rev) = auth.git_revision {
         data.agents.set_git_revision(&auth.name, &rev)?;
     }
 
     data.agents.record_heartbeat(&auth.name)?;
+
+    // Keep the crates assigned to the agent as long as it's alive
+    let agent = Assignee::Agent(auth.name);
+    if let Some(ex) = Experiment::run_by(&data.db, &agent)? {
+        let timeout = Duration::seconds(data.config.server.distributed.lease_timeout as i64);
+        ex.queue().renew(&data.db, &agent, timeout)?;
+    }
+
     Ok(ApiResponse::Success { result: true }.into_response()?)
 }
 
@@ -222,7 +483,7 @@ fn endpoint_error(
     Ok(ApiResponse::Success { result: true }.into_response()?)
 }
 
-fn handle_results(resp: Fallible<Response<Body>>) -> Response<Body> {
+pub(super) fn handle_results(resp: Fallible<Response<Body>>) -> Response<Body> {
     match resp {
         Ok(resp) => resp,
         Err(err) => ApiResponse::internal_error(err.to_string())
@@ -231,7 +492,7 @@ fn handle_results(resp: Fallible<Response<Body>>) -> Response<Body> {
     }
 }
 
-fn handle_errors(err: Rejection) -> Result<Response<Body>, Rejection> {
+pub(super) fn handle_errors(err: Rejection) -> Result<Response<Body>, Rejection> {
     let error = if let Some(compat) = err.find_cause::<Compat<HttpError>>() {
         Some(*compat.get_ref())
     } else if let StatusCode::NOT_FOUND = err.status() {
@@ -0,0 +1,238 @@
+use crate::crates::Crate;
+use crate::experiments::Experiment;
+use crate::prelude::*;
+use crate::report;
+use crate::results::DatabaseDB;
+use crate::server::api_types::ApiResponse;
+use crate::server::auth::{auth_filter, AuthDetails, TokenType};
+use crate::server::reports;
+use crate::server::routes::agent::{handle_errors, handle_results};
+use crate::server::Data;
+use http::header::{HeaderValue, CONTENT_DISPOSITION, CONTENT_TYPE};
+use http::Response;
+use hyper::Body;
+use std::sync::Arc;
+use warp::{self, path::Tail, Filter, Rejection};
+
+#[derive(Serialize)]
+pub struct CrateLog {
+    toolchain: String,
+    log: String,
+}
+
+#[derive(Serialize)]
+pub struct Draft {
+    status: &'static str,
+    url: String,
+}
+
+pub fn routes(
+    data: Arc<Data>,
+) -> impl Filter<Extract = (Response<Body>,), Error = Rejection> + Clone {
+    let data_cloned = data.clone();
+    let data_filter = warp::any().map(move || data_cloned.clone());
+
+    // Crate IDs contain slashes, so the rest of the path is parsed by the endpoint
+    let logs = warp::get2()
+        .and(warp::path("experiments"))
+        .and(warp::path::param())
+        .and(warp::path("crates"))
+        .and(warp::path::tail())
+        .and(data_filter.clone())
+        .map(endpoint_logs);
+
+    let diff = warp::get2()
+        .and(warp::path("experiments"))
+        .and(warp::path::param())
+        .and(warp::path("diff"))
+        .and(warp::path::param())
+        .and(warp::path::end())
+        .and(data_filter.clone())
+        .map(endpoint_diff);
+
+    // Drafts of the reports waiting to be signed off are only available to the triagers
+    let draft = warp::get2()
+        .and(warp::path("experiments"))
+        .and(warp::path::param())
+        .and(warp::path("draft"))
+        .and(warp::path::end())
+        .and(data_filter.clone())
+        .and(auth_filter(data.clone(), TokenType::Triager))
+        .map(endpoint_draft);
+
+    let approve = warp::post2()
+        .and(warp::path("experiments"))
+        .and(warp::path::param())
+        .and(warp::path("approve"))
+        .and(warp::path::end())
+        .and(data_filter)
+        .and(auth_filter(data, TokenType::Triager))
+        .map(endpoint_approve);
+
+    warp::any()
+        .and(logs)
+        .or(diff)
+        .unify()
+        .or(draft)
+        .unify()
+        .or(approve)
+        .unify()
+        .map(handle_results)
+        .recover(handle_errors)
+        .unify()
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum LogsFormat {
+    Json,
+    Tarball,
+    /// Logs of the builds still running on the agents.
+    Live,
+}
+
+/// Split `<crate id>/logs`, `<crate id>/logs.tar.gz` or `<crate id>/live-log`.
+fn parse_logs_path(path: &str) -> Option<(Crate, LogsFormat)> {
+    let (id, format) = if path.ends_with("/logs") {
+        (&path[..path.len() - "/logs".len()], LogsFormat::Json)
+    } else if path.ends_with("/logs.tar.gz") {
+        (
+            &path[..path.len() - "/logs.tar.gz".len()],
+            LogsFormat::Tarball,
+        )
+    } else if path.ends_with("/live-log") {
+        (&path[..path.len() - "/live-log".len()], LogsFormat::Live)
+    } else {
+        return None;
+    };
+    id.parse().ok().map(|krate| (krate, format))
+}
+
+fn endpoint_logs(ex: String, tail: Tail, data: Arc<Data>) -> Fallible<Response<Body>> {
+    let (krate, format) = match parse_logs_path(tail.as_str()) {
+        Some(parsed) => parsed,
+        None => return ApiResponse::not_found().into_response(),
+    };
+    let ex = match Experiment::get(&data.db, &ex)? {
+        Some(ex) => ex,
+        None => return ApiResponse::not_found().into_response(),
+    };
+
+    if format == LogsFormat::Live {
+        let result = data
+            .live_logs
+            .get(&ex.name, &krate)
+            .into_iter()
+            .map(|(tc, log)| CrateLog {
+                toolchain: tc.to_string(),
+                log,
+            })
+            .collect::<Vec<_>>();
+        if result.is_empty() {
+            return ApiResponse::not_found().into_response();
+        }
+        return Ok(ApiResponse::Success { result }.into_response()?);
+    }
+
+    let logs = report::load_crate_logs(&DatabaseDB::new(&data.db), &ex, &krate)?;
+    if logs.is_empty() {
+        return ApiResponse::not_found().into_response();
+    }
+
+    if format == LogsFormat::Tarball {
+        let mut resp = Response::new(report::crate_logs_archive(&krate, &logs)?.into());
+        resp.headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/gzip"));
+        resp.headers_mut().insert(
+            CONTENT_DISPOSITION,
+            HeaderValue::from_str(&format!(
+                "attachment; filename=\"{}-{}.tar.gz\"",
+                ex.name,
+                krate.id().replace('/', ".")
+            ))?,
+        );
+        Ok(resp)
+    } else {
+        let result = logs
+            .into_iter()
+            .map(|(tc, log)| CrateLog {
+                toolchain: tc.to_string(),
+                log: String::from_utf8_lossy(&log).into_owned(),
+            })
+            .collect::<Vec<_>>();
+        Ok(ApiResponse::Success { result }.into_response()?)
+    }
+}
+
+fn endpoint_diff(before: String, after: String, data: Arc<Data>) -> Fallible<Response<Body>> {
+    let (before, after) = match (
+        Experiment::get(&data.db, &before)?,
+        Experiment::get(&data.db, &after)?,
+    ) {
+        (Some(before), Some(after)) => (before, after),
+        _ => return ApiResponse::not_found().into_response(),
+    };
+
+    let db = DatabaseDB::new(&data.db);
+    let result = report::diff_experiments(&db, &data.config, &before, &after)?;
+    Ok(ApiResponse::Success { result }.into_response()?)
+}
+
+fn endpoint_draft(ex: String, data: Arc<Data>, _auth: AuthDetails) -> Fallible<Response<Body>> {
+    let ex = match Experiment::get(&data.db, &ex)? {
+        Some(ex) => ex,
+        None => return ApiResponse::not_found().into_response(),
+    };
+
+    match reports::draft_url(&data, &ex) {
+        Some(url) => {
+            let result = Draft {
+                status: ex.status.to_str(),
+                url,
+            };
+            Ok(ApiResponse::Success { result }.into_response()?)
+        }
+        None => ApiResponse::not_found().into_response(),
+    }
+}
+
+fn endpoint_approve(ex: String, data: Arc<Data>, auth: AuthDetails) -> Fallible<Response<Body>> {
+    let mut ex = match Experiment::get(&data.db, &ex)? {
+        Some(ex) => ex,
+        None => return ApiResponse::not_found().into_response(),
+    };
+
+    reports::approve_report(&data, &mut ex, &auth.name)?;
+    Ok(ApiResponse::Success { result: true }.into_response()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_logs_path, LogsFormat};
+    use crate::crates::{Crate, RegistryCrate};
+
+    #[test]
+    fn test_parse_logs_path() {
+        let syn = Crate::Registry(RegistryCrate {
+            name: "syn".into(),
+            version: "1.0.0".into(),
+        });
+        assert_eq!(
+            parse_logs_path("reg/syn/1.0.0/logs"),
+            Some((syn.clone(), LogsFormat::Json))
+        );
+        assert_eq!(
+            parse_logs_path("reg/syn/1.0.0/logs.tar.gz"),
+            Some((syn.clone(), LogsFormat::Tarball))
+        );
+        assert_eq!(
+            parse_logs_path("reg/syn/1.0.0/live-log"),
+            Some((syn, LogsFormat::Live))
+        );
+        assert_eq!(
+            parse_logs_path("gh/rust-lang/crater/logs"),
+            Some(("gh/rust-lang/crater".parse().unwrap(), LogsFormat::Json))
+        );
+        assert_eq!(parse_logs_path("reg/syn/1.0.0"), None);
+        assert_eq!(parse_logs_path("invalid/logs"), None);
+    }
+}
@@ -1,4 +1,6 @@
 pub mod agent;
+pub mod api;
 pub mod metrics;
+pub mod triage;
 pub mod ui;
 pub mod webhooks;
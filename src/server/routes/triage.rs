@@ -0,0 +1,175 @@
+use crate::crates::Crate;
+use crate::experiments::Experiment;
+use crate::prelude::*;
+use crate::report::{self, Comparison};
+use crate::results::{DatabaseDB, ReadResults};
+use crate::server::api_types::ApiResponse;
+use crate::server::auth::{auth_filter, AuthDetails, TokenType};
+use crate::server::routes::agent::{handle_errors, handle_results};
+use crate::server::Data;
+use crate::triage::{self, Decision, TriageItem};
+use http::Response;
+use hyper::Body;
+use std::sync::Arc;
+use warp::{self, Filter, Rejection};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ExperimentName {
+    experiment_name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct CrateData {
+    experiment_name: String,
+    #[serde(rename = "crate")]
+    krate: Crate,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DecisionData {
+    experiment_name: String,
+    #[serde(rename = "crate")]
+    krate: Crate,
+    decision: Decision,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct NoteData {
+    #[serde(rename = "crate")]
+    krate: Crate,
+    note: String,
+}
+
+pub fn routes(
+    data: Arc<Data>,
+) -> impl Filter<Extract = (Response<Body>,), Error = Rejection> + Clone {
+    let data_cloned = data.clone();
+    let data_filter = warp::any().map(move || data_cloned.clone());
+
+    let regressions = warp::post2()
+        .and(warp::path("regressions"))
+        .and(warp::path::end())
+        .and(warp::body::json())
+        .and(data_filter.clone())
+        .and(auth_filter(data.clone(), TokenType::Triager))
+        .map(endpoint_regressions);
+
+    let excerpt = warp::post2()
+        .and(warp::path("excerpt"))
+        .and(warp::path::end())
+        .and(warp::body::json())
+        .and(data_filter.clone())
+        .and(auth_filter(data.clone(), TokenType::Triager))
+        .map(endpoint_excerpt);
+
+    let decide = warp::post2()
+        .and(warp::path("decide"))
+        .and(warp::path::end())
+        .and(warp::body::json())
+        .and(data_filter.clone())
+        .and(auth_filter(data.clone(), TokenType::Triager))
+        .map(endpoint_decide);
+
+    let note = warp::post2()
+        .and(warp::path("note"))
+        .and(warp::path::end())
+        .and(warp::body::json())
+        .and(data_filter)
+        .and(auth_filter(data, TokenType::Triager))
+        .map(endpoint_note);
+
+    warp::any()
+        .and(
+            regressions
+                .or(excerpt)
+                .unify()
+                .or(decide)
+                .unify()
+                .or(note)
+                .unify(),
+        )
+        .map(handle_results)
+        .recover(handle_errors)
+        .unify()
+}
+
+fn get_experiment(data: &Data, name: &str) -> Fallible<Experiment> {
+    Experiment::get(&data.db, name)?.ok_or_else(|| err_msg("no experiment with this name"))
+}
+
+fn endpoint_regressions(
+    body: ExperimentName,
+    data: Arc<Data>,
+    _auth: AuthDetails,
+) -> Fallible<Response<Body>> {
+    let ex = get_experiment(&data, &body.experiment_name)?;
+    let results = DatabaseDB::new(&data.db);
+    let decisions = triage::load_decisions(&data.db, &ex.name)?;
+
+    let mut items = Vec::new();
+    for krate in ex.get_crates(&data.db)? {
+        if report::compare_crate(&results, &data.config, &ex, &krate)? == Comparison::Regressed {
+            let decision = decisions.get(&krate).cloned();
+            let note = triage::load_note(&data.db, &krate)?;
+            items.push(TriageItem {
+                krate,
+                decision,
+                note,
+            });
+        }
+    }
+
+    Ok(ApiResponse::Success { result: items }.into_response()?)
+}
+
+fn endpoint_excerpt(
+    body: CrateData,
+    data: Arc<Data>,
+    _auth: AuthDetails,
+) -> Fallible<Response<Body>> {
+    let ex = get_experiment(&data, &body.experiment_name)?;
+    let results = DatabaseDB::new(&data.db);
+
+    let logs = ex
+        .toolchains
+        .iter()
+        .map(|tc| -> Fallible<String> {
+            Ok(match results.load_log(&ex, tc, &body.krate)? {
+                Some(log) => String::from_utf8_lossy(&log.to_plain()?).into_owned(),
+                None => String::new(),
+            })
+        })
+        .collect::<Fallible<Vec<_>>>()?;
+
+    Ok(ApiResponse::Success {
+        result: triage::diff_excerpt(&logs[0], &logs[1]),
+    }
+    .into_response()?)
+}
+
+fn endpoint_decide(
+    body: DecisionData,
+    data: Arc<Data>,
+    auth: AuthDetails,
+) -> Fallible<Response<Body>> {
+    let ex = get_experiment(&data, &body.experiment_name)?;
+    triage::record_decision(&data.db, &ex.name, &body.krate, body.decision, &auth.name)?;
+
+    info!(
+        "{} classified {} in {} as {}",
+        auth.name, body.krate, ex.name, body.decision
+    );
+
+    Ok(ApiResponse::Success { result: true }.into_response()?)
+}
+
+fn endpoint_note(body: NoteData, data: Arc<Data>, auth: AuthDetails) -> Fallible<Response<Body>> {
+    triage::set_note(&data.db, &body.krate, &body.note, &auth.name)?;
+    info!("{} updated the note of {}", auth.name, body.krate);
+
+    Ok(ApiResponse::Success { result: true }.into_response()?)
+}
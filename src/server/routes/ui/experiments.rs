@@ -1,6 +1,7 @@
 use crate::experiments::{Experiment, Mode, Status};
 use crate::prelude::*;
-use crate::report::ResultName;
+use crate::report::{self, Comparison, ResultName};
+use crate::results::DatabaseDB;
 use crate::server::routes::ui::{render_template, LayoutContext};
 use crate::server::{Data, HttpError};
 use chrono::{Duration, SecondsFormat, Utc};
@@ -10,6 +11,9 @@ use hyper::Body;
 use std::collections::HashMap;
 use std::sync::Arc;
 
+/// Number of error signatures of the regressed crates shown on the page of running experiments.
+const LIVE_TOP_ERRORS: usize = 10;
+
 #[derive(Serialize)]
 struct ExperimentData {
     name: String,
@@ -18,6 +22,7 @@ struct ExperimentData {
     mode: &'static str,
     assigned_to: Option<String>,
     requirement: Option<String>,
+    namespace: Option<String>,
     progress: u8,
     priority: i32,
 }
@@ -27,10 +32,12 @@ impl ExperimentData {
         let (status_class, status_pretty, show_progress) = match experiment.status {
             Status::Queued => ("", "Queued", true),
             Status::Running => ("orange", "Running", true),
+            Status::Paused => ("red", "Paused", true),
             Status::NeedsReport => ("orange", "Needs report", false),
             Status::Failed => ("red", "Failed", false),
             Status::GeneratingReport => ("orange", "Generating report", false),
             Status::ReportFailed => ("red", "Report failed", false),
+            Status::AwaitingApproval => ("orange", "Awaiting sign-off", false),
             Status::Completed => ("green", "Completed", false),
         };
 
@@ -42,13 +49,18 @@ impl ExperimentData {
                 Mode::BuildAndTest => "cargo test",
                 Mode::BuildOnly => "cargo build",
                 Mode::CheckOnly => "cargo check",
+                Mode::CheckThenTest => "cargo check, then cargo test",
                 Mode::Clippy => "cargo clippy",
                 Mode::Rustdoc => "cargo doc",
+                Mode::RustdocStrict => "cargo doc, denying broken intra-doc links",
                 Mode::UnstableFeatures => "unstable features",
+                Mode::Msrv => "cargo build, with the declared MSRV",
+                Mode::Miri => "cargo miri test",
             },
             assigned_to: experiment.assigned_to.as_ref().map(|a| a.to_string()),
             priority: experiment.priority,
             requirement: experiment.requirement.clone(),
+            namespace: experiment.namespace.clone(),
             progress: if show_progress {
                 experiment.progress(&data.db)?
             } else {
@@ -69,8 +81,10 @@ pub fn endpoint_queue(data: Arc<Data>) -> Fallible<Response<Body>> {
     let mut running = Vec::new();
     let mut needs_report = Vec::new();
     let mut failed = Vec::new();
+    let mut paused = Vec::new();
     let mut generating_report = Vec::new();
     let mut report_failed = Vec::new();
+    let mut awaiting_approval = Vec::new();
 
     for experiment in &Experiment::unfinished(&data.db)? {
         // Don't include completed experiments in the queue
@@ -83,19 +97,23 @@ pub fn endpoint_queue(data: Arc<Data>) -> Fallible<Response<Body>> {
         match experiment.status {
             Status::Queued => queued.push(ex),
             Status::Running => running.push(ex),
+            Status::Paused => paused.push(ex),
             Status::NeedsReport => needs_report.push(ex),
             Status::Failed => failed.push(ex),
             Status::GeneratingReport => generating_report.push(ex),
             Status::ReportFailed => report_failed.push(ex),
+            Status::AwaitingApproval => awaiting_approval.push(ex),
             Status::Completed => unreachable!(),
         };
     }
 
     let mut experiments = Vec::new();
     experiments.append(&mut report_failed);
+    experiments.append(&mut awaiting_approval);
     experiments.append(&mut generating_report);
     experiments.append(&mut needs_report);
     experiments.append(&mut failed);
+    experiments.append(&mut paused);
     experiments.append(&mut running);
     experiments.append(&mut queued);
 
@@ -126,6 +144,40 @@ struct ExperimentExt {
     duration: Option<String>,
     estimated_end: Option<String>,
     average_job_duration: Option<String>,
+
+    live: bool,
+    comparison_counts: Vec<(String, u32)>,
+    top_errors: Vec<(String, u32)>,
+}
+
+type LiveCounters = (Vec<(String, u32)>, Vec<(String, u32)>);
+
+/// Count the crates completed on both toolchains in each category of the report, along with the
+/// most common results of the regressed crates.
+fn live_counters(data: &Data, ex: &Experiment) -> Fallible<LiveCounters> {
+    let db = DatabaseDB::new(&data.db);
+    let mut comparisons = HashMap::new();
+    let mut errors = HashMap::new();
+    for (comparison, end) in report::compare_completed(&db, &data.config, ex)? {
+        *comparisons.entry(comparison).or_insert(0) += 1;
+        if comparison == Comparison::Regressed {
+            *errors.entry(end.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    let mut comparisons = comparisons.into_iter().collect::<Vec<_>>();
+    comparisons.sort_by_key(|(comparison, _)| (comparison.report_order(), comparison.to_string()));
+    let comparisons = comparisons
+        .into_iter()
+        .map(|(comparison, count)| (comparison.to_string(), count))
+        .collect();
+
+    // Ties are sorted by name, to avoid the page changing randomly between refreshes
+    let mut errors = errors.into_iter().collect::<Vec<_>>();
+    errors.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    errors.truncate(LIVE_TOP_ERRORS);
+
+    Ok((comparisons, errors))
 }
 
 #[derive(Serialize)]
@@ -183,6 +235,14 @@ pub fn endpoint_experiment(name: String, data: Arc<Data>) -> Fallible<Response<B
             (None, None, None)
         };
 
+        // Comparing the results is only worth it while they're still arriving
+        let live = ex.status == Status::Running || ex.status == Status::Paused;
+        let (comparison_counts, top_errors) = if live {
+            live_counters(&data, &ex)?
+        } else {
+            (Vec::new(), Vec::new())
+        };
+
         let experiment = ExperimentExt {
             common: ExperimentData::new(&data, &ex)?,
 
@@ -203,6 +263,10 @@ pub fn endpoint_experiment(name: String, data: Arc<Data>) -> Fallible<Response<B
             duration,
             estimated_end,
             average_job_duration,
+
+            live,
+            comparison_counts,
+            top_errors,
         };
 
         render_template(
@@ -0,0 +1,23 @@
+use crate::prelude::*;
+use crate::report::heatmap::{self, HeatMap};
+use crate::server::routes::ui::{render_template, LayoutContext};
+use crate::server::Data;
+use http::Response;
+use hyper::Body;
+use std::sync::Arc;
+
+#[derive(Serialize)]
+struct HeatMapContext {
+    layout: LayoutContext,
+    heatmap: HeatMap,
+}
+
+pub fn endpoint_heatmap(data: Arc<Data>) -> Fallible<Response<Body>> {
+    render_template(
+        "ui/heatmap.html",
+        &HeatMapContext {
+            layout: LayoutContext::new(),
+            heatmap: heatmap::load(&data.db)?,
+        },
+    )
+}
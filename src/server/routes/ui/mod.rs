@@ -10,6 +10,7 @@ use warp::{self, Filter, Rejection};
 
 mod agents;
 mod experiments;
+mod heatmap;
 
 #[derive(Serialize)]
 struct LayoutContext {
@@ -44,9 +45,15 @@ pub fn routes(
     let agents = warp::get2()
         .and(warp::path("agents"))
         .and(warp::path::end())
-        .and(data_filter)
+        .and(data_filter.clone())
         .map(agents::endpoint_list);
 
+    let heatmap = warp::get2()
+        .and(warp::path("heatmap"))
+        .and(warp::path::end())
+        .and(data_filter)
+        .map(heatmap::endpoint_heatmap);
+
     let assets = warp::get2()
         .and(warp::path("assets"))
         .and(warp::path::param())
@@ -60,6 +67,8 @@ pub fn routes(
                 .unify()
                 .or(agents)
                 .unify()
+                .or(heatmap)
+                .unify()
                 .or(assets)
                 .unify(),
         )
@@ -1,4 +1,4 @@
-use crate::experiments::{Assignee, CapLints, DeferredCrateSelect, Mode};
+use crate::experiments::{Assignee, CapLints, DeferredCrateSelect, Mode, TestRunner};
 use crate::toolchain::Toolchain;
 use failure::{self, Fallible};
 
@@ -7,24 +7,40 @@ use failure::{self, Fallible};
 pub enum CommandParseError {
     #[fail(display = "missing command")]
     MissingCommand,
+    #[fail(display = "unknown command: {}", _0)]
+    UnknownCommand(String),
+    #[fail(display = "unknown command: {} (did you mean `{}`?)", _0, _1)]
+    MisspelledCommand(String, String),
     #[fail(display = "invalid argument: {}", _0)]
     InvalidArgument(String),
     #[fail(display = "duplicate key: {}", _0)]
     DuplicateKey(String),
     #[fail(display = "unknown key: {}", _0)]
     UnknownKey(String),
+    #[fail(display = "unknown key: {} (did you mean `{}`?)", _0, _1)]
+    MisspelledKey(String, String),
+}
+
+/// Names of a command of the bot, and keys it accepts.
+pub struct CommandHelp {
+    /// The first name is the main one, followed by its aliases.
+    pub names: &'static [&'static str],
+    pub keys: &'static [&'static str],
 }
 
 macro_rules! generate_parser {
     (pub enum $enum:ident {
-        $($command:expr => $variant:ident($var_struct:ident {
+        $($($command:literal)|+ => $variant:ident($var_struct:ident {
             $($flag:ident: $type:ty = $name:expr,)*
         }))*
-        _ => $d_variant:ident($d_var_struct:ident {$($d_flag:ident: $d_type:ty = $d_name:expr,)*})
+        _ $(| $d_command:literal)* => $d_variant:ident($d_var_struct:ident {
+            $($d_flag:ident: $d_type:ty = $d_name:expr,)*
+        })
     }) => {
         use crate::prelude::*;
         use std::str::FromStr;
-        use crate::utils::string::split_quoted;
+        use crate::server::routes::webhooks::args::CommandHelp;
+        use crate::utils::string::{closest, split_quoted};
 
         $(
             #[cfg_attr(test, derive(Debug, PartialEq))]
@@ -45,6 +61,22 @@ macro_rules! generate_parser {
             $($variant($var_struct),)*
         }
 
+        impl $enum {
+            /// List the commands of the parser, ending with the one used when no command is given.
+            pub fn help() -> Vec<CommandHelp> {
+                vec![
+                    $(CommandHelp {
+                        names: &[$($command),+],
+                        keys: &[$($name),*],
+                    },)*
+                    CommandHelp {
+                        names: &[$($d_command),*],
+                        keys: &[$($d_name),*],
+                    },
+                ]
+            }
+        }
+
         #[allow(unused_variables, unused_mut)]
         impl FromStr for $enum {
             type Err = ::failure::Error;
@@ -53,11 +85,30 @@ macro_rules! generate_parser {
                 let mut parts = split_quoted(input)?.into_iter().peekable();
                 Ok(match parts.peek().map(|s| s.as_str()) {
                     $(
-                        Some($command) => generate_parser!(@parser
+                        $(Some($command))|+ => generate_parser!(@parser
                             parts.skip(1), $enum, $variant, $var_struct,
                             $($flag, $type, $name),*
                         ),
                     )*
+                    $(
+                        Some($d_command) => generate_parser!(@parser
+                            parts.skip(1), $enum, $d_variant, $d_var_struct,
+                            $($d_flag, $d_type, $d_name),*
+                        ),
+                    )*
+                    // Arguments of the default command always contain an `=`
+                    Some(command) if !command.is_empty() && !command.contains('=') => {
+                        let help = $enum::help();
+                        let names = help.iter().flat_map(|h| h.names.iter().cloned());
+                        return Err(match closest(command, names) {
+                            Some(suggestion) => CommandParseError::MisspelledCommand(
+                                command.to_string(),
+                                suggestion.to_string(),
+                            ),
+                            None => CommandParseError::UnknownCommand(command.to_string()),
+                        }
+                        .into());
+                    }
                     Some(_) => generate_parser!(@parser
                         parts, $enum, $d_variant, $d_var_struct,
                         $($d_flag, $d_type, $d_name),*
@@ -94,7 +145,13 @@ macro_rules! generate_parser {
                 }
             })*
             else {
-                return Err(CommandParseError::UnknownKey(key.to_string()).into());
+                return Err(match closest(key, vec![$($name),*]) {
+                    Some(suggestion) => {
+                        CommandParseError::MisspelledKey(key.to_string(), suggestion.to_string())
+                    }
+                    None => CommandParseError::UnknownKey(key.to_string()),
+                }
+                .into());
             }
         }
 
@@ -103,7 +160,7 @@ macro_rules! generate_parser {
 }
 
 generate_parser!(pub enum Command {
-    "run" => Run(RunArgs {
+    "run" | "start" => Run(RunArgs {
         name: Option<String> = "name",
         start: Option<Toolchain> = "start",
         end: Option<Toolchain> = "end",
@@ -114,6 +171,17 @@ generate_parser!(pub enum Command {
         ignore_blacklist: Option<bool> = "ignore-blacklist",
         assign: Option<Assignee> = "assign",
         requirement: Option<String> = "requirement",
+        expect_regression: Option<String> = "expect-regression",
+        env: Option<String> = "env",
+        deny_lints: Option<String> = "deny-lints",
+        cargo_update: Option<bool> = "cargo-update",
+        failure_threshold: Option<String> = "failure-threshold",
+        reuse_baseline: Option<bool> = "reuse-baseline",
+        canary: Option<bool> = "canary",
+        test_runner: Option<TestRunner> = "test-runner",
+        survey: Option<bool> = "survey",
+        parent: Option<String> = "parent",
+        sandbox_image: Option<String> = "sandbox-image",
     })
 
     "check" => Check(CheckArgs {
@@ -126,6 +194,38 @@ generate_parser!(pub enum Command {
         ignore_blacklist: Option<bool> = "ignore-blacklist",
         assign: Option<Assignee> = "assign",
         requirement: Option<String> = "requirement",
+        expect_regression: Option<String> = "expect-regression",
+        env: Option<String> = "env",
+        deny_lints: Option<String> = "deny-lints",
+        cargo_update: Option<bool> = "cargo-update",
+        failure_threshold: Option<String> = "failure-threshold",
+        reuse_baseline: Option<bool> = "reuse-baseline",
+        canary: Option<bool> = "canary",
+    })
+
+    "clone" => CloneExperiment(CloneArgs {
+        from: Option<String> = "from",
+        name: Option<String> = "name",
+        start: Option<Toolchain> = "start",
+        end: Option<Toolchain> = "end",
+        mode: Option<Mode> = "mode",
+        crates: Option<DeferredCrateSelect> = "crates",
+        cap_lints: Option<CapLints> = "cap-lints",
+        priority: Option<i32> = "p",
+        ignore_blacklist: Option<bool> = "ignore-blacklist",
+        assign: Option<Assignee> = "assign",
+        requirement: Option<String> = "requirement",
+        cargo_update: Option<bool> = "cargo-update",
+        failure_threshold: Option<String> = "failure-threshold",
+        reuse_baseline: Option<bool> = "reuse-baseline",
+        canary: Option<bool> = "canary",
+        test_runner: Option<TestRunner> = "test-runner",
+        sandbox_image: Option<String> = "sandbox-image",
+    })
+
+    "expect-regression" => ExpectRegression(ExpectRegressionArgs {
+        name: Option<String> = "name",
+        pattern: Option<String> = "pattern",
     })
 
     "abort" => Abort(AbortArgs {
@@ -134,17 +234,33 @@ generate_parser!(pub enum Command {
 
     "ping" => Ping(PingArgs {})
 
+    "status" => Status(StatusArgs {
+        name: Option<String> = "name",
+    })
+
+    "partial-report" => PartialReport(PartialReportArgs {
+        name: Option<String> = "name",
+    })
+
     "retry-report" => RetryReport(RetryReportArgs {
         name: Option<String> = "name",
     })
 
+    "approve-report" => ApproveReport(ApproveReportArgs {
+        name: Option<String> = "name",
+    })
+
     "retry" => Retry(RetryArgs {
         name: Option<String> = "name",
     })
 
     "reload-acl" => ReloadACL(ReloadACLArgs {})
 
-    _ => Edit(EditArgs {
+    "help" => Help(HelpArgs {
+        command: Option<String> = "command",
+    })
+
+    _ | "edit" => Edit(EditArgs {
         name: Option<String> = "name",
         start: Option<Toolchain> = "start",
         end: Option<Toolchain> = "end",
@@ -155,6 +271,14 @@ generate_parser!(pub enum Command {
         ignore_blacklist: Option<bool> = "ignore-blacklist",
         assign: Option<Assignee> = "assign",
         requirement: Option<String> = "requirement",
+        env: Option<String> = "env",
+        deny_lints: Option<String> = "deny-lints",
+        cargo_update: Option<bool> = "cargo-update",
+        failure_threshold: Option<String> = "failure-threshold",
+        reuse_baseline: Option<bool> = "reuse-baseline",
+        canary: Option<bool> = "canary",
+        test_runner: Option<TestRunner> = "test-runner",
+        sandbox_image: Option<String> = "sandbox-image",
     })
 });
 
@@ -169,11 +293,11 @@ mod tests {
             arg2: Option<String> = "arg2",
         })
 
-        "bar" => Bar(BarArgs {
+        "bar" | "qux" => Bar(BarArgs {
             arg3: Option<String> = "arg3",
         })
 
-        _ => Baz(BazArgs {
+        _ | "baz" => Baz(BazArgs {
             arg4: Option<i32> = "arg4",
         })
     });
@@ -234,8 +358,21 @@ mod tests {
 
         // Test if invalid args are rejected
         test!(fail "foo arg1=98 arg1=42", CommandParseError::DuplicateKey("arg1".into()));
-        test!(fail "bar arg1=98", CommandParseError::UnknownKey("arg1".into()));
-        test!(fail "foo arg4=42", CommandParseError::UnknownKey("arg4".into()));
+        test!(fail "bar foo=98", CommandParseError::UnknownKey("foo".into()));
         test!(fail "foo bar", CommandParseError::InvalidArgument("bar".into()));
+
+        // Test if aliases are recognized
+        test!(
+            "qux arg3=foo",
+            TestCommand::Bar(BarArgs {
+                arg3: Some("foo".into())
+            })
+        );
+        test!("baz arg4=42", TestCommand::Baz(BazArgs { arg4: Some(42) }));
+
+        // Test if typos are detected
+        test!(fail "bar arg1=98", CommandParseError::MisspelledKey("arg1".into(), "arg3".into()));
+        test!(fail "fo arg1=98", CommandParseError::MisspelledCommand("fo".into(), "foo".into()));
+        test!(fail "something", CommandParseError::UnknownCommand("something".into()));
     }
 }
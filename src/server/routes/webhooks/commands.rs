@@ -1,15 +1,42 @@
 use crate::actions::{self, Action, ActionsCtx};
 use crate::db::{Database, QueryUtils};
-use crate::experiments::{CapLints, CrateSelect, Experiment, GitHubIssue, Mode, Status};
+use crate::experiments::{
+    CapLints, CrateSelect, DeferredCrateSelect, Experiment, GitHubIssue, Mode, Status,
+};
 use crate::prelude::*;
+use crate::server::auth::Role;
 use crate::server::github::{GitHub, Issue, Repository};
 use crate::server::messages::{Label, Message};
 use crate::server::routes::webhooks::args::{
-    AbortArgs, CheckArgs, EditArgs, RetryArgs, RetryReportArgs, RunArgs,
+    AbortArgs, ApproveReportArgs, CheckArgs, CloneArgs, Command, EditArgs, ExpectRegressionArgs,
+    HelpArgs, PartialReportArgs, RetryArgs, RetryReportArgs, RunArgs, StatusArgs,
 };
 use crate::server::Data;
 use crate::toolchain::Toolchain;
 use rustwide::Toolchain as RustwideToolchain;
+use std::collections::BTreeMap;
+
+/// Resolve the `crates` argument. Lists stored in files are rejected, as they would be read from
+/// the filesystem of the server.
+fn resolve_crates(crates: Option<DeferredCrateSelect>) -> Fallible<Option<CrateSelect>> {
+    let crates = crates
+        .map(|c| c.resolve())
+        .transpose()
+        .map_err(|e| e.context("Failed to resolve crate list"))?;
+    if let Some(CrateSelect::File(_)) = crates {
+        bail!("File lists of crates can only be used from the command line");
+    }
+    Ok(crates)
+}
+
+/// Parse the `env` argument, containing whitespace-separated `NAME=value` pairs.
+fn parse_env(env: Option<String>) -> Fallible<Option<BTreeMap<String, String>>> {
+    env.map(|env| {
+        let vars = env.split_whitespace().map(String::from).collect::<Vec<_>>();
+        actions::parse_env(&vars)
+    })
+    .transpose()
+}
 
 pub fn ping(data: &Data, issue: &Issue) -> Fallible<()> {
     Message::new()
@@ -19,12 +46,95 @@ pub fn ping(data: &Data, issue: &Issue) -> Fallible<()> {
     Ok(())
 }
 
+pub fn help(data: &Data, issue: &Issue, args: HelpArgs) -> Fallible<()> {
+    let docs = format!("{}/blob/master/docs/bot-usage.md", crate::CRATER_REPO_URL);
+    let commands = Command::help();
+    let mut message = Message::new();
+
+    if let Some(command) = args.command {
+        let help = commands
+            .iter()
+            .find(|help| help.names.contains(&command.as_str()))
+            .ok_or_else(|| err_msg(format!("unknown command: {}", command)))?;
+        let keys = help
+            .keys
+            .iter()
+            .map(|key| format!("`{}`", key))
+            .collect::<Vec<_>>();
+        message = message.line(
+            "book",
+            format!(
+                "Options of the **`{}`** command: {}.",
+                command,
+                keys.join(", ")
+            ),
+        );
+        if help.names.len() > 1 {
+            let aliases = help
+                .names
+                .iter()
+                .map(|n| format!("`{}`", n))
+                .collect::<Vec<_>>();
+            message = message.line("link", format!("Also known as {}.", aliases.join(", ")));
+        }
+    } else {
+        let names = commands
+            .iter()
+            .filter_map(|help| help.names.first())
+            .map(|name| format!("`{}`", name))
+            .collect::<Vec<_>>();
+        message = message
+            .line("book", format!("Available commands: {}.", names.join(", ")))
+            .line(
+                "information_source",
+                "Use `help <command>` to list the options of a command. Commands without a \
+                 name edit an existing experiment.",
+            );
+    }
+
+    message
+        .note(
+            "mag",
+            format!("The full documentation is [available here]({}).", docs),
+        )
+        .send(&issue.url, data)?;
+    Ok(())
+}
+
+pub fn status(
+    data: &Data,
+    issue: &Issue,
+    args: StatusArgs,
+    namespace: Option<&str>,
+) -> Fallible<()> {
+    let name = get_name(&data.db, issue, args.name, namespace)?;
+
+    if let Some(experiment) = Experiment::get(&data.db, &name)? {
+        let mut message = format!(
+            "Experiment **`{}`** is **{}**",
+            name,
+            experiment.status.to_str()
+        );
+        if experiment.status == Status::Running {
+            message.push_str(&format!(" ({}% done)", experiment.progress(&data.db)?));
+        }
+
+        Message::new()
+            .line("information_source", format!("{}.", message))
+            .send(&issue.url, data)?;
+        Ok(())
+    } else {
+        bail!("an experiment named **`{}`** doesn't exist!", name);
+    }
+}
+
 pub fn check(
     host: &str,
     data: &Data,
     repo: &Repository,
     issue: &Issue,
     args: CheckArgs,
+    namespace: Option<&str>,
 ) -> Fallible<()> {
     run(
         host,
@@ -42,7 +152,19 @@ pub fn check(
             ignore_blacklist: args.ignore_blacklist,
             assign: args.assign,
             requirement: args.requirement,
+            expect_regression: args.expect_regression,
+            env: args.env,
+            deny_lints: args.deny_lints,
+            cargo_update: args.cargo_update,
+            failure_threshold: args.failure_threshold,
+            reuse_baseline: args.reuse_baseline,
+            canary: args.canary,
+            test_runner: None,
+            survey: None,
+            parent: None,
+            sandbox_image: None,
         },
+        namespace,
     )
 }
 
@@ -52,6 +174,7 @@ pub fn run(
     repo: &Repository,
     issue: &Issue,
     args: RunArgs,
+    namespace: Option<&str>,
 ) -> Fallible<()> {
     let name = setup_run_name(&data.db, issue, args.name)?;
 
@@ -60,21 +183,30 @@ pub fn run(
         format!("Experiment **`{}`** created and queued.", name),
     );
 
+    let survey = args.survey.unwrap_or(false);
+    if survey && args.end.is_some() {
+        bail!("survey experiments only have one toolchain (`start=`)");
+    }
+
     // Autodetect toolchains only if none of them was specified
     let (mut detected_start, mut detected_end) = (None, None);
-    if args.start.is_none() && args.end.is_none() {
+    if args.start.is_none() && args.end.is_none() && !survey {
         if let Some(build) =
             crate::server::try_builds::get_sha(&data.db, &repo.full_name, issue.number)?
         {
             detected_start = Some(Toolchain {
                 source: RustwideToolchain::ci(&build.base_sha, false),
                 rustflags: None,
+                env: BTreeMap::new(),
+                cargo_config: BTreeMap::new(),
                 ci_try: false,
                 patches: Vec::new(),
             });
             detected_end = Some(Toolchain {
                 source: RustwideToolchain::ci(&build.merge_sha, false),
                 rustflags: None,
+                env: BTreeMap::new(),
+                cargo_config: BTreeMap::new(),
                 ci_try: true,
                 patches: Vec::new(),
             });
@@ -97,22 +229,24 @@ pub fn run(
 
     // Make crater runs created via webhook require linux by default.
     let requirement = args.requirement.unwrap_or_else(|| "linux".to_string());
-    let crates = args
-        .crates
-        .map(|c| c.resolve())
-        .transpose()
-        .map_err(|e| e.context("Failed to resolve crate list"))?;
+    let crates = resolve_crates(args.crates)?;
+
+    let start = args
+        .start
+        .or(detected_start)
+        .ok_or_else(|| err_msg("missing start toolchain"))?;
+    // Survey experiments store their only toolchain in both slots
+    let end = if survey {
+        start.clone()
+    } else {
+        args.end
+            .or(detected_end)
+            .ok_or_else(|| err_msg("missing end toolchain"))?
+    };
 
     actions::CreateExperiment {
         name: name.clone(),
-        toolchains: [
-            args.start
-                .or(detected_start)
-                .ok_or_else(|| err_msg("missing start toolchain"))?,
-            args.end
-                .or(detected_end)
-                .ok_or_else(|| err_msg("missing end toolchain"))?,
-        ],
+        toolchains: [start, end],
         mode: args.mode.unwrap_or(Mode::BuildAndTest),
         crates: crates.unwrap_or(CrateSelect::Full),
         cap_lints: args.cap_lints.unwrap_or(CapLints::Forbid),
@@ -125,6 +259,27 @@ pub fn run(
         ignore_blacklist: args.ignore_blacklist.unwrap_or(false),
         assign: args.assign,
         requirement: Some(requirement),
+        cargo_config: None,
+        expected_regressions: args.expect_regression.into_iter().collect(),
+        env: parse_env(args.env)?.unwrap_or_default(),
+        deny_lints: args
+            .deny_lints
+            .map(|lints| actions::parse_lints(&lints))
+            .unwrap_or_default(),
+        cargo_update: args.cargo_update.unwrap_or(false),
+        failure_threshold: match args.failure_threshold {
+            Some(threshold) => actions::parse_failure_threshold(&threshold)?,
+            None => None,
+        },
+        reuse_baseline: args.reuse_baseline.unwrap_or(false),
+        canary: args.canary.unwrap_or(false),
+        test_runner: args.test_runner.unwrap_or_default(),
+        namespace: namespace.map(String::from),
+        survey,
+        parent: args.parent,
+        sandbox_image: args
+            .sandbox_image
+            .and_then(|image| actions::parse_sandbox_image(&image)),
     }
     .apply(&ActionsCtx::new(&data.db, &data.config))?;
 
@@ -140,14 +295,84 @@ pub fn run(
     Ok(())
 }
 
-pub fn edit(data: &Data, issue: &Issue, args: EditArgs) -> Fallible<()> {
-    let name = get_name(&data.db, issue, args.name)?;
+pub fn clone(
+    host: &str,
+    data: &Data,
+    issue: &Issue,
+    args: CloneArgs,
+    namespace: Option<&str>,
+) -> Fallible<()> {
+    let from = args
+        .from
+        .ok_or_else(|| err_msg("missing the name of the experiment to clone (`from=`)"))?;
+    if !Experiment::exists(&data.db, &from)? {
+        bail!("an experiment named **`{}`** doesn't exist!", from);
+    }
+    ensure_in_namespace(&data.db, &from, namespace)?;
+
+    let name = setup_run_name(&data.db, issue, args.name)?;
+    let crates = resolve_crates(args.crates)?;
+
+    actions::CloneExperiment {
+        name: from.clone(),
+        new_name: name.clone(),
+        toolchains: [args.start, args.end],
+        crates,
+        mode: args.mode,
+        cap_lints: args.cap_lints,
+        priority: args.priority,
+        github_issue: Some(GitHubIssue {
+            api_url: issue.url.clone(),
+            html_url: issue.html_url.clone(),
+            number: issue.number,
+        }),
+        ignore_blacklist: args.ignore_blacklist,
+        assign: args.assign,
+        requirement: args.requirement,
+        cargo_update: args.cargo_update,
+        failure_threshold: args
+            .failure_threshold
+            .map(|threshold| actions::parse_failure_threshold(&threshold))
+            .transpose()?,
+        reuse_baseline: args.reuse_baseline,
+        canary: args.canary,
+        test_runner: args.test_runner,
+        sandbox_image: args
+            .sandbox_image
+            .map(|image| actions::parse_sandbox_image(&image)),
+    }
+    .apply(&ActionsCtx::new(&data.db, &data.config))?;
+
+    Message::new()
+        .line(
+            "ok_hand",
+            format!(
+                "Experiment **`{}`** created from **`{}`** and queued.",
+                name, from
+            ),
+        )
+        .line(
+            "mag",
+            format!(
+                "You can check out [the queue](https://{}) and [this experiment's details](https://{0}/ex/{1}).", host, name
+            ),
+        ).set_label(Label::ExperimentQueued)
+        .send(&issue.url, data)?;
+
+    Ok(())
+}
+
+pub fn edit(
+    data: &Data,
+    issue: &Issue,
+    args: EditArgs,
+    role: Role,
+    namespace: Option<&str>,
+) -> Fallible<()> {
+    let name = get_name(&data.db, issue, args.name, namespace)?;
+    ensure_can_change(&data.db, issue, &name, role)?;
 
-    let crates = args
-        .crates
-        .map(|c| c.resolve())
-        .transpose()
-        .map_err(|e| e.context("Failed to resolve crate list"))?;
+    let crates = resolve_crates(args.crates)?;
 
     actions::EditExperiment {
         name: name.clone(),
@@ -159,6 +384,20 @@ pub fn edit(data: &Data, issue: &Issue, args: EditArgs) -> Fallible<()> {
         ignore_blacklist: args.ignore_blacklist,
         assign: args.assign,
         requirement: args.requirement,
+        cargo_config: None,
+        env: parse_env(args.env)?,
+        deny_lints: args.deny_lints.map(|lints| actions::parse_lints(&lints)),
+        cargo_update: args.cargo_update,
+        failure_threshold: args
+            .failure_threshold
+            .map(|threshold| actions::parse_failure_threshold(&threshold))
+            .transpose()?,
+        reuse_baseline: args.reuse_baseline,
+        canary: args.canary,
+        test_runner: args.test_runner,
+        sandbox_image: args
+            .sandbox_image
+            .map(|image| actions::parse_sandbox_image(&image)),
     }
     .apply(&ActionsCtx::new(&data.db, &data.config))?;
 
@@ -172,8 +411,85 @@ pub fn edit(data: &Data, issue: &Issue, args: EditArgs) -> Fallible<()> {
     Ok(())
 }
 
-pub fn retry_report(data: &Data, issue: &Issue, args: RetryReportArgs) -> Fallible<()> {
-    let name = get_name(&data.db, issue, args.name)?;
+pub fn expect_regression(
+    data: &Data,
+    issue: &Issue,
+    args: ExpectRegressionArgs,
+    role: Role,
+    namespace: Option<&str>,
+) -> Fallible<()> {
+    let name = get_name(&data.db, issue, args.name, namespace)?;
+    let pattern = args
+        .pattern
+        .ok_or_else(|| err_msg("missing the pattern of the expected regressions (`pattern=`)"))?;
+    ensure_can_change(&data.db, issue, &name, role)?;
+
+    actions::ExpectRegressions {
+        name: name.clone(),
+        patterns: vec![pattern.clone()],
+    }
+    .apply(&ActionsCtx::new(&data.db, &data.config))?;
+
+    Message::new()
+        .line(
+            "memo",
+            format!(
+                "Regressions of **`{}`** matching `{}` will be reported as expected.",
+                name, pattern
+            ),
+        )
+        .send(&issue.url, data)?;
+
+    Ok(())
+}
+
+pub fn partial_report(
+    data: &Data,
+    issue: &Issue,
+    args: PartialReportArgs,
+    namespace: Option<&str>,
+) -> Fallible<()> {
+    let name = get_name(&data.db, issue, args.name, namespace)?;
+
+    if let Some(experiment) = Experiment::get(&data.db, &name)? {
+        if experiment.status != Status::Running {
+            bail!("experiment **`{}`** is not running!", name);
+        }
+
+        data.reports_worker.request_partial(&name);
+
+        // The location of drafts must not be posted on the (public) tracking issue
+        let location = if crate::server::reports::is_draft(data, &experiment) {
+            "with the draft of the report".to_string()
+        } else {
+            format!(
+                "[here]({})",
+                crate::server::reports::report_url(data, &experiment)
+            )
+        };
+        Message::new()
+            .line(
+                "hammer_and_wrench",
+                format!(
+                    "Partial report for **`{}`** queued, it will be available {} in a few \
+                     minutes.",
+                    name, location,
+                ),
+            )
+            .send(&issue.url, data)?;
+        Ok(())
+    } else {
+        bail!("an experiment named **`{}`** doesn't exist!", name);
+    }
+}
+
+pub fn retry_report(
+    data: &Data,
+    issue: &Issue,
+    args: RetryReportArgs,
+    namespace: Option<&str>,
+) -> Fallible<()> {
+    let name = get_name(&data.db, issue, args.name, namespace)?;
 
     if let Some(mut experiment) = Experiment::get(&data.db, &name)? {
         if experiment.status != Status::ReportFailed
@@ -202,13 +518,43 @@ pub fn retry_report(data: &Data, issue: &Issue, args: RetryReportArgs) -> Fallib
     }
 }
 
-pub fn retry(data: &Data, issue: &Issue, args: RetryArgs) -> Fallible<()> {
-    let name = get_name(&data.db, issue, args.name)?;
+pub fn approve_report(
+    data: &Data,
+    issue: &Issue,
+    args: ApproveReportArgs,
+    sender: &str,
+    namespace: Option<&str>,
+) -> Fallible<()> {
+    let name = get_name(&data.db, issue, args.name, namespace)?;
 
     if let Some(mut experiment) = Experiment::get(&data.db, &name)? {
-        if experiment.status != Status::Failed {
-            bail!("Experiment **`{}`** didn't fail!", name);
-        }
+        crate::server::reports::approve_report(data, &mut experiment, sender)?;
+
+        Message::new()
+            .line(
+                "white_check_mark",
+                format!(
+                    "Report of **`{}`** signed off, it will be published in a few minutes.",
+                    name
+                ),
+            )
+            .send(&issue.url, data)?;
+
+        Ok(())
+    } else {
+        bail!("an experiment named **`{}`** doesn't exist!", name);
+    }
+}
+
+pub fn retry(data: &Data, issue: &Issue, args: RetryArgs, namespace: Option<&str>) -> Fallible<()> {
+    let name = get_name(&data.db, issue, args.name, namespace)?;
+
+    if let Some(mut experiment) = Experiment::get(&data.db, &name)? {
+        let action = match experiment.status {
+            Status::Failed => "queued again",
+            Status::Paused => "resumed",
+            _ => bail!("Experiment **`{}`** didn't fail!", name),
+        };
 
         experiment.set_status(&data.db, Status::Queued)?;
         data.reports_worker.wake();
@@ -216,7 +562,7 @@ pub fn retry(data: &Data, issue: &Issue, args: RetryArgs) -> Fallible<()> {
         Message::new()
             .line(
                 "hammer_and_wrench",
-                format!("Experiment **`{}`** queued again.", name),
+                format!("Experiment **`{}`** {}.", name, action),
             )
             .set_label(Label::ExperimentQueued)
             .send(&issue.url, data)?;
@@ -227,8 +573,15 @@ pub fn retry(data: &Data, issue: &Issue, args: RetryArgs) -> Fallible<()> {
     }
 }
 
-pub fn abort(data: &Data, issue: &Issue, args: AbortArgs) -> Fallible<()> {
-    let name = get_name(&data.db, issue, args.name)?;
+pub fn abort(
+    data: &Data,
+    issue: &Issue,
+    args: AbortArgs,
+    role: Role,
+    namespace: Option<&str>,
+) -> Fallible<()> {
+    let name = get_name(&data.db, issue, args.name, namespace)?;
+    ensure_can_change(&data.db, issue, &name, role)?;
 
     actions::DeleteExperiment { name: name.clone() }
         .apply(&ActionsCtx::new(&data.db, &data.config))?;
@@ -251,15 +604,77 @@ pub fn reload_acl(data: &Data, issue: &Issue) -> Fallible<()> {
     Ok(())
 }
 
-fn get_name(db: &Database, issue: &Issue, name: Option<String>) -> Fallible<String> {
-    if let Some(name) = name {
+/// Admins can change every experiment, while other users can only change the experiments created
+/// in the issue they're commenting on.
+fn ensure_can_change(db: &Database, issue: &Issue, name: &str, role: Role) -> Fallible<()> {
+    if role >= Role::Admin {
+        return Ok(());
+    }
+
+    // Let the command itself report missing experiments
+    let experiment = match Experiment::get(db, name)? {
+        Some(experiment) => experiment,
+        None => return Ok(()),
+    };
+
+    let same_issue = experiment
+        .github_issue
+        .map(|gh| gh.api_url == issue.url)
+        .unwrap_or(false);
+    if !same_issue {
+        bail!(
+            "only admins can change the **`{}`** experiment from another issue!",
+            name
+        );
+    }
+
+    Ok(())
+}
+
+fn get_name(
+    db: &Database,
+    issue: &Issue,
+    name: Option<String>,
+    namespace: Option<&str>,
+) -> Fallible<String> {
+    let name = if let Some(name) = name {
         store_experiment_name(db, issue, &name)?;
-        Ok(name)
+        name
     } else if let Some(default) = default_experiment_name(db, issue)? {
-        Ok(default)
+        default
     } else {
         bail!("missing experiment name");
+    };
+    ensure_in_namespace(db, &name, namespace)?;
+    Ok(name)
+}
+
+/// Commands can only refer to the experiments of their own namespace, so that teams sharing the
+/// deployment don't change each other's experiments by mistake.
+fn ensure_in_namespace(db: &Database, name: &str, namespace: Option<&str>) -> Fallible<()> {
+    // Let the command itself report missing experiments
+    let experiment = match Experiment::get(db, name)? {
+        Some(experiment) => experiment,
+        None => return Ok(()),
+    };
+
+    if experiment.namespace.as_deref() != namespace {
+        match experiment.namespace {
+            Some(ns) => bail!(
+                "experiment **`{}`** belongs to the `{}` namespace, prefix the command with \
+                 `{}` to use it!",
+                name,
+                ns,
+                ns
+            ),
+            None => bail!(
+                "experiment **`{}`** belongs to the default namespace!",
+                name
+            ),
+        }
     }
+
+    Ok(())
 }
 
 fn store_experiment_name(db: &Database, issue: &Issue, name: &str) -> Fallible<()> {
@@ -319,13 +734,15 @@ fn generate_new_experiment_name(db: &Database, issue: &Issue) -> Fallible<String
 #[cfg(test)]
 mod tests {
     use super::{
-        default_experiment_name, generate_new_experiment_name, get_name, setup_run_name,
-        store_experiment_name,
+        default_experiment_name, ensure_can_change, ensure_in_namespace,
+        generate_new_experiment_name, get_name, setup_run_name, store_experiment_name,
     };
     use crate::actions::{self, Action, ActionsCtx};
-    use crate::config::Config;
+    use crate::config::{Config, NamespaceConfig};
     use crate::db::Database;
+    use crate::experiments::GitHubIssue;
     use crate::prelude::*;
+    use crate::server::auth::Role;
     use crate::server::github;
 
     /// Simulate to the `run` command, and return experiment name
@@ -341,7 +758,7 @@ mod tests {
     fn dummy_edit(db: &Database, issue: &github::Issue, name: Option<String>) -> Fallible<String> {
         let config = Config::default();
         let ctx = ActionsCtx::new(&db, &config);
-        let name = get_name(db, issue, name)?;
+        let name = get_name(db, issue, name, None)?;
         actions::EditExperiment::dummy(&name).apply(&ctx)?;
         Ok(name)
     }
@@ -526,4 +943,63 @@ mod tests {
             "pr-12345-2"
         );
     }
+
+    #[test]
+    fn test_ensure_can_change() {
+        let db = Database::temp().unwrap();
+        let config = Config::default();
+        let ctx = ActionsCtx::new(&db, &config);
+
+        let issue = |number: i32| github::Issue {
+            number,
+            url: format!(
+                "https://api.github.com/repos/rust-lang/rust/issues/{}",
+                number
+            ),
+            html_url: String::new(),
+            labels: Vec::new(),
+            pull_request: None,
+        };
+
+        let mut create = actions::CreateExperiment::dummy("foo");
+        create.github_issue = Some(GitHubIssue {
+            api_url: issue(1).url,
+            html_url: String::new(),
+            number: 1,
+        });
+        create.apply(&ctx).unwrap();
+
+        // Trusted users can only change experiments created in the same issue
+        assert!(ensure_can_change(&db, &issue(1), "foo", Role::Trusted).is_ok());
+        assert!(ensure_can_change(&db, &issue(2), "foo", Role::Trusted).is_err());
+
+        // Admins can change every experiment
+        assert!(ensure_can_change(&db, &issue(2), "foo", Role::Admin).is_ok());
+
+        // Missing experiments are reported by the commands themselves
+        assert!(ensure_can_change(&db, &issue(2), "bar", Role::Trusted).is_ok());
+    }
+
+    #[test]
+    fn test_ensure_in_namespace() {
+        let db = Database::temp().unwrap();
+        let mut config = Config::default();
+        config
+            .namespaces
+            .insert("clippy".into(), NamespaceConfig::default());
+        let ctx = ActionsCtx::new(&db, &config);
+
+        actions::CreateExperiment::dummy("foo").apply(&ctx).unwrap();
+        let mut create = actions::CreateExperiment::dummy("bar");
+        create.namespace = Some("clippy".into());
+        create.apply(&ctx).unwrap();
+
+        assert!(ensure_in_namespace(&db, "foo", None).is_ok());
+        assert!(ensure_in_namespace(&db, "foo", Some("clippy")).is_err());
+        assert!(ensure_in_namespace(&db, "bar", Some("clippy")).is_ok());
+        assert!(ensure_in_namespace(&db, "bar", None).is_err());
+
+        // Missing experiments are reported by the commands themselves
+        assert!(ensure_in_namespace(&db, "baz", Some("clippy")).is_ok());
+    }
 }
@@ -1,7 +1,9 @@
 mod args;
 mod commands;
 
+use crate::config::Config;
 use crate::prelude::*;
+use crate::server::auth::Role;
 use crate::server::github::{EventIssueComment, Issue, Repository};
 use crate::server::messages::Message;
 use crate::server::routes::webhooks::args::Command;
@@ -82,16 +84,22 @@ fn process_command(
             continue;
         }
 
-        let command = line[line.find(' ').unwrap()..].trim();
+        let (namespace, command) = split_namespace(&data.config, &line[line.find(' ').unwrap()..]);
+        let command = normalize_command(command);
         if command == "" {
             continue;
         }
 
-        if !data.acl.allowed(sender, sender_id)? {
+        // The cause is included in the message, as only the context would be shown otherwise
+        let args: Command = Command::from_str(&command)
+            .map_err(|err| err_msg(format!("failed to parse the command: {}", err)))?;
+
+        let role = data.acl.role(sender, sender_id)?;
+        if role < args.required_role() {
             Message::new()
                 .line(
                     "lock",
-                    "**Error:** you're not allowed to interact with this bot.",
+                    "**Error:** you're not allowed to use this command of the bot.",
                 )
                 .note(
                     "key",
@@ -107,36 +115,57 @@ fn process_command(
 
         info!("user @{} sent command: {}", sender, command);
 
-        let args: Command =
-            Command::from_str(command).with_context(|_| "failed to parse the command")?;
-
         match args {
             Command::Ping(_) => {
                 commands::ping(data, issue)?;
             }
 
+            Command::Help(args) => {
+                commands::help(data, issue, args)?;
+            }
+
+            Command::Status(args) => {
+                commands::status(data, issue, args, namespace)?;
+            }
+
             Command::Run(args) => {
-                commands::run(host, data, repo, issue, args)?;
+                commands::run(host, data, repo, issue, args, namespace)?;
             }
 
             Command::Check(args) => {
-                commands::check(host, data, repo, issue, args)?;
+                commands::check(host, data, repo, issue, args, namespace)?;
+            }
+
+            Command::CloneExperiment(args) => {
+                commands::clone(host, data, issue, args, namespace)?;
             }
 
             Command::Edit(args) => {
-                commands::edit(data, issue, args)?;
+                commands::edit(data, issue, args, role, namespace)?;
+            }
+
+            Command::ExpectRegression(args) => {
+                commands::expect_regression(data, issue, args, role, namespace)?;
+            }
+
+            Command::PartialReport(args) => {
+                commands::partial_report(data, issue, args, namespace)?;
             }
 
             Command::RetryReport(args) => {
-                commands::retry_report(data, issue, args)?;
+                commands::retry_report(data, issue, args, namespace)?;
+            }
+
+            Command::ApproveReport(args) => {
+                commands::approve_report(data, issue, args, sender, namespace)?;
             }
 
             Command::Retry(args) => {
-                commands::retry(data, issue, args)?;
+                commands::retry(data, issue, args, namespace)?;
             }
 
             Command::Abort(args) => {
-                commands::abort(data, issue, args)?;
+                commands::abort(data, issue, args, role, namespace)?;
             }
 
             Command::ReloadACL(_) => {
@@ -150,6 +179,56 @@ fn process_command(
     Ok(())
 }
 
+impl Command {
+    fn required_role(&self) -> Role {
+        match *self {
+            Command::Ping(_) | Command::Help(_) | Command::Status(_) => Role::Guest,
+            // Users without the admin role can only edit or abort experiments created in the
+            // same issue, which is checked by the commands themselves.
+            Command::Run(_)
+            | Command::Check(_)
+            | Command::CloneExperiment(_)
+            | Command::Edit(_)
+            | Command::ExpectRegression(_)
+            | Command::Abort(_)
+            | Command::PartialReport(_) => Role::Trusted,
+            Command::RetryReport(_)
+            | Command::ApproveReport(_)
+            | Command::Retry(_)
+            | Command::ReloadACL(_) => Role::Admin,
+        }
+    }
+}
+
+/// Split the namespace prefixing a command, like `clippy` in `@craterbot clippy run ...`. Only the
+/// namespaces present in the configuration are recognized, so commands without a prefix belong to
+/// the default namespace.
+fn split_namespace<'a>(config: &Config, command: &'a str) -> (Option<&'a str>, &'a str) {
+    let command = command.trim_start();
+    let mut parts = command.splitn(2, ' ');
+    match (parts.next(), parts.next()) {
+        (Some(first), rest) if config.namespaces.contains_key(first) => {
+            (Some(first), rest.unwrap_or(""))
+        }
+        _ => (None, command),
+    }
+}
+
+/// Accept the usual ways people write commands, like `crater run ...` (as the bot is called
+/// craterbot) and `help run` instead of `help command=run`.
+fn normalize_command(command: &str) -> String {
+    let mut command = command.trim();
+    if command.starts_with("crater ") {
+        command = command["crater ".len()..].trim_start();
+    }
+
+    let mut words = command.split_whitespace();
+    match (words.next(), words.next(), words.next()) {
+        (Some("help"), Some(name), None) if !name.contains('=') => format!("help command={}", name),
+        _ => command.to_string(),
+    }
+}
+
 fn verify_signature(secret: &str, payload: &[u8], raw_signature: &str) -> bool {
     type HmacSha1 = Hmac<sha1::Sha1>;
 
@@ -230,3 +309,44 @@ pub fn routes(
             resp
         })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize_command, split_namespace};
+    use crate::config::{Config, NamespaceConfig};
+
+    #[test]
+    fn test_normalize_command() {
+        assert_eq!(normalize_command(" run start=stable "), "run start=stable");
+        assert_eq!(
+            normalize_command("crater run start=stable"),
+            "run start=stable"
+        );
+        assert_eq!(normalize_command("help run"), "help command=run");
+        assert_eq!(normalize_command("help"), "help");
+        assert_eq!(normalize_command("help command=run"), "help command=run");
+        assert_eq!(normalize_command("crater"), "crater");
+    }
+
+    #[test]
+    fn test_split_namespace() {
+        let mut config = Config::default();
+        config
+            .namespaces
+            .insert("clippy".into(), NamespaceConfig::default());
+
+        assert_eq!(
+            split_namespace(&config, " clippy run start=stable"),
+            (Some("clippy"), "run start=stable")
+        );
+        assert_eq!(split_namespace(&config, " clippy"), (Some("clippy"), ""));
+        assert_eq!(
+            split_namespace(&config, " run start=stable"),
+            (None, "run start=stable")
+        );
+        assert_eq!(
+            split_namespace(&config, " miri run start=stable"),
+            (None, "miri run start=stable")
+        );
+    }
+}
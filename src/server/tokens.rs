@@ -3,7 +3,7 @@ use rusoto_core::Region;
 use rusoto_credential::StaticProvider;
 use serde_derive::Deserialize;
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 static TOKENS_PATH: &str = "tokens.toml";
 
@@ -26,21 +26,68 @@ impl BucketRegion {
     }
 }
 
+impl Default for BucketRegion {
+    fn default() -> Self {
+        BucketRegion::S3 {
+            region: "us-east-1".to_string(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct BotTokens {
     pub webhooks_secret: String,
+    /// Personal access token of the bot account, used when the bot isn't a GitHub App.
+    #[serde(default)]
     pub api_token: String,
+    #[serde(default)]
+    pub app: Option<GitHubAppTokens>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct GitHubAppTokens {
+    pub app_id: u64,
+    /// ID of the installation of the app on the organization the bot is used in.
+    pub installation_id: u64,
+    /// Path of the PEM-encoded private key of the app, which signs the requests of installation
+    /// tokens.
+    pub private_key: PathBuf,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct ReportsBucket {
+    #[serde(default)]
     pub region: BucketRegion,
     pub bucket: String,
     pub public_url: String,
+    #[serde(default)]
     pub access_key: String,
+    #[serde(default)]
     pub secret_key: String,
+    #[serde(default)]
+    pub gcs: GcsCredentials,
+    #[serde(default)]
+    pub azure: Option<AzureCredentials>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct GcsCredentials {
+    /// OAuth access token used to upload the reports. When missing, the tokens of the service
+    /// account of the machine are fetched from the metadata server of Google Compute Engine.
+    #[serde(default)]
+    pub access_token: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct AzureCredentials {
+    pub account: String,
+    /// Shared access signature allowing to create blobs in the container.
+    pub sas_token: String,
 }
 
 impl ReportsBucket {
@@ -55,6 +102,8 @@ pub struct Tokens {
     pub bot: BotTokens,
     pub reports_bucket: ReportsBucket,
     pub agents: HashMap<String, String>,
+    #[serde(default)]
+    pub triagers: HashMap<String, String>,
 }
 
 #[cfg(test)]
@@ -64,6 +113,7 @@ impl Default for Tokens {
             bot: BotTokens {
                 webhooks_secret: String::new(),
                 api_token: String::new(),
+                app: None,
             },
             reports_bucket: ReportsBucket {
                 region: BucketRegion::S3 {
@@ -73,8 +123,11 @@ impl Default for Tokens {
                 public_url: String::new(),
                 access_key: String::new(),
                 secret_key: String::new(),
+                gcs: GcsCredentials::default(),
+                azure: None,
             },
             agents: HashMap::new(),
+            triagers: HashMap::new(),
         }
     }
 }
@@ -1,6 +1,7 @@
 use crate::prelude::*;
 use crate::utils;
 use rustwide::Toolchain as RustwideToolchain;
+use std::collections::BTreeMap;
 use std::fmt;
 use std::str::FromStr;
 
@@ -10,6 +11,8 @@ lazy_static! {
     pub(crate) static ref MAIN_TOOLCHAIN: Toolchain = Toolchain {
         source: RustwideToolchain::dist("stable"),
         rustflags: None,
+        env: BTreeMap::new(),
+        cargo_config: BTreeMap::new(),
         ci_try: false,
         patches: Vec::new(),
     };
@@ -18,6 +21,8 @@ lazy_static! {
     pub(crate) static ref TEST_TOOLCHAIN: Toolchain = Toolchain {
         source: RustwideToolchain::dist("beta"),
         rustflags: None,
+        env: BTreeMap::new(),
+        cargo_config: BTreeMap::new(),
         ci_try: false,
         patches: Vec::new(),
     };
@@ -27,6 +32,12 @@ lazy_static! {
 pub struct Toolchain {
     pub source: RustwideToolchain,
     pub rustflags: Option<String>,
+    /// Environment variables set only in the builds with this toolchain.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Cargo configuration values set only in the builds with this toolchain.
+    #[serde(default)]
+    pub cargo_config: BTreeMap<String, String>,
     pub ci_try: bool,
     pub patches: Vec<CratePatch>,
 }
@@ -37,6 +48,35 @@ impl Toolchain {
 
         encode(&self.to_string(), &utils::FILENAME_ENCODE_SET).to_string()
     }
+
+    /// Environment variables setting the cargo configuration values of the toolchain, following
+    /// the `CARGO_SECTION_KEY` convention of cargo (so `profile.dev.debug` is set by
+    /// `CARGO_PROFILE_DEV_DEBUG`).
+    pub(crate) fn cargo_config_env(&self) -> Vec<(String, &str)> {
+        self.cargo_config
+            .iter()
+            .map(|(key, value)| {
+                let name = key.to_uppercase().replace('.', "_").replace('-', "_");
+                (format!("CARGO_{}", name), value.as_str())
+            })
+            .collect()
+    }
+}
+
+fn is_valid_env_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn is_valid_config_key(key: &str) -> bool {
+    key.split('.').count() >= 2
+        && key.split('.').all(|part| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+        })
 }
 
 impl std::ops::Deref for Toolchain {
@@ -52,10 +92,11 @@ impl fmt::Display for Toolchain {
         if let Some(dist) = self.source.as_dist() {
             write!(f, "{}", dist.name())?;
         } else if let Some(ci) = self.source.as_ci() {
-            if self.ci_try {
-                write!(f, "try#{}", ci.sha())?;
+            let name = if self.ci_try { "try" } else { "master" };
+            if ci.alt() {
+                write!(f, "{}-alt#{}", name, ci.sha())?;
             } else {
-                write!(f, "master#{}", ci.sha())?;
+                write!(f, "{}#{}", name, ci.sha())?;
             }
         } else {
             panic!("unsupported rustwide toolchain");
@@ -65,6 +106,14 @@ impl fmt::Display for Toolchain {
             write!(f, "+rustflags={}", flag)?;
         }
 
+        for (name, value) in &self.env {
+            write!(f, "+env:{}={}", name, value)?;
+        }
+
+        for (key, value) in &self.cargo_config {
+            write!(f, "+config:{}={}", key, value)?;
+        }
+
         for patch in self.patches.iter() {
             write!(f, "+patch={}", patch)?;
         }
@@ -99,12 +148,14 @@ impl FromStr for Toolchain {
                 return Err(ToolchainParseError::EmptyName);
             }
 
+            // The alt builds are built by CI for every commit alongside the normal ones, with the
+            // debug assertions and the LLVM assertions enabled
             match source_name {
-                "try" => {
+                "try" | "try-alt" => {
                     ci_try = true;
-                    RustwideToolchain::ci(sha, false)
+                    RustwideToolchain::ci(sha, source_name == "try-alt")
                 }
-                "master" => RustwideToolchain::ci(sha, false),
+                "master" | "master-alt" => RustwideToolchain::ci(sha, source_name == "master-alt"),
                 name => return Err(ToolchainParseError::InvalidSourceName(name.to_string())),
             }
         } else if raw_source.is_empty() {
@@ -114,6 +165,8 @@ impl FromStr for Toolchain {
         };
 
         let mut rustflags = None;
+        let mut env = BTreeMap::new();
+        let mut cargo_config = BTreeMap::new();
         let mut patches: Vec<CratePatch> = vec![];
         for part in parts {
             if let Some(equal_idx) = part.find('=') {
@@ -127,6 +180,12 @@ impl FromStr for Toolchain {
                 match flag {
                     "rustflags" => rustflags = Some(value),
                     "patch" => patches.push(value.parse()?),
+                    _ if flag.starts_with("env:") && is_valid_env_name(&flag[4..]) => {
+                        env.insert(flag[4..].to_string(), value);
+                    }
+                    _ if flag.starts_with("config:") && is_valid_config_key(&flag[7..]) => {
+                        cargo_config.insert(flag[7..].to_string(), value);
+                    }
                     unknown => return Err(ToolchainParseError::InvalidFlag(unknown.to_string())),
                 }
             } else {
@@ -137,6 +196,8 @@ impl FromStr for Toolchain {
         Ok(Toolchain {
             source,
             rustflags,
+            env,
+            cargo_config,
             ci_try,
             patches,
         })
@@ -178,6 +239,7 @@ impl fmt::Display for CratePatch {
 mod tests {
     use super::{CratePatch, Toolchain};
     use rustwide::Toolchain as RustwideToolchain;
+    use std::collections::BTreeMap;
     use std::str::FromStr;
 
     #[test]
@@ -189,6 +251,8 @@ mod tests {
                     test_from_str!($str => Toolchain {
                         source: $source,
                         rustflags: None,
+                        env: BTreeMap::new(),
+                        cargo_config: BTreeMap::new(),
                         ci_try: $ci_try,
                         patches: Vec::new(),
                     });
@@ -197,6 +261,23 @@ mod tests {
                     test_from_str!(concat!($str, "+rustflags=foo bar") => Toolchain {
                         source: $source,
                         rustflags: Some("foo bar".to_string()),
+                        env: BTreeMap::new(),
+                        cargo_config: BTreeMap::new(),
+                        ci_try: $ci_try,
+                        patches: Vec::new(),
+                    });
+
+                    // Test parsing with environment variables and cargo configuration
+                    test_from_str!(concat!($str, "+env:RUSTC_BOOTSTRAP=1+env:RUSTFLAGS=-Zpolonius+config:profile.dev.debug=0") => Toolchain {
+                        source: $source,
+                        rustflags: None,
+                        env: vec![
+                            ("RUSTC_BOOTSTRAP".to_string(), "1".to_string()),
+                            ("RUSTFLAGS".to_string(), "-Zpolonius".to_string()),
+                        ].into_iter().collect(),
+                        cargo_config: vec![
+                            ("profile.dev.debug".to_string(), "0".to_string()),
+                        ].into_iter().collect(),
                         ci_try: $ci_try,
                         patches: Vec::new(),
                     });
@@ -205,6 +286,8 @@ mod tests {
                     test_from_str!(concat!($str, "+patch=example=https://git.example.com/some/repo=master") => Toolchain {
                         source: $source,
                         rustflags: None,
+                        env: BTreeMap::new(),
+                        cargo_config: BTreeMap::new(),
                         ci_try: $ci_try,
                         patches: vec![CratePatch {
                             name: "example".to_string(),
@@ -217,6 +300,8 @@ mod tests {
                     test_from_str!(concat!($str, "+rustflags=foo bar+patch=example=https://git.example.com/some/repo=master") => Toolchain {
                         source: $source,
                         rustflags: Some("foo bar".to_string()),
+                        env: BTreeMap::new(),
+                        cargo_config: BTreeMap::new(),
                         ci_try: $ci_try,
                         patches: vec![CratePatch {
                             name: "example".to_string(),
@@ -260,15 +345,45 @@ mod tests {
                 source: RustwideToolchain::ci("0000000000000000000000000000000000000000", false),
                 ci_try: true,
             },
+            "master-alt#0000000000000000000000000000000000000000" => {
+                source: RustwideToolchain::ci("0000000000000000000000000000000000000000", true),
+                ci_try: false,
+            },
+            "try-alt#0000000000000000000000000000000000000000" => {
+                source: RustwideToolchain::ci("0000000000000000000000000000000000000000", true),
+                ci_try: true,
+            },
         };
 
         // Test invalid reprs
         assert!(Toolchain::from_str("").is_err());
         assert!(Toolchain::from_str("master#").is_err());
+        assert!(Toolchain::from_str("master-alt#").is_err());
+        assert!(Toolchain::from_str("beta-alt#0000000000000000000000000000000000000000").is_err());
         assert!(Toolchain::from_str("foo#0000000000000000000000000000000000000000").is_err());
         assert!(Toolchain::from_str("stable+rustflags").is_err());
         assert!(Toolchain::from_str("stable+rustflags=").is_err());
         assert!(Toolchain::from_str("stable+donotusethisflag=ever").is_err());
-        assert!(Toolchain::from_str("stable+patch=").is_err())
+        assert!(Toolchain::from_str("stable+patch=").is_err());
+        assert!(Toolchain::from_str("stable+env:=1").is_err());
+        assert!(Toolchain::from_str("stable+env:1FOO=1").is_err());
+        assert!(Toolchain::from_str("stable+env:FOO").is_err());
+        assert!(Toolchain::from_str("stable+config:debug=0").is_err());
+        assert!(Toolchain::from_str("stable+config:profile..debug=0").is_err());
+    }
+
+    #[test]
+    fn test_cargo_config_env() {
+        let tc = Toolchain::from_str(
+            "stable+config:profile.dev.debug=0+config:net.git-fetch-with-cli=true",
+        )
+        .unwrap();
+        assert_eq!(
+            tc.cargo_config_env(),
+            vec![
+                ("CARGO_NET_GIT_FETCH_WITH_CLI".to_string(), "true"),
+                ("CARGO_PROFILE_DEV_DEBUG".to_string(), "0"),
+            ]
+        );
     }
 }
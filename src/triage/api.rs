@@ -0,0 +1,82 @@
+use crate::agent::api::ResponseExt;
+use crate::crates::Crate;
+use crate::prelude::*;
+use crate::server::api_types::CraterToken;
+use crate::triage::{Decision, TriageItem};
+use crate::utils;
+use http::{header::AUTHORIZATION, Method};
+use serde_json::json;
+
+pub(super) struct TriageApi {
+    url: String,
+    token: String,
+    experiment: String,
+}
+
+impl TriageApi {
+    pub(super) fn new(url: &str, token: &str, experiment: &str) -> Self {
+        TriageApi {
+            url: url.to_string(),
+            token: token.to_string(),
+            experiment: experiment.to_string(),
+        }
+    }
+
+    fn post<T: serde::de::DeserializeOwned>(
+        &self,
+        url: &str,
+        body: serde_json::Value,
+    ) -> Fallible<T> {
+        utils::http::prepare_sync(Method::POST, &format!("{}/triage-api/{}", self.url, url))
+            .header(
+                AUTHORIZATION,
+                (CraterToken {
+                    token: self.token.clone(),
+                })
+                .to_string(),
+            )
+            .json(&body)
+            .send()?
+            .to_api_response()
+    }
+
+    pub(super) fn regressions(&self) -> Fallible<Vec<TriageItem>> {
+        self.post(
+            "regressions",
+            json!({
+                "experiment-name": self.experiment,
+            }),
+        )
+    }
+
+    pub(super) fn excerpt(&self, krate: &Crate) -> Fallible<Vec<String>> {
+        self.post(
+            "excerpt",
+            json!({
+                "experiment-name": self.experiment,
+                "crate": krate,
+            }),
+        )
+    }
+
+    pub(super) fn decide(&self, krate: &Crate, decision: Decision) -> Fallible<bool> {
+        self.post(
+            "decide",
+            json!({
+                "experiment-name": self.experiment,
+                "crate": krate,
+                "decision": decision.to_str(),
+            }),
+        )
+    }
+
+    pub(super) fn note(&self, krate: &Crate, note: &str) -> Fallible<bool> {
+        self.post(
+            "note",
+            json!({
+                "crate": krate,
+                "note": note,
+            }),
+        )
+    }
+}
@@ -0,0 +1,164 @@
+mod api;
+mod ui;
+
+pub use crate::triage::ui::run;
+
+use crate::crates::Crate;
+use crate::db::{Database, QueryUtils};
+use crate::prelude::*;
+use chrono::Utc;
+use std::collections::{HashMap, HashSet};
+
+/// Maximum number of lines shown to the triagers for each regression.
+const MAX_EXCERPT_LINES: usize = 40;
+
+string_enum!(pub enum Decision {
+    Regression => "regression",
+    Spurious => "spurious",
+    Skip => "skip",
+});
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct TriageItem {
+    #[serde(rename = "crate")]
+    pub krate: Crate,
+    pub decision: Option<Decision>,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+pub fn record_decision(
+    db: &Database,
+    ex: &str,
+    krate: &Crate,
+    decision: Decision,
+    triager: &str,
+) -> Fallible<()> {
+    db.execute(
+        "INSERT INTO triage_decisions (experiment, crate, decision, triager, created_at) \
+         VALUES (?1, ?2, ?3, ?4, ?5);",
+        &[&ex, &krate.id(), &decision.to_str(), &triager, &Utc::now()],
+    )?;
+    Ok(())
+}
+
+pub fn load_decisions(db: &Database, ex: &str) -> Fallible<HashMap<Crate, Decision>> {
+    let rows = db.query(
+        "SELECT crate, decision FROM triage_decisions WHERE experiment = ?1;",
+        &[&ex],
+        |row| -> (String, String) { (row.get("crate"), row.get("decision")) },
+    )?;
+
+    rows.into_iter()
+        .map(|(krate, decision)| Ok((krate.parse()?, decision.parse()?)))
+        .collect()
+}
+
+/// Record a note about a crate, shown in the reports of every experiment including any of its
+/// versions. An empty note removes the existing one.
+pub fn set_note(db: &Database, krate: &Crate, note: &str, author: &str) -> Fallible<()> {
+    if note.trim().is_empty() {
+        db.execute(
+            "DELETE FROM crate_notes WHERE crate = ?1;",
+            &[&krate.unversioned_id()],
+        )?;
+    } else {
+        db.execute(
+            "INSERT INTO crate_notes (crate, note, author, updated_at) VALUES (?1, ?2, ?3, ?4);",
+            &[&krate.unversioned_id(), &note.trim(), &author, &Utc::now()],
+        )?;
+    }
+    Ok(())
+}
+
+pub fn load_note(db: &Database, krate: &Crate) -> Fallible<Option<String>> {
+    db.get_row(
+        "SELECT note FROM crate_notes WHERE crate = ?1;",
+        &[&krate.unversioned_id()],
+        |row| row.get("note"),
+    )
+}
+
+/// Extract the lines of the log of the second toolchain that are not present in the log of the
+/// first one, which usually point straight at the regression.
+pub fn diff_excerpt(start: &str, end: &str) -> Vec<String> {
+    let known = start.lines().collect::<HashSet<_>>();
+    end.lines()
+        .filter(|line| !line.trim().is_empty() && !known.contains(line))
+        .take(MAX_EXCERPT_LINES)
+        .map(|line| line.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff_excerpt, load_decisions, load_note, record_decision, set_note, Decision};
+    use crate::actions::{Action, ActionsCtx, CreateExperiment};
+    use crate::config::Config;
+    use crate::crates::{Crate, RegistryCrate};
+    use crate::db::Database;
+
+    #[test]
+    fn test_decisions() {
+        let db = Database::temp().unwrap();
+        let config = Config::default();
+        let ctx = ActionsCtx::new(&db, &config);
+        crate::crates::lists::setup_test_lists(&db, &config).unwrap();
+        CreateExperiment::dummy("foo").apply(&ctx).unwrap();
+
+        let krate = Crate::Local("build-fail".into());
+        assert!(load_decisions(&db, "foo").unwrap().is_empty());
+
+        record_decision(&db, "foo", &krate, Decision::Spurious, "alice").unwrap();
+        assert_eq!(
+            load_decisions(&db, "foo").unwrap().get(&krate),
+            Some(&Decision::Spurious)
+        );
+
+        // Triaging a crate again overrides the previous decision
+        record_decision(&db, "foo", &krate, Decision::Regression, "bob").unwrap();
+        let decisions = load_decisions(&db, "foo").unwrap();
+        assert_eq!(decisions.len(), 1);
+        assert_eq!(decisions.get(&krate), Some(&Decision::Regression));
+    }
+
+    #[test]
+    fn test_notes() {
+        let db = Database::temp().unwrap();
+        let reg = |version: &str| {
+            Crate::Registry(RegistryCrate {
+                name: "syn".into(),
+                version: version.into(),
+            })
+        };
+        assert_eq!(load_note(&db, &reg("1.0.0")).unwrap(), None);
+
+        // Notes are shared between all the versions of a crate
+        set_note(&db, &reg("1.0.0"), "tests require postgres", "alice").unwrap();
+        assert_eq!(
+            load_note(&db, &reg("1.0.1")).unwrap(),
+            Some("tests require postgres".to_string())
+        );
+
+        set_note(&db, &reg("1.0.1"), "maintainer aware, fix in git", "bob").unwrap();
+        assert_eq!(
+            load_note(&db, &reg("1.0.0")).unwrap(),
+            Some("maintainer aware, fix in git".to_string())
+        );
+
+        set_note(&db, &reg("1.0.0"), "", "bob").unwrap();
+        assert_eq!(load_note(&db, &reg("1.0.0")).unwrap(), None);
+    }
+
+    #[test]
+    fn test_diff_excerpt() {
+        let start = "[INFO] fetching\n[INFO] Compiling foo\n[INFO] Finished\n";
+        let end = "[INFO] fetching\n[INFO] Compiling foo\n\n\
+                   [INFO] error[E0425]: cannot find value `x`\n[INFO] Finished\n";
+        assert_eq!(
+            diff_excerpt(start, end),
+            vec!["[INFO] error[E0425]: cannot find value `x`".to_string()]
+        );
+    }
+}
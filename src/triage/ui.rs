@@ -0,0 +1,169 @@
+use crate::prelude::*;
+use crate::triage::api::TriageApi;
+use crate::triage::Decision;
+use std::io::{self, BufRead, Read, Write};
+use std::process::{Command, Stdio};
+
+/// Switches the terminal to non-canonical mode while alive, so keys can be read without waiting
+/// for a newline. The previous mode is restored when dropped.
+struct SingleKeyMode;
+
+impl SingleKeyMode {
+    fn enable() -> Option<Self> {
+        if cfg!(unix) && stty(&["-icanon", "min", "1"]) {
+            Some(SingleKeyMode)
+        } else {
+            None
+        }
+    }
+}
+
+impl Drop for SingleKeyMode {
+    fn drop(&mut self) {
+        stty(&["icanon"]);
+    }
+}
+
+fn stty(args: &[&str]) -> bool {
+    Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+enum Key {
+    Decide(Decision),
+    Note,
+    Quit,
+    Other,
+}
+
+fn read_key(single_key: bool) -> Fallible<Key> {
+    let stdin = io::stdin();
+    let mut stdin = stdin.lock();
+
+    let key = if single_key {
+        let mut buf = [0; 1];
+        if stdin.read(&mut buf)? == 0 {
+            return Ok(Key::Quit);
+        }
+        buf[0] as char
+    } else {
+        let mut line = String::new();
+        if stdin.read_line(&mut line)? == 0 {
+            return Ok(Key::Quit);
+        }
+        line.trim().chars().next().unwrap_or(' ')
+    };
+
+    Ok(match key.to_ascii_lowercase() {
+        'r' => Key::Decide(Decision::Regression),
+        's' => Key::Decide(Decision::Spurious),
+        'k' => Key::Decide(Decision::Skip),
+        'n' => Key::Note,
+        'q' => Key::Quit,
+        _ => Key::Other,
+    })
+}
+
+/// Ask for a note about the current crate, shared with every future report including it. The
+/// terminal is temporarily switched back to line mode so the note can be edited.
+fn read_note(stdout: &mut impl Write, single_key: bool) -> Fallible<String> {
+    if single_key {
+        stty(&["icanon"]);
+    }
+    write!(stdout, "\nnote (empty to remove): ")?;
+    stdout.flush()?;
+
+    let stdin = io::stdin();
+    let mut line = String::new();
+    let res = stdin.lock().read_line(&mut line);
+    if single_key {
+        stty(&["-icanon", "min", "1"]);
+    }
+    res?;
+
+    Ok(line.trim().to_string())
+}
+
+/// Step through the regressions of an experiment, asking the triager to classify each one and
+/// sending the decisions to the server as soon as they're made. Regressions already classified as
+/// regression or spurious are not shown again, unless `all` is set.
+pub fn run(url: &str, token: &str, experiment: &str, all: bool) -> Fallible<()> {
+    let api = TriageApi::new(url, token, experiment);
+    let items = api
+        .regressions()?
+        .into_iter()
+        .filter(|item| match item.decision {
+            Some(Decision::Regression) | Some(Decision::Spurious) => all,
+            Some(Decision::Skip) | None => true,
+        })
+        .collect::<Vec<_>>();
+
+    if items.is_empty() {
+        println!("nothing left to triage in {}", experiment);
+        return Ok(());
+    }
+
+    let single_key = SingleKeyMode::enable();
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+
+    for (i, item) in items.iter().enumerate() {
+        writeln!(stdout)?;
+        writeln!(stdout, "[{}/{}] {}", i + 1, items.len(), item.krate)?;
+        if let Some(decision) = item.decision {
+            writeln!(stdout, "previously classified as {}", decision)?;
+        }
+        if let Some(note) = &item.note {
+            writeln!(stdout, "note: {}", note)?;
+        }
+        writeln!(stdout)?;
+        let excerpt = api.excerpt(&item.krate)?;
+        if excerpt.is_empty() {
+            writeln!(stdout, "    (no new lines in the log)")?;
+        }
+        for line in &excerpt {
+            writeln!(stdout, "    {}", line)?;
+        }
+
+        loop {
+            write!(
+                stdout,
+                "\n[r] regression  [s] spurious  [k] skip  [n] note  [q] quit > "
+            )?;
+            stdout.flush()?;
+
+            match read_key(single_key.is_some())? {
+                Key::Decide(decision) => {
+                    api.decide(&item.krate, decision)?;
+                    writeln!(stdout, "\nmarked {} as {}", item.krate, decision)?;
+                    break;
+                }
+                Key::Note => {
+                    let note = read_note(&mut stdout, single_key.is_some())?;
+                    api.note(&item.krate, &note)?;
+                    if note.trim().is_empty() {
+                        writeln!(stdout, "removed the note of {}", item.krate)?;
+                    } else {
+                        writeln!(stdout, "saved the note of {}", item.krate)?;
+                    }
+                }
+                Key::Quit => {
+                    writeln!(stdout)?;
+                    return Ok(());
+                }
+                Key::Other => continue,
+            }
+        }
+    }
+
+    writeln!(
+        stdout,
+        "\nreached the end of the regressions of {}",
+        experiment
+    )?;
+    Ok(())
+}
@@ -0,0 +1,190 @@
+use crate::prelude::*;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::process::ExitStatus;
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Exit code of a container killed with SIGKILL, which is what the kernel sends on OOM.
+const SIGKILL_EXIT_CODE: i32 = 128 + 9;
+
+/// Parent cgroups of the containers created by Docker, with its systemd and cgroupfs drivers.
+const CONTAINERS_CGROUPS: &[&str] = &["system.slice", "docker"];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum CgroupVersion {
+    V1,
+    /// The unified hierarchy, along with the controllers available in its root.
+    V2 {
+        memory: bool,
+        cpu: bool,
+    },
+}
+
+lazy_static! {
+    static ref HOST_CGROUP: Option<CgroupVersion> = detect(Path::new(CGROUP_ROOT));
+}
+
+fn detect(root: &Path) -> Option<CgroupVersion> {
+    // Only the root of the unified hierarchy contains `cgroup.controllers`
+    match fs::read_to_string(root.join("cgroup.controllers")) {
+        Ok(content) => {
+            let controllers = content.split_whitespace().collect::<HashSet<_>>();
+            Some(CgroupVersion::V2 {
+                memory: controllers.contains("memory"),
+                cpu: controllers.contains("cpu"),
+            })
+        }
+        Err(_) if root.join("memory").is_dir() => Some(CgroupVersion::V1),
+        Err(_) => None,
+    }
+}
+
+/// Warn if the resource limits of the sandbox can't be enforced on this host.
+pub(crate) fn check_resource_limits() {
+    match *HOST_CGROUP {
+        Some(CgroupVersion::V1) => info!("cgroup v1 detected"),
+        Some(CgroupVersion::V2 { memory, cpu }) => {
+            info!("cgroup v2 (unified hierarchy) detected");
+            if !memory {
+                warn!("the cgroup v2 memory controller is not enabled, memory limits won't work");
+            }
+            if !cpu {
+                warn!("the cgroup v2 cpu controller is not enabled, cpu limits won't work");
+            }
+        }
+        None => {}
+    }
+}
+
+/// Number of OOM kills recorded in the `memory.events` of the cgroups containing the containers,
+/// or `None` if it's not available. The events of a cgroup include the ones of its descendants,
+/// so the kills are still counted there after the cgroup of the container is removed.
+pub(crate) fn oom_kills() -> Option<u64> {
+    oom_kills_in(Path::new(CGROUP_ROOT), *HOST_CGROUP)
+}
+
+fn oom_kills_in(root: &Path, cgroup: Option<CgroupVersion>) -> Option<u64> {
+    if let Some(CgroupVersion::V2 { memory: true, .. }) = cgroup {
+        let mut total = None;
+        for parent in CONTAINERS_CGROUPS {
+            if let Ok(events) = fs::read_to_string(root.join(parent).join("memory.events")) {
+                *total.get_or_insert(0) += parse_oom_kills(&events)?;
+            }
+        }
+        total
+    } else {
+        None
+    }
+}
+
+fn parse_oom_kills(events: &str) -> Option<u64> {
+    events.lines().find_map(|line| {
+        let mut parts = line.split_whitespace();
+        if parts.next() == Some("oom_kill") {
+            parts.next()?.parse().ok()
+        } else {
+            None
+        }
+    })
+}
+
+/// With cgroup v2 some versions of Docker don't report OOM kills in the container state, as the
+/// kill is only recorded in the `memory.events` of the (already removed) container cgroup. A
+/// container killed with SIGKILL while limited by the memory controller is out of memory if the
+/// OOM kills counted by `oom_kills` increased since `before` was taken, or is assumed to be if
+/// they can't be counted on this host.
+pub(crate) fn is_oom_kill(status: ExitStatus, before: Option<u64>) -> bool {
+    is_oom_kill_on(*HOST_CGROUP, status.code(), before, oom_kills())
+}
+
+fn is_oom_kill_on(
+    cgroup: Option<CgroupVersion>,
+    code: Option<i32>,
+    before: Option<u64>,
+    after: Option<u64>,
+) -> bool {
+    match cgroup {
+        Some(CgroupVersion::V2 { memory: true, .. }) if code == Some(SIGKILL_EXIT_CODE) => {
+            match (before, after) {
+                (Some(before), Some(after)) => after > before,
+                _ => true,
+            }
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{detect, is_oom_kill_on, oom_kills_in, CgroupVersion};
+    use std::fs;
+
+    #[test]
+    fn test_detect() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(detect(dir.path()), None);
+
+        fs::create_dir(dir.path().join("memory")).unwrap();
+        assert_eq!(detect(dir.path()), Some(CgroupVersion::V1));
+
+        fs::write(
+            dir.path().join("cgroup.controllers"),
+            "cpuset cpu io pids\n",
+        )
+        .unwrap();
+        assert_eq!(
+            detect(dir.path()),
+            Some(CgroupVersion::V2 {
+                memory: false,
+                cpu: true
+            })
+        );
+    }
+
+    #[test]
+    fn test_oom_detection() {
+        let v2 = Some(CgroupVersion::V2 {
+            memory: true,
+            cpu: true,
+        });
+        assert!(is_oom_kill_on(v2, Some(137), Some(1), Some(2)));
+        assert!(!is_oom_kill_on(v2, Some(137), Some(2), Some(2)));
+        assert!(!is_oom_kill_on(v2, Some(101), Some(1), Some(2)));
+        // Without the OOM kills counters, SIGKILL is the only hint available
+        assert!(is_oom_kill_on(v2, Some(137), None, None));
+        assert!(!is_oom_kill_on(v2, Some(101), None, None));
+        assert!(!is_oom_kill_on(
+            Some(CgroupVersion::V1),
+            Some(137),
+            None,
+            None
+        ));
+        assert!(!is_oom_kill_on(None, Some(137), None, None));
+    }
+
+    #[test]
+    fn test_oom_kills() {
+        let v2 = Some(CgroupVersion::V2 {
+            memory: true,
+            cpu: true,
+        });
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(oom_kills_in(dir.path(), v2), None);
+
+        let events = "low 0\nhigh 0\nmax 12\noom 3\noom_kill 2\noom_group_kill 0\n";
+        fs::create_dir(dir.path().join("system.slice")).unwrap();
+        fs::write(dir.path().join("system.slice/memory.events"), events).unwrap();
+        assert_eq!(oom_kills_in(dir.path(), v2), Some(2));
+        assert_eq!(oom_kills_in(dir.path(), Some(CgroupVersion::V1)), None);
+
+        fs::create_dir(dir.path().join("docker")).unwrap();
+        fs::write(
+            dir.path().join("docker/memory.events"),
+            "oom 1\noom_kill 1\n",
+        )
+        .unwrap();
+        assert_eq!(oom_kills_in(dir.path(), v2), Some(3));
+    }
+}
@@ -0,0 +1,62 @@
+use crate::prelude::*;
+use std::env;
+use std::ffi::OsString;
+
+/// Variables pointing cargo to state shared with other processes. Rustwide sets its own cargo home
+/// for every command it runs (and every build gets its own in the sandbox), but a stray target
+/// directory would still be shared by all the builds running at the same time.
+const SHARED_CARGO_VARS: &[&str] = &["CARGO_HOME", "CARGO_TARGET_DIR", "CARGO_BUILD_TARGET_DIR"];
+
+/// Variables redirecting every git command spawned by the workspace (fetching repositories,
+/// reading their commits) to the same repository. The other ones, like `GIT_SSH_COMMAND` or
+/// `GIT_ASKPASS`, only configure how git works and are needed to reach some repositories.
+const SHARED_GIT_VARS: &[&str] = &[
+    "GIT_DIR",
+    "GIT_WORK_TREE",
+    "GIT_INDEX_FILE",
+    "GIT_OBJECT_DIRECTORY",
+    "GIT_ALTERNATE_OBJECT_DIRECTORIES",
+    "GIT_COMMON_DIR",
+    "GIT_NAMESPACE",
+];
+
+fn is_shared_state_var(name: &str) -> bool {
+    SHARED_GIT_VARS.contains(&name) || SHARED_CARGO_VARS.contains(&name)
+}
+
+/// Remove from the environment of the current process the variables that would make concurrent
+/// builds share their git or cargo state, as they're inherited by every command spawned on the
+/// host. This must be called before any build is started.
+pub fn isolate_builds() {
+    let vars = env::vars_os()
+        .map(|(name, _)| name)
+        .filter(|name| name.to_str().map(is_shared_state_var).unwrap_or(false))
+        .collect::<Vec<OsString>>();
+
+    for name in vars {
+        warn!(
+            "ignoring the {} environment variable to isolate the builds",
+            name.to_string_lossy()
+        );
+        env::remove_var(&name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_shared_state_var;
+
+    #[test]
+    fn test_is_shared_state_var() {
+        assert!(is_shared_state_var("GIT_DIR"));
+        assert!(is_shared_state_var("GIT_WORK_TREE"));
+        assert!(is_shared_state_var("GIT_INDEX_FILE"));
+        assert!(!is_shared_state_var("GIT_SSH_COMMAND"));
+        assert!(!is_shared_state_var("GIT_ASKPASS"));
+        assert!(is_shared_state_var("CARGO_TARGET_DIR"));
+        assert!(is_shared_state_var("CARGO_HOME"));
+        assert!(!is_shared_state_var("CARGO_INCREMENTAL"));
+        assert!(!is_shared_state_var("GITHUB_TOKEN"));
+        assert!(!is_shared_state_var("RUST_LOG"));
+    }
+}
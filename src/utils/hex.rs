@@ -1,4 +1,5 @@
 use crate::prelude::*;
+use sha1::{Digest, Sha1};
 
 #[derive(Debug, Fail)]
 #[cfg_attr(test, derive(PartialEq, Eq))]
@@ -42,9 +43,18 @@ pub(crate) fn from_hex(input: &str) -> Result<Vec<u8>, HexError> {
     }
 }
 
+pub(crate) fn to_hex(input: &[u8]) -> String {
+    input.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Hex-encoded SHA-1 digest of the input, used to check the integrity of the uploaded data.
+pub(crate) fn sha1_hex(input: &[u8]) -> String {
+    to_hex(Sha1::digest(input).as_slice())
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{from_hex, HexError};
+    use super::{from_hex, sha1_hex, to_hex, HexError};
 
     #[test]
     fn test_from_hex() {
@@ -60,4 +70,16 @@ mod tests {
         // Invalid length
         assert_eq!(from_hex("000").unwrap_err(), HexError::InvalidLength);
     }
+
+    #[test]
+    fn test_to_hex() {
+        assert_eq!(to_hex(&[0x00, 0x01, 0x02, 0x10, 0xFF]), "00010210ff");
+        assert_eq!(from_hex(&to_hex(b"crater")).unwrap(), b"crater");
+    }
+
+    #[test]
+    fn test_sha1_hex() {
+        assert_eq!(sha1_hex(b""), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(sha1_hex(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    }
 }
@@ -5,15 +5,19 @@ use std::any::Any;
 use std::fmt;
 use std::str::FromStr;
 
+pub(crate) mod cgroup;
 pub(crate) mod hex;
 pub(crate) mod http;
 #[macro_use]
 mod macros;
 pub(crate) mod disk_usage;
+pub mod env;
 pub(crate) mod path;
 pub(crate) mod serialize;
 pub mod size;
 pub(crate) mod string;
+pub mod timeout;
+pub mod workspace;
 
 /// The set of characters which cannot be used in a [filename on Windows][windows].
 ///
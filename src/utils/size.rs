@@ -12,6 +12,17 @@ pub enum Size {
 }
 
 impl Size {
+    /// Multiply the size by a factor, keeping its unit.
+    pub(crate) fn scaled(&self, factor: usize) -> Size {
+        match self {
+            Size::Bytes(b) => Size::Bytes(b * factor),
+            Size::Kilobytes(kb) => Size::Kilobytes(kb * factor),
+            Size::Megabytes(mb) => Size::Megabytes(mb * factor),
+            Size::Gigabytes(gb) => Size::Gigabytes(gb * factor),
+            Size::Terabytes(tb) => Size::Terabytes(tb * factor),
+        }
+    }
+
     pub(crate) fn to_bytes(&self) -> usize {
         match self {
             Size::Bytes(b) => *b,
@@ -95,6 +106,7 @@ mod tests {
         assert_eq!("1234Gb".parse::<Size>().unwrap(), Size::Gigabytes(1234));
         assert_eq!(Size::Gigabytes(1234).to_string(), "1234G");
         assert_eq!(Size::Gigabytes(42).to_bytes(), 42 * 1024 * 1024 * 1024);
+        assert_eq!(Size::Gigabytes(2).scaled(2), Size::Gigabytes(4));
 
         assert_eq!("1234T".parse::<Size>().unwrap(), Size::Terabytes(1234));
         assert_eq!("1234t".parse::<Size>().unwrap(), Size::Terabytes(1234));
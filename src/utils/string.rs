@@ -41,9 +41,39 @@ pub(crate) fn split_quoted(input: &str) -> Result<Vec<String>, SplitQuotedError>
     }
 }
 
+/// Number of single-character insertions, deletions or substitutions needed to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == *cb { 0 } else { 1 };
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Find the candidate closest to a misspelled input, if any of them is close enough to be what
+/// the user meant.
+pub(crate) fn closest<'a, I>(input: &str, candidates: I) -> Option<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let max_distance = (input.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(input, candidate), candidate))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::split_quoted;
+    use super::{closest, edit_distance, split_quoted};
 
     #[test]
     fn test_split_quoted() {
@@ -69,4 +99,26 @@ mod tests {
         // Unbalanced quotes
         assert!(split_quoted("a b \" c").is_err());
     }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("run", "run"), 0);
+        assert_eq!(edit_distance("rn", "run"), 1);
+        assert_eq!(edit_distance("strat", "start"), 2);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_closest() {
+        let candidates = ["run", "check", "retry", "retry-report"];
+        assert_eq!(closest("rnu", candidates.iter().cloned()), None);
+        assert_eq!(closest("rn", candidates.iter().cloned()), Some("run"));
+        assert_eq!(closest("chek", candidates.iter().cloned()), Some("check"));
+        assert_eq!(
+            closest("retry-reprot", candidates.iter().cloned()),
+            Some("retry-report")
+        );
+        assert_eq!(closest("foo", candidates.iter().cloned()), None);
+    }
 }
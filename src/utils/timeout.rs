@@ -0,0 +1,86 @@
+use crate::prelude::*;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Timeout written in the configuration as a number followed by its unit, like `90s`, `40m` or
+/// `2h`. Plain numbers are seconds.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Timeout {
+    Seconds(u64),
+    Minutes(u64),
+    Hours(u64),
+}
+
+impl Timeout {
+    pub(crate) fn to_duration(&self) -> Duration {
+        match self {
+            Timeout::Seconds(s) => Duration::from_secs(*s),
+            Timeout::Minutes(m) => Duration::from_secs(m * 60),
+            Timeout::Hours(h) => Duration::from_secs(h * 60 * 60),
+        }
+    }
+}
+
+impl fmt::Display for Timeout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Timeout::Seconds(count) => write!(f, "{}s", count),
+            Timeout::Minutes(count) => write!(f, "{}m", count),
+            Timeout::Hours(count) => write!(f, "{}h", count),
+        }
+    }
+}
+
+impl FromStr for Timeout {
+    type Err = failure::Error;
+
+    fn from_str(input: &str) -> Fallible<Timeout> {
+        let last = input
+            .chars()
+            .last()
+            .ok_or_else(|| err_msg("empty timeout"))?;
+        let count = &input[..input.len() - last.len_utf8()];
+
+        match last {
+            's' => Ok(Timeout::Seconds(count.parse()?)),
+            'm' => Ok(Timeout::Minutes(count.parse()?)),
+            'h' => Ok(Timeout::Hours(count.parse()?)),
+            _ => Ok(Timeout::Seconds(input.parse()?)),
+        }
+    }
+}
+
+impl_serde_from_parse!(Timeout, expecting = "a timeout");
+
+#[cfg(test)]
+mod tests {
+    use super::Timeout;
+    use std::time::Duration;
+
+    #[test]
+    fn test_timeout() {
+        assert_eq!("90".parse::<Timeout>().unwrap(), Timeout::Seconds(90));
+        assert_eq!("90s".parse::<Timeout>().unwrap(), Timeout::Seconds(90));
+        assert_eq!(Timeout::Seconds(90).to_string(), "90s");
+        assert_eq!(Timeout::Seconds(90).to_duration(), Duration::from_secs(90));
+
+        assert_eq!("40m".parse::<Timeout>().unwrap(), Timeout::Minutes(40));
+        assert_eq!(Timeout::Minutes(40).to_string(), "40m");
+        assert_eq!(
+            Timeout::Minutes(40).to_duration(),
+            Duration::from_secs(40 * 60)
+        );
+
+        assert_eq!("2h".parse::<Timeout>().unwrap(), Timeout::Hours(2));
+        assert_eq!(Timeout::Hours(2).to_string(), "2h");
+        assert_eq!(
+            Timeout::Hours(2).to_duration(),
+            Duration::from_secs(2 * 60 * 60)
+        );
+
+        assert!("".parse::<Timeout>().is_err());
+        assert!("m".parse::<Timeout>().is_err());
+        assert!("40d".parse::<Timeout>().is_err());
+    }
+}
@@ -0,0 +1,627 @@
+use crate::prelude::*;
+use rustwide::cmd::SandboxImage;
+use rustwide::WorkspaceBuilder;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+use walkdir::WalkDir;
+
+#[cfg(windows)]
+const DEFAULT_SANDBOX_IMAGE: &str = "rustops/crates-build-env-windows";
+#[cfg(not(windows))]
+const DEFAULT_SANDBOX_IMAGE: &str = "rustops/crates-build-env";
+
+/// Environment variable selecting the container runtime the sandbox runs with.
+pub const CONTAINER_RUNTIME_ENV: &str = "CRATER_CONTAINER_RUNTIME";
+
+string_enum!(pub enum ContainerRuntime {
+    Docker => "docker",
+    Podman => "podman",
+});
+
+lazy_static! {
+    static ref CONTAINER_RUNTIME: ContainerRuntime = std::env::var(CONTAINER_RUNTIME_ENV)
+        .ok()
+        .and_then(|name| name.parse().ok())
+        .unwrap_or(ContainerRuntime::Docker);
+}
+
+impl ContainerRuntime {
+    /// Runtime selected through the `CRATER_CONTAINER_RUNTIME` environment variable.
+    pub fn current() -> ContainerRuntime {
+        *CONTAINER_RUNTIME
+    }
+}
+
+fn container_cli() -> Command {
+    Command::new(ContainerRuntime::current().to_str())
+}
+
+fn run_failed() -> String {
+    format!("failed to run {}", ContainerRuntime::current())
+}
+
+/// Check the container runtime selected through the environment, and make rustwide use it. This
+/// must be called before any build is started, as it changes the environment of the process.
+///
+/// Rustwide always invokes the `docker` CLI, so with Podman a directory containing a `docker`
+/// link to `podman` is put at the start of the `PATH`. Podman supports the flags used by rustwide
+/// to create the sandbox, but the memory limit only works with rootless containers on cgroups v2
+/// hosts, and the mounts of hosts enforcing SELinux need to be labeled beforehand.
+pub fn select_container_runtime(work_dir: &Path) -> Fallible<()> {
+    let runtime = match std::env::var(CONTAINER_RUNTIME_ENV) {
+        Ok(name) => name.parse::<ContainerRuntime>()?,
+        Err(_) => return Ok(()),
+    };
+    if runtime == ContainerRuntime::Docker {
+        return Ok(());
+    }
+    if !cfg!(unix) {
+        bail!(
+            "the {} container runtime is only supported on unix",
+            runtime
+        );
+    }
+
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    let podman = std::env::split_paths(&path)
+        .map(|dir| dir.join(runtime.to_str()))
+        .find(|candidate| candidate.is_file())
+        .ok_or_else(|| err_msg(format!("{} is not installed", runtime)))?;
+
+    let bin = work_dir.join("container-runtime");
+    let docker = bin.join("docker");
+    fs::create_dir_all(&bin)?;
+    if fs::symlink_metadata(&docker).is_ok() {
+        fs::remove_file(&docker)?;
+    }
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(&podman, &docker)
+        .with_context(|_| format!("failed to create {}", docker.display()))?;
+
+    let mut paths = vec![bin];
+    paths.extend(std::env::split_paths(&path));
+    std::env::set_var("PATH", std::env::join_paths(paths)?);
+    info!("running the sandbox with {}", podman.display());
+    Ok(())
+}
+
+/// Name of `image` to pull it with the selected container runtime. Podman doesn't pull the
+/// images without a registry in their name (as the ones of Docker Hub usually are) unless it's
+/// configured to search for them, so the registry of Docker is added there.
+pub fn qualified_image(image: &str) -> String {
+    qualify_image(ContainerRuntime::current(), image)
+}
+
+fn qualify_image(runtime: ContainerRuntime, image: &str) -> String {
+    let registry = image.split('/').next().unwrap_or("");
+    let has_registry = image.contains('/')
+        && (registry.contains('.') || registry.contains(':') || registry == "localhost");
+    if runtime == ContainerRuntime::Podman && !has_registry {
+        if image.contains('/') {
+            format!("docker.io/{}", image)
+        } else {
+            format!("docker.io/library/{}", image)
+        }
+    } else {
+        image.to_string()
+    }
+}
+
+/// Sandbox image used by rustwide when no other one is selected.
+pub fn default_sandbox_image() -> String {
+    qualified_image(DEFAULT_SANDBOX_IMAGE)
+}
+
+/// Builder of the workspace in the work directory, with the settings shared by every command.
+pub fn builder() -> WorkspaceBuilder {
+    WorkspaceBuilder::new(&crate::dirs::WORK_DIR, &crate::USER_AGENT)
+        .fetch_registry_index_during_builds(false)
+        .command_timeout(Some(Duration::from_secs(15 * 60)))
+        .command_no_output_timeout(Some(Duration::from_secs(5 * 60)))
+        .running_inside_docker(std::env::var("CRATER_INSIDE_DOCKER").is_ok())
+}
+
+/// Sandbox image named `name`, pulled first if its name includes the repository it's from.
+/// Offline, only the images already available locally can be used.
+pub fn sandbox_image(name: &str, offline: bool) -> Fallible<SandboxImage> {
+    if name.contains('/') && !offline {
+        let name = qualified_image(name);
+        info!("pulling the sandbox image {}", name);
+        Ok(SandboxImage::remote(&name)?)
+    } else {
+        Ok(SandboxImage::local(name)?)
+    }
+}
+
+/// Size on disk of a single toolchain, cache or build directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsageItem {
+    pub name: String,
+    pub size: u64,
+}
+
+/// Space used by a group of directories of the workspace, along with the size of each one.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UsageCategory {
+    pub total: u64,
+    pub items: Vec<UsageItem>,
+}
+
+impl UsageCategory {
+    fn load(dir: &Path) -> Fallible<Self> {
+        let mut category = UsageCategory::default();
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(category),
+            Err(err) => return Err(err.into()),
+        };
+
+        for entry in entries {
+            let entry = entry?;
+            let size = dir_size(&entry.path())?;
+            category.total += size;
+            category.items.push(UsageItem {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                size,
+            });
+        }
+
+        // Biggest items first, as they're the most interesting ones to evict
+        category
+            .items
+            .sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+        Ok(category)
+    }
+}
+
+/// Breakdown of the disk space used by the workspace.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorkspaceUsage {
+    pub toolchains: UsageCategory,
+    pub registry_cache: UsageCategory,
+    pub git_cache: UsageCategory,
+    pub build_dirs: UsageCategory,
+}
+
+impl WorkspaceUsage {
+    pub fn total(&self) -> u64 {
+        self.categories().iter().map(|(_, c)| c.total).sum()
+    }
+
+    pub fn categories(&self) -> [(&'static str, &UsageCategory); 4] {
+        [
+            ("toolchains", &self.toolchains),
+            ("registry cache", &self.registry_cache),
+            ("git cache", &self.git_cache),
+            ("build directories", &self.build_dirs),
+        ]
+    }
+}
+
+/// Calculate how much disk space is used by each part of the workspace. Symlinks are not
+/// followed, so nothing is counted twice.
+pub fn disk_usage(work_dir: &Path) -> Fallible<WorkspaceUsage> {
+    let cargo_home = work_dir.join("cargo-home");
+    Ok(WorkspaceUsage {
+        toolchains: UsageCategory::load(&work_dir.join("rustup-home").join("toolchains"))?,
+        registry_cache: UsageCategory::load(&cargo_home.join("registry"))?,
+        git_cache: UsageCategory::load(&cargo_home.join("git"))?,
+        build_dirs: UsageCategory::load(&work_dir.join("builds"))?,
+    })
+}
+
+pub(crate) fn dir_size(path: &Path) -> Fallible<u64> {
+    let mut size = 0;
+    for entry in WalkDir::new(path) {
+        let entry = entry?;
+        if !entry.file_type().is_dir() {
+            size += entry.metadata()?.len();
+        }
+    }
+    Ok(size)
+}
+
+/// Check the workspace directory already contains everything needed to run experiments, so it
+/// can be initialized without network access (for example when it's baked into an image).
+pub fn check_offline(work_dir: &Path) -> Fallible<()> {
+    let cargo = work_dir
+        .join("cargo-home")
+        .join("bin")
+        .join(format!("cargo{}", std::env::consts::EXE_SUFFIX));
+    if !cargo.is_file() {
+        bail!(
+            "can't initialize the workspace offline: {} is missing",
+            cargo.display()
+        );
+    }
+
+    let toolchains = work_dir.join("rustup-home").join("toolchains");
+    let installed = match fs::read_dir(&toolchains) {
+        Ok(entries) => entries.count(),
+        Err(_) => 0,
+    };
+    if installed == 0 {
+        bail!(
+            "can't initialize the workspace offline: no toolchain installed in {}",
+            toolchains.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Digest of the sandbox image the builds run in (or of the default one), which identifies the
+/// environment the crates are built in. `None` is returned if docker can't tell it, as when the
+/// image isn't pulled yet.
+pub fn sandbox_image_digest(image: Option<&str>) -> Option<String> {
+    let default = default_sandbox_image();
+    let image = image.unwrap_or(&default);
+    let output = container_cli()
+        .args(&["image", "inspect", "--format", "{{.Id}}", image])
+        .output();
+    match output {
+        Ok(output) if output.status.success() => {
+            let digest = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            if digest.is_empty() {
+                None
+            } else {
+                Some(digest)
+            }
+        }
+        Ok(output) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            warn!(
+                "failed to inspect the sandbox image {}: {}",
+                image,
+                stderr.trim()
+            );
+            None
+        }
+        Err(err) => {
+            warn!("failed to inspect the sandbox image {}: {}", image, err);
+            None
+        }
+    }
+}
+
+/// Kill the running containers with `dir` (or a directory inside it) mounted, returning how many
+/// of them were killed. The containers are created by rustwide, which doesn't expose them, so
+/// they're found through the mounts docker reports for every running container.
+pub(crate) fn kill_containers_using(dir: &Path) -> Fallible<usize> {
+    let dir = dir.canonicalize()?;
+    let output = container_cli()
+        .args(&["ps", "--quiet", "--no-trunc"])
+        .output()
+        .with_context(|_| run_failed())?;
+    if !output.status.success() {
+        bail!("failed to list the running containers ({})", output.status);
+    }
+    let running = String::from_utf8_lossy(&output.stdout);
+    let running = running.split_whitespace().collect::<Vec<_>>();
+    if running.is_empty() {
+        return Ok(0);
+    }
+
+    let output = container_cli()
+        .args(&[
+            "inspect",
+            "--format",
+            "{{.Id}}{{range .Mounts}}\t{{.Source}}{{end}}",
+        ])
+        .args(&running)
+        .output()
+        .with_context(|_| run_failed())?;
+    // Containers exiting in the meantime make the command fail, but the others are still listed
+    let inspected = String::from_utf8_lossy(&output.stdout);
+    let containers = containers_using(&inspected, &dir);
+    if containers.is_empty() {
+        return Ok(0);
+    }
+
+    let status = container_cli()
+        .arg("kill")
+        .args(&containers)
+        .stdout(Stdio::null())
+        .status()
+        .with_context(|_| run_failed())?;
+    if !status.success() {
+        bail!(
+            "failed to kill the containers using {} ({})",
+            dir.display(),
+            status
+        );
+    }
+    Ok(containers.len())
+}
+
+/// IDs of the containers with `dir` mounted, from the output of `docker inspect` listing the ID
+/// of every container followed by the sources of its mounts, separated by tabs.
+fn containers_using<'a>(inspected: &'a str, dir: &Path) -> Vec<&'a str> {
+    inspected
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let id = fields.next()?;
+            if fields.any(|source| Path::new(source).starts_with(dir)) {
+                Some(id)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Dockerfile of an image derived from `base`, installing the system `packages` with apt before
+/// running the instructions of `fragment`.
+fn derived_dockerfile(base: &str, packages: &[String], fragment: &str) -> String {
+    let mut dockerfile = format!("FROM {}\n", base);
+    if !packages.is_empty() {
+        dockerfile.push_str(&format!(
+            "RUN apt-get update && \\\n    \
+             apt-get install -y --no-install-recommends {} && \\\n    \
+             rm -rf /var/lib/apt/lists/*\n",
+            packages.join(" ")
+        ));
+    }
+    dockerfile.push_str(fragment);
+    if !dockerfile.ends_with('\n') {
+        dockerfile.push('\n');
+    }
+    dockerfile
+}
+
+/// Build a sandbox image derived from `base` (or from the default sandbox image), for the builds
+/// needing a system library missing from it, and tag it as `tag`.
+pub fn build_sandbox_image(
+    tag: &str,
+    base: Option<&str>,
+    packages: &[String],
+    fragment: &str,
+) -> Fallible<SandboxImage> {
+    let default = default_sandbox_image();
+    let dockerfile = derived_dockerfile(base.unwrap_or(&default), packages, fragment);
+    debug!("building the sandbox image {} from:\n{}", tag, dockerfile);
+
+    // The Dockerfile is sent through stdin, so no build context is uploaded to the daemon
+    let mut child = container_cli()
+        .args(&["build", "--tag", tag, "-"])
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|_| run_failed())?;
+    child
+        .stdin
+        .take()
+        .expect("missing the stdin of docker")
+        .write_all(dockerfile.as_bytes())?;
+    let status = child.wait()?;
+    if !status.success() {
+        bail!("failed to build the sandbox image {} ({})", tag, status);
+    }
+
+    SandboxImage::local(tag)
+}
+
+/// Instructions restoring the user and the command of the image described by `config` (the
+/// output of `docker image inspect --format '{{json .Config}}'`), which `docker commit` would
+/// otherwise take from the container the setup command ran in.
+fn restore_config(config: &str) -> Fallible<Vec<String>> {
+    let config: serde_json::Value = serde_json::from_str(config)?;
+    let user = match config["User"].as_str() {
+        Some(user) if !user.is_empty() => user,
+        _ => "root",
+    };
+    let cmd = match &config["Cmd"] {
+        serde_json::Value::Null => serde_json::Value::Array(Vec::new()),
+        cmd => cmd.clone(),
+    };
+    Ok(vec![format!("USER {}", user), format!("CMD {}", cmd)])
+}
+
+/// Run `command` as root in a container of the `base` image (or of the default sandbox image),
+/// and tag the resulting image as `tag`. The builds still run as an unprivileged user, and only
+/// the ones using the new image see the changes, so setup steps (like installing a missing
+/// library) can be tried one at a time by passing the previous tag as the base.
+pub fn run_as_root(tag: &str, base: Option<&str>, command: &[String]) -> Fallible<SandboxImage> {
+    let default = default_sandbox_image();
+    let base = base.unwrap_or(&default);
+    if command.is_empty() {
+        bail!("no command to run in the sandbox image");
+    }
+
+    let output = container_cli()
+        .args(&["image", "inspect", "--format", "{{json .Config}}", base])
+        .output()
+        .with_context(|_| run_failed())?;
+    if !output.status.success() {
+        bail!(
+            "failed to inspect the sandbox image {} ({})",
+            base,
+            output.status
+        );
+    }
+    let changes = restore_config(&String::from_utf8_lossy(&output.stdout))?;
+
+    let output = container_cli()
+        .args(&["create", "--user", "root", base])
+        .args(command)
+        .output()
+        .with_context(|_| run_failed())?;
+    if !output.status.success() {
+        bail!("failed to create the setup container ({})", output.status);
+    }
+    let container = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+    let result = (|| -> Fallible<()> {
+        let status = container_cli()
+            .args(&["start", "--attach", container.as_str()])
+            .status()
+            .with_context(|_| run_failed())?;
+        if !status.success() {
+            bail!("the setup command failed ({})", status);
+        }
+
+        let mut commit = container_cli();
+        commit.arg("commit");
+        for change in &changes {
+            commit.args(&["--change", change]);
+        }
+        let status = commit
+            .args(&[container.as_str(), tag])
+            .stdout(Stdio::null())
+            .status()
+            .with_context(|_| run_failed())?;
+        if !status.success() {
+            bail!("failed to commit the sandbox image {} ({})", tag, status);
+        }
+        Ok(())
+    })();
+
+    let status = container_cli()
+        .args(&["rm", container.as_str()])
+        .stdout(Stdio::null())
+        .status();
+    if !status.map(|status| status.success()).unwrap_or(false) {
+        warn!("failed to remove the setup container {}", container);
+    }
+    result?;
+
+    SandboxImage::local(tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_offline, containers_using, derived_dockerfile, disk_usage, qualify_image,
+        restore_config, ContainerRuntime, UsageItem,
+    };
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn test_check_offline() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(check_offline(dir.path()).is_err());
+
+        let bin = dir.path().join("cargo-home").join("bin");
+        fs::create_dir_all(&bin).unwrap();
+        fs::write(
+            bin.join(format!("cargo{}", std::env::consts::EXE_SUFFIX)),
+            "",
+        )
+        .unwrap();
+        assert!(check_offline(dir.path()).is_err());
+
+        let toolchains = dir.path().join("rustup-home").join("toolchains");
+        fs::create_dir_all(toolchains.join("stable-x86_64-unknown-linux-gnu")).unwrap();
+        assert!(check_offline(dir.path()).is_ok());
+    }
+
+    #[test]
+    fn test_containers_using() {
+        let inspected = "aaa\t/work/cargo-home\t/work/builds/foo/target\n\
+                         bbb\t/work/cargo-home\t/work/builds/bar/target\n\
+                         ccc\n";
+        let dir = Path::new("/work/builds/foo");
+        assert_eq!(containers_using(inspected, dir), vec!["aaa"]);
+        assert!(containers_using(inspected, Path::new("/work/builds/baz")).is_empty());
+    }
+
+    #[test]
+    fn test_derived_dockerfile() {
+        assert_eq!(
+            derived_dockerfile("rustops/crates-build-env", &[], "ENV FOO=bar"),
+            "FROM rustops/crates-build-env\nENV FOO=bar\n"
+        );
+        assert_eq!(
+            derived_dockerfile(
+                "rustops/crates-build-env",
+                &["libfoo-dev".into(), "libbar-dev".into()],
+                ""
+            ),
+            "FROM rustops/crates-build-env\n\
+             RUN apt-get update && \\\n    \
+             apt-get install -y --no-install-recommends libfoo-dev libbar-dev && \\\n    \
+             rm -rf /var/lib/apt/lists/*\n"
+        );
+    }
+
+    #[test]
+    fn test_qualify_image() {
+        let docker = ContainerRuntime::Docker;
+        let podman = ContainerRuntime::Podman;
+        assert_eq!(
+            qualify_image(docker, "rustops/crates-build-env"),
+            "rustops/crates-build-env"
+        );
+        assert_eq!(
+            qualify_image(podman, "rustops/crates-build-env"),
+            "docker.io/rustops/crates-build-env"
+        );
+        assert_eq!(qualify_image(podman, "ubuntu"), "docker.io/library/ubuntu");
+        assert_eq!(qualify_image(podman, "ghcr.io/foo/bar"), "ghcr.io/foo/bar");
+        assert_eq!(
+            qualify_image(podman, "localhost:5000/foo"),
+            "localhost:5000/foo"
+        );
+        assert_eq!(qualify_image(podman, "localhost/foo"), "localhost/foo");
+    }
+
+    #[test]
+    fn test_restore_config() {
+        assert_eq!(
+            restore_config(r#"{"User":"","Cmd":null,"Env":["PATH=/usr/bin"]}"#).unwrap(),
+            vec!["USER root".to_string(), "CMD []".to_string()]
+        );
+        assert_eq!(
+            restore_config(r#"{"User":"builder","Cmd":["/bin/sh","-c","true"]}"#).unwrap(),
+            vec![
+                "USER builder".to_string(),
+                r#"CMD ["/bin/sh","-c","true"]"#.to_string()
+            ]
+        );
+        assert!(restore_config("not json").is_err());
+    }
+
+    #[test]
+    fn test_disk_usage() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(disk_usage(dir.path()).unwrap().total(), 0);
+
+        let toolchains = dir.path().join("rustup-home").join("toolchains");
+        fs::create_dir_all(toolchains.join("stable").join("bin")).unwrap();
+        fs::write(
+            toolchains.join("stable").join("bin").join("rustc"),
+            [0; 100],
+        )
+        .unwrap();
+        fs::create_dir_all(toolchains.join("nightly")).unwrap();
+        fs::write(toolchains.join("nightly").join("rustc"), [0; 300]).unwrap();
+
+        let registry = dir.path().join("cargo-home").join("registry").join("cache");
+        fs::create_dir_all(&registry).unwrap();
+        fs::write(registry.join("foo-1.0.0.crate"), [0; 50]).unwrap();
+
+        let usage = disk_usage(dir.path()).unwrap();
+        assert_eq!(usage.toolchains.total, 400);
+        assert_eq!(
+            usage.toolchains.items,
+            vec![
+                UsageItem {
+                    name: "nightly".into(),
+                    size: 300
+                },
+                UsageItem {
+                    name: "stable".into(),
+                    size: 100
+                },
+            ]
+        );
+        assert_eq!(usage.registry_cache.total, 50);
+        assert_eq!(usage.git_cache.total, 0);
+        assert_eq!(usage.build_dirs.total, 0);
+        assert_eq!(usage.total(), 450);
+    }
+}